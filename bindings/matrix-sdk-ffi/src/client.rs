@@ -497,6 +497,15 @@ impl Client {
         self.inner.send_queue().set_enabled(enable).await;
     }
 
+    /// Enables or disables the reporting of the progress of the media uploads
+    /// in the send queue, in the send state of the local echoes of the
+    /// timeline.
+    ///
+    /// It's disabled by default.
+    pub fn enable_send_queue_upload_progress(&self, enable: bool) {
+        self.inner.send_queue().enable_upload_progress(enable);
+    }
+
     /// Subscribe to the global enablement status of the send queue, at the
     /// client-wide level.
     ///
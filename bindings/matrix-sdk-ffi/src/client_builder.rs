@@ -301,6 +301,8 @@ impl ClientBuilder {
                 backup_download_strategy:
                     matrix_sdk::encryption::BackupDownloadStrategy::AfterDecryptionFailure,
                 auto_enable_backups: false,
+                auto_enable_dehydrated_devices: false,
+                share_history_on_invite: false,
             },
             room_key_recipient_strategy: Default::default(),
             decryption_trust_requirement: TrustRequirement::Untrusted,
@@ -473,6 +475,26 @@ impl ClientBuilder {
         Arc::new(builder)
     }
 
+    /// Automatically handle the lifecycle of the dehydrated device of the
+    /// user, using secret storage to store its pickle key.
+    pub fn auto_enable_dehydrated_devices(
+        self: Arc<Self>,
+        auto_enable_dehydrated_devices: bool,
+    ) -> Arc<Self> {
+        let mut builder = unwrap_or_clone_arc(self);
+        builder.encryption_settings.auto_enable_dehydrated_devices = auto_enable_dehydrated_devices;
+        Arc::new(builder)
+    }
+
+    /// Share the history of encrypted rooms with the users we invite, and
+    /// import the history shared with us when we join a room we were invited
+    /// to.
+    pub fn share_history_on_invite(self: Arc<Self>, share_history_on_invite: bool) -> Arc<Self> {
+        let mut builder = unwrap_or_clone_arc(self);
+        builder.encryption_settings.share_history_on_invite = share_history_on_invite;
+        Arc::new(builder)
+    }
+
     /// Set the strategy to be used for picking recipient devices when sending
     /// an encrypted message.
     pub fn room_key_recipient_strategy(self: Arc<Self>, strategy: CollectStrategy) -> Arc<Self> {
@@ -57,6 +57,12 @@ pub struct RoomInfo {
     /// Events causing mentions/highlights for the user, according to their
     /// notification settings.
     num_unread_mentions: u64,
+    /// The part of `num_unread_messages` that comes from threads.
+    num_unread_thread_messages: u64,
+    /// The part of `num_unread_notifications` that comes from threads.
+    num_unread_thread_notifications: u64,
+    /// The part of `num_unread_mentions` that comes from threads.
+    num_unread_thread_mentions: u64,
     /// The currently pinned event ids.
     pinned_event_ids: Vec<String>,
     /// The join rule for this room, if known.
@@ -68,6 +74,7 @@ pub struct RoomInfo {
 impl RoomInfo {
     pub(crate) async fn new(room: &matrix_sdk::Room) -> Result<Self, ClientError> {
         let unread_notification_counts = room.unread_notification_counts();
+        let unread_thread_counts = room.read_receipts().all_threads_counts();
 
         let power_levels_map = room.users_with_power_levels().await;
         let mut user_power_levels = HashMap::<String, i64>::new();
@@ -129,6 +136,9 @@ impl RoomInfo {
             num_unread_messages: room.num_unread_messages(),
             num_unread_notifications: room.num_unread_notifications(),
             num_unread_mentions: room.num_unread_mentions(),
+            num_unread_thread_messages: unread_thread_counts.num_unread,
+            num_unread_thread_notifications: unread_thread_counts.num_notifications,
+            num_unread_thread_mentions: unread_thread_counts.num_mentions,
             pinned_event_ids,
             join_rule: join_rule.ok(),
             history_visibility: room.history_visibility_or_default().try_into()?,
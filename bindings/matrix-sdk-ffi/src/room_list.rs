@@ -12,8 +12,8 @@ use matrix_sdk_ui::{
     room_list_service::filters::{
         new_filter_all, new_filter_any, new_filter_category, new_filter_favourite,
         new_filter_fuzzy_match_room_name, new_filter_invite, new_filter_joined,
-        new_filter_non_left, new_filter_none, new_filter_normalized_match_room_name,
-        new_filter_unread, BoxedFilterFn, RoomCategory,
+        new_filter_non_left, new_filter_non_replaced, new_filter_none,
+        new_filter_normalized_match_room_name, new_filter_unread, BoxedFilterFn, RoomCategory,
     },
     timeline::default_event_filter,
     unable_to_decrypt_hook::UtdHookManager,
@@ -472,6 +472,7 @@ pub enum RoomListEntriesDynamicFilterKind {
     All { filters: Vec<RoomListEntriesDynamicFilterKind> },
     Any { filters: Vec<RoomListEntriesDynamicFilterKind> },
     NonLeft,
+    NonReplaced,
     Joined,
     Unread,
     Favourite,
@@ -509,6 +510,7 @@ impl From<RoomListEntriesDynamicFilterKind> for BoxedFilterFn {
                 filters.into_iter().map(|filter| BoxedFilterFn::from(filter)).collect(),
             )),
             Kind::NonLeft => Box::new(new_filter_non_left()),
+            Kind::NonReplaced => Box::new(new_filter_non_replaced()),
             Kind::Joined => Box::new(new_filter_joined()),
             Kind::Unread => Box::new(new_filter_unread()),
             Kind::Favourite => Box::new(new_filter_favourite()),
@@ -226,6 +226,11 @@ pub struct UnableToDecryptInfo {
     /// Our local user's own homeserver, or `None` if the client is not logged
     /// in.
     pub own_homeserver: Option<String>,
+
+    /// Whether the event could be decrypted late thanks to a room key that was
+    /// downloaded from the key backup, because the event couldn't be
+    /// decrypted.
+    pub key_downloaded_from_backup: bool,
 }
 
 impl From<SdkUnableToDecryptInfo> for UnableToDecryptInfo {
@@ -238,6 +243,7 @@ impl From<SdkUnableToDecryptInfo> for UnableToDecryptInfo {
             user_trusts_own_identity: value.user_trusts_own_identity,
             sender_homeserver: value.sender_homeserver.to_string(),
             own_homeserver: value.own_homeserver.map(String::from),
+            key_downloaded_from_backup: value.key_downloaded_from_backup,
         }
     }
 }
@@ -8,6 +8,7 @@ pub enum TimelineFocus {
     Live,
     Event { event_id: String, num_context_events: u16 },
     PinnedEvents { max_events_to_load: u16, max_concurrent_requests: u16 },
+    Thread { root_event_id: String },
 }
 
 impl TryFrom<TimelineFocus> for matrix_sdk_ui::timeline::TimelineFocus {
@@ -30,6 +31,16 @@ impl TryFrom<TimelineFocus> for matrix_sdk_ui::timeline::TimelineFocus {
             TimelineFocus::PinnedEvents { max_events_to_load, max_concurrent_requests } => {
                 Ok(Self::PinnedEvents { max_events_to_load, max_concurrent_requests })
             }
+            TimelineFocus::Thread { root_event_id } => {
+                let parsed_root_event_id = EventId::parse(&root_event_id).map_err(|err| {
+                    FocusEventError::InvalidEventId {
+                        event_id: root_event_id.clone(),
+                        err: err.to_string(),
+                    }
+                })?;
+
+                Ok(Self::Thread { root_event_id: parsed_root_event_id })
+            }
         }
     }
 }
@@ -62,7 +62,7 @@ use uuid::Uuid;
 
 use self::content::{Reaction, ReactionSenderData, TimelineItemContent};
 use crate::{
-    client::ProgressWatcher,
+    client::{ProgressWatcher, TransmissionProgress},
     error::{ClientError, RoomError},
     event::EventOrTransactionId,
     helpers::unwrap_or_clone_arc,
@@ -951,7 +951,11 @@ impl TimelineItem {
 #[derive(Clone, uniffi::Enum)]
 pub enum EventSendState {
     /// The local event has not been sent yet.
-    NotSentYet,
+    NotSentYet {
+        /// The progress of the upload of the media, if this is a media event
+        /// whose upload has started.
+        progress: Option<MediaUploadProgress>,
+    },
 
     /// The local event has been sent to the server, but unsuccessfully: The
     /// sending has failed.
@@ -976,7 +980,9 @@ impl From<&matrix_sdk_ui::timeline::EventSendState> for EventSendState {
         use matrix_sdk_ui::timeline::EventSendState::*;
 
         match value {
-            NotSentYet => Self::NotSentYet,
+            NotSentYet { progress } => {
+                Self::NotSentYet { progress: progress.map(MediaUploadProgress::from) }
+            }
             SendingFailed { error, is_recoverable } => {
                 let as_queue_wedge_error: matrix_sdk::QueueWedgeError = (&**error).into();
                 Self::SendingFailed {
@@ -989,6 +995,26 @@ impl From<&matrix_sdk_ui::timeline::EventSendState> for EventSendState {
     }
 }
 
+/// The progress of the upload of the media of a local event.
+#[derive(Clone, Copy, uniffi::Record)]
+pub struct MediaUploadProgress {
+    /// The index of the upload among the uploads of the media event.
+    ///
+    /// The thumbnail, if any, is uploaded first at index 0, then the file. For
+    /// a gallery, the uploads of each item follow the ones of the previous
+    /// item.
+    pub index: u64,
+
+    /// The progress of the upload.
+    pub progress: TransmissionProgress,
+}
+
+impl From<matrix_sdk_ui::timeline::MediaUploadProgress> for MediaUploadProgress {
+    fn from(value: matrix_sdk_ui::timeline::MediaUploadProgress) -> Self {
+        Self { index: value.index, progress: value.progress.into() }
+    }
+}
+
 /// Recommended decorations for decrypted messages, representing the message's
 /// authenticity properties.
 #[derive(uniffi::Enum, Clone)]
@@ -1034,6 +1060,7 @@ pub struct EventTimelineItem {
     read_receipts: HashMap<String, Receipt>,
     origin: Option<EventItemOrigin>,
     can_be_replied_to: bool,
+    thread_summary: Option<ThreadSummary>,
     lazy_provider: Arc<LazyTimelineItemProvider>,
 }
 
@@ -1073,6 +1100,7 @@ impl From<matrix_sdk_ui::timeline::EventTimelineItem> for EventTimelineItem {
             read_receipts,
             origin: item.origin(),
             can_be_replied_to: item.can_be_replied_to(),
+            thread_summary: item.thread_summary().map(|summary| summary.clone().into()),
             lazy_provider,
         }
     }
@@ -1096,6 +1124,23 @@ pub struct EventTimelineItemDebugInfo {
     latest_edit_json: Option<String>,
 }
 
+#[derive(Clone, uniffi::Record)]
+pub struct ThreadSummary {
+    num_replies: u32,
+    latest_reply: Option<String>,
+    participants: Vec<String>,
+}
+
+impl From<matrix_sdk::event_cache::ThreadSummary> for ThreadSummary {
+    fn from(summary: matrix_sdk::event_cache::ThreadSummary) -> Self {
+        Self {
+            num_replies: summary.num_replies,
+            latest_reply: summary.latest_reply.map(|event_id| event_id.to_string()),
+            participants: summary.participants.iter().map(ToString::to_string).collect(),
+        }
+    }
+}
+
 #[derive(Clone, uniffi::Enum)]
 pub enum ProfileDetails {
     Unavailable,
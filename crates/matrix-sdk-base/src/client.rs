@@ -1293,12 +1293,93 @@ impl BaseClient {
             }
         }
 
+        // The rooms whose position in the space graph may have changed, because
+        // they have been added to or removed from a space, or changed parents.
+        //
+        // They must all be known before the room infos are updated, since a child can
+        // come before its parent in the changes.
+        let mut space_relations_changed = BTreeSet::new();
+
+        for (room_id, room_info) in &changes.room_infos {
+            if let Some(room) = self.store.room(room_id) {
+                let old_children = room.space_child_ids();
+                let new_children = &room_info.base_info.space_children;
+
+                if old_children != *new_children {
+                    space_relations_changed
+                        .extend(old_children.symmetric_difference(new_children).cloned());
+                }
+
+                if room.space_parent_ids() != room_info.base_info.space_parents {
+                    space_relations_changed.insert(room_id.clone());
+                }
+            }
+        }
+
         for (room_id, room_info) in &changes.room_infos {
             if let Some(room) = self.store.room(room_id) {
-                let room_info_notable_update_reasons =
+                let mut room_info_notable_update_reasons =
                     room_info_notable_updates.get(room_id).copied().unwrap_or_default();
 
-                room.set_room_info(room_info.clone(), room_info_notable_update_reasons)
+                if space_relations_changed.contains(room_id) {
+                    room_info_notable_update_reasons |=
+                        RoomInfoNotableUpdateReasons::SPACE_RELATIONS;
+                }
+
+                room.set_room_info(room_info.clone(), room_info_notable_update_reasons);
+
+                // The observers of a tombstoned room may depend on the membership of the
+                // current user in its successor.
+                if room_info_notable_update_reasons
+                    .contains(RoomInfoNotableUpdateReasons::MEMBERSHIP)
+                {
+                    if let Some(predecessor) = room_info
+                        .predecessor()
+                        .filter(|predecessor| {
+                            !changes.room_infos.contains_key(&predecessor.room_id)
+                        })
+                        .and_then(|predecessor| self.store.room(&predecessor.room_id))
+                    {
+                        predecessor.notify_notable_update(
+                            RoomInfoNotableUpdateReasons::SUCCESSOR_MEMBERSHIP,
+                        );
+                    }
+                }
+            }
+        }
+
+        self.notify_space_relations_changes(changes, space_relations_changed);
+    }
+
+    /// Broadcast a notable update for the given rooms, and all the rooms they
+    /// contain if they are spaces, so that the observers of the space graph
+    /// (like space filters) can re-evaluate them.
+    ///
+    /// The rooms that are part of `changes` already got a notable update, so
+    /// they are skipped.
+    fn notify_space_relations_changes(
+        &self,
+        changes: &StateChanges,
+        mut rooms_to_visit: BTreeSet<OwnedRoomId>,
+    ) {
+        let mut visited = BTreeSet::new();
+
+        while let Some(room_id) = rooms_to_visit.pop_first() {
+            if !visited.insert(room_id.clone()) {
+                // Spaces can form cycles.
+                continue;
+            }
+
+            let Some(room) = self.store.room(&room_id) else {
+                continue;
+            };
+
+            rooms_to_visit.extend(
+                room.space_child_ids().into_iter().filter(|child_id| !visited.contains(child_id)),
+            );
+
+            if !changes.room_infos.contains_key(&room_id) {
+                room.notify_notable_update(RoomInfoNotableUpdateReasons::SPACE_RELATIONS);
             }
         }
     }
@@ -1745,13 +1826,15 @@ fn handle_room_member_event_for_profiles(
 
 #[cfg(test)]
 mod tests {
+    use std::collections::BTreeSet;
+
     use matrix_sdk_test::{
         async_test, event_factory::EventFactory, ruma_response_from_json, InvitedRoomBuilder,
         LeftRoomBuilder, StateTestEvent, StrippedStateTestEvent, SyncResponseBuilder,
     };
     use ruma::{
         api::client as api, event_id, events::room::member::MembershipState, room_id, serde::Raw,
-        user_id,
+        user_id, RoomId,
     };
     use serde_json::{json, value::to_raw_value};
 
@@ -1759,7 +1842,7 @@ mod tests {
     use crate::{
         store::{StateStoreExt, StoreConfig},
         test_utils::logged_in_base_client,
-        RoomDisplayName, RoomState, SessionMeta,
+        RoomDisplayName, RoomInfoNotableUpdateReasons, RoomState, SessionMeta,
     };
 
     #[async_test]
@@ -2139,4 +2222,117 @@ mod tests {
         assert_eq!(member.display_name().unwrap(), "Invited Alice");
         assert_eq!(member.avatar_url().unwrap().to_string(), "mxc://localhost/fewjilfewjil42");
     }
+
+    #[async_test]
+    async fn test_space_relations_changes_are_notified() {
+        let space_id = room_id!("!space:localhost");
+        let subspace_id = room_id!("!subspace:localhost");
+        let room_id = room_id!("!room:localhost");
+
+        let client = logged_in_base_client(None).await;
+
+        let space_child_event = |parent_id: &RoomId, child_id: &RoomId, via: &[&str]| {
+            StateTestEvent::Custom(json!({
+                "content": { "via": via },
+                "event_id": format!("$child_{}", child_id.localpart()),
+                "origin_server_ts": 151800140,
+                "room_id": parent_id,
+                "sender": "@alice:localhost",
+                "state_key": child_id,
+                "type": "m.space.child",
+            }))
+        };
+
+        let mut sync_builder = SyncResponseBuilder::new();
+        let response = sync_builder
+            .add_joined_room(
+                matrix_sdk_test::JoinedRoomBuilder::new(space_id)
+                    .add_state_event(space_child_event(space_id, subspace_id, &["localhost"])),
+            )
+            .add_joined_room(
+                matrix_sdk_test::JoinedRoomBuilder::new(subspace_id)
+                    .add_state_event(space_child_event(subspace_id, room_id, &["localhost"])),
+            )
+            .add_joined_room(matrix_sdk_test::JoinedRoomBuilder::new(room_id))
+            .build_sync_response();
+        client.receive_sync_response(response).await.unwrap();
+
+        let space = client.get_room(space_id).unwrap();
+        assert_eq!(space.space_child_ids().into_iter().collect::<Vec<_>>(), [subspace_id]);
+
+        let mut notable_updates = client.room_info_notable_update_receiver();
+
+        // The subspace is removed from the space, which concerns all the rooms it
+        // contains.
+        let response = sync_builder
+            .add_joined_room(
+                matrix_sdk_test::JoinedRoomBuilder::new(space_id)
+                    .add_state_event(space_child_event(space_id, subspace_id, &[])),
+            )
+            .build_sync_response();
+        client.receive_sync_response(response).await.unwrap();
+
+        assert!(space.space_child_ids().is_empty());
+
+        let mut updated_rooms = BTreeSet::new();
+        while let Ok(update) = notable_updates.try_recv() {
+            if update.reasons.contains(RoomInfoNotableUpdateReasons::SPACE_RELATIONS) {
+                updated_rooms.insert(update.room_id);
+            }
+        }
+        assert_eq!(updated_rooms, BTreeSet::from([subspace_id.to_owned(), room_id.to_owned()]));
+    }
+
+    #[async_test]
+    async fn test_space_relations_changes_are_notified_for_child_processed_first() {
+        // The child room comes before the space in the changes, which are sorted by
+        // room ID.
+        let space_id = room_id!("!z_space:localhost");
+        let room_id = room_id!("!a_room:localhost");
+
+        let client = logged_in_base_client(None).await;
+
+        let space_child_event = |via: &[&str]| {
+            StateTestEvent::Custom(json!({
+                "content": { "via": via },
+                "event_id": "$child",
+                "origin_server_ts": 151800140,
+                "room_id": space_id,
+                "sender": "@alice:localhost",
+                "state_key": room_id,
+                "type": "m.space.child",
+            }))
+        };
+
+        let mut sync_builder = SyncResponseBuilder::new();
+        let response = sync_builder
+            .add_joined_room(
+                matrix_sdk_test::JoinedRoomBuilder::new(space_id)
+                    .add_state_event(space_child_event(&["localhost"])),
+            )
+            .add_joined_room(matrix_sdk_test::JoinedRoomBuilder::new(room_id))
+            .build_sync_response();
+        client.receive_sync_response(response).await.unwrap();
+
+        let mut notable_updates = client.room_info_notable_update_receiver();
+
+        // The room is removed from the space, in the same sync as an update of the
+        // room.
+        let response = sync_builder
+            .add_joined_room(
+                matrix_sdk_test::JoinedRoomBuilder::new(space_id)
+                    .add_state_event(space_child_event(&[])),
+            )
+            .add_joined_room(matrix_sdk_test::JoinedRoomBuilder::new(room_id))
+            .build_sync_response();
+        client.receive_sync_response(response).await.unwrap();
+
+        let mut updated_rooms = BTreeSet::new();
+        while let Ok(update) = notable_updates.try_recv() {
+            if update.reasons.contains(RoomInfoNotableUpdateReasons::SPACE_RELATIONS) {
+                updated_rooms.insert(update.room_id);
+            }
+        }
+        assert_eq!(updated_rooms, BTreeSet::from([room_id.to_owned()]));
+    }
 }
@@ -27,4 +27,8 @@ pub struct Gap {
     /// The token to use in the query, extracted from a previous "from" /
     /// "end" field of a `/messages` response.
     pub prev_token: String,
+
+    /// The token to paginate forwards from the start of the gap, extracted
+    /// from a previous "end" field of a forward `/messages` response, if any.
+    pub next_token: Option<String>,
 }
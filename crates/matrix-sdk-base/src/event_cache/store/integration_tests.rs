@@ -29,7 +29,10 @@ use ruma::{
     push::Action, room_id, uint, RoomId,
 };
 
-use super::{media::IgnoreMediaRetentionPolicy, DynEventCacheStore};
+use super::{
+    media::{IgnoreMediaRetentionPolicy, MediaKind, MediaOrigin},
+    DynEventCacheStore,
+};
 use crate::{
     event_cache::{store::DEFAULT_CHUNK_CAPACITY, Gap},
     media::{MediaFormat, MediaRequestParameters, MediaThumbnailSettings},
@@ -107,6 +110,9 @@ pub trait EventCacheStoreIntegrationTests {
     /// Test replacing a MXID.
     async fn test_replace_media_key(&self);
 
+    /// Test inspecting the usage of the media cache.
+    async fn test_media_cache_usage(&self);
+
     /// Test handling updates to a linked chunk and reloading these updates from
     /// the store.
     async fn test_handle_updates_and_rebuild_linked_chunk(&self);
@@ -130,6 +136,9 @@ pub trait EventCacheStoreIntegrationTests {
 
     /// Test that an event can be found or not.
     async fn test_find_event(&self);
+
+    /// Test that events can be searched with a full-text query.
+    async fn test_search_events(&self);
 }
 
 #[cfg_attr(target_arch = "wasm32", async_trait(?Send))]
@@ -319,6 +328,121 @@ impl EventCacheStoreIntegrationTests for DynEventCacheStore {
         assert_eq!(self.get_media_content(&new_req).await.unwrap().unwrap(), b"hello");
     }
 
+    async fn test_media_cache_usage(&self) {
+        let room_id = room_id!("!room:localhost");
+        let room_origin = MediaOrigin::room(room_id.to_owned()).with_content_type("image/png");
+
+        let room_file_uri = mxc_uri!("mxc://localhost/room-file");
+        let room_file = MediaRequestParameters {
+            source: MediaSource::Plain(room_file_uri.to_owned()),
+            format: MediaFormat::File,
+        };
+        let room_thumbnail = MediaRequestParameters {
+            source: MediaSource::Plain(mxc_uri!("mxc://localhost/room-thumbnail").to_owned()),
+            format: MediaFormat::Thumbnail(MediaThumbnailSettings::new(uint!(100), uint!(100))),
+        };
+        let avatar = MediaRequestParameters {
+            source: MediaSource::Plain(mxc_uri!("mxc://localhost/avatar").to_owned()),
+            format: MediaFormat::File,
+        };
+        let file = MediaRequestParameters {
+            source: MediaSource::Plain(mxc_uri!("mxc://localhost/file").to_owned()),
+            format: MediaFormat::File,
+        };
+
+        // The media cache is empty.
+        assert!(self.media_cache_entries().await.unwrap().is_empty());
+        assert_eq!(self.media_cache_usage().await.unwrap(), Default::default());
+
+        for (request, origin) in [
+            (&room_file, &room_origin),
+            (&room_thumbnail, &room_origin),
+            (&avatar, &MediaOrigin::avatar().with_content_type("image/jpeg")),
+        ] {
+            self.add_media_content_with_origin(
+                request,
+                b"hello".to_vec(),
+                origin,
+                IgnoreMediaRetentionPolicy::No,
+            )
+            .await
+            .expect("adding media failed");
+        }
+        self.add_media_content(&file, b"hello".to_vec(), IgnoreMediaRetentionPolicy::Yes)
+            .await
+            .expect("adding media failed");
+
+        let entries = self.media_cache_entries().await.unwrap();
+        assert_eq!(entries.len(), 4);
+
+        let room_file_entry =
+            entries.iter().find(|entry| entry.uri.as_deref() == Some(room_file_uri)).unwrap();
+        assert_eq!(room_file_entry.room_id.as_deref(), Some(room_id));
+        assert_eq!(room_file_entry.kind, Some(MediaKind::File));
+        assert!(room_file_entry.size >= 5);
+        assert_eq!(room_file_entry.content_type.as_deref(), Some("image/png"));
+        assert!(!room_file_entry.ignore_policy);
+
+        let file_entry =
+            entries.iter().find(|entry| entry.uri.as_deref() == Some(file.uri())).unwrap();
+        assert_eq!(file_entry.room_id, None);
+        assert_eq!(file_entry.kind, Some(MediaKind::File));
+        assert_eq!(file_entry.content_type, None);
+        assert!(file_entry.ignore_policy);
+
+        let usage = self.media_cache_usage().await.unwrap();
+        assert_eq!(usage.total.count, 4);
+        assert_eq!(usage.total.size, entries.iter().map(|entry| entry.size).sum::<u64>());
+        assert!(usage.total.last_access.is_some());
+
+        assert_eq!(usage.rooms.len(), 1);
+        assert_eq!(usage.rooms[room_id].count, 2);
+        assert_eq!(
+            usage.rooms[room_id].size,
+            entries
+                .iter()
+                .filter(|entry| entry.room_id.as_deref() == Some(room_id))
+                .map(|entry| entry.size)
+                .sum::<u64>()
+        );
+        assert!(usage.rooms[room_id].last_access.is_some());
+
+        assert_eq!(usage.kinds.len(), 3);
+        assert_eq!(usage.kinds[&MediaKind::File].count, 2);
+        assert_eq!(usage.kinds[&MediaKind::Thumbnail].count, 1);
+        assert_eq!(usage.kinds[&MediaKind::Avatar].count, 1);
+
+        assert_eq!(usage.content_types.len(), 2);
+        assert_eq!(usage.content_types["image/png"].count, 2);
+        assert_eq!(usage.content_types["image/jpeg"].count, 1);
+
+        // Replacing the key of a media keeps its room.
+        let new_room_file_uri = mxc_uri!("mxc://localhost/new-room-file");
+        let new_room_file = MediaRequestParameters {
+            source: MediaSource::Plain(new_room_file_uri.to_owned()),
+            format: MediaFormat::File,
+        };
+        self.replace_media_key(&room_file, &new_room_file).await.unwrap();
+
+        let entries = self.media_cache_entries().await.unwrap();
+        let new_room_file_entry =
+            entries.iter().find(|entry| entry.uri.as_deref() == Some(new_room_file_uri)).unwrap();
+        assert_eq!(new_room_file_entry.room_id.as_deref(), Some(room_id));
+
+        // Removing the media of the room only removes the media of the room.
+        self.remove_media_content_for_room(room_id).await.unwrap();
+
+        let usage = self.media_cache_usage().await.unwrap();
+        assert_eq!(usage.total.count, 2);
+        assert!(usage.rooms.is_empty());
+        assert_eq!(usage.content_types.len(), 1);
+        assert_eq!(usage.content_types["image/jpeg"].count, 1);
+        assert!(self.get_media_content(&new_room_file).await.unwrap().is_none());
+        assert!(self.get_media_content(&room_thumbnail).await.unwrap().is_none());
+        assert!(self.get_media_content(&avatar).await.unwrap().is_some());
+        assert!(self.get_media_content(&file).await.unwrap().is_some());
+    }
+
     async fn test_handle_updates_and_rebuild_linked_chunk(&self) {
         let room_id = room_id!("!r0:matrix.org");
 
@@ -340,7 +464,7 @@ impl EventCacheStoreIntegrationTests for DynEventCacheStore {
                     previous: Some(CId::new(0)),
                     new: CId::new(1),
                     next: None,
-                    gap: Gap { prev_token: "parmesan".to_owned() },
+                    gap: Gap { prev_token: "parmesan".to_owned(), next_token: None },
                 },
                 // another items chunk
                 Update::NewItemsChunk { previous: Some(CId::new(1)), new: CId::new(2), next: None },
@@ -425,7 +549,7 @@ impl EventCacheStoreIntegrationTests for DynEventCacheStore {
                     previous: Some(CId::new(0)),
                     new: CId::new(1),
                     next: None,
-                    gap: Gap { prev_token: "morbier".to_owned() },
+                    gap: Gap { prev_token: "morbier".to_owned(), next_token: None },
                 },
                 // new chunk for items
                 Update::NewItemsChunk { previous: Some(CId::new(1)), new: CId::new(2), next: None },
@@ -646,7 +770,7 @@ impl EventCacheStoreIntegrationTests for DynEventCacheStore {
                     previous: Some(CId::new(0)),
                     new: CId::new(1),
                     next: None,
-                    gap: Gap { prev_token: "bleu d'auvergne".to_owned() },
+                    gap: Gap { prev_token: "bleu d'auvergne".to_owned(), next_token: None },
                 },
                 // another items chunk
                 Update::NewItemsChunk { previous: Some(CId::new(1)), new: CId::new(2), next: None },
@@ -753,7 +877,7 @@ impl EventCacheStoreIntegrationTests for DynEventCacheStore {
                     previous: Some(CId::new(0)),
                     new: CId::new(1),
                     next: None,
-                    gap: Gap { prev_token: "brillat-savarin".to_owned() },
+                    gap: Gap { prev_token: "brillat-savarin".to_owned(), next_token: None },
                 },
                 Update::NewItemsChunk { previous: Some(CId::new(1)), new: CId::new(2), next: None },
                 Update::PushItems {
@@ -864,6 +988,104 @@ impl EventCacheStoreIntegrationTests for DynEventCacheStore {
             .expect("failed to query for finding an event")
             .is_none());
     }
+
+    async fn test_search_events(&self) {
+        let room_id = room_id!("!r0:matrix.org");
+        let another_room_id = room_id!("!r1:matrix.org");
+        let f = EventFactory::new().room(room_id).sender(*ALICE);
+
+        let event_raclette = f.text_msg("Raclette tonight?").into_event();
+        let event_fondue = f.text_msg("No, fondue tonight!").into_event();
+        let event_lunch = f.text_msg("What about lunch?").into_event();
+        let event_elsewhere = f.text_msg("Tonight, tonight").room(another_room_id).into_event();
+
+        self.handle_linked_chunk_updates(
+            room_id,
+            vec![
+                Update::NewItemsChunk { previous: None, new: CId::new(0), next: None },
+                Update::PushItems {
+                    at: Position::new(CId::new(0), 0),
+                    items: vec![event_raclette.clone(), event_fondue.clone(), event_lunch],
+                },
+            ],
+        )
+        .await
+        .unwrap();
+
+        self.handle_linked_chunk_updates(
+            another_room_id,
+            vec![
+                Update::NewItemsChunk { previous: None, new: CId::new(0), next: None },
+                Update::PushItems {
+                    at: Position::new(CId::new(0), 0),
+                    items: vec![event_elsewhere],
+                },
+            ],
+        )
+        .await
+        .unwrap();
+
+        let event_ids = |events: Vec<TimelineEvent>| {
+            events.into_iter().map(|event| event.event_id().unwrap()).collect::<Vec<_>>()
+        };
+
+        // Results only include events from the requested room, the most recent first.
+        let results = self.search_events(room_id, "TONIGHT", 10).await.unwrap();
+        assert_eq!(
+            event_ids(results),
+            [event_fondue.event_id().unwrap(), event_raclette.event_id().unwrap()]
+        );
+
+        // All the words must match.
+        let results = self.search_events(room_id, "raclette tonight", 10).await.unwrap();
+        assert_eq!(event_ids(results), [event_raclette.event_id().unwrap()]);
+        assert!(self.search_events(room_id, "raclette lunch", 10).await.unwrap().is_empty());
+
+        // Words are matched exactly.
+        assert!(self.search_events(room_id, "raclet", 10).await.unwrap().is_empty());
+
+        // The limit is respected.
+        let results = self.search_events(room_id, "tonight", 1).await.unwrap();
+        assert_eq!(event_ids(results), [event_fondue.event_id().unwrap()]);
+
+        // Removed events aren't found anymore.
+        self.handle_linked_chunk_updates(
+            room_id,
+            vec![Update::RemoveItem { at: Position::new(CId::new(0), 1) }],
+        )
+        .await
+        .unwrap();
+
+        let results = self.search_events(room_id, "tonight", 10).await.unwrap();
+        assert_eq!(event_ids(results), [event_raclette.event_id().unwrap()]);
+
+        // Replaced events are found with their new content.
+        let event_tartiflette = f
+            .text_msg("Tartiflette, then")
+            .event_id(&event_raclette.event_id().unwrap())
+            .into_event();
+        self.handle_linked_chunk_updates(
+            room_id,
+            vec![Update::ReplaceItem {
+                at: Position::new(CId::new(0), 0),
+                item: event_tartiflette,
+            }],
+        )
+        .await
+        .unwrap();
+
+        assert!(self.search_events(room_id, "raclette", 10).await.unwrap().is_empty());
+        let results = self.search_events(room_id, "tartiflette", 10).await.unwrap();
+        assert_eq!(event_ids(results), [event_raclette.event_id().unwrap()]);
+
+        // Clearing the room empties the search results.
+        self.handle_linked_chunk_updates(room_id, vec![Update::Clear]).await.unwrap();
+        assert!(self.search_events(room_id, "tartiflette", 10).await.unwrap().is_empty());
+
+        // Other rooms are unaffected.
+        let results = self.search_events(another_room_id, "tonight", 10).await.unwrap();
+        assert_eq!(results.len(), 1);
+    }
 }
 
 /// Macro building to allow your `EventCacheStore` implementation to run the
@@ -919,6 +1141,13 @@ macro_rules! event_cache_store_integration_tests {
                 event_cache_store.test_replace_media_key().await;
             }
 
+            #[async_test]
+            async fn test_media_cache_usage() {
+                let event_cache_store =
+                    get_event_cache_store().await.unwrap().into_event_cache_store();
+                event_cache_store.test_media_cache_usage().await;
+            }
+
             #[async_test]
             async fn test_handle_updates_and_rebuild_linked_chunk() {
                 let event_cache_store =
@@ -967,6 +1196,13 @@ macro_rules! event_cache_store_integration_tests {
                     get_event_cache_store().await.unwrap().into_event_cache_store();
                 event_cache_store.test_find_event().await;
             }
+
+            #[async_test]
+            async fn test_search_events() {
+                let event_cache_store =
+                    get_event_cache_store().await.unwrap().into_event_cache_store();
+                event_cache_store.test_search_events().await;
+            }
         }
     };
 }
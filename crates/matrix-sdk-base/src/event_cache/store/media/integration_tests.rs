@@ -18,14 +18,16 @@
 use async_trait::async_trait;
 use ruma::{
     events::room::MediaSource,
-    mxc_uri, owned_mxc_uri,
+    mxc_uri, owned_mxc_uri, owned_room_id,
     time::{Duration, SystemTime},
+    uint,
 };
 
 use super::{
-    media_service::IgnoreMediaRetentionPolicy, EventCacheStoreMedia, MediaRetentionPolicy,
+    media_service::IgnoreMediaRetentionPolicy, EventCacheStoreMedia, MediaKind, MediaOrigin,
+    MediaRetentionPolicy, MediaRetentionScope, ScopedMediaRetentionPolicies,
 };
-use crate::media::{MediaFormat, MediaRequestParameters};
+use crate::media::{MediaFormat, MediaRequestParameters, MediaThumbnailSettings};
 
 /// [`EventCacheStoreMedia`] integration tests.
 ///
@@ -37,6 +39,9 @@ pub trait EventCacheStoreMediaIntegrationTests {
     /// Test media retention policy storage.
     async fn test_store_media_retention_policy(&self);
 
+    /// Test scoped media retention policies storage.
+    async fn test_store_scoped_media_retention_policies(&self);
+
     /// Test media content's retention policy max file size.
     async fn test_media_max_file_size(&self);
 
@@ -54,6 +59,13 @@ pub trait EventCacheStoreMediaIntegrationTests {
     /// policy expiry.
     async fn test_media_ignore_expiry(&self);
 
+    /// Test pinned media content with the media content's retention policy
+    /// expiry.
+    async fn test_media_pinned_expiry(&self);
+
+    /// Test the expiry of media content with scoped retention policies.
+    async fn test_media_scoped_expiry(&self);
+
     /// Test last media cleanup time storage.
     async fn test_store_last_media_cleanup_time(&self);
 }
@@ -75,6 +87,25 @@ where
         assert_eq!(stored, Some(policy));
     }
 
+    async fn test_store_scoped_media_retention_policies(&self) {
+        let stored = self.scoped_media_retention_policies_inner().await.unwrap();
+        assert!(stored.is_none());
+
+        let mut policies = ScopedMediaRetentionPolicies::default();
+        policies.set(
+            MediaRetentionScope::Room(owned_room_id!("!room:localhost")),
+            Some(MediaRetentionPolicy::empty()),
+        );
+        policies.set(
+            MediaRetentionScope::Kind(MediaKind::Thumbnail),
+            Some(MediaRetentionPolicy::default()),
+        );
+        self.set_scoped_media_retention_policies_inner(&policies).await.unwrap();
+
+        let stored = self.scoped_media_retention_policies_inner().await.unwrap();
+        assert_eq!(stored, Some(policies));
+    }
+
     async fn test_media_max_file_size(&self) {
         let time = SystemTime::now();
 
@@ -111,6 +142,7 @@ where
             time,
             policy,
             IgnoreMediaRetentionPolicy::No,
+            &MediaOrigin::default(),
         )
         .await
         .unwrap();
@@ -120,6 +152,7 @@ where
             time,
             policy,
             IgnoreMediaRetentionPolicy::No,
+            &MediaOrigin::default(),
         )
         .await
         .unwrap();
@@ -129,6 +162,7 @@ where
             time,
             policy,
             IgnoreMediaRetentionPolicy::No,
+            &MediaOrigin::default(),
         )
         .await
         .unwrap();
@@ -142,7 +176,7 @@ where
         assert!(stored.is_some());
 
         // A cleanup doesn't have any effect.
-        self.clean_up_media_cache_inner(policy, time).await.unwrap();
+        self.clean_up_media_cache_inner(policy, &Default::default(), time).await.unwrap();
 
         let stored = self.get_media_content_inner(&request_avg, time).await.unwrap();
         assert!(stored.is_some());
@@ -153,7 +187,7 @@ where
         let policy = MediaRetentionPolicy::empty().with_max_file_size(Some(100));
 
         // The cleanup removes the average media.
-        self.clean_up_media_cache_inner(policy, time).await.unwrap();
+        self.clean_up_media_cache_inner(policy, &Default::default(), time).await.unwrap();
 
         let stored = self.get_media_content_inner(&request_avg, time).await.unwrap();
         assert!(stored.is_none());
@@ -167,6 +201,7 @@ where
             time,
             policy,
             IgnoreMediaRetentionPolicy::No,
+            &MediaOrigin::default(),
         )
         .await
         .unwrap();
@@ -176,6 +211,7 @@ where
             time,
             policy,
             IgnoreMediaRetentionPolicy::No,
+            &MediaOrigin::default(),
         )
         .await
         .unwrap();
@@ -197,6 +233,7 @@ where
             time,
             policy,
             IgnoreMediaRetentionPolicy::No,
+            &MediaOrigin::default(),
         )
         .await
         .unwrap();
@@ -206,6 +243,7 @@ where
             time,
             policy,
             IgnoreMediaRetentionPolicy::No,
+            &MediaOrigin::default(),
         )
         .await
         .unwrap();
@@ -221,7 +259,7 @@ where
             .with_max_file_size(Some(1000));
 
         // The cleanup removes the average media.
-        self.clean_up_media_cache_inner(policy, time).await.unwrap();
+        self.clean_up_media_cache_inner(policy, &Default::default(), time).await.unwrap();
 
         let stored = self.get_media_content_inner(&request_avg, time).await.unwrap();
         assert!(stored.is_none());
@@ -235,6 +273,7 @@ where
             time,
             policy,
             IgnoreMediaRetentionPolicy::No,
+            &MediaOrigin::default(),
         )
         .await
         .unwrap();
@@ -244,6 +283,7 @@ where
             time,
             policy,
             IgnoreMediaRetentionPolicy::No,
+            &MediaOrigin::default(),
         )
         .await
         .unwrap();
@@ -310,6 +350,7 @@ where
             time,
             policy,
             IgnoreMediaRetentionPolicy::No,
+            &MediaOrigin::default(),
         )
         .await
         .unwrap();
@@ -320,6 +361,7 @@ where
             time,
             policy,
             IgnoreMediaRetentionPolicy::No,
+            &MediaOrigin::default(),
         )
         .await
         .unwrap();
@@ -330,6 +372,7 @@ where
             time,
             policy,
             IgnoreMediaRetentionPolicy::No,
+            &MediaOrigin::default(),
         )
         .await
         .unwrap();
@@ -340,6 +383,7 @@ where
             time,
             policy,
             IgnoreMediaRetentionPolicy::No,
+            &MediaOrigin::default(),
         )
         .await
         .unwrap();
@@ -350,6 +394,7 @@ where
             time,
             policy,
             IgnoreMediaRetentionPolicy::No,
+            &MediaOrigin::default(),
         )
         .await
         .unwrap();
@@ -360,6 +405,7 @@ where
             time,
             policy,
             IgnoreMediaRetentionPolicy::No,
+            &MediaOrigin::default(),
         )
         .await
         .unwrap();
@@ -370,6 +416,7 @@ where
             time,
             policy,
             IgnoreMediaRetentionPolicy::No,
+            &MediaOrigin::default(),
         )
         .await
         .unwrap();
@@ -399,7 +446,7 @@ where
 
         // Cleanup removes the oldest content first.
         time += Duration::from_secs(1);
-        self.clean_up_media_cache_inner(policy, time).await.unwrap();
+        self.clean_up_media_cache_inner(policy, &Default::default(), time).await.unwrap();
 
         time += Duration::from_secs(1);
         let stored = self.get_media_content_inner(&request_small_1, time).await.unwrap();
@@ -428,6 +475,7 @@ where
             time,
             policy,
             IgnoreMediaRetentionPolicy::No,
+            &MediaOrigin::default(),
         )
         .await
         .unwrap();
@@ -438,6 +486,7 @@ where
             time,
             policy,
             IgnoreMediaRetentionPolicy::No,
+            &MediaOrigin::default(),
         )
         .await
         .unwrap();
@@ -448,6 +497,7 @@ where
             time,
             policy,
             IgnoreMediaRetentionPolicy::No,
+            &MediaOrigin::default(),
         )
         .await
         .unwrap();
@@ -458,6 +508,7 @@ where
             time,
             policy,
             IgnoreMediaRetentionPolicy::No,
+            &MediaOrigin::default(),
         )
         .await
         .unwrap();
@@ -485,7 +536,7 @@ where
         // before.
         time += Duration::from_secs(1);
         tracing::info!(?self, "before");
-        self.clean_up_media_cache_inner(policy, time).await.unwrap();
+        self.clean_up_media_cache_inner(policy, &Default::default(), time).await.unwrap();
         tracing::info!(?self, "after");
         time += Duration::from_secs(1);
         let stored = self.get_media_content_inner(&request_small_1, time).await.unwrap();
@@ -539,6 +590,7 @@ where
             time,
             policy,
             IgnoreMediaRetentionPolicy::No,
+            &MediaOrigin::default(),
         )
         .await
         .unwrap();
@@ -549,6 +601,7 @@ where
             time,
             policy,
             IgnoreMediaRetentionPolicy::No,
+            &MediaOrigin::default(),
         )
         .await
         .unwrap();
@@ -559,6 +612,7 @@ where
             time,
             policy,
             IgnoreMediaRetentionPolicy::No,
+            &MediaOrigin::default(),
         )
         .await
         .unwrap();
@@ -569,6 +623,7 @@ where
             time,
             policy,
             IgnoreMediaRetentionPolicy::No,
+            &MediaOrigin::default(),
         )
         .await
         .unwrap();
@@ -579,6 +634,7 @@ where
             time,
             policy,
             IgnoreMediaRetentionPolicy::No,
+            &MediaOrigin::default(),
         )
         .await
         .unwrap();
@@ -606,7 +662,7 @@ where
         assert_eq!(time, SystemTime::UNIX_EPOCH + Duration::from_secs(10));
 
         // Cleanup has no effect, nothing has expired.
-        self.clean_up_media_cache_inner(policy, time).await.unwrap();
+        self.clean_up_media_cache_inner(policy, &Default::default(), time).await.unwrap();
 
         time += Duration::from_secs(1);
         let stored = self.get_media_content_inner(&request_1, time).await.unwrap();
@@ -633,7 +689,7 @@ where
         time += Duration::from_secs(26);
 
         // Cleanup removes the two oldest media contents.
-        self.clean_up_media_cache_inner(policy, time).await.unwrap();
+        self.clean_up_media_cache_inner(policy, &Default::default(), time).await.unwrap();
 
         time += Duration::from_secs(1);
         let stored = self.get_media_content_inner(&request_1, time).await.unwrap();
@@ -689,6 +745,7 @@ where
             time,
             policy,
             IgnoreMediaRetentionPolicy::No,
+            &MediaOrigin::default(),
         )
         .await
         .unwrap();
@@ -704,6 +761,7 @@ where
             time,
             policy,
             IgnoreMediaRetentionPolicy::Yes,
+            &MediaOrigin::default(),
         )
         .await
         .unwrap();
@@ -720,6 +778,7 @@ where
             time,
             policy,
             IgnoreMediaRetentionPolicy::No,
+            &MediaOrigin::default(),
         )
         .await
         .unwrap();
@@ -730,6 +789,7 @@ where
             time,
             policy,
             IgnoreMediaRetentionPolicy::No,
+            &MediaOrigin::default(),
         )
         .await
         .unwrap();
@@ -749,7 +809,7 @@ where
 
         // Because the big and average contents are ignored, cleanup has no effect.
         time += Duration::from_secs(1);
-        self.clean_up_media_cache_inner(policy, time).await.unwrap();
+        self.clean_up_media_cache_inner(policy, &Default::default(), time).await.unwrap();
 
         time += Duration::from_secs(1);
         let stored = self.get_media_content_inner(&request_small, time).await.unwrap();
@@ -767,7 +827,7 @@ where
             .unwrap();
 
         time += Duration::from_secs(1);
-        self.clean_up_media_cache_inner(policy, time).await.unwrap();
+        self.clean_up_media_cache_inner(policy, &Default::default(), time).await.unwrap();
 
         time += Duration::from_secs(1);
         let stored = self.get_media_content_inner(&request_small, time).await.unwrap();
@@ -786,7 +846,7 @@ where
             .unwrap();
 
         time += Duration::from_secs(1);
-        self.clean_up_media_cache_inner(policy, time).await.unwrap();
+        self.clean_up_media_cache_inner(policy, &Default::default(), time).await.unwrap();
 
         time += Duration::from_secs(1);
         let stored = self.get_media_content_inner(&request_small, time).await.unwrap();
@@ -831,6 +891,7 @@ where
             time,
             policy,
             IgnoreMediaRetentionPolicy::Yes,
+            &MediaOrigin::default(),
         )
         .await
         .unwrap();
@@ -841,6 +902,7 @@ where
             time,
             policy,
             IgnoreMediaRetentionPolicy::Yes,
+            &MediaOrigin::default(),
         )
         .await
         .unwrap();
@@ -851,6 +913,7 @@ where
             time,
             policy,
             IgnoreMediaRetentionPolicy::No,
+            &MediaOrigin::default(),
         )
         .await
         .unwrap();
@@ -861,6 +924,7 @@ where
             time,
             policy,
             IgnoreMediaRetentionPolicy::No,
+            &MediaOrigin::default(),
         )
         .await
         .unwrap();
@@ -871,6 +935,7 @@ where
             time,
             policy,
             IgnoreMediaRetentionPolicy::No,
+            &MediaOrigin::default(),
         )
         .await
         .unwrap();
@@ -896,7 +961,7 @@ where
         time += Duration::from_secs(120);
 
         // Cleanup removes all the media contents that are not ignored.
-        self.clean_up_media_cache_inner(policy, time).await.unwrap();
+        self.clean_up_media_cache_inner(policy, &Default::default(), time).await.unwrap();
 
         time += Duration::from_secs(1);
         let stored = self.get_media_content_inner(&request_1, time).await.unwrap();
@@ -926,7 +991,7 @@ where
         time += Duration::from_secs(120);
 
         // Cleanup removes the remaining media contents.
-        self.clean_up_media_cache_inner(policy, time).await.unwrap();
+        self.clean_up_media_cache_inner(policy, &Default::default(), time).await.unwrap();
 
         time += Duration::from_secs(1);
         let stored = self.get_media_content_inner(&request_1, time).await.unwrap();
@@ -945,20 +1010,185 @@ where
         assert!(stored.is_none());
     }
 
+    async fn test_media_pinned_expiry(&self) {
+        // 64 bytes content.
+        let content = vec![0; 64];
+
+        let uri_1 = owned_mxc_uri!("mxc://localhost/media-1");
+        let request_1 =
+            MediaRequestParameters { source: MediaSource::Plain(uri_1), format: MediaFormat::File };
+        let uri_2 = owned_mxc_uri!("mxc://localhost/media-2");
+        let request_2 =
+            MediaRequestParameters { source: MediaSource::Plain(uri_2), format: MediaFormat::File };
+
+        // A policy with 30 seconds expiry.
+        let policy =
+            MediaRetentionPolicy::empty().with_last_access_expiry(Some(Duration::from_secs(30)));
+
+        // Add the content.
+        let mut time = SystemTime::UNIX_EPOCH;
+        for request in [&request_1, &request_2] {
+            self.add_media_content_inner(
+                request,
+                content.clone(),
+                time,
+                policy,
+                IgnoreMediaRetentionPolicy::No,
+                &MediaOrigin::default(),
+            )
+            .await
+            .unwrap();
+        }
+
+        // Pin the first content.
+        self.set_media_pinned_inner(&request_1, true).await.unwrap();
+
+        // Adding the content again or not ignoring the policy doesn't unpin it.
+        time += Duration::from_secs(1);
+        self.add_media_content_inner(
+            &request_1,
+            content,
+            time,
+            policy,
+            IgnoreMediaRetentionPolicy::No,
+            &MediaOrigin::default(),
+        )
+        .await
+        .unwrap();
+        self.set_ignore_media_retention_policy_inner(&request_1, IgnoreMediaRetentionPolicy::No)
+            .await
+            .unwrap();
+
+        // We advance of 120 seconds, all media should be expired.
+        time += Duration::from_secs(120);
+
+        // Cleanup removes the content that is not pinned.
+        self.clean_up_media_cache_inner(policy, &Default::default(), time).await.unwrap();
+
+        time += Duration::from_secs(1);
+        let stored = self.get_media_content_inner(&request_1, time).await.unwrap();
+        assert!(stored.is_some());
+        time += Duration::from_secs(1);
+        let stored = self.get_media_content_inner(&request_2, time).await.unwrap();
+        assert!(stored.is_none());
+
+        // Unpin the content.
+        self.set_media_pinned_inner(&request_1, false).await.unwrap();
+
+        // We advance of 120 seconds, the media should be expired again.
+        time += Duration::from_secs(120);
+
+        // Cleanup removes the remaining content.
+        self.clean_up_media_cache_inner(policy, &Default::default(), time).await.unwrap();
+
+        time += Duration::from_secs(1);
+        let stored = self.get_media_content_inner(&request_1, time).await.unwrap();
+        assert!(stored.is_none());
+    }
+
+    async fn test_media_scoped_expiry(&self) {
+        // 64 bytes content.
+        let content = vec![0; 64];
+
+        let room_id = owned_room_id!("!room:localhost");
+        let thumbnail_format =
+            MediaFormat::Thumbnail(MediaThumbnailSettings::new(uint!(100), uint!(100)));
+
+        let file_request = MediaRequestParameters {
+            source: MediaSource::Plain(owned_mxc_uri!("mxc://localhost/file")),
+            format: MediaFormat::File,
+        };
+        let room_file_request = MediaRequestParameters {
+            source: MediaSource::Plain(owned_mxc_uri!("mxc://localhost/room-file")),
+            format: MediaFormat::File,
+        };
+        let thumbnail_request = MediaRequestParameters {
+            source: MediaSource::Plain(owned_mxc_uri!("mxc://localhost/thumbnail")),
+            format: thumbnail_format.clone(),
+        };
+        let room_thumbnail_request = MediaRequestParameters {
+            source: MediaSource::Plain(owned_mxc_uri!("mxc://localhost/room-thumbnail")),
+            format: thumbnail_format,
+        };
+
+        // A global policy with 60 seconds expiry, media contents in the room never
+        // expire, and thumbnails expire after 30 seconds.
+        let policy =
+            MediaRetentionPolicy::empty().with_last_access_expiry(Some(Duration::from_secs(60)));
+        let mut scoped_policies = ScopedMediaRetentionPolicies::default();
+        scoped_policies
+            .set(MediaRetentionScope::Room(room_id.clone()), Some(MediaRetentionPolicy::empty()));
+        scoped_policies.set(
+            MediaRetentionScope::Kind(MediaKind::Thumbnail),
+            Some(
+                MediaRetentionPolicy::empty()
+                    .with_last_access_expiry(Some(Duration::from_secs(30))),
+            ),
+        );
+
+        let mut time = SystemTime::UNIX_EPOCH;
+        let room_origin = MediaOrigin::room(room_id);
+
+        for (request, origin) in [
+            (&file_request, &MediaOrigin::default()),
+            (&room_file_request, &room_origin),
+            (&thumbnail_request, &MediaOrigin::default()),
+            (&room_thumbnail_request, &room_origin),
+        ] {
+            self.add_media_content_inner(
+                request,
+                content.clone(),
+                time,
+                policy,
+                IgnoreMediaRetentionPolicy::No,
+                origin,
+            )
+            .await
+            .unwrap();
+        }
+
+        // After 40 seconds, only the thumbnail outside of the room has expired.
+        time += Duration::from_secs(40);
+        self.clean_up_media_cache_inner(policy, &scoped_policies, time).await.unwrap();
+
+        let stored = self.get_media_content_inner(&thumbnail_request, time).await.unwrap();
+        assert!(stored.is_none());
+        let stored = self.get_media_content_inner(&room_thumbnail_request, time).await.unwrap();
+        assert!(stored.is_some());
+        let stored = self.get_media_content_inner(&room_file_request, time).await.unwrap();
+        assert!(stored.is_some());
+
+        // After 70 seconds, the file outside of the room has expired too.
+        time = SystemTime::UNIX_EPOCH + Duration::from_secs(70);
+        self.clean_up_media_cache_inner(policy, &scoped_policies, time).await.unwrap();
+
+        let stored = self.get_media_content_inner(&file_request, time).await.unwrap();
+        assert!(stored.is_none());
+
+        // The media contents of the room never expire.
+        time += Duration::from_secs(3_600);
+        self.clean_up_media_cache_inner(policy, &scoped_policies, time).await.unwrap();
+
+        let stored = self.get_media_content_inner(&room_file_request, time).await.unwrap();
+        assert!(stored.is_some());
+        let stored = self.get_media_content_inner(&room_thumbnail_request, time).await.unwrap();
+        assert!(stored.is_some());
+    }
+
     async fn test_store_last_media_cleanup_time(&self) {
         let initial = self.last_media_cleanup_time_inner().await.unwrap();
         let new_time = initial.unwrap_or_else(SystemTime::now) + Duration::from_secs(60);
 
         // With an empty policy.
         let policy = MediaRetentionPolicy::empty();
-        self.clean_up_media_cache_inner(policy, new_time).await.unwrap();
+        self.clean_up_media_cache_inner(policy, &Default::default(), new_time).await.unwrap();
 
         let stored = self.last_media_cleanup_time_inner().await.unwrap();
         assert_eq!(stored, initial);
 
         // With the default policy.
         let policy = MediaRetentionPolicy::default();
-        self.clean_up_media_cache_inner(policy, new_time).await.unwrap();
+        self.clean_up_media_cache_inner(policy, &Default::default(), new_time).await.unwrap();
 
         let stored = self.last_media_cleanup_time_inner().await.unwrap();
         assert_eq!(stored, Some(new_time));
@@ -1042,6 +1272,12 @@ macro_rules! event_cache_store_media_integration_tests {
             event_cache_store_media.test_store_media_retention_policy().await;
         }
 
+        #[async_test]
+        async fn test_store_scoped_media_retention_policies() {
+            let event_cache_store_media = get_event_cache_store().await.unwrap();
+            event_cache_store_media.test_store_scoped_media_retention_policies().await;
+        }
+
         #[async_test]
         async fn test_media_expiry() {
             let event_cache_store_media = get_event_cache_store().await.unwrap();
@@ -1054,6 +1290,18 @@ macro_rules! event_cache_store_media_integration_tests {
             event_cache_store_media.test_media_ignore_expiry().await;
         }
 
+        #[async_test]
+        async fn test_media_pinned_expiry() {
+            let event_cache_store_media = get_event_cache_store().await.unwrap();
+            event_cache_store_media.test_media_pinned_expiry().await;
+        }
+
+        #[async_test]
+        async fn test_media_scoped_expiry() {
+            let event_cache_store_media = get_event_cache_store().await.unwrap();
+            event_cache_store_media.test_media_scoped_expiry().await;
+        }
+
         #[async_test]
         async fn test_store_last_media_cleanup_time() {
             let event_cache_store_media = get_event_cache_store().await.unwrap();
@@ -0,0 +1,223 @@
+// Copyright 2025 The Matrix.org Foundation C.I.C.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Types to inspect the usage of the media cache.
+
+use std::collections::BTreeMap;
+
+use ruma::{time::SystemTime, OwnedMxcUri, OwnedRoomId};
+
+use super::MediaKind;
+
+/// A media content in the media cache.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MediaCacheEntry {
+    /// The MXC URI of the media content.
+    ///
+    /// It might be unknown for media content that was cached by an older
+    /// version of the store.
+    pub uri: Option<OwnedMxcUri>,
+
+    /// The room where the media content was found, if any.
+    pub room_id: Option<OwnedRoomId>,
+
+    /// The kind of the media content.
+    ///
+    /// It might be unknown for media content that was cached by an older
+    /// version of the store.
+    pub kind: Option<MediaKind>,
+
+    /// The MIME type of the media content, if it is known.
+    pub content_type: Option<String>,
+
+    /// The size of the media content in the store, in bytes.
+    ///
+    /// This is the size of the content as it is stored, so it might be bigger
+    /// than the size of the media itself if the store is encrypted.
+    pub size: u64,
+
+    /// The time of the last access of the media content.
+    pub last_access: SystemTime,
+
+    /// Whether the media content is kept in the cache regardless of the
+    /// media retention policies.
+    pub ignore_policy: bool,
+
+    /// Whether the media content was pinned in the cache.
+    pub pinned: bool,
+}
+
+/// Statistics about a subset of the media cache.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct MediaCacheUsageStats {
+    /// The total size of the media contents, in bytes.
+    pub size: u64,
+
+    /// The number of media contents.
+    pub count: u64,
+
+    /// The most recent access of one of the media contents, if there are any.
+    pub last_access: Option<SystemTime>,
+}
+
+impl MediaCacheUsageStats {
+    /// Add the given media content to these statistics.
+    fn add(&mut self, entry: &MediaCacheEntry) {
+        self.size = self.size.saturating_add(entry.size);
+        self.count += 1;
+        self.last_access = self.last_access.max(Some(entry.last_access));
+    }
+}
+
+/// The usage of the media cache.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct MediaCacheUsage {
+    /// The statistics of the whole media cache.
+    pub total: MediaCacheUsageStats,
+
+    /// The statistics of the media contents found in each room.
+    ///
+    /// The media contents whose room is unknown are not included.
+    pub rooms: BTreeMap<OwnedRoomId, MediaCacheUsageStats>,
+
+    /// The statistics of the media contents of each kind.
+    ///
+    /// The media contents whose kind is unknown are not included.
+    pub kinds: BTreeMap<MediaKind, MediaCacheUsageStats>,
+
+    /// The statistics of the media contents of each MIME type.
+    ///
+    /// The media contents whose MIME type is unknown are not included.
+    pub content_types: BTreeMap<String, MediaCacheUsageStats>,
+}
+
+impl MediaCacheUsage {
+    /// Compute the usage of the media cache from its entries.
+    pub fn from_entries<'a>(entries: impl IntoIterator<Item = &'a MediaCacheEntry>) -> Self {
+        let mut usage = Self::default();
+
+        for entry in entries {
+            usage.total.add(entry);
+
+            if let Some(room_id) = &entry.room_id {
+                usage.rooms.entry(room_id.clone()).or_default().add(entry);
+            }
+
+            if let Some(kind) = entry.kind {
+                usage.kinds.entry(kind).or_default().add(entry);
+            }
+
+            if let Some(content_type) = &entry.content_type {
+                usage.content_types.entry(content_type.clone()).or_default().add(entry);
+            }
+        }
+
+        usage
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ruma::{
+        owned_mxc_uri, owned_room_id,
+        time::{Duration, SystemTime},
+    };
+
+    use super::{MediaCacheEntry, MediaCacheUsage, MediaCacheUsageStats};
+    use crate::event_cache::store::media::MediaKind;
+
+    #[test]
+    fn test_media_cache_usage_from_entries() {
+        let room_id = owned_room_id!("!room:localhost");
+        let time = SystemTime::UNIX_EPOCH;
+
+        let entries = [
+            MediaCacheEntry {
+                uri: Some(owned_mxc_uri!("mxc://localhost/file")),
+                room_id: Some(room_id.clone()),
+                kind: Some(MediaKind::File),
+                content_type: Some("image/png".to_owned()),
+                size: 100,
+                last_access: time,
+                ignore_policy: false,
+                pinned: false,
+            },
+            MediaCacheEntry {
+                uri: Some(owned_mxc_uri!("mxc://localhost/thumbnail")),
+                room_id: Some(room_id.clone()),
+                kind: Some(MediaKind::Thumbnail),
+                content_type: Some("image/png".to_owned()),
+                size: 10,
+                last_access: time + Duration::from_secs(10),
+                ignore_policy: false,
+                pinned: false,
+            },
+            MediaCacheEntry {
+                uri: None,
+                room_id: None,
+                kind: None,
+                content_type: None,
+                size: 1,
+                last_access: time + Duration::from_secs(5),
+                ignore_policy: true,
+                pinned: false,
+            },
+        ];
+
+        let usage = MediaCacheUsage::from_entries(&entries);
+
+        assert_eq!(
+            usage.total,
+            MediaCacheUsageStats {
+                size: 111,
+                count: 3,
+                last_access: Some(time + Duration::from_secs(10))
+            }
+        );
+
+        assert_eq!(usage.rooms.len(), 1);
+        assert_eq!(
+            usage.rooms[&room_id],
+            MediaCacheUsageStats {
+                size: 110,
+                count: 2,
+                last_access: Some(time + Duration::from_secs(10))
+            }
+        );
+
+        assert_eq!(usage.kinds.len(), 2);
+        assert_eq!(
+            usage.kinds[&MediaKind::File],
+            MediaCacheUsageStats { size: 100, count: 1, last_access: Some(time) }
+        );
+        assert_eq!(
+            usage.kinds[&MediaKind::Thumbnail],
+            MediaCacheUsageStats {
+                size: 10,
+                count: 1,
+                last_access: Some(time + Duration::from_secs(10))
+            }
+        );
+
+        assert_eq!(usage.content_types.len(), 1);
+        assert_eq!(
+            usage.content_types["image/png"],
+            MediaCacheUsageStats {
+                size: 110,
+                count: 2,
+                last_access: Some(time + Duration::from_secs(10))
+            }
+        );
+    }
+}
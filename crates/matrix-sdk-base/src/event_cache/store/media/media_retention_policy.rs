@@ -20,11 +20,15 @@
 //! [`EventCacheStore::set_media_retention_policy()`]. Then call
 //! [`EventCacheStore::clean_up_media_cache()`].
 //!
+//! Different policies can be used for the media of some rooms or some kinds of
+//! media with [`EventCacheStore::set_scoped_media_retention_policy()`].
+//!
 //! In the future, other settings will allow to run automatic periodic cleanup
 //! jobs.
 //!
 //! [`EventCacheStore::set_media_retention_policy()`]: crate::event_cache::store::EventCacheStore::set_media_retention_policy
 //! [`EventCacheStore::clean_up_media_cache()`]: crate::event_cache::store::EventCacheStore::clean_up_media_cache
+//! [`EventCacheStore::set_scoped_media_retention_policy()`]: crate::event_cache::store::EventCacheStore::set_scoped_media_retention_policy
 
 use ruma::time::{Duration, SystemTime};
 use serde::{Deserialize, Serialize};
@@ -0,0 +1,305 @@
+// Copyright 2025 The Matrix.org Foundation C.I.C.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Media retention policies that only apply to a subset of the media cache.
+//!
+//! Every media content in the cache is subject to a single policy:
+//!
+//! 1. the policy of the room where the media was found, if it is known and such
+//!    a policy was set,
+//! 2. otherwise, the policy of the kind of the media, if such a policy was set,
+//! 3. otherwise, the global [`MediaRetentionPolicy`].
+//!
+//! The criteria of a policy only take into account the media contents that are
+//! subject to it. In particular, the maximum cache size of a scoped policy is
+//! the maximum size of the media contents in that scope, and these media
+//! contents are not counted in the size of the cache for the other policies.
+//! This allows, for example, to make sure that the media of a single big room
+//! cannot evict all the other media contents from the cache.
+
+use std::collections::BTreeMap;
+
+use ruma::{OwnedRoomId, RoomId};
+use serde::{Deserialize, Serialize};
+
+use super::MediaRetentionPolicy;
+use crate::media::{MediaFormat, MediaRequestParameters};
+
+/// The kind of a media content in the media cache.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MediaKind {
+    /// The full content of a media file.
+    File,
+
+    /// A thumbnail of a media file.
+    Thumbnail,
+
+    /// The avatar of a room or a user, in any format.
+    Avatar,
+}
+
+impl MediaKind {
+    /// The string representation of this kind, that can be persisted.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::File => "file",
+            Self::Thumbnail => "thumbnail",
+            Self::Avatar => "avatar",
+        }
+    }
+}
+
+/// Where a media content comes from, used to find the scoped media retention
+/// policy that applies to it, and to report the usage of the media cache.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MediaOrigin {
+    /// The room where the media was found, if any.
+    pub room_id: Option<OwnedRoomId>,
+
+    /// Whether the media is the avatar of a room or a user.
+    pub is_avatar: bool,
+
+    /// The MIME type of the media, if it is known.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub content_type: Option<String>,
+}
+
+impl MediaOrigin {
+    /// A media that was found in the given room.
+    pub fn room(room_id: OwnedRoomId) -> Self {
+        Self { room_id: Some(room_id), ..Default::default() }
+    }
+
+    /// A media that is the avatar of a room or a user.
+    pub fn avatar() -> Self {
+        Self { is_avatar: true, ..Default::default() }
+    }
+
+    /// Set the MIME type of the media.
+    pub fn with_content_type(mut self, content_type: impl Into<String>) -> Self {
+        self.content_type = Some(content_type.into());
+        self
+    }
+
+    /// The kind of the media with the given request and this origin.
+    pub fn media_kind(&self, request: &MediaRequestParameters) -> MediaKind {
+        if self.is_avatar {
+            MediaKind::Avatar
+        } else if matches!(request.format, MediaFormat::Thumbnail(_)) {
+            MediaKind::Thumbnail
+        } else {
+            MediaKind::File
+        }
+    }
+}
+
+/// A subset of the media cache that a [`MediaRetentionPolicy`] can be scoped
+/// to.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum MediaRetentionScope {
+    /// The media contents that were found in the given room.
+    Room(OwnedRoomId),
+
+    /// The media contents of the given kind.
+    Kind(MediaKind),
+}
+
+/// The media retention policies scoped to a subset of the media cache, that
+/// take precedence over the global [`MediaRetentionPolicy`].
+///
+/// The `cleanup_frequency` of the scoped policies is ignored, cleanups are
+/// scheduled according to the global policy.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ScopedMediaRetentionPolicies {
+    /// The policies scoped to a room.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    rooms: BTreeMap<OwnedRoomId, MediaRetentionPolicy>,
+
+    /// The policies scoped to a kind of media.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    kinds: BTreeMap<MediaKind, MediaRetentionPolicy>,
+}
+
+impl ScopedMediaRetentionPolicies {
+    /// Get the policy for the given scope, if any.
+    pub fn get(&self, scope: &MediaRetentionScope) -> Option<MediaRetentionPolicy> {
+        match scope {
+            MediaRetentionScope::Room(room_id) => self.rooms.get(room_id),
+            MediaRetentionScope::Kind(kind) => self.kinds.get(kind),
+        }
+        .copied()
+    }
+
+    /// Set the policy for the given scope.
+    ///
+    /// If `policy` is `None`, the policy of the scope is removed.
+    pub fn set(&mut self, scope: MediaRetentionScope, policy: Option<MediaRetentionPolicy>) {
+        match (scope, policy) {
+            (MediaRetentionScope::Room(room_id), Some(policy)) => {
+                self.rooms.insert(room_id, policy);
+            }
+            (MediaRetentionScope::Room(room_id), None) => {
+                self.rooms.remove(&room_id);
+            }
+            (MediaRetentionScope::Kind(kind), Some(policy)) => {
+                self.kinds.insert(kind, policy);
+            }
+            (MediaRetentionScope::Kind(kind), None) => {
+                self.kinds.remove(&kind);
+            }
+        }
+    }
+
+    /// The policies scoped to a room.
+    pub fn rooms(&self) -> &BTreeMap<OwnedRoomId, MediaRetentionPolicy> {
+        &self.rooms
+    }
+
+    /// The policies scoped to a kind of media.
+    pub fn kinds(&self) -> &BTreeMap<MediaKind, MediaRetentionPolicy> {
+        &self.kinds
+    }
+
+    /// Whether there are no scoped policies.
+    pub fn is_empty(&self) -> bool {
+        self.rooms.is_empty() && self.kinds.is_empty()
+    }
+
+    /// Whether at least one of the scoped policies has limitations.
+    pub fn has_limitations(&self) -> bool {
+        self.rooms.values().chain(self.kinds.values()).any(|policy| policy.has_limitations())
+    }
+
+    /// The scope whose policy applies to a media content with the given room
+    /// and kind.
+    ///
+    /// Returns `None` if the global policy applies.
+    pub fn scope_of(
+        &self,
+        room_id: Option<&RoomId>,
+        kind: Option<MediaKind>,
+    ) -> Option<MediaRetentionScope> {
+        if let Some(room_id) = room_id.filter(|room_id| self.rooms.contains_key(*room_id)) {
+            return Some(MediaRetentionScope::Room(room_id.to_owned()));
+        }
+
+        kind.filter(|kind| self.kinds.contains_key(kind)).map(MediaRetentionScope::Kind)
+    }
+
+    /// The policy that applies to a media content with the given room and
+    /// kind, given the global policy.
+    pub fn policy_for(
+        &self,
+        room_id: Option<&RoomId>,
+        kind: Option<MediaKind>,
+        global: MediaRetentionPolicy,
+    ) -> MediaRetentionPolicy {
+        room_id
+            .and_then(|room_id| self.rooms.get(room_id))
+            .or_else(|| kind.and_then(|kind| self.kinds.get(&kind)))
+            .copied()
+            .unwrap_or(global)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ruma::{owned_room_id, room_id};
+
+    use super::{MediaKind, MediaRetentionScope, ScopedMediaRetentionPolicies};
+    use crate::event_cache::store::media::MediaRetentionPolicy;
+
+    #[test]
+    fn test_scoped_media_retention_policies_precedence() {
+        let global = MediaRetentionPolicy::new();
+        let room_policy = MediaRetentionPolicy::empty().with_max_file_size(Some(0));
+        let avatar_policy = MediaRetentionPolicy::empty();
+
+        let mut policies = ScopedMediaRetentionPolicies::default();
+        assert!(policies.is_empty());
+        assert!(!policies.has_limitations());
+
+        policies.set(MediaRetentionScope::Kind(MediaKind::Avatar), Some(avatar_policy));
+        assert!(!policies.is_empty());
+        assert!(!policies.has_limitations());
+
+        policies
+            .set(MediaRetentionScope::Room(owned_room_id!("!big:localhost")), Some(room_policy));
+        assert!(policies.has_limitations());
+
+        let big_room = room_id!("!big:localhost");
+        let other_room = room_id!("!other:localhost");
+
+        // The policy of the room takes precedence over the one of the kind.
+        assert_eq!(
+            policies.scope_of(Some(big_room), Some(MediaKind::Avatar)),
+            Some(MediaRetentionScope::Room(big_room.to_owned()))
+        );
+        assert_eq!(
+            policies.policy_for(Some(big_room), Some(MediaKind::Avatar), global),
+            room_policy
+        );
+
+        // The policy of the kind applies to the media in other rooms.
+        assert_eq!(
+            policies.scope_of(Some(other_room), Some(MediaKind::Avatar)),
+            Some(MediaRetentionScope::Kind(MediaKind::Avatar))
+        );
+        assert_eq!(
+            policies.policy_for(Some(other_room), Some(MediaKind::Avatar), global),
+            avatar_policy
+        );
+
+        // The global policy applies to the other media.
+        assert_eq!(policies.scope_of(Some(other_room), Some(MediaKind::File)), None);
+        assert_eq!(policies.scope_of(None, None), None);
+        assert_eq!(policies.policy_for(None, Some(MediaKind::File), global), global);
+
+        // Removing a policy.
+        policies.set(MediaRetentionScope::Room(big_room.to_owned()), None);
+        assert_eq!(policies.get(&MediaRetentionScope::Room(big_room.to_owned())), None);
+        assert_eq!(
+            policies.scope_of(Some(big_room), Some(MediaKind::Avatar)),
+            Some(MediaRetentionScope::Kind(MediaKind::Avatar))
+        );
+    }
+
+    #[test]
+    fn test_scoped_media_retention_policies_serialization() {
+        let mut policies = ScopedMediaRetentionPolicies::default();
+        assert_eq!(serde_json::to_string(&policies).unwrap(), "{}");
+
+        policies.set(
+            MediaRetentionScope::Kind(MediaKind::Thumbnail),
+            Some(MediaRetentionPolicy::empty().with_max_cache_size(Some(1_024))),
+        );
+        policies.set(
+            MediaRetentionScope::Room(owned_room_id!("!room:localhost")),
+            Some(MediaRetentionPolicy::empty()),
+        );
+
+        let json = serde_json::to_value(&policies).unwrap();
+        assert_eq!(
+            json,
+            serde_json::json!({
+                "rooms": { "!room:localhost": {} },
+                "kinds": { "thumbnail": { "max_cache_size": 1_024 } },
+            })
+        );
+
+        let deserialized: ScopedMediaRetentionPolicies = serde_json::from_value(json).unwrap();
+        assert_eq!(deserialized, policies);
+    }
+}
@@ -24,7 +24,7 @@ use ruma::{time::SystemTime, MxcUri};
 use tokio::sync::Mutex as AsyncMutex;
 use tracing::error;
 
-use super::MediaRetentionPolicy;
+use super::{MediaOrigin, MediaRetentionPolicy, MediaRetentionScope, ScopedMediaRetentionPolicies};
 use crate::{event_cache::store::EventCacheStoreError, media::MediaRequestParameters};
 
 /// API for implementors of [`EventCacheStore`] to manage their media through
@@ -44,6 +44,9 @@ struct MediaServiceInner<Time: TimeProvider = DefaultTimeProvider> {
     /// The current [`MediaRetentionPolicy`].
     policy: Mutex<MediaRetentionPolicy>,
 
+    /// The current [`ScopedMediaRetentionPolicies`].
+    scoped_policies: Mutex<ScopedMediaRetentionPolicies>,
+
     /// A mutex to ensure a single cleanup is running at a time.
     cleanup_guard: AsyncMutex<()>,
 
@@ -83,6 +86,7 @@ where
         let inner = MediaServiceInner {
             time_provider,
             policy: Mutex::new(MediaRetentionPolicy::empty()),
+            scoped_policies: Mutex::new(ScopedMediaRetentionPolicies::default()),
             cleanup_guard: AsyncMutex::new(()),
             last_media_cleanup_time: Mutex::new(None),
             automatic_media_cleanup_join_handle: Mutex::new(None),
@@ -99,15 +103,26 @@ where
     /// # Arguments
     ///
     /// * `policy` - The `MediaRetentionPolicy` that was persisted in the store.
+    ///
+    /// * `scoped_policies` - The `ScopedMediaRetentionPolicies` that were
+    ///   persisted in the store.
+    ///
+    /// * `last_media_cleanup_time` - The time of the last media cache cleanup
+    ///   that was persisted in the store.
     pub fn restore(
         &self,
         policy: Option<MediaRetentionPolicy>,
+        scoped_policies: Option<ScopedMediaRetentionPolicies>,
         last_media_cleanup_time: Option<SystemTime>,
     ) {
         if let Some(policy) = policy {
             *self.inner.policy.lock() = policy;
         }
 
+        if let Some(scoped_policies) = scoped_policies {
+            *self.inner.scoped_policies.lock() = scoped_policies;
+        }
+
         if let Some(time) = last_media_cleanup_time {
             *self.inner.last_media_cleanup_time.lock() = Some(time);
         }
@@ -144,8 +159,51 @@ where
         *self.inner.policy.lock()
     }
 
+    /// Set or remove the `MediaRetentionPolicy` of the given scope.
+    ///
+    /// # Arguments
+    ///
+    /// * `store` - The `EventCacheStoreMedia`.
+    ///
+    /// * `scope` - The scope of the policy.
+    ///
+    /// * `policy` - The `MediaRetentionPolicy` to use for the scope, or `None`
+    ///   to remove the policy of the scope.
+    pub async fn set_scoped_media_retention_policy<Store: EventCacheStoreMedia + 'static>(
+        &self,
+        store: &Store,
+        scope: MediaRetentionScope,
+        policy: Option<MediaRetentionPolicy>,
+    ) -> Result<(), Store::Error> {
+        let mut scoped_policies = self.scoped_media_retention_policies();
+        scoped_policies.set(scope, policy);
+
+        store.set_scoped_media_retention_policies_inner(&scoped_policies).await?;
+
+        *self.inner.scoped_policies.lock() = scoped_policies;
+
+        self.maybe_spawn_automatic_media_cache_cleanup(store, self.now());
+
+        Ok(())
+    }
+
+    /// Get the `ScopedMediaRetentionPolicies` of this service.
+    pub fn scoped_media_retention_policies(&self) -> ScopedMediaRetentionPolicies {
+        self.inner.scoped_policies.lock().clone()
+    }
+
+    /// Whether the current policies have limitations, i.e. whether a cleanup
+    /// would have an effect.
+    fn policies_have_limitations(&self) -> bool {
+        self.media_retention_policy().has_limitations()
+            || self.inner.scoped_policies.lock().has_limitations()
+    }
+
     /// Add a media file's content in the media store.
     ///
+    /// The origin of the media is unknown, so the policy scoped to its kind or
+    /// the global policy applies to it.
+    ///
     /// # Arguments
     ///
     /// * `store` - The `EventCacheStoreMedia`.
@@ -163,7 +221,45 @@ where
         content: Vec<u8>,
         ignore_policy: IgnoreMediaRetentionPolicy,
     ) -> Result<(), Store::Error> {
-        let policy = self.media_retention_policy();
+        self.add_media_content_with_origin(
+            store,
+            request,
+            content,
+            &MediaOrigin::default(),
+            ignore_policy,
+        )
+        .await
+    }
+
+    /// Add a media file's content in the media store, with its origin.
+    ///
+    /// # Arguments
+    ///
+    /// * `store` - The `EventCacheStoreMedia`.
+    ///
+    /// * `request` - The `MediaRequestParameters` of the file.
+    ///
+    /// * `content` - The content of the file.
+    ///
+    /// * `origin` - Where the media comes from, to find the scoped
+    ///   `MediaRetentionPolicy` that applies to it.
+    ///
+    /// * `ignore_policy` - Whether the current `MediaRetentionPolicy` should be
+    ///   ignored.
+    pub async fn add_media_content_with_origin<Store: EventCacheStoreMedia + 'static>(
+        &self,
+        store: &Store,
+        request: &MediaRequestParameters,
+        content: Vec<u8>,
+        origin: &MediaOrigin,
+        ignore_policy: IgnoreMediaRetentionPolicy,
+    ) -> Result<(), Store::Error> {
+        let global_policy = self.media_retention_policy();
+        let policy = self.inner.scoped_policies.lock().policy_for(
+            origin.room_id.as_deref(),
+            Some(origin.media_kind(request)),
+            global_policy,
+        );
 
         if ignore_policy == IgnoreMediaRetentionPolicy::No
             && policy.exceeds_max_file_size(content.len())
@@ -174,7 +270,7 @@ where
 
         let current_time = self.now();
         store
-            .add_media_content_inner(request, content, current_time, policy, ignore_policy)
+            .add_media_content_inner(request, content, current_time, policy, ignore_policy, origin)
             .await?;
 
         self.maybe_spawn_automatic_media_cache_cleanup(store, current_time);
@@ -204,6 +300,24 @@ where
         store.set_ignore_media_retention_policy_inner(request, ignore_policy).await
     }
 
+    /// Set whether the media is pinned in the media cache.
+    ///
+    /// # Arguments
+    ///
+    /// * `store` - The `EventCacheStoreMedia`.
+    ///
+    /// * `request` - The `MediaRequestParameters` of the file.
+    ///
+    /// * `pinned` - Whether the media should be pinned.
+    pub async fn set_media_pinned<Store: EventCacheStoreMedia>(
+        &self,
+        store: &Store,
+        request: &MediaRequestParameters,
+        pinned: bool,
+    ) -> Result<(), Store::Error> {
+        store.set_media_pinned_inner(request, pinned).await
+    }
+
     /// Get a media file's content out of the media store.
     ///
     /// # Arguments
@@ -269,14 +383,15 @@ where
             return Ok(());
         };
 
-        let policy = self.media_retention_policy();
-
-        if !policy.has_limitations() {
+        if !self.policies_have_limitations() {
             // No need to call the backend.
             return Ok(());
         }
 
-        store.clean_up_media_cache_inner(policy, current_time).await?;
+        let policy = self.media_retention_policy();
+        let scoped_policies = self.scoped_media_retention_policies();
+
+        store.clean_up_media_cache_inner(policy, &scoped_policies, current_time).await?;
 
         *self.inner.last_media_cleanup_time.lock() = Some(current_time);
 
@@ -303,7 +418,7 @@ where
         }
 
         let policy = self.media_retention_policy();
-        if policy.cleanup_frequency.is_none() || !policy.has_limitations() {
+        if policy.cleanup_frequency.is_none() || !self.policies_have_limitations() {
             // Automatic cleanups are disabled or have no effect.
             return;
         }
@@ -377,6 +492,21 @@ pub trait EventCacheStoreMedia: AsyncTraitDeps + Clone {
         policy: MediaRetentionPolicy,
     ) -> Result<(), Self::Error>;
 
+    /// The persisted scoped media retention policies in the media cache.
+    async fn scoped_media_retention_policies_inner(
+        &self,
+    ) -> Result<Option<ScopedMediaRetentionPolicies>, Self::Error>;
+
+    /// Persist the scoped media retention policies in the media cache.
+    ///
+    /// # Arguments
+    ///
+    /// * `policies` - The `ScopedMediaRetentionPolicies` to persist.
+    async fn set_scoped_media_retention_policies_inner(
+        &self,
+        policies: &ScopedMediaRetentionPolicies,
+    ) -> Result<(), Self::Error>;
+
     /// Add a media file's content in the media cache.
     ///
     /// # Arguments
@@ -388,12 +518,16 @@ pub trait EventCacheStoreMedia: AsyncTraitDeps + Clone {
     /// * `current_time` - The current time, to set the last access time of the
     ///   media.
     ///
-    /// * `policy` - The media retention policy, to check whether the media is
-    ///   too big to be cached.
+    /// * `policy` - The media retention policy that applies to the media, to
+    ///   check whether the media is too big to be cached.
     ///
     /// * `ignore_policy` - Whether the `MediaRetentionPolicy` should be ignored
     ///   for this media. This setting should be persisted alongside the media
     ///   and taken into account whenever the policy is used.
+    ///
+    /// * `origin` - Where the media comes from. The room and the kind of the
+    ///   media should be persisted alongside the media, to find the scoped
+    ///   policy that applies to it during a cleanup.
     async fn add_media_content_inner(
         &self,
         request: &MediaRequestParameters,
@@ -401,6 +535,7 @@ pub trait EventCacheStoreMedia: AsyncTraitDeps + Clone {
         current_time: SystemTime,
         policy: MediaRetentionPolicy,
         ignore_policy: IgnoreMediaRetentionPolicy,
+        origin: &MediaOrigin,
     ) -> Result<(), Self::Error>;
 
     /// Set whether the current [`MediaRetentionPolicy`] should be ignored for
@@ -422,6 +557,26 @@ pub trait EventCacheStoreMedia: AsyncTraitDeps + Clone {
         ignore_policy: IgnoreMediaRetentionPolicy,
     ) -> Result<(), Self::Error>;
 
+    /// Set whether the media is pinned in the media cache.
+    ///
+    /// Pinned media content must not be removed during a cleanup, nor be
+    /// counted in the size of the media cache. Adding the media content again
+    /// with [`EventCacheStoreMedia::add_media_content_inner()`] must not
+    /// change whether it is pinned.
+    ///
+    /// If the media of the given request is not found, this should be a noop.
+    ///
+    /// # Arguments
+    ///
+    /// * `request` - The `MediaRequestParameters` of the file.
+    ///
+    /// * `pinned` - Whether the media should be pinned.
+    async fn set_media_pinned_inner(
+        &self,
+        request: &MediaRequestParameters,
+        pinned: bool,
+    ) -> Result<(), Self::Error>;
+
     /// Get a media file's content out of the media cache.
     ///
     /// # Arguments
@@ -451,7 +606,11 @@ pub trait EventCacheStoreMedia: AsyncTraitDeps + Clone {
         current_time: SystemTime,
     ) -> Result<Option<Vec<u8>>, Self::Error>;
 
-    /// Clean up the media cache with the given policy.
+    /// Clean up the media cache with the given policies.
+    ///
+    /// Each media content is subject to a single policy, as explained in the
+    /// docs of [`ScopedMediaRetentionPolicies`], and the criteria of a policy
+    /// are only applied to the media contents that are subject to it.
     ///
     /// For the integration tests, it is expected that content that does not
     /// pass the last access expiry and max file size criteria will be
@@ -460,14 +619,18 @@ pub trait EventCacheStoreMedia: AsyncTraitDeps + Clone {
     ///
     /// # Arguments
     ///
-    /// * `policy` - The media retention policy to use for the cleanup. The
-    ///   `cleanup_frequency` will be ignored.
+    /// * `policy` - The global media retention policy to use for the cleanup.
+    ///   The `cleanup_frequency` will be ignored.
+    ///
+    /// * `scoped_policies` - The scoped media retention policies to use for the
+    ///   cleanup, that take precedence over the global policy.
     ///
     /// * `current_time` - The current time, to be used to check for expired
     ///   content and to be stored as the time of the last media cache cleanup.
     async fn clean_up_media_cache_inner(
         &self,
         policy: MediaRetentionPolicy,
+        scoped_policies: &ScopedMediaRetentionPolicies,
         current_time: SystemTime,
     ) -> Result<(), Self::Error>;
 
@@ -494,8 +657,8 @@ pub enum IgnoreMediaRetentionPolicy {
     /// `MediaRetentionPolicy`. This applies to ANY criteria, like the maximum
     /// file size, the maximum cache size or the last access expiry.
     ///
-    /// This state is supposed to be transient, and to only be used internally
-    /// by the SDK.
+    /// This state is used internally by the SDK for transient media, and can
+    /// also be used to pin media in the cache.
     Yes,
 
     /// The media retention policy will be respected and the current action
@@ -539,14 +702,20 @@ mod tests {
     use matrix_sdk_test::async_test;
     use ruma::{
         events::room::MediaSource,
-        mxc_uri,
+        mxc_uri, owned_mxc_uri, owned_room_id,
         time::{Duration, SystemTime},
         MxcUri, OwnedMxcUri,
     };
 
     use super::{EventCacheStoreMedia, IgnoreMediaRetentionPolicy, MediaService, TimeProvider};
     use crate::{
-        event_cache::store::{media::MediaRetentionPolicy, EventCacheStoreError},
+        event_cache::store::{
+            media::{
+                MediaKind, MediaOrigin, MediaRetentionPolicy, MediaRetentionScope,
+                ScopedMediaRetentionPolicies,
+            },
+            EventCacheStoreError,
+        },
         media::{MediaFormat, MediaRequestParameters, UniqueKey},
     };
 
@@ -587,6 +756,9 @@ mod tests {
         /// The persisted media retention policy.
         media_retention_policy: Option<MediaRetentionPolicy>,
 
+        /// The persisted scoped media retention policies.
+        scoped_media_retention_policies: Option<ScopedMediaRetentionPolicies>,
+
         /// The list of media content.
         media_list: Vec<MediaContent>,
 
@@ -609,6 +781,12 @@ mod tests {
         /// content;
         ignore_policy: bool,
 
+        /// Whether the media content is pinned.
+        pinned: bool,
+
+        /// The origin of the media content.
+        origin: MediaOrigin,
+
         /// The time of the last access of the media content.
         last_access: SystemTime,
     }
@@ -649,6 +827,20 @@ mod tests {
             Ok(())
         }
 
+        async fn scoped_media_retention_policies_inner(
+            &self,
+        ) -> Result<Option<ScopedMediaRetentionPolicies>, Self::Error> {
+            Ok(self.inner().scoped_media_retention_policies.clone())
+        }
+
+        async fn set_scoped_media_retention_policies_inner(
+            &self,
+            policies: &ScopedMediaRetentionPolicies,
+        ) -> Result<(), Self::Error> {
+            self.inner().scoped_media_retention_policies = Some(policies.clone());
+            Ok(())
+        }
+
         async fn add_media_content_inner(
             &self,
             request: &MediaRequestParameters,
@@ -656,6 +848,7 @@ mod tests {
             current_time: SystemTime,
             policy: MediaRetentionPolicy,
             ignore_policy: IgnoreMediaRetentionPolicy,
+            origin: &MediaOrigin,
         ) -> Result<(), Self::Error> {
             let ignore_policy = ignore_policy.is_yes();
 
@@ -671,12 +864,15 @@ mod tests {
                 media_content.content = content;
                 media_content.last_access = current_time;
                 media_content.ignore_policy = ignore_policy;
+                media_content.origin = origin.clone();
             } else {
                 inner.media_list.push(MediaContent {
                     key,
                     uri: request.uri().to_owned(),
                     content,
                     ignore_policy,
+                    pinned: false,
+                    origin: origin.clone(),
                     last_access: current_time,
                 });
             }
@@ -699,6 +895,21 @@ mod tests {
             Ok(())
         }
 
+        async fn set_media_pinned_inner(
+            &self,
+            request: &MediaRequestParameters,
+            pinned: bool,
+        ) -> Result<(), Self::Error> {
+            let key = request.unique_key();
+            let mut inner = self.inner();
+
+            if let Some(pos) = inner.media_list.iter().position(|content| content.key == key) {
+                inner.media_list[pos].pinned = pinned;
+            }
+
+            Ok(())
+        }
+
         async fn get_media_content_inner(
             &self,
             request: &MediaRequestParameters,
@@ -739,6 +950,7 @@ mod tests {
         async fn clean_up_media_cache_inner(
             &self,
             _policy: MediaRetentionPolicy,
+            _scoped_policies: &ScopedMediaRetentionPolicies,
             current_time: SystemTime,
         ) -> Result<(), Self::Error> {
             // This is mostly a noop. We don't care about this test implementation, only
@@ -792,7 +1004,7 @@ mod tests {
 
         // By default an empty policy is used.
         assert!(!service.media_retention_policy().has_limitations());
-        service.restore(None, None);
+        service.restore(None, None, None);
         assert!(!service.media_retention_policy().has_limitations());
         assert!(!store.accessed());
 
@@ -881,7 +1093,7 @@ mod tests {
         let service = MediaService::with_time_provider(MockTimeProvider::new(now));
 
         // Check that restoring the policy works.
-        service.restore(Some(MediaRetentionPolicy::default()), None);
+        service.restore(Some(MediaRetentionPolicy::default()), None, None);
         assert_eq!(service.media_retention_policy(), MediaRetentionPolicy::default());
         assert!(!store.accessed());
 
@@ -1102,4 +1314,102 @@ mod tests {
 
         assert_eq!(store.last_media_cleanup_time_inner().await.unwrap(), Some(now));
     }
+
+    #[async_test]
+    async fn test_media_service_scoped_policies() {
+        let content = vec![0; 64];
+
+        let room_id = owned_room_id!("!big:localhost");
+        let file_request = MediaRequestParameters {
+            source: MediaSource::Plain(owned_mxc_uri!("mxc://localhost/file")),
+            format: MediaFormat::File,
+        };
+        let avatar_request = MediaRequestParameters {
+            source: MediaSource::Plain(owned_mxc_uri!("mxc://localhost/avatar")),
+            format: MediaFormat::File,
+        };
+
+        let now = SystemTime::UNIX_EPOCH;
+
+        let store = MockEventCacheStoreMedia::default();
+        let service = MediaService::with_time_provider(MockTimeProvider::new(now));
+
+        // The global policy doesn't accept the content.
+        let policy = MediaRetentionPolicy::empty().with_max_file_size(Some(32));
+        service.set_media_retention_policy(&store, policy).await.unwrap();
+
+        // But avatars are kept whatever their size, and nothing is cached for the room.
+        service
+            .set_scoped_media_retention_policy(
+                &store,
+                MediaRetentionScope::Kind(MediaKind::Avatar),
+                Some(MediaRetentionPolicy::empty()),
+            )
+            .await
+            .unwrap();
+        service
+            .set_scoped_media_retention_policy(
+                &store,
+                MediaRetentionScope::Room(room_id.clone()),
+                Some(MediaRetentionPolicy::empty().with_max_file_size(Some(0))),
+            )
+            .await
+            .unwrap();
+
+        let scoped_policies = service.scoped_media_retention_policies();
+        assert_eq!(scoped_policies.kinds().len(), 1);
+        assert_eq!(scoped_policies.rooms().len(), 1);
+        assert_eq!(store.inner().scoped_media_retention_policies, Some(scoped_policies));
+
+        // The file of the room is not cached.
+        service
+            .add_media_content_with_origin(
+                &store,
+                &file_request,
+                content.clone(),
+                &MediaOrigin::room(room_id.clone()),
+                IgnoreMediaRetentionPolicy::No,
+            )
+            .await
+            .unwrap();
+        assert!(store.inner().media_list.is_empty());
+
+        // The avatar is cached.
+        service
+            .add_media_content_with_origin(
+                &store,
+                &avatar_request,
+                content.clone(),
+                &MediaOrigin::avatar(),
+                IgnoreMediaRetentionPolicy::No,
+            )
+            .await
+            .unwrap();
+        assert_eq!(store.inner().media_list.len(), 1);
+        assert_eq!(store.inner().media_list[0].origin, MediaOrigin::avatar());
+
+        // Removing the scoped policy of the room means that the global policy applies
+        // again.
+        service
+            .set_scoped_media_retention_policy(
+                &store,
+                MediaRetentionScope::Room(room_id.clone()),
+                None,
+            )
+            .await
+            .unwrap();
+        assert!(service.scoped_media_retention_policies().rooms().is_empty());
+
+        service
+            .add_media_content_with_origin(
+                &store,
+                &file_request,
+                content,
+                &MediaOrigin::room(room_id),
+                IgnoreMediaRetentionPolicy::No,
+            )
+            .await
+            .unwrap();
+        assert_eq!(store.inner().media_list.len(), 1);
+    }
 }
@@ -14,7 +14,9 @@
 
 //! Types and traits regarding media caching of the event cache store.
 
+mod media_cache_usage;
 mod media_retention_policy;
+mod media_retention_scope;
 mod media_service;
 #[cfg(any(test, feature = "testing"))]
 #[macro_use]
@@ -23,6 +25,10 @@ pub mod integration_tests;
 #[cfg(any(test, feature = "testing"))]
 pub use self::integration_tests::EventCacheStoreMediaIntegrationTests;
 pub use self::{
+    media_cache_usage::{MediaCacheEntry, MediaCacheUsage, MediaCacheUsageStats},
     media_retention_policy::MediaRetentionPolicy,
+    media_retention_scope::{
+        MediaKind, MediaOrigin, MediaRetentionScope, ScopedMediaRetentionPolicies,
+    },
     media_service::{EventCacheStoreMedia, IgnoreMediaRetentionPolicy, MediaService},
 };
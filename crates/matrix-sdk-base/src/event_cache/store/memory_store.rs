@@ -13,6 +13,7 @@
 // limitations under the License.
 
 use std::{
+    cmp::Reverse,
     collections::HashMap,
     num::NonZeroUsize,
     sync::{Arc, RwLock as StdRwLock},
@@ -29,12 +30,15 @@ use matrix_sdk_common::{
 };
 use ruma::{
     time::{Instant, SystemTime},
-    EventId, MxcUri, OwnedEventId, OwnedMxcUri, RoomId,
+    EventId, MxcUri, OwnedEventId, OwnedMxcUri, OwnedRoomId, RoomId,
 };
 
 use super::{
-    media::{EventCacheStoreMedia, IgnoreMediaRetentionPolicy, MediaRetentionPolicy, MediaService},
-    EventCacheStore, EventCacheStoreError, Result,
+    media::{
+        EventCacheStoreMedia, IgnoreMediaRetentionPolicy, MediaCacheEntry, MediaKind, MediaOrigin,
+        MediaRetentionPolicy, MediaRetentionScope, MediaService, ScopedMediaRetentionPolicies,
+    },
+    search, EventCacheStore, EventCacheStoreError, Result,
 };
 use crate::{
     event_cache::{Event, Gap},
@@ -56,6 +60,7 @@ struct MemoryStoreInner {
     leases: HashMap<String, (String, Instant)>,
     events: RelationalLinkedChunk<Event, Gap>,
     media_retention_policy: Option<MediaRetentionPolicy>,
+    scoped_media_retention_policies: Option<ScopedMediaRetentionPolicies>,
     last_media_cleanup_time: SystemTime,
 }
 
@@ -74,6 +79,19 @@ struct MediaContent {
     /// Whether we should ignore the [`MediaRetentionPolicy`] for this content.
     ignore_policy: bool,
 
+    /// Whether the content is pinned, and kept regardless of the
+    /// [`MediaRetentionPolicy`].
+    pinned: bool,
+
+    /// The room where the content was found, if any.
+    room_id: Option<OwnedRoomId>,
+
+    /// The kind of the content.
+    kind: MediaKind,
+
+    /// The MIME type of the content, if it is known.
+    content_type: Option<String>,
+
     /// The time of the last access of the content.
     last_access: SystemTime,
 }
@@ -85,7 +103,7 @@ impl Default for MemoryStore {
         // Given that the store is empty, we won't need to clean it up right away.
         let last_media_cleanup_time = SystemTime::now();
         let media_service = MediaService::new();
-        media_service.restore(None, Some(last_media_cleanup_time));
+        media_service.restore(None, None, Some(last_media_cleanup_time));
 
         Self {
             inner: Arc::new(StdRwLock::new(MemoryStoreInner {
@@ -93,6 +111,7 @@ impl Default for MemoryStore {
                 leases: Default::default(),
                 events: RelationalLinkedChunk::new(),
                 media_retention_policy: None,
+                scoped_media_retention_policies: None,
                 last_media_cleanup_time,
             })),
             media_service,
@@ -217,6 +236,27 @@ impl EventCacheStore for MemoryStore {
         Ok(event_and_room)
     }
 
+    async fn search_events(
+        &self,
+        room_id: &RoomId,
+        query: &str,
+        limit: usize,
+    ) -> Result<Vec<Event>, Self::Error> {
+        let inner = self.inner.read().unwrap();
+
+        let mut events = inner
+            .events
+            .unordered_room_items(room_id)
+            .filter(|(event, _)| search::event_matches_query(event, query))
+            .map(|(event, _)| event.clone())
+            .collect::<Vec<_>>();
+
+        events.sort_by_key(|event| Reverse(search::event_timestamp(event)));
+        events.truncate(limit);
+
+        Ok(events)
+    }
+
     async fn add_media_content(
         &self,
         request: &MediaRequestParameters,
@@ -226,6 +266,18 @@ impl EventCacheStore for MemoryStore {
         self.media_service.add_media_content(self, request, data, ignore_policy).await
     }
 
+    async fn add_media_content_with_origin(
+        &self,
+        request: &MediaRequestParameters,
+        data: Vec<u8>,
+        origin: &MediaOrigin,
+        ignore_policy: IgnoreMediaRetentionPolicy,
+    ) -> Result<()> {
+        self.media_service
+            .add_media_content_with_origin(self, request, data, origin, ignore_policy)
+            .await
+    }
+
     async fn replace_media_key(
         &self,
         from: &MediaRequestParameters,
@@ -290,6 +342,31 @@ impl EventCacheStore for MemoryStore {
         Ok(())
     }
 
+    async fn remove_media_content_for_room(&self, room_id: &RoomId) -> Result<()> {
+        let mut inner = self.inner.write().unwrap();
+        inner.media.retain(|media_content| media_content.room_id.as_deref() != Some(room_id));
+        Ok(())
+    }
+
+    async fn media_cache_entries(&self) -> Result<Vec<MediaCacheEntry>> {
+        let inner = self.inner.read().unwrap();
+
+        Ok(inner
+            .media
+            .iter()
+            .map(|media_content| MediaCacheEntry {
+                uri: Some(media_content.uri.clone()),
+                room_id: media_content.room_id.clone(),
+                kind: Some(media_content.kind),
+                content_type: media_content.content_type.clone(),
+                size: media_content.data.len() as u64,
+                last_access: media_content.last_access,
+                ignore_policy: media_content.ignore_policy,
+                pinned: media_content.pinned,
+            })
+            .collect())
+    }
+
     async fn set_media_retention_policy(
         &self,
         policy: MediaRetentionPolicy,
@@ -301,6 +378,18 @@ impl EventCacheStore for MemoryStore {
         self.media_service.media_retention_policy()
     }
 
+    async fn set_scoped_media_retention_policy(
+        &self,
+        scope: MediaRetentionScope,
+        policy: Option<MediaRetentionPolicy>,
+    ) -> Result<(), Self::Error> {
+        self.media_service.set_scoped_media_retention_policy(self, scope, policy).await
+    }
+
+    fn scoped_media_retention_policies(&self) -> ScopedMediaRetentionPolicies {
+        self.media_service.scoped_media_retention_policies()
+    }
+
     async fn set_ignore_media_retention_policy(
         &self,
         request: &MediaRequestParameters,
@@ -309,6 +398,14 @@ impl EventCacheStore for MemoryStore {
         self.media_service.set_ignore_media_retention_policy(self, request, ignore_policy).await
     }
 
+    async fn set_media_pinned(
+        &self,
+        request: &MediaRequestParameters,
+        pinned: bool,
+    ) -> Result<(), Self::Error> {
+        self.media_service.set_media_pinned(self, request, pinned).await
+    }
+
     async fn clean_up_media_cache(&self) -> Result<(), Self::Error> {
         self.media_service.clean_up_media_cache(self).await
     }
@@ -333,6 +430,20 @@ impl EventCacheStoreMedia for MemoryStore {
         Ok(())
     }
 
+    async fn scoped_media_retention_policies_inner(
+        &self,
+    ) -> Result<Option<ScopedMediaRetentionPolicies>, Self::Error> {
+        Ok(self.inner.read().unwrap().scoped_media_retention_policies.clone())
+    }
+
+    async fn set_scoped_media_retention_policies_inner(
+        &self,
+        policies: &ScopedMediaRetentionPolicies,
+    ) -> Result<(), Self::Error> {
+        self.inner.write().unwrap().scoped_media_retention_policies = Some(policies.clone());
+        Ok(())
+    }
+
     async fn add_media_content_inner(
         &self,
         request: &MediaRequestParameters,
@@ -340,13 +451,24 @@ impl EventCacheStoreMedia for MemoryStore {
         last_access: SystemTime,
         policy: MediaRetentionPolicy,
         ignore_policy: IgnoreMediaRetentionPolicy,
+        origin: &MediaOrigin,
     ) -> Result<(), Self::Error> {
+        // Keep whether the content is pinned.
+        let expected_key = request.unique_key();
+        let pinned = self
+            .inner
+            .read()
+            .unwrap()
+            .media
+            .iter()
+            .any(|media| media.key == expected_key && media.pinned);
+
         // Avoid duplication. Let's try to remove it first.
         self.remove_media_content(request).await?;
 
         let ignore_policy = ignore_policy.is_yes();
 
-        if !ignore_policy && policy.exceeds_max_file_size(data.len()) {
+        if !ignore_policy && !pinned && policy.exceeds_max_file_size(data.len()) {
             // Do not store it.
             return Ok(());
         };
@@ -355,9 +477,13 @@ impl EventCacheStoreMedia for MemoryStore {
         let mut inner = self.inner.write().unwrap();
         inner.media.push(MediaContent {
             uri: request.uri().to_owned(),
-            key: request.unique_key(),
+            key: expected_key,
             data,
             ignore_policy,
+            pinned,
+            room_id: origin.room_id.clone(),
+            kind: origin.media_kind(request),
+            content_type: origin.content_type.clone(),
             last_access,
         });
 
@@ -380,6 +506,22 @@ impl EventCacheStoreMedia for MemoryStore {
         Ok(())
     }
 
+    async fn set_media_pinned_inner(
+        &self,
+        request: &MediaRequestParameters,
+        pinned: bool,
+    ) -> Result<(), Self::Error> {
+        let mut inner = self.inner.write().unwrap();
+        let expected_key = request.unique_key();
+
+        if let Some(media_content) = inner.media.iter_mut().find(|media| media.key == expected_key)
+        {
+            media_content.pinned = pinned;
+        }
+
+        Ok(())
+    }
+
     async fn get_media_content_inner(
         &self,
         request: &MediaRequestParameters,
@@ -440,75 +582,79 @@ impl EventCacheStoreMedia for MemoryStore {
     async fn clean_up_media_cache_inner(
         &self,
         policy: MediaRetentionPolicy,
+        scoped_policies: &ScopedMediaRetentionPolicies,
         current_time: SystemTime,
     ) -> Result<(), Self::Error> {
-        if !policy.has_limitations() {
+        if !policy.has_limitations() && !scoped_policies.has_limitations() {
             // We can safely skip all the checks.
             return Ok(());
         }
 
         let mut inner = self.inner.write().unwrap();
 
-        // First, check media content that exceed the max filesize.
-        if policy.computed_max_file_size().is_some() {
-            inner.media.retain(|content| {
-                content.ignore_policy || !policy.exceeds_max_file_size(content.data.len())
-            });
-        }
+        // The policy that applies to the given content.
+        let policy_for = |content: &MediaContent| {
+            scoped_policies.policy_for(content.room_id.as_deref(), Some(content.kind), policy)
+        };
 
-        // Then, clean up expired media content.
-        if policy.last_access_expiry.is_some() {
-            inner.media.retain(|content| {
-                content.ignore_policy
-                    || !policy.has_content_expired(current_time, content.last_access)
-            });
-        }
+        // First, check media content that exceed the max filesize, and clean up expired
+        // media content.
+        inner.media.retain(|content| {
+            if content.ignore_policy || content.pinned {
+                return true;
+            }
 
-        // Finally, if the cache size is too big, remove old items until it fits.
-        if let Some(max_cache_size) = policy.max_cache_size {
-            // Reverse the iterator because in case the cache size is overflowing, we want
-            // to count the number of old items to remove. Items are sorted by last access
-            // and old items are at the start.
-            let (_, items_to_remove) = inner.media.iter().enumerate().rev().fold(
-                (0usize, Vec::with_capacity(NUMBER_OF_MEDIAS.into())),
-                |(mut cache_size, mut items_to_remove), (index, content)| {
-                    if content.ignore_policy {
-                        // Do not count it.
-                        return (cache_size, items_to_remove);
-                    }
-
-                    let remove_item = if items_to_remove.is_empty() {
-                        // We have not reached the max cache size yet.
-                        if let Some(sum) = cache_size.checked_add(content.data.len()) {
-                            cache_size = sum;
-                            // Start removing items if we have exceeded the max cache size.
-                            cache_size > max_cache_size
-                        } else {
-                            // The cache size is overflowing, remove the remaining items, since the
-                            // max cache size cannot be bigger than
-                            // usize::MAX.
-                            true
-                        }
-                    } else {
-                        // We have reached the max cache size already, just remove it.
-                        true
-                    };
-
-                    if remove_item {
-                        items_to_remove.push(index);
-                    }
-
-                    (cache_size, items_to_remove)
-                },
-            );
-
-            // The indexes are already in reverse order so we can just iterate in that order
-            // to remove them starting by the end.
-            for index in items_to_remove {
-                inner.media.remove(index);
+            let policy = policy_for(content);
+            !policy.exceeds_max_file_size(content.data.len())
+                && !policy.has_content_expired(current_time, content.last_access)
+        });
+
+        // Finally, if the cache size of a scope is too big, remove old items of the
+        // scope until it fits.
+        //
+        // Reverse the iterator because in case the cache size is overflowing, we want
+        // to count the number of old items to remove. Items are sorted by last access
+        // and old items are at the start.
+        //
+        // The cache size of a scope is `None` once its max cache size has been
+        // exceeded.
+        let mut cache_sizes = HashMap::<Option<MediaRetentionScope>, Option<usize>>::new();
+        let mut items_to_remove = Vec::with_capacity(NUMBER_OF_MEDIAS.into());
+
+        for (index, content) in inner.media.iter().enumerate().rev() {
+            if content.ignore_policy || content.pinned {
+                // Do not count it.
+                continue;
+            }
+
+            let Some(max_cache_size) = policy_for(content).max_cache_size else {
+                continue;
+            };
+
+            let scope = scoped_policies.scope_of(content.room_id.as_deref(), Some(content.kind));
+            let cache_size = cache_sizes.entry(scope).or_insert(Some(0));
+
+            match cache_size.and_then(|size| size.checked_add(content.data.len())) {
+                Some(size) if size <= max_cache_size => {
+                    // We have not reached the max cache size yet.
+                    *cache_size = Some(size);
+                }
+                _ => {
+                    // We have exceeded the max cache size, or the cache size is overflowing
+                    // since the max cache size cannot be bigger than usize::MAX. Remove this
+                    // item and all the older items of the scope.
+                    *cache_size = None;
+                    items_to_remove.push(index);
+                }
             }
         }
 
+        // The indexes are already in reverse order so we can just iterate in that order
+        // to remove them starting by the end.
+        for index in items_to_remove {
+            inner.media.remove(index);
+        }
+
         inner.last_media_cleanup_time = current_time;
 
         Ok(())
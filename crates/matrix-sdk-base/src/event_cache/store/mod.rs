@@ -26,6 +26,7 @@ use std::{fmt, ops::Deref, str::Utf8Error, sync::Arc};
 pub mod integration_tests;
 pub mod media;
 mod memory_store;
+pub mod search;
 mod traits;
 
 use matrix_sdk_common::store_locks::{
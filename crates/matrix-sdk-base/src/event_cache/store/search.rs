@@ -0,0 +1,108 @@
+// Copyright 2025 The Matrix.org Foundation C.I.C.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Helpers for the local full-text search over events, shared by the
+//! [`EventCacheStore`](super::EventCacheStore) implementations, so they all
+//! agree on what is being indexed and how.
+
+use std::collections::BTreeSet;
+
+use ruma::MilliSecondsSinceUnixEpoch;
+use serde::Deserialize;
+
+use crate::event_cache::Event;
+
+/// Split a text into normalized search tokens.
+///
+/// Tokens are the sequences of alphanumeric characters of the text, in
+/// lowercase. Duplicates are removed.
+pub fn tokenize(text: &str) -> BTreeSet<String> {
+    text.split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(str::to_lowercase)
+        .collect()
+}
+
+/// Extract the search tokens of an event.
+///
+/// Only the `body` of the event's content is indexed, which covers all the
+/// message types. Events without a body (state events, reactions,
+/// undecryptable events, etc.) have no tokens.
+pub fn event_search_tokens(event: &Event) -> BTreeSet<String> {
+    #[derive(Deserialize)]
+    struct ContentProbe {
+        body: Option<String>,
+    }
+
+    #[derive(Deserialize)]
+    struct EventProbe {
+        content: Option<ContentProbe>,
+    }
+
+    event
+        .raw()
+        .deserialize_as::<EventProbe>()
+        .ok()
+        .and_then(|probe| probe.content?.body)
+        .map(|body| tokenize(&body))
+        .unwrap_or_default()
+}
+
+/// Get the `origin_server_ts` of an event, used to sort the search results
+/// from the most recent to the oldest.
+pub fn event_timestamp(event: &Event) -> Option<MilliSecondsSinceUnixEpoch> {
+    event.raw().get_field("origin_server_ts").ok().flatten()
+}
+
+/// Whether an event matches a search query, i.e. whether it contains all the
+/// tokens of the query.
+///
+/// A query without any token doesn't match anything.
+pub fn event_matches_query(event: &Event, query: &str) -> bool {
+    let query_tokens = tokenize(query);
+
+    !query_tokens.is_empty() && query_tokens.is_subset(&event_search_tokens(event))
+}
+
+#[cfg(test)]
+mod tests {
+    use matrix_sdk_test::{event_factory::EventFactory, ALICE};
+    use ruma::room_id;
+
+    use super::{event_matches_query, event_search_tokens, tokenize};
+
+    #[test]
+    fn test_tokenize() {
+        assert_eq!(
+            tokenize("Let's have LUNCH, at 12:30?").into_iter().collect::<Vec<_>>(),
+            ["12", "30", "at", "have", "let", "lunch", "s"]
+        );
+        assert!(tokenize("  ?! ").is_empty());
+    }
+
+    #[test]
+    fn test_event_matches_query() {
+        let f = EventFactory::new().room(room_id!("!r:b.c")).sender(*ALICE);
+        let event = f.text_msg("Fancy some raclette tonight?").into_event();
+
+        assert!(event_matches_query(&event, "raclette"));
+        assert!(event_matches_query(&event, "TONIGHT raclette"));
+        assert!(!event_matches_query(&event, "raclette fondue"));
+        assert!(!event_matches_query(&event, "racl"));
+        assert!(!event_matches_query(&event, ""));
+
+        let reaction = f.reaction(ruma::event_id!("$1"), "👍").into_event();
+        assert!(event_search_tokens(&reaction).is_empty());
+    }
+}
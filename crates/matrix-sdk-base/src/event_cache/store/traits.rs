@@ -22,7 +22,10 @@ use matrix_sdk_common::{
 use ruma::{EventId, MxcUri, OwnedEventId, RoomId};
 
 use super::{
-    media::{IgnoreMediaRetentionPolicy, MediaRetentionPolicy},
+    media::{
+        IgnoreMediaRetentionPolicy, MediaCacheEntry, MediaCacheUsage, MediaOrigin,
+        MediaRetentionPolicy, MediaRetentionScope, ScopedMediaRetentionPolicies,
+    },
     EventCacheStoreError,
 };
 use crate::{
@@ -116,6 +119,20 @@ pub trait EventCacheStore: AsyncTraitDeps {
         event_id: &EventId,
     ) -> Result<Option<(Position, Event)>, Self::Error>;
 
+    /// Search the events of a room containing all the words of the given
+    /// query, using a local full-text index.
+    ///
+    /// Words are matched exactly, regardless of their case, in the body of the
+    /// events; see the [`search`](super::search) module for the details.
+    /// Results are sorted from the most recent event to the oldest one, and at
+    /// most `limit` events are returned.
+    async fn search_events(
+        &self,
+        room_id: &RoomId,
+        query: &str,
+        limit: usize,
+    ) -> Result<Vec<Event>, Self::Error>;
+
     /// Add a media file's content in the media store.
     ///
     /// # Arguments
@@ -130,6 +147,26 @@ pub trait EventCacheStore: AsyncTraitDeps {
         ignore_policy: IgnoreMediaRetentionPolicy,
     ) -> Result<(), Self::Error>;
 
+    /// Add a media file's content in the media store, with its origin.
+    ///
+    /// The origin is used to find the scoped `MediaRetentionPolicy` that
+    /// applies to the media.
+    ///
+    /// # Arguments
+    ///
+    /// * `request` - The `MediaRequest` of the file.
+    ///
+    /// * `content` - The content of the file.
+    ///
+    /// * `origin` - Where the media comes from.
+    async fn add_media_content_with_origin(
+        &self,
+        request: &MediaRequestParameters,
+        content: Vec<u8>,
+        origin: &MediaOrigin,
+        ignore_policy: IgnoreMediaRetentionPolicy,
+    ) -> Result<(), Self::Error>;
+
     /// Replaces the given media's content key with another one.
     ///
     /// This should be used whenever a temporary (local) MXID has been used, and
@@ -203,6 +240,34 @@ pub trait EventCacheStore: AsyncTraitDeps {
     /// * `uri` - The `MxcUri` of the media files.
     async fn remove_media_content_for_uri(&self, uri: &MxcUri) -> Result<(), Self::Error>;
 
+    /// Remove all the media files' content that were found in the given room
+    /// from the media store.
+    ///
+    /// Only the media content that was added with its room, with
+    /// [`EventCacheStore::add_media_content_with_origin()`], is removed.
+    ///
+    /// # Arguments
+    ///
+    /// * `room_id` - The ID of the room.
+    async fn remove_media_content_for_room(&self, room_id: &RoomId) -> Result<(), Self::Error>;
+
+    /// Get the list of all the media contents in the media store.
+    ///
+    /// This doesn't update the last access time of the media contents.
+    async fn media_cache_entries(&self) -> Result<Vec<MediaCacheEntry>, Self::Error>;
+
+    /// Get the usage of the media store, as the size and number of media
+    /// contents, in total, per room, per kind of media and per MIME type.
+    ///
+    /// This doesn't update the last access time of the media contents.
+    ///
+    /// The default implementation computes it from
+    /// [`EventCacheStore::media_cache_entries()`], stores should compute it
+    /// more efficiently if they can.
+    async fn media_cache_usage(&self) -> Result<MediaCacheUsage, Self::Error> {
+        Ok(MediaCacheUsage::from_entries(&self.media_cache_entries().await?))
+    }
+
     /// Set the `MediaRetentionPolicy` to use for deciding whether to store or
     /// keep media content.
     ///
@@ -217,6 +282,24 @@ pub trait EventCacheStore: AsyncTraitDeps {
     /// Get the current `MediaRetentionPolicy`.
     fn media_retention_policy(&self) -> MediaRetentionPolicy;
 
+    /// Set or remove the `MediaRetentionPolicy` of a subset of the media
+    /// cache, that takes precedence over the global one.
+    ///
+    /// # Arguments
+    ///
+    /// * `scope` - The scope of the policy.
+    ///
+    /// * `policy` - The `MediaRetentionPolicy` to use for the scope, or `None`
+    ///   to remove the policy of the scope.
+    async fn set_scoped_media_retention_policy(
+        &self,
+        scope: MediaRetentionScope,
+        policy: Option<MediaRetentionPolicy>,
+    ) -> Result<(), Self::Error>;
+
+    /// Get the current `ScopedMediaRetentionPolicies`.
+    fn scoped_media_retention_policies(&self) -> ScopedMediaRetentionPolicies;
+
     /// Set whether the current [`MediaRetentionPolicy`] should be ignored for
     /// the media.
     ///
@@ -234,6 +317,25 @@ pub trait EventCacheStore: AsyncTraitDeps {
         ignore_policy: IgnoreMediaRetentionPolicy,
     ) -> Result<(), Self::Error>;
 
+    /// Set whether the media is pinned in the media cache.
+    ///
+    /// Pinned media content is kept regardless of the [`MediaRetentionPolicy`],
+    /// like media content that ignores it, but this flag is only changed by
+    /// this method. Adding the media content again doesn't reset it.
+    ///
+    /// If the media of the given request is not found, this is a noop.
+    ///
+    /// # Arguments
+    ///
+    /// * `request` - The `MediaRequestParameters` of the file.
+    ///
+    /// * `pinned` - Whether the media should be pinned.
+    async fn set_media_pinned(
+        &self,
+        request: &MediaRequestParameters,
+        pinned: bool,
+    ) -> Result<(), Self::Error>;
+
     /// Clean up the media cache with the current `MediaRetentionPolicy`.
     ///
     /// If there is already an ongoing cleanup, this is a noop.
@@ -314,6 +416,15 @@ impl<T: EventCacheStore> EventCacheStore for EraseEventCacheStoreError<T> {
         self.0.find_event(room_id, event_id).await.map_err(Into::into)
     }
 
+    async fn search_events(
+        &self,
+        room_id: &RoomId,
+        query: &str,
+        limit: usize,
+    ) -> Result<Vec<Event>, Self::Error> {
+        self.0.search_events(room_id, query, limit).await.map_err(Into::into)
+    }
+
     async fn add_media_content(
         &self,
         request: &MediaRequestParameters,
@@ -323,6 +434,19 @@ impl<T: EventCacheStore> EventCacheStore for EraseEventCacheStoreError<T> {
         self.0.add_media_content(request, content, ignore_policy).await.map_err(Into::into)
     }
 
+    async fn add_media_content_with_origin(
+        &self,
+        request: &MediaRequestParameters,
+        content: Vec<u8>,
+        origin: &MediaOrigin,
+        ignore_policy: IgnoreMediaRetentionPolicy,
+    ) -> Result<(), Self::Error> {
+        self.0
+            .add_media_content_with_origin(request, content, origin, ignore_policy)
+            .await
+            .map_err(Into::into)
+    }
+
     async fn replace_media_key(
         &self,
         from: &MediaRequestParameters,
@@ -356,6 +480,18 @@ impl<T: EventCacheStore> EventCacheStore for EraseEventCacheStoreError<T> {
         self.0.remove_media_content_for_uri(uri).await.map_err(Into::into)
     }
 
+    async fn remove_media_content_for_room(&self, room_id: &RoomId) -> Result<(), Self::Error> {
+        self.0.remove_media_content_for_room(room_id).await.map_err(Into::into)
+    }
+
+    async fn media_cache_entries(&self) -> Result<Vec<MediaCacheEntry>, Self::Error> {
+        self.0.media_cache_entries().await.map_err(Into::into)
+    }
+
+    async fn media_cache_usage(&self) -> Result<MediaCacheUsage, Self::Error> {
+        self.0.media_cache_usage().await.map_err(Into::into)
+    }
+
     async fn set_media_retention_policy(
         &self,
         policy: MediaRetentionPolicy,
@@ -367,6 +503,18 @@ impl<T: EventCacheStore> EventCacheStore for EraseEventCacheStoreError<T> {
         self.0.media_retention_policy()
     }
 
+    async fn set_scoped_media_retention_policy(
+        &self,
+        scope: MediaRetentionScope,
+        policy: Option<MediaRetentionPolicy>,
+    ) -> Result<(), Self::Error> {
+        self.0.set_scoped_media_retention_policy(scope, policy).await.map_err(Into::into)
+    }
+
+    fn scoped_media_retention_policies(&self) -> ScopedMediaRetentionPolicies {
+        self.0.scoped_media_retention_policies()
+    }
+
     async fn set_ignore_media_retention_policy(
         &self,
         request: &MediaRequestParameters,
@@ -375,6 +523,14 @@ impl<T: EventCacheStore> EventCacheStore for EraseEventCacheStoreError<T> {
         self.0.set_ignore_media_retention_policy(request, ignore_policy).await.map_err(Into::into)
     }
 
+    async fn set_media_pinned(
+        &self,
+        request: &MediaRequestParameters,
+        pinned: bool,
+    ) -> Result<(), Self::Error> {
+        self.0.set_media_pinned(request, pinned).await.map_err(Into::into)
+    }
+
     async fn clean_up_media_cache(&self) -> Result<(), Self::Error> {
         self.0.clean_up_media_cache().await.map_err(Into::into)
     }
@@ -115,6 +115,25 @@
 //!   timeline, leading to incorrect results. We have to take that into account
 //!   by resetting the read counts *every* time we see an event that was the
 //!   target of the latest active read receipt.
+//!
+//! ## Threads
+//!
+//! The counts are also split between the main timeline, and each thread of the
+//! room: an event is part of a thread if it has a `m.thread` relation to a
+//! thread root (the thread root itself being part of the main timeline). The
+//! room-wide counts are always the sum of the main timeline counts and of all
+//! the threads counts.
+//!
+//! The latest main-threaded or unthreaded receipt (as described above) marks
+//! all the events before it as read, including those in threads. In addition,
+//! each thread may have its own latest threaded receipt, received from sync or
+//! implied by an event sent by the current user in the thread, which marks
+//! the events of that thread before it as read.
+//!
+//! When counting events, we reset the counts of a thread every time we see the
+//! event targeted by its latest threaded receipt. When a new threaded receipt
+//! comes in, the counts of its thread are computed again from the events we
+//! know about, if we know about the event it's targeting.
 #![allow(dead_code)] // too many different build configurations, I give up
 
 use std::{
@@ -128,12 +147,13 @@ use ruma::{
     events::{
         poll::{start::PollStartEventContent, unstable_start::UnstablePollStartEventContent},
         receipt::{ReceiptEventContent, ReceiptThread, ReceiptType},
+        relation::RelationType,
         room::message::Relation,
         AnySyncMessageLikeEvent, AnySyncTimelineEvent, OriginalSyncMessageLikeEvent,
         SyncMessageLikeEvent,
     },
     serde::Raw,
-    EventId, OwnedEventId, OwnedUserId, RoomId, UserId,
+    EventId, MilliSecondsSinceUnixEpoch, OwnedEventId, OwnedUserId, RoomId, UserId,
 };
 use serde::{Deserialize, Serialize};
 use tracing::{debug, instrument, trace, warn};
@@ -145,6 +165,74 @@ struct LatestReadReceipt {
     event_id: OwnedEventId,
 }
 
+/// Unread counts for a part of a room, i.e. its main timeline or one of its
+/// threads.
+#[derive(Clone, Copy, Debug, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub struct UnreadCounts {
+    /// The number of unread messages.
+    pub num_unread: u64,
+
+    /// The number of unread events that should notify.
+    pub num_notifications: u64,
+
+    /// The number of unread messages causing highlights for the users (aka
+    /// mentions).
+    pub num_mentions: u64,
+}
+
+impl UnreadCounts {
+    /// Compute the counts a single event adds to the unread counts.
+    fn for_event(event: &TimelineEvent, user_id: &UserId) -> Self {
+        let mut counts = Self::default();
+
+        if marks_as_unread(event.raw(), user_id) {
+            counts.num_unread = 1;
+        }
+
+        let Some(actions) = event.push_actions.as_ref() else {
+            return counts;
+        };
+
+        for action in actions.iter() {
+            if action.should_notify() {
+                counts.num_notifications = 1;
+            }
+            if action.is_highlight() {
+                counts.num_mentions = 1;
+            }
+        }
+
+        counts
+    }
+
+    /// Is there nothing unread?
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+
+    fn add(&mut self, other: Self) {
+        self.num_unread += other.num_unread;
+        self.num_notifications += other.num_notifications;
+        self.num_mentions += other.num_mentions;
+    }
+}
+
+/// Read receipts data for a single thread of a room.
+#[derive(Clone, Debug, Default, Serialize, Deserialize, PartialEq)]
+struct ThreadReadReceipts {
+    /// The unread counts of the thread.
+    counts: UnreadCounts,
+
+    /// The latest threaded read receipt known for this thread, be it
+    /// explicit, or implicit because the current user sent an event in this
+    /// thread.
+    latest_active: Option<LatestReadReceipt>,
+
+    /// The timestamp of the latest event known in this thread.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    latest_event_ts: Option<MilliSecondsSinceUnixEpoch>,
+}
+
 /// Public data about read receipts collected during processing of that room.
 ///
 /// Remember that each time a field of `RoomReadReceipts` is updated in
@@ -152,15 +240,34 @@ struct LatestReadReceipt {
 #[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
 pub struct RoomReadReceipts {
     /// Does the room have unread messages?
+    ///
+    /// This includes the unread messages of the main timeline and of all the
+    /// threads.
     pub num_unread: u64,
 
     /// Does the room have unread events that should notify?
+    ///
+    /// This includes the unread events of the main timeline and of all the
+    /// threads.
     pub num_notifications: u64,
 
     /// Does the room have messages causing highlights for the users? (aka
     /// mentions)
+    ///
+    /// This includes the unread mentions of the main timeline and of all the
+    /// threads.
     pub num_mentions: u64,
 
+    /// The unread counts of the main timeline only, i.e. excluding the events
+    /// in threads.
+    #[serde(default)]
+    pub main_timeline: UnreadCounts,
+
+    /// The read receipts data of the threads of the room, mapped by the event
+    /// ID of their thread root.
+    #[serde(default)]
+    threads: BTreeMap<OwnedEventId, ThreadReadReceipts>,
+
     /// The latest read receipt (main-threaded or unthreaded) known for the
     /// room.
     #[serde(default)]
@@ -184,12 +291,20 @@ impl Default for RoomReadReceipts {
             num_unread: Default::default(),
             num_notifications: Default::default(),
             num_mentions: Default::default(),
+            main_timeline: Default::default(),
+            threads: Default::default(),
             latest_active: Default::default(),
             pending: new_nonempty_ring_buffer(),
         }
     }
 }
 
+/// The maximum number of threads whose read receipts data is kept for a room.
+///
+/// When there are more, the threads without unread events are forgotten first,
+/// then the threads whose latest event is the oldest.
+const MAX_TRACKED_THREADS: usize = 100;
+
 fn new_nonempty_ring_buffer() -> RingBuffer<OwnedEventId> {
     // 10 pending read receipts per room should be enough for everyone.
     // SAFETY: `unwrap` is safe because 10 is not zero.
@@ -197,33 +312,57 @@ fn new_nonempty_ring_buffer() -> RingBuffer<OwnedEventId> {
 }
 
 impl RoomReadReceipts {
+    /// Get the unread counts of the thread starting at the given thread root.
+    ///
+    /// Returns empty counts if nothing is known about this thread.
+    pub fn thread_counts(&self, thread_root: &EventId) -> UnreadCounts {
+        self.threads.get(thread_root).map(|thread| thread.counts).unwrap_or_default()
+    }
+
+    /// Get the unread counts of all the threads known to have unread events,
+    /// along with the event ID of their thread root.
+    pub fn threads_with_unread(&self) -> impl Iterator<Item = (&EventId, UnreadCounts)> + '_ {
+        self.threads
+            .iter()
+            .filter(|(_, thread)| !thread.counts.is_empty())
+            .map(|(thread_root, thread)| (&**thread_root, thread.counts))
+    }
+
+    /// Get the unread counts of all the threads of the room, summed up.
+    pub fn all_threads_counts(&self) -> UnreadCounts {
+        let mut counts = UnreadCounts::default();
+        for thread in self.threads.values() {
+            counts.add(thread.counts);
+        }
+        counts
+    }
+
     /// Update the [`RoomReadReceipts`] unread counts according to the new
     /// event.
-    ///
-    /// Returns whether a new event triggered a new unread/notification/mention.
     #[inline(always)]
     fn process_event(&mut self, event: &TimelineEvent, user_id: &UserId) {
-        if marks_as_unread(event.raw(), user_id) {
-            self.num_unread += 1;
+        let thread_root = thread_root(event);
+
+        if let Some(event_id) = event.event_id() {
+            // If this event is the target of a threaded receipt, all the events in that
+            // thread up to this one have been read.
+            if let Some(read_thread_root) = self.reset_thread_at(&event_id) {
+                if thread_root.as_ref() == Some(&read_thread_root) {
+                    return;
+                }
+            }
         }
 
-        let mut has_notify = false;
-        let mut has_mention = false;
-
-        let Some(actions) = event.push_actions.as_ref() else {
-            return;
-        };
+        let counts = UnreadCounts::for_event(event, user_id);
 
-        for action in actions.iter() {
-            if !has_notify && action.should_notify() {
-                self.num_notifications += 1;
-                has_notify = true;
-            }
-            if !has_mention && action.is_highlight() {
-                self.num_mentions += 1;
-                has_mention = true;
-            }
+        match thread_root {
+            Some(thread_root) => self.threads.entry(thread_root).or_default().counts.add(counts),
+            None => self.main_timeline.add(counts),
         }
+
+        self.num_unread += counts.num_unread;
+        self.num_notifications += counts.num_notifications;
+        self.num_mentions += counts.num_mentions;
     }
 
     #[inline(always)]
@@ -231,10 +370,142 @@ impl RoomReadReceipts {
         self.num_unread = 0;
         self.num_notifications = 0;
         self.num_mentions = 0;
+        self.main_timeline = UnreadCounts::default();
+
+        for thread in self.threads.values_mut() {
+            thread.counts = UnreadCounts::default();
+        }
+    }
+
+    /// Replace the unread counts of a single thread, keeping the room-wide
+    /// counts in sync.
+    fn set_thread_counts(&mut self, thread_root: &EventId, counts: UnreadCounts) {
+        let thread = self.threads.entry(thread_root.to_owned()).or_default();
+        let previous = std::mem::replace(&mut thread.counts, counts);
+
+        self.num_unread = (self.num_unread + counts.num_unread).saturating_sub(previous.num_unread);
+        self.num_notifications = (self.num_notifications + counts.num_notifications)
+            .saturating_sub(previous.num_notifications);
+        self.num_mentions =
+            (self.num_mentions + counts.num_mentions).saturating_sub(previous.num_mentions);
+    }
+
+    /// Reset the counts of the thread whose latest threaded receipt targets
+    /// the given event, if any.
+    ///
+    /// Returns the event ID of the root of that thread.
+    fn reset_thread_at(&mut self, event_id: &EventId) -> Option<OwnedEventId> {
+        let thread_root = self
+            .threads
+            .iter()
+            .find(|(_, thread)| {
+                thread.latest_active.as_ref().is_some_and(|receipt| receipt.event_id == event_id)
+            })
+            .map(|(thread_root, _)| thread_root.clone())?;
+
+        trace!(%thread_root, "Found the event a threaded receipt was referring to.");
+        self.set_thread_counts(&thread_root, UnreadCounts::default());
+
+        Some(thread_root)
+    }
+
+    /// Save the new threaded receipts from a receipt event, and the implicit
+    /// threaded receipts for the events the current user sent in threads.
+    ///
+    /// Returns the event IDs of the roots of the threads that got a new
+    /// receipt.
+    fn handle_new_thread_receipts(
+        &mut self,
+        user_id: &UserId,
+        receipt_event: Option<&ReceiptEventContent>,
+        new_events: &[TimelineEvent],
+    ) -> BTreeSet<OwnedEventId> {
+        let mut updated_threads = BTreeSet::new();
+
+        for (event_id, receipts) in receipt_event.iter().flat_map(|content| content.0.iter()) {
+            for ty in [ReceiptType::Read, ReceiptType::ReadPrivate] {
+                let Some(receipt) = receipts.get(&ty).and_then(|receipts| receipts.get(user_id))
+                else {
+                    continue;
+                };
+
+                if let ReceiptThread::Thread(thread_root) = &receipt.thread {
+                    trace!(%event_id, %thread_root, "found new threaded receipt");
+                    self.threads.entry(thread_root.clone()).or_default().latest_active =
+                        Some(LatestReadReceipt { event_id: event_id.clone() });
+                    updated_threads.insert(thread_root.clone());
+                }
+            }
+        }
+
+        for event in new_events {
+            let Ok(Some(sender)) = event.raw().get_field::<OwnedUserId>("sender") else {
+                continue;
+            };
+
+            if sender != user_id {
+                continue;
+            }
+
+            if let (Some(thread_root), Some(event_id)) = (thread_root(event), event.event_id()) {
+                trace!(%event_id, %thread_root, "found an implicit threaded receipt");
+                self.threads.entry(thread_root.clone()).or_default().latest_active =
+                    Some(LatestReadReceipt { event_id });
+                updated_threads.insert(thread_root);
+            }
+        }
+
+        updated_threads
+    }
+
+    /// Compute again the counts of a thread, from the known events.
+    ///
+    /// The counts are only updated if we know about the event targeted by the
+    /// latest active receipt, or by the latest threaded receipt of the thread;
+    /// otherwise, we can't know which events of the thread have been read.
+    fn recompute_thread_counts(
+        &mut self,
+        root: &EventId,
+        user_id: &UserId,
+        all_events: &Vector<TimelineEvent>,
+    ) {
+        let main_receipt = self.latest_active.as_ref().map(|receipt| &*receipt.event_id);
+        let thread_receipt = self
+            .threads
+            .get(root)
+            .and_then(|thread| thread.latest_active.as_ref())
+            .map(|receipt| &*receipt.event_id);
+
+        let mut counts = UnreadCounts::default();
+        let mut found_receipt = false;
+
+        for event in all_events {
+            let is_receipt_target = event.event_id().is_some_and(|event_id| {
+                Some(&*event_id) == main_receipt || Some(&*event_id) == thread_receipt
+            });
+
+            if is_receipt_target {
+                // Everything in the thread up to this event has been read.
+                counts = UnreadCounts::default();
+                found_receipt = true;
+                continue;
+            }
+
+            if thread_root(event).as_deref() == Some(root) {
+                counts.add(UnreadCounts::for_event(event, user_id));
+            }
+        }
+
+        if found_receipt {
+            self.set_thread_counts(root, counts);
+        }
     }
 
     /// Try to find the event to which the receipt attaches to, and if found,
     /// will update the notification count in the room.
+    ///
+    /// The threaded receipts that target events before it are forgotten, since
+    /// the events of their threads are all read now.
     #[instrument(skip_all)]
     fn find_and_process_events<'a>(
         &mut self,
@@ -242,6 +513,15 @@ impl RoomReadReceipts {
         user_id: &UserId,
         events: impl IntoIterator<Item = &'a TimelineEvent>,
     ) -> bool {
+        let thread_receipt_targets: BTreeSet<_> = self
+            .threads
+            .values()
+            .filter_map(|thread| thread.latest_active.as_ref())
+            .map(|receipt| receipt.event_id.clone())
+            .collect();
+        let mut seen_thread_receipt_targets = BTreeSet::new();
+        let mut superseded_thread_receipts = BTreeSet::new();
+
         let mut counting_receipts = false;
 
         for event in events {
@@ -255,8 +535,13 @@ impl RoomReadReceipts {
                     trace!("Found the event the receipt was referring to! Starting to count.");
                     self.reset();
                     counting_receipts = true;
+                    superseded_thread_receipts.append(&mut seen_thread_receipt_targets);
                     continue;
                 }
+
+                if thread_receipt_targets.contains(&event_id) {
+                    seen_thread_receipt_targets.insert(event_id);
+                }
             }
 
             if counting_receipts {
@@ -264,8 +549,68 @@ impl RoomReadReceipts {
             }
         }
 
+        for thread in self.threads.values_mut() {
+            if thread
+                .latest_active
+                .as_ref()
+                .is_some_and(|receipt| superseded_thread_receipts.contains(&receipt.event_id))
+            {
+                thread.latest_active = None;
+            }
+        }
+
         counting_receipts
     }
+
+    /// Remember the timestamp of the latest event of the known threads.
+    fn update_threads_latest_event(&mut self, new_events: &[TimelineEvent]) {
+        for event in new_events {
+            let Some(thread) = thread_root(event).and_then(|root| self.threads.get_mut(&root))
+            else {
+                continue;
+            };
+
+            let Ok(Some(ts)) =
+                event.raw().get_field::<MilliSecondsSinceUnixEpoch>("origin_server_ts")
+            else {
+                continue;
+            };
+
+            thread.latest_event_ts = thread.latest_event_ts.max(Some(ts));
+        }
+    }
+
+    /// Forget the threads that don't hold any information anymore, and the
+    /// least relevant threads if there are too many of them.
+    ///
+    /// The threads without unread events are forgotten first, then the ones
+    /// whose latest event is the oldest. The unread counts of the forgotten
+    /// threads are removed from the room-wide counts.
+    fn prune_threads(&mut self) {
+        self.threads
+            .retain(|_, thread| !thread.counts.is_empty() || thread.latest_active.is_some());
+
+        let excess = self.threads.len().saturating_sub(MAX_TRACKED_THREADS);
+        if excess == 0 {
+            return;
+        }
+
+        trace!(excess, "Too many threads, forgetting the least relevant ones");
+
+        let mut threads: Vec<_> = self
+            .threads
+            .iter()
+            .map(|(thread_root, thread)| {
+                (!thread.counts.is_empty(), thread.latest_event_ts, thread_root.clone())
+            })
+            .collect();
+        threads.sort();
+
+        for (_, _, thread_root) in threads.into_iter().take(excess) {
+            self.set_thread_counts(&thread_root, UnreadCounts::default());
+            self.threads.remove(&thread_root);
+        }
+    }
 }
 
 /// Provider for timeline events prior to the current sync.
@@ -407,12 +752,15 @@ impl ReceiptSelector {
 
     /// Try to match an implicit receipt, that is, the one we get for events we
     /// sent ourselves.
+    ///
+    /// Events sent in threads are implicit threaded receipts, so they're not
+    /// considered here.
     #[instrument(skip_all)]
     fn try_match_implicit(&mut self, user_id: &UserId, new_events: &[TimelineEvent]) {
         for ev in new_events {
             // Get the `sender` field, if any, or skip this event.
             let Ok(Some(sender)) = ev.raw().get_field::<OwnedUserId>("sender") else { continue };
-            if sender == user_id {
+            if sender == user_id && thread_root(ev).is_none() {
                 // Get the event id, if any, or skip this event.
                 let Some(event_id) = ev.event_id() else { continue };
                 if let Some(event_pos) = self.event_id_to_pos.get(&event_id) {
@@ -463,6 +811,9 @@ pub(crate) fn compute_unread_counts(
 ) {
     debug!(?read_receipts, "Starting.");
 
+    let updated_threads =
+        read_receipts.handle_new_thread_receipts(user_id, receipt_event, new_events);
+
     let all_events = if events_intersects(previous_events.iter(), new_events) {
         // The previous and new events sets can intersect, for instance if we restored
         // previous events from the disk cache, or a timeline was limited. This
@@ -509,21 +860,54 @@ pub(crate) fn compute_unread_counts(
         read_receipts.find_and_process_events(&event_id, user_id, all_events.iter());
 
         debug!(?read_receipts, "after finding a better receipt");
-        return;
+    } else {
+        // We don't have any new "active" read receipt. So either there was a previous
+        // one further in the past, or none.
+        //
+        // In that case, accumulate all events as part of the current batch, and wait
+        // for the next receipt.
+
+        for event in new_events {
+            read_receipts.process_event(event, user_id);
+        }
+
+        debug!(?read_receipts, "no better receipt, {} new events", new_events.len());
     }
 
-    // If we haven't returned at this point, it means we don't have any new "active"
-    // read receipt. So either there was a previous one further in the past, or
-    // none.
-    //
-    // In that case, accumulate all events as part of the current batch, and wait
-    // for the next receipt.
+    // The threads which got a new threaded receipt may have had events before it
+    // that were counted as unread; count them again.
+    for thread_root in updated_threads {
+        read_receipts.recompute_thread_counts(&thread_root, user_id, &all_events);
+    }
 
-    for event in new_events {
-        read_receipts.process_event(event, user_id);
+    read_receipts.update_threads_latest_event(new_events);
+    read_receipts.prune_threads();
+}
+
+/// Returns the event ID of the thread root, if the event is part of a thread.
+///
+/// The thread root itself isn't considered to be part of its thread, but of
+/// the main timeline.
+fn thread_root(event: &TimelineEvent) -> Option<OwnedEventId> {
+    #[derive(Deserialize)]
+    struct RelatesTo {
+        rel_type: Option<RelationType>,
+        event_id: Option<OwnedEventId>,
     }
 
-    debug!(?read_receipts, "no better receipt, {} new events", new_events.len());
+    #[derive(Deserialize)]
+    struct Content {
+        #[serde(rename = "m.relates_to")]
+        relates_to: Option<RelatesTo>,
+    }
+
+    let relates_to = event.raw().get_field::<Content>("content").ok().flatten()?.relates_to?;
+
+    if relates_to.rel_type == Some(RelationType::Thread) {
+        relates_to.event_id
+    } else {
+        None
+    }
 }
 
 /// Is the event worth marking a room as unread?
@@ -636,7 +1020,7 @@ mod tests {
         room_id, user_id, EventId, UserId,
     };
 
-    use super::compute_unread_counts;
+    use super::{compute_unread_counts, MAX_TRACKED_THREADS};
     use crate::read_receipts::{marks_as_unread, ReceiptSelector, RoomReadReceipts};
 
     #[test]
@@ -1565,4 +1949,266 @@ mod tests {
         // And the active receipt is the implicit one on my event.
         assert_eq!(read_receipts.latest_active.unwrap().event_id, event_id!("$6"));
     }
+
+    #[test]
+    fn test_compute_unread_counts_with_threads() {
+        let user_id = user_id!("@alice:example.org");
+        let bob = user_id!("@bob:example.org");
+        let room_id = room_id!("!room:example.org");
+        let thread_root = event_id!("$1");
+
+        // Given a thread root, replies in the thread, and messages in the main
+        // timeline, all sent by Bob,
+        let f = EventFactory::new().sender(bob);
+        let events = vec![
+            f.text_msg("Thread root").event_id(thread_root).into_event(),
+            f.text_msg("First reply")
+                .in_thread(thread_root, thread_root)
+                .event_id(event_id!("$2"))
+                .into_event(),
+            f.text_msg("Main timeline").event_id(event_id!("$3")).into_event(),
+            f.text_msg("Second reply")
+                .in_thread(thread_root, event_id!("$2"))
+                .event_id(event_id!("$4"))
+                .into_event(),
+        ];
+
+        // And an unthreaded receipt on the thread root,
+        let receipt_event = f
+            .read_receipts()
+            .add(thread_root, user_id, ReceiptType::Read, ReceiptThread::Unthreaded)
+            .build();
+
+        let mut read_receipts = RoomReadReceipts::default();
+        compute_unread_counts(
+            user_id,
+            room_id,
+            Some(&receipt_event),
+            Vector::new(),
+            &events,
+            &mut read_receipts,
+        );
+
+        // Then the counts are split between the main timeline and the thread.
+        assert_eq!(read_receipts.num_unread, 3);
+        assert_eq!(read_receipts.main_timeline.num_unread, 1);
+        assert_eq!(read_receipts.thread_counts(thread_root).num_unread, 2);
+        assert_eq!(read_receipts.all_threads_counts().num_unread, 2);
+
+        // When a threaded receipt comes in for the first reply,
+        let receipt_event = f
+            .read_receipts()
+            .add(
+                event_id!("$2"),
+                user_id,
+                ReceiptType::Read,
+                ReceiptThread::Thread(thread_root.to_owned()),
+            )
+            .build();
+
+        let previous_events: Vector<_> = events.into_iter().collect();
+        compute_unread_counts(
+            user_id,
+            room_id,
+            Some(&receipt_event),
+            previous_events.clone(),
+            &[],
+            &mut read_receipts,
+        );
+
+        // Then only the thread counts are updated.
+        assert_eq!(read_receipts.num_unread, 2);
+        assert_eq!(read_receipts.main_timeline.num_unread, 1);
+        assert_eq!(read_receipts.thread_counts(thread_root).num_unread, 1);
+
+        // When I reply in the thread, and Bob sends a new message in the main
+        // timeline,
+        let new_events = vec![
+            f.text_msg("My reply")
+                .sender(user_id)
+                .in_thread(thread_root, event_id!("$4"))
+                .event_id(event_id!("$5"))
+                .into_event(),
+            f.text_msg("Main timeline again").event_id(event_id!("$6")).into_event(),
+        ];
+
+        compute_unread_counts(
+            user_id,
+            room_id,
+            None,
+            previous_events,
+            &new_events,
+            &mut read_receipts,
+        );
+
+        // Then my reply is an implicit threaded receipt, which doesn't mark the main
+        // timeline as read.
+        assert_eq!(read_receipts.num_unread, 2);
+        assert_eq!(read_receipts.main_timeline.num_unread, 2);
+        assert!(read_receipts.thread_counts(thread_root).is_empty());
+        assert_eq!(read_receipts.threads_with_unread().count(), 0);
+    }
+
+    #[test]
+    fn test_compute_unread_counts_forgets_read_threads() {
+        let user_id = user_id!("@alice:example.org");
+        let bob = user_id!("@bob:example.org");
+        let room_id = room_id!("!room:example.org");
+        let thread_root = event_id!("$1");
+
+        // Given a thread with a reply, and a threaded receipt on the reply,
+        let f = EventFactory::new().sender(bob);
+        let events = vec![
+            f.text_msg("Thread root").event_id(thread_root).into_event(),
+            f.text_msg("Reply")
+                .in_thread(thread_root, thread_root)
+                .event_id(event_id!("$2"))
+                .into_event(),
+            f.text_msg("Main timeline").event_id(event_id!("$3")).into_event(),
+        ];
+
+        let receipt_event = f
+            .read_receipts()
+            .add(
+                event_id!("$2"),
+                user_id,
+                ReceiptType::Read,
+                ReceiptThread::Thread(thread_root.to_owned()),
+            )
+            .build();
+
+        let mut read_receipts = RoomReadReceipts::default();
+        compute_unread_counts(
+            user_id,
+            room_id,
+            Some(&receipt_event),
+            Vector::new(),
+            &events,
+            &mut read_receipts,
+        );
+
+        // The threaded receipt is kept.
+        assert_eq!(read_receipts.threads.len(), 1);
+
+        // When an unthreaded receipt comes in for a later event,
+        let receipt_event = f
+            .read_receipts()
+            .add(event_id!("$3"), user_id, ReceiptType::Read, ReceiptThread::Unthreaded)
+            .build();
+
+        compute_unread_counts(
+            user_id,
+            room_id,
+            Some(&receipt_event),
+            events.into_iter().collect(),
+            &[],
+            &mut read_receipts,
+        );
+
+        // Then the thread is entirely read, and forgotten.
+        assert_eq!(read_receipts.num_unread, 0);
+        assert!(read_receipts.threads.is_empty());
+    }
+
+    #[test]
+    fn test_compute_unread_counts_caps_the_number_of_threads() {
+        let user_id = user_id!("@alice:example.org");
+        let bob = user_id!("@bob:example.org");
+        let room_id = room_id!("!room:example.org");
+        let f = EventFactory::new().sender(bob);
+
+        let mut read_receipts = RoomReadReceipts::default();
+
+        // Given more threads than we track, that were all read with threaded
+        // receipts,
+        for i in 0..MAX_TRACKED_THREADS + 10 {
+            let thread_root = EventId::parse(format!("$root{i}")).unwrap();
+            let reply = EventId::parse(format!("$reply{i}")).unwrap();
+
+            let events = vec![f
+                .text_msg("Reply")
+                .in_thread(&thread_root, &thread_root)
+                .event_id(&reply)
+                .into_event()];
+            let receipt_event = f
+                .read_receipts()
+                .add(&reply, user_id, ReceiptType::Read, ReceiptThread::Thread(thread_root))
+                .build();
+
+            compute_unread_counts(
+                user_id,
+                room_id,
+                Some(&receipt_event),
+                Vector::new(),
+                &events,
+                &mut read_receipts,
+            );
+        }
+
+        // Then only the maximum number of threads is kept.
+        assert_eq!(read_receipts.threads.len(), MAX_TRACKED_THREADS);
+
+        // When a new thread has an unread event,
+        let thread_root = event_id!("$unread_root");
+        let events = vec![f
+            .text_msg("Unread reply")
+            .in_thread(thread_root, thread_root)
+            .event_id(event_id!("$unread_reply"))
+            .into_event()];
+
+        compute_unread_counts(user_id, room_id, None, Vector::new(), &events, &mut read_receipts);
+
+        // Then it is kept, and a read thread is forgotten instead.
+        assert_eq!(read_receipts.threads.len(), MAX_TRACKED_THREADS);
+        assert_eq!(read_receipts.thread_counts(thread_root).num_unread, 1);
+    }
+
+    #[test]
+    fn test_compute_unread_counts_caps_the_number_of_unread_threads() {
+        let user_id = user_id!("@alice:example.org");
+        let bob = user_id!("@bob:example.org");
+        let room_id = room_id!("!room:example.org");
+        let f = EventFactory::new().sender(bob);
+
+        let mut read_receipts = RoomReadReceipts::default();
+
+        // Given more threads than we track, that all have an unread event, the older
+        // threads being received last,
+        let num_threads = MAX_TRACKED_THREADS + 10;
+        for i in 0..num_threads {
+            let age = i as u64 + 1;
+            let thread_root = EventId::parse(format!("$root{i}")).unwrap();
+            let reply = EventId::parse(format!("$reply{i}")).unwrap();
+
+            let events = vec![f
+                .text_msg("Reply")
+                .in_thread(&thread_root, &thread_root)
+                .event_id(&reply)
+                .server_ts(1_000_000 - age)
+                .into_event()];
+
+            compute_unread_counts(
+                user_id,
+                room_id,
+                None,
+                Vector::new(),
+                &events,
+                &mut read_receipts,
+            );
+        }
+
+        // Then only the maximum number of threads is kept,
+        assert_eq!(read_receipts.threads.len(), MAX_TRACKED_THREADS);
+
+        // The threads with the oldest latest event are forgotten,
+        for i in 0..num_threads {
+            let thread_root = EventId::parse(format!("$root{i}")).unwrap();
+            let expected = if i < MAX_TRACKED_THREADS { 1 } else { 0 };
+            assert_eq!(read_receipts.thread_counts(&thread_root).num_unread, expected);
+        }
+
+        // And the room-wide counts only include the threads that are kept.
+        assert_eq!(read_receipts.num_unread, MAX_TRACKED_THREADS as u64);
+        assert_eq!(read_receipts.all_threads_counts().num_unread, MAX_TRACKED_THREADS as u64);
+    }
 }
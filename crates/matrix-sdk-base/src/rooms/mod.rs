@@ -4,7 +4,7 @@ mod members;
 pub(crate) mod normal;
 
 use std::{
-    collections::{BTreeMap, HashSet},
+    collections::{BTreeMap, BTreeSet, HashSet},
     fmt,
     hash::Hash,
 };
@@ -12,8 +12,8 @@ use std::{
 use bitflags::bitflags;
 pub use members::RoomMember;
 pub use normal::{
-    apply_redaction, Room, RoomHero, RoomInfo, RoomInfoNotableUpdate, RoomInfoNotableUpdateReasons,
-    RoomMembersUpdate, RoomState, RoomStateFilter,
+    apply_redaction, PredecessorRoom, Room, RoomHero, RoomInfo, RoomInfoNotableUpdate,
+    RoomInfoNotableUpdateReasons, RoomMembersUpdate, RoomState, RoomStateFilter, SuccessorRoom,
 };
 use regex::Regex;
 use ruma::{
@@ -42,7 +42,7 @@ use ruma::{
         RedactedStateEventContent, StaticStateEventContent, SyncStateEvent,
     },
     room::RoomType,
-    EventId, OwnedUserId, RoomVersionId,
+    EventId, OwnedRoomId, OwnedUserId, RoomVersionId,
 };
 use serde::{Deserialize, Serialize};
 
@@ -161,6 +161,14 @@ pub struct BaseRoomInfo {
     pub(crate) notable_tags: RoomNotableTags,
     /// The `m.room.pinned_events` of this room.
     pub(crate) pinned_events: Option<RoomPinnedEventsEventContent>,
+    /// The rooms that this room declares as its children, if it's a space,
+    /// from its valid `m.space.child` events.
+    #[serde(skip_serializing_if = "BTreeSet::is_empty", default)]
+    pub(crate) space_children: BTreeSet<OwnedRoomId>,
+    /// The spaces that this room declares as its parents, from its valid
+    /// `m.space.parent` events.
+    #[serde(skip_serializing_if = "BTreeSet::is_empty", default)]
+    pub(crate) space_parents: BTreeSet<OwnedRoomId>,
 }
 
 impl BaseRoomInfo {
@@ -244,6 +252,25 @@ impl BaseRoomInfo {
             AnySyncStateEvent::RoomPinnedEvents(p) => {
                 self.pinned_events = p.as_original().map(|p| p.content.clone());
             }
+            // A space relation without a `via` field is considered removed.
+            AnySyncStateEvent::SpaceChild(c) => {
+                let child_id = c.state_key().clone();
+
+                if c.as_original().is_some_and(|c| !c.content.via.is_empty()) {
+                    self.space_children.insert(child_id);
+                } else {
+                    self.space_children.remove(&child_id);
+                }
+            }
+            AnySyncStateEvent::SpaceParent(p) => {
+                let parent_id = p.state_key().clone();
+
+                if p.as_original().is_some_and(|p| !p.content.via.is_empty()) {
+                    self.space_parents.insert(parent_id);
+                } else {
+                    self.space_parents.remove(&parent_id);
+                }
+            }
             _ => return false,
         }
 
@@ -407,6 +434,8 @@ impl Default for BaseRoomInfo {
             is_marked_unread: false,
             notable_tags: RoomNotableTags::empty(),
             pinned_events: None,
+            space_children: BTreeSet::new(),
+            space_parents: BTreeSet::new(),
         }
     }
 }
@@ -47,6 +47,7 @@ use ruma::{
             redaction::SyncRoomRedactionEvent,
             tombstone::RoomTombstoneEventContent,
         },
+        space::{child::SpaceChildEventContent, parent::SpaceParentEventContent},
         tag::{TagEventContent, Tags},
         AnyRoomAccountDataEvent, AnyStrippedStateEvent, AnySyncStateEvent, AnySyncTimelineEvent,
         RoomAccountDataEventType, StateEventType, SyncStateEvent,
@@ -113,9 +114,35 @@ bitflags! {
 
         /// A membership change happened for the current user.
         const MEMBERSHIP = 0b0001_0000;
+
+        /// The `m.space.child` or `m.space.parent` relations of the `Room`, or
+        /// of one of the spaces it belongs to, have changed.
+        const SPACE_RELATIONS = 0b0010_0000;
+
+        /// A membership change happened for the current user in the room that
+        /// replaced the `Room`.
+        const SUCCESSOR_MEMBERSHIP = 0b0100_0000;
     }
 }
 
+/// The room that replaced a room, after it has been upgraded, as announced by
+/// its `m.room.tombstone` event.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SuccessorRoom {
+    /// The ID of the new room.
+    pub room_id: OwnedRoomId,
+
+    /// The message explaining why the room has been replaced, if any.
+    pub reason: Option<String>,
+}
+
+/// The room that a room replaced, as announced in its `m.room.create` event.
+#[derive(Clone, Debug, PartialEq)]
+pub struct PredecessorRoom {
+    /// The ID of the old room.
+    pub room_id: OwnedRoomId,
+}
+
 /// The result of a room summary computation.
 ///
 /// If the homeserver does not provide a room summary, we perform a best-effort
@@ -338,6 +365,18 @@ impl Room {
         self.inner.read().room_type().is_some_and(|t| *t == RoomType::Space)
     }
 
+    /// Get the IDs of the rooms that this room declares as its children, with
+    /// valid `m.space.child` events.
+    pub fn space_child_ids(&self) -> BTreeSet<OwnedRoomId> {
+        self.inner.read().base_info.space_children.clone()
+    }
+
+    /// Get the IDs of the spaces that this room declares as its parents, with
+    /// valid `m.space.parent` events.
+    pub fn space_parent_ids(&self) -> BTreeSet<OwnedRoomId> {
+        self.inner.read().base_info.space_parents.clone()
+    }
+
     /// Returns the room's type as defined in its creation event
     /// (`m.room.create`).
     pub fn room_type(&self) -> Option<RoomType> {
@@ -605,6 +644,17 @@ impl Room {
         self.inner.read().tombstone().cloned()
     }
 
+    /// Get the room that replaced this room, if it has been tombstoned.
+    pub fn successor(&self) -> Option<SuccessorRoom> {
+        self.inner.read().successor()
+    }
+
+    /// Get the room that this room replaced, if it's the result of a room
+    /// upgrade.
+    pub fn predecessor(&self) -> Option<PredecessorRoom> {
+        self.inner.read().predecessor()
+    }
+
     /// Get the topic of the room.
     pub fn topic(&self) -> Option<String> {
         self.inner.read().topic().map(ToOwned::to_owned)
@@ -1074,6 +1124,15 @@ impl Room {
         });
     }
 
+    /// Broadcast a [`RoomInfoNotableUpdate`] for this room, without changing
+    /// its `RoomInfo`.
+    pub(crate) fn notify_notable_update(&self, reasons: RoomInfoNotableUpdateReasons) {
+        // Ignore error if no receiver exists.
+        let _ = self
+            .room_info_notable_update_sender
+            .send(RoomInfoNotableUpdate { room_id: self.room_id.clone(), reasons });
+    }
+
     /// Get the `RoomMember` with the given `user_id`.
     ///
     /// Returns `None` if the member was never part of this room, otherwise
@@ -1466,7 +1525,7 @@ impl RoomInfo {
     #[doc(hidden)] // used by store tests, otherwise it would be pub(crate)
     pub fn new(room_id: &RoomId, room_state: RoomState) -> Self {
         Self {
-            version: 1,
+            version: 2,
             room_id: room_id.into(),
             room_state,
             prev_room_state: None,
@@ -1862,6 +1921,25 @@ impl RoomInfo {
         Some(&self.base_info.tombstone.as_ref()?.as_original()?.content)
     }
 
+    /// Get the room that replaced this room, from its `m.room.tombstone`
+    /// event.
+    pub fn successor(&self) -> Option<SuccessorRoom> {
+        self.tombstone().map(|tombstone| SuccessorRoom {
+            room_id: tombstone.replacement_room.clone(),
+            reason: Some(tombstone.body.clone()).filter(|body| !body.is_empty()),
+        })
+    }
+
+    /// Get the room that this room replaced, from its `m.room.create` event.
+    pub fn predecessor(&self) -> Option<PredecessorRoom> {
+        let predecessor = match self.base_info.create.as_ref()? {
+            MinimalStateEvent::Original(ev) => ev.content.predecessor.as_ref(),
+            MinimalStateEvent::Redacted(ev) => ev.content.predecessor.as_ref(),
+        }?;
+
+        Some(PredecessorRoom { room_id: predecessor.room_id.clone() })
+    }
+
     /// Returns the topic for this room, if set.
     pub fn topic(&self) -> Option<&str> {
         Some(&self.base_info.topic.as_ref()?.as_original()?.content.topic)
@@ -2012,6 +2090,60 @@ impl RoomInfo {
             migrated = true;
         }
 
+        if self.version < 2 {
+            info!("Migrating room info to version 2");
+
+            // space_children
+            match store.get_state_events_static::<SpaceChildEventContent>(&self.room_id).await {
+                Ok(events) => {
+                    // Space relations are only handled in the full state.
+                    for event in events {
+                        let RawSyncOrStrippedState::Sync(raw_event) = event else {
+                            continue;
+                        };
+
+                        match raw_event.deserialize() {
+                            Ok(event) => {
+                                self.handle_state_event(&event.into());
+                            }
+                            Err(error) => {
+                                warn!("Failed to deserialize space child event: {error}");
+                            }
+                        }
+                    }
+                }
+                Err(error) => {
+                    warn!("Failed to load space child events: {error}");
+                }
+            }
+
+            // space_parents
+            match store.get_state_events_static::<SpaceParentEventContent>(&self.room_id).await {
+                Ok(events) => {
+                    for event in events {
+                        let RawSyncOrStrippedState::Sync(raw_event) = event else {
+                            continue;
+                        };
+
+                        match raw_event.deserialize() {
+                            Ok(event) => {
+                                self.handle_state_event(&event.into());
+                            }
+                            Err(error) => {
+                                warn!("Failed to deserialize space parent event: {error}");
+                            }
+                        }
+                    }
+                }
+                Err(error) => {
+                    warn!("Failed to load space parent events: {error}");
+                }
+            }
+
+            self.version = 2;
+            migrated = true;
+        }
+
         migrated
     }
 }
@@ -2197,7 +2329,10 @@ mod tests {
     use similar_asserts::assert_eq;
     use stream_assert::{assert_pending, assert_ready};
 
-    use super::{compute_display_name_from_heroes, Room, RoomHero, RoomInfo, RoomState, SyncInfo};
+    use super::{
+        compute_display_name_from_heroes, PredecessorRoom, Room, RoomHero, RoomInfo, RoomState,
+        SuccessorRoom, SyncInfo,
+    };
     use crate::{
         latest_event::LatestEvent,
         rooms::RoomNotableTags,
@@ -3657,14 +3792,14 @@ mod tests {
         // Apply migrations with an empty store.
         assert!(room_info.apply_migrations(store.clone()).await);
 
-        assert_eq!(room_info.version, 1);
+        assert_eq!(room_info.version, 2);
         assert!(room_info.base_info.notable_tags.is_empty());
         assert!(room_info.base_info.pinned_events.is_none());
 
         // Applying migrations again has no effect.
         assert!(!room_info.apply_migrations(store.clone()).await);
 
-        assert_eq!(room_info.version, 1);
+        assert_eq!(room_info.version, 2);
         assert!(room_info.base_info.notable_tags.is_empty());
         assert!(room_info.base_info.pinned_events.is_none());
 
@@ -3685,13 +3820,76 @@ mod tests {
         room_info.version = 0;
         assert!(room_info.apply_migrations(store.clone()).await);
 
-        assert_eq!(room_info.version, 1);
+        assert_eq!(room_info.version, 2);
         assert!(room_info.base_info.notable_tags.contains(RoomNotableTags::FAVOURITE));
         assert!(room_info.base_info.pinned_events.is_some());
 
-        // Creating a new room info initializes it to version 1.
+        // Creating a new room info initializes it to version 2.
         let new_room_info = RoomInfo::new(room_id!("!new_room:localhost"), RoomState::Joined);
-        assert_eq!(new_room_info.version, 1);
+        assert_eq!(new_room_info.version, 2);
+    }
+
+    #[async_test]
+    async fn test_room_info_migration_v2() {
+        let store = MemoryStore::new().into_state_store();
+        let room_id = room_id!("!space:localhost");
+
+        let mut room_info = RoomInfo::new(room_id, RoomState::Joined);
+        room_info.version = 1;
+
+        // The space relations are already in the store, from a previous sync.
+        let mut changes = StateChanges::default();
+
+        for event in [
+            json!({
+                "content": { "via": ["localhost"] },
+                "event_id": "$child",
+                "origin_server_ts": 151393755,
+                "sender": "@example:localhost",
+                "state_key": "!child:localhost",
+                "type": "m.space.child",
+            }),
+            // A child without a `via` field is not a child anymore.
+            json!({
+                "content": {},
+                "event_id": "$removed_child",
+                "origin_server_ts": 151393755,
+                "sender": "@example:localhost",
+                "state_key": "!removed_child:localhost",
+                "type": "m.space.child",
+            }),
+            json!({
+                "content": { "via": ["localhost"] },
+                "event_id": "$parent",
+                "origin_server_ts": 151393755,
+                "sender": "@example:localhost",
+                "state_key": "!parent:localhost",
+                "type": "m.space.parent",
+            }),
+        ] {
+            let raw_event: Raw<AnySyncStateEvent> = Raw::new(&event).unwrap().cast();
+            changes.add_state_event(room_id, raw_event.deserialize().unwrap(), raw_event);
+        }
+
+        store.save_changes(&changes).await.unwrap();
+
+        assert!(room_info.base_info.space_children.is_empty());
+        assert!(room_info.base_info.space_parents.is_empty());
+
+        assert!(room_info.apply_migrations(store.clone()).await);
+
+        assert_eq!(room_info.version, 2);
+        assert_eq!(
+            room_info.base_info.space_children,
+            BTreeSet::from([owned_room_id!("!child:localhost")])
+        );
+        assert_eq!(
+            room_info.base_info.space_parents,
+            BTreeSet::from([owned_room_id!("!parent:localhost")])
+        );
+
+        // Applying migrations again has no effect.
+        assert!(!room_info.apply_migrations(store).await);
     }
 
     #[async_test]
@@ -3794,4 +3992,60 @@ mod tests {
             ]
         );
     }
+
+    #[test]
+    fn test_successor_and_predecessor() {
+        let (_, room) = make_room_test_helper(RoomState::Joined);
+
+        assert!(room.successor().is_none());
+        assert!(room.predecessor().is_none());
+
+        let create_event: Raw<AnySyncStateEvent> = Raw::new(&json!({
+            "type": "m.room.create",
+            "content": {
+                "room_version": "11",
+                "predecessor": {
+                    "room_id": "!old:localhost",
+                    "event_id": "$tombstone",
+                },
+            },
+            "event_id": "$create",
+            "origin_server_ts": 0,
+            "sender": "@me:example.org",
+            "state_key": "",
+        }))
+        .unwrap()
+        .cast();
+
+        let tombstone_event: Raw<AnySyncStateEvent> = Raw::new(&json!({
+            "type": "m.room.tombstone",
+            "content": {
+                "body": "This room has been replaced",
+                "replacement_room": "!new:localhost",
+            },
+            "event_id": "$tombstone",
+            "origin_server_ts": 1,
+            "sender": "@me:example.org",
+            "state_key": "",
+        }))
+        .unwrap()
+        .cast();
+
+        room.inner.update(|info| {
+            info.handle_state_event(&create_event.deserialize().unwrap());
+            info.handle_state_event(&tombstone_event.deserialize().unwrap());
+        });
+
+        assert_eq!(
+            room.successor(),
+            Some(SuccessorRoom {
+                room_id: owned_room_id!("!new:localhost"),
+                reason: Some("This room has been replaced".to_owned()),
+            })
+        );
+        assert_eq!(
+            room.predecessor(),
+            Some(PredecessorRoom { room_id: owned_room_id!("!old:localhost") })
+        );
+    }
 }
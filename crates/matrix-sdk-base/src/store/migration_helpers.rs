@@ -218,6 +218,8 @@ impl BaseRoomInfoV1 {
             is_marked_unread: false,
             notable_tags: RoomNotableTags::empty(),
             pinned_events: None,
+            space_children: Default::default(),
+            space_parents: Default::default(),
         })
     }
 }
@@ -74,9 +74,10 @@ pub use self::integration_tests::StateStoreIntegrationTests;
 pub use self::{
     memory_store::MemoryStore,
     send_queue::{
-        ChildTransactionId, DependentQueuedRequest, DependentQueuedRequestKind,
-        FinishUploadThumbnailInfo, QueueWedgeError, QueuedRequest, QueuedRequestKind,
-        SentMediaInfo, SentRequestKey, SerializableEventContent,
+        AccumulatedSentMediaInfo, ChildTransactionId, DependentQueuedRequest,
+        DependentQueuedRequestKind, FinishGalleryItemInfo, FinishUploadThumbnailInfo,
+        QueueWedgeError, QueuedRequest, QueuedRequestKind, ResumableMediaUpload,
+        ResumableMediaUploadKey, SentMediaInfo, SentRequestKey, SerializableEventContent,
     },
     traits::{
         ComposerDraft, ComposerDraftType, DynStateStore, IntoStateStore, ServerCapabilities,
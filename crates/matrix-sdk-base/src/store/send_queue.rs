@@ -19,12 +19,12 @@ use std::{collections::BTreeMap, fmt, ops::Deref};
 use as_variant::as_variant;
 use ruma::{
     events::{
-        room::{message::RoomMessageEventContent, MediaSource},
-        AnyMessageLikeEventContent, EventContent as _, RawExt as _,
+        room::{message::RoomMessageEventContent, JsonWebKey, MediaSource},
+        AnyMessageLikeEventContent, AnyStateEventContent, EventContent as _, RawExt as _,
     },
-    serde::Raw,
-    MilliSecondsSinceUnixEpoch, OwnedDeviceId, OwnedEventId, OwnedTransactionId, OwnedUserId,
-    TransactionId, UInt,
+    serde::{Base64, Raw},
+    MilliSecondsSinceUnixEpoch, OwnedDeviceId, OwnedEventId, OwnedMxcUri, OwnedTransactionId,
+    OwnedUserId, TransactionId, UInt,
 };
 use serde::{Deserialize, Serialize};
 
@@ -103,6 +103,65 @@ pub enum QueuedRequestKind {
 
         /// To which media event transaction does this upload relate?
         related_to: OwnedTransactionId,
+
+        /// The medias uploaded before this one for the same event, when it
+        /// holds several medias, like a gallery.
+        #[serde(default, skip_serializing_if = "Vec::is_empty")]
+        accumulated: Vec<AccumulatedSentMediaInfo>,
+
+        /// The progress of the upload, if it's been started as a resumable
+        /// upload.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        resumable: Option<ResumableMediaUpload>,
+    },
+
+    /// An event to be sent via the send queue, not before a given time.
+    ScheduledEvent {
+        /// The content of the message-like event we'd like to send.
+        content: SerializableEventContent,
+
+        /// The time at which the event should be sent.
+        send_at: MilliSecondsSinceUnixEpoch,
+
+        /// Whether the event should be handed over to the homeserver as a
+        /// delayed event ([MSC4140]), instead of waiting in the queue until
+        /// its time.
+        ///
+        /// [MSC4140]: https://github.com/matrix-org/matrix-spec-proposals/pull/4140
+        use_delayed_event: bool,
+
+        /// The identifier of the delayed event, once it's been handed over to
+        /// the homeserver.
+        delay_id: Option<String>,
+    },
+
+    /// A state event to be sent via the send queue.
+    StateEvent {
+        /// The type of the state event.
+        event_type: String,
+
+        /// The state key of the state event.
+        state_key: String,
+
+        /// The content of the state event.
+        content: Raw<AnyStateEventContent>,
+    },
+
+    /// A redaction of an event that has been sent already.
+    Redaction {
+        /// The event to redact.
+        redacts: OwnedEventId,
+
+        /// The reason of the redaction, if any.
+        reason: Option<String>,
+
+        /// Whether the send queue queued the redaction itself, to cancel the
+        /// sending of an event that had been sent already.
+        ///
+        /// Such a redaction isn't reflected as a local echo, nor in the
+        /// updates of the send queue.
+        #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+        hidden: bool,
     },
 }
 
@@ -112,6 +171,41 @@ impl From<SerializableEventContent> for QueuedRequestKind {
     }
 }
 
+/// The progress of a media upload that can be resumed after a failure, by
+/// uploading the rest of the media to the same preallocated MXC URI.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ResumableMediaUpload {
+    /// The MXC URI preallocated for the media.
+    pub uri: OwnedMxcUri,
+
+    /// The number of bytes of the media the homeserver has received.
+    pub offset: u64,
+
+    /// The key the media is encrypted with, if it's uploaded encrypted.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub encryption_key: Option<ResumableMediaUploadKey>,
+}
+
+/// The key an encrypted media is encrypted with, during a resumable upload.
+///
+/// Encrypting the media again with the same key gives the same bytes, so the
+/// encrypted media doesn't need to be kept around to resume its upload.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct ResumableMediaUploadKey {
+    /// The web key used to encrypt the media.
+    pub key: JsonWebKey,
+
+    /// The initialization vector used to encrypt the media.
+    pub iv: Base64,
+}
+
+#[cfg(not(tarpaulin_include))]
+impl fmt::Debug for ResumableMediaUploadKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ResumableMediaUploadKey").finish_non_exhaustive()
+    }
+}
+
 /// A request to be sent with a send queue.
 #[derive(Clone)]
 pub struct QueuedRequest {
@@ -232,6 +326,23 @@ pub enum DependentQueuedRequestKind {
         related_to: OwnedTransactionId,
     },
 
+    /// Upload the next file or thumbnail of a media event holding several
+    /// medias, like a gallery, once the previous upload is done.
+    UploadFileOrThumbnail {
+        /// Content type for the file or thumbnail.
+        content_type: String,
+
+        /// Media request necessary to retrieve the file or thumbnail.
+        cache_key: MediaRequestParameters,
+
+        /// To which media transaction id does this upload relate to?
+        related_to: OwnedTransactionId,
+
+        /// Whether the parent upload is the thumbnail of this upload, or the
+        /// file of the previous item.
+        parent_is_thumbnail_upload: bool,
+    },
+
     /// Finish an upload by updating references to the media cache and sending
     /// the final media event with the remote MXC URIs.
     FinishUpload {
@@ -244,6 +355,17 @@ pub enum DependentQueuedRequestKind {
         /// Information about the thumbnail, if present.
         thumbnail_info: Option<FinishUploadThumbnailInfo>,
     },
+
+    /// Finish the uploads of a gallery by updating references to the media
+    /// cache and sending the final gallery event with the remote MXC URIs.
+    FinishGallery {
+        /// Local echo for the event (containing the local MXC URIs).
+        local_echo: RoomMessageEventContent,
+
+        /// Information about the uploads of each item of the gallery, in
+        /// order.
+        item_infos: Vec<FinishGalleryItemInfo>,
+    },
 }
 
 /// Detailed record about a thumbnail used when finishing a media upload.
@@ -263,6 +385,15 @@ pub struct FinishUploadThumbnailInfo {
     pub height: Option<UInt>,
 }
 
+/// Detailed record about an item of a gallery used when finishing its upload.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct FinishGalleryItemInfo {
+    /// Transaction id for the file upload.
+    pub file_upload: OwnedTransactionId,
+    /// Information about the thumbnail, if present.
+    pub thumbnail_info: Option<FinishUploadThumbnailInfo>,
+}
+
 /// A transaction id identifying a [`DependentQueuedRequest`] rather than its
 /// parent [`QueuedRequest`].
 ///
@@ -322,6 +453,22 @@ pub struct SentMediaInfo {
     ///
     /// When uploading a thumbnail, this is set to `None`.
     pub thumbnail: Option<MediaSource>,
+
+    /// The medias uploaded before this one for the same event, when it holds
+    /// several medias, like a gallery.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub accumulated: Vec<AccumulatedSentMediaInfo>,
+}
+
+/// Information about a media (and its thumbnail) that have been sent to a
+/// homeserver, before another media of the same event.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct AccumulatedSentMediaInfo {
+    /// File that was uploaded.
+    pub file: MediaSource,
+
+    /// Optional thumbnail previously uploaded with the file.
+    pub thumbnail: Option<MediaSource>,
 }
 
 /// A unique key (identifier) indicating that a transaction has been
@@ -335,6 +482,10 @@ pub enum SentRequestKey {
 
     /// The parent transaction returned an uploaded resource URL.
     Media(SentMediaInfo),
+
+    /// The parent transaction has been handed over to the homeserver as a
+    /// delayed event, which returned its identifier.
+    DelayedEvent(String),
 }
 
 impl SentRequestKey {
@@ -388,12 +539,14 @@ impl DependentQueuedRequest {
             DependentQueuedRequestKind::EditEvent { .. }
             | DependentQueuedRequestKind::RedactEvent
             | DependentQueuedRequestKind::ReactEvent { .. }
-            | DependentQueuedRequestKind::UploadFileWithThumbnail { .. } => {
+            | DependentQueuedRequestKind::UploadFileWithThumbnail { .. }
+            | DependentQueuedRequestKind::UploadFileOrThumbnail { .. } => {
                 // These are all aggregated events, or non-visible items (file upload producing
                 // a new MXC ID).
                 false
             }
-            DependentQueuedRequestKind::FinishUpload { .. } => {
+            DependentQueuedRequestKind::FinishUpload { .. }
+            | DependentQueuedRequestKind::FinishGallery { .. } => {
                 // This one graduates into a new media event.
                 true
             }
@@ -424,3 +424,21 @@ pub enum SessionRecipientCollectionError {
     #[error("Encryption failed because your device is not verified")]
     SendingFromUnverifiedDevice,
 }
+
+/// Errors that can be returned by
+/// [`crate::machine::OlmMachine::receive_room_key_bundle`].
+#[derive(Debug, Error)]
+pub enum RoomKeyBundleImportError {
+    /// The device that sent the bundle is unknown.
+    #[error("the device that sent the room key bundle is unknown")]
+    UnknownSenderDevice,
+
+    /// The device that sent the bundle isn't cross-signed by its owner, or its
+    /// owner was previously verified and changed their identity since then.
+    #[error("the device that sent the room key bundle is not trusted")]
+    UntrustedSenderDevice,
+
+    /// The store ran into an error.
+    #[error(transparent)]
+    Store(#[from] CryptoStoreError),
+}
@@ -41,9 +41,7 @@ type Aes256Ctr = ctr::Ctr128BE<Aes256>;
 /// Matrix attachment.
 pub struct AttachmentDecryptor<'a, R: Read> {
     inner: &'a mut R,
-    expected_hash: Vec<u8>,
-    sha: Sha256,
-    aes: Aes256Ctr,
+    decryptor: AttachmentChunkDecryptor,
 }
 
 #[cfg(not(tarpaulin_include))]
@@ -51,7 +49,7 @@ impl<'a, R: 'a + Read + std::fmt::Debug> std::fmt::Debug for AttachmentDecryptor
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         f.debug_struct("AttachmentDecryptor")
             .field("inner", &self.inner)
-            .field("expected_hash", &self.expected_hash)
+            .field("expected_hash", &self.decryptor.expected_hash)
             .finish()
     }
 }
@@ -61,16 +59,13 @@ impl<R: Read> Read for AttachmentDecryptor<'_, R> {
         let read_bytes = self.inner.read(buf)?;
 
         if read_bytes == 0 {
-            let hash = self.sha.finalize_reset();
-
-            if hash.as_slice() == self.expected_hash.as_slice() {
+            if self.decryptor.verify_hash() {
                 Ok(0)
             } else {
                 Err(IoError::new(ErrorKind::Other, "Hash mismatch while decrypting"))
             }
         } else {
-            self.sha.update(&buf[0..read_bytes]);
-            self.aes.apply_keystream(&mut buf[0..read_bytes]);
+            self.decryptor.decrypt_chunk(&mut buf[0..read_bytes]);
 
             Ok(read_bytes)
         }
@@ -94,6 +89,10 @@ pub enum DecryptorError {
     /// attachment encryption spec.
     #[error("Unknown version for the encrypted attachment.")]
     UnknownVersion,
+    /// The hash of the encrypted data doesn't match the one of the encryption
+    /// info.
+    #[error("Hash mismatch while decrypting")]
+    HashMismatch,
 }
 
 impl<'a, R: Read + 'a> AttachmentDecryptor<'a, R> {
@@ -130,6 +129,57 @@ impl<'a, R: Read + 'a> AttachmentDecryptor<'a, R> {
         input: &'a mut R,
         info: MediaEncryptionInfo,
     ) -> Result<AttachmentDecryptor<'a, R>, DecryptorError> {
+        let decryptor = AttachmentChunkDecryptor::new(info)?;
+        Ok(AttachmentDecryptor { inner: input, decryptor })
+    }
+}
+
+/// A decryptor of an encrypted attachment that is received chunk by chunk, for
+/// example while it is downloaded, so it never needs to be held in memory at
+/// once.
+///
+/// Unlike the [`AttachmentDecryptor`], it doesn't need a reader of the
+/// encrypted data.
+///
+/// The integrity of the data can only be verified once all the chunks have
+/// been decrypted, with [`AttachmentChunkDecryptor::finish()`]. The decrypted
+/// data must be discarded if it fails.
+pub struct AttachmentChunkDecryptor {
+    expected_hash: Vec<u8>,
+    sha: Sha256,
+    aes: Aes256Ctr,
+}
+
+#[cfg(not(tarpaulin_include))]
+impl std::fmt::Debug for AttachmentChunkDecryptor {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("AttachmentChunkDecryptor")
+            .field("expected_hash", &self.expected_hash)
+            .finish_non_exhaustive()
+    }
+}
+
+impl AttachmentChunkDecryptor {
+    /// Create a decryptor for the attachment with the given encryption info.
+    ///
+    /// # Examples
+    /// ```
+    /// # use matrix_sdk_crypto::{AttachmentChunkDecryptor, AttachmentChunkEncryptor};
+    /// let mut data = b"Hello world".to_vec();
+    ///
+    /// let mut encryptor = AttachmentChunkEncryptor::new();
+    /// encryptor.encrypt_chunk(&mut data);
+    /// let info = encryptor.finish();
+    ///
+    /// let mut decryptor = AttachmentChunkDecryptor::new(info).unwrap();
+    /// let (start, end) = data.split_at_mut(5);
+    /// decryptor.decrypt_chunk(start);
+    /// decryptor.decrypt_chunk(end);
+    /// decryptor.finish().unwrap();
+    ///
+    /// assert_eq!(data, b"Hello world");
+    /// ```
+    pub fn new(info: MediaEncryptionInfo) -> Result<Self, DecryptorError> {
         if info.version != VERSION {
             return Err(DecryptorError::UnknownVersion);
         }
@@ -151,7 +201,28 @@ impl<'a, R: Read + 'a> AttachmentDecryptor<'a, R> {
         let aes = Aes256Ctr::new(key_array, &iv);
         key.zeroize();
 
-        Ok(AttachmentDecryptor { inner: input, expected_hash: hash, sha, aes })
+        Ok(Self { expected_hash: hash, sha, aes })
+    }
+
+    /// Decrypt the next chunk of the attachment, in place.
+    pub fn decrypt_chunk(&mut self, chunk: &mut [u8]) {
+        self.sha.update(&*chunk);
+        self.aes.apply_keystream(chunk);
+    }
+
+    /// Verify the integrity of all the chunks that were decrypted.
+    pub fn finish(mut self) -> Result<(), DecryptorError> {
+        if self.verify_hash() {
+            Ok(())
+        } else {
+            Err(DecryptorError::HashMismatch)
+        }
+    }
+
+    /// Check the hash of the data decrypted so far, and reset it.
+    fn verify_hash(&mut self) -> bool {
+        let hash = self.sha.finalize_reset();
+        hash.as_slice() == self.expected_hash.as_slice()
     }
 }
 
@@ -159,11 +230,8 @@ impl<'a, R: Read + 'a> AttachmentDecryptor<'a, R> {
 pub struct AttachmentEncryptor<'a, R: Read + ?Sized> {
     finished: bool,
     inner: &'a mut R,
-    web_key: JsonWebKey,
-    iv: Base64,
     hashes: BTreeMap<String, Base64>,
-    aes: Aes256Ctr,
-    sha: Sha256,
+    encryptor: AttachmentChunkEncryptor,
 }
 
 #[cfg(not(tarpaulin_include))]
@@ -181,14 +249,11 @@ impl<'a, R: Read + ?Sized + 'a> Read for AttachmentEncryptor<'a, R> {
         let read_bytes = self.inner.read(buf)?;
 
         if read_bytes == 0 {
-            let hash = self.sha.finalize_reset();
-            self.hashes
-                .entry("sha256".to_owned())
-                .or_insert_with(|| Base64::new(hash.as_slice().to_owned()));
+            let hash = self.encryptor.finalize_hash();
+            self.hashes.entry("sha256".to_owned()).or_insert(hash);
             Ok(0)
         } else {
-            self.aes.apply_keystream(&mut buf[0..read_bytes]);
-            self.sha.update(&buf[0..read_bytes]);
+            self.encryptor.encrypt_chunk(&mut buf[0..read_bytes]);
 
             Ok(read_bytes)
         }
@@ -225,6 +290,65 @@ impl<'a, R: Read + ?Sized + 'a> AttachmentEncryptor<'a, R> {
     /// let key = encryptor.finish();
     /// ```
     pub fn new(reader: &'a mut R) -> Self {
+        AttachmentEncryptor {
+            finished: false,
+            inner: reader,
+            hashes: BTreeMap::new(),
+            encryptor: AttachmentChunkEncryptor::new(),
+        }
+    }
+
+    /// Consume the encryptor and get the encryption key.
+    pub fn finish(mut self) -> MediaEncryptionInfo {
+        let hash = self.encryptor.finalize_hash();
+        self.hashes.entry("sha256".to_owned()).or_insert(hash);
+
+        MediaEncryptionInfo {
+            version: VERSION.to_owned(),
+            hashes: self.hashes,
+            iv: self.encryptor.iv,
+            key: self.encryptor.web_key,
+        }
+    }
+}
+
+/// An encryptor of an attachment that is available chunk by chunk, for example
+/// while it is read from a file and uploaded, so it never needs to be held in
+/// memory at once.
+///
+/// Unlike the [`AttachmentEncryptor`], it doesn't need a reader of the data.
+pub struct AttachmentChunkEncryptor {
+    web_key: JsonWebKey,
+    iv: Base64,
+    aes: Aes256Ctr,
+    sha: Sha256,
+}
+
+#[cfg(not(tarpaulin_include))]
+impl std::fmt::Debug for AttachmentChunkEncryptor {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("AttachmentChunkEncryptor").finish_non_exhaustive()
+    }
+}
+
+impl Default for AttachmentChunkEncryptor {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl AttachmentChunkEncryptor {
+    /// Create an encryptor with a fresh encryption key.
+    ///
+    /// After all the chunks are encrypted, a call to
+    /// [`finish()`](#method.finish) is necessary to get the decryption key for
+    /// the data.
+    ///
+    /// # Panics
+    ///
+    /// Panics if we can't generate enough random data to create a fresh
+    /// encryption key.
+    pub fn new() -> Self {
         let mut key = [0u8; KEY_SIZE];
         let mut iv = [0u8; IV_SIZE];
 
@@ -251,31 +375,70 @@ impl<'a, R: Read + ?Sized + 'a> AttachmentEncryptor<'a, R> {
         let aes = Aes256Ctr::new(key_array, &iv.into());
         key.zeroize();
 
-        AttachmentEncryptor {
-            finished: false,
-            inner: reader,
-            iv: encoded_iv,
-            web_key,
-            hashes: BTreeMap::new(),
-            aes,
-            sha: Sha256::default(),
+        Self { web_key, iv: encoded_iv, aes, sha: Sha256::default() }
+    }
+
+    /// Create an encryptor with the key and initialization vector of another
+    /// encryptor, which can be obtained with [`Self::web_key()`] and
+    /// [`Self::iv()`].
+    ///
+    /// Both encryptors encrypt the same data to the same bytes, which allows
+    /// to encrypt an attachment again, for example to resume its upload,
+    /// without keeping the encrypted data around.
+    ///
+    /// Returns an error if the key or the initialization vector don't have the
+    /// expected length.
+    pub fn with_key(web_key: JsonWebKey, iv: Base64) -> Result<Self, DecryptorError> {
+        let mut key = web_key.k.as_bytes().to_owned();
+
+        if key.len() != KEY_SIZE {
+            key.zeroize();
+            return Err(DecryptorError::KeyNonceLength);
         }
+
+        let Some(iv_array) = GenericArray::from_exact_iter(iv.as_bytes().iter().copied()) else {
+            key.zeroize();
+            return Err(DecryptorError::KeyNonceLength);
+        };
+
+        let aes = Aes256Ctr::new(GenericArray::from_slice(&key), &iv_array);
+        key.zeroize();
+
+        Ok(Self { web_key, iv, aes, sha: Sha256::default() })
+    }
+
+    /// The web key used to encrypt the attachment.
+    pub fn web_key(&self) -> &JsonWebKey {
+        &self.web_key
+    }
+
+    /// The initialization vector used to encrypt the attachment.
+    pub fn iv(&self) -> &Base64 {
+        &self.iv
+    }
+
+    /// Encrypt the next chunk of the attachment, in place.
+    pub fn encrypt_chunk(&mut self, chunk: &mut [u8]) {
+        self.aes.apply_keystream(chunk);
+        self.sha.update(&*chunk);
     }
 
     /// Consume the encryptor and get the encryption key.
     pub fn finish(mut self) -> MediaEncryptionInfo {
-        let hash = self.sha.finalize();
-        self.hashes
-            .entry("sha256".to_owned())
-            .or_insert_with(|| Base64::new(hash.as_slice().to_owned()));
+        let hash = self.finalize_hash();
 
         MediaEncryptionInfo {
             version: VERSION.to_owned(),
-            hashes: self.hashes,
+            hashes: BTreeMap::from([("sha256".to_owned(), hash)]),
             iv: self.iv,
             key: self.web_key,
         }
     }
+
+    /// Get the hash of the data encrypted so far, and reset it.
+    fn finalize_hash(&mut self) -> Base64 {
+        Base64::new(self.sha.finalize_reset().as_slice().to_owned())
+    }
 }
 
 /// Struct holding all the information that is needed to decrypt an encrypted
@@ -303,9 +466,13 @@ impl From<EncryptedFile> for MediaEncryptionInfo {
 mod tests {
     use std::io::{Cursor, Read};
 
+    use ruma::serde::Base64;
     use serde_json::json;
 
-    use super::{AttachmentDecryptor, AttachmentEncryptor, MediaEncryptionInfo};
+    use super::{
+        AttachmentChunkDecryptor, AttachmentChunkEncryptor, AttachmentDecryptor,
+        AttachmentEncryptor, DecryptorError, MediaEncryptionInfo,
+    };
 
     const EXAMPLE_DATA: &[u8] = &[
         179, 154, 118, 127, 186, 127, 110, 33, 203, 33, 33, 134, 67, 100, 173, 46, 235, 27, 215,
@@ -379,4 +546,87 @@ mod tests {
 
         decryptor.read_to_end(&mut decrypted_data).unwrap_err();
     }
+
+    #[test]
+    fn chunk_encrypt_decrypt_cycle() {
+        let data = b"Hello world, in several chunks".to_vec();
+
+        let mut encrypted = data.clone();
+        let mut encryptor = AttachmentChunkEncryptor::new();
+        for chunk in encrypted.chunks_mut(7) {
+            encryptor.encrypt_chunk(chunk);
+        }
+        let key = encryptor.finish();
+        assert_ne!(encrypted, data);
+
+        // The chunk encryptor is compatible with the reader-based decryptor.
+        let mut cursor = Cursor::new(encrypted.clone());
+        let mut decryptor = AttachmentDecryptor::new(&mut cursor, key).unwrap();
+        let mut decrypted_data = Vec::new();
+        decryptor.read_to_end(&mut decrypted_data).unwrap();
+        assert_eq!(decrypted_data, data);
+
+        // And the chunk decryptor with the reader-based encryptor.
+        let mut cursor = Cursor::new(data.clone());
+        let mut encryptor = AttachmentEncryptor::new(&mut cursor);
+        let mut encrypted = Vec::new();
+        encryptor.read_to_end(&mut encrypted).unwrap();
+        let key = encryptor.finish();
+
+        let mut decryptor = AttachmentChunkDecryptor::new(key).unwrap();
+        for chunk in encrypted.chunks_mut(5) {
+            decryptor.decrypt_chunk(chunk);
+        }
+        decryptor.finish().unwrap();
+        assert_eq!(encrypted, data);
+    }
+
+    #[test]
+    fn chunk_encrypt_with_key() {
+        let data = b"It's a secret to everybody".to_vec();
+
+        let mut encrypted = data.clone();
+        let mut encryptor = AttachmentChunkEncryptor::new();
+        let mut resumed_encryptor =
+            AttachmentChunkEncryptor::with_key(encryptor.web_key().clone(), encryptor.iv().clone())
+                .unwrap();
+        encryptor.encrypt_chunk(&mut encrypted);
+        let key = encryptor.finish();
+
+        // The same key and initialization vector give the same encrypted data.
+        let mut encrypted_again = data.clone();
+        for chunk in encrypted_again.chunks_mut(5) {
+            resumed_encryptor.encrypt_chunk(chunk);
+        }
+        let key_again = resumed_encryptor.finish();
+
+        assert_eq!(encrypted_again, encrypted);
+        assert_eq!(key_again.hashes, key.hashes);
+
+        let mut decryptor = AttachmentChunkDecryptor::new(key_again).unwrap();
+        decryptor.decrypt_chunk(&mut encrypted_again);
+        decryptor.finish().unwrap();
+        assert_eq!(encrypted_again, data);
+    }
+
+    #[test]
+    fn chunk_encrypt_with_invalid_key() {
+        let key = example_key();
+        let short_iv = Base64::new(vec![0; 8]);
+
+        assert!(matches!(
+            AttachmentChunkEncryptor::with_key(key.key, short_iv),
+            Err(DecryptorError::KeyNonceLength)
+        ));
+    }
+
+    #[test]
+    fn chunk_decrypt_invalid_hash() {
+        let mut data = b"fake message".to_vec();
+
+        let mut decryptor = AttachmentChunkDecryptor::new(example_key()).unwrap();
+        decryptor.decrypt_chunk(&mut data);
+
+        assert!(matches!(decryptor.finish(), Err(DecryptorError::HashMismatch)));
+    }
 }
@@ -78,12 +78,13 @@ impl RoomKeyImportResult {
 }
 
 pub use error::{
-    EventError, MegolmError, OlmError, SessionCreationError, SessionRecipientCollectionError,
-    SetRoomSettingsError, SignatureError,
+    EventError, MegolmError, OlmError, RoomKeyBundleImportError, SessionCreationError,
+    SessionRecipientCollectionError, SetRoomSettingsError, SignatureError,
 };
 pub use file_encryption::{
-    decrypt_room_key_export, encrypt_room_key_export, AttachmentDecryptor, AttachmentEncryptor,
-    DecryptorError, KeyExportError, MediaEncryptionInfo,
+    decrypt_room_key_export, encrypt_room_key_export, AttachmentChunkDecryptor,
+    AttachmentChunkEncryptor, AttachmentDecryptor, AttachmentEncryptor, DecryptorError,
+    KeyExportError, MediaEncryptionInfo,
 };
 pub use gossiping::{GossipRequest, GossippedSecret};
 pub use identities::{
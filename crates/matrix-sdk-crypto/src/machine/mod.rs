@@ -14,6 +14,7 @@
 
 use std::{
     collections::{BTreeMap, HashMap, HashSet},
+    iter,
     sync::Arc,
     time::Duration,
 };
@@ -42,9 +43,10 @@ use ruma::{
     assign,
     events::{
         secret::request::SecretName, AnyMessageLikeEvent, AnyMessageLikeEventContent,
-        AnyToDeviceEvent, MessageLikeEventContent,
+        AnyToDeviceEvent, MessageLikeEventContent, ToDeviceEventType,
     },
     serde::{JsonObject, Raw},
+    to_device::DeviceIdOrAllDevices,
     DeviceId, MilliSecondsSinceUnixEpoch, OneTimeKeyAlgorithm, OwnedDeviceId, OwnedDeviceKeyId,
     OwnedTransactionId, OwnedUserId, RoomId, TransactionId, UInt, UserId,
 };
@@ -63,7 +65,10 @@ use vodozemac::{
 use crate::{
     backups::{BackupMachine, MegolmV1BackupKey},
     dehydrated_devices::{DehydratedDevices, DehydrationError},
-    error::{EventError, MegolmError, MegolmResult, OlmError, OlmResult, SetRoomSettingsError},
+    error::{
+        EventError, MegolmError, MegolmResult, OlmError, OlmResult, RoomKeyBundleImportError,
+        SetRoomSettingsError,
+    },
     gossiping::GossipMachine,
     identities::{user::UserIdentity, Device, IdentityManager, UserDevices},
     olm::{
@@ -71,7 +76,9 @@ use crate::{
         KnownSenderData, OlmDecryptionInfo, PrivateCrossSigningIdentity, SenderData,
         SenderDataFinder, SessionType, StaticAccountData,
     },
-    session_manager::{GroupSessionManager, SessionManager},
+    session_manager::{
+        collect_recipients_for_share_strategy, CollectStrategy, GroupSessionManager, SessionManager,
+    },
     store::{
         Changes, CryptoStoreWrapper, DeviceChanges, IdentityChanges, IntoCryptoStore, MemoryStore,
         PendingChanges, Result as StoreResult, RoomKeyInfo, RoomSettings, SecretImportError, Store,
@@ -85,21 +92,23 @@ use crate::{
                 RoomEventEncryptionScheme, SupportedEventEncryptionSchemes,
             },
             room_key::{MegolmV1AesSha2Content, RoomKeyContent},
+            room_key_bundle::RoomKeyBundleContent,
             room_key_withheld::{
                 MegolmV1AesSha2WithheldContent, RoomKeyWithheldContent, RoomKeyWithheldEvent,
             },
-            ToDeviceEvents,
+            EventType, ToDeviceEvents,
         },
         requests::{
             AnyIncomingResponse, KeysQueryRequest, OutgoingRequest, ToDeviceRequest,
             UploadSigningKeysRequest,
         },
+        room_history::{RoomKeyBundle, StoredRoomKeyBundleData},
         EventEncryptionAlgorithm, Signatures,
     },
     utilities::timestamp_to_iso8601,
     verification::{Verification, VerificationMachine, VerificationRequest},
     CrossSigningKeyExport, CryptoStoreError, DecryptionSettings, DeviceData, LocalTrust,
-    RoomEventDecryptionResult, SignatureError, TrustRequirement,
+    RoomEventDecryptionResult, RoomKeyImportResult, SignatureError, TrustRequirement,
 };
 
 /// State machine implementation of the Olm/Megolm encryption protocol used for
@@ -1089,6 +1098,204 @@ impl OlmMachine {
         self.inner.group_session_manager.share_room_key(room_id, users, encryption_settings).await
     }
 
+    /// Build a bundle of the room keys of the given room, that can be shared
+    /// with a user that we invite to the room, as defined in [MSC4268].
+    ///
+    /// Only the room keys that can be shared with users invited to the room
+    /// after they were created are included, see
+    /// [`InboundGroupSession::shared_history()`].
+    ///
+    /// [MSC4268]: https://github.com/matrix-org/matrix-spec-proposals/pull/4268
+    pub async fn build_room_key_bundle(&self, room_id: &RoomId) -> StoreResult<RoomKeyBundle> {
+        let room_keys = self
+            .store()
+            .export_room_keys(|session| session.room_id() == room_id && session.shared_history())
+            .await?;
+
+        Ok(RoomKeyBundle { room_keys })
+    }
+
+    /// Get the devices of the given user that should receive a room key
+    /// bundle, according to the given [`CollectStrategy`], and that we share
+    /// an Olm session with.
+    async fn room_key_bundle_recipients(
+        &self,
+        user_id: &UserId,
+        collect_strategy: &CollectStrategy,
+    ) -> OlmResult<Vec<Device>> {
+        let recipients = collect_recipients_for_share_strategy(
+            self.store(),
+            iter::once(user_id),
+            collect_strategy,
+            None,
+        )
+        .await?;
+
+        let user_devices = self.get_user_devices(user_id, None).await?;
+        let mut devices = Vec::new();
+
+        for device_data in recipients.devices.into_values().flatten() {
+            let Some(device) = user_devices.get(device_data.device_id()) else {
+                continue;
+            };
+
+            if device.get_most_recent_session().await?.is_some() {
+                devices.push(device);
+            } else {
+                warn!(
+                    device_id = ?device.device_id(),
+                    "Can't share a room key bundle with a device, we don't share an Olm session \
+                     with it",
+                );
+            }
+        }
+
+        Ok(devices)
+    }
+
+    /// Whether a room key bundle can be shared with the given user, i.e. if
+    /// any of their devices should receive it according to the given
+    /// [`CollectStrategy`], and we share an Olm session with it.
+    ///
+    /// This should be checked before building and uploading the bundle. The
+    /// missing Olm sessions should be established beforehand, using
+    /// [`OlmMachine::get_missing_sessions()`].
+    pub async fn can_share_room_key_bundle(
+        &self,
+        user_id: &UserId,
+        collect_strategy: &CollectStrategy,
+    ) -> OlmResult<bool> {
+        Ok(!self.room_key_bundle_recipients(user_id, collect_strategy).await?.is_empty())
+    }
+
+    /// Get the to-device request to send the location of an uploaded room key
+    /// bundle to the devices of the given user.
+    ///
+    /// The content is only encrypted for the devices of the user that should
+    /// receive room keys according to the given [`CollectStrategy`], usually
+    /// the one used to share the room keys of the room, and that we share an
+    /// Olm session with. The other devices are skipped. The missing Olm
+    /// sessions should be established beforehand, using
+    /// [`OlmMachine::get_missing_sessions()`].
+    ///
+    /// # Arguments
+    ///
+    /// * `user_id` - The user that should receive the room key bundle.
+    ///
+    /// * `collect_strategy` - The strategy used to select the devices of the
+    ///   user that should receive the room key bundle.
+    ///
+    /// * `bundle_data` - The content pointing to the uploaded bundle.
+    ///
+    /// # Returns
+    ///
+    /// The to-device request that needs to be sent out to the server, or `None`
+    /// if none of the devices of the user should receive the bundle.
+    pub async fn share_room_key_bundle_data(
+        &self,
+        user_id: &UserId,
+        collect_strategy: &CollectStrategy,
+        bundle_data: &RoomKeyBundleContent,
+    ) -> OlmResult<Option<ToDeviceRequest>> {
+        let content = serde_json::to_value(bundle_data)?;
+        let mut messages = BTreeMap::new();
+
+        for device in self.room_key_bundle_recipients(user_id, collect_strategy).await? {
+            let encrypted =
+                device.encrypt_event_raw(RoomKeyBundleContent::EVENT_TYPE, &content).await?;
+            messages.insert(
+                DeviceIdOrAllDevices::DeviceId(device.device_id().to_owned()),
+                encrypted.cast(),
+            );
+        }
+
+        if messages.is_empty() {
+            return Ok(None);
+        }
+
+        Ok(Some(ToDeviceRequest {
+            event_type: ToDeviceEventType::RoomEncrypted,
+            txn_id: TransactionId::new(),
+            messages: BTreeMap::from([(user_id.to_owned(), messages)]),
+        }))
+    }
+
+    /// Get the data of the room key bundle that the given user sent us for the
+    /// given room, if any.
+    ///
+    /// This is usually called when we join a room we were invited to, with the
+    /// user that invited us.
+    pub async fn get_received_room_key_bundle_data(
+        &self,
+        room_id: &RoomId,
+        sender_user: &UserId,
+    ) -> StoreResult<Option<StoredRoomKeyBundleData>> {
+        self.store().get_value(&room_key_bundle_data_key(room_id, sender_user)).await
+    }
+
+    /// Import the room keys of a room key bundle that we received.
+    ///
+    /// The bundle is only imported if the device that sent it is cross-signed
+    /// by its owner, and if its owner didn't change their identity since we
+    /// verified them.
+    ///
+    /// Only the room keys of the room of the bundle that can be shared with
+    /// users invited to the room are imported. Since we can't check who created
+    /// them, they are considered as imported, like the room keys that are
+    /// imported from a file export or from a backup.
+    ///
+    /// # Arguments
+    ///
+    /// * `bundle_data` - The data of the bundle, as returned by
+    ///   [`OlmMachine::get_received_room_key_bundle_data()`].
+    ///
+    /// * `bundle` - The bundle, downloaded from the media repository and
+    ///   decrypted.
+    ///
+    /// * `progress_listener` - A closure that will be called with the number of
+    ///   room keys that were processed and the total number of room keys.
+    pub async fn receive_room_key_bundle(
+        &self,
+        bundle_data: &StoredRoomKeyBundleData,
+        bundle: RoomKeyBundle,
+        progress_listener: impl Fn(usize, usize),
+    ) -> Result<RoomKeyImportResult, RoomKeyBundleImportError> {
+        let sender_device = self
+            .store()
+            .get_device_from_curve_key(&bundle_data.sender_user, bundle_data.sender_key)
+            .await?
+            .ok_or(RoomKeyBundleImportError::UnknownSenderDevice)?;
+
+        if !sender_device.is_cross_signed_by_owner() {
+            return Err(RoomKeyBundleImportError::UntrustedSenderDevice);
+        }
+
+        match SenderDataFinder::device_is_cross_signed_by_sender(sender_device) {
+            SenderData::SenderUnverified(_) | SenderData::SenderVerified(_) => {}
+            _ => return Err(RoomKeyBundleImportError::UntrustedSenderDevice),
+        }
+
+        let room_id = &bundle_data.bundle_data.room_id;
+        let room_keys: Vec<_> = bundle
+            .room_keys
+            .into_iter()
+            .filter(|key| {
+                if &key.room_id != room_id || !key.shared_history {
+                    warn!(
+                        session_id = key.session_id,
+                        "Ignoring a room key of a bundle that isn't shareable in the room of the \
+                         bundle",
+                    );
+                    false
+                } else {
+                    true
+                }
+            })
+            .collect();
+
+        Ok(self.store().import_room_keys(room_keys, None, progress_listener).await?)
+    }
+
     /// Receive an unencrypted verification event.
     ///
     /// This method can be used to pass verification events that are happening
@@ -1168,6 +1375,22 @@ impl OlmMachine {
             AnyDecryptedOlmEvent::Dummy(_) => {
                 debug!("Received an `m.dummy` event");
             }
+            AnyDecryptedOlmEvent::RoomKeyBundle(e) => {
+                debug!(room_id = ?e.content.room_id, "Received a room key bundle");
+
+                let bundle_data = StoredRoomKeyBundleData {
+                    sender_user: e.sender.clone(),
+                    sender_key: decrypted.result.sender_key,
+                    bundle_data: e.content.clone(),
+                };
+
+                self.store()
+                    .set_value(
+                        &room_key_bundle_data_key(&e.content.room_id, &e.sender),
+                        &bundle_data,
+                    )
+                    .await?;
+            }
             AnyDecryptedOlmEvent::Custom(_) => {
                 warn!("Received an unexpected encrypted to-device event");
             }
@@ -2573,6 +2796,12 @@ impl OlmMachine {
     }
 }
 
+/// The key of the custom value of the store where the data of the room key
+/// bundle sent by the given user for the given room is stored.
+fn room_key_bundle_data_key(room_id: &RoomId, sender_user: &UserId) -> String {
+    format!("room_key_bundle_data|{room_id}|{sender_user}")
+}
+
 fn sender_data_to_verification_state(
     sender_data: SenderData,
     session_has_been_imported: bool,
@@ -82,6 +82,7 @@ mod decryption_verification_state;
 mod interactive_verification;
 mod megolm_sender_data;
 mod olm_encryption;
+mod room_history;
 mod room_settings;
 mod send_encrypted_to_device;
 
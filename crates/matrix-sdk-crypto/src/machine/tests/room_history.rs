@@ -0,0 +1,187 @@
+// Copyright 2025 The Matrix.org Foundation C.I.C.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::{iter, sync::Arc};
+
+use assert_matches2::assert_matches;
+use matrix_sdk_test::async_test;
+use ruma::{events::room::history_visibility::HistoryVisibility, room_id, RoomId};
+use serde_json::json;
+
+use crate::{
+    machine::{
+        test_helpers::get_machine_pair_with_session,
+        tests::{self, sign_alice_device_for_machine_test_helper},
+    },
+    types::events::{room_key_bundle::RoomKeyBundleContent, ToDeviceEvent},
+    utilities::json_convert,
+    CollectStrategy, EncryptionSettings, EncryptionSyncChanges, OlmMachine,
+    RoomKeyBundleImportError,
+};
+
+fn bundle_content(room_id: &RoomId) -> RoomKeyBundleContent {
+    serde_json::from_value(json!({
+        "room_id": room_id,
+        "file": {
+            "url": "mxc://localhost/bundle",
+            "key": {
+                "kty": "oct",
+                "key_ops": ["encrypt", "decrypt"],
+                "alg": "A256CTR",
+                "k": "qcHVMSgYg-71CauWBezXI5qkaRb0LuIy-Wx5kIaHMIA",
+                "ext": true,
+            },
+            "iv": "X85+XgHN+HEAAAAAAAAAAA",
+            "hashes": {
+                "sha256": "5qG4fFnbbVdlAB1Q72JDKwCagV6Dbkx9uds4rSak37c",
+            },
+            "v": "v2",
+        },
+    }))
+    .unwrap()
+}
+
+/// Create a room key in the given room with a `shared` history visibility,
+/// then one with a `joined` history visibility.
+async fn create_room_keys(machine: &OlmMachine, room_id: &RoomId) {
+    machine.share_room_key(room_id, iter::empty(), EncryptionSettings::default()).await.unwrap();
+    machine.discard_room_key(room_id).await.unwrap();
+
+    let settings =
+        EncryptionSettings { history_visibility: HistoryVisibility::Joined, ..Default::default() };
+    machine.share_room_key(room_id, iter::empty(), settings).await.unwrap();
+}
+
+/// Send the bundle data from `sender` to `receiver`.
+async fn send_bundle_data(sender: &OlmMachine, receiver: &OlmMachine, room_id: &RoomId) {
+    let request = sender
+        .share_room_key_bundle_data(
+            receiver.user_id(),
+            &CollectStrategy::AllDevices,
+            &bundle_content(room_id),
+        )
+        .await
+        .unwrap()
+        .expect("We should share an Olm session with the receiver");
+
+    let event = ToDeviceEvent::new(
+        sender.user_id().to_owned(),
+        tests::to_device_requests_to_content(vec![Arc::new(request)]),
+    );
+
+    receiver
+        .receive_sync_changes(EncryptionSyncChanges {
+            to_device_events: vec![json_convert(&event).unwrap()],
+            changed_devices: &Default::default(),
+            one_time_keys_counts: &Default::default(),
+            unused_fallback_keys: None,
+            next_batch_token: None,
+        })
+        .await
+        .unwrap();
+}
+
+#[async_test]
+async fn test_room_key_bundle_only_contains_shareable_room_keys() {
+    let (alice, _) =
+        get_machine_pair_with_session(tests::alice_id(), tests::user_id(), false).await;
+    let room_id = room_id!("!test:example.org");
+
+    create_room_keys(&alice, room_id).await;
+    create_room_keys(&alice, room_id!("!other:example.org")).await;
+
+    assert_eq!(alice.store().get_inbound_group_sessions().await.unwrap().len(), 4);
+
+    let bundle = alice.build_room_key_bundle(room_id).await.unwrap();
+    assert_eq!(bundle.room_keys.len(), 1);
+    assert_eq!(bundle.room_keys[0].room_id, room_id);
+    assert!(bundle.room_keys[0].shared_history);
+}
+
+#[async_test]
+async fn test_receive_room_key_bundle() {
+    let (alice, bob) =
+        get_machine_pair_with_session(tests::alice_id(), tests::user_id(), false).await;
+    sign_alice_device_for_machine_test_helper(&alice, &bob).await;
+    let room_id = room_id!("!test:example.org");
+
+    create_room_keys(&alice, room_id).await;
+    send_bundle_data(&alice, &bob, room_id).await;
+
+    let bundle_data = bob
+        .get_received_room_key_bundle_data(room_id, alice.user_id())
+        .await
+        .unwrap()
+        .expect("Bob should have stored the data of the bundle");
+    assert_eq!(bundle_data.sender_user, alice.user_id());
+    assert_eq!(bundle_data.sender_key, alice.identity_keys().curve25519);
+    assert_eq!(bundle_data.bundle_data.room_id, room_id);
+
+    let bundle = alice.build_room_key_bundle(room_id).await.unwrap();
+    let session_id = bundle.room_keys[0].session_id.clone();
+
+    let result = bob.receive_room_key_bundle(&bundle_data, bundle, |_, _| {}).await.unwrap();
+    assert_eq!(result.imported_count, 1);
+
+    let session = bob
+        .store()
+        .get_inbound_group_session(room_id, &session_id)
+        .await
+        .unwrap()
+        .expect("Bob should have imported the room key");
+    assert!(session.has_been_imported());
+    assert!(session.shared_history());
+}
+
+#[async_test]
+async fn test_room_key_bundle_from_untrusted_device_is_rejected() {
+    let (alice, bob) =
+        get_machine_pair_with_session(tests::alice_id(), tests::user_id(), false).await;
+    let room_id = room_id!("!test:example.org");
+
+    create_room_keys(&alice, room_id).await;
+    send_bundle_data(&alice, &bob, room_id).await;
+
+    let bundle_data =
+        bob.get_received_room_key_bundle_data(room_id, alice.user_id()).await.unwrap().unwrap();
+    let bundle = alice.build_room_key_bundle(room_id).await.unwrap();
+
+    // Alice's device isn't cross-signed, so we don't trust the bundle.
+    assert_matches!(
+        bob.receive_room_key_bundle(&bundle_data, bundle, |_, _| {}).await,
+        Err(RoomKeyBundleImportError::UntrustedSenderDevice)
+    );
+    assert!(bob.store().get_inbound_group_sessions().await.unwrap().is_empty());
+}
+
+#[async_test]
+async fn test_room_key_bundle_data_respects_the_collect_strategy() {
+    let (alice, bob) =
+        get_machine_pair_with_session(tests::alice_id(), tests::user_id(), false).await;
+    let room_id = room_id!("!test:example.org");
+
+    assert!(alice
+        .can_share_room_key_bundle(bob.user_id(), &CollectStrategy::AllDevices)
+        .await
+        .unwrap());
+
+    // Bob's device isn't trusted, so it must not receive the history of the room.
+    let strategy = CollectStrategy::OnlyTrustedDevices;
+    assert!(!alice.can_share_room_key_bundle(bob.user_id(), &strategy).await.unwrap());
+    assert!(alice
+        .share_room_key_bundle_data(bob.user_id(), &strategy, &bundle_content(room_id))
+        .await
+        .unwrap()
+        .is_none());
+}
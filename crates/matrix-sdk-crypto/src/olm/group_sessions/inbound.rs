@@ -152,6 +152,12 @@ pub struct InboundGroupSession {
     /// created.
     history_visibility: Arc<Option<HistoryVisibility>>,
 
+    /// Whether this room key can be shared with users that are invited to the
+    /// room later on, as defined in [MSC3061].
+    ///
+    /// [MSC3061]: https://github.com/matrix-org/matrix-spec-proposals/pull/3061
+    shared_history: bool,
+
     /// Was this room key backed up to the server.
     backed_up: Arc<AtomicBool>,
 }
@@ -194,9 +200,12 @@ impl InboundGroupSession {
         let mut keys = SigningKeys::new();
         keys.insert(DeviceKeyAlgorithm::Ed25519, signing_key.into());
 
+        let shared_history = shared_history_from_history_visibility(history_visibility.as_ref());
+
         Ok(InboundGroupSession {
             inner: Arc::new(Mutex::new(session)),
             history_visibility: history_visibility.into(),
+            shared_history,
             session_id: session_id.into(),
             first_known_index,
             creator_info: SessionCreatorInfo {
@@ -228,9 +237,15 @@ impl InboundGroupSession {
         signing_key: Ed25519PublicKey,
         content: &room_key::MegolmV1AesSha2Content,
     ) -> Result<Self, SessionCreationError> {
-        let room_key::MegolmV1AesSha2Content { room_id, session_id: _, session_key, .. } = content;
+        let room_key::MegolmV1AesSha2Content {
+            room_id,
+            session_id: _,
+            session_key,
+            shared_history,
+            ..
+        } = content;
 
-        Self::new(
+        let mut session = Self::new(
             sender_key,
             signing_key,
             room_id,
@@ -238,7 +253,10 @@ impl InboundGroupSession {
             SenderData::unknown(),
             EventEncryptionAlgorithm::MegolmV1AesSha2,
             None,
-        )
+        )?;
+        session.shared_history = *shared_history;
+
+        Ok(session)
     }
 
     /// Create a new [`InboundGroupSession`] from an exported version of the
@@ -264,6 +282,7 @@ impl InboundGroupSession {
             imported: self.imported,
             backed_up: self.backed_up(),
             history_visibility: self.history_visibility.as_ref().clone(),
+            shared_history: self.shared_history,
             algorithm: (*self.algorithm).to_owned(),
         }
     }
@@ -317,6 +336,7 @@ impl InboundGroupSession {
             forwarding_curve25519_key_chain: vec![],
             sender_claimed_keys: (*self.creator_info.signing_keys).clone(),
             session_key,
+            shared_history: self.shared_history,
         }
     }
 
@@ -345,6 +365,7 @@ impl InboundGroupSession {
             },
             sender_data: pickle.sender_data,
             history_visibility: pickle.history_visibility.into(),
+            shared_history: pickle.shared_history,
             first_known_index,
             room_id: (*pickle.room_id).into(),
             backed_up: AtomicBool::from(pickle.backed_up).into(),
@@ -380,6 +401,14 @@ impl InboundGroupSession {
         self.imported
     }
 
+    /// Can this session be shared with users that are invited to the room
+    /// after it was created, as defined in [MSC3061]?
+    ///
+    /// [MSC3061]: https://github.com/matrix-org/matrix-spec-proposals/pull/3061
+    pub fn shared_history(&self) -> bool {
+        self.shared_history
+    }
+
     /// Check if the [`InboundGroupSession`] is better than the given other
     /// [`InboundGroupSession`]
     pub async fn compare(&self, other: &InboundGroupSession) -> SessionOrdering {
@@ -541,6 +570,10 @@ pub struct PickledInboundGroupSession {
     pub backed_up: bool,
     /// History visibility of the room when the session was created.
     pub history_visibility: Option<HistoryVisibility>,
+    /// Flag remembering if the session can be shared with users invited to the
+    /// room later on.
+    #[serde(default)]
+    pub shared_history: bool,
     /// The algorithm of this inbound group session.
     #[serde(default = "default_algorithm")]
     pub algorithm: EventEncryptionAlgorithm,
@@ -550,6 +583,17 @@ fn default_algorithm() -> EventEncryptionAlgorithm {
     EventEncryptionAlgorithm::MegolmV1AesSha2
 }
 
+/// Whether the room keys created with the given history visibility can be
+/// shared with users invited to the room later on.
+///
+/// Those users are allowed to see the messages sent before they were invited
+/// only if the history visibility is `shared` or `world_readable`.
+pub(crate) fn shared_history_from_history_visibility(
+    history_visibility: Option<&HistoryVisibility>,
+) -> bool {
+    matches!(history_visibility, Some(HistoryVisibility::Shared | HistoryVisibility::WorldReadable))
+}
+
 impl TryFrom<&ExportedRoomKey> for InboundGroupSession {
     type Error = SessionCreationError;
 
@@ -569,6 +613,7 @@ impl TryFrom<&ExportedRoomKey> for InboundGroupSession {
             // See https://github.com/matrix-org/matrix-rust-sdk/issues/3548
             sender_data: SenderData::default(),
             history_visibility: None.into(),
+            shared_history: key.shared_history,
             first_known_index,
             room_id: key.room_id.to_owned(),
             imported: true,
@@ -599,6 +644,7 @@ impl From<&ForwardedMegolmV1AesSha2Content> for InboundGroupSession {
             // See https://github.com/matrix-org/matrix-rust-sdk/issues/3548
             sender_data: SenderData::default(),
             history_visibility: None.into(),
+            shared_history: false,
             first_known_index,
             room_id: value.room_id.to_owned(),
             imported: true,
@@ -625,6 +671,7 @@ impl From<&ForwardedMegolmV2AesSha2Content> for InboundGroupSession {
             // See https://github.com/matrix-org/matrix-rust-sdk/issues/3548
             sender_data: SenderData::default(),
             history_visibility: None.into(),
+            shared_history: false,
             first_known_index,
             room_id: value.room_id.to_owned(),
             imported: true,
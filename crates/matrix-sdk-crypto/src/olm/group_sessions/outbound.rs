@@ -43,7 +43,7 @@ pub use vodozemac::{
     PickleError,
 };
 
-use super::SessionCreationError;
+use super::{inbound::shared_history_from_history_visibility, SessionCreationError};
 #[cfg(feature = "experimental-algorithms")]
 use crate::types::events::room::encrypted::MegolmV2AesSha2Content;
 use crate::{
@@ -527,14 +527,15 @@ impl OutboundGroupSession {
     pub(crate) async fn as_content(&self) -> RoomKeyContent {
         let session_key = self.session_key().await;
 
-        RoomKeyContent::MegolmV1AesSha2(
-            MegolmV1AesSha2RoomKeyContent::new(
-                self.room_id().to_owned(),
-                self.session_id().to_owned(),
-                session_key,
-            )
-            .into(),
-        )
+        let mut content = MegolmV1AesSha2RoomKeyContent::new(
+            self.room_id().to_owned(),
+            self.session_id().to_owned(),
+            session_key,
+        );
+        content.shared_history =
+            shared_history_from_history_visibility(Some(&self.settings.history_visibility));
+
+        RoomKeyContent::MegolmV1AesSha2(content.into())
     }
 
     /// Has or will the session be shared with the given user/device pair.
@@ -232,7 +232,7 @@ impl<'a> SenderDataFinder<'a> {
         let cross_signed = sender_device.is_cross_signed_by_owner();
 
         Ok(match (device_is_owner, cross_signed) {
-            (true, true) => self.device_is_cross_signed_by_sender(sender_device),
+            (true, true) => Self::device_is_cross_signed_by_sender(sender_device),
             (true, false) => {
                 // F (we have device keys, but they are not signed by the sender)
                 SenderData::device_info(sender_device.as_device_keys().clone())
@@ -246,7 +246,11 @@ impl<'a> SenderDataFinder<'a> {
     }
 
     /// Step G (device is cross-signed by the sender)
-    fn device_is_cross_signed_by_sender(&self, sender_device: Device) -> SenderData {
+    ///
+    /// This doesn't depend on the session, so it can also be used to decide
+    /// whether we trust a device that sent us something else than a room key,
+    /// as long as it was checked that the device is cross-signed by its owner.
+    pub(crate) fn device_is_cross_signed_by_sender(sender_device: Device) -> SenderData {
         // H (cross-signing key matches that used to sign the device!)
         let user_id = sender_device.user_id().to_owned();
         let device_id = Some(sender_device.device_id().to_owned());
@@ -31,8 +31,8 @@ use ruma::{
     to_device::DeviceIdOrAllDevices,
     OwnedDeviceId, OwnedRoomId, OwnedTransactionId, OwnedUserId, RoomId, TransactionId, UserId,
 };
-pub(crate) use share_strategy::CollectRecipientsResult;
 pub use share_strategy::CollectStrategy;
+pub(crate) use share_strategy::{collect_recipients_for_share_strategy, CollectRecipientsResult};
 use tracing::{debug, error, info, instrument, trace};
 
 use crate::{
@@ -157,8 +157,6 @@ pub(crate) async fn collect_session_recipients(
     outbound: &OutboundGroupSession,
 ) -> OlmResult<CollectRecipientsResult> {
     let users: BTreeSet<&UserId> = users.collect();
-    let mut result = CollectRecipientsResult::default();
-    let mut verified_users_with_new_identities: Vec<OwnedUserId> = Default::default();
 
     trace!(?users, ?settings, "Calculating group session recipients");
 
@@ -181,13 +179,52 @@ pub(crate) async fn collect_session_recipients(
     // 3. The history visibility changed.
     // 4. The encryption algorithm changed.
     //
-    // This is calculated in the following code and stored in this variable.
-    result.should_rotate = user_left || visibility_changed || algorithm_changed;
+    // The second condition is checked while collecting the recipient devices.
+    let should_rotate = user_left || visibility_changed || algorithm_changed;
+
+    let mut result = collect_recipients_for_share_strategy(
+        store,
+        users.into_iter(),
+        &settings.sharing_strategy,
+        Some(outbound),
+    )
+    .await?;
+    result.should_rotate |= should_rotate;
+
+    if result.should_rotate {
+        debug!(
+            result.should_rotate,
+            user_left,
+            visibility_changed,
+            algorithm_changed,
+            "Rotating room key to protect room history",
+        );
+    }
+    trace!(result.should_rotate, "Done calculating group session recipients");
+
+    Ok(result)
+}
+
+/// Given a list of users and a [`CollectStrategy`], return the devices of the
+/// users that should receive room keys, and the ones that should not, with the
+/// withheld reason.
+///
+/// If an outbound session is given, the result also indicates whether the
+/// session was shared with devices that should not receive it anymore, in which
+/// case it needs to be rotated.
+pub(crate) async fn collect_recipients_for_share_strategy(
+    store: &Store,
+    users: impl Iterator<Item = &UserId>,
+    share_strategy: &CollectStrategy,
+    outbound: Option<&OutboundGroupSession>,
+) -> OlmResult<CollectRecipientsResult> {
+    let mut result = CollectRecipientsResult::default();
+    let mut verified_users_with_new_identities: Vec<OwnedUserId> = Default::default();
 
     let own_identity = store.get_user_identity(store.user_id()).await?.and_then(|i| i.into_own());
 
     // Get the recipient and withheld devices, based on the collection strategy.
-    match settings.sharing_strategy {
+    match share_strategy {
         CollectStrategy::AllDevices => {
             for user_id in users {
                 trace!(
@@ -325,17 +362,6 @@ pub(crate) async fn collect_session_recipients(
         ));
     }
 
-    if result.should_rotate {
-        debug!(
-            result.should_rotate,
-            user_left,
-            visibility_changed,
-            algorithm_changed,
-            "Rotating room key to protect room history",
-        );
-    }
-    trace!(result.should_rotate, "Done calculating group session recipients");
-
     Ok(result)
 }
 
@@ -343,7 +369,7 @@ pub(crate) async fn collect_session_recipients(
 /// user.
 fn update_recipients_for_user(
     recipients: &mut CollectRecipientsResult,
-    outbound: &OutboundGroupSession,
+    outbound: Option<&OutboundGroupSession>,
     user_id: &UserId,
     recipient_devices: RecipientDevicesForUser,
 ) {
@@ -351,9 +377,14 @@ fn update_recipients_for_user(
     // rotated for other reasons, we also need to check whether any
     // of the devices in the session got deleted or blacklisted in the
     // meantime. If so, we should also rotate the session.
-    if !recipients.should_rotate {
-        recipients.should_rotate =
-            is_session_overshared_for_user(outbound, user_id, &recipient_devices.allowed_devices)
+    if let Some(outbound) = outbound {
+        if !recipients.should_rotate {
+            recipients.should_rotate = is_session_overshared_for_user(
+                outbound,
+                user_id,
+                &recipient_devices.allowed_devices,
+            )
+        }
     }
 
     recipients
@@ -16,5 +16,7 @@ mod group_sessions;
 mod sessions;
 
 pub use group_sessions::CollectStrategy;
-pub(crate) use group_sessions::{GroupSessionCache, GroupSessionManager};
+pub(crate) use group_sessions::{
+    collect_recipients_for_share_strategy, GroupSessionCache, GroupSessionManager,
+};
 pub(crate) use sessions::SessionManager;
@@ -0,0 +1,478 @@
+// Copyright 2025 The Matrix.org Foundation C.I.C.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Diagnostics for the consistency of a [`CryptoStore`].
+//!
+//! When debugging decryption issues, it is useful to know whether the data in
+//! the crypto store of the user is consistent. [`DiagnosticsReport::collect()`]
+//! walks a [`CryptoStore`] and reports the anomalies it finds, and
+//! [`DiagnosticsReport::repair()`] repairs the ones that can be fixed without
+//! talking to the homeserver.
+//!
+//! ```no_run
+//! # use matrix_sdk_crypto::store::{diagnostics::DiagnosticsReport, MemoryStore};
+//! # async {
+//! # let store = MemoryStore::new();
+//! let report = DiagnosticsReport::collect(&store).await?;
+//!
+//! for anomaly in &report.anomalies {
+//!     println!("{anomaly}");
+//! }
+//!
+//! let repaired = report.repair(&store).await?;
+//! println!("Repaired {repaired} anomalies");
+//! # anyhow::Ok(()) };
+//! ```
+
+use std::{collections::BTreeSet, fmt};
+
+use ruma::{OwnedDeviceId, OwnedRoomId, OwnedUserId};
+use serde::Serialize;
+
+use super::{Changes, CryptoStore};
+use crate::olm::{InboundGroupSession, SenderData};
+
+/// An anomaly found in a [`CryptoStore`].
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum StoreAnomaly {
+    /// The store doesn't contain an account, so it can't be used by an
+    /// [`OlmMachine`].
+    ///
+    /// [`OlmMachine`]: crate::OlmMachine
+    MissingAccount,
+
+    /// The devices of the owner of the account are not tracked, so we don't
+    /// know about our own devices.
+    OwnUserNotTracked {
+        /// The owner of the account.
+        user_id: OwnedUserId,
+    },
+
+    /// The list of devices of a tracked user is considered up to date, but we
+    /// don't know any device or identity of the user, so it is likely that
+    /// their keys were never fetched.
+    StaleTrackedUser {
+        /// The tracked user.
+        user_id: OwnedUserId,
+    },
+
+    /// The sender data of an inbound group session attributes it to a device
+    /// whose Curve25519 key isn't the one that sent the session.
+    MismatchedSenderData {
+        /// The room of the session.
+        room_id: OwnedRoomId,
+        /// The ID of the session.
+        session_id: String,
+        /// The user the session is attributed to, if known.
+        user_id: Option<OwnedUserId>,
+        /// The device the session is attributed to, if known.
+        device_id: Option<OwnedDeviceId>,
+    },
+
+    /// The outbound group session of a room doesn't have a matching inbound
+    /// group session, so we can't decrypt the messages we send with it.
+    OrphanedOutboundGroupSession {
+        /// The room of the session.
+        room_id: OwnedRoomId,
+        /// The ID of the session.
+        session_id: String,
+    },
+
+    /// Inbound group sessions are marked as backed up, but no backup is
+    /// enabled, so they won't be uploaded to the next backup.
+    BackedUpWithoutBackup {
+        /// The number of sessions that are marked as backed up.
+        count: usize,
+    },
+}
+
+impl StoreAnomaly {
+    /// Whether this anomaly can be repaired with
+    /// [`DiagnosticsReport::repair()`].
+    pub fn is_repairable(&self) -> bool {
+        !matches!(self, Self::MissingAccount)
+    }
+}
+
+impl fmt::Display for StoreAnomaly {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::MissingAccount => write!(f, "The store doesn't contain an account"),
+            Self::OwnUserNotTracked { user_id } => {
+                write!(f, "The devices of our own user {user_id} are not tracked")
+            }
+            Self::StaleTrackedUser { user_id } => write!(
+                f,
+                "The tracked user {user_id} is up to date, but none of their keys are known"
+            ),
+            Self::MismatchedSenderData { room_id, session_id, user_id, device_id } => write!(
+                f,
+                "The inbound group session {session_id} in {room_id} is attributed to \
+                 {user_id:?}/{device_id:?}, which didn't send it"
+            ),
+            Self::OrphanedOutboundGroupSession { room_id, session_id } => write!(
+                f,
+                "The outbound group session {session_id} in {room_id} doesn't have a matching \
+                 inbound group session"
+            ),
+            Self::BackedUpWithoutBackup { count } => write!(
+                f,
+                "{count} inbound group sessions are marked as backed up, but backups are disabled"
+            ),
+        }
+    }
+}
+
+/// The report of the diagnostics of a [`CryptoStore`].
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize)]
+pub struct DiagnosticsReport {
+    /// The number of users whose devices are tracked.
+    pub tracked_user_count: usize,
+
+    /// The number of inbound group sessions in the store.
+    pub inbound_group_session_count: usize,
+
+    /// The anomalies found in the store.
+    pub anomalies: Vec<StoreAnomaly>,
+}
+
+impl DiagnosticsReport {
+    /// Walk the given store and collect the anomalies found in it.
+    ///
+    /// This loads all the inbound group sessions of the store, so it might
+    /// take a while for big stores.
+    pub async fn collect<S: CryptoStore + ?Sized>(store: &S) -> Result<Self, S::Error> {
+        let mut report = Self::default();
+
+        let own_user_id = store.load_account().await?.map(|account| account.user_id().to_owned());
+
+        if own_user_id.is_none() {
+            report.anomalies.push(StoreAnomaly::MissingAccount);
+        }
+
+        let tracked_users = store.load_tracked_users().await?;
+        report.tracked_user_count = tracked_users.len();
+
+        if let Some(own_user_id) = own_user_id {
+            if !tracked_users.iter().any(|user| user.user_id == own_user_id) {
+                report.anomalies.push(StoreAnomaly::OwnUserNotTracked { user_id: own_user_id });
+            }
+        }
+
+        for user in tracked_users.into_iter().filter(|user| !user.dirty) {
+            if store.get_user_devices(&user.user_id).await?.is_empty()
+                && store.get_user_identity(&user.user_id).await?.is_none()
+            {
+                report.anomalies.push(StoreAnomaly::StaleTrackedUser { user_id: user.user_id });
+            }
+        }
+
+        let sessions = store.get_inbound_group_sessions().await?;
+        report.inbound_group_session_count = sessions.len();
+
+        for session in &sessions {
+            if let Some(anomaly) = check_sender_data(store, session).await? {
+                report.anomalies.push(anomaly);
+            }
+        }
+
+        // We can only find the outbound group sessions of the rooms we know about.
+        let room_ids: BTreeSet<_> = sessions.iter().map(|session| session.room_id()).collect();
+
+        for room_id in room_ids {
+            let Some(outbound) = store.get_outbound_group_session(room_id).await? else {
+                continue;
+            };
+
+            if !outbound.invalidated()
+                && store.get_inbound_group_session(room_id, outbound.session_id()).await?.is_none()
+            {
+                report.anomalies.push(StoreAnomaly::OrphanedOutboundGroupSession {
+                    room_id: room_id.to_owned(),
+                    session_id: outbound.session_id().to_owned(),
+                });
+            }
+        }
+
+        if store.load_backup_keys().await?.backup_version.is_none() {
+            let count = sessions.iter().filter(|session| session.backed_up()).count();
+
+            if count > 0 {
+                report.anomalies.push(StoreAnomaly::BackedUpWithoutBackup { count });
+            }
+        }
+
+        Ok(report)
+    }
+
+    /// Whether no anomaly was found in the store.
+    pub fn is_healthy(&self) -> bool {
+        self.anomalies.is_empty()
+    }
+
+    /// Repair the anomalies of this report that can be repaired, in the given
+    /// store.
+    ///
+    /// The store must be the one this report was collected from. Returns the
+    /// number of anomalies that were repaired:
+    ///
+    /// * The users that are not, or wrongly, tracked are marked as dirty, so
+    ///   their keys are fetched again.
+    /// * The sender data of the inbound group sessions that are attributed to
+    ///   the wrong device is reset, so it is calculated again the next time the
+    ///   sessions are used.
+    /// * The orphaned outbound group sessions are invalidated, so new ones are
+    ///   created the next time a message is sent in the room.
+    /// * The backup state of the inbound group sessions is reset, so they are
+    ///   uploaded to the next backup.
+    pub async fn repair<S: CryptoStore + ?Sized>(&self, store: &S) -> Result<usize, S::Error> {
+        let mut repaired = 0;
+        let mut changes = Changes::default();
+
+        for anomaly in &self.anomalies {
+            match anomaly {
+                StoreAnomaly::MissingAccount => continue,
+                StoreAnomaly::OwnUserNotTracked { user_id }
+                | StoreAnomaly::StaleTrackedUser { user_id } => {
+                    store.save_tracked_users(&[(user_id, true)]).await?;
+                }
+                StoreAnomaly::MismatchedSenderData { room_id, session_id, .. } => {
+                    let Some(mut session) =
+                        store.get_inbound_group_session(room_id, session_id).await?
+                    else {
+                        continue;
+                    };
+
+                    session.sender_data = SenderData::unknown();
+                    changes.inbound_group_sessions.push(session);
+                }
+                StoreAnomaly::OrphanedOutboundGroupSession { room_id, session_id } => {
+                    let Some(session) = store
+                        .get_outbound_group_session(room_id)
+                        .await?
+                        .filter(|session| session.session_id() == session_id)
+                    else {
+                        continue;
+                    };
+
+                    session.invalidate_session();
+                    changes.outbound_group_sessions.push(session);
+                }
+                StoreAnomaly::BackedUpWithoutBackup { .. } => {
+                    store.reset_backup_state().await?;
+                }
+            }
+
+            repaired += 1;
+        }
+
+        if !changes.is_empty() {
+            store.save_changes(changes).await?;
+        }
+
+        Ok(repaired)
+    }
+}
+
+/// Check that the sender data of the given session attributes it to the
+/// device that sent it.
+async fn check_sender_data<S: CryptoStore + ?Sized>(
+    store: &S,
+    session: &InboundGroupSession,
+) -> Result<Option<StoreAnomaly>, S::Error> {
+    let sender_key = session.sender_key();
+
+    let (user_id, device_id, is_consistent) = match &session.sender_data {
+        SenderData::UnknownDevice { .. } => return Ok(None),
+        SenderData::DeviceInfo { device_keys, .. } => (
+            Some(device_keys.user_id.clone()),
+            Some(device_keys.device_id.clone()),
+            device_keys.curve25519_key() == Some(sender_key),
+        ),
+        SenderData::VerificationViolation(known)
+        | SenderData::SenderUnverified(known)
+        | SenderData::SenderVerified(known) => {
+            let Some(device_id) = &known.device_id else { return Ok(None) };
+
+            // If the device was deleted since, we can't check anything.
+            let Some(device) = store.get_device(&known.user_id, device_id).await? else {
+                return Ok(None);
+            };
+
+            (
+                Some(known.user_id.clone()),
+                Some(device_id.clone()),
+                device.curve25519_key() == Some(sender_key),
+            )
+        }
+    };
+
+    Ok((!is_consistent).then(|| StoreAnomaly::MismatchedSenderData {
+        room_id: session.room_id().to_owned(),
+        session_id: session.session_id().to_owned(),
+        user_id,
+        device_id,
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use assert_matches2::assert_matches;
+    use matrix_sdk_test::async_test;
+    use ruma::{device_id, room_id, user_id};
+
+    use super::{DiagnosticsReport, StoreAnomaly};
+    use crate::{
+        olm::{Account, SenderData},
+        store::{Changes, CryptoStore, DeviceChanges, MemoryStore, PendingChanges},
+        DeviceData,
+    };
+
+    async fn store_with_account() -> (MemoryStore, Account) {
+        let account = Account::with_device_id(user_id!("@alice:localhost"), device_id!("ALICE"));
+        let store = MemoryStore::new();
+
+        store
+            .save_pending_changes(PendingChanges { account: Some(account.deep_clone()) })
+            .await
+            .unwrap();
+        store
+            .save_changes(Changes {
+                devices: DeviceChanges {
+                    new: vec![DeviceData::from_account(&account)],
+                    ..Default::default()
+                },
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+        store.save_tracked_users(&[(account.user_id(), false)]).await.unwrap();
+
+        (store, account)
+    }
+
+    #[async_test]
+    async fn test_healthy_store() {
+        let (store, account) = store_with_account().await;
+        let (outbound, inbound) =
+            account.create_group_session_pair_with_defaults(room_id!("!room:localhost")).await;
+
+        store
+            .save_changes(Changes {
+                inbound_group_sessions: vec![inbound],
+                outbound_group_sessions: vec![outbound],
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+
+        let report = DiagnosticsReport::collect(&store).await.unwrap();
+
+        assert!(report.is_healthy(), "Unexpected anomalies: {:?}", report.anomalies);
+        assert_eq!(report.tracked_user_count, 1);
+        assert_eq!(report.inbound_group_session_count, 1);
+    }
+
+    #[async_test]
+    async fn test_missing_account() {
+        let report = DiagnosticsReport::collect(&MemoryStore::new()).await.unwrap();
+
+        assert_eq!(report.anomalies, vec![StoreAnomaly::MissingAccount]);
+        assert!(!report.anomalies[0].is_repairable());
+    }
+
+    #[async_test]
+    async fn test_repair_tracked_users() {
+        let (store, _) = store_with_account().await;
+        let stale_user_id = user_id!("@bob:localhost");
+        store.save_tracked_users(&[(stale_user_id, false)]).await.unwrap();
+
+        let report = DiagnosticsReport::collect(&store).await.unwrap();
+        assert_eq!(
+            report.anomalies,
+            vec![StoreAnomaly::StaleTrackedUser { user_id: stale_user_id.to_owned() }]
+        );
+
+        assert_eq!(report.repair(&store).await.unwrap(), 1);
+
+        let tracked_users = store.load_tracked_users().await.unwrap();
+        let stale_user = tracked_users.iter().find(|user| user.user_id == stale_user_id).unwrap();
+        assert!(stale_user.dirty);
+
+        // A dirty user is expected to have no known keys.
+        assert!(DiagnosticsReport::collect(&store).await.unwrap().is_healthy());
+    }
+
+    #[async_test]
+    async fn test_repair_mismatched_sender_data() {
+        let (store, account) = store_with_account().await;
+        let room_id = room_id!("!room:localhost");
+        let (_, mut inbound) = account.create_group_session_pair_with_defaults(room_id).await;
+
+        // Attribute the session to another device.
+        let other_account = Account::with_device_id(account.user_id(), device_id!("OTHER"));
+        inbound.sender_data = SenderData::device_info(
+            DeviceData::from_account(&other_account).as_device_keys().clone(),
+        );
+        store
+            .save_changes(Changes { inbound_group_sessions: vec![inbound], ..Default::default() })
+            .await
+            .unwrap();
+
+        let report = DiagnosticsReport::collect(&store).await.unwrap();
+        assert_eq!(report.anomalies.len(), 1);
+        assert_matches!(
+            &report.anomalies[0],
+            StoreAnomaly::MismatchedSenderData { room_id: anomaly_room_id, device_id, .. }
+        );
+        assert_eq!(anomaly_room_id, room_id);
+        assert_eq!(device_id.as_deref(), Some(device_id!("OTHER")));
+
+        assert_eq!(report.repair(&store).await.unwrap(), 1);
+        assert!(DiagnosticsReport::collect(&store).await.unwrap().is_healthy());
+    }
+
+    #[async_test]
+    async fn test_repair_orphaned_outbound_group_session() {
+        let (store, account) = store_with_account().await;
+        let room_id = room_id!("!room:localhost");
+        let (_, inbound) = account.create_group_session_pair_with_defaults(room_id).await;
+        let (outbound, _) = account.create_group_session_pair_with_defaults(room_id).await;
+
+        store
+            .save_changes(Changes {
+                inbound_group_sessions: vec![inbound],
+                outbound_group_sessions: vec![outbound.clone()],
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+
+        let report = DiagnosticsReport::collect(&store).await.unwrap();
+        assert_eq!(
+            report.anomalies,
+            vec![StoreAnomaly::OrphanedOutboundGroupSession {
+                room_id: room_id.to_owned(),
+                session_id: outbound.session_id().to_owned(),
+            }]
+        );
+
+        assert_eq!(report.repair(&store).await.unwrap(), 1);
+
+        let outbound = store.get_outbound_group_session(room_id).await.unwrap().unwrap();
+        assert!(outbound.invalidated());
+        assert!(DiagnosticsReport::collect(&store).await.unwrap().is_healthy());
+    }
+}
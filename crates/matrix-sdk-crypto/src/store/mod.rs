@@ -82,6 +82,7 @@ use crate::{
 
 pub mod caches;
 mod crypto_store_wrapper;
+pub mod diagnostics;
 mod error;
 mod memorystore;
 mod traits;
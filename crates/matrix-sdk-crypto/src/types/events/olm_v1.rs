@@ -26,6 +26,7 @@ use super::{
     dummy::DummyEventContent,
     forwarded_room_key::ForwardedRoomKeyContent,
     room_key::RoomKeyContent,
+    room_key_bundle::RoomKeyBundleContent,
     room_key_request::{self, SupportedKeyInfo},
     secret_send::SecretSendContent,
     EventType,
@@ -77,6 +78,10 @@ impl DecryptedForwardedRoomKeyEvent {
 /// `m.olm.v1.curve25519-aes-sha2` algorithm
 pub type DecryptedSecretSendEvent = DecryptedOlmV1Event<SecretSendContent>;
 
+/// An `io.element.msc4268.room_key_bundle` event that was decrypted using the
+/// `m.olm.v1.curve25519-aes-sha2` algorithm
+pub type DecryptedRoomKeyBundleEvent = DecryptedOlmV1Event<RoomKeyBundleContent>;
+
 /// An enum over the various events that were decrypted using the
 /// `m.olm.v1.curve25519-aes-sha2` algorithm.
 #[derive(Debug)]
@@ -89,6 +94,8 @@ pub enum AnyDecryptedOlmEvent {
     SecretSend(DecryptedSecretSendEvent),
     /// The `m.dummy` decrypted to-device event.
     Dummy(DecryptedDummyEvent),
+    /// The `io.element.msc4268.room_key_bundle` decrypted to-device event.
+    RoomKeyBundle(DecryptedRoomKeyBundleEvent),
     /// A decrypted to-device event of an unknown or custom type.
     Custom(Box<ToDeviceCustomEvent>),
 }
@@ -102,6 +109,7 @@ impl AnyDecryptedOlmEvent {
             AnyDecryptedOlmEvent::SecretSend(e) => &e.sender,
             AnyDecryptedOlmEvent::Custom(e) => &e.sender,
             AnyDecryptedOlmEvent::Dummy(e) => &e.sender,
+            AnyDecryptedOlmEvent::RoomKeyBundle(e) => &e.sender,
         }
     }
 
@@ -113,6 +121,7 @@ impl AnyDecryptedOlmEvent {
             AnyDecryptedOlmEvent::SecretSend(e) => &e.recipient,
             AnyDecryptedOlmEvent::Custom(e) => &e.recipient,
             AnyDecryptedOlmEvent::Dummy(e) => &e.recipient,
+            AnyDecryptedOlmEvent::RoomKeyBundle(e) => &e.recipient,
         }
     }
 
@@ -124,6 +133,7 @@ impl AnyDecryptedOlmEvent {
             AnyDecryptedOlmEvent::SecretSend(e) => &e.keys,
             AnyDecryptedOlmEvent::Custom(e) => &e.keys,
             AnyDecryptedOlmEvent::Dummy(e) => &e.keys,
+            AnyDecryptedOlmEvent::RoomKeyBundle(e) => &e.keys,
         }
     }
 
@@ -135,6 +145,7 @@ impl AnyDecryptedOlmEvent {
             AnyDecryptedOlmEvent::SecretSend(e) => &e.recipient_keys,
             AnyDecryptedOlmEvent::Custom(e) => &e.recipient_keys,
             AnyDecryptedOlmEvent::Dummy(e) => &e.recipient_keys,
+            AnyDecryptedOlmEvent::RoomKeyBundle(e) => &e.recipient_keys,
         }
     }
 
@@ -146,6 +157,7 @@ impl AnyDecryptedOlmEvent {
             AnyDecryptedOlmEvent::ForwardedRoomKey(e) => e.content.event_type(),
             AnyDecryptedOlmEvent::SecretSend(e) => e.content.event_type(),
             AnyDecryptedOlmEvent::Dummy(e) => e.content.event_type(),
+            AnyDecryptedOlmEvent::RoomKeyBundle(e) => e.content.event_type(),
         }
     }
 
@@ -157,6 +169,7 @@ impl AnyDecryptedOlmEvent {
             AnyDecryptedOlmEvent::ForwardedRoomKey(e) => e.sender_device_keys.as_ref(),
             AnyDecryptedOlmEvent::SecretSend(e) => e.sender_device_keys.as_ref(),
             AnyDecryptedOlmEvent::Dummy(e) => e.sender_device_keys.as_ref(),
+            AnyDecryptedOlmEvent::RoomKeyBundle(e) => e.sender_device_keys.as_ref(),
         }
     }
 }
@@ -256,6 +269,9 @@ impl<'de> Deserialize<'de> for AnyDecryptedOlmEvent {
             "m.forwarded_room_key" => AnyDecryptedOlmEvent::ForwardedRoomKey(from_str(json)?),
             "m.secret.send" => AnyDecryptedOlmEvent::SecretSend(from_str(json)?),
             "m.dummy" => AnyDecryptedOlmEvent::Dummy(from_str(json)?),
+            "io.element.msc4268.room_key_bundle" => {
+                AnyDecryptedOlmEvent::RoomKeyBundle(from_str(json)?)
+            }
 
             _ => AnyDecryptedOlmEvent::Custom(from_str(json)?),
         })
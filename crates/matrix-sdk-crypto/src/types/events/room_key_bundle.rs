@@ -0,0 +1,98 @@
+// Copyright 2025 The Matrix.org Foundation C.I.C.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Types for the `io.element.msc4268.room_key_bundle` to-device events, as
+//! defined in [MSC4268].
+//!
+//! [MSC4268]: https://github.com/matrix-org/matrix-spec-proposals/pull/4268
+
+use std::collections::BTreeMap;
+
+use ruma::{events::room::EncryptedFile, OwnedRoomId};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use super::EventType;
+
+/// The content of an `io.element.msc4268.room_key_bundle` to-device event.
+///
+/// It points to an encrypted file, uploaded to the media repository, that
+/// contains the bundle of room keys that a user shares with a user they
+/// invited to a room.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct RoomKeyBundleContent {
+    /// The room that the room keys of the bundle are for.
+    pub room_id: OwnedRoomId,
+
+    /// The location and encryption info of the bundle.
+    pub file: EncryptedFile,
+
+    /// Any other, custom and non-specced fields of the content.
+    #[serde(flatten)]
+    other: BTreeMap<String, Value>,
+}
+
+impl RoomKeyBundleContent {
+    /// Create a new `io.element.msc4268.room_key_bundle` event content.
+    pub fn new(room_id: OwnedRoomId, file: EncryptedFile) -> Self {
+        Self { room_id, file, other: Default::default() }
+    }
+}
+
+impl EventType for RoomKeyBundleContent {
+    const EVENT_TYPE: &'static str = "io.element.msc4268.room_key_bundle";
+}
+
+#[cfg(test)]
+pub(super) mod tests {
+    use serde_json::{json, Value};
+
+    use super::RoomKeyBundleContent;
+
+    pub fn json() -> Value {
+        json!({
+            "room_id": "!Cuyf34gef24t:localhost",
+            "file": {
+                "url": "mxc://localhost/bundle",
+                "key": {
+                    "kty": "oct",
+                    "key_ops": ["encrypt", "decrypt"],
+                    "alg": "A256CTR",
+                    "k": "qcHVMSgYg-71CauWBezXI5qkaRb0LuIy-Wx5kIaHMIA",
+                    "ext": true,
+                },
+                "iv": "X85+XgHN+HEAAAAAAAAAAA",
+                "hashes": {
+                    "sha256": "5qG4fFnbbVdlAB1Q72JDKwCagV6Dbkx9uds4rSak37c",
+                },
+                "v": "v2",
+            },
+            "m.custom": "something custom",
+        })
+    }
+
+    #[test]
+    fn deserialization() -> Result<(), serde_json::Error> {
+        let json = json();
+        let content: RoomKeyBundleContent = serde_json::from_value(json.clone())?;
+
+        assert_eq!(content.room_id.as_str(), "!Cuyf34gef24t:localhost");
+        assert_eq!(content.file.url.as_str(), "mxc://localhost/bundle");
+
+        let serialized = serde_json::to_value(content)?;
+        assert_eq!(json, serialized);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,56 @@
+// Copyright 2025 The Matrix.org Foundation C.I.C.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Types for sharing the history of a room with users that are invited to it,
+//! as defined in [MSC4268].
+//!
+//! [MSC4268]: https://github.com/matrix-org/matrix-spec-proposals/pull/4268
+
+use ruma::OwnedUserId;
+use serde::{Deserialize, Serialize};
+use vodozemac::Curve25519PublicKey;
+
+use super::{
+    deserialize_curve_key, events::room_key_bundle::RoomKeyBundleContent, serialize_curve_key,
+};
+use crate::olm::ExportedRoomKey;
+
+/// A bundle of room keys, that a user shares with a user they invited to a
+/// room so they can read the history of the room.
+///
+/// The bundle is serialized to JSON, encrypted and uploaded to the media
+/// repository. Its location is then sent to the devices of the invited user in
+/// an `io.element.msc4268.room_key_bundle` to-device event.
+#[derive(Deserialize, Serialize)]
+#[allow(missing_debug_implementations)]
+pub struct RoomKeyBundle {
+    /// The room keys of the bundle.
+    #[serde(default)]
+    pub room_keys: Vec<ExportedRoomKey>,
+}
+
+/// The information about a bundle of room keys that we received, and that we
+/// can import once we join the room.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct StoredRoomKeyBundleData {
+    /// The user that sent us the bundle.
+    pub sender_user: OwnedUserId,
+
+    /// The Curve25519 key of the device that sent us the bundle.
+    #[serde(deserialize_with = "deserialize_curve_key", serialize_with = "serialize_curve_key")]
+    pub sender_key: Curve25519PublicKey,
+
+    /// The content of the to-device event that pointed to the bundle.
+    pub bundle_data: RoomKeyBundleContent,
+}
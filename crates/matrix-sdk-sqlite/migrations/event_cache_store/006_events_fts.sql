@@ -0,0 +1,20 @@
+-- Full-text index over the events, to search them locally.
+--
+-- Each row shares its `rowid` with the row of the indexed event in `events`.
+CREATE VIRTUAL TABLE "events_fts" USING fts5(
+    -- The search tokens of the event, separated by spaces. They are hashed
+    -- with the store cipher when there is one.
+    "tokens",
+    -- The `origin_server_ts` of the event, to sort the results.
+    "origin_server_ts" UNINDEXED,
+    -- The tokens are already normalized, there is no need for more than
+    -- splitting them on spaces.
+    tokenize = 'ascii'
+);
+
+-- Remove an event from the index when it's removed, including when it's
+-- removed by cascading from `linked_chunks`.
+CREATE TRIGGER "events_fts_delete" AFTER DELETE ON "events"
+BEGIN
+    DELETE FROM "events_fts" WHERE rowid = old.rowid;
+END;
@@ -0,0 +1,9 @@
+-- Add the room and the kind of the media content, to apply scoped media
+-- retention policies. They are unknown for the existing media content.
+ALTER TABLE "media"
+    ADD COLUMN "room_id" BLOB NULL;
+ALTER TABLE "media"
+    ADD COLUMN "kind" TEXT NULL;
+
+CREATE INDEX "media_room_id_idx"
+    ON "media" ("room_id");
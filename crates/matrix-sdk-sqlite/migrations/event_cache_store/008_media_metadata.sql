@@ -0,0 +1,5 @@
+-- Add the metadata of the media content, encrypted like the data, to be able
+-- to list the media content even when the other columns are hashed. It is
+-- unknown for the existing media content.
+ALTER TABLE "media"
+    ADD COLUMN "metadata" BLOB NULL;
@@ -0,0 +1,3 @@
+-- Add a pinned column, defaulting to FALSE for all media content.
+ALTER TABLE "media"
+    ADD COLUMN "pinned" BOOLEAN NOT NULL DEFAULT FALSE;
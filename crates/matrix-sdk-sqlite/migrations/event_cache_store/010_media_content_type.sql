@@ -0,0 +1,7 @@
+-- Add the MIME type of the media content, to report the usage of the media
+-- cache per MIME type. It is unknown for the existing media content.
+ALTER TABLE "media"
+    ADD COLUMN "content_type" BLOB NULL;
+
+CREATE INDEX "media_content_type_idx"
+    ON "media" ("content_type");
@@ -0,0 +1,4 @@
+-- Add the token to paginate forwards from the start of a gap. It is unknown
+-- for the existing gaps.
+ALTER TABLE "gaps"
+    ADD COLUMN "next_token" BLOB NULL;
@@ -51,7 +51,7 @@ use crate::{
         repeat_vars, Key, SqliteAsyncConnExt, SqliteKeyValueStoreAsyncConnExt,
         SqliteKeyValueStoreConnExt,
     },
-    OpenStoreError,
+    ChangePassphraseError, OpenStoreError, StoreSecret,
 };
 
 /// A sqlite based cryptostore.
@@ -78,13 +78,24 @@ impl SqliteCryptoStore {
     pub async fn open(
         path: impl AsRef<Path>,
         passphrase: Option<&str>,
+    ) -> Result<Self, OpenStoreError> {
+        Self::open_with_secret(path, passphrase.map(StoreSecret::Passphrase)).await
+    }
+
+    /// Open the sqlite-based crypto store at the given path using the given
+    /// secret to encrypt private data.
+    ///
+    /// Unlike [`SqliteCryptoStore::open()`], the secret can be a raw key.
+    pub async fn open_with_secret(
+        path: impl AsRef<Path>,
+        secret: Option<StoreSecret<'_>>,
     ) -> Result<Self, OpenStoreError> {
         let path = path.as_ref();
         fs::create_dir_all(path).await.map_err(OpenStoreError::CreateDir)?;
         let cfg = deadpool_sqlite::Config::new(path.join("matrix-sdk-crypto.sqlite3"));
         let pool = cfg.create_pool(Runtime::Tokio1)?;
 
-        Self::open_with_pool(pool, passphrase).await
+        Self::open_with_pool_and_secret(pool, secret).await
     }
 
     /// Create a sqlite-based crypto store using the given sqlite database pool.
@@ -92,6 +103,15 @@ impl SqliteCryptoStore {
     pub async fn open_with_pool(
         pool: SqlitePool,
         passphrase: Option<&str>,
+    ) -> Result<Self, OpenStoreError> {
+        Self::open_with_pool_and_secret(pool, passphrase.map(StoreSecret::Passphrase)).await
+    }
+
+    /// Create a sqlite-based crypto store using the given sqlite database pool.
+    /// The given secret will be used to encrypt private data.
+    pub async fn open_with_pool_and_secret(
+        pool: SqlitePool,
+        secret: Option<StoreSecret<'_>>,
     ) -> Result<Self, OpenStoreError> {
         let conn = pool.get().await?;
         conn.set_journal_size_limit().await?;
@@ -100,8 +120,8 @@ impl SqliteCryptoStore {
         run_migrations(&conn, version).await?;
         conn.optimize().await?;
 
-        let store_cipher = match passphrase {
-            Some(p) => Some(Arc::new(conn.get_or_create_store_cipher(p).await?)),
+        let store_cipher = match secret {
+            Some(s) => Some(Arc::new(conn.get_or_create_store_cipher(s).await?)),
             None => None,
         };
 
@@ -113,6 +133,26 @@ impl SqliteCryptoStore {
         })
     }
 
+    /// Change the secret protecting the private data of this store, from `old`
+    /// to `new`.
+    ///
+    /// Only the store cipher is re-encrypted with the new secret, the data of
+    /// the store is left untouched, so this is cheap even for big stores. A
+    /// passphrase can be changed into a raw key, and vice versa. The store
+    /// must be opened with the new secret afterwards.
+    pub async fn change_passphrase(
+        &self,
+        old: StoreSecret<'_>,
+        new: StoreSecret<'_>,
+    ) -> Result<(), ChangePassphraseError> {
+        if self.store_cipher.is_none() {
+            return Err(ChangePassphraseError::NotEncrypted);
+        }
+
+        let conn = self.pool.get().await?;
+        conn.change_store_cipher_secret(old, new).await
+    }
+
     fn encode_value(&self, value: Vec<u8>) -> Result<Vec<u8>> {
         if let Some(key) = &self.store_cipher {
             let encrypted = key.encrypt_value_data(value)?;
@@ -1408,6 +1448,7 @@ impl CryptoStore for SqliteCryptoStore {
 mod tests {
     use std::path::Path;
 
+    use assert_matches::assert_matches;
     use matrix_sdk_common::deserialized_responses::WithheldCode;
     use matrix_sdk_crypto::{
         cryptostore_integration_tests, cryptostore_integration_tests_time, olm::SenderDataType,
@@ -1421,6 +1462,7 @@ mod tests {
     use tokio::fs;
 
     use super::SqliteCryptoStore;
+    use crate::{ChangePassphraseError, OpenStoreError, StoreSecret};
 
     static TMP_DIR: Lazy<TempDir> = Lazy::new(|| tempdir().unwrap());
 
@@ -1803,6 +1845,79 @@ mod tests {
             .expect("Can't create a passphrase protected store")
     }
 
+    #[async_test]
+    async fn test_change_passphrase() {
+        let dir = tempdir().unwrap();
+        let old = StoreSecret::Passphrase("old_passphrase");
+        let new = StoreSecret::Passphrase("new_passphrase");
+
+        let store = SqliteCryptoStore::open_with_secret(dir.path(), Some(old)).await.unwrap();
+        store.set_custom_value("test_value", b"secret data".to_vec()).await.unwrap();
+
+        // The old passphrase must be the one of the store.
+        assert_matches!(
+            store.change_passphrase(new, new).await,
+            Err(ChangePassphraseError::Cipher(_))
+        );
+
+        store.change_passphrase(old, new).await.unwrap();
+        drop(store);
+
+        // The store can't be opened with the old passphrase anymore.
+        assert_matches!(
+            SqliteCryptoStore::open_with_secret(dir.path(), Some(old)).await,
+            Err(OpenStoreError::InitCipher(_))
+        );
+
+        // The data is still readable with the new passphrase.
+        let store = SqliteCryptoStore::open_with_secret(dir.path(), Some(new)).await.unwrap();
+        assert_eq!(
+            store.get_custom_value("test_value").await.unwrap().as_deref(),
+            Some(b"secret data".as_slice())
+        );
+    }
+
+    #[async_test]
+    async fn test_change_passphrase_to_key_and_back() {
+        let dir = tempdir().unwrap();
+        let passphrase = StoreSecret::Passphrase("passphrase");
+        let key = StoreSecret::Key(&[7; 32]);
+
+        let store =
+            SqliteCryptoStore::open_with_secret(dir.path(), Some(passphrase)).await.unwrap();
+        store.set_custom_value("test_value", b"secret data".to_vec()).await.unwrap();
+        store.change_passphrase(passphrase, key).await.unwrap();
+        drop(store);
+
+        let store = SqliteCryptoStore::open_with_secret(dir.path(), Some(key)).await.unwrap();
+        assert_eq!(
+            store.get_custom_value("test_value").await.unwrap().as_deref(),
+            Some(b"secret data".as_slice())
+        );
+        store.change_passphrase(key, passphrase).await.unwrap();
+        drop(store);
+
+        let store =
+            SqliteCryptoStore::open_with_secret(dir.path(), Some(passphrase)).await.unwrap();
+        assert_eq!(
+            store.get_custom_value("test_value").await.unwrap().as_deref(),
+            Some(b"secret data".as_slice())
+        );
+    }
+
+    #[async_test]
+    async fn test_change_passphrase_of_unencrypted_store() {
+        let dir = tempdir().unwrap();
+        let store = SqliteCryptoStore::open(dir.path(), None).await.unwrap();
+
+        assert_matches!(
+            store
+                .change_passphrase(StoreSecret::Passphrase("old"), StoreSecret::Passphrase("new"))
+                .await,
+            Err(ChangePassphraseError::NotEncrypted)
+        );
+    }
+
     cryptostore_integration_tests!();
     cryptostore_integration_tests_time!();
 }
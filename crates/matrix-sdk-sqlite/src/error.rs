@@ -67,6 +67,33 @@ pub enum OpenStoreError {
     SaveCipher(#[source] rusqlite::Error),
 }
 
+/// All the errors that can occur when changing the passphrase of a SQLite
+/// store.
+#[derive(Error, Debug)]
+#[non_exhaustive]
+pub enum ChangePassphraseError {
+    /// The store isn't encrypted, so it doesn't have a passphrase.
+    #[error("The store isn't encrypted")]
+    NotEncrypted,
+
+    /// The old secret didn't unlock the store cipher, or the store cipher
+    /// couldn't be exported with the new secret.
+    #[error(transparent)]
+    Cipher(#[from] matrix_sdk_store_encryption::Error),
+
+    /// The store cipher was changed concurrently.
+    #[error("The store cipher was changed concurrently")]
+    ConcurrentChange,
+
+    /// Failed to get a DB connection from the pool.
+    #[error(transparent)]
+    Pool(#[from] PoolError),
+
+    /// Failed to load or save the store cipher.
+    #[error(transparent)]
+    Sqlite(#[from] rusqlite::Error),
+}
+
 #[derive(Debug, Error)]
 pub enum Error {
     #[error(transparent)]
@@ -107,6 +134,9 @@ pub enum Error {
 
     #[error("The store contains invalid data: {details}")]
     InvalidData { details: String },
+
+    #[error("Full-text search is unavailable, the SQLite library doesn't support FTS5")]
+    FullTextSearchUnavailable,
 }
 
 macro_rules! impl_from {
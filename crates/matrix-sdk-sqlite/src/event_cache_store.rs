@@ -18,14 +18,16 @@ use std::{borrow::Cow, fmt, iter::once, path::Path, sync::Arc};
 
 use async_trait::async_trait;
 use deadpool_sqlite::{Object as SqliteAsyncConn, Pool as SqlitePool, Runtime};
+use itertools::Itertools;
 use matrix_sdk_base::{
     event_cache::{
         store::{
             media::{
-                EventCacheStoreMedia, IgnoreMediaRetentionPolicy, MediaRetentionPolicy,
-                MediaService,
+                EventCacheStoreMedia, IgnoreMediaRetentionPolicy, MediaCacheEntry, MediaCacheUsage,
+                MediaCacheUsageStats, MediaKind, MediaOrigin, MediaRetentionPolicy,
+                MediaRetentionScope, MediaService, ScopedMediaRetentionPolicies,
             },
-            EventCacheStore,
+            search, EventCacheStore,
         },
         Event, Gap,
     },
@@ -35,28 +37,36 @@ use matrix_sdk_base::{
     media::{MediaRequestParameters, UniqueKey},
 };
 use matrix_sdk_store_encryption::StoreCipher;
-use ruma::{time::SystemTime, EventId, MilliSecondsSinceUnixEpoch, MxcUri, OwnedEventId, RoomId};
-use rusqlite::{params_from_iter, OptionalExtension, ToSql, Transaction, TransactionBehavior};
+use ruma::{
+    time::SystemTime, EventId, MilliSecondsSinceUnixEpoch, MxcUri, OwnedEventId, OwnedMxcUri,
+    OwnedRoomId, RoomId,
+};
+use rusqlite::{
+    params_from_iter, types::Value, OptionalExtension, ToSql, Transaction, TransactionBehavior,
+};
+use serde::{Deserialize, Serialize};
 use tokio::fs;
-use tracing::{debug, trace};
+use tracing::{debug, trace, warn};
 
 use crate::{
     error::{Error, Result},
     utils::{
-        repeat_vars, time_to_timestamp, Key, SqliteAsyncConnExt, SqliteKeyValueStoreAsyncConnExt,
-        SqliteKeyValueStoreConnExt, SqliteTransactionExt,
+        repeat_vars, time_to_timestamp, timestamp_to_time, Key, SqliteAsyncConnExt,
+        SqliteKeyValueStoreAsyncConnExt, SqliteKeyValueStoreConnExt, SqliteTransactionExt,
     },
-    OpenStoreError,
+    ChangePassphraseError, OpenStoreError, StoreSecret,
 };
 
 mod keys {
     // Entries in Key-value store
     pub const MEDIA_RETENTION_POLICY: &str = "media_retention_policy";
+    pub const SCOPED_MEDIA_RETENTION_POLICIES: &str = "scoped_media_retention_policies";
     pub const LAST_MEDIA_CLEANUP_TIME: &str = "last_media_cleanup_time";
 
     // Tables
     pub const LINKED_CHUNKS: &str = "linked_chunks";
     pub const MEDIA: &str = "media";
+    pub const EVENTS_FTS: &str = "events_fts";
 }
 
 /// Identifier of the latest database version.
@@ -64,7 +74,17 @@ mod keys {
 /// This is used to figure whether the SQLite database requires a migration.
 /// Every new SQL migration should imply a bump of this number, and changes in
 /// the [`run_migrations`] function.
-const DATABASE_VERSION: u8 = 5;
+const DATABASE_VERSION: u8 = 11;
+
+/// The metadata of a media content, that is stored encrypted because the
+/// corresponding columns are hashed.
+#[derive(Debug, Serialize, Deserialize)]
+struct MediaMetadata {
+    uri: OwnedMxcUri,
+    room_id: Option<OwnedRoomId>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    content_type: Option<String>,
+}
 
 /// The string used to identify a chunk of type events, in the `type` field in
 /// the database.
@@ -79,6 +99,9 @@ pub struct SqliteEventCacheStore {
     store_cipher: Option<Arc<StoreCipher>>,
     pool: SqlitePool,
     media_service: MediaService,
+    /// Whether the full-text index over the events is available, which
+    /// depends on the SQLite library having been built with FTS5.
+    full_text_search: bool,
 }
 
 #[cfg(not(tarpaulin_include))]
@@ -94,10 +117,21 @@ impl SqliteEventCacheStore {
     pub async fn open(
         path: impl AsRef<Path>,
         passphrase: Option<&str>,
+    ) -> Result<Self, OpenStoreError> {
+        Self::open_with_secret(path, passphrase.map(StoreSecret::Passphrase)).await
+    }
+
+    /// Open the SQLite-based event cache store at the given path using the
+    /// given secret to encrypt private data.
+    ///
+    /// Unlike [`SqliteEventCacheStore::open()`], the secret can be a raw key.
+    pub async fn open_with_secret(
+        path: impl AsRef<Path>,
+        secret: Option<StoreSecret<'_>>,
     ) -> Result<Self, OpenStoreError> {
         let pool = create_pool(path.as_ref()).await?;
 
-        Self::open_with_pool(pool, passphrase).await
+        Self::open_with_pool_and_secret(pool, secret).await
     }
 
     /// Open an SQLite-based event cache store using the given SQLite database
@@ -105,6 +139,15 @@ impl SqliteEventCacheStore {
     pub async fn open_with_pool(
         pool: SqlitePool,
         passphrase: Option<&str>,
+    ) -> Result<Self, OpenStoreError> {
+        Self::open_with_pool_and_secret(pool, passphrase.map(StoreSecret::Passphrase)).await
+    }
+
+    /// Open an SQLite-based event cache store using the given SQLite database
+    /// pool. The given secret will be used to encrypt private data.
+    pub async fn open_with_pool_and_secret(
+        pool: SqlitePool,
+        secret: Option<StoreSecret<'_>>,
     ) -> Result<Self, OpenStoreError> {
         let conn = pool.get().await?;
         conn.set_journal_size_limit().await?;
@@ -113,17 +156,60 @@ impl SqliteEventCacheStore {
         run_migrations(&conn, version).await?;
         conn.optimize().await?;
 
-        let store_cipher = match passphrase {
-            Some(p) => Some(Arc::new(conn.get_or_create_store_cipher(p).await?)),
+        let store_cipher = match secret {
+            Some(s) => Some(Arc::new(conn.get_or_create_store_cipher(s).await?)),
             None => None,
         };
 
         let media_service = MediaService::new();
         let media_retention_policy = conn.get_serialized_kv(keys::MEDIA_RETENTION_POLICY).await?;
+        let scoped_media_retention_policies =
+            conn.get_serialized_kv(keys::SCOPED_MEDIA_RETENTION_POLICIES).await?;
         let last_media_cleanup_time = conn.get_serialized_kv(keys::LAST_MEDIA_CLEANUP_TIME).await?;
-        media_service.restore(media_retention_policy, last_media_cleanup_time);
+        media_service.restore(
+            media_retention_policy,
+            scoped_media_retention_policies,
+            last_media_cleanup_time,
+        );
+
+        let full_text_search = conn
+            .query_row(
+                "SELECT EXISTS (SELECT 1 FROM sqlite_master WHERE type = 'table' AND name = ?)",
+                (keys::EVENTS_FTS,),
+                |row| row.get(0),
+            )
+            .await
+            .map_err(Error::from)?;
+
+        let store = Self { store_cipher, pool, media_service, full_text_search };
 
-        Ok(Self { store_cipher, pool, media_service })
+        // The full-text index was just created, index the events that were already in
+        // the store.
+        if full_text_search && (1..6).contains(&version) {
+            store.index_all_events().await?;
+        }
+
+        Ok(store)
+    }
+
+    /// Change the secret protecting the private data of this store, from `old`
+    /// to `new`.
+    ///
+    /// Only the store cipher is re-encrypted with the new secret, the data of
+    /// the store is left untouched, so this is cheap even for big stores. A
+    /// passphrase can be changed into a raw key, and vice versa. The store
+    /// must be opened with the new secret afterwards.
+    pub async fn change_passphrase(
+        &self,
+        old: StoreSecret<'_>,
+        new: StoreSecret<'_>,
+    ) -> Result<(), ChangePassphraseError> {
+        if self.store_cipher.is_none() {
+            return Err(ChangePassphraseError::NotEncrypted);
+        }
+
+        let conn = self.pool.get().await?;
+        conn.change_store_cipher_secret(old, new).await
     }
 
     fn encode_value(&self, value: Vec<u8>) -> Result<Vec<u8>> {
@@ -154,6 +240,68 @@ impl SqliteEventCacheStore {
         }
     }
 
+    /// Encode a search token for the full-text index, so that it is not
+    /// stored in clear when there is a store cipher.
+    fn encode_search_token(&self, token: &str) -> String {
+        if let Some(store_cipher) = &self.store_cipher {
+            store_cipher
+                .hash_key(keys::EVENTS_FTS, token.as_bytes())
+                .iter()
+                .map(|byte| format!("{byte:02x}"))
+                .collect()
+        } else {
+            token.to_owned()
+        }
+    }
+
+    /// Add an event to the full-text index, using the `rowid` of its row in
+    /// the `events` table.
+    ///
+    /// This is a no-op if the full-text index isn't available, or if the event
+    /// has nothing to index.
+    fn index_event(&self, txn: &Transaction<'_>, rowid: i64, event: &Event) -> Result<()> {
+        if !self.full_text_search {
+            return Ok(());
+        }
+
+        let tokens = search::event_search_tokens(event);
+        if tokens.is_empty() {
+            return Ok(());
+        }
+
+        let tokens = tokens.iter().map(|token| self.encode_search_token(token)).join(" ");
+        let origin_server_ts = search::event_timestamp(event).map(|ts| u64::from(ts.get()));
+
+        txn.execute(
+            "INSERT INTO events_fts(rowid, tokens, origin_server_ts) VALUES (?, ?, ?)",
+            (rowid, tokens, origin_server_ts),
+        )?;
+
+        Ok(())
+    }
+
+    /// Add all the events of the store to the full-text index.
+    async fn index_all_events(&self) -> Result<()> {
+        let this = self.clone();
+
+        with_immediate_transaction(self.acquire().await?, move |txn| {
+            txn.execute("DELETE FROM events_fts", ())?;
+
+            let rows = txn
+                .prepare("SELECT rowid, content FROM events")?
+                .query_map((), |row| Ok((row.get::<_, i64>(0)?, row.get::<_, Vec<u8>>(1)?)))?
+                .collect::<Result<Vec<_>, _>>()?;
+
+            for (rowid, content) in rows {
+                let event = serde_json::from_slice(&this.decode_value(&content)?)?;
+                this.index_event(txn, rowid, &event)?;
+            }
+
+            Ok(())
+        })
+        .await
+    }
+
     async fn acquire(&self) -> Result<SqliteAsyncConn> {
         Ok(self.pool.get().await?)
     }
@@ -245,14 +393,20 @@ impl TransactionExtForLinkedChunks for Transaction<'_> {
     ) -> Result<Gap> {
         // There's at most one row for it in the database, so a call to `query_row` is
         // sufficient.
-        let encoded_prev_token: Vec<u8> = self.query_row(
-            "SELECT prev_token FROM gaps WHERE chunk_id = ? AND room_id = ?",
+        let (encoded_prev_token, encoded_next_token): (Vec<u8>, Option<Vec<u8>>) = self.query_row(
+            "SELECT prev_token, next_token FROM gaps WHERE chunk_id = ? AND room_id = ?",
             (chunk_id.index(), &room_id),
-            |row| row.get(0),
+            |row| Ok((row.get(0)?, row.get(1)?)),
         )?;
         let prev_token_bytes = store.decode_value(&encoded_prev_token)?;
         let prev_token = serde_json::from_slice(&prev_token_bytes)?;
-        Ok(Gap { prev_token })
+        let next_token = encoded_next_token
+            .map(|encoded_next_token| -> Result<String> {
+                let next_token_bytes = store.decode_value(&encoded_next_token)?;
+                Ok(serde_json::from_slice(&next_token_bytes)?)
+            })
+            .transpose()?;
+        Ok(Gap { prev_token, next_token })
     }
 
     fn load_events_content(
@@ -351,6 +505,78 @@ async fn run_migrations(conn: &SqliteAsyncConn, version: u8) -> Result<()> {
         .await?;
     }
 
+    if version < 6 {
+        conn.with_transaction(|txn| {
+            // The full-text index is optional: it's only created if the SQLite library
+            // supports FTS5.
+            let has_fts5: bool =
+                txn.query_row("SELECT sqlite_compileoption_used('ENABLE_FTS5')", (), |row| {
+                    row.get(0)
+                })?;
+
+            if has_fts5 {
+                txn.execute_batch(include_str!(
+                    "../migrations/event_cache_store/006_events_fts.sql"
+                ))?;
+            } else {
+                warn!("SQLite was built without FTS5, the events won't be searchable");
+            }
+
+            txn.set_db_version(6)
+        })
+        .await?;
+    }
+
+    if version < 7 {
+        conn.with_transaction(|txn| {
+            txn.execute_batch(include_str!(
+                "../migrations/event_cache_store/007_media_scopes.sql"
+            ))?;
+            txn.set_db_version(7)
+        })
+        .await?;
+    }
+
+    if version < 8 {
+        conn.with_transaction(|txn| {
+            txn.execute_batch(include_str!(
+                "../migrations/event_cache_store/008_media_metadata.sql"
+            ))?;
+            txn.set_db_version(8)
+        })
+        .await?;
+    }
+
+    if version < 9 {
+        conn.with_transaction(|txn| {
+            txn.execute_batch(include_str!(
+                "../migrations/event_cache_store/009_media_pinned.sql"
+            ))?;
+            txn.set_db_version(9)
+        })
+        .await?;
+    }
+
+    if version < 10 {
+        conn.with_transaction(|txn| {
+            txn.execute_batch(include_str!(
+                "../migrations/event_cache_store/010_media_content_type.sql"
+            ))?;
+            txn.set_db_version(10)
+        })
+        .await?;
+    }
+
+    if version < 11 {
+        conn.with_transaction(|txn| {
+            txn.execute_batch(include_str!(
+                "../migrations/event_cache_store/011_gaps_next_token.sql"
+            ))?;
+            txn.set_db_version(11)
+        })
+        .await?;
+    }
+
     Ok(())
 }
 
@@ -428,6 +654,13 @@ impl EventCacheStore for SqliteEventCacheStore {
                     Update::NewGapChunk { previous, new, next, gap } => {
                         let serialized = serde_json::to_vec(&gap.prev_token)?;
                         let prev_token = this.encode_value(serialized)?;
+                        let next_token = gap
+                            .next_token
+                            .as_ref()
+                            .map(|next_token| -> Result<Vec<u8>> {
+                                this.encode_value(serde_json::to_vec(next_token)?)
+                            })
+                            .transpose()?;
 
                         let previous = previous.as_ref().map(ChunkIdentifier::index);
                         let new = new.index();
@@ -451,10 +684,10 @@ impl EventCacheStore for SqliteEventCacheStore {
                         // Insert the gap's value.
                         txn.execute(
                             r#"
-                            INSERT INTO gaps(chunk_id, room_id, prev_token)
-                            VALUES (?, ?, ?)
+                            INSERT INTO gaps(chunk_id, room_id, prev_token, next_token)
+                            VALUES (?, ?, ?, ?)
                         "#,
-                            (new, &hashed_room_id, prev_token),
+                            (new, &hashed_room_id, prev_token, next_token),
                         )?;
                     }
 
@@ -504,6 +737,8 @@ impl EventCacheStore for SqliteEventCacheStore {
                             "#,
                                 (chunk_id, &hashed_room_id, event_id, content, index),
                             )?;
+
+                            this.index_event(txn, txn.last_insert_rowid(), &event)?;
                         }
                     }
 
@@ -527,6 +762,18 @@ impl EventCacheStore for SqliteEventCacheStore {
                         "#,
                             (content, event_id, &hashed_room_id, chunk_id, index,)
                         )?;
+
+                        // Re-index the event with its new content.
+                        if this.full_text_search {
+                            let rowid: i64 = txn.query_row(
+                                "SELECT rowid FROM events WHERE room_id = ? AND chunk_id = ? AND position = ?",
+                                (&hashed_room_id, chunk_id, index),
+                                |row| row.get(0),
+                            )?;
+
+                            txn.execute("DELETE FROM events_fts WHERE rowid = ?", (rowid,))?;
+                            this.index_event(txn, rowid, &event)?;
+                        }
                     }
 
                     Update::RemoveItem { at } => {
@@ -890,6 +1137,57 @@ impl EventCacheStore for SqliteEventCacheStore {
             .await
     }
 
+    async fn search_events(
+        &self,
+        room_id: &RoomId,
+        query: &str,
+        limit: usize,
+    ) -> Result<Vec<Event>, Self::Error> {
+        if !self.full_text_search {
+            return Err(Error::FullTextSearchUnavailable);
+        }
+
+        let tokens = search::tokenize(query);
+        if tokens.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        // Quote every token so they're not interpreted as FTS5 operators; listing them
+        // means that all of them must match.
+        let match_query =
+            tokens.iter().map(|token| format!("\"{}\"", self.encode_search_token(token))).join(" ");
+        let hashed_room_id = self.encode_key(keys::LINKED_CHUNKS, room_id);
+        let limit = i64::try_from(limit).unwrap_or(i64::MAX);
+        let this = self.clone();
+
+        self.acquire()
+            .await?
+            .with_transaction(move |txn| -> Result<_> {
+                let mut events = Vec::new();
+
+                for content in txn
+                    .prepare(
+                        r#"
+                        SELECT events.content FROM events_fts
+                        JOIN events ON events.rowid = events_fts.rowid
+                        WHERE events_fts MATCH ? AND events.room_id = ?
+                        ORDER BY events_fts.origin_server_ts DESC
+                        LIMIT ?
+                    "#,
+                    )?
+                    .query_map((match_query, hashed_room_id, limit), |row| {
+                        row.get::<_, Vec<u8>>(0)
+                    })?
+                {
+                    let content = content?;
+                    events.push(serde_json::from_slice(&this.decode_value(&content)?)?);
+                }
+
+                Ok(events)
+            })
+            .await
+    }
+
     async fn add_media_content(
         &self,
         request: &MediaRequestParameters,
@@ -899,6 +1197,18 @@ impl EventCacheStore for SqliteEventCacheStore {
         self.media_service.add_media_content(self, request, content, ignore_policy).await
     }
 
+    async fn add_media_content_with_origin(
+        &self,
+        request: &MediaRequestParameters,
+        content: Vec<u8>,
+        origin: &MediaOrigin,
+        ignore_policy: IgnoreMediaRetentionPolicy,
+    ) -> Result<()> {
+        self.media_service
+            .add_media_content_with_origin(self, request, content, origin, ignore_policy)
+            .await
+    }
+
     async fn replace_media_key(
         &self,
         from: &MediaRequestParameters,
@@ -911,9 +1221,28 @@ impl EventCacheStore for SqliteEventCacheStore {
         let new_format = self.encode_key(keys::MEDIA, to.format.unique_key());
 
         let conn = self.acquire().await?;
+
+        // The URI is also in the metadata, update it too.
+        let metadata = conn
+            .query_row(
+                "SELECT metadata FROM media WHERE uri = ? AND format = ?",
+                (prev_uri.clone(), prev_format.clone()),
+                |row| row.get::<_, Option<Vec<u8>>>(0),
+            )
+            .await
+            .optional()?
+            .flatten()
+            .map(|metadata| {
+                let mut metadata: MediaMetadata =
+                    serde_json::from_slice(&self.decode_value(&metadata)?)?;
+                metadata.uri = to.uri().to_owned();
+                self.encode_value(serde_json::to_vec(&metadata)?)
+            })
+            .transpose()?;
+
         conn.execute(
-            r#"UPDATE media SET uri = ?, format = ? WHERE uri = ? AND format = ?"#,
-            (new_uri, new_format, prev_uri, prev_format),
+            r#"UPDATE media SET uri = ?, format = ?, metadata = ? WHERE uri = ? AND format = ?"#,
+            (new_uri, new_format, metadata, prev_uri, prev_format),
         )
         .await?;
 
@@ -950,6 +1279,152 @@ impl EventCacheStore for SqliteEventCacheStore {
         Ok(())
     }
 
+    async fn remove_media_content_for_room(&self, room_id: &RoomId) -> Result<()> {
+        let room_id = self.encode_key(keys::MEDIA, room_id);
+
+        let conn = self.acquire().await?;
+        conn.execute("DELETE FROM media WHERE room_id = ?", (room_id,)).await?;
+
+        Ok(())
+    }
+
+    async fn media_cache_entries(&self) -> Result<Vec<MediaCacheEntry>> {
+        let rows = self
+            .acquire()
+            .await?
+            .with_transaction(move |txn| -> Result<_> {
+                let rows = txn
+                    .prepare(
+                        "SELECT metadata, kind, length(data), last_access, ignore_policy, pinned \
+                         FROM media",
+                    )?
+                    .query_map((), |row| {
+                        Ok((
+                            row.get::<_, Option<Vec<u8>>>(0)?,
+                            row.get::<_, Option<String>>(1)?,
+                            row.get::<_, u64>(2)?,
+                            row.get::<_, i64>(3)?,
+                            row.get::<_, bool>(4)?,
+                            row.get::<_, bool>(5)?,
+                        ))
+                    })?
+                    .collect::<Result<Vec<_>, _>>()?;
+
+                Ok(rows)
+            })
+            .await?;
+
+        rows.into_iter()
+            .map(|(metadata, kind, size, last_access, ignore_policy, pinned)| {
+                let metadata = metadata
+                    .map(|metadata| -> Result<MediaMetadata> {
+                        Ok(serde_json::from_slice(&self.decode_value(&metadata)?)?)
+                    })
+                    .transpose()?;
+                let kind = kind.and_then(|kind| {
+                    [MediaKind::File, MediaKind::Thumbnail, MediaKind::Avatar]
+                        .into_iter()
+                        .find(|known_kind| known_kind.as_str() == kind)
+                });
+                let (uri, room_id, content_type) = metadata
+                    .map(|metadata| (Some(metadata.uri), metadata.room_id, metadata.content_type))
+                    .unwrap_or_default();
+
+                Ok(MediaCacheEntry {
+                    uri,
+                    room_id,
+                    kind,
+                    content_type,
+                    size,
+                    last_access: timestamp_to_time(last_access),
+                    ignore_policy,
+                    pinned,
+                })
+            })
+            .collect()
+    }
+
+    async fn media_cache_usage(&self) -> Result<MediaCacheUsage> {
+        // The room and the MIME type are hashed, so only the metadata of one media
+        // content per group needs to be decrypted to know them.
+        let (total, kinds, rooms, content_types) = self
+            .acquire()
+            .await?
+            .with_transaction(move |txn| -> Result<_> {
+                let total = txn.query_row(
+                    "SELECT count(*), sum(length(data)), max(last_access) FROM media",
+                    (),
+                    media_cache_usage_stats_from_row,
+                )?;
+
+                let kinds = txn
+                    .prepare(
+                        "SELECT count(*), sum(length(data)), max(last_access), kind \
+                         FROM media WHERE kind IS NOT NULL GROUP BY kind",
+                    )?
+                    .query_map((), |row| {
+                        Ok((media_cache_usage_stats_from_row(row)?, row.get::<_, String>(3)?))
+                    })?
+                    .collect::<Result<Vec<_>, _>>()?;
+
+                let grouped_by_hashed_column = |column: &str| -> rusqlite::Result<Vec<_>> {
+                    txn.prepare(&format!(
+                        "SELECT count(*), sum(length(data)), max(last_access), \
+                         (SELECT metadata FROM media AS m \
+                          WHERE m.{column} = media.{column} AND m.metadata IS NOT NULL LIMIT 1) \
+                         FROM media WHERE {column} IS NOT NULL GROUP BY {column}"
+                    ))?
+                    .query_map((), |row| {
+                        Ok((
+                            media_cache_usage_stats_from_row(row)?,
+                            row.get::<_, Option<Vec<u8>>>(3)?,
+                        ))
+                    })?
+                    .collect::<Result<Vec<_>, _>>()
+                };
+
+                let rooms = grouped_by_hashed_column("room_id")?;
+                let content_types = grouped_by_hashed_column("content_type")?;
+
+                Ok((total, kinds, rooms, content_types))
+            })
+            .await?;
+
+        let decode_metadata = |metadata: Option<Vec<u8>>| -> Result<Option<MediaMetadata>> {
+            metadata
+                .map(|metadata| Ok(serde_json::from_slice(&self.decode_value(&metadata)?)?))
+                .transpose()
+        };
+
+        let mut usage = MediaCacheUsage { total, ..Default::default() };
+
+        for (stats, kind) in kinds {
+            if let Some(kind) = [MediaKind::File, MediaKind::Thumbnail, MediaKind::Avatar]
+                .into_iter()
+                .find(|known_kind| known_kind.as_str() == kind)
+            {
+                usage.kinds.insert(kind, stats);
+            }
+        }
+
+        for (stats, metadata) in rooms {
+            if let Some(room_id) = decode_metadata(metadata)?.and_then(|metadata| metadata.room_id)
+            {
+                usage.rooms.insert(room_id, stats);
+            }
+        }
+
+        for (stats, metadata) in content_types {
+            if let Some(content_type) =
+                decode_metadata(metadata)?.and_then(|metadata| metadata.content_type)
+            {
+                usage.content_types.insert(content_type, stats);
+            }
+        }
+
+        Ok(usage)
+    }
+
     async fn set_media_retention_policy(
         &self,
         policy: MediaRetentionPolicy,
@@ -961,6 +1436,18 @@ impl EventCacheStore for SqliteEventCacheStore {
         self.media_service.media_retention_policy()
     }
 
+    async fn set_scoped_media_retention_policy(
+        &self,
+        scope: MediaRetentionScope,
+        policy: Option<MediaRetentionPolicy>,
+    ) -> Result<(), Self::Error> {
+        self.media_service.set_scoped_media_retention_policy(self, scope, policy).await
+    }
+
+    fn scoped_media_retention_policies(&self) -> ScopedMediaRetentionPolicies {
+        self.media_service.scoped_media_retention_policies()
+    }
+
     async fn set_ignore_media_retention_policy(
         &self,
         request: &MediaRequestParameters,
@@ -969,6 +1456,14 @@ impl EventCacheStore for SqliteEventCacheStore {
         self.media_service.set_ignore_media_retention_policy(self, request, ignore_policy).await
     }
 
+    async fn set_media_pinned(
+        &self,
+        request: &MediaRequestParameters,
+        pinned: bool,
+    ) -> Result<(), Self::Error> {
+        self.media_service.set_media_pinned(self, request, pinned).await
+    }
+
     async fn clean_up_media_cache(&self) -> Result<(), Self::Error> {
         self.media_service.clean_up_media_cache(self).await
     }
@@ -995,6 +1490,22 @@ impl EventCacheStoreMedia for SqliteEventCacheStore {
         Ok(())
     }
 
+    async fn scoped_media_retention_policies_inner(
+        &self,
+    ) -> Result<Option<ScopedMediaRetentionPolicies>, Self::Error> {
+        let conn = self.acquire().await?;
+        conn.get_serialized_kv(keys::SCOPED_MEDIA_RETENTION_POLICIES).await
+    }
+
+    async fn set_scoped_media_retention_policies_inner(
+        &self,
+        policies: &ScopedMediaRetentionPolicies,
+    ) -> Result<(), Self::Error> {
+        let conn = self.acquire().await?;
+        conn.set_serialized_kv(keys::SCOPED_MEDIA_RETENTION_POLICIES, policies).await?;
+        Ok(())
+    }
+
     async fn add_media_content_inner(
         &self,
         request: &MediaRequestParameters,
@@ -1002,22 +1513,61 @@ impl EventCacheStoreMedia for SqliteEventCacheStore {
         last_access: SystemTime,
         policy: MediaRetentionPolicy,
         ignore_policy: IgnoreMediaRetentionPolicy,
+        origin: &MediaOrigin,
     ) -> Result<(), Self::Error> {
         let ignore_policy = ignore_policy.is_yes();
         let data = self.encode_value(data)?;
-
-        if !ignore_policy && policy.exceeds_max_file_size(data.len()) {
-            return Ok(());
-        }
-
         let uri = self.encode_key(keys::MEDIA, request.source.unique_key());
         let format = self.encode_key(keys::MEDIA, request.format.unique_key());
         let timestamp = time_to_timestamp(last_access);
+        let room_id = origin.room_id.as_ref().map(|room_id| self.encode_key(keys::MEDIA, room_id));
+        let kind = origin.media_kind(request).as_str();
+        let content_type = origin
+            .content_type
+            .as_ref()
+            .map(|content_type| self.encode_key(keys::MEDIA, content_type));
+        let metadata = self.encode_value(serde_json::to_vec(&MediaMetadata {
+            uri: request.uri().to_owned(),
+            room_id: origin.room_id.clone(),
+            content_type: origin.content_type.clone(),
+        })?)?;
 
         let conn = self.acquire().await?;
+
+        if !ignore_policy && policy.exceeds_max_file_size(data.len()) {
+            // Only replace the content if it is pinned.
+            conn.execute(
+                "UPDATE media SET data = ?, last_access = ?, ignore_policy = ?, room_id = ?, \
+                 kind = ?, content_type = ?, metadata = ? \
+                 WHERE uri = ? AND format = ? AND pinned IS TRUE",
+                (
+                    data,
+                    timestamp,
+                    ignore_policy,
+                    room_id,
+                    kind,
+                    content_type,
+                    metadata,
+                    uri,
+                    format,
+                ),
+            )
+            .await?;
+
+            return Ok(());
+        }
+
+        // Don't use `INSERT OR REPLACE`, which would reset whether the content is
+        // pinned.
         conn.execute(
-            "INSERT OR REPLACE INTO media (uri, format, data, last_access, ignore_policy) VALUES (?, ?, ?, ?, ?)",
-            (uri, format, data, timestamp, ignore_policy),
+            "INSERT INTO media \
+             (uri, format, data, last_access, ignore_policy, room_id, kind, content_type, metadata) \
+             VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?) \
+             ON CONFLICT (uri, format) DO UPDATE SET data = excluded.data, \
+             last_access = excluded.last_access, ignore_policy = excluded.ignore_policy, \
+             room_id = excluded.room_id, kind = excluded.kind, \
+             content_type = excluded.content_type, metadata = excluded.metadata",
+            (uri, format, data, timestamp, ignore_policy, room_id, kind, content_type, metadata),
         )
         .await?;
 
@@ -1043,6 +1593,24 @@ impl EventCacheStoreMedia for SqliteEventCacheStore {
         Ok(())
     }
 
+    async fn set_media_pinned_inner(
+        &self,
+        request: &MediaRequestParameters,
+        pinned: bool,
+    ) -> Result<(), Self::Error> {
+        let uri = self.encode_key(keys::MEDIA, request.source.unique_key());
+        let format = self.encode_key(keys::MEDIA, request.format.unique_key());
+
+        let conn = self.acquire().await?;
+        conn.execute(
+            r#"UPDATE media SET pinned = ? WHERE uri = ? AND format = ?"#,
+            (pinned, uri, format),
+        )
+        .await?;
+
+        Ok(())
+    }
+
     async fn get_media_content_inner(
         &self,
         request: &MediaRequestParameters,
@@ -1106,117 +1674,63 @@ impl EventCacheStoreMedia for SqliteEventCacheStore {
     async fn clean_up_media_cache_inner(
         &self,
         policy: MediaRetentionPolicy,
+        scoped_policies: &ScopedMediaRetentionPolicies,
         current_time: SystemTime,
     ) -> Result<(), Self::Error> {
-        if !policy.has_limitations() {
+        if !policy.has_limitations() && !scoped_policies.has_limitations() {
             // We can safely skip all the checks.
             return Ok(());
         }
 
-        let conn = self.acquire().await?;
-        let removed = conn
-            .with_transaction::<_, Error, _>(move |txn| {
-                let mut removed = false;
-
-                // First, check media content that exceed the max filesize.
-                if let Some(max_file_size) = policy.computed_max_file_size() {
-                    let count = txn.execute(
-                        "DELETE FROM media WHERE ignore_policy IS FALSE AND length(data) > ?",
-                        (max_file_size,),
-                    )?;
-
-                    if count > 0 {
-                        removed = true;
-                    }
-                }
+        // Every media content is subject to a single policy, build the SQL condition
+        // matching the media contents of each policy.
+        let scoped_room_ids = scoped_policies
+            .rooms()
+            .keys()
+            .map(|room_id| Value::Blob(self.encode_key(keys::MEDIA, room_id).to_vec()))
+            .collect::<Vec<_>>();
+        let scoped_kinds = scoped_policies
+            .kinds()
+            .keys()
+            .map(|kind| Value::Text(kind.as_str().to_owned()))
+            .collect::<Vec<_>>();
+
+        let not_in_scoped_rooms = if scoped_room_ids.is_empty() {
+            "1".to_owned()
+        } else {
+            format!("(room_id IS NULL OR room_id NOT IN ({}))", repeat_vars(scoped_room_ids.len()))
+        };
+        let not_in_scoped_kinds = if scoped_kinds.is_empty() {
+            "1".to_owned()
+        } else {
+            format!("(kind IS NULL OR kind NOT IN ({}))", repeat_vars(scoped_kinds.len()))
+        };
 
-                // Then, clean up expired media content.
-                if let Some(last_access_expiry) = policy.last_access_expiry {
-                    let current_timestamp = time_to_timestamp(current_time);
-                    let expiry_secs = last_access_expiry.as_secs();
-                    let count = txn.execute(
-                        "DELETE FROM media WHERE ignore_policy IS FALSE AND (? - last_access) >= ?",
-                        (current_timestamp, expiry_secs),
-                    )?;
+        let mut groups = Vec::new();
 
-                    if count > 0 {
-                        removed = true;
-                    }
-                }
+        for (room_id, policy) in scoped_policies.rooms() {
+            let room_id = Value::Blob(self.encode_key(keys::MEDIA, room_id).to_vec());
+            groups.push((*policy, "room_id = ?".to_owned(), vec![room_id]));
+        }
 
-                // Finally, if the cache size is too big, remove old items until it fits.
-                if let Some(max_cache_size) = policy.max_cache_size {
-                    // i64 is the integer type used by SQLite, use it here to avoid usize overflow
-                    // during the conversion of the result.
-                    let cache_size_int = txn
-                        .query_row(
-                            "SELECT sum(length(data)) FROM media WHERE ignore_policy IS FALSE",
-                            (),
-                            |row| {
-                                // `sum()` returns `NULL` if there are no rows.
-                                row.get::<_, Option<i64>>(0)
-                            },
-                        )?
-                        .unwrap_or_default();
-                    let cache_size_usize = usize::try_from(cache_size_int);
-
-                    // If the cache size is overflowing or bigger than max cache size, clean up.
-                    if cache_size_usize.is_err()
-                        || cache_size_usize.is_ok_and(|cache_size| cache_size > max_cache_size)
-                    {
-                        // Get the sizes of the media contents ordered by last access.
-                        let mut cached_stmt = txn.prepare_cached(
-                            "SELECT rowid, length(data) FROM media \
-                             WHERE ignore_policy IS FALSE ORDER BY last_access DESC",
-                        )?;
-                        let content_sizes = cached_stmt
-                            .query(())?
-                            .mapped(|row| Ok((row.get::<_, i64>(0)?, row.get::<_, usize>(1)?)));
-
-                        let mut accumulated_items_size = 0usize;
-                        let mut limit_reached = false;
-                        let mut rows_to_remove = Vec::new();
-
-                        for result in content_sizes {
-                            let (row_id, size) = match result {
-                                Ok(content_size) => content_size,
-                                Err(error) => {
-                                    return Err(error.into());
-                                }
-                            };
-
-                            if limit_reached {
-                                rows_to_remove.push(row_id);
-                                continue;
-                            }
+        for (kind, policy) in scoped_policies.kinds() {
+            let mut params = vec![Value::Text(kind.as_str().to_owned())];
+            params.extend(scoped_room_ids.iter().cloned());
+            groups.push((*policy, format!("kind = ? AND {not_in_scoped_rooms}"), params));
+        }
 
-                            match accumulated_items_size.checked_add(size) {
-                                Some(acc) if acc > max_cache_size => {
-                                    // We can stop accumulating.
-                                    limit_reached = true;
-                                    rows_to_remove.push(row_id);
-                                }
-                                Some(acc) => accumulated_items_size = acc,
-                                None => {
-                                    // The accumulated size is overflowing but the setting cannot be
-                                    // bigger than usize::MAX, we can stop accumulating.
-                                    limit_reached = true;
-                                    rows_to_remove.push(row_id);
-                                }
-                            };
-                        }
+        let mut params = scoped_kinds;
+        params.extend(scoped_room_ids);
+        groups.push((policy, format!("{not_in_scoped_kinds} AND {not_in_scoped_rooms}"), params));
 
-                        if !rows_to_remove.is_empty() {
-                            removed = true;
-                        }
+        let conn = self.acquire().await?;
+        let removed = conn
+            .with_transaction::<_, Error, _>(move |txn| {
+                let mut removed = false;
 
-                        txn.chunk_large_query_over(rows_to_remove, None, |txn, row_ids| {
-                            let sql_params = repeat_vars(row_ids.len());
-                            let query = format!("DELETE FROM media WHERE rowid IN ({sql_params})");
-                            txn.prepare(&query)?.execute(params_from_iter(row_ids))?;
-                            Ok(Vec::<()>::new())
-                        })?;
-                    }
+                for (policy, condition, params) in groups {
+                    removed |=
+                        clean_up_media_cache_group(txn, policy, &condition, &params, current_time)?;
                 }
 
                 txn.set_serialized_kv(keys::LAST_MEDIA_CLEANUP_TIME, current_time)?;
@@ -1240,6 +1754,160 @@ impl EventCacheStoreMedia for SqliteEventCacheStore {
     }
 }
 
+/// Build the [`MediaCacheUsageStats`] from a row whose first columns are the
+/// number of media contents, their total size and their most recent access.
+fn media_cache_usage_stats_from_row(
+    row: &rusqlite::Row<'_>,
+) -> rusqlite::Result<MediaCacheUsageStats> {
+    // `sum()` and `max()` return `NULL` if there are no rows.
+    Ok(MediaCacheUsageStats {
+        count: row.get::<_, i64>(0)?.try_into().unwrap_or_default(),
+        size: row.get::<_, Option<i64>>(1)?.unwrap_or_default().try_into().unwrap_or_default(),
+        last_access: row.get::<_, Option<i64>>(2)?.map(timestamp_to_time),
+    })
+}
+
+/// Clean up the media contents matching the given SQL condition, according to
+/// the given policy.
+///
+/// `params` are the parameters of the SQL condition.
+///
+/// Returns whether media contents were removed.
+fn clean_up_media_cache_group(
+    txn: &Transaction<'_>,
+    policy: MediaRetentionPolicy,
+    condition: &str,
+    params: &[Value],
+    current_time: SystemTime,
+) -> Result<bool> {
+    if !policy.has_limitations() {
+        // We can safely skip all the checks.
+        return Ok(false);
+    }
+
+    let mut removed = false;
+
+    // First, check media content that exceed the max filesize.
+    if let Some(max_file_size) = policy.computed_max_file_size() {
+        let count = txn.execute(
+            &format!(
+                "DELETE FROM media WHERE ignore_policy IS FALSE AND pinned IS FALSE \
+                 AND {condition} AND length(data) > ?"
+            ),
+            params_from_iter(
+                params
+                    .iter()
+                    .chain([&Value::Integer(max_file_size.try_into().unwrap_or(i64::MAX))]),
+            ),
+        )?;
+
+        if count > 0 {
+            removed = true;
+        }
+    }
+
+    // Then, clean up expired media content.
+    if let Some(last_access_expiry) = policy.last_access_expiry {
+        let current_timestamp = time_to_timestamp(current_time);
+        let expiry_secs = last_access_expiry.as_secs();
+        let count = txn.execute(
+            &format!(
+                "DELETE FROM media WHERE ignore_policy IS FALSE AND pinned IS FALSE \
+                 AND {condition} AND (? - last_access) >= ?"
+            ),
+            params_from_iter(params.iter().chain([
+                &Value::Integer(current_timestamp),
+                &Value::Integer(expiry_secs.try_into().unwrap_or(i64::MAX)),
+            ])),
+        )?;
+
+        if count > 0 {
+            removed = true;
+        }
+    }
+
+    // Finally, if the cache size is too big, remove old items until it fits.
+    if let Some(max_cache_size) = policy.max_cache_size {
+        // i64 is the integer type used by SQLite, use it here to avoid usize overflow
+        // during the conversion of the result.
+        let cache_size_int = txn
+            .query_row(
+                &format!(
+                    "SELECT sum(length(data)) FROM media \
+                     WHERE ignore_policy IS FALSE AND pinned IS FALSE AND {condition}"
+                ),
+                params_from_iter(params),
+                |row| {
+                    // `sum()` returns `NULL` if there are no rows.
+                    row.get::<_, Option<i64>>(0)
+                },
+            )?
+            .unwrap_or_default();
+        let cache_size_usize = usize::try_from(cache_size_int);
+
+        // If the cache size is overflowing or bigger than max cache size, clean up.
+        if cache_size_usize.is_err()
+            || cache_size_usize.is_ok_and(|cache_size| cache_size > max_cache_size)
+        {
+            // Get the sizes of the media contents ordered by last access.
+            let mut stmt = txn.prepare(&format!(
+                "SELECT rowid, length(data) FROM media \
+                 WHERE ignore_policy IS FALSE AND pinned IS FALSE AND {condition} \
+                 ORDER BY last_access DESC"
+            ))?;
+            let content_sizes = stmt
+                .query(params_from_iter(params))?
+                .mapped(|row| Ok((row.get::<_, i64>(0)?, row.get::<_, usize>(1)?)));
+
+            let mut accumulated_items_size = 0usize;
+            let mut limit_reached = false;
+            let mut rows_to_remove = Vec::new();
+
+            for result in content_sizes {
+                let (row_id, size) = match result {
+                    Ok(content_size) => content_size,
+                    Err(error) => {
+                        return Err(error.into());
+                    }
+                };
+
+                if limit_reached {
+                    rows_to_remove.push(row_id);
+                    continue;
+                }
+
+                match accumulated_items_size.checked_add(size) {
+                    Some(acc) if acc > max_cache_size => {
+                        // We can stop accumulating.
+                        limit_reached = true;
+                        rows_to_remove.push(row_id);
+                    }
+                    Some(acc) => accumulated_items_size = acc,
+                    None => {
+                        // The accumulated size is overflowing but the setting cannot be
+                        // bigger than usize::MAX, we can stop accumulating.
+                        limit_reached = true;
+                        rows_to_remove.push(row_id);
+                    }
+                };
+            }
+
+            if !rows_to_remove.is_empty() {
+                removed = true;
+            }
+
+            txn.chunk_large_query_over(rows_to_remove, None, |txn, row_ids| {
+                let sql_params = repeat_vars(row_ids.len());
+                let query = format!("DELETE FROM media WHERE rowid IN ({sql_params})");
+                txn.prepare(&query)?.execute(params_from_iter(row_ids))?;
+                Ok(Vec::<()>::new())
+            })?;
+        }
+    }
+
+    Ok(removed)
+}
+
 /// Like `deadpool::managed::Object::with_transaction`, but starts the
 /// transaction in immediate (write) mode from the beginning, precluding errors
 /// of the kind SQLITE_BUSY from happening, for transactions that may involve
@@ -1349,7 +2017,7 @@ mod tests {
     use tempfile::{tempdir, TempDir};
 
     use super::SqliteEventCacheStore;
-    use crate::utils::SqliteAsyncConnExt;
+    use crate::{utils::SqliteAsyncConnExt, ChangePassphraseError, OpenStoreError, StoreSecret};
 
     static TMP_DIR: Lazy<TempDir> = Lazy::new(|| tempdir().unwrap());
     static NUM: AtomicU32 = AtomicU32::new(0);
@@ -1367,6 +2035,57 @@ mod tests {
     event_cache_store_integration_tests_time!();
     event_cache_store_media_integration_tests!(with_media_size_tests);
 
+    #[async_test]
+    async fn test_change_passphrase() {
+        let dir = tempdir().unwrap();
+        let old = StoreSecret::Key(&[7; 32]);
+        let new = StoreSecret::Passphrase("new_passphrase");
+        let request = MediaRequestParameters {
+            source: MediaSource::Plain(mxc_uri!("mxc://localhost/media").to_owned()),
+            format: MediaFormat::File,
+        };
+
+        let store = SqliteEventCacheStore::open_with_secret(dir.path(), Some(old)).await.unwrap();
+        store
+            .add_media_content(&request, b"secret data".to_vec(), IgnoreMediaRetentionPolicy::No)
+            .await
+            .unwrap();
+
+        // The old key must be the one of the store.
+        assert_matches!(
+            store.change_passphrase(new, new).await,
+            Err(ChangePassphraseError::Cipher(_))
+        );
+
+        store.change_passphrase(old, new).await.unwrap();
+        drop(store);
+
+        // The store can't be opened with the old key anymore.
+        assert_matches!(
+            SqliteEventCacheStore::open_with_secret(dir.path(), Some(old)).await,
+            Err(OpenStoreError::InitCipher(_))
+        );
+
+        // The media is still readable with the new passphrase.
+        let store = SqliteEventCacheStore::open_with_secret(dir.path(), Some(new)).await.unwrap();
+        assert_eq!(
+            store.get_media_content(&request).await.unwrap().as_deref(),
+            Some(b"secret data".as_slice())
+        );
+    }
+
+    #[async_test]
+    async fn test_change_passphrase_of_unencrypted_store() {
+        let store = get_event_cache_store().await.unwrap();
+
+        assert_matches!(
+            store
+                .change_passphrase(StoreSecret::Passphrase("old"), StoreSecret::Passphrase("new"))
+                .await,
+            Err(ChangePassphraseError::NotEncrypted)
+        );
+    }
+
     async fn get_event_cache_store_content_sorted_by_last_access(
         event_cache_store: &SqliteEventCacheStore,
     ) -> Vec<Vec<u8>> {
@@ -1522,7 +2241,10 @@ mod tests {
                     previous: None,
                     new: ChunkIdentifier::new(42),
                     next: None,
-                    gap: Gap { prev_token: "raclette".to_owned() },
+                    gap: Gap {
+                        prev_token: "raclette".to_owned(),
+                        next_token: Some("fondue".to_owned()),
+                    },
                 }],
             )
             .await
@@ -1539,6 +2261,7 @@ mod tests {
         assert_eq!(c.next, None);
         assert_matches!(c.content, ChunkContent::Gap(gap) => {
             assert_eq!(gap.prev_token, "raclette");
+            assert_eq!(gap.next_token.as_deref(), Some("fondue"));
         });
     }
 
@@ -1602,19 +2325,19 @@ mod tests {
                         previous: None,
                         new: ChunkIdentifier::new(42),
                         next: None,
-                        gap: Gap { prev_token: "raclette".to_owned() },
+                        gap: Gap { prev_token: "raclette".to_owned(), next_token: None },
                     },
                     Update::NewGapChunk {
                         previous: Some(ChunkIdentifier::new(42)),
                         new: ChunkIdentifier::new(43),
                         next: None,
-                        gap: Gap { prev_token: "fondue".to_owned() },
+                        gap: Gap { prev_token: "fondue".to_owned(), next_token: None },
                     },
                     Update::NewGapChunk {
                         previous: Some(ChunkIdentifier::new(43)),
                         new: ChunkIdentifier::new(44),
                         next: None,
-                        gap: Gap { prev_token: "tartiflette".to_owned() },
+                        gap: Gap { prev_token: "tartiflette".to_owned(), next_token: None },
                     },
                     Update::RemoveChunk(ChunkIdentifier::new(43)),
                 ],
@@ -1881,7 +2604,7 @@ mod tests {
                         previous: Some(ChunkIdentifier::new(42)),
                         new: ChunkIdentifier::new(54),
                         next: None,
-                        gap: Gap { prev_token: "fondue".to_owned() },
+                        gap: Gap { prev_token: "fondue".to_owned(), next_token: None },
                     },
                     Update::PushItems {
                         at: Position::new(ChunkIdentifier::new(42), 0),
@@ -21,17 +21,21 @@ mod crypto_store;
 mod error;
 #[cfg(feature = "event-cache")]
 mod event_cache_store;
+mod secret;
 #[cfg(feature = "state-store")]
 mod state_store;
 mod utils;
 
 #[cfg(feature = "crypto-store")]
 pub use self::crypto_store::SqliteCryptoStore;
-pub use self::error::OpenStoreError;
 #[cfg(feature = "event-cache")]
 pub use self::event_cache_store::SqliteEventCacheStore;
 #[cfg(feature = "state-store")]
 pub use self::state_store::SqliteStateStore;
+pub use self::{
+    error::{ChangePassphraseError, OpenStoreError},
+    secret::StoreSecret,
+};
 
 #[cfg(test)]
 matrix_sdk_test::init_tracing_for_tests!();
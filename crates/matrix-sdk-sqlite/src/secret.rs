@@ -0,0 +1,61 @@
+// Copyright 2025 The Matrix.org Foundation C.I.C.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::fmt;
+
+use matrix_sdk_store_encryption::{Error, StoreCipher};
+
+/// The secret protecting the [`StoreCipher`] that encrypts the private data of
+/// a SQLite store.
+#[derive(Clone, Copy)]
+pub enum StoreSecret<'a> {
+    /// A passphrase, the key protecting the cipher is derived from it with
+    /// PBKDF2.
+    Passphrase(&'a str),
+
+    /// A raw 32-byte key, used as is to protect the cipher.
+    Key(&'a [u8; 32]),
+}
+
+impl StoreSecret<'_> {
+    /// Import the [`StoreCipher`] that was exported with this secret.
+    pub(crate) fn import_cipher(&self, encrypted: &[u8]) -> Result<StoreCipher, Error> {
+        match self {
+            Self::Passphrase(passphrase) => StoreCipher::import(passphrase, encrypted),
+            Self::Key(key) => StoreCipher::import_with_key(key, encrypted),
+        }
+    }
+
+    /// Export the given [`StoreCipher`] with this secret.
+    pub(crate) fn export_cipher(&self, cipher: &StoreCipher) -> Result<Vec<u8>, Error> {
+        match self {
+            #[cfg(not(test))]
+            Self::Passphrase(passphrase) => cipher.export(passphrase),
+            #[cfg(test)]
+            Self::Passphrase(passphrase) => cipher._insecure_export_fast_for_testing(passphrase),
+            Self::Key(key) => cipher.export_with_key(key),
+        }
+    }
+}
+
+#[cfg(not(tarpaulin_include))]
+impl fmt::Debug for StoreSecret<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // Never leak the secret in the logs.
+        match self {
+            Self::Passphrase(_) => f.write_str("Passphrase(..)"),
+            Self::Key(_) => f.write_str("Key(..)"),
+        }
+    }
+}
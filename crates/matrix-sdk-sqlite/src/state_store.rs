@@ -46,7 +46,7 @@ use crate::{
         repeat_vars, Key, SqliteAsyncConnExt, SqliteKeyValueStoreAsyncConnExt,
         SqliteKeyValueStoreConnExt,
     },
-    OpenStoreError,
+    ChangePassphraseError, OpenStoreError, StoreSecret,
 };
 
 mod keys {
@@ -91,10 +91,21 @@ impl SqliteStateStore {
     pub async fn open(
         path: impl AsRef<Path>,
         passphrase: Option<&str>,
+    ) -> Result<Self, OpenStoreError> {
+        Self::open_with_secret(path, passphrase.map(StoreSecret::Passphrase)).await
+    }
+
+    /// Open the sqlite-based state store at the given path using the given
+    /// secret to encrypt private data.
+    ///
+    /// Unlike [`SqliteStateStore::open()`], the secret can be a raw key.
+    pub async fn open_with_secret(
+        path: impl AsRef<Path>,
+        secret: Option<StoreSecret<'_>>,
     ) -> Result<Self, OpenStoreError> {
         let pool = create_pool(path.as_ref()).await?;
 
-        Self::open_with_pool(pool, passphrase).await
+        Self::open_with_pool_and_secret(pool, secret).await
     }
 
     /// Create a sqlite-based state store using the given sqlite database pool.
@@ -102,6 +113,15 @@ impl SqliteStateStore {
     pub async fn open_with_pool(
         pool: SqlitePool,
         passphrase: Option<&str>,
+    ) -> Result<Self, OpenStoreError> {
+        Self::open_with_pool_and_secret(pool, passphrase.map(StoreSecret::Passphrase)).await
+    }
+
+    /// Create a sqlite-based state store using the given sqlite database pool.
+    /// The given secret will be used to encrypt private data.
+    pub async fn open_with_pool_and_secret(
+        pool: SqlitePool,
+        secret: Option<StoreSecret<'_>>,
     ) -> Result<Self, OpenStoreError> {
         let conn = pool.get().await?;
         conn.set_journal_size_limit().await?;
@@ -113,8 +133,8 @@ impl SqliteStateStore {
             version = 1;
         }
 
-        let store_cipher = match passphrase {
-            Some(p) => Some(Arc::new(conn.get_or_create_store_cipher(p).await?)),
+        let store_cipher = match secret {
+            Some(s) => Some(Arc::new(conn.get_or_create_store_cipher(s).await?)),
             None => None,
         };
         let this = Self { store_cipher, pool };
@@ -124,6 +144,26 @@ impl SqliteStateStore {
         Ok(this)
     }
 
+    /// Change the secret protecting the private data of this store, from `old`
+    /// to `new`.
+    ///
+    /// Only the store cipher is re-encrypted with the new secret, the data of
+    /// the store is left untouched, so this is cheap even for big stores. A
+    /// passphrase can be changed into a raw key, and vice versa. The store
+    /// must be opened with the new secret afterwards.
+    pub async fn change_passphrase(
+        &self,
+        old: StoreSecret<'_>,
+        new: StoreSecret<'_>,
+    ) -> Result<(), ChangePassphraseError> {
+        if self.store_cipher.is_none() {
+            return Err(ChangePassphraseError::NotEncrypted);
+        }
+
+        let conn = self.pool.get().await?;
+        conn.change_store_cipher_secret(old, new).await
+    }
+
     /// Run database migrations from the given `from` version to the given `to`
     /// version
     ///
@@ -2103,11 +2143,14 @@ struct ReceiptData {
 mod tests {
     use std::sync::atomic::{AtomicU32, Ordering::SeqCst};
 
+    use assert_matches::assert_matches;
     use matrix_sdk_base::{statestore_integration_tests, StateStore, StoreError};
+    use matrix_sdk_test::async_test;
     use once_cell::sync::Lazy;
     use tempfile::{tempdir, TempDir};
 
     use super::SqliteStateStore;
+    use crate::{ChangePassphraseError, OpenStoreError, StoreSecret};
 
     static TMP_DIR: Lazy<TempDir> = Lazy::new(|| tempdir().unwrap());
     static NUM: AtomicU32 = AtomicU32::new(0);
@@ -2122,6 +2165,51 @@ mod tests {
     }
 
     statestore_integration_tests!();
+
+    #[async_test]
+    async fn test_change_passphrase() {
+        let dir = tempdir().unwrap();
+        let old = StoreSecret::Passphrase("old_passphrase");
+        let new = StoreSecret::Key(&[7; 32]);
+
+        let store = SqliteStateStore::open_with_secret(dir.path(), Some(old)).await.unwrap();
+        store.set_custom_value(b"test_value", b"secret data".to_vec()).await.unwrap();
+
+        // The old passphrase must be the one of the store.
+        assert_matches!(
+            store.change_passphrase(new, new).await,
+            Err(ChangePassphraseError::Cipher(_))
+        );
+
+        store.change_passphrase(old, new).await.unwrap();
+        drop(store);
+
+        // The store can't be opened with the old passphrase anymore.
+        assert_matches!(
+            SqliteStateStore::open_with_secret(dir.path(), Some(old)).await,
+            Err(OpenStoreError::InitCipher(_))
+        );
+
+        // The data is still readable with the new key.
+        let store = SqliteStateStore::open_with_secret(dir.path(), Some(new)).await.unwrap();
+        assert_eq!(
+            store.get_custom_value(b"test_value").await.unwrap().as_deref(),
+            Some(b"secret data".as_slice())
+        );
+    }
+
+    #[async_test]
+    async fn test_change_passphrase_of_unencrypted_store() {
+        let dir = tempdir().unwrap();
+        let store = SqliteStateStore::open(dir.path(), None).await.unwrap();
+
+        assert_matches!(
+            store
+                .change_passphrase(StoreSecret::Passphrase("old"), StoreSecret::Passphrase("new"))
+                .await,
+            Err(ChangePassphraseError::NotEncrypted)
+        );
+    }
 }
 
 #[cfg(test)]
@@ -2184,6 +2272,7 @@ mod migration_tests {
     use crate::{
         error::{Error, Result},
         utils::{SqliteAsyncConnExt, SqliteKeyValueStoreAsyncConnExt},
+        StoreSecret,
     };
 
     static TMP_DIR: Lazy<TempDir> = Lazy::new(|| tempdir().unwrap());
@@ -2201,7 +2290,9 @@ mod migration_tests {
 
         init(&conn).await?;
 
-        let store_cipher = Some(Arc::new(conn.get_or_create_store_cipher(SECRET).await.unwrap()));
+        let store_cipher = Some(Arc::new(
+            conn.get_or_create_store_cipher(StoreSecret::Passphrase(SECRET)).await.unwrap(),
+        ));
         let this = SqliteStateStore { store_cipher, pool };
         this.run_migrations(&conn, 1, Some(version)).await?;
 
@@ -19,13 +19,13 @@ use async_trait::async_trait;
 use deadpool_sqlite::Object as SqliteAsyncConn;
 use itertools::Itertools;
 use matrix_sdk_store_encryption::StoreCipher;
-use ruma::time::SystemTime;
+use ruma::time::{Duration, SystemTime};
 use rusqlite::{limits::Limit, OptionalExtension, Params, Row, Statement, Transaction};
 use serde::{de::DeserializeOwned, Serialize};
 
 use crate::{
     error::{Error, Result},
-    OpenStoreError,
+    ChangePassphraseError, OpenStoreError, StoreSecret,
 };
 
 #[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
@@ -412,24 +412,51 @@ pub(crate) trait SqliteKeyValueStoreAsyncConnExt: SqliteAsyncConnExt {
     /// Get the [`StoreCipher`] of the database or create it.
     async fn get_or_create_store_cipher(
         &self,
-        passphrase: &str,
+        secret: StoreSecret<'_>,
     ) -> Result<StoreCipher, OpenStoreError> {
         let encrypted_cipher = self.get_kv("cipher").await.map_err(OpenStoreError::LoadCipher)?;
 
         let cipher = if let Some(encrypted) = encrypted_cipher {
-            StoreCipher::import(passphrase, &encrypted)?
+            secret.import_cipher(&encrypted)?
         } else {
             let cipher = StoreCipher::new()?;
-            #[cfg(not(test))]
-            let export = cipher.export(passphrase);
-            #[cfg(test)]
-            let export = cipher._insecure_export_fast_for_testing(passphrase);
-            self.set_kv("cipher", export?).await.map_err(OpenStoreError::SaveCipher)?;
+            let export = secret.export_cipher(&cipher)?;
+            self.set_kv("cipher", export).await.map_err(OpenStoreError::SaveCipher)?;
             cipher
         };
 
         Ok(cipher)
     }
+
+    /// Export the [`StoreCipher`] of the database with the `new` secret,
+    /// instead of the `old` one.
+    ///
+    /// Only the cipher is re-wrapped, the data encrypted with it is left
+    /// untouched. The export is replaced only if it wasn't changed since it
+    /// was loaded, so the change is atomic.
+    async fn change_store_cipher_secret(
+        &self,
+        old: StoreSecret<'_>,
+        new: StoreSecret<'_>,
+    ) -> Result<(), ChangePassphraseError> {
+        let old_export = self.get_kv("cipher").await?.ok_or(ChangePassphraseError::NotEncrypted)?;
+
+        let cipher = old.import_cipher(&old_export)?;
+        let new_export = new.export_cipher(&cipher)?;
+
+        let changed = self
+            .execute(
+                "UPDATE kv SET value = ?1 WHERE key = 'cipher' AND value = ?2",
+                (new_export, old_export),
+            )
+            .await?;
+
+        if changed == 1 {
+            Ok(())
+        } else {
+            Err(ChangePassphraseError::ConcurrentChange)
+        }
+    }
 }
 
 #[async_trait]
@@ -480,10 +507,17 @@ pub(crate) fn time_to_timestamp(time: SystemTime) -> i64 {
         .unwrap_or(0)
 }
 
+/// Convert the given timestamp, as the number of seconds since Unix Epoch, to a
+/// `SystemTime`.
+///
+/// This is the inverse of [`time_to_timestamp`].
+pub(crate) fn timestamp_to_time(timestamp: i64) -> SystemTime {
+    let secs = timestamp.try_into().unwrap_or_default();
+    SystemTime::UNIX_EPOCH.checked_add(Duration::from_secs(secs)).unwrap_or(SystemTime::UNIX_EPOCH)
+}
+
 #[cfg(test)]
 mod unit_tests {
-    use std::time::Duration;
-
     use super::*;
 
     #[test]
@@ -507,4 +541,13 @@ mod unit_tests {
         // Fallback value on overflow.
         assert_eq!(time_to_timestamp(SystemTime::UNIX_EPOCH - Duration::from_secs(60)), 0);
     }
+
+    #[test]
+    fn test_timestamp_to_time() {
+        assert_eq!(timestamp_to_time(0), SystemTime::UNIX_EPOCH);
+        assert_eq!(timestamp_to_time(60), SystemTime::UNIX_EPOCH + Duration::from_secs(60));
+
+        // Fallback value on underflow.
+        assert_eq!(timestamp_to_time(-60), SystemTime::UNIX_EPOCH);
+    }
 }
@@ -59,9 +59,11 @@ mod fuzzy_match_room_name;
 mod invite;
 mod joined;
 mod non_left;
+mod non_replaced;
 mod none;
 mod normalized_match_room_name;
 mod not;
+mod space;
 mod unread;
 
 #[cfg(test)]
@@ -79,11 +81,13 @@ use matrix_sdk::{test_utils::logged_in_client_with_server, Client, SlidingSync};
 #[cfg(test)]
 use matrix_sdk_test::{JoinedRoomBuilder, SyncResponseBuilder};
 pub use non_left::new_filter as new_filter_non_left;
+pub use non_replaced::new_filter as new_filter_non_replaced;
 pub use none::new_filter as new_filter_none;
 pub use normalized_match_room_name::new_filter as new_filter_normalized_match_room_name;
 pub use not::new_filter as new_filter_not;
 #[cfg(test)]
 use ruma::RoomId;
+pub use space::new_filter as new_filter_space;
 use unicode_normalization::{char::is_combining_mark, UnicodeNormalization};
 pub use unread::new_filter as new_filter_unread;
 #[cfg(test)]
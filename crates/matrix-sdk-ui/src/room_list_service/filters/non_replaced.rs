@@ -0,0 +1,86 @@
+// Copyright 2025 The Matrix.org Foundation C.I.C.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use matrix_sdk_base::RoomState;
+
+use super::{super::Room, Filter};
+
+struct NonReplacedRoomMatcher<F>
+where
+    F: Fn(&Room) -> Option<RoomState>,
+{
+    /// Get the state of the room that replaced this room, if the room has been
+    /// tombstoned and its successor is known.
+    successor_state: F,
+}
+
+impl<F> NonReplacedRoomMatcher<F>
+where
+    F: Fn(&Room) -> Option<RoomState>,
+{
+    fn matches(&self, room: &Room) -> bool {
+        // A tombstoned room is kept until the user has joined its successor, so that
+        // they can still find their way to the new room.
+        !matches!((self.successor_state)(room), Some(RoomState::Joined))
+    }
+}
+
+/// Create a new filter that will filter out the rooms that have been upgraded
+/// and replaced by a room that the user has joined.
+pub fn new_filter() -> impl Filter {
+    let matcher = NonReplacedRoomMatcher {
+        successor_state: move |room| {
+            let successor = room.successor()?;
+            Some(room.client().get_room(&successor.room_id)?.state())
+        },
+    };
+
+    move |room| -> bool { matcher.matches(room) }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::ops::Not;
+
+    use matrix_sdk_base::RoomState;
+    use matrix_sdk_test::async_test;
+    use ruma::room_id;
+
+    use super::{
+        super::{client_and_server_prelude, new_rooms},
+        *,
+    };
+
+    #[async_test]
+    async fn test_non_replaced() {
+        let (client, server, sliding_sync) = client_and_server_prelude().await;
+        let [room] = new_rooms([room_id!("!a:b.c")], &client, &server, &sliding_sync).await;
+
+        let matcher = NonReplacedRoomMatcher { successor_state: |_| None };
+        assert!(matcher.matches(&room));
+
+        // The successor hasn't been joined yet.
+        let matcher = NonReplacedRoomMatcher { successor_state: |_| Some(RoomState::Invited) };
+        assert!(matcher.matches(&room));
+    }
+
+    #[async_test]
+    async fn test_replaced() {
+        let (client, server, sliding_sync) = client_and_server_prelude().await;
+        let [room] = new_rooms([room_id!("!a:b.c")], &client, &server, &sliding_sync).await;
+
+        let matcher = NonReplacedRoomMatcher { successor_state: |_| Some(RoomState::Joined) };
+        assert!(matcher.matches(&room).not());
+    }
+}
@@ -0,0 +1,255 @@
+// Copyright 2025 The Matrix.org Foundation C.I.C.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::{collections::BTreeSet, sync::Mutex};
+
+use matrix_sdk_base::{RoomInfoNotableUpdate, RoomInfoNotableUpdateReasons};
+use ruma::{OwnedRoomId, RoomId};
+use tokio::sync::broadcast::{self, error::TryRecvError};
+
+use super::{super::Room, Filter};
+
+/// The rooms of a space, as declared by its `m.space.child` events and those
+/// of its subspaces.
+struct SpaceMembers {
+    /// The space, and all the spaces it contains if the matcher is recursive.
+    spaces: BTreeSet<OwnedRoomId>,
+    /// The children declared by these spaces.
+    children: BTreeSet<OwnedRoomId>,
+}
+
+struct SpaceRoomMatcher<C, P>
+where
+    C: Fn(&RoomId) -> BTreeSet<OwnedRoomId>,
+    P: Fn(&RoomId) -> BTreeSet<OwnedRoomId>,
+{
+    /// Get the children declared by a room with `m.space.child` events.
+    space_children: C,
+    /// Get the parents declared by a room with `m.space.parent` events.
+    space_parents: P,
+    space_id: OwnedRoomId,
+    recursive: bool,
+}
+
+impl<C, P> SpaceRoomMatcher<C, P>
+where
+    C: Fn(&RoomId) -> BTreeSet<OwnedRoomId>,
+    P: Fn(&RoomId) -> BTreeSet<OwnedRoomId>,
+{
+    /// Get the space, all the spaces it contains if the matcher is recursive,
+    /// and their children.
+    fn members(&self) -> SpaceMembers {
+        let mut spaces = BTreeSet::from([self.space_id.clone()]);
+        let mut children = (self.space_children)(&self.space_id);
+
+        if !self.recursive {
+            return SpaceMembers { spaces, children };
+        }
+
+        let mut to_visit = children.clone();
+
+        while let Some(room_id) = to_visit.pop_first() {
+            // Spaces can form cycles, don't visit a space twice.
+            if spaces.insert(room_id.clone()) {
+                let room_children = (self.space_children)(&room_id);
+                to_visit.extend(
+                    room_children.iter().filter(|child_id| !spaces.contains(*child_id)).cloned(),
+                );
+                children.extend(room_children);
+            }
+        }
+
+        SpaceMembers { spaces, children }
+    }
+
+    fn matches_members(&self, members: &SpaceMembers, room_id: &RoomId) -> bool {
+        if room_id == self.space_id {
+            return false;
+        }
+
+        // Either a space declares the room as its child…
+        members.children.contains(room_id)
+            // … or the room declares one of the spaces as its parent.
+            || (self.space_parents)(room_id).iter().any(|parent_id| members.spaces.contains(parent_id))
+    }
+
+    #[cfg(test)]
+    fn matches(&self, room_id: &RoomId) -> bool {
+        self.matches_members(&self.members(), room_id)
+    }
+}
+
+/// The members of a space, computed once and kept until the space graph
+/// changes.
+struct CachedSpaceMembers {
+    members: SpaceMembers,
+    /// Receives the notable updates of the rooms, to know when the space graph
+    /// changed.
+    updates: broadcast::Receiver<RoomInfoNotableUpdate>,
+}
+
+impl CachedSpaceMembers {
+    /// Whether the space graph changed since the members were computed.
+    fn is_outdated(&mut self) -> bool {
+        let mut outdated = false;
+
+        loop {
+            match self.updates.try_recv() {
+                Ok(update) => {
+                    outdated |=
+                        update.reasons.contains(RoomInfoNotableUpdateReasons::SPACE_RELATIONS);
+                }
+                Err(TryRecvError::Lagged(_)) => outdated = true,
+                Err(TryRecvError::Empty | TryRecvError::Closed) => break,
+            }
+        }
+
+        outdated
+    }
+}
+
+/// Create a new filter that will accept all rooms that belong to the space
+/// `space_id`, according to the `m.space.child` events of the space and the
+/// `m.space.parent` events of the rooms.
+///
+/// If `recursive` is true, the rooms that belong to the subspaces of the space
+/// are accepted too, at any depth.
+///
+/// The filter is evaluated against the latest known relations, so a room list
+/// using it is updated when the hierarchy of the space changes. The rooms of
+/// the space are only computed again when the hierarchy changes, not for
+/// every room the filter is evaluated against.
+pub fn new_filter(space_id: OwnedRoomId, recursive: bool) -> impl Filter {
+    let cache = Mutex::new(None::<CachedSpaceMembers>);
+
+    move |room| -> bool {
+        let client = room.client();
+
+        let matcher = SpaceRoomMatcher {
+            space_children: |room_id: &RoomId| {
+                client.get_room(room_id).map(|room| room.space_child_ids()).unwrap_or_default()
+            },
+            space_parents: |room_id: &RoomId| {
+                client.get_room(room_id).map(|room| room.space_parent_ids()).unwrap_or_default()
+            },
+            space_id: space_id.clone(),
+            recursive,
+        };
+
+        let mut cache = cache.lock().unwrap();
+
+        if cache.as_mut().is_none_or(|cache| cache.is_outdated()) {
+            // Subscribe before computing the members, so no change is missed.
+            let updates = client.room_info_notable_update_receiver();
+            *cache = Some(CachedSpaceMembers { members: matcher.members(), updates });
+        }
+
+        let members = &cache.as_ref().expect("the cache was just filled").members;
+        matcher.matches_members(members, room.room_id())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        collections::{BTreeMap, BTreeSet},
+        ops::Not,
+    };
+
+    use ruma::{owned_room_id, room_id, OwnedRoomId, RoomId};
+
+    use super::SpaceRoomMatcher;
+
+    fn new_matcher(
+        children: BTreeMap<OwnedRoomId, BTreeSet<OwnedRoomId>>,
+        parents: BTreeMap<OwnedRoomId, BTreeSet<OwnedRoomId>>,
+        recursive: bool,
+    ) -> SpaceRoomMatcher<
+        impl Fn(&RoomId) -> BTreeSet<OwnedRoomId>,
+        impl Fn(&RoomId) -> BTreeSet<OwnedRoomId>,
+    > {
+        SpaceRoomMatcher {
+            space_children: move |room_id: &RoomId| {
+                children.get(room_id).cloned().unwrap_or_default()
+            },
+            space_parents: move |room_id: &RoomId| {
+                parents.get(room_id).cloned().unwrap_or_default()
+            },
+            space_id: owned_room_id!("!space:b.c"),
+            recursive,
+        }
+    }
+
+    fn hierarchy() -> BTreeMap<OwnedRoomId, BTreeSet<OwnedRoomId>> {
+        BTreeMap::from([
+            (
+                owned_room_id!("!space:b.c"),
+                BTreeSet::from([owned_room_id!("!a:b.c"), owned_room_id!("!subspace:b.c")]),
+            ),
+            (owned_room_id!("!subspace:b.c"), BTreeSet::from([owned_room_id!("!b:b.c")])),
+        ])
+    }
+
+    #[test]
+    fn test_direct_child() {
+        let matcher = new_matcher(hierarchy(), BTreeMap::new(), false);
+
+        assert!(matcher.matches(room_id!("!a:b.c")));
+        assert!(matcher.matches(room_id!("!subspace:b.c")));
+        assert!(matcher.matches(room_id!("!b:b.c")).not());
+        assert!(matcher.matches(room_id!("!c:b.c")).not());
+        // The space itself isn't part of the space.
+        assert!(matcher.matches(room_id!("!space:b.c")).not());
+    }
+
+    #[test]
+    fn test_recursive_child() {
+        let matcher = new_matcher(hierarchy(), BTreeMap::new(), true);
+
+        assert!(matcher.matches(room_id!("!a:b.c")));
+        assert!(matcher.matches(room_id!("!subspace:b.c")));
+        assert!(matcher.matches(room_id!("!b:b.c")));
+        assert!(matcher.matches(room_id!("!c:b.c")).not());
+    }
+
+    #[test]
+    fn test_parent() {
+        let parents = BTreeMap::from([
+            (owned_room_id!("!c:b.c"), BTreeSet::from([owned_room_id!("!space:b.c")])),
+            (owned_room_id!("!d:b.c"), BTreeSet::from([owned_room_id!("!subspace:b.c")])),
+        ]);
+
+        let matcher = new_matcher(hierarchy(), parents.clone(), false);
+        assert!(matcher.matches(room_id!("!c:b.c")));
+        assert!(matcher.matches(room_id!("!d:b.c")).not());
+
+        let matcher = new_matcher(hierarchy(), parents, true);
+        assert!(matcher.matches(room_id!("!c:b.c")));
+        assert!(matcher.matches(room_id!("!d:b.c")));
+    }
+
+    #[test]
+    fn test_cycle() {
+        let mut children = hierarchy();
+        children
+            .entry(owned_room_id!("!subspace:b.c"))
+            .or_default()
+            .insert(owned_room_id!("!space:b.c"));
+
+        let matcher = new_matcher(children, BTreeMap::new(), true);
+
+        assert!(matcher.matches(room_id!("!b:b.c")));
+        assert!(matcher.matches(room_id!("!space:b.c")).not());
+    }
+}
@@ -143,6 +143,11 @@ impl RoomList {
     /// call to [`RoomListDynamicEntriesController::set_filter`], the stream
     /// will yield a [`VectorDiff::Reset`] followed by any updates of the
     /// room list under that filter (until the next reset).
+    ///
+    /// To hide the rooms that have been upgraded once the user has joined
+    /// their successor, include [`new_filter_non_replaced()`] in the filter.
+    ///
+    /// [`new_filter_non_replaced()`]: super::filters::new_filter_non_replaced
     pub fn entries_with_dynamic_adapters(
         &self,
         page_size: usize,
@@ -28,6 +28,7 @@ use tracing::{info, info_span, trace, warn, Instrument, Span};
 
 use super::{
     controller::{TimelineController, TimelineSettings},
+    predecessor::PredecessorHistory,
     to_device::{handle_forwarded_room_key_event, handle_room_key_event},
     DateDividerMode, Error, Timeline, TimelineDropHandle, TimelineFocus,
 };
@@ -48,6 +49,10 @@ pub struct TimelineBuilder {
 
     /// An optional prefix for internal IDs.
     internal_id_prefix: Option<String>,
+
+    /// Whether a live timeline paginates back into the predecessors of the
+    /// room.
+    paginate_into_predecessors: bool,
 }
 
 impl TimelineBuilder {
@@ -58,6 +63,7 @@ impl TimelineBuilder {
             unable_to_decrypt_hook: None,
             focus: TimelineFocus::Live,
             internal_id_prefix: None,
+            paginate_into_predecessors: false,
         }
     }
 
@@ -101,6 +107,19 @@ impl TimelineBuilder {
         self
     }
 
+    /// Enable back-pagination into the predecessors of the room, i.e. the
+    /// rooms it replaced after room upgrades, once the start of the room has
+    /// been reached.
+    ///
+    /// The history of the predecessors then appears seamlessly before the
+    /// history of the room. Only the predecessors that the current user is a
+    /// member of can be paginated. This has no effect if the timeline isn't
+    /// focused on live events.
+    pub fn paginate_into_predecessors(mut self) -> Self {
+        self.paginate_into_predecessors = true;
+        self
+    }
+
     /// Use the given filter to choose whether to add events to the timeline.
     ///
     /// # Arguments
@@ -151,7 +170,14 @@ impl TimelineBuilder {
         )
     )]
     pub async fn build(self) -> Result<Timeline, Error> {
-        let Self { room, settings, unable_to_decrypt_hook, focus, internal_id_prefix } = self;
+        let Self {
+            room,
+            settings,
+            unable_to_decrypt_hook,
+            focus,
+            internal_id_prefix,
+            paginate_into_predecessors,
+        } = self;
 
         let client = room.client();
         let event_cache = client.event_cache();
@@ -164,8 +190,12 @@ impl TimelineBuilder {
 
         let is_live = matches!(focus, TimelineFocus::Live);
         let is_pinned_events = matches!(focus, TimelineFocus::PinnedEvents { .. });
+        let is_thread = matches!(focus, TimelineFocus::Thread { .. });
         let is_room_encrypted = room.is_encrypted().await.ok().unwrap_or_default();
 
+        let predecessor_history = (is_live && paginate_into_predecessors)
+            .then(|| Arc::new(PredecessorHistory::new(room.clone())));
+
         let controller = TimelineController::new(
             room,
             focus.clone(),
@@ -175,6 +205,10 @@ impl TimelineBuilder {
         )
         .with_settings(settings);
 
+        // Start with the thread summaries known to the event cache, so thread roots get
+        // their summary as soon as they're added to the timeline.
+        controller.handle_thread_summaries(room_event_cache.thread_summaries().await).await;
+
         let has_events = controller.init_focus(&room_event_cache).await?;
 
         let room = controller.room();
@@ -211,6 +245,7 @@ impl TimelineBuilder {
         let room_update_join_handle = spawn({
             let room_event_cache = room_event_cache.clone();
             let inner = controller.clone();
+            let predecessor_history = predecessor_history.clone();
 
             let span = info_span!(
                 parent: Span::none(),
@@ -244,6 +279,10 @@ impl TimelineBuilder {
                             // current timeline.
                             let (initial_events, _stream) = room_event_cache.subscribe().await;
 
+                            if let Some(predecessor_history) = &predecessor_history {
+                                predecessor_history.reset().await;
+                            }
+
                             inner
                                 .replace_with_initial_remote_events(
                                     initial_events.into_iter(),
@@ -264,6 +303,15 @@ impl TimelineBuilder {
                         RoomEventCacheUpdate::UpdateTimelineEvents { diffs, origin } => {
                             trace!("Received new timeline events diffs");
 
+                            // A thread timeline only takes the new events from sync that belong
+                            // to the thread.
+                            if is_thread {
+                                if matches!(origin, EventsOrigin::Sync) {
+                                    inner.handle_live_thread_events(diffs).await;
+                                }
+                                continue;
+                            }
+
                             // We shouldn't use the general way of adding events to timelines to
                             // non-live timelines, such as pinned events or focused timeline.
                             // These timelines should handle any live updates by themselves.
@@ -271,16 +319,26 @@ impl TimelineBuilder {
                                 continue;
                             }
 
-                            inner
-                                .handle_remote_events_with_diffs(
-                                    diffs,
-                                    match origin {
-                                        EventsOrigin::Sync => RemoteEventOrigin::Sync,
-                                        EventsOrigin::Pagination => RemoteEventOrigin::Pagination,
-                                        EventsOrigin::Cache => RemoteEventOrigin::Cache,
-                                    },
-                                )
-                                .await;
+                            let origin = match origin {
+                                EventsOrigin::Sync => RemoteEventOrigin::Sync,
+                                EventsOrigin::Pagination => RemoteEventOrigin::Pagination,
+                                EventsOrigin::Cache => RemoteEventOrigin::Cache,
+                            };
+
+                            // The events of the predecessors of the room, if any, shift the
+                            // positions of the events of the room.
+                            if let Some(predecessor_history) = &predecessor_history {
+                                predecessor_history
+                                    .handle_remote_events_with_diffs(&inner, diffs, origin)
+                                    .await;
+                            } else {
+                                inner.handle_remote_events_with_diffs(diffs, origin).await;
+                            }
+                        }
+
+                        RoomEventCacheUpdate::UpdateThreadSummaries { summaries } => {
+                            trace!("Received new thread summaries");
+                            inner.handle_thread_summaries(summaries).await;
                         }
 
                         RoomEventCacheUpdate::AddEphemeralEvents { events } => {
@@ -473,6 +531,7 @@ impl TimelineBuilder {
         let timeline = Timeline {
             controller,
             event_cache: room_event_cache,
+            predecessor_history,
             drop_handle: Arc::new(TimelineDropHandle {
                 client,
                 event_handler_handles: handles,
@@ -18,7 +18,7 @@ use std::{
     sync::Arc,
 };
 
-use matrix_sdk::ring_buffer::RingBuffer;
+use matrix_sdk::{event_cache::ThreadSummary, ring_buffer::RingBuffer};
 use ruma::{EventId, OwnedEventId, OwnedUserId, RoomVersionId};
 use tracing::trace;
 
@@ -82,6 +82,13 @@ pub(in crate::timeline) struct TimelineMetadata {
     /// Given an event, what are all the events that are replies to it?
     pub replies: HashMap<OwnedEventId, BTreeSet<OwnedEventId>>,
 
+    /// The summaries of the threads known to the event cache, mapped by the
+    /// event ID of their thread root.
+    ///
+    /// This is kept across timeline clears, since it mirrors the event cache
+    /// state, which sends updates only for the summaries that changed.
+    pub thread_summaries: HashMap<OwnedEventId, ThreadSummary>,
+
     /// Edit events received before the related event they're editing.
     pub pending_edits: RingBuffer<PendingEdit>,
 
@@ -122,6 +129,7 @@ impl TimelineMetadata {
             aggregations: Default::default(),
             pending_edits: RingBuffer::new(MAX_NUM_STASHED_PENDING_EDITS),
             replies: Default::default(),
+            thread_summaries: Default::default(),
             fully_read_event: Default::default(),
             // It doesn't make sense to set this to false until we fill the `fully_read_event`
             // field, otherwise we'll keep on exiting early in `Self::update_read_marker`.
@@ -12,7 +12,11 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use std::{collections::BTreeSet, fmt, sync::Arc};
+use std::{
+    collections::{BTreeMap, BTreeSet, HashSet},
+    fmt,
+    sync::Arc,
+};
 
 use as_variant::as_variant;
 use eyeball_im::VectorDiff;
@@ -25,7 +29,7 @@ use matrix_sdk::{
     deserialized_responses::{TimelineEvent, TimelineEventKind as SdkTimelineEventKind},
     event_cache::{
         paginator::{PaginationResult, Paginator},
-        RoomEventCache,
+        RoomEventCache, ThreadSummary,
     },
     send_queue::{
         LocalEcho, LocalEchoContent, RoomSendQueueUpdate, SendHandle, SendReactionHandle,
@@ -38,10 +42,10 @@ use ruma::{
         poll::unstable_start::UnstablePollStartEventContent,
         reaction::ReactionEventContent,
         receipt::{Receipt, ReceiptThread, ReceiptType},
-        relation::Annotation,
+        relation::{Annotation, Thread},
         room::message::{MessageType, Relation},
-        AnyMessageLikeEventContent, AnySyncEphemeralRoomEvent, AnySyncMessageLikeEvent,
-        AnySyncTimelineEvent, MessageLikeEventType,
+        AnyMessageLikeEventContent, AnyStateEventContent, AnySyncEphemeralRoomEvent,
+        AnySyncMessageLikeEvent, AnySyncTimelineEvent, FullStateEventContent, MessageLikeEventType,
     },
     serde::Raw,
     EventId, MilliSecondsSinceUnixEpoch, OwnedEventId, OwnedTransactionId, RoomVersionId,
@@ -66,13 +70,14 @@ pub(super) use self::{
 use super::{
     algorithms::{rfind_event_by_id, rfind_event_item},
     event_handler::TimelineEventKind,
-    event_item::{ReactionStatus, RemoteEventOrigin},
+    event_item::{ReactionStatus, RemoteEventOrigin, RemoteEventTimelineItem},
     item::TimelineUniqueId,
     subscriber::TimelineSubscriber,
     traits::{Decryptor, RoomDataProvider},
-    DateDividerMode, Error, EventSendState, EventTimelineItem, InReplyToDetails, Message,
-    PaginationError, Profile, RepliedToEvent, TimelineDetails, TimelineEventItemId, TimelineFocus,
-    TimelineItem, TimelineItemContent, TimelineItemKind,
+    AnyOtherFullStateEventContent, DateDividerMode, Error, EventSendState, EventTimelineItem,
+    InReplyToDetails, MediaUploadProgress, Message, PaginationError, Profile, RepliedToEvent,
+    TimelineDetails, TimelineEventItemId, TimelineFocus, TimelineItem, TimelineItemContent,
+    TimelineItemKind,
 };
 use crate::{
     timeline::{
@@ -80,6 +85,7 @@ use crate::{
         date_dividers::DateDividerAdjuster,
         event_item::EventTimelineItemKind,
         pinned_events_loader::{PinnedEventsLoader, PinnedEventsLoaderError},
+        threaded_events_loader::{thread_membership, ThreadMembership, ThreadedEventsLoader},
         TimelineEventFilterFn,
     },
     unable_to_decrypt_hook::UtdHookManager,
@@ -114,8 +120,18 @@ enum TimelineFocusData<P: RoomDataProvider> {
     PinnedEvents {
         loader: PinnedEventsLoader,
     },
+
+    /// The timeline is focused on a thread, and receives the thread replies
+    /// from sync.
+    Thread {
+        loader: ThreadedEventsLoader,
+    },
 }
 
+/// Number of thread replies to load when initializing a thread-focused
+/// timeline.
+const THREAD_INITIAL_BATCH_SIZE: u16 = 20;
+
 #[derive(Clone, Debug)]
 pub(super) struct TimelineController<P: RoomDataProvider = Room> {
     /// Inner mutable state.
@@ -175,6 +191,7 @@ pub(super) enum TimelineFocusKind {
     Live,
     Event,
     PinnedEvents,
+    Thread,
 }
 
 /// The default event filter for
@@ -285,6 +302,16 @@ impl<P: RoomDataProvider> TimelineController<P> {
                 },
                 TimelineFocusKind::PinnedEvents,
             ),
+
+            TimelineFocus::Thread { root_event_id } => (
+                TimelineFocusData::Thread {
+                    loader: ThreadedEventsLoader::new(
+                        Box::new(room_data_provider.clone()),
+                        root_event_id,
+                    ),
+                },
+                TimelineFocusKind::Thread,
+            ),
         };
 
         let state = TimelineState::new(
@@ -367,6 +394,26 @@ impl<P: RoomDataProvider> TimelineController<P> {
 
                 Ok(has_events)
             }
+
+            TimelineFocusData::Thread { loader } => {
+                let PaginationResult { events, .. } = loader
+                    .paginate_backwards(THREAD_INITIAL_BATCH_SIZE.into())
+                    .await
+                    .map_err(PaginationError::Paginator)?;
+
+                drop(focus_guard);
+
+                let has_events = !events.is_empty();
+
+                // Events are in reverse topological order.
+                self.replace_with_initial_remote_events(
+                    events.into_iter().rev(),
+                    RemoteEventOrigin::Pagination,
+                )
+                .await;
+
+                Ok(has_events)
+            }
         }
     }
 
@@ -450,6 +497,10 @@ impl<P: RoomDataProvider> TimelineController<P> {
                 .paginate_backward(num_events.into())
                 .await
                 .map_err(PaginationError::Paginator)?,
+            TimelineFocusData::Thread { loader } => loader
+                .paginate_backwards(num_events.into())
+                .await
+                .map_err(PaginationError::Paginator)?,
         };
 
         // Events are in reverse topological order.
@@ -479,6 +530,11 @@ impl<P: RoomDataProvider> TimelineController<P> {
                 .paginate_forward(num_events.into())
                 .await
                 .map_err(PaginationError::Paginator)?,
+            TimelineFocusData::Thread { .. } => {
+                // A thread timeline receives new thread replies from the sync, so there's
+                // nothing to paginate forwards.
+                return Ok(true);
+            }
         };
 
         // Events are in topological order.
@@ -497,6 +553,61 @@ impl<P: RoomDataProvider> TimelineController<P> {
         matches!(&*self.focus.read().await, TimelineFocusData::Live)
     }
 
+    /// The thread root this timeline is focused on, if any.
+    pub(super) async fn thread_root(&self) -> Option<OwnedEventId> {
+        as_variant!(
+            &*self.focus.read().await,
+            TimelineFocusData::Thread { loader } => loader.root_event_id().to_owned()
+        )
+    }
+
+    /// If the timeline is focused on a thread, turns a room message without
+    /// any relation into a reply in this thread.
+    ///
+    /// Any other content is returned as is.
+    pub(super) async fn maybe_add_thread_relation(
+        &self,
+        content: AnyMessageLikeEventContent,
+    ) -> AnyMessageLikeEventContent {
+        let Some(root_event_id) = self.thread_root().await else {
+            return content;
+        };
+
+        match content {
+            AnyMessageLikeEventContent::RoomMessage(mut message)
+                if message.relates_to.is_none() =>
+            {
+                // Use the latest event in the thread as the reply fallback, for clients not
+                // supporting threads.
+                let latest_event_id =
+                    self.latest_event_id().await.unwrap_or_else(|| root_event_id.clone());
+                message.relates_to =
+                    Some(Relation::Thread(Thread::plain(root_event_id, latest_event_id)));
+                AnyMessageLikeEventContent::RoomMessage(message)
+            }
+            content => content,
+        }
+    }
+
+    /// Should a local echo with the given content be displayed in this
+    /// timeline?
+    async fn should_add_local_echo(&self, content: &TimelineEventKind) -> bool {
+        match &*self.focus.read().await {
+            TimelineFocusData::Live => true,
+            TimelineFocusData::Event { .. } | TimelineFocusData::PinnedEvents { .. } => false,
+            TimelineFocusData::Thread { loader } => matches!(
+                content,
+                TimelineEventKind::Message {
+                    content: AnyMessageLikeEventContent::RoomMessage(message),
+                    ..
+                } if matches!(
+                    &message.relates_to,
+                    Some(Relation::Thread(thread)) if *thread.event_id == *loader.root_event_id()
+                )
+            ),
+        }
+    }
+
     pub(super) fn with_settings(mut self, settings: TimelineSettings) -> Self {
         self.settings = settings;
         self
@@ -700,6 +811,117 @@ impl<P: RoomDataProvider> TimelineController<P> {
             .await
     }
 
+    /// Add remote events, given in reverse topological order, to the start of
+    /// the timeline.
+    ///
+    /// Returns the number of remote events that were actually added, which can
+    /// be lower than the number of given events if some couldn't be handled.
+    pub(super) async fn push_front_remote_events(
+        &self,
+        events: Vec<TimelineEvent>,
+        origin: RemoteEventOrigin,
+    ) -> usize {
+        if events.is_empty() {
+            return 0;
+        }
+
+        let mut state = self.state.write().await;
+        let previous_len = state.items.all_remote_events().iter().len();
+
+        state
+            .handle_remote_events_with_diffs(
+                events.into_iter().map(|value| VectorDiff::PushFront { value }).collect(),
+                origin,
+                &self.room_data_provider,
+                &self.settings,
+            )
+            .await;
+
+        state.items.all_remote_events().iter().len().saturating_sub(previous_len)
+    }
+
+    /// Update the thread summaries of the timeline, and the thread roots in
+    /// the timeline, if any.
+    pub(super) async fn handle_thread_summaries(
+        &self,
+        summaries: BTreeMap<OwnedEventId, ThreadSummary>,
+    ) {
+        if summaries.is_empty() {
+            return;
+        }
+
+        let mut state = self.state.write().await;
+
+        for (root_event_id, summary) in summaries {
+            if let Some((item_pos, item)) = rfind_event_by_id(&state.items, &root_event_id) {
+                if item.thread_summary() != Some(&summary) {
+                    trace!(%root_event_id, "Updating thread summary");
+                    let new_item = item.with_thread_summary(Some(summary.clone()));
+                    let new_item = TimelineItem::new(new_item, item.internal_id.clone());
+                    state.items.replace(item_pos, new_item);
+                }
+            }
+
+            state.meta.thread_summaries.insert(root_event_id, summary);
+        }
+    }
+
+    /// Handle live updates from the room's event cache, for a timeline
+    /// focused on a thread.
+    ///
+    /// Only the new events belonging to the thread, or relating to an event
+    /// already in this timeline (e.g. reactions, edits and redactions), are
+    /// added to the timeline.
+    pub(super) async fn handle_live_thread_events(&self, diffs: Vec<VectorDiff<TimelineEvent>>) {
+        let Some(root_event_id) = self.thread_root().await else {
+            return;
+        };
+
+        let mut state = self.state.write().await;
+
+        let mut known_event_ids: HashSet<OwnedEventId> =
+            state.items.all_remote_events().iter().map(|meta| meta.event_id.clone()).collect();
+
+        let mut thread_events = Vector::new();
+
+        for diff in diffs {
+            let new_events: Vec<TimelineEvent> = match diff {
+                VectorDiff::Append { values } => values.into_iter().collect(),
+                VectorDiff::PushBack { value } | VectorDiff::Insert { value, .. } => vec![value],
+                // Other updates don't bring new events from sync.
+                _ => continue,
+            };
+
+            for event in new_events {
+                let is_relevant = match thread_membership(&event, &root_event_id) {
+                    ThreadMembership::InThread => true,
+                    ThreadMembership::RelatesTo(target) => known_event_ids.contains(&target),
+                    ThreadMembership::Unrelated => false,
+                };
+
+                if is_relevant {
+                    if let Some(event_id) = event.event_id() {
+                        known_event_ids.insert(event_id);
+                    }
+                    thread_events.push_back(event);
+                }
+            }
+        }
+
+        if thread_events.is_empty() {
+            return;
+        }
+
+        state
+            .handle_remote_events_with_diffs(
+                vec![VectorDiff::Append { values: thread_events }],
+                RemoteEventOrigin::Sync,
+                &self.room_data_provider,
+                &self.settings,
+            )
+            .await;
+    }
+
     pub(super) async fn clear(&self) {
         self.state.write().await.clear();
     }
@@ -777,8 +999,9 @@ impl<P: RoomDataProvider> TimelineController<P> {
         let sender = self.room_data_provider.own_user_id().to_owned();
         let profile = self.room_data_provider.profile_from_user_id(&sender).await;
 
-        // Only add new items if the timeline is live.
-        let should_add_new_items = self.is_live().await;
+        // Only add new items if the timeline is live, or if they belong to the thread
+        // the timeline is focused on.
+        let should_add_new_items = self.should_add_local_echo(&content).await;
 
         let date_divider_mode = self.settings.date_divider_mode.clone();
 
@@ -896,6 +1119,18 @@ impl<P: RoomDataProvider> TimelineController<P> {
                 }
             }
 
+            // A redaction only updates the item it redacts once it's received from the
+            // server.
+            if rfind_event_item(&txn.items, |it| {
+                it.as_remote()
+                    .is_some_and(|remote| remote.pending_redaction.as_deref() == Some(txn_id))
+            })
+            .is_some()
+            {
+                trace!("Ignoring send state update of a pending redaction");
+                return;
+            }
+
             warn!("Timeline item not found, can't update send state");
             return;
         };
@@ -1012,7 +1247,7 @@ impl<P: RoomDataProvider> TimelineController<P> {
                 warn!("We looked for a local item, but it transitioned as remote??");
                 return false;
             };
-            prev_local_item.with_send_state(EventSendState::NotSentYet)
+            prev_local_item.with_send_state(EventSendState::NotSentYet { progress: None })
         };
 
         // Replace the local-related state (kind) and the content state.
@@ -1147,7 +1382,7 @@ impl<P: RoomDataProvider> TimelineController<P> {
                             } else {
                                 // Notify observers that we managed to eventually decrypt an event.
                                 if let Some(hook) = unable_to_decrypt_hook {
-                                    hook.on_late_decrypt(&remote_event.event_id).await;
+                                    hook.on_late_decrypt(&remote_event.event_id, session_id).await;
                                 }
 
                                 Some(event)
@@ -1315,7 +1550,7 @@ impl<P: RoomDataProvider> TimelineController<P> {
 
     /// Handle a room send update that's a new local echo.
     pub(crate) async fn handle_local_echo(&self, echo: LocalEcho) {
-        match echo.content {
+        let (content, send_handle, send_error) = match echo.content {
             LocalEchoContent::Event { serialized_event, send_handle, send_error } => {
                 let content = match serialized_event.deserialize() {
                     Ok(d) => d,
@@ -1325,31 +1560,117 @@ impl<P: RoomDataProvider> TimelineController<P> {
                     }
                 };
 
-                self.handle_local_event(
-                    echo.transaction_id.clone(),
+                (
                     TimelineEventKind::Message { content, relations: Default::default() },
-                    Some(send_handle),
+                    send_handle,
+                    send_error,
                 )
-                .await;
+            }
 
-                if let Some(send_error) = send_error {
-                    self.update_event_send_state(
-                        &echo.transaction_id,
-                        EventSendState::SendingFailed {
-                            error: Arc::new(matrix_sdk::Error::SendQueueWedgeError(send_error)),
-                            is_recoverable: false,
-                        },
-                    )
-                    .await;
-                }
+            LocalEchoContent::State { event_type, state_key, content, send_handle, send_error } => {
+                let content = match content.deserialize_with_type(event_type.clone().into()) {
+                    Ok(d) => d,
+                    Err(err) => {
+                        warn!("error deserializing local echo of a state event: {err}");
+                        return;
+                    }
+                };
+
+                let content = match content {
+                    AnyStateEventContent::RoomMember(content) => {
+                        let Ok(user_id) = UserId::parse(&state_key) else {
+                            warn!("invalid state key for the local echo of a membership event");
+                            return;
+                        };
+
+                        TimelineEventKind::RoomMember {
+                            user_id,
+                            content: FullStateEventContent::Original {
+                                content,
+                                prev_content: None,
+                            },
+                            sender: self.room_data_provider.own_user_id().to_owned(),
+                        }
+                    }
+
+                    content => TimelineEventKind::OtherState {
+                        state_key,
+                        content: AnyOtherFullStateEventContent::with_local_event_content(
+                            content, event_type,
+                        ),
+                    },
+                };
+
+                (content, send_handle, send_error)
             }
 
             LocalEchoContent::React { key, send_handle, applies_to } => {
                 self.handle_local_reaction(key, send_handle, applies_to).await;
+                return;
+            }
+
+            LocalEchoContent::Redaction { redacts, .. } => {
+                // The redaction is only applied once it's been received from the server, in
+                // the meantime the redacted item is marked as pending redaction.
+                if !self
+                    .set_pending_redaction(
+                        |item| item.event_id == redacts,
+                        Some(echo.transaction_id),
+                    )
+                    .await
+                {
+                    debug!(%redacts, "the item of the redacted event is not in the timeline");
+                }
+                return;
             }
+        };
+
+        self.handle_local_event(echo.transaction_id.clone(), content, Some(send_handle)).await;
+
+        if let Some(send_error) = send_error {
+            self.update_event_send_state(
+                &echo.transaction_id,
+                EventSendState::SendingFailed {
+                    error: Arc::new(matrix_sdk::Error::SendQueueWedgeError(send_error)),
+                    is_recoverable: false,
+                },
+            )
+            .await;
         }
     }
 
+    /// Set the transaction ID of the pending redaction of the most recent
+    /// remote item matching the predicate, or unset it if `txn_id` is `None`.
+    ///
+    /// Returns whether a matching item was found.
+    async fn set_pending_redaction(
+        &self,
+        predicate: impl Fn(&RemoteEventTimelineItem) -> bool,
+        txn_id: Option<OwnedTransactionId>,
+    ) -> bool {
+        let mut state = self.state.write().await;
+
+        let Some((item_pos, item)) =
+            rfind_event_item(&state.items, |item| item.as_remote().is_some_and(&predicate))
+        else {
+            return false;
+        };
+
+        let Some(remote_item) = item.as_remote() else {
+            return false;
+        };
+
+        let internal_id = item.internal_id.to_owned();
+        let new_item = item.with_kind(RemoteEventTimelineItem {
+            pending_redaction: txn_id,
+            ..remote_item.clone()
+        });
+
+        state.items.replace(item_pos, TimelineItem::new(new_item, internal_id));
+
+        true
+    }
+
     /// Adds a reaction (local echo) to a local echo.
     #[instrument(skip(self, send_handle))]
     async fn handle_local_reaction(
@@ -1389,7 +1710,14 @@ impl<P: RoomDataProvider> TimelineController<P> {
             }
 
             RoomSendQueueUpdate::CancelledLocalEvent { transaction_id } => {
-                if !self.discard_local_echo(&transaction_id).await {
+                if !self.discard_local_echo(&transaction_id).await
+                    && !self
+                        .set_pending_redaction(
+                            |item| item.pending_redaction.as_ref() == Some(&transaction_id),
+                            None,
+                        )
+                        .await
+                {
                     warn!("couldn't find the local echo to discard");
                 }
             }
@@ -1417,7 +1745,11 @@ impl<P: RoomDataProvider> TimelineController<P> {
             }
 
             RoomSendQueueUpdate::RetryEvent { transaction_id } => {
-                self.update_event_send_state(&transaction_id, EventSendState::NotSentYet).await;
+                self.update_event_send_state(
+                    &transaction_id,
+                    EventSendState::NotSentYet { progress: None },
+                )
+                .await;
             }
 
             RoomSendQueueUpdate::SentEvent { transaction_id, event_id } => {
@@ -1425,6 +1757,16 @@ impl<P: RoomDataProvider> TimelineController<P> {
                     .await;
             }
 
+            RoomSendQueueUpdate::MediaUploadProgress { transaction_id, index, progress } => {
+                self.update_event_send_state(
+                    &transaction_id,
+                    EventSendState::NotSentYet {
+                        progress: Some(MediaUploadProgress { index, progress }),
+                    },
+                )
+                .await;
+            }
+
             RoomSendQueueUpdate::UploadedMedia { related_to, .. } => {
                 // TODO(bnjbvr): Do something else?
                 info!(txn_id = %related_to, "some media for a media event has been uploaded");
@@ -1522,7 +1864,7 @@ impl TimelineController {
         thread: &ReceiptThread,
         event_id: &EventId,
     ) -> bool {
-        // We don't support threaded receipts yet.
+        // Threaded receipts aren't tracked by the timeline, let the server handle them.
         if *thread != ReceiptThread::Unthreaded {
             return true;
         }
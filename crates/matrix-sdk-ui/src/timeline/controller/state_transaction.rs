@@ -270,6 +270,11 @@ impl<'a> TimelineStateTransaction<'a> {
                                 }
                             }
                         }
+                        TimelineFocusKind::Thread => {
+                            // Events from sync have already been filtered to
+                            // only keep the ones belonging to the thread, so
+                            // forward the previous decision to add it.
+                        }
                     }
                 }
 
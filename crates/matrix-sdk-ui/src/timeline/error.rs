@@ -114,6 +114,10 @@ pub enum PaginationError {
     #[error("The timeline isn't in the event focus mode")]
     NotEventFocusMode,
 
+    /// The timeline isn't in the live mode.
+    #[error("The timeline isn't in the live mode")]
+    NotLiveMode,
+
     /// An error occurred while paginating.
     #[error("Error when paginating.")]
     Paginator(#[source] PaginatorError),
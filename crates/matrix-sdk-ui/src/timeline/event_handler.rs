@@ -1011,7 +1011,7 @@ impl<'a, 'o> TimelineEventHandler<'a, 'o> {
 
         let kind: EventTimelineItemKind = match &self.ctx.flow {
             Flow::Local { txn_id, send_handle } => LocalEventTimelineItem {
-                send_state: EventSendState::NotSentYet,
+                send_state: EventSendState::NotSentYet { progress: None },
                 transaction_id: txn_id.to_owned(),
                 send_handle: send_handle.clone(),
             }
@@ -1043,6 +1043,7 @@ impl<'a, 'o> TimelineEventHandler<'a, 'o> {
                     original_json: Some(raw_event.clone()),
                     latest_edit_json: edit_json,
                     origin,
+                    pending_redaction: None,
                 }
                 .into()
             }
@@ -1059,6 +1060,11 @@ impl<'a, 'o> TimelineEventHandler<'a, 'o> {
             is_room_encrypted,
         );
 
+        if let Flow::Remote { event_id, .. } = &self.ctx.flow {
+            // Attach the summary of the thread starting at this event, if there's one.
+            item.thread_summary = self.meta.thread_summaries.get(event_id).cloned();
+        }
+
         match &self.ctx.flow {
             Flow::Local { .. } => {
                 trace!("Adding new local timeline item");
@@ -18,6 +18,7 @@ use std::{fmt, sync::Arc};
 
 use imbl::{vector, Vector};
 use matrix_sdk::{
+    attachment::gallery_items,
     crypto::types::events::UtdCause,
     deserialized_responses::{TimelineEvent, TimelineEventKind},
     Room,
@@ -132,6 +133,18 @@ impl Message {
         self.msgtype.body()
     }
 
+    /// Get the items of this message, if it's a gallery, like the ones sent
+    /// with [`RoomSendQueue::send_gallery()`].
+    ///
+    /// The gallery itself has a custom `msgtype`, whose body is the caption of
+    /// the gallery. Returns `None` if the message isn't a gallery, or if any of
+    /// its items is invalid.
+    ///
+    /// [`RoomSendQueue::send_gallery()`]: matrix_sdk::send_queue::RoomSendQueue::send_gallery
+    pub fn gallery_items(&self) -> Option<Vec<MessageType>> {
+        gallery_items(&self.msgtype)
+    }
+
     /// Get the event this message is replying to, if any.
     pub fn in_reply_to(&self) -> Option<&InReplyToDetails> {
         self.in_reply_to.as_ref()
@@ -54,8 +54,8 @@ use ruma::{
         },
         space::{child::SpaceChildEventContent, parent::SpaceParentEventContent},
         sticker::{StickerEventContent, SyncStickerEvent},
-        AnyFullStateEventContent, AnySyncTimelineEvent, FullStateEventContent,
-        MessageLikeEventType, StateEventType,
+        AnyFullStateEventContent, AnyStateEventContent, AnySyncTimelineEvent,
+        FullStateEventContent, MessageLikeEventType, StateEventType,
     },
     OwnedDeviceId, OwnedMxcUri, OwnedUserId, RoomVersionId, UserId,
 };
@@ -836,6 +836,110 @@ impl AnyOtherFullStateEventContent {
         }
     }
 
+    /// Create an `AnyOtherFullStateEventContent` from the content of a state
+    /// event that hasn't been sent yet, with the given type.
+    ///
+    /// Panics if the event content is an `m.room.member` event content.
+    pub(crate) fn with_local_event_content(
+        content: AnyStateEventContent,
+        event_type: String,
+    ) -> Self {
+        match content {
+            AnyStateEventContent::PolicyRuleRoom(content) => {
+                Self::PolicyRuleRoom(FullStateEventContent::Original {
+                    content,
+                    prev_content: None,
+                })
+            }
+            AnyStateEventContent::PolicyRuleServer(content) => {
+                Self::PolicyRuleServer(FullStateEventContent::Original {
+                    content,
+                    prev_content: None,
+                })
+            }
+            AnyStateEventContent::PolicyRuleUser(content) => {
+                Self::PolicyRuleUser(FullStateEventContent::Original {
+                    content,
+                    prev_content: None,
+                })
+            }
+            AnyStateEventContent::RoomAliases(content) => {
+                Self::RoomAliases(FullStateEventContent::Original { content, prev_content: None })
+            }
+            AnyStateEventContent::RoomAvatar(content) => {
+                Self::RoomAvatar(FullStateEventContent::Original { content, prev_content: None })
+            }
+            AnyStateEventContent::RoomCanonicalAlias(content) => {
+                Self::RoomCanonicalAlias(FullStateEventContent::Original {
+                    content,
+                    prev_content: None,
+                })
+            }
+            AnyStateEventContent::RoomCreate(content) => {
+                Self::RoomCreate(FullStateEventContent::Original { content, prev_content: None })
+            }
+            AnyStateEventContent::RoomEncryption(content) => {
+                Self::RoomEncryption(FullStateEventContent::Original {
+                    content,
+                    prev_content: None,
+                })
+            }
+            AnyStateEventContent::RoomGuestAccess(content) => {
+                Self::RoomGuestAccess(FullStateEventContent::Original {
+                    content,
+                    prev_content: None,
+                })
+            }
+            AnyStateEventContent::RoomHistoryVisibility(content) => {
+                Self::RoomHistoryVisibility(FullStateEventContent::Original {
+                    content,
+                    prev_content: None,
+                })
+            }
+            AnyStateEventContent::RoomJoinRules(content) => {
+                Self::RoomJoinRules(FullStateEventContent::Original { content, prev_content: None })
+            }
+            AnyStateEventContent::RoomName(content) => {
+                Self::RoomName(FullStateEventContent::Original { content, prev_content: None })
+            }
+            AnyStateEventContent::RoomPinnedEvents(content) => {
+                Self::RoomPinnedEvents(FullStateEventContent::Original {
+                    content,
+                    prev_content: None,
+                })
+            }
+            AnyStateEventContent::RoomPowerLevels(content) => {
+                Self::RoomPowerLevels(FullStateEventContent::Original {
+                    content,
+                    prev_content: None,
+                })
+            }
+            AnyStateEventContent::RoomServerAcl(content) => {
+                Self::RoomServerAcl(FullStateEventContent::Original { content, prev_content: None })
+            }
+            AnyStateEventContent::RoomThirdPartyInvite(content) => {
+                Self::RoomThirdPartyInvite(FullStateEventContent::Original {
+                    content,
+                    prev_content: None,
+                })
+            }
+            AnyStateEventContent::RoomTombstone(content) => {
+                Self::RoomTombstone(FullStateEventContent::Original { content, prev_content: None })
+            }
+            AnyStateEventContent::RoomTopic(content) => {
+                Self::RoomTopic(FullStateEventContent::Original { content, prev_content: None })
+            }
+            AnyStateEventContent::SpaceChild(content) => {
+                Self::SpaceChild(FullStateEventContent::Original { content, prev_content: None })
+            }
+            AnyStateEventContent::SpaceParent(content) => {
+                Self::SpaceParent(FullStateEventContent::Original { content, prev_content: None })
+            }
+            AnyStateEventContent::RoomMember(_) => unreachable!(),
+            _ => Self::_Custom { event_type },
+        }
+    }
+
     /// Get the event's type, like `m.room.create`.
     pub fn event_type(&self) -> StateEventType {
         match self {
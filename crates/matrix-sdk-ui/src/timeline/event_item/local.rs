@@ -15,7 +15,7 @@
 use std::sync::Arc;
 
 use as_variant::as_variant;
-use matrix_sdk::{send_queue::SendHandle, Error};
+use matrix_sdk::{send_queue::SendHandle, Error, TransmissionProgress};
 use ruma::{EventId, OwnedEventId, OwnedTransactionId};
 
 use super::TimelineEventItemId;
@@ -65,7 +65,15 @@ impl LocalEventTimelineItem {
 #[derive(Clone, Debug)]
 pub enum EventSendState {
     /// The local event has not been sent yet.
-    NotSentYet,
+    NotSentYet {
+        /// The progress of the upload of the media, if this is a media event
+        /// whose upload has started.
+        ///
+        /// Only reported if
+        /// [`SendQueue::enable_upload_progress()`](matrix_sdk::send_queue::SendQueue::enable_upload_progress)
+        /// has been called.
+        progress: Option<MediaUploadProgress>,
+    },
     /// The local event has been sent to the server, but unsuccessfully: The
     /// sending has failed.
     SendingFailed {
@@ -84,3 +92,17 @@ pub enum EventSendState {
         event_id: OwnedEventId,
     },
 }
+
+/// The progress of the upload of the media of a local event.
+#[derive(Clone, Copy, Debug)]
+pub struct MediaUploadProgress {
+    /// The index of the upload among the uploads of the media event.
+    ///
+    /// The thumbnail, if any, is uploaded first at index 0, then the file. For
+    /// a gallery, the uploads of each item follow the ones of the previous
+    /// item.
+    pub index: u64,
+
+    /// The progress of the upload.
+    pub progress: TransmissionProgress,
+}
@@ -21,6 +21,7 @@ use as_variant::as_variant;
 use indexmap::IndexMap;
 use matrix_sdk::{
     deserialized_responses::{EncryptionInfo, ShieldState},
+    event_cache::ThreadSummary,
     send_queue::{SendHandle, SendReactionHandle},
     Client, Error,
 };
@@ -55,7 +56,7 @@ pub use self::{
         MembershipChange, Message, OtherState, PollResult, PollState, RepliedToEvent,
         RoomMembershipChange, RoomPinnedEventsChange, Sticker, TimelineItemContent,
     },
-    local::EventSendState,
+    local::{EventSendState, MediaUploadProgress},
 };
 use super::{RepliedToInfo, ReplyContent, UnsupportedReplyItem};
 
@@ -80,6 +81,9 @@ pub struct EventTimelineItem {
     ///
     /// May be false when we don't know about the room encryption status yet.
     pub(super) is_room_encrypted: bool,
+    /// The summary of the thread starting at this event, if it's a thread
+    /// root.
+    pub(super) thread_summary: Option<ThreadSummary>,
 }
 
 #[derive(Clone, Debug)]
@@ -119,7 +123,15 @@ impl EventTimelineItem {
         kind: EventTimelineItemKind,
         is_room_encrypted: bool,
     ) -> Self {
-        Self { sender, sender_profile, timestamp, content, kind, is_room_encrypted }
+        Self {
+            sender,
+            sender_profile,
+            timestamp,
+            content,
+            kind,
+            is_room_encrypted,
+            thread_summary: None,
+        }
     }
 
     /// If the supplied low-level [`TimelineEvent`] is suitable for use as the
@@ -192,6 +204,7 @@ impl EventTimelineItem {
             original_json: Some(raw_sync_event),
             latest_edit_json,
             origin,
+            pending_redaction: None,
         }
         .into();
 
@@ -209,7 +222,15 @@ impl EventTimelineItem {
             TimelineDetails::Unavailable
         };
 
-        Some(Self { sender, sender_profile, timestamp, content, kind, is_room_encrypted: false })
+        Some(Self {
+            sender,
+            sender_profile,
+            timestamp,
+            content,
+            kind,
+            is_room_encrypted: false,
+            thread_summary: None,
+        })
     }
 
     /// Check whether this item is a local echo.
@@ -385,6 +406,14 @@ impl EventTimelineItem {
         }
     }
 
+    /// Whether a redaction of the event is waiting in the send queue.
+    pub fn is_pending_redaction(&self) -> bool {
+        match &self.kind {
+            EventTimelineItemKind::Local(_) => false,
+            EventTimelineItemKind::Remote(remote_event) => remote_event.pending_redaction.is_some(),
+        }
+    }
+
     /// Get the encryption information for the event, if any.
     pub fn encryption_info(&self) -> Option<&EncryptionInfo> {
         match &self.kind {
@@ -458,6 +487,16 @@ impl EventTimelineItem {
         self.latest_edit_json().or_else(|| self.original_json())
     }
 
+    /// Get the summary of the thread starting at this event, if this event is
+    /// a thread root.
+    ///
+    /// The summary is maintained by the event cache, and is updated whenever a
+    /// new reply to the thread is received, so it's not required to load all
+    /// the replies of a thread to know how many there are.
+    pub fn thread_summary(&self) -> Option<&ThreadSummary> {
+        self.thread_summary.as_ref()
+    }
+
     /// Get the origin of the event, i.e. where it came from.
     ///
     /// May return `None` in some edge cases that are subject to change.
@@ -510,6 +549,11 @@ impl EventTimelineItem {
         Self { sender_profile, ..self.clone() }
     }
 
+    /// Clone the current event item, and update its `thread_summary`.
+    pub(super) fn with_thread_summary(&self, thread_summary: Option<ThreadSummary>) -> Self {
+        Self { thread_summary, ..self.clone() }
+    }
+
     /// Clone the current event item, and update its `encryption_info`.
     pub(super) fn with_encryption_info(&self, encryption_info: Option<EncryptionInfo>) -> Self {
         let mut new = self.clone();
@@ -534,6 +578,7 @@ impl EventTimelineItem {
             content,
             kind,
             is_room_encrypted: self.is_room_encrypted,
+            thread_summary: self.thread_summary.clone(),
         }
     }
 
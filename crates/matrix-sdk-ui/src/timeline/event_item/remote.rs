@@ -65,12 +65,21 @@ pub(in crate::timeline) struct RemoteEventTimelineItem {
 
     /// Where we got this event from: A sync response or pagination.
     pub origin: RemoteEventOrigin,
+
+    /// The transaction ID of a redaction of this event that is waiting in the
+    /// send queue, if any.
+    pub pending_redaction: Option<OwnedTransactionId>,
 }
 
 impl RemoteEventTimelineItem {
     /// Clone the current event item, and redacts its fields.
     pub fn redact(&self) -> Self {
-        Self { original_json: None, latest_edit_json: None, ..self.clone() }
+        Self {
+            original_json: None,
+            latest_edit_json: None,
+            pending_redaction: None,
+            ..self.clone()
+        }
     }
 }
 
@@ -101,6 +110,7 @@ impl fmt::Debug for RemoteEventTimelineItem {
             latest_edit_json: _,
             is_highlighted,
             origin,
+            pending_redaction,
         } = self;
 
         f.debug_struct("RemoteEventTimelineItem")
@@ -111,6 +121,7 @@ impl fmt::Debug for RemoteEventTimelineItem {
             .field("is_highlighted", is_highlighted)
             .field("encryption_info", encryption_info)
             .field("origin", origin)
+            .field("pending_redaction", pending_redaction)
             .finish_non_exhaustive()
     }
 }
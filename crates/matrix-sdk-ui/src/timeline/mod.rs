@@ -35,7 +35,7 @@ use matrix_sdk::{
 use mime::Mime;
 use pinned_events_loader::PinnedEventsRoom;
 use ruma::{
-    api::client::receipt::create_receipt::v3::ReceiptType,
+    api::{client::receipt::create_receipt::v3::ReceiptType, Direction},
     events::{
         poll::unstable_start::{NewUnstablePollStartEventContent, UnstablePollStartEventContent},
         receipt::{Receipt, ReceiptThread},
@@ -58,6 +58,7 @@ use tracing::{error, instrument, trace, warn};
 
 use self::{
     algorithms::rfind_event_by_id, controller::TimelineController, futures::SendAttachment,
+    predecessor::PredecessorHistory,
 };
 
 mod algorithms;
@@ -72,9 +73,11 @@ pub mod futures;
 mod item;
 mod pagination;
 mod pinned_events_loader;
+mod predecessor;
 mod subscriber;
 #[cfg(test)]
 mod tests;
+mod threaded_events_loader;
 mod to_device;
 mod traits;
 mod virtual_item;
@@ -85,10 +88,10 @@ pub use self::{
     error::*,
     event_item::{
         AnyOtherFullStateEventContent, EncryptedMessage, EventItemOrigin, EventSendState,
-        EventTimelineItem, InReplyToDetails, MemberProfileChange, MembershipChange, Message,
-        OtherState, PollResult, PollState, Profile, ReactionInfo, ReactionStatus,
-        ReactionsByKeyBySender, RepliedToEvent, RoomMembershipChange, RoomPinnedEventsChange,
-        Sticker, TimelineDetails, TimelineEventItemId, TimelineItemContent,
+        EventTimelineItem, InReplyToDetails, MediaUploadProgress, MemberProfileChange,
+        MembershipChange, Message, OtherState, PollResult, PollState, Profile, ReactionInfo,
+        ReactionStatus, ReactionsByKeyBySender, RepliedToEvent, RoomMembershipChange,
+        RoomPinnedEventsChange, Sticker, TimelineDetails, TimelineEventItemId, TimelineItemContent,
     },
     event_type_filter::TimelineEventTypeFilter,
     item::{TimelineItem, TimelineItemKind, TimelineUniqueId},
@@ -150,6 +153,10 @@ pub struct Timeline {
     /// The event cache specialized for this room's view.
     event_cache: RoomEventCache,
 
+    /// The history of the predecessors of the room, if the timeline paginates
+    /// into them.
+    predecessor_history: Option<Arc<PredecessorHistory>>,
+
     /// References to long-running tasks held by the timeline.
     drop_handle: Arc<TimelineDropHandle>,
 }
@@ -166,14 +173,38 @@ pub enum TimelineFocus {
 
     /// Only show pinned events.
     PinnedEvents { max_events_to_load: u16, max_concurrent_requests: u16 },
+
+    /// Focus on a thread, i.e. only show the thread root and its replies.
+    ///
+    /// Messages sent with [`Timeline::send`] are automatically sent as replies
+    /// in this thread.
+    Thread { root_event_id: OwnedEventId },
 }
 
 impl TimelineFocus {
+    /// Create a focus on the first event of the room sent at or after the
+    /// given date, to jump to this date in the room.
+    ///
+    /// The date can be the one of a [`VirtualTimelineItem::DateDivider`], for
+    /// example.
+    ///
+    /// The returned focus is an event focus, with `num_context_events` events
+    /// around the found event.
+    pub async fn for_date(
+        room: &Room,
+        date: MilliSecondsSinceUnixEpoch,
+        num_context_events: u16,
+    ) -> Result<Self> {
+        let target = room.event_id_for_timestamp(date, Direction::Forward).await?;
+        Ok(Self::Event { target, num_context_events })
+    }
+
     pub(super) fn debug_string(&self) -> String {
         match self {
             TimelineFocus::Live => "live".to_owned(),
             TimelineFocus::Event { target, .. } => format!("permalink:{target}"),
             TimelineFocus::PinnedEvents { .. } => "pinned-events".to_owned(),
+            TimelineFocus::Thread { root_event_id } => format!("thread:{root_event_id}"),
         }
     }
 }
@@ -291,6 +322,9 @@ impl Timeline {
     /// If sending the message fails, the local echo item will change its
     /// `send_state` to [`EventSendState::SendingFailed`].
     ///
+    /// If the timeline is focused on a thread, room messages without a relation
+    /// are sent as replies in that thread.
+    ///
     /// # Arguments
     ///
     /// * `content` - The content of the message event.
@@ -302,6 +336,7 @@ impl Timeline {
         &self,
         content: AnyMessageLikeEventContent,
     ) -> Result<SendHandle, RoomSendQueueError> {
+        let content = self.controller.maybe_add_thread_relation(content).await;
         self.room().send_queue().send(content).await
     }
 
@@ -525,9 +560,10 @@ impl Timeline {
     /// If the encryption feature is enabled, this method will transparently
     /// encrypt the room message if the room is encrypted.
     ///
-    /// The attachment and its optional thumbnail are stored in the media cache
-    /// and can be retrieved at any time, by calling
-    /// [`Media::get_media_content()`] with the `MediaSource` that can be found
+    /// The attachment and its optional thumbnail are stored in the media cache,
+    /// associated to the room, and can be retrieved at any time, by calling
+    /// [`Media::get_media_content()`] on the media manager of the room,
+    /// returned by [`Room::media()`], with the `MediaSource` that can be found
     /// in the corresponding `TimelineEventItem`, and using a
     /// `MediaFormat::File`.
     ///
@@ -541,6 +577,7 @@ impl Timeline {
     ///   the attachment like a thumbnail, its size, duration etc.
     ///
     /// [`Media::get_media_content()`]: matrix_sdk::Media::get_media_content
+    /// [`Room::media()`]: matrix_sdk::Room::media
     #[instrument(skip_all)]
     pub fn send_attachment(
         &self,
@@ -729,22 +766,36 @@ impl Timeline {
         self.room().send_multiple_receipts(receipts).await
     }
 
-    /// Mark the room as read by sending an unthreaded read receipt on the
-    /// latest event, be it visible or not.
+    /// Mark the room as read by sending a read receipt on the latest event, be
+    /// it visible or not.
+    ///
+    /// For a timeline focused on a thread, a threaded read receipt is sent on
+    /// the latest event of the thread, so only the thread is marked as read.
+    /// If the latest event is the thread root, which belongs to the main
+    /// timeline, a main-threaded read receipt is sent instead. The fully read
+    /// marker can't be threaded, so it's always sent unthreaded.
     ///
-    /// This works even if the latest event belongs to a thread, as a threaded
-    /// reply also belongs to the unthreaded timeline. No threaded receipt
-    /// will be sent here (see also #3123).
+    /// Otherwise, an unthreaded read receipt is sent. This works even if the
+    /// latest event belongs to a thread, as a threaded reply also belongs to
+    /// the unthreaded timeline.
     ///
     /// Returns a boolean indicating if we sent the request or not.
     #[instrument(skip(self), fields(room_id = ?self.room().room_id()))]
     pub async fn mark_as_read(&self, receipt_type: ReceiptType) -> Result<bool> {
-        if let Some(event_id) = self.controller.latest_event_id().await {
-            self.send_single_receipt(receipt_type, ReceiptThread::Unthreaded, event_id).await
-        } else {
+        let Some(event_id) = self.controller.latest_event_id().await else {
             trace!("can't mark room as read because there's no latest event id");
-            Ok(false)
-        }
+            return Ok(false);
+        };
+
+        let thread = match self.controller.thread_root().await {
+            _ if receipt_type == ReceiptType::FullyRead => ReceiptThread::Unthreaded,
+            // The thread root is not part of its thread, but of the main timeline.
+            Some(root_event_id) if root_event_id == event_id => ReceiptThread::Main,
+            Some(root_event_id) => ReceiptThread::Thread(root_event_id),
+            None => ReceiptThread::Unthreaded,
+        };
+
+        self.send_single_receipt(receipt_type, thread, event_id).await
     }
 
     /// Adds a new pinned event by sending an updated `m.room.pinned_events`
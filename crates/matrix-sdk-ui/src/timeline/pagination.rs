@@ -19,11 +19,11 @@ use futures_util::{pin_mut, StreamExt as _};
 use matrix_sdk::event_cache::{
     self,
     paginator::{PaginatorError, PaginatorState},
-    EventCacheError, RoomPagination,
+    EventCacheError, RoomPagination, TimelineGap,
 };
 use tracing::{instrument, warn};
 
-use super::Error;
+use super::{predecessor::PredecessorHistory, Error, PaginationError};
 
 impl super::Timeline {
     /// Add more events to the start of the timeline.
@@ -32,6 +32,16 @@ impl super::Timeline {
     #[instrument(skip_all, fields(room_id = ?self.room().room_id()))]
     pub async fn paginate_backwards(&self, mut num_events: u16) -> Result<bool, Error> {
         if self.controller.is_live().await {
+            // Once the timeline has reached the predecessors of the room, only their
+            // history remains to be paginated.
+            if let Some(predecessor_history) = &self.predecessor_history {
+                if predecessor_history.is_started().await {
+                    return Ok(predecessor_history
+                        .paginate_backwards(&self.controller, num_events)
+                        .await?);
+                }
+            }
+
             match self.controller.live_lazy_paginate_backwards(num_events).await {
                 Some(needed_num_events) => {
                     num_events = needed_num_events.try_into().expect(
@@ -48,7 +58,14 @@ impl super::Timeline {
                 }
             }
 
-            Ok(self.live_paginate_backwards(num_events).await?)
+            let hit_start = self.live_paginate_backwards(num_events).await?;
+
+            match &self.predecessor_history {
+                Some(predecessor_history) if hit_start => {
+                    Ok(predecessor_history.paginate_backwards(&self.controller, num_events).await?)
+                }
+                _ => Ok(hit_start),
+            }
         } else {
             Ok(self.controller.focused_paginate_backwards(num_events).await?)
         }
@@ -66,6 +83,70 @@ impl super::Timeline {
         }
     }
 
+    /// Get the gaps between the events of a live timeline, from the oldest to
+    /// the most recent.
+    ///
+    /// A gap is a range of events that are missing between two items of the
+    /// timeline, e.g. after a long offline period. It can be shown after the
+    /// item of its [`TimelineGap::previous_event_id`], and filled with
+    /// [`Self::fill_gap_backwards()`] or [`Self::fill_gap_forwards()`]. The
+    /// gaps may change after any update of the timeline.
+    ///
+    /// The gap at the start of the timeline isn't included, it's filled with
+    /// [`Self::paginate_backwards()`]. This returns an empty list if the
+    /// timeline isn't in the live mode.
+    pub async fn gaps(&self) -> Vec<TimelineGap> {
+        if !self.controller.is_live().await {
+            return Vec::new();
+        }
+
+        self.event_cache
+            .pagination()
+            .gaps()
+            .await
+            .into_iter()
+            .filter(TimelineGap::is_interior)
+            .collect()
+    }
+
+    /// Fill a gap of a live timeline from its most recent end, i.e. add
+    /// events before the item following the gap.
+    ///
+    /// Returns what remains of the gap, or `None` if it has been filled.
+    #[instrument(skip(self), fields(room_id = ?self.room().room_id()))]
+    pub async fn fill_gap_backwards(
+        &self,
+        gap: &TimelineGap,
+        num_events: u16,
+    ) -> Result<Option<TimelineGap>, Error> {
+        if !self.controller.is_live().await {
+            return Err(PaginationError::NotLiveMode.into());
+        }
+
+        let outcome = self.event_cache.pagination().run_backwards_from_gap(gap, num_events).await?;
+
+        Ok(outcome.remaining_gap)
+    }
+
+    /// Fill a gap of a live timeline from its oldest end, i.e. add events
+    /// after the item preceding the gap.
+    ///
+    /// Returns what remains of the gap, or `None` if it has been filled.
+    #[instrument(skip(self), fields(room_id = ?self.room().room_id()))]
+    pub async fn fill_gap_forwards(
+        &self,
+        gap: &TimelineGap,
+        num_events: u16,
+    ) -> Result<Option<TimelineGap>, Error> {
+        if !self.controller.is_live().await {
+            return Err(PaginationError::NotLiveMode.into());
+        }
+
+        let outcome = self.event_cache.pagination().run_forwards_from_gap(gap, num_events).await?;
+
+        Ok(outcome.remaining_gap)
+    }
+
     /// Paginate backwards in live mode.
     ///
     /// This can only be called when the timeline is in live mode, not focused
@@ -106,6 +187,11 @@ impl super::Timeline {
     ///
     /// Note: this may send multiple Paginating/Idle sequences during a single
     /// call to [`Self::paginate_backwards()`].
+    ///
+    /// Note: the status only follows the back-pagination of the room itself.
+    /// When the timeline paginates into the predecessors of the room, the
+    /// result of [`Self::paginate_backwards()`] tells when their start has
+    /// been reached.
     pub async fn live_back_pagination_status(
         &self,
     ) -> Option<(LiveBackPaginationStatus, impl Stream<Item = LiveBackPaginationStatus>)> {
@@ -114,11 +200,15 @@ impl super::Timeline {
         }
 
         let pagination = self.event_cache.pagination();
+        let predecessor_history = self.predecessor_history.clone();
 
         let mut status = pagination.status();
 
-        let current_value =
-            LiveBackPaginationStatus::from_paginator_status(&pagination, status.next_now());
+        let current_value = LiveBackPaginationStatus::from_paginator_status(
+            &pagination,
+            predecessor_history.as_deref(),
+            status.next_now(),
+        );
 
         let stream = Box::pin(stream! {
             let status_stream = status.dedup();
@@ -126,7 +216,11 @@ impl super::Timeline {
             pin_mut!(status_stream);
 
             while let Some(state) = status_stream.next().await {
-                yield LiveBackPaginationStatus::from_paginator_status(&pagination, state);
+                yield LiveBackPaginationStatus::from_paginator_status(
+                    &pagination,
+                    predecessor_history.as_deref(),
+                    state,
+                );
             }
         });
 
@@ -154,15 +248,23 @@ impl LiveBackPaginationStatus {
     ///
     /// Private method instead of `From`/`Into` impl, to avoid making it public
     /// API.
-    fn from_paginator_status(pagination: &RoomPagination, state: PaginatorState) -> Self {
+    ///
+    /// The start of the timeline isn't considered as hit while there might be
+    /// predecessors of the room to paginate.
+    fn from_paginator_status(
+        pagination: &RoomPagination,
+        predecessor_history: Option<&PredecessorHistory>,
+        state: PaginatorState,
+    ) -> Self {
         match state {
             PaginatorState::Initial => Self::Idle { hit_start_of_timeline: false },
             PaginatorState::FetchingTargetEvent => {
                 panic!("unexpected paginator state for a live backpagination")
             }
-            PaginatorState::Idle => {
-                Self::Idle { hit_start_of_timeline: pagination.hit_timeline_start() }
-            }
+            PaginatorState::Idle => Self::Idle {
+                hit_start_of_timeline: pagination.hit_timeline_start()
+                    && !predecessor_history.is_some_and(PredecessorHistory::may_have_more),
+            },
             PaginatorState::Paginating => Self::Paginating,
         }
     }
@@ -0,0 +1,212 @@
+// Copyright 2025 The Matrix.org Foundation C.I.C.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Back-pagination of a live timeline into the predecessors of its room, i.e.
+//! the rooms it replaced after room upgrades.
+
+use std::{
+    collections::BTreeSet,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+};
+
+use eyeball_im::VectorDiff;
+use matrix_sdk::{
+    deserialized_responses::TimelineEvent,
+    event_cache::{self, EventCacheDropHandles, RoomEventCache},
+    Room,
+};
+use ruma::OwnedRoomId;
+use tokio::sync::Mutex;
+use tracing::{debug, instrument};
+
+use super::{controller::TimelineController, event_item::RemoteEventOrigin};
+
+/// The history of the predecessors of a room, prepended to the live timeline
+/// of this room.
+///
+/// The events of the predecessors are at the start of the timeline, so the
+/// positions in the updates of the room's own event cache must be shifted by
+/// the number of events of the predecessors. All the updates of the timeline's
+/// remote events must go through this type for this reason.
+#[derive(Debug)]
+pub(super) struct PredecessorHistory {
+    /// The room of the timeline.
+    room: Room,
+
+    state: Mutex<PredecessorHistoryState>,
+
+    /// Whether all the predecessors have been paginated, copied out of the
+    /// state so it can be read without waiting for a pagination.
+    hit_start: AtomicBool,
+}
+
+#[derive(Debug, Default)]
+struct PredecessorHistoryState {
+    /// The number of events of the predecessors at the start of the timeline.
+    num_events: usize,
+
+    /// The oldest room whose events are in the timeline, if it's a
+    /// predecessor, along with its event cache.
+    predecessor: Option<(Room, RoomEventCache, Arc<EventCacheDropHandles>)>,
+
+    /// Whether the event cache of the oldest predecessor has reached the start
+    /// of this room.
+    predecessor_reached_start: bool,
+
+    /// The predecessors whose events are in the timeline, to stop at cycles of
+    /// predecessors.
+    visited_rooms: BTreeSet<OwnedRoomId>,
+}
+
+impl PredecessorHistory {
+    pub(super) fn new(room: Room) -> Self {
+        Self { room, state: Default::default(), hit_start: AtomicBool::new(false) }
+    }
+
+    /// Whether the timeline contains events from a predecessor of the room,
+    /// and so back-paginations must go on in the predecessors.
+    pub(super) async fn is_started(&self) -> bool {
+        self.state.lock().await.predecessor.is_some()
+    }
+
+    /// Whether there might be more events to back-paginate in the
+    /// predecessors.
+    pub(super) fn may_have_more(&self) -> bool {
+        !self.hit_start.load(Ordering::SeqCst) && self.known_predecessor(&self.room).is_some()
+    }
+
+    /// Get the predecessor of a room, if the current user is a member of it.
+    fn known_predecessor(&self, room: &Room) -> Option<Room> {
+        let predecessor = room.predecessor()?;
+        self.room.client().get_room(&predecessor.room_id)
+    }
+
+    /// Handle the updates of the room's own event cache.
+    pub(super) async fn handle_remote_events_with_diffs(
+        &self,
+        controller: &TimelineController,
+        diffs: Vec<VectorDiff<TimelineEvent>>,
+        origin: RemoteEventOrigin,
+    ) {
+        let mut state = self.state.lock().await;
+
+        let mut shifted_diffs = Vec::with_capacity(diffs.len());
+
+        for diff in diffs {
+            let offset = state.num_events;
+
+            shifted_diffs.push(match diff {
+                VectorDiff::PushFront { value } => VectorDiff::Insert { index: offset, value },
+                VectorDiff::Insert { index, value } => {
+                    VectorDiff::Insert { index: index + offset, value }
+                }
+                VectorDiff::Set { index, value } => {
+                    VectorDiff::Set { index: index + offset, value }
+                }
+                VectorDiff::Remove { index } => VectorDiff::Remove { index: index + offset },
+                VectorDiff::Clear => {
+                    // The whole timeline is cleared, the predecessors must be paginated again.
+                    *state = Default::default();
+                    self.hit_start.store(false, Ordering::SeqCst);
+                    VectorDiff::Clear
+                }
+                diff => diff,
+            });
+        }
+
+        controller.handle_remote_events_with_diffs(shifted_diffs, origin).await;
+    }
+
+    /// Forget about the events of the predecessors, after the timeline has
+    /// been reset.
+    pub(super) async fn reset(&self) {
+        *self.state.lock().await = Default::default();
+        self.hit_start.store(false, Ordering::SeqCst);
+    }
+
+    /// Add events of the predecessors to the start of the timeline, once the
+    /// room itself has been fully back-paginated.
+    ///
+    /// Returns whether we hit the start of the oldest predecessor.
+    #[instrument(skip_all)]
+    pub(super) async fn paginate_backwards(
+        &self,
+        controller: &TimelineController,
+        batch_size: u16,
+    ) -> event_cache::Result<bool> {
+        let mut state = self.state.lock().await;
+
+        loop {
+            let paginated_event_cache = state
+                .predecessor
+                .as_ref()
+                .filter(|_| !state.predecessor_reached_start)
+                .map(|(_, room_event_cache, _)| room_event_cache.clone());
+
+            let events = if let Some(room_event_cache) = &paginated_event_cache {
+                let outcome = room_event_cache.pagination().run_backwards_once(batch_size).await?;
+                state.predecessor_reached_start = outcome.reached_start;
+                outcome.events
+            } else {
+                // Move on to the predecessor of the oldest room in the timeline.
+                let oldest_room = match &state.predecessor {
+                    Some((room, ..)) => room,
+                    None => &self.room,
+                };
+
+                let Some(predecessor) = self.known_predecessor(oldest_room).filter(|predecessor| {
+                    predecessor.room_id() != self.room.room_id()
+                        && !state.visited_rooms.contains(predecessor.room_id())
+                }) else {
+                    self.hit_start.store(true, Ordering::SeqCst);
+                    return Ok(true);
+                };
+
+                debug!(room_id = %predecessor.room_id(), "Paginating into a predecessor room");
+
+                let (room_event_cache, drop_handles) = predecessor.event_cache().await?;
+
+                // Start with the events the event cache already knows about, they are in
+                // topological order.
+                let (mut events, _) = room_event_cache.subscribe().await;
+                events.reverse();
+
+                state.visited_rooms.insert(predecessor.room_id().to_owned());
+                state.predecessor = Some((predecessor, room_event_cache, drop_handles));
+                state.predecessor_reached_start = false;
+                events
+            };
+
+            if events.is_empty() {
+                if state.predecessor_reached_start || paginated_event_cache.is_none() {
+                    // Move on to the next predecessor, or paginate the new one.
+                    continue;
+                }
+
+                // The server returned an empty chunk, let the caller try again.
+                return Ok(false);
+            }
+
+            // Only the events actually added to the timeline shift the positions of the
+            // room's own events.
+            state.num_events +=
+                controller.push_front_remote_events(events, RemoteEventOrigin::Pagination).await;
+
+            return Ok(false);
+        }
+    }
+}
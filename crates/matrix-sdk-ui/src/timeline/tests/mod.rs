@@ -30,7 +30,7 @@ use matrix_sdk::{
     config::RequestConfig,
     deserialized_responses::TimelineEvent,
     event_cache::paginator::{PaginableRoom, PaginatorError},
-    room::{EventWithContextResponse, Messages, MessagesOptions},
+    room::{EventWithContextResponse, Messages, MessagesOptions, Relations},
     send_queue::RoomSendQueueUpdate,
     BoxFuture,
 };
@@ -62,7 +62,10 @@ use super::{
     TimelineItem,
 };
 use crate::{
-    timeline::pinned_events_loader::PinnedEventsRoom, unable_to_decrypt_hook::UtdHookManager,
+    timeline::{
+        pinned_events_loader::PinnedEventsRoom, threaded_events_loader::ThreadedEventsRoom,
+    },
+    unable_to_decrypt_hook::UtdHookManager,
 };
 
 mod basic;
@@ -314,6 +317,24 @@ impl PinnedEventsRoom for TestRoomDataProvider {
     }
 }
 
+impl ThreadedEventsRoom for TestRoomDataProvider {
+    fn load_thread_replies<'a>(
+        &'a self,
+        _root_event_id: &'a EventId,
+        _from: Option<String>,
+        _num_events: UInt,
+    ) -> BoxFuture<'a, Result<Relations, PaginatorError>> {
+        unimplemented!();
+    }
+
+    fn load_thread_root<'a>(
+        &'a self,
+        _root_event_id: &'a EventId,
+    ) -> BoxFuture<'a, Result<TimelineEvent, PaginatorError>> {
+        unimplemented!();
+    }
+}
+
 impl RoomDataProvider for TestRoomDataProvider {
     fn own_user_id(&self) -> &UserId {
         &ALICE
@@ -0,0 +1,277 @@
+// Copyright 2025 The Matrix.org Foundation C.I.C.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::{fmt::Formatter, sync::Mutex};
+
+use matrix_sdk::{
+    event_cache::{
+        paginator::{PaginationResult, PaginatorError},
+        PaginationToken,
+    },
+    room::{IncludeRelations, Relations, RelationsOptions},
+    BoxFuture, Room, SendOutsideWasm, SyncOutsideWasm,
+};
+use matrix_sdk_base::deserialized_responses::TimelineEvent;
+use ruma::{api::Direction, events::relation::RelationType, EventId, OwnedEventId, UInt};
+use serde::Deserialize;
+use tracing::debug;
+
+/// Utility to load the events of a thread, i.e. the thread root and all the
+/// events that have a `m.thread` relation to it.
+pub struct ThreadedEventsLoader {
+    /// Backend to load the thread's events.
+    room: Box<dyn ThreadedEventsRoom>,
+
+    /// The event ID of the thread root.
+    root_event_id: OwnedEventId,
+
+    /// The token to use for the next backward pagination in the thread.
+    ///
+    /// This mutex is always short-lived, so it's sync.
+    token: Mutex<PaginationToken>,
+}
+
+impl ThreadedEventsLoader {
+    /// Creates a new `ThreadedEventsLoader` for the thread starting at the
+    /// given root event.
+    pub fn new(room: Box<dyn ThreadedEventsRoom>, root_event_id: OwnedEventId) -> Self {
+        Self { room, root_event_id, token: Mutex::new(PaginationToken::None) }
+    }
+
+    /// The event ID of the thread root.
+    pub fn root_event_id(&self) -> &EventId {
+        &self.root_event_id
+    }
+
+    /// Runs a backward pagination in the thread, requesting `num_events`
+    /// thread replies to the server.
+    ///
+    /// The returned events are in reverse topological order. Once the start of
+    /// the thread has been reached, the thread root is included as the last
+    /// event of the result.
+    pub async fn paginate_backwards(
+        &self,
+        num_events: UInt,
+    ) -> Result<PaginationResult, PaginatorError> {
+        let from = match &*self.token.lock().unwrap() {
+            PaginationToken::None => None,
+            PaginationToken::HasMore(token) => Some(token.clone()),
+            PaginationToken::HitEnd => {
+                return Ok(PaginationResult { events: Vec::new(), hit_end_of_timeline: true });
+            }
+        };
+
+        let Relations { chunk: mut events, next_batch_token, .. } =
+            self.room.load_thread_replies(&self.root_event_id, from, num_events).await?;
+
+        let hit_end_of_timeline = next_batch_token.is_none();
+
+        if hit_end_of_timeline {
+            // We've reached the start of the thread, so include the root event, which
+            // isn't part of the relations returned by the server.
+            debug!("reached the start of the thread, loading the thread root");
+            let root = self.room.load_thread_root(&self.root_event_id).await?;
+            events.push(root);
+        }
+
+        *self.token.lock().unwrap() = next_batch_token.into();
+
+        Ok(PaginationResult { events, hit_end_of_timeline })
+    }
+}
+
+#[cfg(not(tarpaulin_include))]
+impl std::fmt::Debug for ThreadedEventsLoader {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ThreadedEventsLoader")
+            .field("root_event_id", &self.root_event_id)
+            .field("token", &self.token)
+            .finish()
+    }
+}
+
+pub trait ThreadedEventsRoom: SendOutsideWasm + SyncOutsideWasm {
+    /// Load a batch of events having a `m.thread` relation to the given thread
+    /// root, in reverse topological order, starting from the `from` token if
+    /// set, or from the latest thread reply otherwise.
+    fn load_thread_replies<'a>(
+        &'a self,
+        root_event_id: &'a EventId,
+        from: Option<String>,
+        num_events: UInt,
+    ) -> BoxFuture<'a, Result<Relations, PaginatorError>>;
+
+    /// Load the thread root event, using the cache or network.
+    fn load_thread_root<'a>(
+        &'a self,
+        root_event_id: &'a EventId,
+    ) -> BoxFuture<'a, Result<TimelineEvent, PaginatorError>>;
+}
+
+impl ThreadedEventsRoom for Room {
+    fn load_thread_replies<'a>(
+        &'a self,
+        root_event_id: &'a EventId,
+        from: Option<String>,
+        num_events: UInt,
+    ) -> BoxFuture<'a, Result<Relations, PaginatorError>> {
+        Box::pin(async move {
+            let options = RelationsOptions {
+                from,
+                dir: Direction::Backward,
+                limit: Some(num_events),
+                include_relations: IncludeRelations::RelationsOfType(RelationType::Thread),
+                recurse: false,
+            };
+
+            self.relations(root_event_id.to_owned(), options)
+                .await
+                .map_err(|err| PaginatorError::SdkError(Box::new(err)))
+        })
+    }
+
+    fn load_thread_root<'a>(
+        &'a self,
+        root_event_id: &'a EventId,
+    ) -> BoxFuture<'a, Result<TimelineEvent, PaginatorError>> {
+        Box::pin(async move {
+            if let Ok((cache, _handles)) = self.event_cache().await {
+                if let Some(event) = cache.event(root_event_id).await {
+                    debug!("Loaded thread root {root_event_id} from cache");
+                    return Ok(event);
+                }
+            }
+
+            debug!("Loading thread root {root_event_id} from HS");
+            self.event(root_event_id, None)
+                .await
+                .map_err(|err| PaginatorError::SdkError(Box::new(err)))
+        })
+    }
+}
+
+/// Minimal representation of an event, to find out which event it relates to.
+#[derive(Deserialize)]
+struct RelatedEventProbe {
+    event_id: Option<OwnedEventId>,
+    #[serde(default)]
+    content: RelatedEventProbeContent,
+    /// The redacted event, for redactions in room versions before v11.
+    redacts: Option<OwnedEventId>,
+}
+
+#[derive(Default, Deserialize)]
+struct RelatedEventProbeContent {
+    #[serde(rename = "m.relates_to")]
+    relates_to: Option<RelatesToProbe>,
+    /// The redacted event, for redactions in room versions v11 and later.
+    redacts: Option<OwnedEventId>,
+}
+
+#[derive(Deserialize)]
+struct RelatesToProbe {
+    rel_type: Option<RelationType>,
+    event_id: Option<OwnedEventId>,
+}
+
+/// How a given event is related to a thread.
+#[derive(Debug, PartialEq)]
+pub(super) enum ThreadMembership {
+    /// The event is the thread root, or a reply in the thread.
+    InThread,
+
+    /// The event relates to another event (e.g. it's a reaction, an edit or a
+    /// redaction), whose ID is given.
+    RelatesTo(OwnedEventId),
+
+    /// The event is unrelated to the thread.
+    Unrelated,
+}
+
+/// Find out how the given event is related to the thread starting at
+/// `root_event_id`.
+pub(super) fn thread_membership(
+    event: &TimelineEvent,
+    root_event_id: &EventId,
+) -> ThreadMembership {
+    let Ok(probe) = event.raw().deserialize_as::<RelatedEventProbe>() else {
+        return ThreadMembership::Unrelated;
+    };
+
+    if probe.event_id.as_deref() == Some(root_event_id) {
+        return ThreadMembership::InThread;
+    }
+
+    if let Some(relates_to) = probe.content.relates_to {
+        if relates_to.rel_type == Some(RelationType::Thread) {
+            return if relates_to.event_id.as_deref() == Some(root_event_id) {
+                ThreadMembership::InThread
+            } else {
+                ThreadMembership::Unrelated
+            };
+        }
+
+        if let Some(target) = relates_to.event_id {
+            return ThreadMembership::RelatesTo(target);
+        }
+    }
+
+    match probe.content.redacts.or(probe.redacts) {
+        Some(redacted) => ThreadMembership::RelatesTo(redacted),
+        None => ThreadMembership::Unrelated,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use matrix_sdk_test::event_factory::EventFactory;
+    use ruma::{event_id, owned_event_id, room_id, user_id};
+
+    use super::{thread_membership, ThreadMembership};
+
+    #[test]
+    fn test_thread_membership() {
+        let f = EventFactory::new().room(room_id!("!r:b.c")).sender(user_id!("@a:b.c"));
+        let root = event_id!("$root");
+
+        let root_event = f.text_msg("root").event_id(root).into_event();
+        assert_eq!(thread_membership(&root_event, root), ThreadMembership::InThread);
+
+        let reply =
+            f.text_msg("reply").in_thread(root, root).event_id(event_id!("$1")).into_event();
+        assert_eq!(thread_membership(&reply, root), ThreadMembership::InThread);
+
+        let other_thread = f
+            .text_msg("another thread")
+            .in_thread(event_id!("$other"), event_id!("$other"))
+            .event_id(event_id!("$2"))
+            .into_event();
+        assert_eq!(thread_membership(&other_thread, root), ThreadMembership::Unrelated);
+
+        let reaction = f.reaction(event_id!("$1"), "👍").event_id(event_id!("$3")).into_event();
+        assert_eq!(
+            thread_membership(&reaction, root),
+            ThreadMembership::RelatesTo(owned_event_id!("$1"))
+        );
+
+        let redaction = f.redaction(event_id!("$1")).event_id(event_id!("$4")).into_event();
+        assert_eq!(
+            thread_membership(&redaction, root),
+            ThreadMembership::RelatesTo(owned_event_id!("$1"))
+        );
+
+        let unrelated = f.text_msg("hello").event_id(event_id!("$5")).into_event();
+        assert_eq!(thread_membership(&unrelated, root), ThreadMembership::Unrelated);
+    }
+}
@@ -36,7 +36,10 @@ use ruma::{
 use tracing::{debug, error};
 
 use super::{Profile, RedactError, TimelineBuilder};
-use crate::timeline::{self, pinned_events_loader::PinnedEventsRoom, Timeline};
+use crate::timeline::{
+    self, pinned_events_loader::PinnedEventsRoom, threaded_events_loader::ThreadedEventsRoom,
+    Timeline,
+};
 
 pub trait RoomExt {
     /// Get a [`Timeline`] for this room.
@@ -71,7 +74,7 @@ impl RoomExt for Room {
 }
 
 pub(super) trait RoomDataProvider:
-    Clone + PaginableRoom + PinnedEventsRoom + 'static
+    Clone + PaginableRoom + PinnedEventsRoom + ThreadedEventsRoom + 'static
 {
     fn own_user_id(&self) -> &UserId;
     fn room_version(&self) -> RoomVersionId;
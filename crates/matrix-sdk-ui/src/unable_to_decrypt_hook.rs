@@ -81,6 +81,17 @@ pub struct UnableToDecryptInfo {
     /// Our local user's own homeserver, or `None` if the client is not logged
     /// in.
     pub own_homeserver: Option<OwnedServerName>,
+
+    /// Whether the event could be decrypted late thanks to a room key that was
+    /// downloaded from the key backup, because the event couldn't be
+    /// decrypted.
+    ///
+    /// This is always `false` if [`UnableToDecryptInfo::time_to_decrypt`] is
+    /// not set. See [`Backups::was_room_key_downloaded_after_utd()`] for more
+    /// details.
+    ///
+    /// [`Backups::was_room_key_downloaded_after_utd()`]: matrix_sdk::encryption::backups::Backups::was_room_key_downloaded_after_utd
+    pub key_downloaded_from_backup: bool,
 }
 
 /// Data about a UTD event which we are waiting to report to the parent hook.
@@ -276,6 +287,7 @@ impl UtdHookManager {
             user_trusts_own_identity,
             own_homeserver,
             sender_homeserver,
+            key_downloaded_from_backup: false,
         };
 
         let Some(max_delay) = self.max_delay else {
@@ -330,7 +342,12 @@ impl UtdHookManager {
     ///
     /// Note: if this is called for an event that was never marked as a UTD
     /// before, it has no effect.
-    pub(crate) async fn on_late_decrypt(&self, event_id: &EventId) {
+    ///
+    /// # Arguments
+    ///  * `event_id` - The ID of the event that was decrypted.
+    ///  * `session_id` - The ID of the room key that was used to decrypt the
+    ///    event.
+    pub(crate) async fn on_late_decrypt(&self, event_id: &EventId, session_id: &str) {
         // Hold the lock on `reported_utds` throughout, to avoid races with other
         // threads.
         let mut reported_utds_lock = self.reported_utds.lock().await;
@@ -348,6 +365,8 @@ impl UtdHookManager {
         // Update the UTD Info struct with new data, then report it
         let mut info = pending_utd_report.utd_info;
         info.time_to_decrypt = Some(pending_utd_report.marked_utd_at.elapsed());
+        info.key_downloaded_from_backup =
+            self.client.encryption().backups().was_room_key_downloaded_after_utd(session_id);
         Self::report_utd(info, &self.parent, &self.client, &mut reported_utds_lock).await;
     }
 
@@ -600,7 +619,7 @@ mod tests {
 
         // And I call the `on_late_decrypt` method before the event had been marked as
         // utd,
-        wrapper.on_late_decrypt(event_id!("$1")).await;
+        wrapper.on_late_decrypt(event_id!("$1"), "session_id").await;
 
         // Then nothing is registered in the parent hook.
         assert!(hook.utds.lock().unwrap().is_empty());
@@ -633,7 +652,7 @@ mod tests {
         }
 
         // And when I call the `on_late_decrypt` method,
-        wrapper.on_late_decrypt(event_id!("$1")).await;
+        wrapper.on_late_decrypt(event_id!("$1"), "session_id").await;
 
         // Then the event is not reported again as a late-decryption.
         {
@@ -718,7 +737,7 @@ mod tests {
         // If I wait for 1 second, and mark the event as late-decrypted,
         sleep(Duration::from_secs(1)).await;
 
-        wrapper.on_late_decrypt(event_id!("$1")).await;
+        wrapper.on_late_decrypt(event_id!("$1"), "session_id").await;
 
         // Then it's being immediately reported as a late-decryption UTD.
         {
@@ -726,6 +745,9 @@ mod tests {
             assert_eq!(utds.len(), 1);
             assert_eq!(utds[0].event_id, event_id!("$1"));
             assert!(utds[0].time_to_decrypt.is_some());
+
+            // The room key wasn't downloaded from the backup.
+            assert!(!utds[0].key_downloaded_from_backup);
         }
 
         // And there aren't any pending delayed reports anymore.
@@ -68,7 +68,7 @@ async fn test_echo() {
 
     assert_let!(VectorDiff::PushBack { value: local_echo } = &timeline_updates[0]);
     let item = local_echo.as_event().unwrap();
-    assert_matches!(item.send_state(), Some(EventSendState::NotSentYet));
+    assert_matches!(item.send_state(), Some(EventSendState::NotSentYet { .. }));
     assert_let!(TimelineItemContent::Message(msg) = item.content());
     assert_let!(MessageType::Text(text) = msg.msgtype());
     assert_eq!(text.body, "Hello, World!");
@@ -150,7 +150,7 @@ async fn test_retry_failed() {
 
     // First, local echo is added.
     assert_next_matches!(timeline_stream, VectorDiff::PushBack { value } => {
-        assert_matches!(value.send_state(), Some(EventSendState::NotSentYet));
+        assert_matches!(value.send_state(), Some(EventSendState::NotSentYet { .. }));
     });
 
     // Sending fails, because the error is a transient one that's recoverable,
@@ -216,7 +216,7 @@ async fn test_dedup_by_event_id_late() {
     // Timeline: [local echo]
     assert_let!(VectorDiff::PushBack { value: local_echo } = &timeline_updates[0]);
     let item = local_echo.as_event().unwrap();
-    assert_matches!(item.send_state(), Some(EventSendState::NotSentYet));
+    assert_matches!(item.send_state(), Some(EventSendState::NotSentYet { .. }));
 
     // Timeline: [date-divider, local echo]
     assert_let!(VectorDiff::PushFront { value: date_divider } = &timeline_updates[1]);
@@ -283,7 +283,7 @@ async fn test_cancel_failed() {
 
     // Local echo is added (immediately)
     assert_next_matches!(timeline_stream, VectorDiff::PushBack { value } => {
-        assert_matches!(value.send_state(), Some(EventSendState::NotSentYet));
+        assert_matches!(value.send_state(), Some(EventSendState::NotSentYet { .. }));
     });
 
     // Sending fails, the mock server has no matching route
@@ -19,13 +19,19 @@ use std::time::Duration;
 use assert_matches2::assert_let;
 use eyeball_im::VectorDiff;
 use futures_util::StreamExt;
-use matrix_sdk::{config::SyncSettings, test_utils::logged_in_client_with_server};
+use matrix_sdk::{
+    config::SyncSettings,
+    test_utils::{logged_in_client_with_server, mocks::MatrixMockServer},
+};
 use matrix_sdk_test::{
     async_test, event_factory::EventFactory, mocks::mock_encryption_state, JoinedRoomBuilder,
     SyncResponseBuilder, ALICE, BOB,
 };
 use matrix_sdk_ui::{timeline::TimelineFocus, Timeline};
-use ruma::{event_id, events::room::message::RoomMessageEventContent, room_id};
+use ruma::{
+    api::Direction, event_id, events::room::message::RoomMessageEventContent, room_id, uint,
+    MilliSecondsSinceUnixEpoch,
+};
 use stream_assert::assert_pending;
 use tokio::time::sleep;
 
@@ -402,3 +408,29 @@ async fn test_focused_timeline_doesnt_show_local_echoes() {
     // And nothing more.
     assert_pending!(timeline_stream);
 }
+
+#[async_test]
+async fn test_focus_for_date() {
+    let room_id = room_id!("!a98sd12bjh:example.org");
+
+    let server = MatrixMockServer::new().await;
+    let client = server.client_builder().build().await;
+    let room = server.sync_joined_room(&client, room_id).await;
+
+    let date = MilliSecondsSinceUnixEpoch(uint!(1_700_000_000_000));
+
+    server
+        .mock_timestamp_to_event()
+        .match_timestamp(date, Direction::Forward)
+        .ok(event_id!("$1"), MilliSecondsSinceUnixEpoch(uint!(1_700_000_001_000)))
+        .mock_once()
+        .mount()
+        .await;
+
+    let focus = TimelineFocus::for_date(&room, date, 20).await.unwrap();
+
+    assert_eq!(
+        focus,
+        TimelineFocus::Event { target: event_id!("$1").to_owned(), num_context_events: 20 }
+    );
+}
@@ -19,7 +19,9 @@ use assert_matches2::assert_let;
 use eyeball_im::VectorDiff;
 use futures_util::{FutureExt, StreamExt};
 use matrix_sdk::{
-    assert_let_timeout, attachment::AttachmentConfig, test_utils::mocks::MatrixMockServer,
+    assert_let_timeout,
+    attachment::{AttachmentConfig, GalleryItem},
+    test_utils::mocks::MatrixMockServer,
 };
 use matrix_sdk_test::{async_test, event_factory::EventFactory, JoinedRoomBuilder, ALICE};
 use matrix_sdk_ui::timeline::{AttachmentSource, EventSendState, RoomExt, TimelineItemContent};
@@ -104,7 +106,7 @@ async fn test_send_attachment_from_file() {
 
     {
         assert_let_timeout!(Some(VectorDiff::PushBack { value: item }) = timeline_stream.next());
-        assert_matches!(item.send_state(), Some(EventSendState::NotSentYet));
+        assert_matches!(item.send_state(), Some(EventSendState::NotSentYet { .. }));
         assert_let!(TimelineItemContent::Message(msg) = item.content());
 
         // Body is the caption, because there's both a caption and filename.
@@ -125,7 +127,7 @@ async fn test_send_attachment_from_file() {
             Some(VectorDiff::Set { index: 1, value: item }) = timeline_stream.next()
         );
         assert_let!(TimelineItemContent::Message(msg) = item.content());
-        assert_matches!(item.send_state(), Some(EventSendState::NotSentYet));
+        assert_matches!(item.send_state(), Some(EventSendState::NotSentYet { .. }));
         assert_eq!(get_filename_and_caption(msg.msgtype()), ("test.bin", Some("caption")));
 
         // The URI now refers to the final MXC URI.
@@ -203,7 +205,7 @@ async fn test_send_attachment_from_bytes() {
 
     {
         assert_let_timeout!(Some(VectorDiff::PushBack { value: item }) = timeline_stream.next());
-        assert_matches!(item.send_state(), Some(EventSendState::NotSentYet));
+        assert_matches!(item.send_state(), Some(EventSendState::NotSentYet { .. }));
         assert_let!(TimelineItemContent::Message(msg) = item.content());
 
         // Body is the caption, because there's both a caption and filename.
@@ -224,7 +226,7 @@ async fn test_send_attachment_from_bytes() {
             Some(VectorDiff::Set { index: 1, value: item }) = timeline_stream.next()
         );
         assert_let!(TimelineItemContent::Message(msg) = item.content());
-        assert_matches!(item.send_state(), Some(EventSendState::NotSentYet));
+        assert_matches!(item.send_state(), Some(EventSendState::NotSentYet { .. }));
         assert_eq!(get_filename_and_caption(msg.msgtype()), (filename, Some("caption")));
 
         // The URI now refers to the final MXC URI.
@@ -247,6 +249,57 @@ async fn test_send_attachment_from_bytes() {
     assert!(timeline_stream.next().now_or_never().is_none());
 }
 
+#[async_test]
+async fn test_send_gallery_local_echo() {
+    let mock = MatrixMockServer::new().await;
+    let client = mock.client_builder().build().await;
+
+    mock.mock_room_state_encryption().plain().mount().await;
+
+    let room_id = room_id!("!a98sd12bjh:example.org");
+    let room = mock.sync_joined_room(&client, room_id).await;
+    let timeline = room.timeline().await.unwrap();
+
+    let (items, mut timeline_stream) =
+        timeline.subscribe_filter_map(|item| item.as_event().cloned()).await;
+    assert!(items.is_empty());
+
+    // Set up mocks for the file uploads, which take a while so the local echo can
+    // be observed before they're done.
+    mock.mock_upload()
+        .respond_with(ResponseTemplate::new(200).set_delay(Duration::from_secs(2)).set_body_json(
+            json!({
+              "content_uri": "mxc://sdk.rs/media"
+            }),
+        ))
+        .mount()
+        .await;
+
+    // Queue sending of a gallery.
+    let gallery = vec![
+        GalleryItem::new("first.jpg", mime::IMAGE_JPEG, b"first image".to_vec()),
+        GalleryItem::new("second.bin", mime::TEXT_PLAIN, b"hello world".to_vec())
+            .caption(Some("second caption".to_owned())),
+    ];
+    room.send_queue().send_gallery(gallery, Some("my gallery".to_owned())).await.unwrap();
+
+    // The local echo of the gallery contains all its items.
+    assert_let_timeout!(Some(VectorDiff::PushBack { value: item }) = timeline_stream.next());
+    assert_matches!(item.send_state(), Some(EventSendState::NotSentYet { .. }));
+    assert_let!(TimelineItemContent::Message(msg) = item.content());
+    assert_eq!(msg.body(), "my gallery");
+
+    assert_let!(Some(gallery_items) = msg.gallery_items());
+    assert_eq!(gallery_items.len(), 2);
+    assert_eq!(get_filename_and_caption(&gallery_items[0]), ("first.jpg", None));
+    assert_eq!(get_filename_and_caption(&gallery_items[1]), ("second.bin", Some("second caption")));
+
+    // The URIs refer to the local cache.
+    assert_let!(MessageType::Image(image) = &gallery_items[0]);
+    assert_let!(MediaSource::Plain(uri) = &image.source);
+    assert!(uri.to_string().contains("localhost"));
+}
+
 #[async_test]
 async fn test_react_to_local_media() {
     let mock = MatrixMockServer::new().await;
@@ -37,8 +37,9 @@ use matrix_sdk_ui::timeline::{
 };
 use once_cell::sync::Lazy;
 use ruma::{
+    event_id,
     events::{room::message::MessageType, FullStateEventContent},
-    room_id, EventId,
+    room_id, EventId, RoomId,
 };
 use serde_json::{json, Value as JsonValue};
 use stream_assert::{assert_next_eq, assert_pending};
@@ -1084,3 +1085,148 @@ async fn test_lazy_back_pagination() {
         drop(network_pagination);
     }
 }
+
+#[async_test]
+async fn test_back_pagination_into_predecessor() {
+    let old_room_id = room_id!("!old:bar.baz");
+    let room_id = room_id!("!new:bar.baz");
+
+    let mock_server = MatrixMockServer::new().await;
+    let client = mock_server.client_builder().build().await;
+    client.event_cache().subscribe().unwrap();
+
+    // The old room has been fully synced, and tombstoned.
+    let old_event_factory = EventFactory::new().room(old_room_id).sender(&ALICE);
+    mock_server
+        .sync_room(
+            &client,
+            JoinedRoomBuilder::new(old_room_id)
+                .add_timeline_event(
+                    old_event_factory.text_msg("hello").event_id(event_id!("$old0")),
+                )
+                .add_timeline_event(old_event_factory.text_msg("bye").event_id(event_id!("$old1")))
+                .add_timeline_event(
+                    old_event_factory
+                        .room_tombstone("This room has been replaced", room_id)
+                        .event_id(event_id!("$tombstone")),
+                ),
+        )
+        .await;
+
+    // The new room declares the old room as its predecessor.
+    let event_factory = EventFactory::new().room(room_id).sender(&ALICE);
+    let room = mock_server
+        .sync_room(
+            &client,
+            JoinedRoomBuilder::new(room_id)
+                .add_state_event(StateTestEvent::Custom(json!({
+                    "type": "m.room.create",
+                    "content": {
+                        "room_version": "11",
+                        "predecessor": { "room_id": old_room_id, "event_id": "$tombstone" },
+                    },
+                    "event_id": "$create",
+                    "origin_server_ts": 1,
+                    "sender": *ALICE,
+                    "state_key": "",
+                })))
+                .add_timeline_event(event_factory.text_msg("welcome").event_id(event_id!("$new0"))),
+        )
+        .await;
+
+    let timeline = room.timeline_builder().paginate_into_predecessors().build().await.unwrap();
+
+    // Paginate until the start of the predecessor is reached.
+    while !timeline.paginate_backwards(10).await.unwrap() {}
+
+    let event_ids = timeline
+        .items()
+        .await
+        .iter()
+        .filter_map(|item| item.as_event()?.event_id().map(ToOwned::to_owned))
+        .collect::<Vec<_>>();
+
+    assert_eq!(event_ids, ["$old0", "$old1", "$tombstone", "$new0"]);
+
+    // Events from the room are still added after the events of the predecessor.
+    mock_server
+        .sync_room(
+            &client,
+            JoinedRoomBuilder::new(room_id).add_timeline_event(
+                event_factory.text_msg("hi there").event_id(event_id!("$new1")),
+            ),
+        )
+        .await;
+
+    sleep(Duration::from_millis(100)).await;
+
+    let event_ids = timeline
+        .items()
+        .await
+        .iter()
+        .filter_map(|item| item.as_event()?.event_id().map(ToOwned::to_owned))
+        .collect::<Vec<_>>();
+
+    assert_eq!(event_ids, ["$old0", "$old1", "$tombstone", "$new0", "$new1"]);
+}
+
+#[async_test]
+async fn test_back_pagination_into_predecessor_cycle() {
+    let old_room_id = room_id!("!old:bar.baz");
+    let room_id = room_id!("!new:bar.baz");
+
+    let mock_server = MatrixMockServer::new().await;
+    let client = mock_server.client_builder().build().await;
+    client.event_cache().subscribe().unwrap();
+
+    let create_event = |predecessor: &RoomId, event_id: &str| {
+        StateTestEvent::Custom(json!({
+            "type": "m.room.create",
+            "content": {
+                "room_version": "11",
+                "predecessor": { "room_id": predecessor, "event_id": "$tombstone" },
+            },
+            "event_id": event_id,
+            "origin_server_ts": 1,
+            "sender": *ALICE,
+            "state_key": "",
+        }))
+    };
+
+    // Both rooms declare each other as their predecessor.
+    let old_event_factory = EventFactory::new().room(old_room_id).sender(&ALICE);
+    mock_server
+        .sync_room(
+            &client,
+            JoinedRoomBuilder::new(old_room_id)
+                .add_state_event(create_event(room_id, "$old_create"))
+                .add_timeline_event(
+                    old_event_factory.text_msg("hello").event_id(event_id!("$old0")),
+                ),
+        )
+        .await;
+
+    let event_factory = EventFactory::new().room(room_id).sender(&ALICE);
+    let room = mock_server
+        .sync_room(
+            &client,
+            JoinedRoomBuilder::new(room_id)
+                .add_state_event(create_event(old_room_id, "$create"))
+                .add_timeline_event(event_factory.text_msg("welcome").event_id(event_id!("$new0"))),
+        )
+        .await;
+
+    let timeline = room.timeline_builder().paginate_into_predecessors().build().await.unwrap();
+
+    // The pagination stops once it loops back to the room itself.
+    while !timeline.paginate_backwards(10).await.unwrap() {}
+
+    let event_ids = timeline
+        .items()
+        .await
+        .iter()
+        .filter_map(|item| item.as_event()?.event_id().map(ToOwned::to_owned))
+        .collect::<Vec<_>>();
+
+    assert_eq!(event_ids, ["$old0", "$new0"]);
+}
@@ -18,15 +18,27 @@ use assert_matches::assert_matches;
 use assert_matches2::assert_let;
 use eyeball_im::VectorDiff;
 use futures_util::StreamExt;
-use matrix_sdk::{config::SyncSettings, test_utils::logged_in_client_with_server, Error};
+use matrix_sdk::{
+    assert_next_matches_with_timeout,
+    config::SyncSettings,
+    test_utils::{logged_in_client_with_server, mocks::MatrixMockServer},
+    Error,
+};
 use matrix_sdk_base::store::QueueWedgeError;
 use matrix_sdk_test::{
     async_test, event_factory::EventFactory, mocks::mock_encryption_state, JoinedRoomBuilder,
     SyncResponseBuilder, ALICE,
 };
-use matrix_sdk_ui::timeline::{EventItemOrigin, EventSendState, RoomExt};
+use matrix_sdk_ui::timeline::{
+    AnyOtherFullStateEventContent, EventItemOrigin, EventSendState, RoomExt, TimelineItemContent,
+};
 use ruma::{
-    event_id, events::room::message::RoomMessageEventContent, room_id, MilliSecondsSinceUnixEpoch,
+    event_id,
+    events::{
+        room::{message::RoomMessageEventContent, topic::RoomTopicEventContent},
+        FullStateEventContent,
+    },
+    owned_event_id, room_id, MilliSecondsSinceUnixEpoch,
 };
 use serde_json::json;
 use stream_assert::{assert_next_matches, assert_pending};
@@ -377,7 +389,7 @@ async fn test_clear_with_echoes() {
     // The message that failed to send.
     assert_matches!(event_items[1].send_state(), Some(EventSendState::SendingFailed { .. }));
     // The message that is still pending.
-    assert_matches!(event_items[2].send_state(), Some(EventSendState::NotSentYet));
+    assert_matches!(event_items[2].send_state(), Some(EventSendState::NotSentYet { .. }));
 
     // When we clear the timeline now,
     timeline.clear().await;
@@ -388,7 +400,7 @@ async fn test_clear_with_echoes() {
 
     assert_eq!(event_items.len(), 2);
     assert_matches!(event_items[0].send_state(), Some(EventSendState::SendingFailed { .. }));
-    assert_matches!(event_items[1].send_state(), Some(EventSendState::NotSentYet));
+    assert_matches!(event_items[1].send_state(), Some(EventSendState::NotSentYet { .. }));
 }
 
 #[async_test]
@@ -531,3 +543,88 @@ async fn test_no_duplicate_date_divider() {
 
     assert_pending!(timeline_stream);
 }
+
+#[async_test]
+async fn test_local_echo_of_state_event() {
+    let server = MatrixMockServer::new().await;
+    let client = server.client_builder().build().await;
+
+    let room_id = room_id!("!a98sd12bjh:example.org");
+    let room = server.sync_joined_room(&client, room_id).await;
+
+    server.mock_room_state_encryption().plain().mount().await;
+    server.mock_room_send_state().ok(event_id!("$topic")).mock_once().mount().await;
+
+    let timeline = room.timeline().await.unwrap();
+    let (_, mut timeline_stream) =
+        timeline.subscribe_filter_map(|item| item.as_event().cloned()).await;
+
+    room.send_queue().send_state(RoomTopicEventContent::new("new topic".to_owned())).await.unwrap();
+
+    // The state event is shown as a local echo…
+    assert_next_matches_with_timeout!(timeline_stream, VectorDiff::PushBack { value } => {
+        assert_matches!(value.send_state(), Some(EventSendState::NotSentYet { .. }));
+        assert_let!(TimelineItemContent::OtherState(state) = value.content());
+        assert_eq!(state.state_key(), "");
+        assert_let!(
+            AnyOtherFullStateEventContent::RoomTopic(FullStateEventContent::Original {
+                content,
+                ..
+            }) = state.content()
+        );
+        assert_eq!(content.topic, "new topic");
+    });
+
+    // … until it's sent.
+    assert_next_matches_with_timeout!(timeline_stream, VectorDiff::Set { index: 0, value } => {
+        assert_matches!(value.send_state(), Some(EventSendState::Sent { .. }));
+    });
+
+    assert_pending!(timeline_stream);
+}
+
+#[async_test]
+async fn test_local_echo_of_redaction() {
+    let server = MatrixMockServer::new().await;
+    let client = server.client_builder().build().await;
+    client.event_cache().subscribe().unwrap();
+
+    let room_id = room_id!("!a98sd12bjh:example.org");
+    let f = EventFactory::new().room(room_id).sender(&ALICE);
+    let room = server
+        .sync_room(
+            &client,
+            JoinedRoomBuilder::new(room_id)
+                .add_timeline_event(f.text_msg("spam").event_id(event_id!("$spam"))),
+        )
+        .await;
+
+    server.mock_room_state_encryption().plain().mount().await;
+
+    let timeline = room.timeline().await.unwrap();
+    let (items, mut timeline_stream) =
+        timeline.subscribe_filter_map(|item| item.as_event().cloned()).await;
+    assert_eq!(items.len(), 1);
+    assert!(!items[0].is_pending_redaction());
+
+    // Keep the redaction in the queue.
+    let send_queue = room.send_queue();
+    send_queue.set_enabled(false);
+
+    let handle = send_queue.redact(owned_event_id!("$spam"), None).await.unwrap();
+
+    // The redacted event is marked as pending redaction…
+    assert_next_matches_with_timeout!(timeline_stream, VectorDiff::Set { index: 0, value } => {
+        assert!(value.is_pending_redaction());
+        assert_eq!(value.content().as_message().unwrap().body(), "spam");
+    });
+
+    // … until the redaction is aborted.
+    assert!(handle.abort().await.unwrap());
+
+    assert_next_matches_with_timeout!(timeline_stream, VectorDiff::Set { index: 0, value } => {
+        assert!(!value.is_pending_redaction());
+    });
+
+    assert_pending!(timeline_stream);
+}
@@ -0,0 +1,223 @@
+// Copyright 2025 The Matrix.org Foundation C.I.C.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Tests specific to a timeline focused on a thread.
+
+use assert_matches2::assert_let;
+use eyeball_im::VectorDiff;
+use futures_util::StreamExt;
+use matrix_sdk::{
+    assert_let_timeout,
+    test_utils::mocks::{MatrixMockServer, RoomRelationsResponseTemplate},
+};
+use matrix_sdk_test::{async_test, event_factory::EventFactory, JoinedRoomBuilder, ALICE, BOB};
+use matrix_sdk_ui::{timeline::TimelineFocus, Timeline};
+use ruma::{
+    api::client::receipt::create_receipt::v3::ReceiptType, event_id,
+    events::relation::RelationType, room_id,
+};
+use serde_json::json;
+use stream_assert::assert_pending;
+
+#[async_test]
+async fn test_thread_focus() {
+    let server = MatrixMockServer::new().await;
+    let client = server.client_builder().build().await;
+
+    let room_id = room_id!("!a:b.c");
+    let room = server.sync_joined_room(&client, room_id).await;
+
+    server.mock_room_state_encryption().plain().mount().await;
+
+    let f = EventFactory::new().room(room_id);
+    let root_event_id = event_id!("$root");
+
+    // The server returns the thread replies in reverse topological order, and
+    // doesn't include the thread root.
+    server
+        .mock_room_relations()
+        .match_target(root_event_id)
+        .match_rel_type(RelationType::Thread)
+        .ok(RoomRelationsResponseTemplate::default().events(vec![
+            f.text_msg("second reply")
+                .sender(&ALICE)
+                .in_thread(root_event_id, event_id!("$1"))
+                .event_id(event_id!("$2"))
+                .into_raw_timeline(),
+            f.text_msg("first reply")
+                .sender(&BOB)
+                .in_thread(root_event_id, root_event_id)
+                .event_id(event_id!("$1"))
+                .into_raw_timeline(),
+        ]))
+        .mock_once()
+        .mount()
+        .await;
+
+    server
+        .mock_room_event()
+        .match_event_id()
+        .ok(f.text_msg("thread root").sender(&ALICE).event_id(root_event_id).into_event())
+        .mock_once()
+        .mount()
+        .await;
+
+    let timeline = Timeline::builder(&room)
+        .with_focus(TimelineFocus::Thread { root_event_id: root_event_id.to_owned() })
+        .build()
+        .await
+        .unwrap();
+
+    let (items, mut timeline_stream) = timeline.subscribe().await;
+
+    assert_eq!(items.len(), 1 + 3);
+    assert!(items[0].is_date_divider());
+    assert_eq!(items[1].as_event().unwrap().content().as_message().unwrap().body(), "thread root");
+    assert_eq!(items[2].as_event().unwrap().content().as_message().unwrap().body(), "first reply");
+    assert_eq!(items[3].as_event().unwrap().content().as_message().unwrap().body(), "second reply");
+
+    // The start of the thread has been reached.
+    let hit_start = timeline.paginate_backwards(20).await.unwrap();
+    assert!(hit_start);
+
+    // Only the new events from sync that belong to the thread are added to the
+    // timeline.
+    server
+        .sync_room(
+            &client,
+            JoinedRoomBuilder::new(room_id)
+                .add_timeline_event(
+                    f.text_msg("not in the thread").sender(&BOB).event_id(event_id!("$3")),
+                )
+                .add_timeline_event(
+                    f.text_msg("third reply")
+                        .sender(&BOB)
+                        .in_thread(root_event_id, event_id!("$2"))
+                        .event_id(event_id!("$4")),
+                ),
+        )
+        .await;
+
+    assert_let_timeout!(Some(timeline_updates) = timeline_stream.next());
+    assert_eq!(timeline_updates.len(), 1);
+
+    assert_let!(VectorDiff::PushBack { value } = &timeline_updates[0]);
+    assert_eq!(value.as_event().unwrap().content().as_message().unwrap().body(), "third reply");
+
+    assert_pending!(timeline_stream);
+}
+
+#[async_test]
+async fn test_thread_focus_mark_as_read() {
+    let server = MatrixMockServer::new().await;
+    let client = server.client_builder().build().await;
+
+    let room_id = room_id!("!a:b.c");
+    let room = server.sync_joined_room(&client, room_id).await;
+
+    server.mock_room_state_encryption().plain().mount().await;
+
+    let f = EventFactory::new().room(room_id);
+    let root_event_id = event_id!("$root");
+
+    server
+        .mock_room_relations()
+        .match_target(root_event_id)
+        .match_rel_type(RelationType::Thread)
+        .ok(RoomRelationsResponseTemplate::default().events(vec![f
+            .text_msg("reply")
+            .sender(&BOB)
+            .in_thread(root_event_id, root_event_id)
+            .event_id(event_id!("$1"))
+            .into_raw_timeline()]))
+        .mock_once()
+        .mount()
+        .await;
+
+    server
+        .mock_room_event()
+        .match_event_id()
+        .ok(f.text_msg("thread root").sender(&ALICE).event_id(root_event_id).into_event())
+        .mock_once()
+        .mount()
+        .await;
+
+    let timeline = Timeline::builder(&room)
+        .with_focus(TimelineFocus::Thread { root_event_id: root_event_id.to_owned() })
+        .build()
+        .await
+        .unwrap();
+
+    // Marking a thread-focused timeline as read sends a threaded receipt on the
+    // latest event of the thread.
+    server
+        .mock_send_receipt(ReceiptType::Read)
+        .body_matches_partial_json(json!({ "thread_id": root_event_id }))
+        .ok()
+        .mock_once()
+        .mount()
+        .await;
+
+    assert!(timeline.mark_as_read(ReceiptType::Read).await.unwrap());
+}
+
+#[async_test]
+async fn test_thread_focus_mark_as_read_on_thread_root() {
+    let server = MatrixMockServer::new().await;
+    let client = server.client_builder().build().await;
+
+    let room_id = room_id!("!a:b.c");
+    let room = server.sync_joined_room(&client, room_id).await;
+
+    server.mock_room_state_encryption().plain().mount().await;
+
+    let f = EventFactory::new().room(room_id);
+    let root_event_id = event_id!("$root");
+
+    // The thread doesn't have any reply yet.
+    server
+        .mock_room_relations()
+        .match_target(root_event_id)
+        .match_rel_type(RelationType::Thread)
+        .ok(RoomRelationsResponseTemplate::default())
+        .mock_once()
+        .mount()
+        .await;
+
+    server
+        .mock_room_event()
+        .match_event_id()
+        .ok(f.text_msg("thread root").sender(&BOB).event_id(root_event_id).into_event())
+        .mock_once()
+        .mount()
+        .await;
+
+    let timeline = Timeline::builder(&room)
+        .with_focus(TimelineFocus::Thread { root_event_id: root_event_id.to_owned() })
+        .build()
+        .await
+        .unwrap();
+
+    // The thread root belongs to the main timeline, so the receipt on it is
+    // main-threaded.
+    server
+        .mock_send_receipt(ReceiptType::Read)
+        .body_matches_partial_json(json!({ "thread_id": "main" }))
+        .ok()
+        .mock_once()
+        .mount()
+        .await;
+
+    assert!(timeline.mark_as_read(ReceiptType::Read).await.unwrap());
+}
@@ -49,7 +49,7 @@ use ruma::{
 use serde::Deserialize;
 use tracing::error;
 
-use crate::{config::RequestConfig, Client, Error, Result};
+use crate::{config::RequestConfig, media::MediaOrigin, Client, Error, Result};
 
 /// A high-level API to manage the client owner's account.
 ///
@@ -218,7 +218,12 @@ impl Account {
     pub async fn get_avatar(&self, format: MediaFormat) -> Result<Option<Vec<u8>>> {
         if let Some(url) = self.get_avatar_url().await? {
             let request = MediaRequestParameters { source: MediaSource::Plain(url), format };
-            Ok(Some(self.client.media().get_media_content(&request, true).await?))
+            Ok(Some(
+                self.client
+                    .media()
+                    .get_media_content_with_origin(&request, true, &MediaOrigin::avatar())
+                    .await?,
+            ))
         } else {
             Ok(None)
         }
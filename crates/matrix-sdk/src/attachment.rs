@@ -20,13 +20,18 @@ use ruma::{
     assign,
     events::{
         room::{
-            message::{AudioInfo, FileInfo, FormattedBody, VideoInfo},
+            message::{AudioInfo, FileInfo, FormattedBody, MessageType, VideoInfo},
             ImageInfo, ThumbnailInfo,
         },
         Mentions,
     },
+    serde::JsonObject,
     OwnedTransactionId, TransactionId, UInt,
 };
+use serde_json::Value as JsonValue;
+
+#[cfg(feature = "image-processing")]
+pub use crate::image_processing::ImageProcessingConfig;
 
 /// Base metadata about an image.
 #[derive(Debug, Clone, Default)]
@@ -188,6 +193,8 @@ pub struct AttachmentConfig {
     pub(crate) caption: Option<String>,
     pub(crate) formatted_caption: Option<FormattedBody>,
     pub(crate) mentions: Option<Mentions>,
+    #[cfg(feature = "image-processing")]
+    pub(crate) image_processing: Option<ImageProcessingConfig>,
 }
 
 impl AttachmentConfig {
@@ -262,4 +269,186 @@ impl AttachmentConfig {
         self.mentions = mentions;
         self
     }
+
+    /// Process the attachment before sending it, if it's an image in a
+    /// supported format (JPEG, PNG, GIF or WebP).
+    ///
+    /// Its dimensions, size and blurhash are computed and set in the
+    /// [`AttachmentInfo`], a thumbnail is generated if none was provided, and
+    /// the image is downsized if it's too big. See [`ImageProcessingConfig`]
+    /// for the details.
+    ///
+    /// # Arguments
+    ///
+    /// * `config` - The settings of the processing.
+    #[cfg(feature = "image-processing")]
+    #[must_use]
+    pub fn process_image(mut self, config: ImageProcessingConfig) -> Self {
+        self.image_processing = Some(config);
+        self
+    }
+}
+
+/// An item of a gallery to send, see [`RoomSendQueue::send_gallery()`].
+///
+/// [`RoomSendQueue::send_gallery()`]: crate::send_queue::RoomSendQueue::send_gallery
+#[derive(Debug)]
+pub struct GalleryItem {
+    pub(crate) filename: String,
+    pub(crate) content_type: mime::Mime,
+    pub(crate) data: Vec<u8>,
+    pub(crate) info: Option<AttachmentInfo>,
+    pub(crate) thumbnail: Option<Thumbnail>,
+    pub(crate) caption: Option<String>,
+    pub(crate) formatted_caption: Option<FormattedBody>,
+    #[cfg(feature = "image-processing")]
+    pub(crate) image_processing: Option<ImageProcessingConfig>,
+}
+
+impl GalleryItem {
+    /// Create a new `GalleryItem` for the given file.
+    ///
+    /// # Arguments
+    ///
+    /// * `filename` - The file name of the item.
+    ///
+    /// * `content_type` - The type of the media, this will be used as the
+    ///   content-type header.
+    ///
+    /// * `data` - The raw bytes of the item.
+    pub fn new(filename: impl Into<String>, content_type: mime::Mime, data: Vec<u8>) -> Self {
+        Self {
+            filename: filename.into(),
+            content_type,
+            data,
+            info: None,
+            thumbnail: None,
+            caption: None,
+            formatted_caption: None,
+            #[cfg(feature = "image-processing")]
+            image_processing: None,
+        }
+    }
+
+    /// Set the thumbnail to send.
+    ///
+    /// # Arguments
+    ///
+    /// * `thumbnail` - The thumbnail of the media. If the `content_type` does
+    ///   not support it (e.g. audio clips), it is ignored.
+    #[must_use]
+    pub fn thumbnail(mut self, thumbnail: Option<Thumbnail>) -> Self {
+        self.thumbnail = thumbnail;
+        self
+    }
+
+    /// Set the media metadata to send.
+    ///
+    /// # Arguments
+    ///
+    /// * `info` - The metadata of the media. If the `AttachmentInfo` type
+    ///   doesn't match the `content_type`, it is ignored.
+    #[must_use]
+    pub fn info(mut self, info: AttachmentInfo) -> Self {
+        self.info = Some(info);
+        self
+    }
+
+    /// Set the optional caption of the item.
+    ///
+    /// # Arguments
+    ///
+    /// * `caption` - The optional caption
+    #[must_use]
+    pub fn caption(mut self, caption: Option<String>) -> Self {
+        self.caption = caption;
+        self
+    }
+
+    /// Set the optional formatted caption of the item.
+    ///
+    /// # Arguments
+    ///
+    /// * `formatted_caption` - The optional formatted caption
+    #[must_use]
+    pub fn formatted_caption(mut self, formatted_caption: Option<FormattedBody>) -> Self {
+        self.formatted_caption = formatted_caption;
+        self
+    }
+
+    /// Process the item before sending it, if it's an image in a supported
+    /// format.
+    ///
+    /// See [`AttachmentConfig::process_image()`] for the details.
+    ///
+    /// # Arguments
+    ///
+    /// * `config` - The settings of the processing.
+    #[cfg(feature = "image-processing")]
+    #[must_use]
+    pub fn process_image(mut self, config: ImageProcessingConfig) -> Self {
+        self.image_processing = Some(config);
+        self
+    }
+}
+
+/// The `msgtype` of a gallery message, as defined in [MSC4274].
+///
+/// [MSC4274]: https://github.com/matrix-org/matrix-spec-proposals/pull/4274
+pub const GALLERY_MSGTYPE: &str = "dm.filament.gallery";
+
+/// Create the message type of a gallery, with the given caption as its body.
+///
+/// `data` holds the additional fields of the message type, the items are added
+/// to it.
+pub(crate) fn make_gallery_type(
+    body: String,
+    mut data: JsonObject,
+    items: &[MessageType],
+) -> serde_json::Result<MessageType> {
+    let itemtypes = items
+        .iter()
+        .map(|item| {
+            let mut item = serde_json::to_value(item)?;
+
+            // Items are message types, whose `msgtype` is called `itemtype`.
+            if let Some(object) = item.as_object_mut() {
+                if let Some(msgtype) = object.remove("msgtype") {
+                    object.insert("itemtype".to_owned(), msgtype);
+                }
+            }
+
+            Ok(item)
+        })
+        .collect::<serde_json::Result<Vec<_>>>()?;
+
+    data.insert("itemtypes".to_owned(), JsonValue::Array(itemtypes));
+
+    MessageType::new(GALLERY_MSGTYPE, body, data)
+}
+
+/// Get the items of a gallery message, as sent by
+/// [`RoomSendQueue::send_gallery()`].
+///
+/// Returns `None` if the message isn't a gallery, or if any of its items is
+/// invalid.
+///
+/// [`RoomSendQueue::send_gallery()`]: crate::send_queue::RoomSendQueue::send_gallery
+pub fn gallery_items(msgtype: &MessageType) -> Option<Vec<MessageType>> {
+    if msgtype.msgtype() != GALLERY_MSGTYPE {
+        return None;
+    }
+
+    msgtype
+        .data()
+        .get("itemtypes")?
+        .as_array()?
+        .iter()
+        .map(|item| {
+            let mut item = item.as_object()?.clone();
+            let itemtype = item.remove("itemtype")?;
+            item.insert("msgtype".to_owned(), itemtype);
+            serde_json::from_value(JsonValue::Object(item)).ok()
+        })
+        .collect()
 }
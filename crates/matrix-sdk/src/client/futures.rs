@@ -101,62 +101,7 @@ where
             if let Err(Some(ErrorKind::UnknownToken { soft_logout })) =
                 res.as_ref().map_err(HttpError::client_api_error_kind)
             {
-                trace!("Token refresh: Unknown token error received.");
-
-                // If automatic token refresh isn't supported, there is nothing more to do.
-                if !client.inner.auth_ctx.handle_refresh_tokens {
-                    trace!("Token refresh: Automatic refresh disabled.");
-                    client.broadcast_unknown_token(soft_logout);
-                    return res;
-                }
-
-                // Try to refresh the token and retry the request.
-                if let Err(refresh_error) = client.refresh_access_token().await {
-                    match &refresh_error {
-                        RefreshTokenError::RefreshTokenRequired => {
-                            trace!("Token refresh: The session doesn't have a refresh token.");
-                            // Refreshing access tokens is not supported by this `Session`, ignore.
-                            client.broadcast_unknown_token(soft_logout);
-                        }
-
-                        #[cfg(feature = "experimental-oidc")]
-                        RefreshTokenError::Oidc(oidc_error) => {
-                            match **oidc_error {
-                                OidcError::Oidc(OidcClientError::TokenRefresh(
-                                    TokenRefreshError::Token(TokenRequestError::Http(
-                                        OidcHttpError {
-                                            body:
-                                                Some(OidcErrorBody {
-                                                    error: ClientErrorCode::InvalidGrant,
-                                                    ..
-                                                }),
-                                            ..
-                                        },
-                                    )),
-                                )) => {
-                                    error!("Token refresh: OIDC refresh_token rejected with invalid grant");
-                                    // The refresh was denied, signal to sign out the user.
-                                    client.broadcast_unknown_token(soft_logout);
-                                }
-                                _ => {
-                                    trace!("Token refresh: OIDC refresh encountered a problem.");
-                                    // The refresh failed for other reasons, no
-                                    // need to sign out.
-                                }
-                            };
-                            return Err(HttpError::RefreshToken(refresh_error));
-                        }
-
-                        _ => {
-                            trace!("Token refresh: Token refresh failed.");
-                            // This isn't necessarily correct, but matches the behaviour when
-                            // implementing OIDC.
-                            client.broadcast_unknown_token(soft_logout);
-                            return Err(HttpError::RefreshToken(refresh_error));
-                        }
-                    }
-                } else {
-                    trace!("Token refresh: Refresh succeeded, retrying request.");
+                if refresh_access_token_after_unknown_token(&client, *soft_logout).await? {
                     return Box::pin(client.send_inner(request, config, send_progress)).await;
                 }
             }
@@ -165,3 +110,67 @@ where
         })
     }
 }
+
+/// Try to refresh the access token after a request failed with an
+/// `M_UNKNOWN_TOKEN` error.
+///
+/// Returns `true` if the token was refreshed and the request should be
+/// retried, and `false` if the original error should be returned.
+pub(crate) async fn refresh_access_token_after_unknown_token(
+    client: &Client,
+    soft_logout: bool,
+) -> HttpResult<bool> {
+    trace!("Token refresh: Unknown token error received.");
+
+    // If automatic token refresh isn't supported, there is nothing more to do.
+    if !client.inner.auth_ctx.handle_refresh_tokens {
+        trace!("Token refresh: Automatic refresh disabled.");
+        client.broadcast_unknown_token(&soft_logout);
+        return Ok(false);
+    }
+
+    // Try to refresh the token and retry the request.
+    let Err(refresh_error) = client.refresh_access_token().await else {
+        trace!("Token refresh: Refresh succeeded, retrying request.");
+        return Ok(true);
+    };
+
+    match &refresh_error {
+        RefreshTokenError::RefreshTokenRequired => {
+            trace!("Token refresh: The session doesn't have a refresh token.");
+            // Refreshing access tokens is not supported by this `Session`, ignore.
+            client.broadcast_unknown_token(&soft_logout);
+            Ok(false)
+        }
+
+        #[cfg(feature = "experimental-oidc")]
+        RefreshTokenError::Oidc(oidc_error) => {
+            match **oidc_error {
+                OidcError::Oidc(OidcClientError::TokenRefresh(TokenRefreshError::Token(
+                    TokenRequestError::Http(OidcHttpError {
+                        body: Some(OidcErrorBody { error: ClientErrorCode::InvalidGrant, .. }),
+                        ..
+                    }),
+                ))) => {
+                    error!("Token refresh: OIDC refresh_token rejected with invalid grant");
+                    // The refresh was denied, signal to sign out the user.
+                    client.broadcast_unknown_token(&soft_logout);
+                }
+                _ => {
+                    trace!("Token refresh: OIDC refresh encountered a problem.");
+                    // The refresh failed for other reasons, no
+                    // need to sign out.
+                }
+            };
+            Err(HttpError::RefreshToken(refresh_error))
+        }
+
+        _ => {
+            trace!("Token refresh: Token refresh failed.");
+            // This isn't necessarily correct, but matches the behaviour when
+            // implementing OIDC.
+            client.broadcast_unknown_token(&soft_logout);
+            Err(HttpError::RefreshToken(refresh_error))
+        }
+    }
+}
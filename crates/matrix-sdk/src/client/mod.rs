@@ -91,6 +91,7 @@ use crate::{
         EventHandlerStore, ObservableEventHandler, SyncEvent,
     },
     http_client::HttpClient,
+    message_search::{self, MessageSearchOptions, MessageSearchResponse},
     notification_settings::NotificationSettings,
     room_preview::RoomPreview,
     send_queue::SendQueueData,
@@ -198,6 +199,11 @@ pub(crate) struct ClientLocks {
     #[cfg(feature = "e2e-encryption")]
     pub(crate) key_claim_lock: Mutex<()>,
 
+    /// Lock making sure that the dehydrated device is only handled by one task
+    /// at a time.
+    #[cfg(feature = "e2e-encryption")]
+    pub(crate) dehydrated_device_lock: Mutex<()>,
+
     /// Handler to ensure that only one members request is running at a time,
     /// given a room.
     pub(crate) members_request_deduplicated_handler: DeduplicatingHandler<OwnedRoomId>,
@@ -331,6 +337,13 @@ pub(crate) struct ClientInner {
     ///
     /// [`SendQueue`]: crate::send_queue::SendQueue
     pub(crate) send_queue_data: Arc<SendQueueData>,
+
+    /// The [`MediaDownloadManager`], spawned the first time it's used.
+    ///
+    /// [`MediaDownloadManager`]: crate::media::download_manager::MediaDownloadManager
+    #[cfg(not(target_arch = "wasm32"))]
+    pub(crate) media_download_manager:
+        std::sync::OnceLock<crate::media::download_manager::MediaDownloadManager>,
 }
 
 impl ClientInner {
@@ -381,6 +394,8 @@ impl ClientInner {
             sync_beat: event_listener::Event::new(),
             event_cache,
             send_queue_data: send_queue,
+            #[cfg(not(target_arch = "wasm32"))]
+            media_download_manager: Default::default(),
             #[cfg(feature = "e2e-encryption")]
             e2ee: EncryptionData::new(encryption_settings),
             #[cfg(feature = "e2e-encryption")]
@@ -1572,6 +1587,43 @@ impl Client {
         self.send(request).await
     }
 
+    /// Search for messages on the server, in all the rooms the user is in or
+    /// only in some of them, using the `/search` endpoint.
+    ///
+    /// This returns a single page of results; use the `next_batch` token of
+    /// the response in the options of a subsequent call to get the next page,
+    /// or use a [`MessageSearch`](crate::message_search::MessageSearch) to keep
+    /// track of the pagination state.
+    ///
+    /// The results and their context events are decrypted if possible. If
+    /// decryption fails for an individual event, that event is returned
+    /// undecrypted.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # use url::Url;
+    /// # use matrix_sdk::Client;
+    /// # async {
+    /// # let homeserver = Url::parse("http://example.com")?;
+    /// use matrix_sdk::message_search::MessageSearchOptions;
+    /// # let mut client = Client::new(homeserver).await?;
+    ///
+    /// let response =
+    ///     client.search_messages(MessageSearchOptions::new("rust")).await?;
+    ///
+    /// for result in response.results {
+    ///     println!("Found event {:?}", result.event.event_id());
+    /// }
+    /// # anyhow::Ok(()) };
+    /// ```
+    pub async fn search_messages(
+        &self,
+        options: MessageSearchOptions,
+    ) -> Result<MessageSearchResponse> {
+        message_search::search_messages(self, options).await
+    }
+
     /// Send an arbitrary request to the server, without updating client state.
     ///
     /// **Warning:** Because this method *does not* update the client state, it
@@ -1649,6 +1701,92 @@ impl Client {
             .await
     }
 
+    /// Send the given request, and return the response as soon as its headers
+    /// are received, so its body can be streamed.
+    ///
+    /// The given headers are added to the request. Like with
+    /// [`Client::send()`], the access token is refreshed if needed.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub(crate) async fn send_streaming<Request>(
+        &self,
+        request: Request,
+        config: Option<RequestConfig>,
+        headers: http::HeaderMap,
+    ) -> HttpResult<reqwest::Response>
+    where
+        Request: OutgoingRequest + Clone + Debug,
+        HttpError: From<FromHttpResponseError<Request::EndpointError>>,
+    {
+        let send = || async {
+            self.inner
+                .http_client
+                .send_streaming(
+                    request.clone(),
+                    config,
+                    self.homeserver().to_string(),
+                    self.access_token().as_deref(),
+                    &self.server_versions().await?,
+                    headers.clone(),
+                )
+                .await
+        };
+
+        let res = send().await;
+
+        // An `M_UNKNOWN_TOKEN` error can potentially be fixed with a token refresh.
+        if let Err(Some(ErrorKind::UnknownToken { soft_logout })) =
+            res.as_ref().map_err(HttpError::client_api_error_kind)
+        {
+            if futures::refresh_access_token_after_unknown_token(self, *soft_logout).await? {
+                return send().await;
+            }
+        }
+
+        res
+    }
+
+    /// Send the given request with the given streamed body, in place of the
+    /// serialized body of the request.
+    ///
+    /// Unlike with [`Client::send()`], the request is not retried, even after
+    /// a token refresh, because a streamed body can't be replayed.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub(crate) async fn send_with_body<Request>(
+        &self,
+        request: Request,
+        body: reqwest::Body,
+        content_length: u64,
+        config: Option<RequestConfig>,
+    ) -> HttpResult<Request::IncomingResponse>
+    where
+        Request: OutgoingRequest + Debug,
+        HttpError: From<FromHttpResponseError<Request::EndpointError>>,
+    {
+        let res = self
+            .inner
+            .http_client
+            .send_with_body(
+                request,
+                body,
+                content_length,
+                config,
+                self.homeserver().to_string(),
+                self.access_token().as_deref(),
+                &self.server_versions().await?,
+            )
+            .await;
+
+        // The request can't be retried, but a refreshed token is still useful for the
+        // next requests.
+        if let Err(Some(ErrorKind::UnknownToken { soft_logout })) =
+            res.as_ref().map_err(HttpError::client_api_error_kind)
+        {
+            futures::refresh_access_token_after_unknown_token(self, *soft_logout).await?;
+        }
+
+        res
+    }
+
     fn broadcast_unknown_token(&self, soft_logout: &bool) {
         _ = self
             .inner
@@ -445,75 +445,130 @@ impl Backups {
     /// Download all room keys for a certain room from the server-side key
     /// backup.
     pub async fn download_room_keys_for_room(&self, room_id: &RoomId) -> Result<(), Error> {
+        self.download_room_keys_for_room_impl(room_id, false).await?;
+        Ok(())
+    }
+
+    /// Download a single room key from the server-side key backup.
+    ///
+    /// Returns `true` if we managed to download a room key, `false` or an error
+    /// if we failed to download it. `false` indicates that there was no
+    /// error, we just don't have backups enabled so we can't download a
+    /// room key.
+    pub async fn download_room_key(
+        &self,
+        room_id: &RoomId,
+        session_id: &str,
+    ) -> Result<bool, Error> {
+        self.download_room_key_impl(room_id, session_id, false).await
+    }
+
+    /// Whether the room key with the given session ID was downloaded from the
+    /// backup because an event failed to be decrypted with it.
+    ///
+    /// This is only the case if the [`BackupDownloadStrategy`] is set to
+    /// [`BackupDownloadStrategy::AfterDecryptionFailure`] or
+    /// [`BackupDownloadStrategy::RoomAfterDecryptionFailure`], and can be used
+    /// to find out why an event could be decrypted late.
+    pub fn was_room_key_downloaded_after_utd(&self, session_id: &str) -> bool {
+        self.client
+            .inner
+            .e2ee
+            .backup_state
+            .room_keys_downloaded_after_utd
+            .lock()
+            .contains(session_id)
+    }
+
+    /// Download all room keys for a certain room from the server-side key
+    /// backup.
+    ///
+    /// Returns `false` if we don't have backups enabled. `after_utd` tells
+    /// whether the room keys are downloaded because an event failed to be
+    /// decrypted.
+    pub(crate) async fn download_room_keys_for_room_impl(
+        &self,
+        room_id: &RoomId,
+        after_utd: bool,
+    ) -> Result<bool, Error> {
         let olm_machine = self.client.olm_machine().await;
         let olm_machine = olm_machine.as_ref().ok_or(Error::NoOlmMachine)?;
 
         let backup_keys = olm_machine.store().load_backup_keys().await?;
 
-        if let Some(decryption_key) = backup_keys.decryption_key {
-            if let Some(version) = backup_keys.backup_version {
-                let request =
-                    get_backup_keys_for_room::v3::Request::new(version.clone(), room_id.to_owned());
-                let response = self.client.send(request).await?;
+        let (Some(decryption_key), Some(version)) =
+            (backup_keys.decryption_key, backup_keys.backup_version)
+        else {
+            return Ok(false);
+        };
 
-                // Transform response to standard format (map of room ID -> room key).
-                let response = get_backup_keys::v3::Response::new(BTreeMap::from([(
-                    room_id.to_owned(),
-                    RoomKeyBackup::new(response.sessions),
-                )]));
+        let request =
+            get_backup_keys_for_room::v3::Request::new(version.clone(), room_id.to_owned());
+        let response = self.client.send(request).await?;
 
-                self.handle_downloaded_room_keys(response, decryption_key, &version, olm_machine)
-                    .await?;
-            }
-        }
+        // Transform response to standard format (map of room ID -> room key).
+        let response = get_backup_keys::v3::Response::new(BTreeMap::from([(
+            room_id.to_owned(),
+            RoomKeyBackup::new(response.sessions),
+        )]));
+
+        self.handle_downloaded_room_keys(
+            response,
+            decryption_key,
+            &version,
+            after_utd,
+            olm_machine,
+        )
+        .await?;
 
-        Ok(())
+        Ok(true)
     }
 
     /// Download a single room key from the server-side key backup.
     ///
-    /// Returns `true` if we managed to download a room key, `false` or an error
-    /// if we failed to download it. `false` indicates that there was no
-    /// error, we just don't have backups enabled so we can't download a
-    /// room key.
-    pub async fn download_room_key(
+    /// Returns `false` if we don't have backups enabled. `after_utd` tells
+    /// whether the room key is downloaded because an event failed to be
+    /// decrypted.
+    pub(crate) async fn download_room_key_impl(
         &self,
         room_id: &RoomId,
         session_id: &str,
+        after_utd: bool,
     ) -> Result<bool, Error> {
         let olm_machine = self.client.olm_machine().await;
         let olm_machine = olm_machine.as_ref().ok_or(Error::NoOlmMachine)?;
 
         let backup_keys = olm_machine.store().load_backup_keys().await?;
 
-        if let Some(decryption_key) = backup_keys.decryption_key {
-            if let Some(version) = backup_keys.backup_version {
-                let request = get_backup_keys_for_session::v3::Request::new(
-                    version.clone(),
-                    room_id.to_owned(),
-                    session_id.to_owned(),
-                );
-                let response = self.client.send(request).await?;
+        let (Some(decryption_key), Some(version)) =
+            (backup_keys.decryption_key, backup_keys.backup_version)
+        else {
+            return Ok(false);
+        };
 
-                // Transform response to standard format (map of room ID -> room key).
-                let response = get_backup_keys::v3::Response::new(BTreeMap::from([(
-                    room_id.to_owned(),
-                    RoomKeyBackup::new(BTreeMap::from([(
-                        session_id.to_owned(),
-                        response.key_data,
-                    )])),
-                )]));
+        let request = get_backup_keys_for_session::v3::Request::new(
+            version.clone(),
+            room_id.to_owned(),
+            session_id.to_owned(),
+        );
+        let response = self.client.send(request).await?;
 
-                self.handle_downloaded_room_keys(response, decryption_key, &version, olm_machine)
-                    .await?;
+        // Transform response to standard format (map of room ID -> room key).
+        let response = get_backup_keys::v3::Response::new(BTreeMap::from([(
+            room_id.to_owned(),
+            RoomKeyBackup::new(BTreeMap::from([(session_id.to_owned(), response.key_data)])),
+        )]));
+
+        self.handle_downloaded_room_keys(
+            response,
+            decryption_key,
+            &version,
+            after_utd,
+            olm_machine,
+        )
+        .await?;
 
-                Ok(true)
-            } else {
-                Ok(false)
-            }
-        } else {
-            Ok(false)
-        }
+        Ok(true)
     }
 
     /// Set the state of the backup.
@@ -548,6 +603,7 @@ impl Backups {
         backed_up_keys: get_backup_keys::v3::Response,
         backup_decryption_key: BackupDecryptionKey,
         backup_version: &str,
+        after_utd: bool,
         olm_machine: &OlmMachine,
     ) -> Result<(), Error> {
         let mut decrypted_room_keys: Vec<_> = Vec::new();
@@ -585,6 +641,17 @@ impl Backups {
             }
         }
 
+        let backup_state = &self.client.inner.e2ee.backup_state;
+
+        // Remember the room keys downloaded because of a decryption failure before
+        // importing them, so the listeners of the room keys streams can tell why the
+        // events could be decrypted.
+        if after_utd {
+            backup_state.add_room_keys_downloaded_after_utd(
+                decrypted_room_keys.iter().map(|key| key.session_id.as_str()),
+            );
+        }
+
         let result = olm_machine
             .store()
             .import_room_keys(decrypted_room_keys, Some(backup_version), |_, _| {})
@@ -592,7 +659,7 @@ impl Backups {
 
         // Since we can't use the usual room keys stream from the `OlmMachine`
         // we're going to send things out in our own custom broadcaster.
-        let _ = self.client.inner.e2ee.backup_state.room_keys_broadcaster.send(result);
+        let _ = backup_state.room_keys_broadcaster.send(result);
 
         Ok(())
     }
@@ -609,7 +676,8 @@ impl Backups {
         let olm_machine = self.client.olm_machine().await;
         let olm_machine = olm_machine.as_ref().ok_or(Error::NoOlmMachine)?;
 
-        self.handle_downloaded_room_keys(response, decryption_key, &version, olm_machine).await?;
+        self.handle_downloaded_room_keys(response, decryption_key, &version, false, olm_machine)
+            .await?;
 
         Ok(())
     }
@@ -755,8 +823,13 @@ impl Backups {
 
         self.client.add_event_handler(Self::secret_send_event_handler);
 
-        if self.client.inner.e2ee.encryption_settings.backup_download_strategy
-            == BackupDownloadStrategy::AfterDecryptionFailure
+        if self
+            .client
+            .inner
+            .e2ee
+            .encryption_settings
+            .backup_download_strategy
+            .downloads_after_decryption_failure()
         {
             self.client.add_event_handler(Self::utd_event_handler);
         }
@@ -1421,4 +1494,21 @@ mod test {
 
         assert_eq!(old_duration, current_duration);
     }
+
+    #[async_test]
+    async fn test_only_utd_room_keys_are_marked_as_downloaded_after_utd() {
+        let client = logged_in_client(None).await;
+        let backup_state = &client.inner.e2ee.backup_state;
+        let backups = client.encryption().backups();
+
+        backup_state.add_utd_session_id("utd".to_owned());
+
+        // All the room keys of the room are downloaded, but only the one that caused a
+        // decryption failure is remembered.
+        backup_state.add_room_keys_downloaded_after_utd(["other", "utd"]);
+
+        assert!(backups.was_room_key_downloaded_after_utd("utd"));
+        assert!(!backups.was_room_key_downloaded_after_utd("other"));
+        assert!(backup_state.utd_session_ids.lock().is_empty());
+    }
 }
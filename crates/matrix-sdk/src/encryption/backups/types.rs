@@ -13,11 +13,13 @@
 // limitations under the License.
 
 use std::{
+    collections::HashSet,
     sync::{Arc, RwLock},
     time::Duration,
 };
 
 use matrix_sdk_base::crypto::{store::RoomKeyCounts, RoomKeyImportResult};
+use matrix_sdk_common::locks::Mutex as StdMutex;
 use tokio::sync::broadcast;
 
 use crate::utils::ChannelObservable;
@@ -61,6 +63,14 @@ pub(crate) struct BackupClientState {
     /// on the server was changed by some other client, we will have a old
     /// value.
     pub(super) backup_exists_on_server: RwLock<Option<bool>>,
+
+    /// The session IDs of the room keys that events failed to be decrypted
+    /// with, and that haven't been downloaded from the backup yet.
+    pub(crate) utd_session_ids: StdMutex<HashSet<String>>,
+
+    /// The session IDs of the room keys that events failed to be decrypted
+    /// with, and that were downloaded from the backup afterwards.
+    pub(crate) room_keys_downloaded_after_utd: StdMutex<HashSet<String>>,
 }
 
 impl BackupClientState {
@@ -84,6 +94,32 @@ impl BackupClientState {
     pub(crate) fn clear_backup_exists_on_server(&self) {
         *self.backup_exists_on_server.write().unwrap() = None;
     }
+
+    /// Remember that an event failed to be decrypted with the room key with
+    /// the given session ID.
+    pub(crate) fn add_utd_session_id(&self, session_id: String) {
+        self.utd_session_ids.lock().insert(session_id);
+    }
+
+    /// Remember the room keys with the given session IDs, downloaded from the
+    /// backup after an event failed to be decrypted, as such.
+    ///
+    /// Only the room keys that events failed to be decrypted with are
+    /// remembered, the other room keys downloaded along with them are
+    /// ignored.
+    pub(crate) fn add_room_keys_downloaded_after_utd<'a>(
+        &self,
+        session_ids: impl IntoIterator<Item = &'a str>,
+    ) {
+        let mut utd_session_ids = self.utd_session_ids.lock();
+        let mut room_keys = self.room_keys_downloaded_after_utd.lock();
+
+        for session_id in session_ids {
+            if let Some(session_id) = utd_session_ids.take(session_id) {
+                room_keys.insert(session_id);
+            }
+        }
+    }
 }
 
 const DEFAULT_BACKUP_UPLOAD_DELAY: Duration = Duration::from_millis(100);
@@ -96,6 +132,8 @@ impl Default for BackupClientState {
             global_state: Default::default(),
             room_keys_broadcaster: broadcast::Sender::new(100),
             backup_exists_on_server: RwLock::new(None),
+            utd_session_ids: Default::default(),
+            room_keys_downloaded_after_utd: Default::default(),
         }
     }
 }
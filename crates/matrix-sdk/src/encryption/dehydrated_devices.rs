@@ -0,0 +1,509 @@
+// Copyright 2025 The Matrix.org Foundation C.I.C.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Dehydrated devices, as defined in [MSC3814].
+//!
+//! A dehydrated device is a device that lives on the homeserver and receives
+//! room keys while the user has no other device logged in. Its private keys are
+//! encrypted with a pickle key, which is stored in secret storage so that a new
+//! device of the user can download the dehydrated device, decrypt it and
+//! collect the room keys it received. This process is called rehydration.
+//!
+//! Once a device was rehydrated, it's replaced by a new dehydrated device. The
+//! dehydrated device is also replaced periodically, so its one-time keys don't
+//! run out and the to-device events it receives don't pile up.
+//!
+//! If [`EncryptionSettings::auto_enable_dehydrated_devices`] is set, the whole
+//! lifecycle is handled by the client:
+//!
+//! - a pickle key is created and stored in secret storage when
+//!   [`Recovery::enable()`] is used,
+//! - the pickle key is imported from secret storage when
+//!   [`Recovery::recover()`] is used, or created and stored in it if there is
+//!   none, and the dehydrated device is rehydrated,
+//! - the dehydrated device is replaced when the client is logged in or
+//!   restored, if a pickle key is known, and then periodically.
+//!
+//! A dehydrated device that was uploaded by the client itself is never
+//! rehydrated by it, since it already received the same room keys.
+//!
+//! [MSC3814]: https://github.com/matrix-org/matrix-spec-proposals/pull/3814
+//! [`EncryptionSettings::auto_enable_dehydrated_devices`]: crate::encryption::EncryptionSettings::auto_enable_dehydrated_devices
+//! [`Recovery::enable()`]: crate::encryption::recovery::Recovery::enable
+//! [`Recovery::recover()`]: crate::encryption::recovery::Recovery::recover
+
+use std::time::Duration;
+
+use matrix_sdk_base::crypto::{
+    store::DehydratedDeviceKey, vodozemac::base64_decode, DehydrationError,
+};
+use ruma::{
+    api::client::{
+        dehydrated_device::{delete_dehydrated_device, get_dehydrated_device, get_events},
+        error::ErrorKind,
+    },
+    events::secret::request::SecretName,
+    OwnedDeviceId,
+};
+use thiserror::Error;
+use tracing::{info, instrument, warn};
+use zeroize::Zeroize;
+
+use crate::{
+    client::WeakClient,
+    encryption::secret_storage::{SecretStorageError, SecretStore},
+    executor::spawn,
+    Client, HttpError,
+};
+
+/// The name of the secret that holds the pickle key of the dehydrated device
+/// in secret storage.
+pub(crate) const DEHYDRATED_DEVICE_SECRET_NAME: &str = "org.matrix.msc3814";
+
+/// The display name of the dehydrated devices created by the client.
+const DEHYDRATED_DEVICE_DISPLAY_NAME: &str = "Dehydrated device";
+
+/// How often the dehydrated device is replaced when it's handled
+/// automatically.
+const DEHYDRATED_DEVICE_ROTATION_PERIOD: Duration = Duration::from_secs(7 * 24 * 60 * 60);
+
+/// The key of the ID of the last dehydrated device uploaded by this client, in
+/// the custom values of the state store.
+const UPLOADED_DEVICE_ID_KEY: &[u8] = b"dehydrated_devices.uploaded_device_id";
+
+/// Error type for the [`DehydratedDevices`] manager.
+#[derive(Debug, Error)]
+pub enum DehydratedDeviceError {
+    /// No pickle key for the dehydrated device is known by this client.
+    ///
+    /// The pickle key can be imported from secret storage with
+    /// [`Recovery::recover()`]. If secret storage is enabled, a new pickle key
+    /// can be created with [`DehydratedDevices::create_with_secret_store()`].
+    ///
+    /// [`Recovery::recover()`]: crate::encryption::recovery::Recovery::recover
+    #[error("The pickle key of the dehydrated device is unknown")]
+    MissingPickleKey,
+
+    /// A typical SDK error.
+    #[error(transparent)]
+    Sdk(#[from] crate::Error),
+
+    /// The dehydrated device could not be created or rehydrated.
+    #[error(transparent)]
+    Dehydration(#[from] DehydrationError),
+
+    /// The pickle key could not be stored in or retrieved from secret storage.
+    #[error(transparent)]
+    SecretStorage(#[from] SecretStorageError),
+}
+
+impl From<HttpError> for DehydratedDeviceError {
+    fn from(error: HttpError) -> Self {
+        Self::Sdk(error.into())
+    }
+}
+
+/// The dehydrated devices manager for the [`Client`].
+#[derive(Debug, Clone)]
+pub struct DehydratedDevices {
+    pub(super) client: Client,
+}
+
+impl DehydratedDevices {
+    /// Whether a pickle key for the dehydrated device is known by this client.
+    ///
+    /// The pickle key is created when [`DehydratedDevices::create()`] is
+    /// called for the first time, or imported from secret storage.
+    pub async fn has_pickle_key(&self) -> Result<bool, DehydratedDeviceError> {
+        Ok(self.pickle_key().await?.is_some())
+    }
+
+    /// Create a new dehydrated device and upload it to the homeserver,
+    /// replacing the existing one, if any.
+    ///
+    /// The known pickle key is used to encrypt the device. If there is none
+    /// and secret storage is not enabled, a new one is created and cached
+    /// locally. It will be stored in secret storage the next time a secret
+    /// store is created, for example with [`Recovery::enable()`]. If secret
+    /// storage is already enabled, this fails with
+    /// [`DehydratedDeviceError::MissingPickleKey`], since the new pickle key
+    /// could not be stored in it: use
+    /// [`DehydratedDevices::create_with_secret_store()`] instead.
+    ///
+    /// This requires the private self-signing key to be available, since the
+    /// dehydrated device needs to be signed by our user identity.
+    ///
+    /// Returns the ID of the new dehydrated device.
+    ///
+    /// [`Recovery::enable()`]: crate::encryption::recovery::Recovery::enable
+    #[instrument(skip_all)]
+    pub async fn create(&self) -> Result<OwnedDeviceId, DehydratedDeviceError> {
+        let _guard = self.client.locks().dehydrated_device_lock.lock().await;
+        self.create_inner(None).await
+    }
+
+    /// Create a new dehydrated device and upload it to the homeserver,
+    /// replacing the existing one, if any.
+    ///
+    /// Like [`DehydratedDevices::create()`], but if no pickle key is known, the
+    /// new one is stored in the given secret store before the device is
+    /// uploaded.
+    ///
+    /// Returns the ID of the new dehydrated device.
+    #[instrument(skip_all)]
+    pub async fn create_with_secret_store(
+        &self,
+        secret_store: &SecretStore,
+    ) -> Result<OwnedDeviceId, DehydratedDeviceError> {
+        let _guard = self.client.locks().dehydrated_device_lock.lock().await;
+        self.create_inner(Some(secret_store)).await
+    }
+
+    /// Rehydrate the dehydrated device of the user, if there is one, to
+    /// collect the room keys it received.
+    ///
+    /// This needs the pickle key that was used to create the dehydrated
+    /// device, it is usually imported from secret storage with
+    /// [`Recovery::recover()`].
+    ///
+    /// The dehydrated device is not usable anymore once it was rehydrated, so
+    /// it should be replaced with [`DehydratedDevices::create()`].
+    ///
+    /// Returns the number of room keys that were imported.
+    ///
+    /// [`Recovery::recover()`]: crate::encryption::recovery::Recovery::recover
+    #[instrument(skip_all)]
+    pub async fn rehydrate(&self) -> Result<usize, DehydratedDeviceError> {
+        let _guard = self.client.locks().dehydrated_device_lock.lock().await;
+        self.rehydrate_inner().await
+    }
+
+    /// Delete the dehydrated device of the user from the homeserver, and
+    /// forget its pickle key.
+    ///
+    /// The pickle key is not removed from secret storage, so it can be used
+    /// again by another device.
+    #[instrument(skip_all)]
+    pub async fn delete(&self) -> Result<(), DehydratedDeviceError> {
+        let _guard = self.client.locks().dehydrated_device_lock.lock().await;
+
+        match self.client.send(delete_dehydrated_device::unstable::Request::new()).await {
+            Ok(_) => {}
+            Err(error) if error.client_api_error_kind() == Some(&ErrorKind::NotFound) => {
+                info!("There is no dehydrated device to delete");
+            }
+            Err(error) => return Err(error.into()),
+        }
+
+        // There's no dehydrated device to replace anymore.
+        let task = self.client.inner.e2ee.tasks.lock().rotate_dehydrated_device.take();
+        if let Some(_task) = task {
+            #[cfg(not(target_arch = "wasm32"))]
+            _task.abort();
+        }
+
+        self.client
+            .store()
+            .remove_custom_value(UPLOADED_DEVICE_ID_KEY)
+            .await
+            .map_err(crate::Error::from)?;
+
+        let olm_machine = self.client.olm_machine().await;
+        let olm_machine = olm_machine.as_ref().ok_or(crate::Error::NoOlmMachine)?;
+        olm_machine.dehydrated_devices().delete_dehydrated_device_pickle_key().await?;
+
+        Ok(())
+    }
+
+    /// Rehydrate the current dehydrated device, then replace it with a new
+    /// one and make sure that it's replaced periodically.
+    ///
+    /// If a secret store is given, a new pickle key is created and stored in it
+    /// if none is known. Otherwise, this does nothing if no pickle key is
+    /// known, since creating a new one here wouldn't store it in secret
+    /// storage. This also does nothing if the dehydrated devices are not
+    /// handled automatically.
+    pub(crate) async fn setup(&self, secret_store: Option<&SecretStore>) {
+        if !self.client.encryption().settings().auto_enable_dehydrated_devices {
+            return;
+        }
+
+        // Setting up can be triggered concurrently, when the client is restored and
+        // when recovery is used.
+        let _guard = self.client.locks().dehydrated_device_lock.lock().await;
+
+        match self.has_pickle_key().await {
+            Ok(true) => {
+                // Failing to rehydrate the device shouldn't prevent us from replacing it.
+                if let Err(e) = self.rehydrate_inner().await {
+                    warn!("Could not rehydrate the dehydrated device: {e:?}");
+                }
+            }
+            Ok(false) if secret_store.is_some() => {
+                info!("No pickle key for the dehydrated device yet, creating a new one");
+            }
+            Ok(false) => {
+                info!("No pickle key for the dehydrated device yet, not setting it up");
+                return;
+            }
+            Err(e) => {
+                warn!("Could not load the pickle key of the dehydrated device: {e:?}");
+                return;
+            }
+        }
+
+        if let Err(e) = self.create_inner(secret_store).await {
+            warn!("Could not create a new dehydrated device: {e:?}");
+        }
+
+        self.spawn_rotation_task();
+    }
+
+    async fn rehydrate_inner(&self) -> Result<usize, DehydratedDeviceError> {
+        let pickle_key = self.pickle_key().await?.ok_or(DehydratedDeviceError::MissingPickleKey)?;
+
+        let response = match self.client.send(get_dehydrated_device::unstable::Request::new()).await
+        {
+            Ok(response) => response,
+            Err(error) if error.client_api_error_kind() == Some(&ErrorKind::NotFound) => {
+                info!("There is no dehydrated device to rehydrate");
+                return Ok(0);
+            }
+            Err(error) => return Err(error.into()),
+        };
+
+        let device_id = response.device_id;
+
+        if self.uploaded_device_id().await?.as_ref() == Some(&device_id) {
+            // We already received all the room keys that were sent to our own dehydrated
+            // device.
+            info!(%device_id, "Not rehydrating the dehydrated device uploaded by this client");
+            return Ok(0);
+        }
+
+        let rehydrated = {
+            let olm_machine = self.client.olm_machine().await;
+            let olm_machine = olm_machine.as_ref().ok_or(crate::Error::NoOlmMachine)?;
+
+            olm_machine
+                .dehydrated_devices()
+                .rehydrate(&pickle_key, &device_id, response.device_data)
+                .await?
+        };
+
+        info!(%device_id, "Rehydrated the dehydrated device, fetching its to-device events");
+
+        let mut next_batch = None;
+        let mut imported_room_keys = 0;
+
+        loop {
+            let mut request = get_events::unstable::Request::new(device_id.clone());
+            request.next_batch = next_batch.take();
+
+            let response = self.client.send(request).await?;
+
+            if response.events.is_empty() {
+                break;
+            }
+
+            next_batch = response.next_batch;
+            imported_room_keys +=
+                rehydrated.receive_events(response.events).await.map_err(crate::Error::from)?.len();
+        }
+
+        info!(imported_room_keys, "Done rehydrating the dehydrated device");
+
+        Ok(imported_room_keys)
+    }
+
+    /// Store the pickle key of the dehydrated device in the given secret
+    /// store.
+    ///
+    /// If no pickle key is known and the dehydrated devices are handled
+    /// automatically, a new pickle key is created first.
+    pub(crate) async fn export_pickle_key(
+        &self,
+        secret_store: &SecretStore,
+    ) -> Result<(), DehydratedDeviceError> {
+        let _guard = self.client.locks().dehydrated_device_lock.lock().await;
+
+        let pickle_key = match self.pickle_key().await? {
+            Some(pickle_key) => pickle_key,
+            None if self.client.encryption().settings().auto_enable_dehydrated_devices => {
+                let pickle_key = DehydratedDeviceKey::new()
+                    .map_err(|e| crate::Error::UnknownError(Box::new(e)))?;
+                self.save_pickle_key(&pickle_key).await?;
+
+                pickle_key
+            }
+            None => return Ok(()),
+        };
+
+        Self::put_pickle_key(secret_store, &pickle_key).await
+    }
+
+    /// Import the pickle key of the dehydrated device from the given secret
+    /// store, if it contains one.
+    pub(crate) async fn import_pickle_key(
+        &self,
+        secret_store: &SecretStore,
+    ) -> Result<(), DehydratedDeviceError> {
+        let _guard = self.client.locks().dehydrated_device_lock.lock().await;
+
+        let secret =
+            secret_store.get_secret(SecretName::from(DEHYDRATED_DEVICE_SECRET_NAME)).await?;
+
+        let Some(mut secret) = secret else {
+            info!("No pickle key for the dehydrated device found in secret storage");
+            return Ok(());
+        };
+
+        let decoded = base64_decode(&secret);
+        secret.zeroize();
+
+        let mut decoded = decoded.map_err(|e| crate::Error::UnknownError(Box::new(e)))?;
+        let pickle_key = DehydratedDeviceKey::from_slice(&decoded);
+        decoded.zeroize();
+
+        self.save_pickle_key(&pickle_key?).await
+    }
+
+    async fn create_inner(
+        &self,
+        secret_store: Option<&SecretStore>,
+    ) -> Result<OwnedDeviceId, DehydratedDeviceError> {
+        let pickle_key = match (self.pickle_key().await?, secret_store) {
+            (Some(pickle_key), _) => pickle_key,
+            (None, Some(secret_store)) => {
+                info!("No pickle key for the dehydrated device, creating a new one");
+
+                let pickle_key = DehydratedDeviceKey::new()
+                    .map_err(|e| crate::Error::UnknownError(Box::new(e)))?;
+
+                // Store the pickle key in secret storage first, so we never upload a device
+                // that other devices can't rehydrate.
+                Self::put_pickle_key(secret_store, &pickle_key).await?;
+                self.save_pickle_key(&pickle_key).await?;
+
+                pickle_key
+            }
+            (None, None) => {
+                if self.client.encryption().secret_storage().is_enabled().await? {
+                    // The new pickle key would only be stored in secret storage the next time
+                    // recovery is enabled.
+                    return Err(DehydratedDeviceError::MissingPickleKey);
+                }
+
+                info!("No pickle key for the dehydrated device, creating a new one");
+
+                let pickle_key = DehydratedDeviceKey::new()
+                    .map_err(|e| crate::Error::UnknownError(Box::new(e)))?;
+                self.save_pickle_key(&pickle_key).await?;
+
+                pickle_key
+            }
+        };
+
+        let request = {
+            let olm_machine = self.client.olm_machine().await;
+            let olm_machine = olm_machine.as_ref().ok_or(crate::Error::NoOlmMachine)?;
+
+            let device = olm_machine.dehydrated_devices().create().await?;
+            device.keys_for_upload(DEHYDRATED_DEVICE_DISPLAY_NAME.to_owned(), &pickle_key).await?
+        };
+
+        let response = self.client.send(request).await?;
+
+        info!(device_id = %response.device_id, "Uploaded a new dehydrated device");
+
+        self.client
+            .store()
+            .set_custom_value_no_read(
+                UPLOADED_DEVICE_ID_KEY,
+                response.device_id.as_bytes().to_vec(),
+            )
+            .await
+            .map_err(crate::Error::from)?;
+
+        Ok(response.device_id)
+    }
+
+    /// The ID of the last dehydrated device uploaded by this client, if any.
+    async fn uploaded_device_id(&self) -> Result<Option<OwnedDeviceId>, DehydratedDeviceError> {
+        let value = self
+            .client
+            .store()
+            .get_custom_value(UPLOADED_DEVICE_ID_KEY)
+            .await
+            .map_err(crate::Error::from)?;
+
+        Ok(value.and_then(|value| String::from_utf8(value).ok()).map(OwnedDeviceId::from))
+    }
+
+    /// Store the given pickle key in the given secret store.
+    async fn put_pickle_key(
+        secret_store: &SecretStore,
+        pickle_key: &DehydratedDeviceKey,
+    ) -> Result<(), DehydratedDeviceError> {
+        let mut secret = pickle_key.to_base64();
+        let ret =
+            secret_store.put_secret(SecretName::from(DEHYDRATED_DEVICE_SECRET_NAME), &secret).await;
+        secret.zeroize();
+
+        Ok(ret?)
+    }
+
+    async fn pickle_key(&self) -> Result<Option<DehydratedDeviceKey>, DehydratedDeviceError> {
+        let olm_machine = self.client.olm_machine().await;
+        let olm_machine = olm_machine.as_ref().ok_or(crate::Error::NoOlmMachine)?;
+
+        Ok(olm_machine.dehydrated_devices().get_dehydrated_device_pickle_key().await?)
+    }
+
+    async fn save_pickle_key(
+        &self,
+        pickle_key: &DehydratedDeviceKey,
+    ) -> Result<(), DehydratedDeviceError> {
+        let olm_machine = self.client.olm_machine().await;
+        let olm_machine = olm_machine.as_ref().ok_or(crate::Error::NoOlmMachine)?;
+
+        Ok(olm_machine.dehydrated_devices().save_dehydrated_device_pickle_key(pickle_key).await?)
+    }
+
+    /// Spawn the task that periodically replaces the dehydrated device, if it
+    /// isn't running already.
+    fn spawn_rotation_task(&self) {
+        let mut tasks = self.client.inner.e2ee.tasks.lock();
+
+        if tasks.rotate_dehydrated_device.is_some() {
+            return;
+        }
+
+        let client = WeakClient::from_client(&self.client);
+
+        tasks.rotate_dehydrated_device = Some(spawn(async move {
+            loop {
+                crate::sleep::sleep(DEHYDRATED_DEVICE_ROTATION_PERIOD).await;
+
+                let Some(client) = client.get() else {
+                    break;
+                };
+
+                if let Err(e) = client.encryption().dehydrated_devices().create().await {
+                    warn!("Could not replace the dehydrated device: {e:?}");
+                }
+            }
+        }));
+    }
+}
@@ -53,9 +53,10 @@ use ruma::{
     assign,
     events::{
         direct::DirectUserIdentifier,
-        room::{MediaSource, ThumbnailInfo},
+        room::{EncryptedFile, EncryptedFileInit, MediaSource, ThumbnailInfo},
     },
-    DeviceId, MilliSecondsSinceUnixEpoch, OwnedDeviceId, OwnedUserId, TransactionId, UserId,
+    DeviceId, MilliSecondsSinceUnixEpoch, OwnedDeviceId, OwnedRoomId, OwnedUserId, TransactionId,
+    UserId,
 };
 use serde::Deserialize;
 use tokio::sync::{Mutex, RwLockReadGuard};
@@ -66,6 +67,7 @@ use vodozemac::Curve25519PublicKey;
 
 use self::{
     backups::{types::BackupClientState, Backups},
+    dehydrated_devices::DehydratedDevices,
     futures::UploadEncryptedFile,
     identities::{Device, DeviceUpdates, IdentityUpdates, UserDevices, UserIdentity},
     recovery::{Recovery, RecoveryState},
@@ -82,6 +84,7 @@ use crate::{
 };
 
 pub mod backups;
+pub mod dehydrated_devices;
 pub mod futures;
 pub mod identities;
 pub mod recovery;
@@ -115,6 +118,11 @@ pub(crate) struct EncryptionData {
 
     /// All state related to secret storage recovery.
     pub recovery_state: SharedObservable<RecoveryState>,
+
+    /// The users who invited us to the rooms we joined before receiving the
+    /// room key bundle they shared with us, so it can be imported when it
+    /// arrives.
+    pub pending_room_history_imports: StdMutex<BTreeMap<OwnedRoomId, OwnedUserId>>,
 }
 
 impl EncryptionData {
@@ -125,6 +133,7 @@ impl EncryptionData {
             tasks: StdMutex::new(Default::default()),
             backup_state: Default::default(),
             recovery_state: Default::default(),
+            pending_room_history_imports: Default::default(),
         }
     }
 
@@ -134,9 +143,7 @@ impl EncryptionData {
         let mut tasks = self.tasks.lock();
         tasks.upload_room_keys = Some(BackupUploadingTask::new(weak_client.clone()));
 
-        if self.encryption_settings.backup_download_strategy
-            == BackupDownloadStrategy::AfterDecryptionFailure
-        {
+        if self.encryption_settings.backup_download_strategy.downloads_after_decryption_failure() {
             tasks.download_room_keys = Some(BackupDownloadTask::new(weak_client));
         }
     }
@@ -174,6 +181,28 @@ pub struct EncryptionSettings {
 
     /// Automatically create a backup version if no backup exists.
     pub auto_enable_backups: bool,
+
+    /// Automatically handle the lifecycle of the dehydrated device of the
+    /// user.
+    ///
+    /// The pickle key of the dehydrated device is stored in secret storage by
+    /// [`Recovery`], and the dehydrated device is rehydrated and replaced on
+    /// login and when recovering, and replaced periodically.
+    ///
+    /// Take a look at the [`dehydrated_devices`] module for more details.
+    pub auto_enable_dehydrated_devices: bool,
+
+    /// Share the history of encrypted rooms with the users we invite, and
+    /// import the history shared by the user that invited us when we join a
+    /// room, as defined in [MSC4268].
+    ///
+    /// The history is only shared if the history visibility of the room allows
+    /// invited users to see it. Take a look at [`Room::share_history()`] for
+    /// more details.
+    ///
+    /// [MSC4268]: https://github.com/matrix-org/matrix-spec-proposals/pull/4268
+    /// [`Room::share_history()`]: crate::Room::share_history
+    pub share_history_on_invite: bool,
 }
 
 /// Settings for end-to-end encryption features.
@@ -195,6 +224,15 @@ pub enum BackupDownloadStrategy {
     /// Attempt to download a single room key if an event fails to be decrypted.
     AfterDecryptionFailure,
 
+    /// Attempt to download all the room keys of a room if an event of this
+    /// room fails to be decrypted.
+    ///
+    /// This downloads more room keys than
+    /// [`BackupDownloadStrategy::AfterDecryptionFailure`], but a single request
+    /// is made for all the events of the room that fail to be decrypted, which
+    /// is useful when opening a room with a lot of undecryptable events.
+    RoomAfterDecryptionFailure,
+
     /// Don't download any room keys automatically. The user can manually
     /// download room keys using the [`Backups::download_room_key()`] methods.
     ///
@@ -203,6 +241,14 @@ pub enum BackupDownloadStrategy {
     Manual,
 }
 
+impl BackupDownloadStrategy {
+    /// Whether room keys are downloaded from the backup when an event fails to
+    /// be decrypted.
+    pub(crate) fn downloads_after_decryption_failure(&self) -> bool {
+        matches!(self, Self::AfterDecryptionFailure | Self::RoomAfterDecryptionFailure)
+    }
+}
+
 /// The verification state of our own device
 ///
 /// This enum tells us if our own user identity trusts these devices, in other
@@ -459,6 +505,54 @@ impl Client {
         UploadEncryptedFile::new(self, content_type, reader)
     }
 
+    /// Encrypt and upload the content of a file to the server, without
+    /// loading it in memory.
+    ///
+    /// The file is read, encrypted and sent chunk by chunk, which allows to
+    /// upload files that are too big to fit in memory. A reasonable timeout
+    /// is inferred from the size of the file.
+    ///
+    /// See [`Client::upload_encrypted_file()`] for how to use the returned
+    /// [`EncryptedFile`].
+    ///
+    /// # Arguments
+    ///
+    /// * `content_type` - The type of the media, this will be used as the
+    ///   content-type header.
+    ///
+    /// * `path` - The path of the file to upload.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub async fn upload_encrypted_file_from_path(
+        &self,
+        content_type: &mime::Mime,
+        path: &std::path::Path,
+    ) -> Result<EncryptedFile> {
+        let encryptor =
+            Arc::new(StdMutex::new(Some(matrix_sdk_base::crypto::AttachmentChunkEncryptor::new())));
+
+        let response = {
+            let encryptor = encryptor.clone();
+            self.media()
+                .upload_file_with(content_type, path, move |chunk| {
+                    if let Some(encryptor) = encryptor.lock().as_mut() {
+                        encryptor.encrypt_chunk(chunk);
+                    }
+                })
+                .await?
+        };
+
+        let keys = encryptor.lock().take().expect("the encryptor is only finished once").finish();
+
+        Ok(EncryptedFileInit {
+            url: response.content_uri,
+            key: keys.key,
+            iv: keys.iv,
+            hashes: keys.hashes,
+            v: keys.version,
+        }
+        .into())
+    }
+
     /// Encrypt and upload the file and thumbnails, and return the source
     /// information.
     pub(crate) async fn upload_encrypted_media_and_thumbnail(
@@ -1500,6 +1594,11 @@ impl Encryption {
         Recovery { client: self.client.to_owned() }
     }
 
+    /// Get the dehydrated devices manager of the client.
+    pub fn dehydrated_devices(&self) -> DehydratedDevices {
+        DehydratedDevices { client: self.client.to_owned() }
+    }
+
     /// Enables the crypto-store cross-process lock.
     ///
     /// This may be required if there are multiple processes that may do writes
@@ -1673,6 +1772,8 @@ impl Encryption {
             if let Err(e) = this.recovery().setup().await {
                 error!("Couldn't setup and resume recovery {e:?}");
             }
+
+            this.dehydrated_devices().setup(None).await;
         }));
     }
 
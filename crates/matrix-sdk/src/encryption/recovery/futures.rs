@@ -143,6 +143,10 @@ impl<'a> IntoFuture for Enable<'a> {
                 recovery.client.encryption().backups().maybe_trigger_backup();
             }
 
+            // The pickle key of the dehydrated device was just stored in the new secret
+            // store, we can now upload a dehydrated device using it.
+            recovery.client.encryption().dehydrated_devices().setup(Some(&store)).await;
+
             let key = store.secret_storage_key();
 
             progress.set(EnableProgress::Done { recovery_key: key });
@@ -109,7 +109,11 @@ use crate::encryption::{
     backups::Backups,
     secret_storage::{SecretStorage, SecretStore},
 };
-use crate::{client::WeakClient, encryption::backups::BackupState, Client};
+use crate::{
+    client::WeakClient,
+    encryption::{backups::BackupState, dehydrated_devices::DEHYDRATED_DEVICE_SECRET_NAME},
+    Client,
+};
 
 pub mod futures;
 mod types;
@@ -488,6 +492,8 @@ impl Recovery {
         store.import_secrets().await?;
         self.update_recovery_state().await?;
 
+        self.client.encryption().dehydrated_devices().setup(Some(&store)).await;
+
         Ok(())
     }
 
@@ -567,13 +573,16 @@ impl Recovery {
     /// Delete all the known secrets we are keeping in secret storage.
     ///
     /// The exact list of secrets is defined in [`Recovery::KNOWN_SECRETS`] and
-    /// might change over time.
+    /// might change over time. The pickle key of the dehydrated device is
+    /// deleted as well.
     ///
     /// Since account data events can't actually be deleted, due to a missing
     /// DELETE API, we're replacing the events with an empty
     /// [`SecretEventContent`].
     async fn delete_all_known_secrets(&self) -> Result<()> {
-        for secret_name in Self::KNOWN_SECRETS {
+        let dehydrated_device_secret = SecretName::from(DEHYDRATED_DEVICE_SECRET_NAME);
+
+        for secret_name in Self::KNOWN_SECRETS.iter().chain([&dehydrated_device_secret]) {
             let event_type = GlobalAccountDataEventType::from(secret_name.to_owned());
             let content = SecretEventContent::new(Default::default());
             let secret_content = Raw::from_json(
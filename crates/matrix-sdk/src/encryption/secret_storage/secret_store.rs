@@ -323,6 +323,7 @@ impl SecretStore {
     /// - `m.cross_signing.self_signing`: The self-signing cross-signing key.
     /// - `m.cross_signing.user_signing`: The user-signing cross-signing key.
     /// - `m.megolm_backup.v1`: The backup recovery key.
+    /// - `org.matrix.msc3814`: The pickle key of the dehydrated device.
     ///
     /// If the `m.cross_signing.self_signing` key is successfully imported, it
     /// is used to sign our own [`Device`], marking it as verified. This step is
@@ -416,6 +417,11 @@ impl SecretStore {
 
         self.maybe_enable_backups().await?;
 
+        if let Err(e) = self.client.encryption().dehydrated_devices().import_pickle_key(self).await
+        {
+            warn!("Could not import the pickle key of the dehydrated device: {e:?}");
+        }
+
         Ok(())
     }
 
@@ -436,6 +442,11 @@ impl SecretStore {
             key.zeroize();
         }
 
+        if let Err(e) = self.client.encryption().dehydrated_devices().export_pickle_key(self).await
+        {
+            warn!("Could not export the pickle key of the dehydrated device: {e:?}");
+        }
+
         Ok(())
     }
 }
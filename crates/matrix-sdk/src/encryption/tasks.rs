@@ -28,13 +28,13 @@ use tracing::{debug, trace, warn};
 
 use crate::{
     client::WeakClient,
-    encryption::backups::UploadState,
+    encryption::{backups::UploadState, BackupDownloadStrategy},
     executor::{spawn, JoinHandle},
     Client,
 };
 
 /// A cache of room keys we already downloaded.
-type DownloadCache = FailuresCache<RoomKeyInfo>;
+type DownloadCache = FailuresCache<DownloadTarget>;
 
 #[derive(Default)]
 pub(crate) struct ClientTasks {
@@ -44,6 +44,8 @@ pub(crate) struct ClientTasks {
     pub(crate) download_room_keys: Option<BackupDownloadTask>,
     #[cfg(feature = "e2e-encryption")]
     pub(crate) update_recovery_state_after_backup: Option<JoinHandle<()>>,
+    #[cfg(feature = "e2e-encryption")]
+    pub(crate) rotate_dehydrated_device: Option<JoinHandle<()>>,
     pub(crate) setup_e2ee: Option<JoinHandle<()>>,
 }
 
@@ -118,12 +120,26 @@ struct RoomKeyDownloadRequest {
 }
 
 impl RoomKeyDownloadRequest {
-    pub fn to_room_key_info(&self) -> RoomKeyInfo {
-        (self.room_id.clone(), self.megolm_session_id.clone())
+    /// What we should download from the backup for this request, depending on
+    /// the given download strategy.
+    fn download_target(&self, strategy: BackupDownloadStrategy) -> DownloadTarget {
+        if strategy == BackupDownloadStrategy::RoomAfterDecryptionFailure {
+            DownloadTarget::Room(self.room_id.clone())
+        } else {
+            DownloadTarget::RoomKey(self.room_id.clone(), self.megolm_session_id.clone())
+        }
     }
 }
 
-pub type RoomKeyInfo = (OwnedRoomId, String);
+/// What to download from the backup for a [`RoomKeyDownloadRequest`].
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+enum DownloadTarget {
+    /// A single room key, identified by its room and session ID.
+    RoomKey(OwnedRoomId, String),
+
+    /// All the room keys of a room.
+    Room(OwnedRoomId),
+}
 
 pub(crate) struct BackupDownloadTask {
     sender: mpsc::UnboundedSender<RoomKeyDownloadRequest>,
@@ -220,7 +236,7 @@ impl BackupDownloadTask {
 
         // Now take the lock, and check that we still want to do a download. If we do,
         // keep hold of a strong reference to the `Client`.
-        let client = {
+        let (client, download_target) = {
             let mut state = state.lock().await;
 
             let Some(client) = state.client.get() else {
@@ -229,8 +245,20 @@ impl BackupDownloadTask {
                 return;
             };
 
+            // Remember the room key that the event failed to be decrypted with, even if
+            // it's not downloaded by this task: it may be downloaded along with the other
+            // room keys of the room.
+            client
+                .inner
+                .e2ee
+                .backup_state
+                .add_utd_session_id(download_request.megolm_session_id.clone());
+
+            let strategy = client.inner.e2ee.encryption_settings.backup_download_strategy;
+            let download_target = download_request.download_target(strategy);
+
             // Check that we still want to do a download.
-            if !state.should_download(&client, &download_request).await {
+            if !state.should_download(&client, &download_request, &download_target).await {
                 // We decided against doing a download. Mark the job done for this event before
                 // dropping the lock.
                 state.active_tasks.remove(&download_request.event_id);
@@ -239,40 +267,43 @@ impl BackupDownloadTask {
 
             // Before we drop the lock, indicate to other tasks that may be considering this
             // room key, that we're going to go ahead and do a download.
-            state.downloaded_room_keys.insert(download_request.to_room_key_info());
+            state.downloaded_room_keys.insert(download_target.clone());
 
-            client
+            (client, download_target)
         };
 
         // Do the download without holding the lock.
-        let result = client
-            .encryption()
-            .backups()
-            .download_room_key(&download_request.room_id, &download_request.megolm_session_id)
-            .await;
+        let backups = client.encryption().backups();
+        let result = match &download_target {
+            DownloadTarget::RoomKey(room_id, session_id) => {
+                backups.download_room_key_impl(room_id, session_id, true).await
+            }
+            DownloadTarget::Room(room_id) => {
+                backups.download_room_keys_for_room_impl(room_id, true).await
+            }
+        };
 
         // Then take the lock again to update the state.
         {
             let mut state = state.lock().await;
-            let room_key_info = download_request.to_room_key_info();
 
             match result {
                 Ok(true) => {
                     // We successfully downloaded the room key. We can clear any record of previous
                     // backoffs from the failures cache, because we won't be needing them again.
-                    state.failures_cache.remove(std::iter::once(&room_key_info))
+                    state.failures_cache.remove(std::iter::once(&download_target))
                 }
                 Ok(false) => {
                     // We did not find a valid backup decryption key or backup version, we did not
                     // even attempt to download the room key.
-                    state.downloaded_room_keys.remove(std::iter::once(&room_key_info));
+                    state.downloaded_room_keys.remove(std::iter::once(&download_target));
                 }
                 Err(_) => {
                     // We were unable to download the room key. Update the failure cache so that we
                     // back off from more requests, and also remove the entry from the list of
                     // room keys that we are downloading.
-                    state.downloaded_room_keys.remove(std::iter::once(&room_key_info));
-                    state.failures_cache.insert(room_key_info);
+                    state.downloaded_room_keys.remove(std::iter::once(&download_target));
+                    state.failures_cache.insert(download_target);
                 }
             }
 
@@ -288,17 +319,18 @@ struct BackupDownloadTaskListenerState {
     client: WeakClient,
 
     /// A record of backup download attempts that have recently failed.
-    failures_cache: FailuresCache<RoomKeyInfo>,
+    failures_cache: FailuresCache<DownloadTarget>,
 
     /// Map from event ID to download task
     active_tasks: BTreeMap<OwnedEventId, JoinHandle<()>>,
 
-    /// A list of room keys that we have already downloaded, or are about to
-    /// download.
+    /// A list of room keys, or rooms, that we have already downloaded, or are
+    /// about to download.
     ///
     /// The idea here is that once we've (successfully) downloaded a room key
     /// from the backup, there's not much point trying again even if we get
-    /// another UTD event that uses the same room key.
+    /// another UTD event that uses the same room key, or that was sent in the
+    /// same room if we download all the room keys of the room.
     downloaded_room_keys: DownloadCache,
 }
 
@@ -325,8 +357,9 @@ impl BackupDownloadTaskListenerState {
     ///
     /// Checks if:
     ///  * we already have the key,
-    ///  * we have already downloaded this room key, or are about to do so, or
-    ///  * we've backed off from trying to download this room key.
+    ///  * we have already downloaded this room key, or the room keys of this
+    ///    room, or are about to do so, or
+    ///  * we've backed off from trying to download them.
     ///
     /// If any of the above are true, returns `false`. Otherwise, returns
     /// `true`.
@@ -334,6 +367,7 @@ impl BackupDownloadTaskListenerState {
         &self,
         client: &Client,
         download_request: &RoomKeyDownloadRequest,
+        download_target: &DownloadTarget,
     ) -> bool {
         // Check that the Client has an OlmMachine
         let machine_guard = client.olm_machine().await;
@@ -366,8 +400,7 @@ impl BackupDownloadTaskListenerState {
 
         // Check if we already downloaded this room key, or another task is in the
         // process of doing so.
-        let room_key_info = download_request.to_room_key_info();
-        if self.downloaded_room_keys.contains(&room_key_info) {
+        if self.downloaded_room_keys.contains(download_target) {
             debug!(
                 ?download_request,
                 "Not performing backup download because this room key has already been downloaded recently"
@@ -376,7 +409,7 @@ impl BackupDownloadTaskListenerState {
         };
 
         // Check if we're backing off from attempts to download this room key
-        if self.failures_cache.contains(&room_key_info) {
+        if self.failures_cache.contains(download_target) {
             debug!(
                 ?download_request,
                 "Not performing backup download because this room key failed to download recently"
@@ -450,7 +483,9 @@ mod test {
         {
             let state = state.lock().await;
             assert!(
-                !state.downloaded_room_keys.contains(&(room_id.to_owned(), session_id.to_owned())),
+                !state
+                    .downloaded_room_keys
+                    .contains(&DownloadTarget::RoomKey(room_id.to_owned(), session_id.to_owned())),
                 "Backups are not enabled, we should not mark any room keys as downloaded."
             )
         }
@@ -382,6 +382,11 @@ pub enum Error {
     /// An error happened during handling of a media subrequest.
     #[error(transparent)]
     Media(#[from] MediaError),
+
+    /// The search filter passed to [`Room::search()`](crate::Room::search)
+    /// is restricted to rooms that don't include the searched room.
+    #[error("the search filter excludes the searched room")]
+    SearchFilterExcludesRoom,
 }
 
 #[rustfmt::skip] // stop rustfmt breaking the `<code>` in docs across multiple lines
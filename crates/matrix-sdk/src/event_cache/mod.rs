@@ -69,8 +69,8 @@ mod pagination;
 mod room;
 
 pub mod paginator;
-pub use pagination::{PaginationToken, RoomPagination};
-pub use room::RoomEventCache;
+pub use pagination::{GapPaginationOutcome, PaginationToken, RoomPagination, TimelineGap};
+pub use room::{RoomEventCache, ThreadSummary};
 
 /// An error observed in the [`EventCache`].
 #[derive(thiserror::Error, Debug)]
@@ -93,6 +93,16 @@ pub enum EventCacheError {
     #[error("The given back-pagination token is unknown to the event cache.")]
     UnknownBackpaginationToken,
 
+    /// The given gap is unknown to the event cache, it may have been filled
+    /// or the timeline may have been cleared in the meantime.
+    #[error("The given gap is unknown to the event cache.")]
+    GapNotFound,
+
+    /// The last event before a gap is unknown, so the gap can't be paginated
+    /// forwards.
+    #[error("The last event before the gap is unknown.")]
+    UnknownGapStart,
+
     /// An error has been observed while back-paginating.
     #[error("Error observed while back-paginating: {0}")]
     BackpaginationError(#[from] PaginatorError),
@@ -797,6 +807,13 @@ pub enum RoomEventCacheUpdate {
         /// cache
         events: Vec<Raw<AnySyncEphemeralRoomEvent>>,
     },
+
+    /// The summaries of some threads have changed.
+    UpdateThreadSummaries {
+        /// The new thread summaries, mapped by the event ID of their thread
+        /// root.
+        summaries: BTreeMap<OwnedEventId, ThreadSummary>,
+    },
 }
 
 /// Indicate where events are coming from.
@@ -17,18 +17,19 @@
 use std::{sync::Arc, time::Duration};
 
 use eyeball::Subscriber;
-use matrix_sdk_base::timeout::timeout;
+use matrix_sdk_base::{deserialized_responses::TimelineEvent, timeout::timeout};
 use matrix_sdk_common::linked_chunk::ChunkContent;
+use ruma::{api::Direction, uint, OwnedEventId};
 use tracing::{debug, instrument, trace};
 
 use super::{
     deduplicator::DeduplicationOutcome,
-    paginator::{PaginationResult, PaginatorState},
+    paginator::{PaginationResult, Paginator, PaginatorState},
     room::{
         events::{Gap, RoomEvents},
         LoadMoreEventsBackwardsOutcome, RoomEventCacheInner,
     },
-    BackPaginationOutcome, EventsOrigin, Result, RoomEventCacheUpdate,
+    BackPaginationOutcome, EventCacheError, EventsOrigin, Result, RoomEventCacheUpdate,
 };
 
 /// An API object to run pagination queries on a [`super::RoomEventCache`].
@@ -112,6 +113,7 @@ impl RoomPagination {
             LoadMoreEventsBackwardsOutcome::Events {
                 events,
                 sync_timeline_events_diffs,
+                thread_summaries,
                 reached_start,
             } => {
                 if !sync_timeline_events_diffs.is_empty() {
@@ -121,6 +123,12 @@ impl RoomPagination {
                     });
                 }
 
+                if !thread_summaries.is_empty() {
+                    let _ = self.inner.sender.send(RoomEventCacheUpdate::UpdateThreadSummaries {
+                        summaries: thread_summaries,
+                    });
+                }
+
                 return Ok(Some(BackPaginationOutcome {
                     reached_start,
                     // This is a backwards pagination. `BackPaginationOutcome` expects events to
@@ -159,7 +167,10 @@ impl RoomPagination {
         // room's timeline has been cleared.
         let prev_gap_id = if let Some(token) = prev_token {
             let gap_id = state.events().chunk_identifier(|chunk| {
-                matches!(chunk.content(), ChunkContent::Gap(Gap { ref prev_token }) if *prev_token == token)
+                matches!(
+                    chunk.content(),
+                    ChunkContent::Gap(Gap { ref prev_token, .. }) if *prev_token == token
+                )
             });
 
             // We got a previous-batch token from the linked chunk *before* running the
@@ -176,7 +187,8 @@ impl RoomPagination {
         };
 
         // The new prev token from this pagination.
-        let new_gap = paginator.prev_batch_token().map(|prev_token| Gap { prev_token });
+        let new_gap =
+            paginator.prev_batch_token().map(|prev_token| Gap { prev_token, next_token: None });
 
         let (
             DeduplicationOutcome {
@@ -296,6 +308,8 @@ impl RoomPagination {
             .map_or(reached_start, |chunk| chunk.is_definitive_head())
         };
 
+        let thread_summaries = state.update_thread_summaries(events.iter(), false);
+
         let backpagination_outcome = BackPaginationOutcome { events, reached_start };
 
         if !sync_timeline_events_diffs.is_empty() {
@@ -305,9 +319,299 @@ impl RoomPagination {
             });
         }
 
+        if !thread_summaries.is_empty() {
+            let _ = self
+                .inner
+                .sender
+                .send(RoomEventCacheUpdate::UpdateThreadSummaries { summaries: thread_summaries });
+        }
+
         Ok(Some(backpagination_outcome))
     }
 
+    /// Get the gaps of the loaded part of the timeline, from the oldest to the
+    /// most recent.
+    ///
+    /// A gap is a range of events that are missing from the cache, e.g.
+    /// after a limited sync following a long offline period. The most recent
+    /// gap is the one filled by [`Self::run_backwards_once()`]; all the gaps
+    /// can be filled with [`Self::run_backwards_from_gap()`] or
+    /// [`Self::run_forwards_from_gap()`].
+    pub async fn gaps(&self) -> Vec<TimelineGap> {
+        let state = self.inner.state.read().await;
+
+        let mut gaps = Vec::new();
+        let mut previous_event_id = None;
+        // The gaps that are still waiting for an event after them.
+        let mut num_open_gaps = 0;
+
+        for chunk in state.events().chunks() {
+            match chunk.content() {
+                ChunkContent::Gap(gap) => {
+                    gaps.push(TimelineGap {
+                        prev_token: gap.prev_token.clone(),
+                        next_token: gap.next_token.clone(),
+                        previous_event_id: previous_event_id.clone(),
+                        next_event_id: None,
+                    });
+                    num_open_gaps += 1;
+                }
+
+                ChunkContent::Items(events) => {
+                    let Some(first_event_id) = events.iter().find_map(|event| event.event_id())
+                    else {
+                        continue;
+                    };
+
+                    let num_gaps = gaps.len();
+                    for gap in &mut gaps[num_gaps - num_open_gaps..] {
+                        gap.next_event_id = Some(first_event_id.clone());
+                    }
+                    num_open_gaps = 0;
+
+                    previous_event_id = events.iter().rev().find_map(|event| event.event_id());
+                }
+            }
+        }
+
+        gaps
+    }
+
+    /// Run a single back-pagination from the given gap, filling it from its
+    /// most recent end.
+    ///
+    /// Unlike [`Self::run_backwards_once()`], which only resolves the most
+    /// recent gap, this can fill any gap returned by [`Self::gaps()`]. The
+    /// gap is considered filled once the pagination reaches the last event
+    /// before the gap, or the start of the timeline.
+    #[instrument(skip(self))]
+    pub async fn run_backwards_from_gap(
+        &self,
+        gap: &TimelineGap,
+        batch_size: u16,
+    ) -> Result<GapPaginationOutcome> {
+        let paginator = Paginator::new(self.inner.weak_room.clone());
+        paginator.set_idle_state(PaginatorState::Idle, Some(gap.prev_token.clone()), None)?;
+
+        let PaginationResult { events, hit_end_of_timeline } =
+            paginator.paginate_backward(batch_size.into()).await?;
+
+        let new_prev_token = if hit_end_of_timeline { None } else { paginator.prev_batch_token() };
+
+        // Events are returned in reverse topological order.
+        let events = events.into_iter().rev().collect();
+
+        self.fill_gap(gap, events, Direction::Backward, new_prev_token).await
+    }
+
+    /// Run a single forward pagination from the given gap, filling it from its
+    /// oldest end.
+    ///
+    /// The pagination starts from the last known event before the gap, which
+    /// may be loaded from the storage if needs be. The gap is considered
+    /// filled once the pagination reaches the first event after the gap, or
+    /// the end of the timeline.
+    #[instrument(skip(self))]
+    pub async fn run_forwards_from_gap(
+        &self,
+        gap: &TimelineGap,
+        batch_size: u16,
+    ) -> Result<GapPaginationOutcome> {
+        let paginator = Paginator::new(self.inner.weak_room.clone());
+
+        if let Some(next_token) = &gap.next_token {
+            paginator.set_idle_state(PaginatorState::Idle, None, Some(next_token.clone()))?;
+        } else {
+            // Get a token to paginate forwards from the event right before the gap.
+            let previous_event_id = match &gap.previous_event_id {
+                Some(event_id) => event_id.clone(),
+                None => {
+                    let state = self.inner.state.read().await;
+
+                    let gap_id = state
+                        .events()
+                        .chunk_identifier(|chunk| gap.matches(chunk.content()))
+                        .ok_or(EventCacheError::GapNotFound)?;
+
+                    state
+                        .last_event_id_before(gap_id)
+                        .await?
+                        .ok_or(EventCacheError::UnknownGapStart)?
+                }
+            };
+
+            paginator.start_from(&previous_event_id, uint!(0)).await?;
+        }
+
+        let PaginationResult { events, hit_end_of_timeline } =
+            paginator.paginate_forward(batch_size.into()).await?;
+
+        let new_next_token = if hit_end_of_timeline { None } else { paginator.next_batch_token() };
+
+        self.fill_gap(gap, events, Direction::Forward, new_next_token).await
+    }
+
+    /// Insert the events of a pagination from a gap into the gap.
+    ///
+    /// `events` are in topological order, and `new_token` is the token to
+    /// continue paginating in the same direction, if any.
+    async fn fill_gap(
+        &self,
+        gap: &TimelineGap,
+        mut events: Vec<TimelineEvent>,
+        direction: Direction,
+        new_token: Option<String>,
+    ) -> Result<GapPaginationOutcome> {
+        let mut state = self.inner.state.write().await;
+
+        // Check that the gap still exists, the timeline may have been cleared in the
+        // meantime.
+        let (gap_id, next_token) = state
+            .events()
+            .chunks()
+            .find_map(|chunk| match chunk.content() {
+                ChunkContent::Gap(Gap { prev_token, next_token })
+                    if *prev_token == gap.prev_token =>
+                {
+                    Some((chunk.identifier(), next_token.clone()))
+                }
+                _ => None,
+            })
+            .ok_or(EventCacheError::GapNotFound)?;
+
+        // The event on the other side of the gap, reaching it means that the gap is
+        // filled.
+        let boundary_event_id = match direction {
+            Direction::Backward => state.last_event_id_before(gap_id).await?,
+            Direction::Forward => state.first_event_id_after(gap_id),
+        };
+
+        let boundary_position = boundary_event_id.and_then(|boundary_event_id| {
+            events.iter().position(|event| event.event_id().as_ref() == Some(&boundary_event_id))
+        });
+
+        // The events past the boundary are already on the other side of the gap.
+        match (boundary_position, direction) {
+            (Some(position), Direction::Backward) => {
+                events.drain(..=position);
+            }
+            (Some(position), Direction::Forward) => events.truncate(position),
+            (None, _) => {}
+        }
+
+        // Without a token, the start or the end of the timeline has been reached, so
+        // there's nothing left to fill either.
+        let is_filled = boundary_position.is_some() || new_token.is_none();
+
+        let (
+            DeduplicationOutcome {
+                all_events: mut events,
+                in_memory_duplicated_event_ids,
+                in_store_duplicated_event_ids,
+            },
+            all_duplicates,
+        ) = state.collect_valid_and_duplicated_events(events).await?;
+
+        let duplicated_event_ids = in_memory_duplicated_event_ids
+            .into_iter()
+            .chain(in_store_duplicated_event_ids)
+            .map(|(event_id, _position)| event_id)
+            .collect::<Vec<_>>();
+
+        if all_duplicates {
+            events.clear();
+        } else {
+            // Like during a back-pagination, the old events are kept and the new ones
+            // are ignored.
+            events.retain(|event| {
+                event.event_id().is_some_and(|event_id| !duplicated_event_ids.contains(&event_id))
+            });
+        }
+
+        let remaining_gap = (!is_filled).then(|| match direction {
+            Direction::Backward => TimelineGap {
+                prev_token: new_token.clone().expect("the gap isn't filled"),
+                next_token,
+                previous_event_id: gap.previous_event_id.clone(),
+                next_event_id: events
+                    .first()
+                    .and_then(|event| event.event_id())
+                    .or_else(|| gap.next_event_id.clone()),
+            },
+            Direction::Forward => TimelineGap {
+                prev_token: gap.prev_token.clone(),
+                next_token: new_token.clone(),
+                previous_event_id: events
+                    .last()
+                    .and_then(|event| event.event_id())
+                    .or_else(|| gap.previous_event_id.clone()),
+                next_event_id: gap.next_event_id.clone(),
+            },
+        });
+
+        let ((), sync_timeline_events_diffs) = state
+            .with_events_mut(|room_events| {
+                let next_position = room_events
+                    .replace_gap_at(events.clone(), gap_id)
+                    .expect("gap identifier is a valid gap chunk id we read previously");
+
+                // Put back what remains of the gap, before the new events if they have been
+                // back-paginated, after them otherwise.
+                if let Some(remaining_gap) = &remaining_gap {
+                    let position = match direction {
+                        Direction::Backward => next_position,
+                        Direction::Forward => {
+                            match events.last().and_then(|event| event.event_id()) {
+                                Some(last_event_id) => room_events
+                                    .events()
+                                    .skip_while(|(_, event)| {
+                                        event.event_id().as_ref() != Some(&last_event_id)
+                                    })
+                                    .nth(1)
+                                    .map(|(position, _)| position),
+                                None => next_position,
+                            }
+                        }
+                    };
+
+                    let new_gap = Gap {
+                        prev_token: remaining_gap.prev_token.clone(),
+                        next_token: remaining_gap.next_token.clone(),
+                    };
+
+                    match position {
+                        Some(position) => room_events
+                            .insert_gap_at(new_gap, position)
+                            .expect("position is a valid position we just read"),
+                        None => room_events.push_gap(new_gap),
+                    }
+                } else {
+                    trace!("the gap has been filled");
+                }
+
+                room_events.on_new_events(&self.inner.room_version, events.iter());
+            })
+            .await?;
+
+        let thread_summaries = state.update_thread_summaries(events.iter(), false);
+
+        if !sync_timeline_events_diffs.is_empty() {
+            let _ = self.inner.sender.send(RoomEventCacheUpdate::UpdateTimelineEvents {
+                diffs: sync_timeline_events_diffs,
+                origin: EventsOrigin::Pagination,
+            });
+        }
+
+        if !thread_summaries.is_empty() {
+            let _ = self
+                .inner
+                .sender
+                .send(RoomEventCacheUpdate::UpdateThreadSummaries { summaries: thread_summaries });
+        }
+
+        Ok(GapPaginationOutcome { events, remaining_gap })
+    }
+
     /// Get the latest pagination token, as stored in the room events linked
     /// list, or wait for it for the given amount of time.
     ///
@@ -411,6 +715,53 @@ pub enum PaginationToken {
     HitEnd,
 }
 
+/// A gap in the timeline of a room, i.e. a range of events that are missing
+/// from the event cache.
+///
+/// See [`RoomPagination::gaps()`].
+#[derive(Clone, Debug, PartialEq)]
+pub struct TimelineGap {
+    /// The token to back-paginate from the end of the gap, which also
+    /// identifies the gap in the linked chunk.
+    prev_token: String,
+
+    /// The token to paginate forwards from the start of the gap, if a forward
+    /// pagination from this gap has already happened.
+    next_token: Option<String>,
+
+    /// The ID of the last loaded event before the gap, if any.
+    ///
+    /// If it's `None`, the gap is at the start of the loaded events.
+    pub previous_event_id: Option<OwnedEventId>,
+
+    /// The ID of the first loaded event after the gap, if any.
+    pub next_event_id: Option<OwnedEventId>,
+}
+
+impl TimelineGap {
+    /// Whether this gap is between two loaded events.
+    pub fn is_interior(&self) -> bool {
+        self.previous_event_id.is_some() && self.next_event_id.is_some()
+    }
+
+    /// Whether the given chunk content is this gap.
+    fn matches(&self, content: &ChunkContent<TimelineEvent, Gap>) -> bool {
+        matches!(content, ChunkContent::Gap(Gap { prev_token, .. }) if *prev_token == self.prev_token)
+    }
+}
+
+/// The result of a pagination from a gap, with
+/// [`RoomPagination::run_backwards_from_gap()`] or
+/// [`RoomPagination::run_forwards_from_gap()`].
+#[derive(Debug)]
+pub struct GapPaginationOutcome {
+    /// The events that have been inserted in the gap, in topological order.
+    pub events: Vec<TimelineEvent>,
+
+    /// What remains of the gap, or `None` if it has been filled.
+    pub remaining_gap: Option<TimelineGap>,
+}
+
 impl From<Option<String>> for PaginationToken {
     fn from(token: Option<String>) -> Self {
         match token {
@@ -591,7 +942,8 @@ mod tests {
                     .write()
                     .await
                     .with_events_mut(|room_events| {
-                        room_events.push_gap(Gap { prev_token: expected_token.clone() });
+                        room_events
+                            .push_gap(Gap { prev_token: expected_token.clone(), next_token: None });
                         room_events.push_events([EventFactory::new()
                             .text_msg("yolo")
                             .sender(user_id!("@b:z.h"))
@@ -655,7 +1007,7 @@ mod tests {
                     .write()
                     .await
                     .with_events_mut(|events| {
-                        events.push_gap(Gap { prev_token: cloned_expected_token })
+                        events.push_gap(Gap { prev_token: cloned_expected_token, next_token: None })
                     })
                     .await
                     .unwrap();
@@ -709,10 +1061,10 @@ mod tests {
                     // This simulates a valid representation of a room: first group of gap+events
                     // were e.g. restored from the cache; second group of gap+events was received
                     // from a subsequent sync.
-                    events.push_gap(Gap { prev_token: old_token });
+                    events.push_gap(Gap { prev_token: old_token, next_token: None });
                     events.push_events([f.text_msg("oldest from cache").into()]);
 
-                    events.push_gap(Gap { prev_token: new_token.clone() });
+                    events.push_gap(Gap { prev_token: new_token.clone(), next_token: None });
                     events.push_events([f.text_msg("sync'd gappy timeline").into()]);
                 })
                 .await
@@ -243,6 +243,14 @@ impl<PR: PaginableRoom> Paginator<PR> {
         }
     }
 
+    /// Returns the current next batch token, as stored in this paginator.
+    pub(super) fn next_batch_token(&self) -> Option<String> {
+        match &self.tokens.lock().unwrap().next {
+            PaginationToken::HitEnd | PaginationToken::None => None,
+            PaginationToken::HasMore(token) => Some(token.clone()),
+        }
+    }
+
     /// Starts the pagination from the initial event, requesting `num_events`
     /// additional context events.
     ///
@@ -360,7 +360,7 @@ impl RoomEvents {
 /// Create a debug string for a [`ChunkContent`] for an event/gap pair.
 fn chunk_debug_string(content: &ChunkContent<Event, Gap>) -> String {
     match content {
-        ChunkContent::Gap(Gap { prev_token }) => {
+        ChunkContent::Gap(Gap { prev_token, .. }) => {
             format!("gap['{prev_token}']")
         }
         ChunkContent::Items(vec) => {
@@ -469,7 +469,7 @@ mod tests {
         let mut room_events = RoomEvents::new();
 
         room_events.push_events([event_0]);
-        room_events.push_gap(Gap { prev_token: "hello".to_owned() });
+        room_events.push_gap(Gap { prev_token: "hello".to_owned(), next_token: None });
         room_events.push_events([event_1]);
 
         assert_events_eq!(
@@ -542,7 +542,10 @@ mod tests {
             .unwrap();
 
         room_events
-            .insert_gap_at(Gap { prev_token: "hello".to_owned() }, position_of_event_1)
+            .insert_gap_at(
+                Gap { prev_token: "hello".to_owned(), next_token: None },
+                position_of_event_1,
+            )
             .unwrap();
 
         assert_events_eq!(
@@ -578,7 +581,7 @@ mod tests {
         let mut room_events = RoomEvents::new();
 
         room_events.push_events([event_0]);
-        room_events.push_gap(Gap { prev_token: "hello".to_owned() });
+        room_events.push_gap(Gap { prev_token: "hello".to_owned(), next_token: None });
 
         let chunk_identifier_of_gap = room_events
             .chunks()
@@ -618,9 +621,9 @@ mod tests {
         let mut room_events = RoomEvents::new();
 
         room_events.push_events([event_0, event_1]);
-        room_events.push_gap(Gap { prev_token: "middle".to_owned() });
+        room_events.push_gap(Gap { prev_token: "middle".to_owned(), next_token: None });
         room_events.push_events([event_2]);
-        room_events.push_gap(Gap { prev_token: "end".to_owned() });
+        room_events.push_gap(Gap { prev_token: "end".to_owned(), next_token: None });
 
         // Remove the first gap.
         let first_gap_id = room_events
@@ -653,7 +656,7 @@ mod tests {
         // Push some events.
         let mut room_events = RoomEvents::new();
         room_events.push_events([event_0, event_1]);
-        room_events.push_gap(Gap { prev_token: "hello".to_owned() });
+        room_events.push_gap(Gap { prev_token: "hello".to_owned(), next_token: None });
         room_events.push_events([event_2, event_3]);
 
         assert_events_eq!(
@@ -726,7 +729,7 @@ mod tests {
         // Push some events.
         let mut room_events = RoomEvents::new();
         room_events.push_events([event_0, event_1]);
-        room_events.push_gap(Gap { prev_token: "raclette".to_owned() });
+        room_events.push_gap(Gap { prev_token: "raclette".to_owned(), next_token: None });
         room_events.push_events([event_2]);
 
         // Read the updates as `VectorDiff`.
@@ -781,7 +784,7 @@ mod tests {
                 .into_event(),
             event_factory.text_msg("you").event_id(event_id!("$2")).into_event(),
         ]);
-        room_events.push_gap(Gap { prev_token: "raclette".to_owned() });
+        room_events.push_gap(Gap { prev_token: "raclette".to_owned(), next_token: None });
 
         let output = room_events.debug_string();
 
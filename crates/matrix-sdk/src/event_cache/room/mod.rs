@@ -50,6 +50,9 @@ use super::{
 use crate::{client::WeakClient, room::WeakRoom};
 
 pub(super) mod events;
+mod threads;
+
+pub use threads::ThreadSummary;
 
 /// A subset of an event cache, for a room.
 ///
@@ -220,6 +223,30 @@ impl RoomEventCache {
         }
     }
 
+    /// Get the summary of the thread starting at the given root event, if the
+    /// event cache knows about this thread.
+    pub async fn thread_summary(&self, root_event_id: &EventId) -> Option<ThreadSummary> {
+        self.inner.state.read().await.thread_summaries().get(root_event_id)
+    }
+
+    /// Get the summaries of all the threads the event cache knows about in
+    /// this room, mapped by the event ID of their thread root.
+    pub async fn thread_summaries(&self) -> BTreeMap<OwnedEventId, ThreadSummary> {
+        self.inner.state.read().await.thread_summaries().all()
+    }
+
+    /// Search the events of this room containing all the words of the given
+    /// query, from the most recent to the oldest, returning at most `limit`
+    /// events.
+    ///
+    /// This is a local search: it uses the full-text index of the event cache
+    /// store if there is one, or looks at the events loaded in memory
+    /// otherwise. As such, it also works in encrypted rooms, but only finds
+    /// the events that the event cache knows about.
+    pub async fn search(&self, query: &str, limit: usize) -> Result<Vec<TimelineEvent>> {
+        self.inner.state.read().await.search_events(query, limit).await
+    }
+
     /// Clear all the storage for this [`RoomEventCache`].
     ///
     /// This will get rid of all the events from the linked chunk and persisted
@@ -251,6 +278,8 @@ impl RoomEventCache {
     // cache. There is a discussion in https://github.com/matrix-org/matrix-rust-sdk/issues/3886.
     pub(crate) async fn save_event(&self, event: TimelineEvent) {
         if let Some(event_id) = event.event_id() {
+            self.update_thread_summaries(std::slice::from_ref(&event)).await;
+
             let mut cache = self.inner.all_events.write().await;
 
             cache.append_related_event(&event);
@@ -267,6 +296,10 @@ impl RoomEventCache {
     // there'll be no distinction between the linked chunk and the separate
     // cache. There is a discussion in https://github.com/matrix-org/matrix-rust-sdk/issues/3886.
     pub(crate) async fn save_events(&self, events: impl IntoIterator<Item = TimelineEvent>) {
+        let events = events.into_iter().collect::<Vec<_>>();
+
+        self.update_thread_summaries(&events).await;
+
         let mut cache = self.inner.all_events.write().await;
         for event in events {
             if let Some(event_id) = event.event_id() {
@@ -278,6 +311,18 @@ impl RoomEventCache {
         }
     }
 
+    /// Update the thread summaries with events saved out of the linked chunk,
+    /// and notify observers about the summaries that changed.
+    async fn update_thread_summaries(&self, events: &[TimelineEvent]) {
+        let summaries =
+            self.inner.state.write().await.update_thread_summaries(events.iter(), false);
+
+        if !summaries.is_empty() {
+            let _ =
+                self.inner.sender.send(RoomEventCacheUpdate::UpdateThreadSummaries { summaries });
+        }
+    }
+
     /// Return a nice debug string (a vector of lines) for the linked chunk of
     /// events for this room.
     pub async fn debug_string(&self) -> Vec<String> {
@@ -318,6 +363,10 @@ pub(super) struct RoomEventCacheInner {
     /// cache.
     pub paginator: Paginator<WeakRoom>,
 
+    /// The room, to run the paginations filling the gaps of the timeline, which
+    /// have their own [`Paginator`]s.
+    pub weak_room: WeakRoom,
+
     /// Sender to the auto-shrink channel.
     ///
     /// See doc comment around [`EventCache::auto_shrink_linked_chunk_task`] for
@@ -345,7 +394,8 @@ impl RoomEventCacheInner {
             all_events: all_events_cache,
             sender,
             pagination_batch_token_notifier: Default::default(),
-            paginator: Paginator::new(weak_room),
+            paginator: Paginator::new(weak_room.clone()),
+            weak_room,
             auto_shrink_sender,
         }
     }
@@ -536,9 +586,9 @@ impl RoomEventCacheInner {
         // pagination where the old event is kept and the new event is ignored.
         //
         // Let's remove the old events that are duplicated.
-        let sync_timeline_events_diffs = if all_duplicates {
+        let (sync_timeline_events_diffs, thread_summaries) = if all_duplicates {
             // No new events, thus no need to change the room events.
-            vec![]
+            (vec![], BTreeMap::new())
         } else {
             // Remove the old duplicated events.
             //
@@ -559,7 +609,8 @@ impl RoomEventCacheInner {
                     // time we sync'd).
                     if !all_duplicates {
                         if let Some(prev_token) = &prev_batch {
-                            room_events.push_gap(Gap { prev_token: prev_token.clone() });
+                            room_events
+                                .push_gap(Gap { prev_token: prev_token.clone(), next_token: None });
                         }
                     }
 
@@ -602,7 +653,9 @@ impl RoomEventCacheInner {
                 }
             }
 
-            sync_timeline_events_diffs
+            let thread_summaries = state.update_thread_summaries(events.iter(), true);
+
+            (sync_timeline_events_diffs, thread_summaries)
         };
 
         // Now that all events have been added, we can trigger the
@@ -620,6 +673,12 @@ impl RoomEventCacheInner {
                 });
             }
 
+            if !thread_summaries.is_empty() {
+                let _ = self.sender.send(RoomEventCacheUpdate::UpdateThreadSummaries {
+                    summaries: thread_summaries,
+                });
+            }
+
             if !ephemeral_events.is_empty() {
                 let _ = self
                     .sender
@@ -649,19 +708,29 @@ pub(super) enum LoadMoreEventsBackwardsOutcome {
     Events {
         events: Vec<TimelineEvent>,
         sync_timeline_events_diffs: Vec<VectorDiff<TimelineEvent>>,
+        thread_summaries: BTreeMap<OwnedEventId, ThreadSummary>,
         reached_start: bool,
     },
 }
 
 // Use a private module to hide `events` to this parent module.
 mod private {
-    use std::sync::{atomic::AtomicUsize, Arc};
+    use std::{
+        cmp::Reverse,
+        collections::BTreeMap,
+        sync::{atomic::AtomicUsize, Arc},
+    };
 
     use eyeball_im::VectorDiff;
     use matrix_sdk_base::{
         deserialized_responses::{TimelineEvent, TimelineEventKind},
-        event_cache::{store::EventCacheStoreLock, Event, Gap},
-        linked_chunk::{lazy_loader, ChunkContent, ChunkIdentifierGenerator, Position, Update},
+        event_cache::{
+            store::{search, EventCacheStoreLock},
+            Event, Gap,
+        },
+        linked_chunk::{
+            lazy_loader, ChunkContent, ChunkIdentifier, ChunkIdentifierGenerator, Position, Update,
+        },
     };
     use matrix_sdk_common::executor::spawn;
     use once_cell::sync::OnceCell;
@@ -674,7 +743,9 @@ mod private {
             EventCacheError,
         },
         events::RoomEvents,
-        sort_positions_descending, LoadMoreEventsBackwardsOutcome,
+        sort_positions_descending,
+        threads::{ThreadSummaries, ThreadSummary},
+        LoadMoreEventsBackwardsOutcome,
     };
 
     /// State for a single room's event cache.
@@ -697,6 +768,10 @@ mod private {
         /// The events deduplicator instance to help finding duplicates.
         deduplicator: Deduplicator,
 
+        /// The summaries of the threads in this room, aggregated from the
+        /// events we've seen so far.
+        thread_summaries: ThreadSummaries,
+
         /// Have we ever waited for a previous-batch-token to come from sync, in
         /// the context of pagination? We do this at most once per room,
         /// the first time we try to run backward pagination. We reset
@@ -756,11 +831,17 @@ mod private {
                 (RoomEvents::default(), Deduplicator::new_memory_based())
             };
 
+            let mut thread_summaries = ThreadSummaries::default();
+            // Iterate from the most recent event, so the most recent thread replies are
+            // considered the latest ones.
+            thread_summaries.handle_events(events.revents().map(|(_pos, event)| event), false);
+
             Ok(Self {
                 room: room_id,
                 store,
                 events,
                 deduplicator,
+                thread_summaries,
                 waited_for_initial_prev_token: false,
                 listener_count: Default::default(),
             })
@@ -888,11 +969,18 @@ mod private {
 
             Ok(match events {
                 None => LoadMoreEventsBackwardsOutcome::Gap,
-                Some((events, reached_start)) => LoadMoreEventsBackwardsOutcome::Events {
-                    events,
-                    sync_timeline_events_diffs: updates_as_vector_diffs,
-                    reached_start,
-                },
+                Some((events, reached_start)) => {
+                    // Iterate from the most recent event, so the most recent thread replies are
+                    // considered the latest ones.
+                    let thread_summaries = self.update_thread_summaries(events.iter().rev(), false);
+
+                    LoadMoreEventsBackwardsOutcome::Events {
+                        events,
+                        sync_timeline_events_diffs: updates_as_vector_diffs,
+                        thread_summaries,
+                        reached_start,
+                    }
+                }
             })
         }
 
@@ -1123,6 +1211,7 @@ mod private {
             self.events.reset();
             self.propagate_changes().await?;
             self.waited_for_initial_prev_token = false;
+            self.thread_summaries.clear();
 
             Ok(self.events.updates_as_vector_diffs())
         }
@@ -1132,6 +1221,25 @@ mod private {
             &self.events
         }
 
+        /// Returns a read-only reference to the thread summaries.
+        pub fn thread_summaries(&self) -> &ThreadSummaries {
+            &self.thread_summaries
+        }
+
+        /// Update the thread summaries with new events, that have been added to
+        /// the room events.
+        ///
+        /// Returns the thread summaries that changed, so the caller may
+        /// propagate them via a `RoomEventCacheUpdate`.
+        #[must_use = "Thread summaries must probably be propagated via `RoomEventCacheUpdate`"]
+        pub fn update_thread_summaries<'a>(
+            &mut self,
+            events: impl Iterator<Item = &'a Event>,
+            is_live: bool,
+        ) -> BTreeMap<OwnedEventId, ThreadSummary> {
+            self.thread_summaries.handle_events(events, is_live)
+        }
+
         /// Find a single event in this room.
         ///
         /// It starts by looking into loaded events in `RoomEvents` before
@@ -1160,6 +1268,122 @@ mod private {
             Ok(store.find_event(room_id, event_id).await?)
         }
 
+        /// Find the ID of the first event after the given chunk.
+        ///
+        /// Returns `None` if there's a gap after the chunk, or if it's the end
+        /// of the timeline. The chunks after a loaded chunk are always loaded,
+        /// so there's no need to look into the storage.
+        pub fn first_event_id_after(
+            &self,
+            chunk_identifier: ChunkIdentifier,
+        ) -> Option<OwnedEventId> {
+            let chunks = self
+                .events
+                .chunks()
+                .skip_while(|chunk| chunk.identifier() != chunk_identifier)
+                .skip(1);
+
+            for chunk in chunks {
+                match chunk.content() {
+                    ChunkContent::Gap(_) => return None,
+                    ChunkContent::Items(events) => {
+                        if let Some(event_id) = events.iter().find_map(|event| event.event_id()) {
+                            return Some(event_id);
+                        }
+                    }
+                }
+            }
+
+            None
+        }
+
+        /// Find the ID of the last event before the given chunk.
+        ///
+        /// It looks into the loaded chunks first, and into the storage if
+        /// there are no loaded events before the chunk. Returns `None` if
+        /// there's a gap before the chunk, or if it's the start of the
+        /// timeline.
+        pub async fn last_event_id_before(
+            &self,
+            chunk_identifier: ChunkIdentifier,
+        ) -> Result<Option<OwnedEventId>, EventCacheError> {
+            let chunks = self
+                .events
+                .rchunks()
+                .skip_while(|chunk| chunk.identifier() != chunk_identifier)
+                .skip(1);
+
+            for chunk in chunks {
+                match chunk.content() {
+                    ChunkContent::Gap(_) => return Ok(None),
+                    ChunkContent::Items(events) => {
+                        if let Some(event_id) =
+                            events.iter().rev().find_map(|event| event.event_id())
+                        {
+                            return Ok(Some(event_id));
+                        }
+                    }
+                }
+            }
+
+            let Some(store) = self.store.get() else {
+                // No store, there's nothing before the loaded chunks.
+                return Ok(None);
+            };
+
+            let store = store.lock().await?;
+
+            // All the loaded chunks before the given one are empty, so look into the
+            // chunks that haven't been loaded yet.
+            let mut chunk_identifier =
+                self.events.chunks().next().expect("a linked chunk is never empty").identifier();
+
+            while let Some(chunk) = store.load_previous_chunk(&self.room, chunk_identifier).await? {
+                match chunk.content {
+                    ChunkContent::Gap(_) => return Ok(None),
+                    ChunkContent::Items(events) => {
+                        if let Some(event_id) =
+                            events.iter().rev().find_map(|event| event.event_id())
+                        {
+                            return Ok(Some(event_id));
+                        }
+                    }
+                }
+
+                chunk_identifier = chunk.identifier;
+            }
+
+            Ok(None)
+        }
+
+        /// Search the events of this room matching the given query.
+        ///
+        /// It uses the full-text index of the storage if it is enabled, or
+        /// looks into the loaded events in `RoomEvents` otherwise.
+        pub async fn search_events(
+            &self,
+            query: &str,
+            limit: usize,
+        ) -> Result<Vec<TimelineEvent>, EventCacheError> {
+            let Some(store) = self.store.get() else {
+                let mut events = self
+                    .events()
+                    .events()
+                    .filter(|(_, event)| search::event_matches_query(event, query))
+                    .map(|(_, event)| event.clone())
+                    .collect::<Vec<_>>();
+
+                events.sort_by_key(|event| Reverse(search::event_timestamp(event)));
+                events.truncate(limit);
+
+                return Ok(events);
+            };
+
+            let store = store.lock().await?;
+
+            Ok(store.search_events(self.room.as_ref(), query, limit).await?)
+        }
+
         /// Gives a temporary mutable handle to the underlying in-memory events,
         /// and will propagate changes to the storage once done.
         ///
@@ -1516,6 +1740,65 @@ mod tests {
         assert!(chunks.next().is_none());
     }
 
+    #[cfg(not(target_arch = "wasm32"))] // This uses the cross-process lock, so needs time support.
+    #[async_test]
+    async fn test_search() {
+        let room_id = room_id!("!galette:saucisse.bzh");
+        let f = EventFactory::new().room(room_id).sender(user_id!("@ben:saucisse.bzh"));
+
+        for enable_storage in [false, true] {
+            let client = MockClientBuilder::new("http://localhost".to_owned())
+                .store_config(
+                    StoreConfig::new("hodlor".to_owned())
+                        .event_cache_store(Arc::new(MemoryStore::new())),
+                )
+                .build()
+                .await;
+
+            let event_cache = client.event_cache();
+            event_cache.subscribe().unwrap();
+            if enable_storage {
+                event_cache.enable_storage().unwrap();
+            }
+
+            client.base_client().get_or_create_room(room_id, matrix_sdk_base::RoomState::Joined);
+            let room = client.get_room(room_id).unwrap();
+
+            let (room_event_cache, _drop_handles) = room.event_cache().await.unwrap();
+
+            let timeline = Timeline {
+                limited: false,
+                prev_batch: None,
+                events: vec![
+                    f.text_msg("galette saucisse").event_id(event_id!("$1")).into_event(),
+                    f.text_msg("crêpe au beurre").event_id(event_id!("$2")).into_event(),
+                    f.text_msg("une galette complète").event_id(event_id!("$3")).into_event(),
+                ],
+            };
+
+            room_event_cache
+                .inner
+                .handle_joined_room_update(
+                    true,
+                    JoinedRoomUpdate { timeline, ..Default::default() },
+                )
+                .await
+                .unwrap();
+
+            let results = room_event_cache.search("Galette", 10).await.unwrap();
+            assert_eq!(
+                results.iter().map(|event| event.event_id().unwrap()).collect::<Vec<_>>(),
+                [event_id!("$3"), event_id!("$1")]
+            );
+
+            let results = room_event_cache.search("galette", 1).await.unwrap();
+            assert_eq!(results.len(), 1);
+            assert_eq!(results[0].event_id().as_deref(), Some(event_id!("$3")));
+
+            assert!(room_event_cache.search("kouign-amann", 10).await.unwrap().is_empty());
+        }
+    }
+
     #[cfg(not(target_arch = "wasm32"))] // This uses the cross-process lock, so needs time support.
     #[async_test]
     async fn test_write_to_storage_strips_bundled_relations() {
@@ -1635,7 +1918,7 @@ mod tests {
                         // Chunk IDs aren't supposed to be ordered, so use a random value here.
                         new: ChunkIdentifier::new(42),
                         next: None,
-                        gap: Gap { prev_token: "comté".to_owned() },
+                        gap: Gap { prev_token: "comté".to_owned(), next_token: None },
                     },
                     // Another items chunk, non-empty this time.
                     Update::NewItemsChunk {
@@ -1777,7 +2060,7 @@ mod tests {
                         // Chunk IDs aren't supposed to be ordered, so use a random value here.
                         new: ChunkIdentifier::new(42),
                         next: None,
-                        gap: Gap { prev_token: "cheddar".to_owned() },
+                        gap: Gap { prev_token: "cheddar".to_owned(), next_token: None },
                     },
                     // Another items chunk, non-empty this time.
                     Update::NewItemsChunk {
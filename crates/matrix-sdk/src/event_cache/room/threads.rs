@@ -0,0 +1,348 @@
+// Copyright 2025 The Matrix.org Foundation C.I.C.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Aggregation of thread summaries, for all the threads known to a room's event
+//! cache.
+
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+
+use matrix_sdk_base::event_cache::Event;
+use ruma::{events::relation::RelationType, EventId, OwnedEventId, OwnedUserId, UInt};
+use serde::Deserialize;
+
+/// A summary of a thread, computed from the events known to the event cache.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ThreadSummary {
+    /// The number of replies in the thread.
+    ///
+    /// This is the maximum of the count reported by the server (as part of the
+    /// thread root's bundled relations), updated with the replies received
+    /// live since then, and the number of replies known locally.
+    pub num_replies: u32,
+
+    /// The event ID of the latest reply in the thread, if known.
+    pub latest_reply: Option<OwnedEventId>,
+
+    /// The users who replied in the thread, as far as we know.
+    pub participants: BTreeSet<OwnedUserId>,
+}
+
+/// The state of the aggregation of a single thread.
+#[derive(Debug, Default)]
+struct ThreadAggregation {
+    /// The number of replies reported by the server in a bundled thread
+    /// summary, incremented by the replies received live since then.
+    server_count: u32,
+
+    /// The IDs of the replies to this thread we know about.
+    known_replies: BTreeSet<OwnedEventId>,
+
+    /// The latest reply in the thread.
+    latest_reply: Option<OwnedEventId>,
+
+    /// The senders of the replies we know about.
+    participants: BTreeSet<OwnedUserId>,
+}
+
+impl ThreadAggregation {
+    /// Take into account a new reply in this thread.
+    ///
+    /// Returns whether the aggregation has changed.
+    fn add_reply(&mut self, reply_id: OwnedEventId, sender: OwnedUserId, is_live: bool) -> bool {
+        if !self.known_replies.insert(reply_id.clone()) {
+            // We already know about this reply.
+            return false;
+        }
+
+        if is_live {
+            // A live reply is more recent than any summary the server may have sent
+            // us before.
+            self.server_count = self.server_count.saturating_add(1);
+            self.latest_reply = Some(reply_id);
+        } else if self.latest_reply.is_none() {
+            self.latest_reply = Some(reply_id);
+        }
+
+        self.participants.insert(sender);
+
+        true
+    }
+
+    /// Take into account a thread summary bundled by the server into the
+    /// thread root.
+    fn add_bundled_summary(&mut self, summary: BundledThreadProbe) {
+        // The server count may be outdated, if we've received live replies since it's
+        // been computed.
+        let count = u32::try_from(summary.count).unwrap_or(u32::MAX);
+        self.server_count = self.server_count.max(count);
+
+        if let Some(latest_event) = summary.latest_event {
+            if self.latest_reply.is_none() {
+                self.latest_reply = latest_event.event_id;
+            }
+
+            if let Some(sender) = latest_event.sender {
+                self.participants.insert(sender);
+            }
+        }
+    }
+
+    fn summary(&self) -> ThreadSummary {
+        let num_replies =
+            u32::try_from(self.known_replies.len()).unwrap_or(u32::MAX).max(self.server_count);
+
+        ThreadSummary {
+            num_replies,
+            latest_reply: self.latest_reply.clone(),
+            participants: self.participants.clone(),
+        }
+    }
+}
+
+/// The thread summaries for all the threads known to a room's event cache.
+#[derive(Debug, Default)]
+pub(super) struct ThreadSummaries {
+    threads: HashMap<OwnedEventId, ThreadAggregation>,
+}
+
+impl ThreadSummaries {
+    /// Take into account new events for the thread summaries.
+    ///
+    /// `is_live` indicates whether those events have been received from sync,
+    /// in which case they're considered more recent than any event we know
+    /// about.
+    ///
+    /// Returns the summaries of the threads that have been affected by those
+    /// events, if any.
+    pub fn handle_events<'a>(
+        &mut self,
+        events: impl Iterator<Item = &'a Event>,
+        is_live: bool,
+    ) -> BTreeMap<OwnedEventId, ThreadSummary> {
+        let mut updated_roots = BTreeSet::new();
+
+        for event in events {
+            let Ok(probe) = event.raw().deserialize_as::<ThreadEventProbe>() else {
+                continue;
+            };
+
+            let Some(event_id) = probe.event_id else {
+                continue;
+            };
+
+            if let Some(bundled) = probe.unsigned.relations.and_then(|relations| relations.thread) {
+                // This is a thread root with a server-side summary.
+                let aggregation = self.threads.entry(event_id.clone()).or_default();
+                aggregation.add_bundled_summary(bundled);
+                updated_roots.insert(event_id);
+                continue;
+            }
+
+            if let Some(RelatesToProbe {
+                rel_type: Some(RelationType::Thread),
+                event_id: Some(root),
+            }) = probe.content.relates_to
+            {
+                let aggregation = self.threads.entry(root.clone()).or_default();
+                if let Some(sender) = probe.sender {
+                    if aggregation.add_reply(event_id, sender, is_live) {
+                        updated_roots.insert(root);
+                    }
+                }
+                continue;
+            }
+
+            if self.threads.contains_key(&event_id) {
+                // This is the root of a thread for which we knew some replies; observers
+                // may not have seen the summary yet.
+                updated_roots.insert(event_id);
+            }
+        }
+
+        updated_roots
+            .into_iter()
+            .filter_map(|root| {
+                let summary = self.threads.get(&root)?.summary();
+                Some((root, summary))
+            })
+            .collect()
+    }
+
+    /// Get the summary of the thread starting at the given root, if any.
+    pub fn get(&self, root_event_id: &EventId) -> Option<ThreadSummary> {
+        self.threads.get(root_event_id).map(ThreadAggregation::summary)
+    }
+
+    /// Get the summaries of all the threads known to this room.
+    pub fn all(&self) -> BTreeMap<OwnedEventId, ThreadSummary> {
+        self.threads
+            .iter()
+            .map(|(root, aggregation)| (root.clone(), aggregation.summary()))
+            .collect()
+    }
+
+    /// Forget about all the thread summaries.
+    pub fn clear(&mut self) {
+        self.threads.clear();
+    }
+}
+
+/// Minimal representation of an event, to find out how it's related to a
+/// thread.
+#[derive(Deserialize)]
+struct ThreadEventProbe {
+    event_id: Option<OwnedEventId>,
+    sender: Option<OwnedUserId>,
+    #[serde(default)]
+    content: ContentProbe,
+    #[serde(default)]
+    unsigned: UnsignedProbe,
+}
+
+#[derive(Default, Deserialize)]
+struct ContentProbe {
+    #[serde(rename = "m.relates_to")]
+    relates_to: Option<RelatesToProbe>,
+}
+
+#[derive(Deserialize)]
+struct RelatesToProbe {
+    rel_type: Option<RelationType>,
+    event_id: Option<OwnedEventId>,
+}
+
+#[derive(Default, Deserialize)]
+struct UnsignedProbe {
+    #[serde(rename = "m.relations")]
+    relations: Option<BundledRelationsProbe>,
+}
+
+#[derive(Deserialize)]
+struct BundledRelationsProbe {
+    #[serde(rename = "m.thread")]
+    thread: Option<BundledThreadProbe>,
+}
+
+#[derive(Deserialize)]
+struct BundledThreadProbe {
+    latest_event: Option<LatestEventProbe>,
+    count: UInt,
+}
+
+#[derive(Deserialize)]
+struct LatestEventProbe {
+    event_id: Option<OwnedEventId>,
+    sender: Option<OwnedUserId>,
+}
+
+#[cfg(test)]
+mod tests {
+    use matrix_sdk_test::{event_factory::EventFactory, ALICE, BOB};
+    use ruma::{
+        event_id,
+        events::{relation::BundledThread, BundledMessageLikeRelations},
+        owned_event_id, room_id, uint,
+    };
+
+    use super::ThreadSummaries;
+
+    #[test]
+    fn test_thread_summary_from_replies() {
+        let f = EventFactory::new().room(room_id!("!r:b.c"));
+        let root = event_id!("$root");
+
+        let mut summaries = ThreadSummaries::default();
+
+        let events = vec![
+            f.text_msg("root").sender(*ALICE).event_id(root).into_event(),
+            f.text_msg("hi")
+                .sender(*BOB)
+                .in_thread(root, root)
+                .event_id(event_id!("$1"))
+                .into_event(),
+            f.text_msg("unrelated").sender(*BOB).event_id(event_id!("$2")).into_event(),
+        ];
+
+        let updated = summaries.handle_events(events.iter(), true);
+        assert_eq!(updated.len(), 1);
+
+        let summary = &updated[root];
+        assert_eq!(summary.num_replies, 1);
+        assert_eq!(summary.latest_reply, Some(owned_event_id!("$1")));
+        assert_eq!(summary.participants.len(), 1);
+        assert!(summary.participants.contains(*BOB));
+
+        // Seeing the same reply again doesn't change anything.
+        let updated = summaries.handle_events(events[1..2].iter(), true);
+        assert!(updated.is_empty());
+
+        // A new live reply is counted, and becomes the latest reply.
+        let reply =
+            f.text_msg("hello").sender(*ALICE).in_thread(root, root).event_id(event_id!("$3"));
+        let updated = summaries.handle_events([reply.into_event()].iter(), true);
+
+        let summary = &updated[root];
+        assert_eq!(summary.num_replies, 2);
+        assert_eq!(summary.latest_reply, Some(owned_event_id!("$3")));
+        assert_eq!(summary.participants.len(), 2);
+
+        // An older reply, e.g. from a back-pagination, is counted but doesn't become
+        // the latest reply.
+        let reply = f.text_msg("old").sender(*BOB).in_thread(root, root).event_id(event_id!("$0"));
+        let updated = summaries.handle_events([reply.into_event()].iter(), false);
+
+        let summary = &updated[root];
+        assert_eq!(summary.num_replies, 3);
+        assert_eq!(summary.latest_reply, Some(owned_event_id!("$3")));
+    }
+
+    #[test]
+    fn test_thread_summary_from_bundled_relations() {
+        let f = EventFactory::new().room(room_id!("!r:b.c"));
+        let root = event_id!("$root");
+
+        let mut summaries = ThreadSummaries::default();
+
+        let latest =
+            f.text_msg("latest").sender(*BOB).in_thread(root, root).event_id(event_id!("$latest"));
+
+        let mut relations = BundledMessageLikeRelations::new();
+        relations.thread =
+            Some(Box::new(BundledThread::new(latest.into_raw_sync(), uint!(7), false)));
+
+        let root_event = f
+            .text_msg("root")
+            .sender(*ALICE)
+            .event_id(root)
+            .bundled_relations(relations)
+            .into_event();
+
+        let updated = summaries.handle_events([root_event].iter(), false);
+
+        let summary = &updated[root];
+        assert_eq!(summary.num_replies, 7);
+        assert_eq!(summary.latest_reply, Some(owned_event_id!("$latest")));
+        assert!(summary.participants.contains(*BOB));
+
+        // A live reply increments the server count.
+        let reply = f.text_msg("hi").sender(*ALICE).in_thread(root, root).event_id(event_id!("$8"));
+        let updated = summaries.handle_events([reply.into_event()].iter(), true);
+
+        let summary = &updated[root];
+        assert_eq!(summary.num_replies, 8);
+        assert_eq!(summary.latest_reply, Some(owned_event_id!("$8")));
+
+        assert_eq!(summaries.get(root).as_ref(), Some(summary));
+    }
+}
@@ -23,9 +23,11 @@ use backoff::{future::retry, Error as RetryError, ExponentialBackoff};
 use bytes::Bytes;
 use bytesize::ByteSize;
 use eyeball::SharedObservable;
-use http::header::CONTENT_LENGTH;
+use http::{header::CONTENT_LENGTH, HeaderMap};
 use reqwest::{tls, Certificate};
-use ruma::api::{error::FromHttpResponseError, IncomingResponse, OutgoingRequest};
+use ruma::api::{
+    error::FromHttpResponseError, EndpointError, IncomingResponse, MatrixVersion, OutgoingRequest,
+};
 use tracing::{debug, info, warn};
 
 use super::{response_to_http_response, HttpClient, TransmissionProgress, DEFAULT_REQUEST_TIMEOUT};
@@ -60,31 +62,7 @@ impl HttpClient {
                     false
                 };
 
-                // Turn errors into permanent errors when the retry limit is reached.
-                let error_type = |err: HttpError| {
-                    if stop {
-                        RetryError::Permanent(err)
-                    } else {
-                        let has_retry_limit = config.retry_limit.is_some();
-                        match err.retry_kind() {
-                            RetryKind::Transient { retry_after } => {
-                                RetryError::Transient { err, retry_after }
-                            }
-                            RetryKind::Permanent => RetryError::Permanent(err),
-                            RetryKind::NetworkFailure => {
-                                // If we ran into a network failure, only retry if there's some
-                                // retry limit associated to this request's configuration;
-                                // otherwise, we would end up running an infinite loop of network
-                                // requests in offline mode.
-                                if has_retry_limit {
-                                    RetryError::Transient { err, retry_after: None }
-                                } else {
-                                    RetryError::Permanent(err)
-                                }
-                            }
-                        }
-                    }
-                };
+                let error_type = |err: HttpError| retry_error(err, &config, stop);
 
                 let response = send_request(&self.inner, &request, config.timeout, send_progress)
                     .await
@@ -118,6 +96,149 @@ impl HttpClient {
     }
 }
 
+impl HttpClient {
+    /// Send the given request, and return the response as soon as its headers
+    /// are received, so its body can be streamed.
+    ///
+    /// Like with [`HttpClient::send()`], the request is retried according to
+    /// the request config, and error responses are turned into errors of the
+    /// endpoint.
+    ///
+    /// The given headers are added to the request.
+    #[allow(clippy::too_many_arguments)]
+    pub(crate) async fn send_streaming<R>(
+        &self,
+        request: R,
+        config: Option<RequestConfig>,
+        homeserver: String,
+        access_token: Option<&str>,
+        server_versions: &[MatrixVersion],
+        headers: HeaderMap,
+    ) -> Result<reqwest::Response, HttpError>
+    where
+        R: OutgoingRequest + Debug,
+        HttpError: From<FromHttpResponseError<R::EndpointError>>,
+    {
+        let config = config.unwrap_or(self.request_config);
+
+        let mut request = self
+            .serialize_request(request, config, homeserver, access_token, server_versions)
+            .map_err(HttpError::IntoHttp)?;
+        request.headers_mut().extend(headers);
+
+        // The permit is only held until the headers of the response are received.
+        let _handle = self.concurrent_request_semaphore.acquire().await;
+
+        let backoff =
+            ExponentialBackoff { max_elapsed_time: config.retry_timeout, ..Default::default() };
+        let retry_count = AtomicU64::new(1);
+
+        let send_request = || async {
+            debug!(num_attempt = retry_count.load(Ordering::SeqCst), "Sending streaming request");
+
+            let stop = if let Some(retry_limit) = config.retry_limit {
+                retry_count.fetch_add(1, Ordering::Relaxed) >= retry_limit
+            } else {
+                false
+            };
+
+            let error_type = |err: HttpError| retry_error(err, &config, stop);
+
+            let mut reqwest_request = reqwest::Request::try_from(clone_request(&request))
+                .map_err(|err| error_type(err.into()))?;
+            *reqwest_request.timeout_mut() = Some(config.timeout);
+
+            let response =
+                self.inner.execute(reqwest_request).await.map_err(|err| error_type(err.into()))?;
+
+            let status_code = response.status();
+            if !status_code.is_client_error() && !status_code.is_server_error() {
+                return Ok(response);
+            }
+
+            let response =
+                response_to_http_response(response).await.map_err(|err| error_type(err.into()))?;
+            let error =
+                FromHttpResponseError::Server(R::EndpointError::from_http_response(response));
+
+            Err(error_type(HttpError::from(error)))
+        };
+
+        retry::<_, HttpError, _, _, _>(backoff, send_request).await
+    }
+}
+
+impl HttpClient {
+    /// Send the given request with the given streamed body, in place of the
+    /// serialized body of the request.
+    ///
+    /// The request is sent only once, because a streamed body can't be
+    /// replayed. The `Content-Length` header is set to `content_length`.
+    #[allow(clippy::too_many_arguments)]
+    pub(crate) async fn send_with_body<R>(
+        &self,
+        request: R,
+        body: reqwest::Body,
+        content_length: u64,
+        config: Option<RequestConfig>,
+        homeserver: String,
+        access_token: Option<&str>,
+        server_versions: &[MatrixVersion],
+    ) -> Result<R::IncomingResponse, HttpError>
+    where
+        R: OutgoingRequest + Debug,
+        HttpError: From<FromHttpResponseError<R::EndpointError>>,
+    {
+        let config = config.unwrap_or(self.request_config);
+
+        let request = self
+            .serialize_request(request, config, homeserver, access_token, server_versions)
+            .map_err(HttpError::IntoHttp)?;
+
+        let _handle = self.concurrent_request_semaphore.acquire().await;
+
+        debug!("Sending request with a streamed body");
+
+        let mut request = reqwest::Request::try_from(request.map(|_| body))?;
+        request.headers_mut().insert(CONTENT_LENGTH, content_length.into());
+        *request.timeout_mut() = Some(config.timeout);
+
+        let response = self.inner.execute(request).await?;
+        let response = response_to_http_response(response).await?;
+
+        Ok(R::IncomingResponse::try_from_http_response(response)?)
+    }
+}
+
+/// Turn the given error into an error that can be retried or not, according to
+/// the request config.
+///
+/// `stop` is `true` if the retry limit is reached, in which case the error is
+/// always permanent.
+fn retry_error(err: HttpError, config: &RequestConfig, stop: bool) -> RetryError<HttpError> {
+    // Turn errors into permanent errors when the retry limit is reached.
+    if stop {
+        return RetryError::Permanent(err);
+    }
+
+    let has_retry_limit = config.retry_limit.is_some();
+    match err.retry_kind() {
+        RetryKind::Transient { retry_after } => RetryError::Transient { err, retry_after },
+        RetryKind::Permanent => RetryError::Permanent(err),
+        RetryKind::NetworkFailure => {
+            // If we ran into a network failure, only retry if there's some
+            // retry limit associated to this request's configuration;
+            // otherwise, we would end up running an infinite loop of network
+            // requests in offline mode.
+            if has_retry_limit {
+                RetryError::Transient { err, retry_after: None }
+            } else {
+                RetryError::Permanent(err)
+            }
+        }
+    }
+}
+
 #[cfg(not(target_arch = "wasm32"))]
 #[derive(Clone, Debug)]
 pub(crate) struct HttpSettings {
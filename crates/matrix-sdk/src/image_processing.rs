@@ -0,0 +1,403 @@
+// Copyright 2025 The Matrix.org Foundation C.I.C.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Processing of the images sent as attachments, to compute their metadata,
+//! generate their thumbnail and downsize them.
+
+use std::io::Cursor;
+#[cfg(not(target_arch = "wasm32"))]
+use std::sync::Arc;
+
+use image::{
+    imageops::FilterType, DynamicImage, ImageDecoder, ImageError, ImageFormat, ImageReader,
+};
+use mime::Mime;
+use ruma::UInt;
+use tracing::{debug, instrument, warn};
+
+use crate::attachment::{AttachmentInfo, BaseImageInfo, Thumbnail};
+
+/// The number of components of the blurhashes, horizontally and vertically.
+const BLURHASH_COMPONENTS: (u32, u32) = (4, 3);
+
+/// The maximum dimensions of the image used to compute a blurhash.
+///
+/// A blurhash only keeps the low frequencies of an image, so there's no need to
+/// compute it from the full image, which would be much slower.
+const BLURHASH_IMAGE_SIZE: u32 = 64;
+
+/// Settings to process the images sent as attachments, see
+/// [`AttachmentConfig::process_image()`].
+///
+/// [`AttachmentConfig::process_image()`]: crate::attachment::AttachmentConfig::process_image
+#[derive(Debug, Clone)]
+pub struct ImageProcessingConfig {
+    /// The maximum width and height of the thumbnail to generate.
+    ///
+    /// A thumbnail is only generated if the image is bigger than these
+    /// dimensions, and if no thumbnail was provided for the attachment. If
+    /// this is `None`, no thumbnail is generated.
+    ///
+    /// Defaults to 800x600.
+    pub thumbnail_size: Option<(u32, u32)>,
+
+    /// The maximum width and height of the image to send.
+    ///
+    /// If the image is bigger than these dimensions, it is downsized and
+    /// re-encoded, as PNG if it has an alpha channel and as JPEG otherwise.
+    /// Animated images are never downsized, since they would lose their
+    /// animation. If this is `None`, the image is sent as-is.
+    ///
+    /// Defaults to `None`.
+    pub max_size: Option<(u32, u32)>,
+
+    /// Whether to compute the blurhash of the image, if none was provided in
+    /// the attachment info.
+    ///
+    /// Defaults to `true`.
+    pub blurhash: bool,
+}
+
+impl Default for ImageProcessingConfig {
+    fn default() -> Self {
+        Self { thumbnail_size: Some((800, 600)), max_size: None, blurhash: true }
+    }
+}
+
+/// The result of processing an image.
+struct ProcessedImage {
+    /// The new content type and data of the image, if it was re-encoded.
+    reencoded: Option<(Mime, Vec<u8>)>,
+    width: u32,
+    height: u32,
+    blurhash: Option<String>,
+    thumbnail: Option<Thumbnail>,
+}
+
+/// Process the data of an attachment according to the given settings, if it's
+/// an image in a supported format.
+///
+/// The dimensions and size of the image are set in `info`, as well as its
+/// blurhash, unless one was already provided. A thumbnail is generated if
+/// `thumbnail` is `None`.
+///
+/// Failing to process the image isn't fatal: the attachment is sent as-is, and
+/// a warning is logged.
+///
+/// Returns the content type and data of the attachment to send.
+#[instrument(skip_all, fields(%content_type))]
+pub(crate) async fn process_attachment(
+    content_type: Mime,
+    data: Vec<u8>,
+    settings: ImageProcessingConfig,
+    info: &mut Option<AttachmentInfo>,
+    thumbnail: &mut Option<Thumbnail>,
+) -> (Mime, Vec<u8>) {
+    let format = (content_type.type_() == mime::IMAGE)
+        .then(|| ImageFormat::from_mime_type(content_type.essence_str()))
+        .flatten();
+
+    let Some(format) = format else {
+        debug!("not an image in a supported format, skipping processing");
+        return (content_type, data);
+    };
+
+    let generate_thumbnail = thumbnail.is_none();
+
+    #[cfg(not(target_arch = "wasm32"))]
+    let (data, result) = {
+        // Share the data with the task rather than copying it, we get it back once
+        // the task is done.
+        let data = Arc::new(data);
+        let task = tokio::task::spawn_blocking({
+            let data = data.clone();
+            move || process_image(&data, format, &settings, generate_thumbnail)
+        });
+        let result = task.await;
+        let data = Arc::try_unwrap(data).unwrap_or_else(|data| data.as_ref().clone());
+
+        match result {
+            Ok(result) => (data, result),
+            Err(error) => {
+                warn!("the image processing task failed, sending the image as-is: {error}");
+                return (content_type, data);
+            }
+        }
+    };
+
+    #[cfg(target_arch = "wasm32")]
+    let result = process_image(&data, format, &settings, generate_thumbnail);
+
+    let processed = match result {
+        Ok(processed) => processed,
+        Err(error) => {
+            warn!("couldn't process the image, sending it as-is: {error}");
+            return (content_type, data);
+        }
+    };
+
+    let (content_type, data) = processed.reencoded.unwrap_or((content_type, data));
+
+    // Keep the values provided by the caller that can't be computed from the
+    // image itself.
+    let (caller_blurhash, is_animated) = match info.take() {
+        Some(AttachmentInfo::Image(info)) => (info.blurhash, info.is_animated),
+        _ => (None, None),
+    };
+
+    *info = Some(AttachmentInfo::Image(BaseImageInfo {
+        height: Some(processed.height.into()),
+        width: Some(processed.width.into()),
+        size: Some(UInt::new_saturating(data.len() as u64)),
+        blurhash: caller_blurhash.or(processed.blurhash),
+        is_animated,
+    }));
+
+    if generate_thumbnail {
+        *thumbnail = processed.thumbnail;
+    }
+
+    (content_type, data)
+}
+
+/// Decode the image and process it according to the given settings.
+///
+/// The EXIF orientation of the image is applied, so the dimensions, the
+/// thumbnail and the re-encoded image match how the image is displayed.
+fn process_image(
+    data: &[u8],
+    format: ImageFormat,
+    settings: &ImageProcessingConfig,
+    generate_thumbnail: bool,
+) -> Result<ProcessedImage, ImageError> {
+    let mut decoder = ImageReader::with_format(Cursor::new(data), format).into_decoder()?;
+    let orientation = decoder.orientation()?;
+
+    let mut image = DynamicImage::from_decoder(decoder)?;
+    image.apply_orientation(orientation);
+
+    let mut reencoded = None;
+
+    // Only the first frame of an animated image is decoded, re-encoding it would
+    // lose the animation.
+    let may_be_animated = matches!(format, ImageFormat::Gif | ImageFormat::WebP);
+
+    if let Some((max_width, max_height)) = settings.max_size.filter(|_| !may_be_animated) {
+        if image.width() > max_width || image.height() > max_height {
+            image = image.resize(max_width, max_height, FilterType::Lanczos3);
+            debug!(width = image.width(), height = image.height(), "downsized the image");
+
+            reencoded = Some(encode(&image)?);
+        }
+    }
+
+    let thumbnail = match settings.thumbnail_size.filter(|_| generate_thumbnail) {
+        Some((max_width, max_height))
+            if image.width() > max_width || image.height() > max_height =>
+        {
+            let thumbnail = image.thumbnail(max_width, max_height);
+            let (content_type, data) = encode(&thumbnail)?;
+            debug!(width = thumbnail.width(), height = thumbnail.height(), "generated a thumbnail");
+
+            Some(Thumbnail {
+                size: UInt::new_saturating(data.len() as u64),
+                height: thumbnail.height().into(),
+                width: thumbnail.width().into(),
+                content_type,
+                data,
+            })
+        }
+        _ => None,
+    };
+
+    let blurhash = settings.blurhash.then(|| compute_blurhash(&image)).flatten();
+
+    Ok(ProcessedImage {
+        reencoded,
+        width: image.width(),
+        height: image.height(),
+        blurhash,
+        thumbnail,
+    })
+}
+
+/// Compute the blurhash of the given image.
+fn compute_blurhash(image: &DynamicImage) -> Option<String> {
+    let small = image.thumbnail(BLURHASH_IMAGE_SIZE, BLURHASH_IMAGE_SIZE).to_rgba8();
+    let (components_x, components_y) = BLURHASH_COMPONENTS;
+
+    blurhash::encode(components_x, components_y, small.width(), small.height(), small.as_raw())
+        .inspect_err(|error| warn!("couldn't compute the blurhash of the image: {error}"))
+        .ok()
+}
+
+/// Encode the given image as PNG if it has an alpha channel, and as JPEG
+/// otherwise.
+fn encode(image: &DynamicImage) -> Result<(Mime, Vec<u8>), ImageError> {
+    let mut data = Cursor::new(Vec::new());
+
+    if image.color().has_alpha() {
+        image.write_to(&mut data, ImageFormat::Png)?;
+        Ok((mime::IMAGE_PNG, data.into_inner()))
+    } else {
+        // The JPEG encoder doesn't support all the color types, convert the image to
+        // one that it supports.
+        DynamicImage::ImageRgb8(image.to_rgb8()).write_to(&mut data, ImageFormat::Jpeg)?;
+        Ok((mime::IMAGE_JPEG, data.into_inner()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use assert_matches2::assert_let;
+    use image::{DynamicImage, ImageFormat, RgbImage};
+    use matrix_sdk_test::async_test;
+    use ruma::uint;
+
+    use super::{encode, process_attachment, ImageProcessingConfig};
+    use crate::attachment::{AttachmentInfo, BaseImageInfo};
+
+    fn jpeg(width: u32, height: u32) -> Vec<u8> {
+        let image = DynamicImage::ImageRgb8(RgbImage::from_pixel(width, height, [42, 0, 0].into()));
+        encode(&image).unwrap().1
+    }
+
+    #[async_test]
+    async fn test_compute_info_and_thumbnail() {
+        let mut info = None;
+        let mut thumbnail = None;
+
+        let (content_type, data) = process_attachment(
+            mime::IMAGE_JPEG,
+            jpeg(1600, 900),
+            ImageProcessingConfig::default(),
+            &mut info,
+            &mut thumbnail,
+        )
+        .await;
+
+        assert_eq!(content_type, mime::IMAGE_JPEG);
+        assert_eq!(image::guess_format(&data).unwrap(), ImageFormat::Jpeg);
+
+        assert_let!(Some(AttachmentInfo::Image(info)) = info);
+        assert_eq!(info.width, Some(uint!(1600)));
+        assert_eq!(info.height, Some(uint!(900)));
+        assert_eq!(info.size, Some(data.len().try_into().unwrap()));
+        assert!(info.blurhash.is_some());
+
+        // The thumbnail keeps the aspect ratio of the image.
+        let thumbnail = thumbnail.unwrap();
+        assert_eq!(thumbnail.content_type, mime::IMAGE_JPEG);
+        assert_eq!(thumbnail.width, uint!(800));
+        assert_eq!(thumbnail.height, uint!(450));
+    }
+
+    #[async_test]
+    async fn test_apply_exif_orientation() {
+        let mut data = jpeg(1600, 900);
+
+        // Insert an EXIF segment right after the start of image marker, with an
+        // orientation that requires a 90° clockwise rotation.
+        #[rustfmt::skip]
+        let exif = [
+            // APP1 marker and length of the segment.
+            0xFF, 0xE1, 0x00, 0x22,
+            b'E', b'x', b'i', b'f', 0x00, 0x00,
+            // Big-endian TIFF header, with the offset of the first IFD.
+            b'M', b'M', 0x00, 0x2A, 0x00, 0x00, 0x00, 0x08,
+            // A single IFD entry: the orientation, as a short.
+            0x00, 0x01,
+            0x01, 0x12, 0x00, 0x03, 0x00, 0x00, 0x00, 0x01, 0x00, 0x06, 0x00, 0x00,
+            // No next IFD.
+            0x00, 0x00, 0x00, 0x00,
+        ];
+        data.splice(2..2, exif);
+
+        let mut info = None;
+        let mut thumbnail = None;
+
+        process_attachment(
+            mime::IMAGE_JPEG,
+            data,
+            ImageProcessingConfig::default(),
+            &mut info,
+            &mut thumbnail,
+        )
+        .await;
+
+        // The dimensions are the ones of the rotated image.
+        assert_let!(Some(AttachmentInfo::Image(info)) = info);
+        assert_eq!(info.width, Some(uint!(900)));
+        assert_eq!(info.height, Some(uint!(1600)));
+
+        let thumbnail = thumbnail.unwrap();
+        assert_eq!(thumbnail.height, uint!(600));
+        assert!(thumbnail.width < thumbnail.height);
+    }
+
+    #[async_test]
+    async fn test_downsize() {
+        let mut info = Some(AttachmentInfo::Image(BaseImageInfo {
+            blurhash: Some("caller blurhash".to_owned()),
+            ..Default::default()
+        }));
+        let mut thumbnail = None;
+
+        let settings = ImageProcessingConfig {
+            thumbnail_size: Some((800, 600)),
+            max_size: Some((400, 400)),
+            blurhash: true,
+        };
+        let (_, data) = process_attachment(
+            mime::IMAGE_JPEG,
+            jpeg(1600, 900),
+            settings,
+            &mut info,
+            &mut thumbnail,
+        )
+        .await;
+
+        let image = image::load_from_memory(&data).unwrap();
+        assert_eq!((image.width(), image.height()), (400, 225));
+
+        assert_let!(Some(AttachmentInfo::Image(info)) = info);
+        assert_eq!(info.width, Some(uint!(400)));
+        assert_eq!(info.height, Some(uint!(225)));
+        // The blurhash of the caller is kept.
+        assert_eq!(info.blurhash.as_deref(), Some("caller blurhash"));
+
+        // The downsized image is smaller than the thumbnail size.
+        assert!(thumbnail.is_none());
+    }
+
+    #[async_test]
+    async fn test_invalid_image_is_sent_as_is() {
+        let mut info = None;
+        let mut thumbnail = None;
+
+        let (content_type, data) = process_attachment(
+            mime::IMAGE_PNG,
+            b"not an image".to_vec(),
+            ImageProcessingConfig::default(),
+            &mut info,
+            &mut thumbnail,
+        )
+        .await;
+
+        assert_eq!(content_type, mime::IMAGE_PNG);
+        assert_eq!(data, b"not an image");
+        assert!(info.is_none());
+        assert!(thumbnail.is_none());
+    }
+}
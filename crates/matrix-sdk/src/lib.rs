@@ -25,10 +25,10 @@ pub use matrix_sdk_base::crypto;
 pub use matrix_sdk_base::{
     deserialized_responses,
     store::{self, DynStateStore, MemoryStore, StateStoreExt},
-    ComposerDraft, ComposerDraftType, QueueWedgeError, Room as BaseRoom,
+    ComposerDraft, ComposerDraftType, PredecessorRoom, QueueWedgeError, Room as BaseRoom,
     RoomCreateWithCreatorEventContent, RoomDisplayName, RoomHero, RoomInfo,
     RoomMember as BaseRoomMember, RoomMemberships, RoomState, SessionMeta, StateChanges,
-    StateStore, StoreError,
+    StateStore, StoreError, SuccessorRoom,
 };
 pub use matrix_sdk_common::*;
 pub use reqwest;
@@ -45,13 +45,17 @@ mod error;
 pub mod event_cache;
 pub mod event_handler;
 mod http_client;
+#[cfg(feature = "image-processing")]
+mod image_processing;
 pub mod media;
+pub mod message_search;
 pub mod notification_settings;
 pub mod pusher;
 pub mod room;
 pub mod room_directory_search;
 pub mod room_preview;
 pub mod send_queue;
+pub mod space_hierarchy;
 pub mod utils;
 pub mod futures {
     //! Named futures returned from methods on types in [the crate root][crate].
@@ -0,0 +1,1133 @@
+// Copyright 2025 The Matrix.org Foundation C.I.C.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! A manager of media downloads, running in the background.
+//!
+//! The [`MediaDownloadManager`] of a client is available with
+//! [`Media::download_manager()`]. Downloads are queued with a
+//! [`MediaDownloadPriority`], and run by a few background tasks, the highest
+//! priority first. Their progress and outcome can be observed with
+//! [`MediaDownloadManager::subscribe()`], and the downloaded media is stored in
+//! the media cache, where it can be read with [`Media::get_media_content()`].
+//! The downloads queued with the manager of a room, returned by
+//! [`Room::media()`], are associated to the room in the media cache.
+//!
+//! # Resumption
+//!
+//! The queue is persisted in the state store, so the downloads that were
+//! pending when the client was shut down are restarted the next time the
+//! manager is used.
+//!
+//! The content of a file is written to a temporary file while it's
+//! downloading, and the offset up to which it was written is persisted with
+//! the queue regularly, and when the download fails. The download is resumed
+//! from there with an HTTP range request, if the homeserver supports it. The
+//! temporary file is removed when the download is finished, cancelled, or
+//! given up. Thumbnails are small enough to always be downloaded from the
+//! start, directly into the media cache.
+//!
+//! Downloads failing because of a network error or a transient server error
+//! are retried a few times, with an exponential backoff.
+//!
+//! [`Media::get_media_content()`]: super::Media::get_media_content
+//! [`Room::media()`]: crate::Room::media
+
+use std::{
+    cmp::Reverse,
+    collections::BTreeSet,
+    fmt,
+    io::SeekFrom,
+    mem,
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use http::{header::CONTENT_TYPE, StatusCode};
+use matrix_sdk_base::{
+    event_cache::store::media::{IgnoreMediaRetentionPolicy, MediaOrigin},
+    media::{MediaFormat, MediaRequestParameters, MediaThumbnailSettings, UniqueKey},
+};
+use matrix_sdk_common::{
+    executor::{spawn, JoinHandle},
+    sleep::sleep,
+};
+use ruma::{events::room::MediaSource, OwnedMxcUri, OwnedRoomId};
+use serde::{Deserialize, Serialize};
+use tempfile::Builder as TempFileBuilder;
+use tokio::{
+    fs::{File as TokioFile, OpenOptions},
+    io::{AsyncSeekExt, AsyncWriteExt},
+    sync::{broadcast, oneshot, Mutex, Notify, OnceCell},
+};
+use tracing::{debug, instrument, trace, warn};
+
+use super::{Media, MediaError};
+use crate::{
+    client::WeakClient,
+    error::{HttpError, RetryKind},
+    Client, Result, Room, TransmissionProgress,
+};
+
+/// The number of downloads that can run concurrently.
+const MAX_CONCURRENT_DOWNLOADS: usize = 3;
+
+/// The number of times a download is attempted, before giving up.
+const MAX_ATTEMPTS: u32 = 5;
+
+/// The maximum delay before retrying a download that failed.
+const MAX_RETRY_DELAY: Duration = Duration::from_secs(60);
+
+/// The number of bytes after which the offset of a partial download is
+/// persisted, so it can be resumed after a restart.
+const CHECKPOINT_INTERVAL: u64 = 8 * 1024 * 1024;
+
+/// The key of the queue of downloads, in the custom values of the state store.
+const PERSISTED_QUEUE_KEY: &[u8] = b"media_download_manager.queue";
+
+/// The prefix of the names of the temporary files of the downloads.
+const PARTIAL_DOWNLOAD_FILE_PREFIX: &str = "matrix-sdk-download-";
+
+/// The priority of a download in the [`MediaDownloadManager`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub enum MediaDownloadPriority {
+    /// A download that can wait, like prefetching.
+    Low,
+    /// The default priority.
+    #[default]
+    Normal,
+    /// A download that the user is waiting for.
+    High,
+}
+
+/// An update about a download of the [`MediaDownloadManager`].
+#[derive(Clone, Debug)]
+pub enum MediaDownloadUpdate {
+    /// Some content of the media has been downloaded.
+    ///
+    /// `progress.total` is `0` if the homeserver didn't say how big the media
+    /// is.
+    Progress {
+        /// The media being downloaded.
+        request: MediaRequestParameters,
+        /// How much of the media has been downloaded.
+        progress: TransmissionProgress,
+    },
+
+    /// The media has been downloaded, and is available in the media cache,
+    /// unless it's bigger than the maximum file size of the media retention
+    /// policy.
+    Finished {
+        /// The media that was downloaded.
+        request: MediaRequestParameters,
+    },
+
+    /// The download failed, and won't be retried.
+    Failed {
+        /// The media that couldn't be downloaded.
+        request: MediaRequestParameters,
+        /// The error of the last attempt.
+        error: Arc<crate::Error>,
+    },
+
+    /// The download was cancelled with [`MediaDownloadHandle::cancel()`].
+    Cancelled {
+        /// The media whose download was cancelled.
+        request: MediaRequestParameters,
+    },
+}
+
+/// Information about a download of the [`MediaDownloadManager`].
+#[derive(Clone, Debug)]
+pub struct MediaDownloadInfo {
+    /// The media to download.
+    pub request: MediaRequestParameters,
+    /// The priority of the download.
+    pub priority: MediaDownloadPriority,
+    /// Whether the media is being downloaded right now.
+    pub is_active: bool,
+    /// How much of the media has been downloaded.
+    pub progress: TransmissionProgress,
+}
+
+/// A handle to a download queued in the [`MediaDownloadManager`].
+#[derive(Clone, Debug)]
+pub struct MediaDownloadHandle {
+    manager: MediaDownloadManager,
+    request: MediaRequestParameters,
+}
+
+impl MediaDownloadHandle {
+    /// The media to download.
+    pub fn request(&self) -> &MediaRequestParameters {
+        &self.request
+    }
+
+    /// Change the priority of the download.
+    ///
+    /// Returns `false` if the download isn't in the queue anymore. A download
+    /// that has already started isn't interrupted.
+    pub async fn set_priority(&self, priority: MediaDownloadPriority) -> bool {
+        let queue = &self.manager.inner.queue;
+        let mut state = queue.state().await;
+
+        let Some(download) = state.get_mut(&self.request.unique_key()) else {
+            return false;
+        };
+
+        download.priority = priority;
+        queue.persist(&state).await;
+
+        true
+    }
+
+    /// Cancel the download, and discard the content downloaded so far.
+    ///
+    /// Returns `false` if the download isn't in the queue anymore.
+    pub async fn cancel(&self) -> bool {
+        self.manager.inner.queue.cancel(&self.request).await
+    }
+}
+
+/// A manager of media downloads, running in the background.
+///
+/// See the [module documentation](self) for more details.
+#[derive(Clone)]
+pub struct MediaDownloadManager {
+    inner: Arc<MediaDownloadManagerInner>,
+    /// The room of the media to download, if this is the manager of a room.
+    room_id: Option<OwnedRoomId>,
+}
+
+#[cfg(not(tarpaulin_include))]
+impl fmt::Debug for MediaDownloadManager {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("MediaDownloadManager").finish_non_exhaustive()
+    }
+}
+
+struct MediaDownloadManagerInner {
+    queue: Arc<DownloadQueue>,
+    tasks: Vec<JoinHandle<()>>,
+}
+
+impl Drop for MediaDownloadManagerInner {
+    fn drop(&mut self) {
+        for task in &self.tasks {
+            task.abort();
+        }
+    }
+}
+
+impl MediaDownloadManager {
+    /// Create a new manager, and spawn its background tasks.
+    pub(crate) fn new(client: WeakClient) -> Self {
+        let (updates, _) = broadcast::channel(64);
+
+        let queue = Arc::new(DownloadQueue {
+            client,
+            restored: OnceCell::new(),
+            state: Default::default(),
+            notifier: Notify::new(),
+            updates,
+        });
+
+        let tasks = (0..MAX_CONCURRENT_DOWNLOADS)
+            .map(|_| spawn(DownloadQueue::download_task(queue.clone())))
+            .collect();
+
+        Self { inner: Arc::new(MediaDownloadManagerInner { queue, tasks }), room_id: None }
+    }
+
+    /// Get the manager for the media of the given room, sharing the same
+    /// queue.
+    pub(super) fn for_room(&self, room_id: Option<OwnedRoomId>) -> Self {
+        Self { inner: self.inner.clone(), room_id }
+    }
+
+    /// Queue a download of the given media.
+    ///
+    /// If the media is already in the queue, its priority is raised to the
+    /// given one if it's higher, and a handle to the existing download is
+    /// returned.
+    pub async fn enqueue(
+        &self,
+        request: MediaRequestParameters,
+        priority: MediaDownloadPriority,
+    ) -> MediaDownloadHandle {
+        let origin = self.room_id.clone().map(MediaOrigin::room).unwrap_or_default();
+        self.enqueue_with_origin(request, priority, origin).await
+    }
+
+    /// Queue a download of the given media, knowing where it comes from.
+    async fn enqueue_with_origin(
+        &self,
+        request: MediaRequestParameters,
+        priority: MediaDownloadPriority,
+        origin: MediaOrigin,
+    ) -> MediaDownloadHandle {
+        let queue = &self.inner.queue;
+        let mut state = queue.state().await;
+
+        if let Some(download) = state.get_mut(&request.unique_key()) {
+            download.priority = download.priority.max(priority);
+        } else {
+            trace!(source = ?request.source, ?priority, "queuing a media download");
+            state.push(request.clone(), priority, origin, None);
+        }
+
+        queue.persist(&state).await;
+        drop(state);
+
+        queue.notifier.notify_one();
+
+        MediaDownloadHandle { manager: self.clone(), request }
+    }
+
+    /// Queue downloads of the thumbnails of the avatars of the given rooms,
+    /// with a [low](MediaDownloadPriority::Low) priority.
+    ///
+    /// This is meant to be called with the rooms that are visible in a room
+    /// list, so their avatars can be displayed right away. Avatars that are
+    /// already in the media cache are skipped.
+    pub async fn prefetch_room_thumbnails(
+        &self,
+        rooms: &[Room],
+        settings: MediaThumbnailSettings,
+    ) -> Result<()> {
+        let avatar_urls: BTreeSet<OwnedMxcUri> =
+            rooms.iter().filter_map(|room| room.avatar_url()).collect();
+
+        for avatar_url in avatar_urls {
+            let request = MediaRequestParameters {
+                source: MediaSource::Plain(avatar_url),
+                format: MediaFormat::Thumbnail(settings.clone()),
+            };
+
+            let client = self.inner.queue.client()?;
+            if client.event_cache_store().lock().await?.get_media_content(&request).await?.is_some()
+            {
+                continue;
+            }
+
+            self.enqueue_with_origin(request, MediaDownloadPriority::Low, MediaOrigin::avatar())
+                .await;
+        }
+
+        Ok(())
+    }
+
+    /// Get the downloads that are in the queue, in the order they were
+    /// queued.
+    pub async fn downloads(&self) -> Vec<MediaDownloadInfo> {
+        self.inner
+            .queue
+            .state()
+            .await
+            .downloads
+            .iter()
+            .map(|download| MediaDownloadInfo {
+                request: download.request.clone(),
+                priority: download.priority,
+                is_active: matches!(download.state, DownloadState::Active(_)),
+                progress: download.progress,
+            })
+            .collect()
+    }
+
+    /// Get a handle to a download in the queue, if any.
+    pub async fn handle(&self, request: &MediaRequestParameters) -> Option<MediaDownloadHandle> {
+        let state = self.inner.queue.state().await;
+        let download = state.get(&request.unique_key())?;
+
+        Some(MediaDownloadHandle { manager: self.clone(), request: download.request.clone() })
+    }
+
+    /// Subscribe to the updates of the downloads.
+    ///
+    /// Progress updates are frequent, a receiver that doesn't keep up might
+    /// miss some updates.
+    pub fn subscribe(&self) -> broadcast::Receiver<MediaDownloadUpdate> {
+        self.inner.queue.updates.subscribe()
+    }
+}
+
+/// A download in the queue.
+#[derive(Debug)]
+struct QueuedDownload {
+    request: MediaRequestParameters,
+    /// The unique key of the request, used to identify the download.
+    key: String,
+    priority: MediaDownloadPriority,
+    /// The position of the download in the queue, to download the media with
+    /// the same priority in the order they were queued.
+    order: u64,
+    /// The number of failed attempts to download the media.
+    attempts: u32,
+    /// The time before which the download mustn't be retried, after a
+    /// failure.
+    retry_at: Option<Instant>,
+    /// Whether the download is running.
+    state: DownloadState,
+    progress: TransmissionProgress,
+    /// Where the media comes from, to store it in the media cache.
+    origin: MediaOrigin,
+    /// The content downloaded so far, if any.
+    partial: Option<PartialDownload>,
+}
+
+/// The state of a download in the queue.
+#[derive(Debug, Default)]
+enum DownloadState {
+    /// The download is waiting for a task to run it.
+    #[default]
+    Queued,
+    /// A task is running the download, until the sender is triggered.
+    Active(oneshot::Sender<()>),
+    /// The download was cancelled while it was running, and its task hasn't
+    /// removed it from the queue yet. It mustn't be run again meanwhile, since
+    /// its temporary file is about to be removed.
+    Cancelled,
+}
+
+/// The content of a download that was partially downloaded.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+struct PartialDownload {
+    /// The temporary file where the content is written.
+    path: PathBuf,
+    /// The number of bytes that are known to be written in the file.
+    offset: u64,
+}
+
+/// A download, as persisted in the state store.
+#[derive(Debug, Serialize, Deserialize)]
+struct PersistedDownload {
+    request: MediaRequestParameters,
+    priority: MediaDownloadPriority,
+    #[serde(default)]
+    origin: MediaOrigin,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    partial: Option<PartialDownload>,
+}
+
+#[derive(Debug, Default)]
+struct DownloadQueueState {
+    downloads: Vec<QueuedDownload>,
+    next_order: u64,
+}
+
+impl DownloadQueueState {
+    fn push(
+        &mut self,
+        request: MediaRequestParameters,
+        priority: MediaDownloadPriority,
+        origin: MediaOrigin,
+        partial: Option<PartialDownload>,
+    ) {
+        let order = self.next_order;
+        self.next_order += 1;
+
+        self.downloads.push(QueuedDownload {
+            key: request.unique_key(),
+            request,
+            priority,
+            order,
+            attempts: 0,
+            retry_at: None,
+            state: DownloadState::Queued,
+            progress: Default::default(),
+            origin,
+            partial,
+        });
+    }
+
+    fn get(&self, key: &str) -> Option<&QueuedDownload> {
+        self.downloads.iter().find(|download| download.key == key)
+    }
+
+    fn get_mut(&mut self, key: &str) -> Option<&mut QueuedDownload> {
+        self.downloads.iter_mut().find(|download| download.key == key)
+    }
+
+    fn remove(&mut self, key: &str) -> Option<QueuedDownload> {
+        let index = self.downloads.iter().position(|download| download.key == key)?;
+        Some(self.downloads.remove(index))
+    }
+}
+
+/// What a download task should do next.
+enum NextDownload {
+    /// Download the given media, until the receiver is triggered.
+    Download(MediaRequestParameters, oneshot::Receiver<()>),
+    /// Wait for a new download, or for the given delay before retrying a
+    /// failed download.
+    Wait(Option<Duration>),
+}
+
+/// The queue of downloads, shared by the background tasks.
+struct DownloadQueue {
+    client: WeakClient,
+    /// Whether the persisted downloads have been restored.
+    restored: OnceCell<()>,
+    state: Mutex<DownloadQueueState>,
+    notifier: Notify,
+    updates: broadcast::Sender<MediaDownloadUpdate>,
+}
+
+impl DownloadQueue {
+    fn client(&self) -> Result<Client> {
+        self.client.get().ok_or(MediaError::ClientDropped.into())
+    }
+
+    /// Lock the state of the queue, after restoring the persisted downloads
+    /// if needed.
+    async fn state(&self) -> tokio::sync::MutexGuard<'_, DownloadQueueState> {
+        self.restored.get_or_init(|| self.restore()).await;
+        self.state.lock().await
+    }
+
+    /// Load the downloads that were persisted in the state store.
+    async fn restore(&self) {
+        let Ok(client) = self.client() else { return };
+
+        let persisted = match client.store().get_custom_value(PERSISTED_QUEUE_KEY).await {
+            Ok(Some(value)) => value,
+            Ok(None) => return,
+            Err(err) => {
+                warn!("couldn't load the persisted media downloads: {err}");
+                return;
+            }
+        };
+
+        let persisted: Vec<PersistedDownload> = match serde_json::from_slice(&persisted) {
+            Ok(persisted) => persisted,
+            Err(err) => {
+                warn!("couldn't deserialize the persisted media downloads: {err}");
+                return;
+            }
+        };
+
+        debug!(num_downloads = persisted.len(), "restoring persisted media downloads");
+
+        let mut state = self.state.lock().await;
+        for download in persisted {
+            state.push(download.request, download.priority, download.origin, download.partial);
+        }
+    }
+
+    /// Save the downloads of the queue in the state store.
+    async fn persist(&self, state: &DownloadQueueState) {
+        let Ok(client) = self.client() else { return };
+
+        let persisted: Vec<_> = state
+            .downloads
+            .iter()
+            .map(|download| PersistedDownload {
+                request: download.request.clone(),
+                priority: download.priority,
+                origin: download.origin.clone(),
+                partial: download.partial.clone(),
+            })
+            .collect();
+
+        let result = match serde_json::to_vec(&persisted) {
+            Ok(value) => client
+                .store()
+                .set_custom_value_no_read(PERSISTED_QUEUE_KEY, value)
+                .await
+                .map_err(|err| err.to_string()),
+            Err(err) => Err(err.to_string()),
+        };
+
+        if let Err(err) = result {
+            warn!("couldn't persist the media downloads: {err}");
+        }
+    }
+
+    /// Pick the next media to download, with the highest priority.
+    async fn next_download(&self) -> NextDownload {
+        let mut state = self.state().await;
+        let now = Instant::now();
+
+        let next = state
+            .downloads
+            .iter_mut()
+            .filter(|download| matches!(download.state, DownloadState::Queued))
+            .filter(|download| download.retry_at.is_none_or(|retry_at| retry_at <= now))
+            .max_by_key(|download| (download.priority, Reverse(download.order)));
+
+        if let Some(download) = next {
+            let (cancel_sender, cancel_receiver) = oneshot::channel();
+            download.state = DownloadState::Active(cancel_sender);
+            download.retry_at = None;
+
+            return NextDownload::Download(download.request.clone(), cancel_receiver);
+        }
+
+        let next_retry_at = state
+            .downloads
+            .iter()
+            .filter(|download| matches!(download.state, DownloadState::Queued))
+            .filter_map(|download| download.retry_at)
+            .min();
+
+        NextDownload::Wait(next_retry_at.map(|retry_at| retry_at.saturating_duration_since(now)))
+    }
+
+    /// A task running downloads in the background, until the manager is
+    /// dropped.
+    async fn download_task(queue: Arc<Self>) {
+        loop {
+            match queue.next_download().await {
+                NextDownload::Download(request, cancel_receiver) => {
+                    let result = tokio::select! {
+                        result = queue.download(&request) => Some(result),
+                        _ = cancel_receiver => None,
+                    };
+
+                    queue.finish(&request, result).await;
+                }
+
+                NextDownload::Wait(Some(delay)) => {
+                    tokio::select! {
+                        _ = queue.notifier.notified() => {}
+                        _ = sleep(delay) => {}
+                    }
+                }
+
+                NextDownload::Wait(None) => queue.notifier.notified().await,
+            }
+        }
+    }
+
+    /// Download the given media, and store it in the media cache.
+    #[instrument(skip_all, fields(source = ?request.source))]
+    async fn download(&self, request: &MediaRequestParameters) -> Result<()> {
+        let client = self.client()?;
+        let key = request.unique_key();
+        let (mut origin, partial) = self
+            .state
+            .lock()
+            .await
+            .get(&key)
+            .map(|download| (download.origin.clone(), download.partial.clone()))
+            .unwrap_or_default();
+
+        if matches!(request.format, MediaFormat::Thumbnail(_)) {
+            client.media().get_media_content_with_origin(request, true, &origin).await?;
+            return Ok(());
+        }
+
+        let (mut file, path, mut offset) = open_partial_download(partial).await?;
+        self.save_partial_download(&key, &path, offset).await;
+
+        if offset > 0 {
+            debug!(offset, "resuming a partial download");
+        }
+
+        let uri = request.uri();
+        let media = client.media();
+        let mut response = match media.download_content_from_offset(uri, offset).await {
+            Err(err) if offset > 0 && is_range_not_satisfiable(&err) => {
+                debug!("the partial download is invalid, restarting from the start");
+                offset = truncate_file(&mut file, 0).await?;
+                media.download_content_from_offset(uri, 0).await?
+            }
+            result => result?,
+        };
+
+        if response.status() != StatusCode::PARTIAL_CONTENT && offset > 0 {
+            debug!("the homeserver doesn't support range requests, restarting from the start");
+            offset = truncate_file(&mut file, 0).await?;
+        }
+
+        // The content type returned by the homeserver for an encrypted media is the one
+        // of the encrypted content, it's meaningless.
+        if origin.content_type.is_none() && matches!(request.source, MediaSource::Plain(_)) {
+            origin.content_type = response
+                .headers()
+                .get(CONTENT_TYPE)
+                .and_then(|content_type| content_type.to_str().ok())
+                .map(ToOwned::to_owned);
+        }
+
+        let total = response.content_length().map_or(0, |length| offset + length);
+        let mut checkpoint = offset;
+
+        loop {
+            let chunk = match response.chunk().await {
+                Ok(Some(chunk)) => chunk,
+                Ok(None) => break,
+                Err(err) => {
+                    // Keep what we got so far, to resume the download later.
+                    if sync_file(&mut file).await {
+                        self.save_partial_download(&key, &path, offset).await;
+                    }
+                    return Err(HttpError::Reqwest(err).into());
+                }
+            };
+
+            file.write_all(&chunk).await?;
+            offset += chunk.len() as u64;
+
+            self.report_progress(
+                request,
+                TransmissionProgress { current: offset as usize, total: total as usize },
+            )
+            .await;
+
+            if offset - checkpoint >= CHECKPOINT_INTERVAL && sync_file(&mut file).await {
+                self.save_partial_download(&key, &path, offset).await;
+                checkpoint = offset;
+            }
+        }
+
+        file.flush().await?;
+        drop(file);
+
+        // Like `Media::get_media_file()`, don't load big files in memory.
+        let max_size = media.max_cached_media_file_size().await?;
+        if offset > max_size {
+            debug!(size = offset, max_size, "not caching the downloaded media, it is too big");
+            return Ok(());
+        }
+
+        let data = tokio::fs::read(&path).await?;
+        let content = match &request.source {
+            MediaSource::Encrypted(file) => Media::decrypt_content(file, data)?,
+            MediaSource::Plain(_) => data,
+        };
+
+        client
+            .event_cache_store()
+            .lock()
+            .await?
+            .add_media_content_with_origin(
+                request,
+                content,
+                &origin,
+                IgnoreMediaRetentionPolicy::No,
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    /// Persist the offset up to which the content of a download has been
+    /// written in the given temporary file.
+    async fn save_partial_download(&self, key: &str, path: &Path, offset: u64) {
+        let mut state = self.state.lock().await;
+        let Some(download) = state.get_mut(key) else {
+            return;
+        };
+
+        download.partial = Some(PartialDownload { path: path.to_owned(), offset });
+        self.persist(&state).await;
+    }
+
+    async fn report_progress(
+        &self,
+        request: &MediaRequestParameters,
+        progress: TransmissionProgress,
+    ) {
+        if let Some(download) = self.state.lock().await.get_mut(&request.unique_key()) {
+            download.progress = progress;
+        }
+
+        let _ =
+            self.updates.send(MediaDownloadUpdate::Progress { request: request.clone(), progress });
+    }
+
+    /// Update the queue after a download ended.
+    ///
+    /// `result` is `None` if the download was cancelled.
+    async fn finish(&self, request: &MediaRequestParameters, result: Option<Result<()>>) {
+        let key = request.unique_key();
+        let mut state = self.state().await;
+
+        // The download might have been cancelled after it failed, but before we got
+        // the lock.
+        let was_cancelled = state
+            .get(&key)
+            .is_some_and(|download| matches!(download.state, DownloadState::Cancelled));
+        let result = match result {
+            Some(Err(_)) if was_cancelled => None,
+            result => result,
+        };
+
+        let update = match result {
+            Some(Ok(())) => {
+                debug!(source = ?request.source, "media downloaded");
+                if let Some(download) = state.remove(&key) {
+                    discard_partial_download(download.partial).await;
+                }
+                MediaDownloadUpdate::Finished { request: request.clone() }
+            }
+
+            Some(Err(error)) => {
+                let Some(download) = state.get_mut(&key) else {
+                    return;
+                };
+
+                download.state = DownloadState::Queued;
+                download.attempts += 1;
+
+                let retry_kind = match &error {
+                    crate::Error::Http(http_error) => http_error.retry_kind(),
+                    _ => RetryKind::Permanent,
+                };
+
+                let retry_after = match retry_kind {
+                    RetryKind::NetworkFailure => Some(None),
+                    RetryKind::Transient { retry_after } => Some(retry_after),
+                    RetryKind::Permanent => None,
+                };
+
+                match retry_after.filter(|_| download.attempts < MAX_ATTEMPTS) {
+                    Some(retry_after) => {
+                        let delay = retry_after.unwrap_or_else(|| {
+                            Duration::from_secs(2u64.pow(download.attempts)).min(MAX_RETRY_DELAY)
+                        });
+
+                        warn!(source = ?request.source, attempts = download.attempts, ?delay, "couldn't download media, retrying later: {error}");
+                        download.retry_at = Some(Instant::now() + delay);
+
+                        // Make sure a task is awake to retry the download.
+                        self.notifier.notify_one();
+                        return;
+                    }
+
+                    None => {
+                        warn!(source = ?request.source, "couldn't download media, giving up: {error}");
+                        if let Some(download) = state.remove(&key) {
+                            discard_partial_download(download.partial).await;
+                        }
+                        MediaDownloadUpdate::Failed {
+                            request: request.clone(),
+                            error: Arc::new(error),
+                        }
+                    }
+                }
+            }
+
+            None => {
+                debug!(source = ?request.source, "media download cancelled");
+                if let Some(download) = state.remove(&key) {
+                    discard_partial_download(download.partial).await;
+                }
+                MediaDownloadUpdate::Cancelled { request: request.clone() }
+            }
+        };
+
+        self.persist(&state).await;
+        drop(state);
+
+        let _ = self.updates.send(update);
+    }
+
+    /// Cancel the download of the given media.
+    ///
+    /// Returns `false` if the download isn't in the queue.
+    async fn cancel(&self, request: &MediaRequestParameters) -> bool {
+        let key = request.unique_key();
+        let mut state = self.state().await;
+
+        let Some(download) = state.get_mut(&key) else {
+            return false;
+        };
+
+        // If the download is running, its task takes care of the rest.
+        if !matches!(download.state, DownloadState::Queued) {
+            if let DownloadState::Active(cancel_sender) =
+                mem::replace(&mut download.state, DownloadState::Cancelled)
+            {
+                let _ = cancel_sender.send(());
+            }
+            return true;
+        }
+
+        let partial = state.remove(&key).and_then(|download| download.partial);
+        self.persist(&state).await;
+        drop(state);
+
+        discard_partial_download(partial).await;
+        let _ = self.updates.send(MediaDownloadUpdate::Cancelled { request: request.clone() });
+
+        true
+    }
+}
+
+/// Open the temporary file of a partial download, or create a new one if there
+/// is none.
+///
+/// Returns the file, positioned at the end of the content downloaded so far,
+/// its path, and the size of that content.
+async fn open_partial_download(
+    partial: Option<PartialDownload>,
+) -> Result<(TokioFile, PathBuf, u64)> {
+    if let Some(PartialDownload { path, offset }) = partial {
+        match OpenOptions::new().write(true).open(&path).await {
+            Ok(mut file) => {
+                // Only keep the content that is known to be written, a crash might have
+                // interrupted the last writes.
+                let len = file.metadata().await?.len();
+                let offset = truncate_file(&mut file, offset.min(len)).await?;
+                return Ok((file, path, offset));
+            }
+            Err(err) => {
+                warn!("couldn't open the partial download, restarting from the start: {err}");
+            }
+        }
+    }
+
+    let (file, path) = TempFileBuilder::new()
+        .prefix(PARTIAL_DOWNLOAD_FILE_PREFIX)
+        .tempfile()?
+        .keep()
+        .map_err(|err| err.error)?;
+
+    Ok((TokioFile::from_std(file), path, 0))
+}
+
+/// Truncate the given file to the given length, and move its cursor to the
+/// end.
+///
+/// Returns the new length of the file.
+async fn truncate_file(file: &mut TokioFile, len: u64) -> Result<u64> {
+    file.set_len(len).await?;
+    file.seek(SeekFrom::Start(len)).await?;
+    Ok(len)
+}
+
+/// Make sure the content written in the given file is on disk.
+///
+/// Returns `false` if it failed, in which case the offset of the partial
+/// download shouldn't be persisted.
+async fn sync_file(file: &mut TokioFile) -> bool {
+    let result = match file.flush().await {
+        Ok(()) => file.sync_data().await,
+        Err(err) => Err(err),
+    };
+
+    if let Err(err) = &result {
+        warn!("couldn't write the partial content of the download: {err}");
+    }
+
+    result.is_ok()
+}
+
+/// Remove the temporary file of a partial download, if any.
+async fn discard_partial_download(partial: Option<PartialDownload>) {
+    let Some(partial) = partial else { return };
+
+    if let Err(err) = tokio::fs::remove_file(&partial.path).await {
+        warn!("couldn't remove the partial content of the download: {err}");
+    }
+}
+
+/// Whether the given error means that the range of a request is invalid, i.e.
+/// the partial content of a download is bigger than the full content.
+fn is_range_not_satisfiable(error: &crate::Error) -> bool {
+    error
+        .as_client_api_error()
+        .is_some_and(|error| error.status_code == StatusCode::RANGE_NOT_SATISFIABLE)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use matrix_sdk_base::{
+        event_cache::store::media::MediaRetentionPolicy,
+        media::{MediaFormat, MediaRequestParameters},
+    };
+    use matrix_sdk_common::sleep::sleep;
+    use matrix_sdk_test::async_test;
+    use ruma::{events::room::MediaSource, mxc_uri};
+    use tempfile::NamedTempFile;
+    use wiremock::ResponseTemplate;
+
+    use super::{MediaDownloadPriority, MediaDownloadUpdate, PERSISTED_QUEUE_KEY};
+    use crate::{assert_let_timeout, test_utils::mocks::MatrixMockServer};
+
+    fn file_request() -> MediaRequestParameters {
+        MediaRequestParameters {
+            source: MediaSource::Plain(mxc_uri!("mxc://example.org/file").to_owned()),
+            format: MediaFormat::File,
+        }
+    }
+
+    #[async_test]
+    async fn test_resume_partial_download() {
+        let server = MatrixMockServer::new().await;
+        let client = server.client_builder().build().await;
+        let request = file_request();
+
+        // A previous attempt downloaded the start of the file before a restart, and
+        // wrote some more bytes after the offset was persisted.
+        let (_, path) = NamedTempFile::new().unwrap().keep().unwrap();
+        std::fs::write(&path, b"Hello, Wor").unwrap();
+
+        let persisted = serde_json::json!([{
+            "request": request,
+            "priority": "Normal",
+            "partial": { "path": path, "offset": 7 },
+        }]);
+        client
+            .store()
+            .set_custom_value_no_read(PERSISTED_QUEUE_KEY, serde_json::to_vec(&persisted).unwrap())
+            .await
+            .unwrap();
+
+        server
+            .mock_authenticated_media_download()
+            .match_range_offset(7)
+            .ok_partial(b"World!".to_vec())
+            .mock_once()
+            .mount()
+            .await;
+
+        let manager = client.media().download_manager();
+        let mut updates = manager.subscribe();
+        // Listing the downloads restores the queue.
+        assert_eq!(manager.downloads().await.len(), 1);
+
+        assert_let_timeout!(Ok(MediaDownloadUpdate::Progress { progress, .. }) = updates.recv());
+        assert_eq!(progress.current, 13);
+        assert_eq!(progress.total, 13);
+        assert_let_timeout!(Ok(MediaDownloadUpdate::Finished { .. }) = updates.recv());
+
+        let cache_store = client.event_cache_store().lock().await.unwrap();
+        assert_eq!(
+            cache_store.get_media_content(&request).await.unwrap().as_deref(),
+            Some(&b"Hello, World!"[..])
+        );
+        // The temporary file has been removed.
+        assert!(!path.exists());
+    }
+
+    #[async_test]
+    async fn test_restore_persisted_downloads() {
+        let server = MatrixMockServer::new().await;
+        let client = server.client_builder().build().await;
+        let request = file_request();
+
+        // The download was queued before a restart.
+        let persisted = serde_json::json!([{ "request": request, "priority": "Normal" }]);
+        client
+            .store()
+            .set_custom_value_no_read(PERSISTED_QUEUE_KEY, serde_json::to_vec(&persisted).unwrap())
+            .await
+            .unwrap();
+
+        server
+            .mock_authenticated_media_download()
+            .ok(b"Hello, World!".to_vec())
+            .mock_once()
+            .mount()
+            .await;
+
+        let manager = client.media().download_manager();
+        let mut updates = manager.subscribe();
+
+        // Listing the downloads restores the queue.
+        let downloads = manager.downloads().await;
+        assert_eq!(downloads.len(), 1);
+        assert_eq!(downloads[0].request.uri(), request.uri());
+
+        loop {
+            assert_let_timeout!(Ok(update) = updates.recv());
+            if let MediaDownloadUpdate::Finished { request: finished } = update {
+                assert_eq!(finished.uri(), request.uri());
+                break;
+            }
+        }
+
+        assert!(manager.downloads().await.is_empty());
+        let persisted = client.store().get_custom_value(PERSISTED_QUEUE_KEY).await.unwrap();
+        assert_eq!(persisted.as_deref(), Some(&b"[]"[..]));
+    }
+
+    #[async_test]
+    async fn test_cancel_active_download() {
+        let server = MatrixMockServer::new().await;
+        let client = server.client_builder().build().await;
+        let request = file_request();
+
+        // The media is only requested once, the cancelled download isn't picked up
+        // again by another task.
+        server
+            .mock_authenticated_media_download()
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .set_body_raw(b"Hello, World!".to_vec(), "application/octet-stream")
+                    .set_delay(Duration::from_secs(2)),
+            )
+            .mock_once()
+            .mount()
+            .await;
+
+        let manager = client.media().download_manager();
+        let mut updates = manager.subscribe();
+        let handle = manager.enqueue(request.clone(), MediaDownloadPriority::Normal).await;
+
+        while !manager.downloads().await.first().is_some_and(|download| download.is_active) {
+            sleep(Duration::from_millis(10)).await;
+        }
+
+        assert!(handle.cancel().await);
+        // The download is being cancelled, so it isn't active anymore.
+        assert!(manager.downloads().await.iter().all(|download| !download.is_active));
+        assert!(handle.cancel().await);
+
+        assert_let_timeout!(Ok(MediaDownloadUpdate::Cancelled { .. }) = updates.recv());
+        assert!(manager.downloads().await.is_empty());
+        assert!(!handle.cancel().await);
+    }
+
+    #[async_test]
+    async fn test_download_too_big_to_cache() {
+        let server = MatrixMockServer::new().await;
+        let client = server.client_builder().build().await;
+        let request = file_request();
+
+        client
+            .media()
+            .set_media_retention_policy(MediaRetentionPolicy::empty().with_max_file_size(Some(5)))
+            .await
+            .unwrap();
+
+        server
+            .mock_authenticated_media_download()
+            .ok(b"Hello, World!".to_vec())
+            .mock_once()
+            .mount()
+            .await;
+
+        let manager = client.media().download_manager();
+        let mut updates = manager.subscribe();
+        manager.enqueue(request.clone(), MediaDownloadPriority::Normal).await;
+
+        loop {
+            assert_let_timeout!(Ok(update) = updates.recv());
+            if matches!(update, MediaDownloadUpdate::Finished { .. }) {
+                break;
+            }
+        }
+
+        // The media is bigger than the maximum file size, it wasn't cached.
+        let cache_store = client.event_cache_store().lock().await.unwrap();
+        assert!(cache_store.get_media_content(&request).await.unwrap().is_none());
+    }
+}
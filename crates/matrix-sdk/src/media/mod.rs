@@ -24,7 +24,13 @@ use std::{fmt, fs::File, path::Path};
 use eyeball::SharedObservable;
 use futures_util::future::try_join;
 use matrix_sdk_base::event_cache::store::media::IgnoreMediaRetentionPolicy;
-pub use matrix_sdk_base::{event_cache::store::media::MediaRetentionPolicy, media::*};
+pub use matrix_sdk_base::{
+    event_cache::store::media::{
+        MediaCacheEntry, MediaCacheUsage, MediaCacheUsageStats, MediaKind, MediaOrigin,
+        MediaRetentionPolicy, MediaRetentionScope, ScopedMediaRetentionPolicies,
+    },
+    media::*,
+};
 use mime::Mime;
 use ruma::{
     api::{
@@ -32,23 +38,43 @@ use ruma::{
         MatrixVersion,
     },
     assign,
-    events::room::{MediaSource, ThumbnailInfo},
-    MilliSecondsSinceUnixEpoch, MxcUri, OwnedMxcUri, TransactionId, UInt,
+    events::room::{EncryptedFile, MediaSource, ThumbnailInfo},
+    MilliSecondsSinceUnixEpoch, MxcUri, OwnedMxcUri, OwnedRoomId, RoomId, TransactionId, UInt,
 };
 #[cfg(not(target_arch = "wasm32"))]
 use tempfile::{Builder as TempFileBuilder, NamedTempFile, TempDir};
 #[cfg(not(target_arch = "wasm32"))]
-use tokio::{fs::File as TokioFile, io::AsyncWriteExt};
+use tokio::{
+    fs::File as TokioFile,
+    io::{AsyncReadExt, AsyncWrite, AsyncWriteExt},
+};
+#[cfg(not(target_arch = "wasm32"))]
+use tracing::debug;
 
+#[cfg(not(target_arch = "wasm32"))]
+use self::download_manager::MediaDownloadManager;
+#[cfg(not(target_arch = "wasm32"))]
+use crate::client::WeakClient;
 use crate::{
-    attachment::Thumbnail, config::RequestConfig, futures::SendRequest, Client, Error, Result,
-    TransmissionProgress,
+    attachment::Thumbnail, config::RequestConfig, futures::SendRequest, Client, Error, HttpError,
+    Result, TransmissionProgress,
 };
 
+#[cfg(not(target_arch = "wasm32"))]
+pub mod download_manager;
+mod resumable_upload;
+
 /// A conservative upload speed of 1Mbps
 const DEFAULT_UPLOAD_SPEED: u64 = 125_000;
 /// 5 min minimal upload request timeout, used to clamp the request timeout.
 const MIN_UPLOAD_REQUEST_TIMEOUT: Duration = Duration::from_secs(60 * 5);
+/// The size of the chunks read from a file while it is uploaded.
+#[cfg(not(target_arch = "wasm32"))]
+const UPLOAD_FILE_CHUNK_SIZE: usize = 64 * 1024;
+/// The maximum size of a media file downloaded with [`Media::get_media_file()`]
+/// that is added to the media cache, 20 MiB.
+#[cfg(not(target_arch = "wasm32"))]
+const MAX_CACHED_MEDIA_FILE_SIZE: u64 = 20 * 1024 * 1024;
 /// The server name used to generate local MXC URIs.
 // This mustn't represent a potentially valid media server, otherwise it'd be
 // possible for an attacker to return malicious content under some
@@ -64,6 +90,8 @@ const LOCAL_MXC_SERVER_NAME: &str = "send-queue.localhost";
 pub struct Media {
     /// The underlying HTTP client.
     client: Client,
+    /// The room of the media, if this is the media manager of a room.
+    room_id: Option<OwnedRoomId>,
 }
 
 /// A file handle that takes ownership of a media file on disk. When the handle
@@ -142,6 +170,11 @@ pub enum MediaError {
     /// Local-only media content was not found.
     #[error("local-only media content was not found")]
     LocalMediaNotFound,
+
+    /// The client was dropped while a media was being downloaded in the
+    /// background.
+    #[error("the client was dropped")]
+    ClientDropped,
 }
 
 /// `IntoFuture` returned by [`Media::upload`].
@@ -149,7 +182,17 @@ pub type SendUploadRequest = SendRequest<media::create_content::v3::Request>;
 
 impl Media {
     pub(crate) fn new(client: Client) -> Self {
-        Self { client }
+        Self { client, room_id: None }
+    }
+
+    /// Create the media manager of the given room.
+    pub(crate) fn for_room(client: Client, room_id: OwnedRoomId) -> Self {
+        Self { client, room_id: Some(room_id) }
+    }
+
+    /// The origin of the media content fetched with this media manager.
+    fn origin(&self) -> MediaOrigin {
+        self.room_id.clone().map(MediaOrigin::room).unwrap_or_default()
     }
 
     /// Upload some media to the server.
@@ -203,12 +246,105 @@ impl Media {
     /// Returns a reasonable upload timeout for an upload, based on the size of
     /// the data to be uploaded.
     pub(crate) fn reasonable_upload_timeout(data: &[u8]) -> Duration {
+        Self::reasonable_upload_timeout_for_length(data.len() as u64)
+    }
+
+    /// Returns a reasonable upload timeout for an upload, based on the length
+    /// of the data to be uploaded.
+    pub(crate) fn reasonable_upload_timeout_for_length(length: u64) -> Duration {
         std::cmp::max(
-            Duration::from_secs(data.len() as u64 / DEFAULT_UPLOAD_SPEED),
+            Duration::from_secs(length / DEFAULT_UPLOAD_SPEED),
             MIN_UPLOAD_REQUEST_TIMEOUT,
         )
     }
 
+    /// Upload the content of a file to the server, without loading it in
+    /// memory.
+    ///
+    /// The file is read and sent chunk by chunk, which allows to upload files
+    /// that are too big to fit in memory. A reasonable timeout is inferred from
+    /// the size of the file.
+    ///
+    /// # Arguments
+    ///
+    /// * `content_type` - The type of the media, this will be used as the
+    ///   content-type header.
+    ///
+    /// * `path` - The path of the file to upload.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # use std::path::Path;
+    /// # use matrix_sdk::Client;
+    /// # use url::Url;
+    /// # use mime;
+    /// # async {
+    /// # let homeserver = Url::parse("http://localhost:8080")?;
+    /// # let mut client = Client::new(homeserver).await?;
+    /// let response = client
+    ///     .media()
+    ///     .upload_file(&mime::VIDEO_MP4, Path::new("/home/example/my-cat.mp4"))
+    ///     .await?;
+    ///
+    /// println!("Cat URI: {}", response.content_uri);
+    /// # anyhow::Ok(()) };
+    /// ```
+    #[cfg(not(target_arch = "wasm32"))]
+    pub async fn upload_file(
+        &self,
+        content_type: &Mime,
+        path: &Path,
+    ) -> Result<media::create_content::v3::Response> {
+        self.upload_file_with(content_type, path, |_| {}).await
+    }
+
+    /// Upload the content of a file to the server, chunk by chunk, after
+    /// applying `transform` to every chunk.
+    ///
+    /// The transformation must not change the length of the chunks.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub(crate) async fn upload_file_with(
+        &self,
+        content_type: &Mime,
+        path: &Path,
+        transform: impl FnMut(&mut [u8]) + Send + Sync + 'static,
+    ) -> Result<media::create_content::v3::Response> {
+        let file = TokioFile::open(path).await?;
+        let length = file.metadata().await?.len();
+
+        let body = futures_util::stream::try_unfold(
+            (file, transform),
+            |(mut file, mut transform)| async move {
+                let mut chunk = vec![0; UPLOAD_FILE_CHUNK_SIZE];
+                let read_bytes = file.read(&mut chunk).await?;
+
+                if read_bytes == 0 {
+                    return Ok(None);
+                }
+
+                chunk.truncate(read_bytes);
+                transform(&mut chunk);
+
+                Ok::<_, std::io::Error>(Some((chunk, (file, transform))))
+            },
+        );
+
+        // The content of the file replaces the empty body of the request.
+        let request = assign!(media::create_content::v3::Request::new(Vec::new()), {
+            content_type: Some(content_type.essence_str().to_owned()),
+        });
+        let request_config = self
+            .client
+            .request_config()
+            .timeout(Self::reasonable_upload_timeout_for_length(length));
+
+        Ok(self
+            .client
+            .send_with_body(request, reqwest::Body::wrap_stream(body), length, Some(request_config))
+            .await?)
+    }
+
     /// Preallocates an MXC URI for a media that will be uploaded soon.
     ///
     /// This preallocates an URI *before* any content is uploaded to the server.
@@ -299,6 +435,118 @@ impl Media {
         }
     }
 
+    /// Whether the homeserver supports uploading the content of preallocated
+    /// MXC URIs in several chunks, with [`Media::upload_preallocated_chunk()`].
+    ///
+    /// There is no MSC for resumable uploads yet, so this relies on a
+    /// homeserver advertising the `org.matrix.resumable_upload` unstable
+    /// feature, which accepts [tus]-style offsets.
+    ///
+    /// [tus]: https://tus.io/protocols/resumable-upload
+    pub async fn supports_resumable_uploads(&self) -> Result<bool> {
+        Ok(self
+            .client
+            .unstable_features()
+            .await?
+            .get(resumable_upload::UNSTABLE_FEATURE)
+            .copied()
+            .unwrap_or(false))
+    }
+
+    /// Get the number of bytes of the content of a preallocated MXC URI that
+    /// the homeserver has received, with
+    /// [`Media::upload_preallocated_chunk()`].
+    ///
+    /// This is the offset at which an interrupted upload must be resumed.
+    pub async fn resumable_upload_offset(&self, uri: &MxcUri) -> Result<u64> {
+        let request = resumable_upload::get_upload_offset::Request::from_url(uri)?;
+        Ok(self.client.send(request).await?.offset)
+    }
+
+    /// Upload a chunk of the content of a preallocated MXC URI, at the given
+    /// offset.
+    ///
+    /// The URI must have been preallocated with [`Self::create_content_uri`],
+    /// and the homeserver must support resumable uploads (see
+    /// [`Self::supports_resumable_uploads()`]). The media is complete once all
+    /// its `total_length` bytes have been uploaded.
+    ///
+    /// If `request_config` is not provided, a reasonable timeout is inferred
+    /// from the size of the chunk.
+    ///
+    /// Returns the offset at which the next chunk must be uploaded.
+    pub async fn upload_preallocated_chunk(
+        &self,
+        uri: &MxcUri,
+        content_type: &Mime,
+        offset: u64,
+        total_length: u64,
+        chunk: Vec<u8>,
+        request_config: Option<RequestConfig>,
+    ) -> Result<u64> {
+        let request_config = request_config.unwrap_or_else(|| {
+            self.client.request_config().timeout(Self::reasonable_upload_timeout(&chunk))
+        });
+
+        let request = resumable_upload::upload_chunk::Request::new(
+            uri,
+            content_type.essence_str().to_owned(),
+            offset,
+            total_length,
+            chunk,
+        )?;
+
+        Ok(self.client.send(request).with_request_config(request_config).await?.offset)
+    }
+
+    /// Get the manager of the media downloads running in the background.
+    ///
+    /// The first time this is called, the manager spawns its background tasks,
+    /// and restarts the downloads that were pending when the client was last
+    /// shut down.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn download_manager(&self) -> MediaDownloadManager {
+        self.client
+            .inner
+            .media_download_manager
+            .get_or_init(|| MediaDownloadManager::new(WeakClient::from_client(&self.client)))
+            .for_room(self.room_id.clone())
+    }
+
+    /// Download the content of a media from the given offset, with an HTTP
+    /// range request.
+    ///
+    /// The homeserver may ignore the range and respond with the whole content,
+    /// which can be detected with the status code of the response: it is `206
+    /// Partial Content` only if the range was honored.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub(crate) async fn download_content_from_offset(
+        &self,
+        uri: &MxcUri,
+        offset: u64,
+    ) -> Result<reqwest::Response> {
+        let mut headers = http::HeaderMap::new();
+        if offset > 0 {
+            headers.insert(
+                http::header::RANGE,
+                format!("bytes={offset}-").try_into().expect("the range is a valid header value"),
+            );
+        }
+
+        let (use_auth, request_config) = self.authenticated_media_config().await?;
+
+        let response = if use_auth {
+            let request = authenticated_media::get_content::v1::Request::from_uri(uri)?;
+            self.client.send_streaming(request, request_config, headers).await?
+        } else {
+            #[allow(deprecated)]
+            let request = media::get_content::v3::Request::from_url(uri)?;
+            self.client.send_streaming(request, None, headers).await?
+        };
+
+        Ok(response)
+    }
+
     /// Gets a media file by copying it to a temporary location on disk.
     ///
     /// The file won't be encrypted even if it is encrypted on the server.
@@ -318,7 +566,9 @@ impl Media {
     /// * `content_type` - The type of the media, this will be used to set the
     ///   temporary file's extension when one isn't included in the filename.
     ///
-    /// * `use_cache` - If we should use the media cache for this request.
+    /// * `use_cache` - If we should use the media cache for this request. The
+    ///   file is always streamed to disk, and it is only added to the cache
+    ///   afterwards if it is small enough.
     ///
     /// * `temp_dir` - Path to a directory where temporary directories can be
     ///   created. If not provided, a default, global temporary directory will
@@ -333,8 +583,6 @@ impl Media {
         use_cache: bool,
         temp_dir: Option<String>,
     ) -> Result<MediaFileHandle> {
-        let data = self.get_media_content(request, use_cache).await?;
-
         let inferred_extension = mime2ext::mime2ext(content_type);
 
         let filename_as_path = filename.as_ref().map(Path::new);
@@ -383,18 +631,177 @@ impl Media {
             };
 
         let mut file = TokioFile::from_std(temp_file.reopen()?);
-        file.write_all(&data).await?;
+
+        let is_local = Self::as_local_uri(&request.source).is_some();
+        let cached_content = if use_cache && !is_local {
+            self.client.event_cache_store().lock().await?.get_media_content(request).await?
+        } else {
+            None
+        };
+
+        if let Some(content) = cached_content {
+            file.write_all(&content).await?;
+        } else {
+            // The temporary file is removed when it's dropped, so nothing is left on disk
+            // if the download fails.
+            let size = self.stream_media_content(request, &mut file).await?;
+
+            if use_cache && !is_local {
+                self.cache_media_file(request, temp_file.path(), size, content_type).await?;
+            }
+        }
+
         // Make sure the file metadata is flushed to disk.
         file.sync_all().await?;
 
         Ok(MediaFileHandle { file: temp_file, _directory: temp_dir })
     }
 
+    /// The maximum size of a media file that is read back to be added to the
+    /// media cache.
+    ///
+    /// It is the maximum file size of the media retention policy, but never
+    /// above [`MAX_CACHED_MEDIA_FILE_SIZE`].
+    #[cfg(not(target_arch = "wasm32"))]
+    pub(super) async fn max_cached_media_file_size(&self) -> Result<u64> {
+        Ok(self
+            .media_retention_policy()
+            .await?
+            .computed_max_file_size()
+            .map_or(MAX_CACHED_MEDIA_FILE_SIZE, |max_size| {
+                (max_size as u64).min(MAX_CACHED_MEDIA_FILE_SIZE)
+            }))
+    }
+
+    /// Add the content of a downloaded media file to the media cache.
+    ///
+    /// The file is only read back if its size is below the maximum file size
+    /// of the media retention policy, and never above
+    /// [`MAX_CACHED_MEDIA_FILE_SIZE`], so big files are not loaded in memory.
+    #[cfg(not(target_arch = "wasm32"))]
+    async fn cache_media_file(
+        &self,
+        request: &MediaRequestParameters,
+        path: &Path,
+        size: u64,
+        content_type: &Mime,
+    ) -> Result<()> {
+        let max_size = self.max_cached_media_file_size().await?;
+
+        if size > max_size {
+            debug!(size, max_size, "Not caching media file, it is too big");
+            return Ok(());
+        }
+
+        let content = tokio::fs::read(path).await?;
+        let origin = self.origin().with_content_type(content_type.essence_str());
+
+        self.client
+            .event_cache_store()
+            .lock()
+            .await?
+            .add_media_content_with_origin(
+                request,
+                content,
+                &origin,
+                IgnoreMediaRetentionPolicy::No,
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    /// Download a media file's content and write it to the given writer, as it
+    /// is received.
+    ///
+    /// Contrary to [`Media::get_media_content()`], the content is never held
+    /// in memory at once, which allows to download media files that are too
+    /// big to fit in memory. The media cache is not used.
+    ///
+    /// If the content is encrypted and encryption is enabled, the content is
+    /// decrypted chunk by chunk, and its integrity is verified once it has
+    /// been fully downloaded.
+    ///
+    /// If this returns an error, the data written so far must be discarded:
+    /// it might be incomplete, or might have been tampered with.
+    ///
+    /// Returns the number of bytes written.
+    ///
+    /// # Arguments
+    ///
+    /// * `request` - The `MediaRequest` of the content.
+    ///
+    /// * `writer` - The destination of the content.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub async fn stream_media_content<W>(
+        &self,
+        request: &MediaRequestParameters,
+        writer: &mut W,
+    ) -> Result<u64>
+    where
+        W: AsyncWrite + Unpin,
+    {
+        let is_local = Self::as_local_uri(&request.source).is_some();
+        let is_thumbnail = matches!(request.format, MediaFormat::Thumbnail(_));
+
+        let uri = match &request.source {
+            MediaSource::Plain(uri) if !is_local && !is_thumbnail => uri,
+            MediaSource::Encrypted(file) if !is_local => &file.url,
+            // Local medias are only available in the media cache, and thumbnails are small
+            // so they don't need to be streamed.
+            _ => {
+                let content = self.get_media_content(request, false).await?;
+                writer.write_all(&content).await?;
+                writer.flush().await?;
+
+                return Ok(content.len() as u64);
+            }
+        };
+
+        #[cfg(feature = "e2e-encryption")]
+        let mut decryptor = match &request.source {
+            MediaSource::Encrypted(file) => {
+                Some(matrix_sdk_base::crypto::AttachmentChunkDecryptor::new(
+                    file.as_ref().clone().into(),
+                )?)
+            }
+            MediaSource::Plain(_) => None,
+        };
+
+        let mut response = self.download_content_from_offset(uri, 0).await?;
+        let mut written_bytes = 0;
+
+        while let Some(chunk) = response.chunk().await.map_err(HttpError::Reqwest)? {
+            #[cfg_attr(not(feature = "e2e-encryption"), allow(unused_mut))]
+            let mut chunk = chunk.to_vec();
+
+            #[cfg(feature = "e2e-encryption")]
+            if let Some(decryptor) = &mut decryptor {
+                decryptor.decrypt_chunk(&mut chunk);
+            }
+
+            writer.write_all(&chunk).await?;
+            written_bytes += chunk.len() as u64;
+        }
+
+        #[cfg(feature = "e2e-encryption")]
+        if let Some(decryptor) = decryptor {
+            decryptor.finish()?;
+        }
+
+        writer.flush().await?;
+
+        Ok(written_bytes)
+    }
+
     /// Get a media file's content.
     ///
     /// If the content is encrypted and encryption is enabled, the content will
     /// be decrypted.
     ///
+    /// If this is the media manager of a room, the content is associated to
+    /// the room in the media cache.
+    ///
     /// # Arguments
     ///
     /// * `request` - The `MediaRequest` of the content.
@@ -404,6 +811,28 @@ impl Media {
         &self,
         request: &MediaRequestParameters,
         use_cache: bool,
+    ) -> Result<Vec<u8>> {
+        self.get_media_content_with_origin(request, use_cache, &self.origin()).await
+    }
+
+    /// Get a media file's content, knowing where it comes from.
+    ///
+    /// This is the same as [`Media::get_media_content()`], except that the
+    /// content is added to the media cache with its origin, so the
+    /// [`MediaRetentionPolicy`] scoped to its room or its kind applies to it.
+    ///
+    /// # Arguments
+    ///
+    /// * `request` - The `MediaRequest` of the content.
+    ///
+    /// * `use_cache` - If we should use the media cache for this request.
+    ///
+    /// * `origin` - Where the media comes from.
+    pub async fn get_media_content_with_origin(
+        &self,
+        request: &MediaRequestParameters,
+        use_cache: bool,
+        origin: &MediaOrigin,
     ) -> Result<Vec<u8>> {
         // Ignore request parameters for local medias, notably those pending in the send
         // queue.
@@ -420,29 +849,9 @@ impl Media {
             }
         };
 
-        // Use the authenticated endpoints when the server supports Matrix 1.11 or the
-        // authenticated media stable feature.
-        const AUTHENTICATED_MEDIA_STABLE_FEATURE: &str = "org.matrix.msc3916.stable";
-
-        let (use_auth, request_config) =
-            if self.client.server_versions().await?.contains(&MatrixVersion::V1_11) {
-                (true, None)
-            } else if self
-                .client
-                .unstable_features()
-                .await?
-                .get(AUTHENTICATED_MEDIA_STABLE_FEATURE)
-                .is_some_and(|is_supported| *is_supported)
-            {
-                // We need to force the use of the stable endpoint with the Matrix version
-                // because Ruma does not handle stable features.
-                let request_config = self.client.request_config();
-                (true, Some(request_config.force_matrix_version(MatrixVersion::V1_11)))
-            } else {
-                (false, None)
-            };
+        let (use_auth, request_config) = self.authenticated_media_config().await?;
 
-        let content: Vec<u8> = match &request.source {
+        let (content, content_type) = match &request.source {
             MediaSource::Encrypted(file) => {
                 let content = if use_auth {
                     let request =
@@ -454,25 +863,9 @@ impl Media {
                     self.client.send(request).await?.file
                 };
 
-                #[cfg(feature = "e2e-encryption")]
-                let content = {
-                    let content_len = content.len();
-                    let mut cursor = std::io::Cursor::new(content);
-                    let mut reader = matrix_sdk_base::crypto::AttachmentDecryptor::new(
-                        &mut cursor,
-                        file.as_ref().clone().into(),
-                    )?;
-
-                    // Encrypted size should be the same as the decrypted size,
-                    // rounded up to a cipher block.
-                    let mut decrypted = Vec::with_capacity(content_len);
-
-                    reader.read_to_end(&mut decrypted)?;
-
-                    decrypted
-                };
-
-                content
+                // The content type returned by the homeserver is the one of the encrypted
+                // content, it's meaningless.
+                (Self::decrypt_content(file, content)?, None)
             }
 
             MediaSource::Plain(uri) => {
@@ -487,7 +880,9 @@ impl Media {
                         request.method = Some(settings.method.clone());
                         request.animated = Some(settings.animated);
 
-                        self.client.send(request).with_request_config(request_config).await?.file
+                        let response =
+                            self.client.send(request).with_request_config(request_config).await?;
+                        (response.file, response.content_type)
                     } else {
                         #[allow(deprecated)]
                         let request = {
@@ -501,31 +896,96 @@ impl Media {
                             request
                         };
 
-                        self.client.send(request).await?.file
+                        let response = self.client.send(request).await?;
+                        (response.file, response.content_type)
                     }
                 } else if use_auth {
                     let request = authenticated_media::get_content::v1::Request::from_uri(uri)?;
-                    self.client.send(request).with_request_config(request_config).await?.file
+                    let response =
+                        self.client.send(request).with_request_config(request_config).await?;
+                    (response.file, response.content_type)
                 } else {
                     #[allow(deprecated)]
                     let request = media::get_content::v3::Request::from_url(uri)?;
-                    self.client.send(request).await?.file
+                    let response = self.client.send(request).await?;
+                    (response.file, response.content_type)
                 }
             }
         };
 
         if use_cache {
+            let mut origin = origin.clone();
+            if origin.content_type.is_none() {
+                origin.content_type = content_type;
+            }
+
             self.client
                 .event_cache_store()
                 .lock()
                 .await?
-                .add_media_content(request, content.clone(), IgnoreMediaRetentionPolicy::No)
+                .add_media_content_with_origin(
+                    request,
+                    content.clone(),
+                    &origin,
+                    IgnoreMediaRetentionPolicy::No,
+                )
                 .await?;
         }
 
         Ok(content)
     }
 
+    /// Whether the authenticated media endpoints must be used, along with the
+    /// request config needed to use them, if any.
+    async fn authenticated_media_config(&self) -> Result<(bool, Option<RequestConfig>)> {
+        // Use the authenticated endpoints when the server supports Matrix 1.11 or the
+        // authenticated media stable feature.
+        const AUTHENTICATED_MEDIA_STABLE_FEATURE: &str = "org.matrix.msc3916.stable";
+
+        if self.client.server_versions().await?.contains(&MatrixVersion::V1_11) {
+            Ok((true, None))
+        } else if self
+            .client
+            .unstable_features()
+            .await?
+            .get(AUTHENTICATED_MEDIA_STABLE_FEATURE)
+            .is_some_and(|is_supported| *is_supported)
+        {
+            // We need to force the use of the stable endpoint with the Matrix version
+            // because Ruma does not handle stable features.
+            let request_config = self.client.request_config();
+            Ok((true, Some(request_config.force_matrix_version(MatrixVersion::V1_11))))
+        } else {
+            Ok((false, None))
+        }
+    }
+
+    /// Decrypt the downloaded content of an encrypted media.
+    ///
+    /// If encryption is disabled, the content is returned as-is.
+    #[cfg_attr(not(feature = "e2e-encryption"), allow(unused_variables))]
+    pub(crate) fn decrypt_content(file: &EncryptedFile, content: Vec<u8>) -> Result<Vec<u8>> {
+        #[cfg(feature = "e2e-encryption")]
+        let content = {
+            let content_len = content.len();
+            let mut cursor = std::io::Cursor::new(content);
+            let mut reader = matrix_sdk_base::crypto::AttachmentDecryptor::new(
+                &mut cursor,
+                file.clone().into(),
+            )?;
+
+            // Encrypted size should be the same as the decrypted size,
+            // rounded up to a cipher block.
+            let mut decrypted = Vec::with_capacity(content_len);
+
+            reader.read_to_end(&mut decrypted)?;
+
+            decrypted
+        };
+
+        Ok(content)
+    }
+
     /// Get a media file's content that is only available in the media cache.
     ///
     /// # Arguments
@@ -702,6 +1162,133 @@ impl Media {
         Ok(self.client.event_cache_store().lock().await?.media_retention_policy())
     }
 
+    /// Set the `MediaRetentionPolicy` of a subset of the media cache.
+    ///
+    /// It takes precedence over the global policy set with
+    /// [`Media::set_media_retention_policy()`] for the media content in that
+    /// scope. The media content in a room is only in the scope of that room
+    /// if it was fetched with the media manager of the room, returned by
+    /// [`Room::media()`], or with [`Media::get_media_content_with_origin()`].
+    ///
+    /// [`Room::media()`]: crate::Room::media
+    ///
+    /// # Arguments
+    ///
+    /// * `scope` - The subset of the media cache the policy applies to.
+    ///
+    /// * `policy` - The `MediaRetentionPolicy` to use for this scope, or `None`
+    ///   to use the global policy again.
+    pub async fn set_scoped_media_retention_policy(
+        &self,
+        scope: MediaRetentionScope,
+        policy: Option<MediaRetentionPolicy>,
+    ) -> Result<()> {
+        self.client
+            .event_cache_store()
+            .lock()
+            .await?
+            .set_scoped_media_retention_policy(scope, policy)
+            .await?;
+        Ok(())
+    }
+
+    /// Get the current scoped `MediaRetentionPolicy`s.
+    pub async fn scoped_media_retention_policies(&self) -> Result<ScopedMediaRetentionPolicies> {
+        Ok(self.client.event_cache_store().lock().await?.scoped_media_retention_policies())
+    }
+
+    /// Pin a media file's content in the media cache.
+    ///
+    /// The content is downloaded if it's not in the media cache yet, and it
+    /// will be kept in the cache regardless of the `MediaRetentionPolicy`
+    /// until [`Media::unpin_media()`] is called.
+    ///
+    /// # Arguments
+    ///
+    /// * `request` - The `MediaRequest` of the content.
+    pub async fn pin_media(&self, request: &MediaRequestParameters) -> Result<()> {
+        {
+            let cache_store = self.client.event_cache_store().lock().await?;
+
+            if cache_store.get_media_content(request).await?.is_some() {
+                cache_store.set_media_pinned(request, true).await?;
+                return Ok(());
+            }
+        }
+
+        let content = self.get_media_content(request, false).await?;
+
+        let cache_store = self.client.event_cache_store().lock().await?;
+        // Ignore the policy to make sure that the content is stored, even if it
+        // exceeds the max file size, then pin it.
+        cache_store
+            .add_media_content_with_origin(
+                request,
+                content,
+                &self.origin(),
+                IgnoreMediaRetentionPolicy::Yes,
+            )
+            .await?;
+        cache_store.set_media_pinned(request, true).await?;
+        cache_store
+            .set_ignore_media_retention_policy(request, IgnoreMediaRetentionPolicy::No)
+            .await?;
+
+        Ok(())
+    }
+
+    /// Unpin a media file's content that was pinned with
+    /// [`Media::pin_media()`].
+    ///
+    /// The content is subject to the `MediaRetentionPolicy` again.
+    ///
+    /// # Arguments
+    ///
+    /// * `request` - The `MediaRequest` of the content.
+    pub async fn unpin_media(&self, request: &MediaRequestParameters) -> Result<()> {
+        self.client.event_cache_store().lock().await?.set_media_pinned(request, false).await?;
+        Ok(())
+    }
+
+    /// Get the usage of the media cache, as the size and number of media
+    /// contents, in total, per room, per kind of media and per MIME type.
+    ///
+    /// Only the media contents that were fetched with the media manager of a
+    /// room, returned by [`Room::media()`], or with
+    /// [`Media::get_media_content_with_origin()`] are associated to a room.
+    /// The MIME type of a media content is known if the homeserver returned it
+    /// when it was downloaded, or if it was uploaded by this client.
+    ///
+    /// [`Room::media()`]: crate::Room::media
+    pub async fn media_cache_usage(&self) -> Result<MediaCacheUsage> {
+        Ok(self.client.event_cache_store().lock().await?.media_cache_usage().await?)
+    }
+
+    /// Get the list of all the media contents in the media cache.
+    ///
+    /// This can be used to find the biggest media contents, with
+    /// [`MediaCacheEntry::size`], and to remove them selectively with
+    /// [`Media::remove_media_content_for_uri()`].
+    pub async fn media_cache_entries(&self) -> Result<Vec<MediaCacheEntry>> {
+        Ok(self.client.event_cache_store().lock().await?.media_cache_entries().await?)
+    }
+
+    /// Remove all the media contents that were found in the given room from
+    /// the media cache.
+    ///
+    /// # Arguments
+    ///
+    /// * `room_id` - The ID of the room.
+    pub async fn remove_media_content_for_room(&self, room_id: &RoomId) -> Result<()> {
+        Ok(self
+            .client
+            .event_cache_store()
+            .lock()
+            .await?
+            .remove_media_content_for_room(room_id)
+            .await?)
+    }
+
     /// Clean up the media cache with the current [`MediaRetentionPolicy`].
     ///
     /// If there is already an ongoing cleanup, this is a noop.
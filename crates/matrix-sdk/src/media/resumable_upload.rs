@@ -0,0 +1,300 @@
+// Copyright 2025 The Matrix.org Foundation C.I.C.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Requests to upload the content of a preallocated MXC URI in several chunks,
+//! that can be resumed after a failure.
+//!
+//! There is no MSC for resumable uploads yet. This is a stand-in using
+//! [tus]-style offsets, on an unstable endpoint that is only used when the
+//! homeserver advertises the [`UNSTABLE_FEATURE`].
+//!
+//! [tus]: https://tus.io/protocols/resumable-upload
+
+use bytes::BufMut;
+use http::{header::AUTHORIZATION, HeaderName};
+use ruma::{
+    api::{
+        client::Error,
+        error::{FromHttpResponseError, HeaderDeserializationError, IntoHttpError},
+        EndpointError as _, MatrixVersion, Metadata, SendAccessToken,
+    },
+    OwnedServerName,
+};
+
+/// The unstable feature advertised by homeservers which accept uploading the
+/// content of a preallocated MXC URI in several chunks.
+pub(super) const UNSTABLE_FEATURE: &str = "org.matrix.resumable_upload";
+
+/// The header holding the offset of a chunk, in the requests, or the number of
+/// bytes received by the homeserver, in the responses.
+const UPLOAD_OFFSET: HeaderName = HeaderName::from_static("upload-offset");
+
+/// The header holding the total length of the media.
+const UPLOAD_LENGTH: HeaderName = HeaderName::from_static("upload-length");
+
+/// The header holding the content type of the media, since the `Content-Type`
+/// header of the requests is always `application/offset+octet-stream`.
+const UPLOAD_CONTENT_TYPE: HeaderName = HeaderName::from_static("upload-content-type");
+
+/// Build a request to the endpoint of a resumable upload, with the given body.
+fn http_request<T: Default + BufMut>(
+    metadata: &Metadata,
+    server_name: &OwnedServerName,
+    media_id: &str,
+    base_url: &str,
+    access_token: SendAccessToken<'_>,
+    considering_versions: &[MatrixVersion],
+) -> Result<http::request::Builder, IntoHttpError> {
+    let url = metadata.make_endpoint_url(
+        considering_versions,
+        base_url,
+        &[server_name, &media_id],
+        "",
+    )?;
+    let access_token =
+        access_token.get_required_for_endpoint().ok_or(IntoHttpError::NeedsAuthentication)?;
+
+    Ok(http::Request::builder()
+        .method(metadata.method.clone())
+        .uri(url)
+        .header(AUTHORIZATION, format!("Bearer {access_token}")))
+}
+
+/// Get the upload offset of a response to a resumable upload request, turning
+/// an error response into an API error.
+fn upload_offset<T: AsRef<[u8]>>(
+    response: http::Response<T>,
+) -> Result<u64, FromHttpResponseError<Error>> {
+    if response.status().as_u16() >= 400 {
+        return Err(FromHttpResponseError::Server(Error::from_http_response(response)));
+    }
+
+    // An invalid offset is reported like a missing one.
+    response
+        .headers()
+        .get(UPLOAD_OFFSET)
+        .and_then(|offset| offset.to_str().ok()?.parse().ok())
+        .ok_or_else(|| {
+            FromHttpResponseError::Deserialization(
+                HeaderDeserializationError::MissingHeader(UPLOAD_OFFSET.to_string()).into(),
+            )
+        })
+}
+
+/// `HEAD /_matrix/media/unstable/org.matrix.resumable_upload/upload/
+/// {serverName}/{mediaId}`
+///
+/// Get the number of bytes of the content of a preallocated MXC URI that the
+/// homeserver has received.
+pub(super) mod get_upload_offset {
+    use bytes::BufMut;
+    use ruma::{
+        api::{
+            client::Error,
+            error::{FromHttpResponseError, IntoHttpError},
+            metadata, IncomingResponse, MatrixVersion, Metadata, OutgoingRequest, SendAccessToken,
+        },
+        IdParseError, MxcUri, OwnedServerName,
+    };
+
+    const METADATA: Metadata = metadata! {
+        method: HEAD,
+        rate_limited: true,
+        authentication: AccessToken,
+        history: {
+            unstable => "/_matrix/media/unstable/org.matrix.resumable_upload/upload/{server_name}/{media_id}",
+        }
+    };
+
+    /// Request type for the `get_upload_offset` endpoint.
+    #[derive(Clone, Debug)]
+    pub struct Request {
+        /// The server name from the preallocated MXC URI.
+        pub server_name: OwnedServerName,
+
+        /// The media ID from the preallocated MXC URI.
+        pub media_id: String,
+    }
+
+    impl Request {
+        /// Creates a new `Request` for the given preallocated MXC URI.
+        pub fn from_url(url: &MxcUri) -> Result<Self, IdParseError> {
+            let (server_name, media_id) = url.parts()?;
+            Ok(Self { server_name: server_name.to_owned(), media_id: media_id.to_owned() })
+        }
+    }
+
+    impl OutgoingRequest for Request {
+        type EndpointError = Error;
+        type IncomingResponse = Response;
+
+        const METADATA: Metadata = METADATA;
+
+        fn try_into_http_request<T: Default + BufMut>(
+            self,
+            base_url: &str,
+            access_token: SendAccessToken<'_>,
+            considering_versions: &[MatrixVersion],
+        ) -> Result<http::Request<T>, IntoHttpError> {
+            Ok(super::http_request::<T>(
+                &METADATA,
+                &self.server_name,
+                &self.media_id,
+                base_url,
+                access_token,
+                considering_versions,
+            )?
+            .body(T::default())?)
+        }
+    }
+
+    /// Response type for the `get_upload_offset` endpoint.
+    #[derive(Clone, Debug)]
+    pub struct Response {
+        /// The number of bytes the homeserver has received, which is the
+        /// offset at which the upload must be resumed.
+        pub offset: u64,
+    }
+
+    impl IncomingResponse for Response {
+        type EndpointError = Error;
+
+        fn try_from_http_response<T: AsRef<[u8]>>(
+            response: http::Response<T>,
+        ) -> Result<Self, FromHttpResponseError<Error>> {
+            Ok(Self { offset: super::upload_offset(response)? })
+        }
+    }
+}
+
+/// `PATCH /_matrix/media/unstable/org.matrix.resumable_upload/upload/
+/// {serverName}/{mediaId}`
+///
+/// Upload a chunk of the content of a preallocated MXC URI.
+pub(super) mod upload_chunk {
+    use bytes::BufMut;
+    use http::header::CONTENT_TYPE;
+    use ruma::{
+        api::{
+            client::Error,
+            error::{FromHttpResponseError, IntoHttpError},
+            metadata, IncomingResponse, MatrixVersion, Metadata, OutgoingRequest, SendAccessToken,
+        },
+        IdParseError, MxcUri, OwnedServerName,
+    };
+
+    use super::{UPLOAD_CONTENT_TYPE, UPLOAD_LENGTH, UPLOAD_OFFSET};
+
+    const METADATA: Metadata = metadata! {
+        method: PATCH,
+        rate_limited: true,
+        authentication: AccessToken,
+        history: {
+            unstable => "/_matrix/media/unstable/org.matrix.resumable_upload/upload/{server_name}/{media_id}",
+        }
+    };
+
+    /// Request type for the `upload_chunk` endpoint.
+    #[derive(Clone, Debug)]
+    pub struct Request {
+        /// The server name from the preallocated MXC URI.
+        pub server_name: OwnedServerName,
+
+        /// The media ID from the preallocated MXC URI.
+        pub media_id: String,
+
+        /// The content type of the whole media.
+        pub content_type: String,
+
+        /// The offset of the chunk in the media.
+        pub offset: u64,
+
+        /// The total length of the media.
+        pub total_length: u64,
+
+        /// The bytes of the chunk.
+        pub chunk: Vec<u8>,
+    }
+
+    impl Request {
+        /// Creates a new `Request` to upload the given chunk of the content of
+        /// a preallocated MXC URI.
+        pub fn new(
+            url: &MxcUri,
+            content_type: String,
+            offset: u64,
+            total_length: u64,
+            chunk: Vec<u8>,
+        ) -> Result<Self, IdParseError> {
+            let (server_name, media_id) = url.parts()?;
+            Ok(Self {
+                server_name: server_name.to_owned(),
+                media_id: media_id.to_owned(),
+                content_type,
+                offset,
+                total_length,
+                chunk,
+            })
+        }
+    }
+
+    impl OutgoingRequest for Request {
+        type EndpointError = Error;
+        type IncomingResponse = Response;
+
+        const METADATA: Metadata = METADATA;
+
+        fn try_into_http_request<T: Default + BufMut>(
+            self,
+            base_url: &str,
+            access_token: SendAccessToken<'_>,
+            considering_versions: &[MatrixVersion],
+        ) -> Result<http::Request<T>, IntoHttpError> {
+            let mut body = T::default();
+            body.put_slice(&self.chunk);
+
+            Ok(super::http_request::<T>(
+                &METADATA,
+                &self.server_name,
+                &self.media_id,
+                base_url,
+                access_token,
+                considering_versions,
+            )?
+            .header(CONTENT_TYPE, "application/offset+octet-stream")
+            .header(UPLOAD_CONTENT_TYPE, self.content_type)
+            .header(UPLOAD_OFFSET, self.offset)
+            .header(UPLOAD_LENGTH, self.total_length)
+            .body(body)?)
+        }
+    }
+
+    /// Response type for the `upload_chunk` endpoint.
+    #[derive(Clone, Debug)]
+    pub struct Response {
+        /// The number of bytes the homeserver has received, which is the
+        /// offset of the next chunk.
+        pub offset: u64,
+    }
+
+    impl IncomingResponse for Response {
+        type EndpointError = Error;
+
+        fn try_from_http_response<T: AsRef<[u8]>>(
+            response: http::Response<T>,
+        ) -> Result<Self, FromHttpResponseError<Error>> {
+            Ok(Self { offset: super::upload_offset(response)? })
+        }
+    }
+}
@@ -0,0 +1,523 @@
+// Copyright 2025 The Matrix.org Foundation C.I.C.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Types for searching messages on the server, using the `/search` endpoint.
+
+use std::collections::BTreeMap;
+
+use eyeball_im::{ObservableVector, VectorDiff};
+use futures_core::Stream;
+use futures_util::future::join_all;
+use imbl::Vector;
+use matrix_sdk_common::deserialized_responses::{TimelineEvent, TimelineEventKind};
+use ruma::{
+    api::client::{
+        filter::RoomEventFilter,
+        search::search_events::{
+            self,
+            v3::{Categories, Criteria, EventContext, OrderBy, SearchKeys, UserProfile},
+        },
+    },
+    assign,
+    events::AnyTimelineEvent,
+    serde::Raw,
+    OwnedRoomId, OwnedUserId,
+};
+use tracing::{debug, warn};
+
+use crate::{room_directory_search::SearchState, Client, Result};
+
+/// Options for [`Client::search_messages`] and
+/// [`Room::search`][crate::Room::search].
+///
+/// See <https://spec.matrix.org/v1.13/client-server-api/#post_matrixclientv3search>
+/// for details.
+#[derive(Clone, Debug)]
+#[non_exhaustive]
+pub struct MessageSearchOptions {
+    /// The string to search events for.
+    pub search_term: String,
+
+    /// The keys to search for; defaults to all keys on the server side.
+    pub keys: Option<Vec<SearchKeys>>,
+
+    /// A [`RoomEventFilter`] to apply to the search, e.g. to restrict it to
+    /// some rooms, or to limit the number of results per page.
+    pub filter: RoomEventFilter,
+
+    /// The order in which to search for results; defaults to ordering by rank
+    /// on the server side.
+    pub order_by: Option<OrderBy>,
+
+    /// Configures the number of events to return around each result, and
+    /// whether to include the profiles of their senders.
+    pub event_context: EventContext,
+
+    /// The token to get the next batch of results, as returned in a previous
+    /// [`MessageSearchResponse::next_batch`].
+    pub next_batch: Option<String>,
+}
+
+impl MessageSearchOptions {
+    /// Creates `MessageSearchOptions` to search for the given term.
+    ///
+    /// All other parameters will be defaulted.
+    pub fn new(search_term: impl Into<String>) -> Self {
+        Self {
+            search_term: search_term.into(),
+            keys: None,
+            filter: RoomEventFilter::default(),
+            order_by: None,
+            event_context: EventContext::new(),
+            next_batch: None,
+        }
+    }
+
+    fn into_request(self) -> search_events::v3::Request {
+        let criteria = assign!(Criteria::new(self.search_term), {
+            keys: self.keys,
+            filter: self.filter,
+            order_by: self.order_by,
+            event_context: self.event_context,
+        });
+
+        let mut categories = Categories::new();
+        categories.room_events = Some(criteria);
+
+        assign!(search_events::v3::Request::new(categories), { next_batch: self.next_batch })
+    }
+}
+
+/// A single result of a message search.
+#[derive(Clone, Debug)]
+pub struct MessageSearchResult {
+    /// The ID of the room the event belongs to, if the server included it.
+    pub room_id: Option<OwnedRoomId>,
+
+    /// The event matching the search, decrypted if possible.
+    ///
+    /// If it couldn't be decrypted, it's an
+    /// [`TimelineEventKind::UnableToDecrypt`] event.
+    pub event: TimelineEvent,
+
+    /// A number that describes how closely this result matches the search;
+    /// higher is closer.
+    pub rank: Option<f64>,
+
+    /// The events that happened just before the result, in reverse
+    /// chronological order, decrypted if possible.
+    pub events_before: Vec<TimelineEvent>,
+
+    /// The events that happened just after the result, in chronological
+    /// order, decrypted if possible.
+    pub events_after: Vec<TimelineEvent>,
+
+    /// The historic profiles of the senders of the events, if requested with
+    /// [`EventContext::include_profile`].
+    pub profile_info: BTreeMap<OwnedUserId, UserProfile>,
+}
+
+/// The result of a [`Client::search_messages`] call.
+#[derive(Clone, Debug, Default)]
+pub struct MessageSearchResponse {
+    /// The results of this page of the search.
+    pub results: Vec<MessageSearchResult>,
+
+    /// An approximation of the total number of results, if the server
+    /// provided it.
+    pub count: Option<u64>,
+
+    /// The words that the server used to search, that can be highlighted in
+    /// the results.
+    pub highlights: Vec<String>,
+
+    /// The token to get the next page of results, if there are more.
+    pub next_batch: Option<String>,
+}
+
+/// Run a single request to the `/search` endpoint, and decrypt the results
+/// where possible.
+///
+/// The results that can't be decrypted are kept undecrypted.
+pub(crate) async fn search_messages(
+    client: &Client,
+    options: MessageSearchOptions,
+) -> Result<MessageSearchResponse> {
+    let response = client.send(options.into_request()).await?;
+    let room_events = response.search_categories.room_events;
+
+    let mut results = Vec::with_capacity(room_events.results.len());
+
+    for result in room_events.results {
+        let Some(event) = result.result else {
+            continue;
+        };
+
+        let room_id = event.get_field::<OwnedRoomId>("room_id").ok().flatten();
+        let room = room_id.as_deref().and_then(|room_id| client.get_room(room_id));
+
+        // Decrypt the events with the room they belong to, if we know it. The events
+        // that can't be decrypted are kept as they are, instead of failing the whole
+        // page.
+        let try_decrypt = |event: Raw<AnyTimelineEvent>| {
+            let room = room.clone();
+            async move {
+                let Some(room) = room else {
+                    return TimelineEvent::new(event.cast());
+                };
+
+                match room.try_decrypt_event(event.clone()).await {
+                    Ok(event) => {
+                        if let TimelineEventKind::UnableToDecrypt { utd_info, .. } = &event.kind {
+                            debug!(
+                                room_id = ?room.room_id(),
+                                event_id = ?event.event_id(),
+                                ?utd_info,
+                                "Unable to decrypt a search result"
+                            );
+                        }
+                        event
+                    }
+                    Err(err) => {
+                        warn!(
+                            room_id = ?room.room_id(),
+                            "Failed to decrypt a search result: {err}"
+                        );
+                        TimelineEvent::new(event.cast())
+                    }
+                }
+            }
+        };
+
+        let event = try_decrypt(event).await;
+        let events_before =
+            join_all(result.context.events_before.into_iter().map(&try_decrypt)).await;
+        let events_after =
+            join_all(result.context.events_after.into_iter().map(&try_decrypt)).await;
+
+        results.push(MessageSearchResult {
+            room_id,
+            event,
+            rank: result.rank,
+            events_before,
+            events_after,
+            profile_info: result.context.profile_info,
+        });
+    }
+
+    Ok(MessageSearchResponse {
+        results,
+        count: room_events.count.map(Into::into),
+        highlights: room_events.highlights,
+        next_batch: room_events.next_batch,
+    })
+}
+
+/// `MessageSearch` allows searching messages on the server, and keeps track of
+/// the current state of the search, so that more results can be loaded on
+/// demand.
+///
+/// The results are exposed as an observable list, which gets the new results
+/// appended every time a new page is loaded.
+///
+/// # Example
+///
+/// ```no_run
+/// use matrix_sdk::{
+///     message_search::{MessageSearch, MessageSearchOptions},
+///     Client,
+/// };
+/// use url::Url;
+///
+/// async {
+///     let homeserver = Url::parse("http://localhost:8080")?;
+///     let client = Client::new(homeserver).await?;
+///     let mut message_search = MessageSearch::new(client);
+///     message_search.search(MessageSearchOptions::new("lunch")).await?;
+///     let (results, mut stream) = message_search.results();
+///     message_search.next_page().await?;
+///     anyhow::Ok(())
+/// };
+/// ```
+#[derive(Debug)]
+pub struct MessageSearch {
+    client: Client,
+    options: Option<MessageSearchOptions>,
+    search_state: SearchState,
+    count: Option<u64>,
+    highlights: Vec<String>,
+    results: ObservableVector<MessageSearchResult>,
+}
+
+impl MessageSearch {
+    /// Constructor for the `MessageSearch`, requires a `Client`.
+    pub fn new(client: Client) -> Self {
+        Self {
+            client,
+            options: None,
+            search_state: Default::default(),
+            count: None,
+            highlights: Vec::new(),
+            results: ObservableVector::new(),
+        }
+    }
+
+    /// Starts a new search with the given options.
+    ///
+    /// This method will clear the current search results and start a new one.
+    /// The `next_batch` token of the options is used to fetch the first page,
+    /// if set.
+    // Should never be used concurrently with another `next_page` or a
+    // `search`.
+    pub async fn search(&mut self, options: MessageSearchOptions) -> Result<()> {
+        self.search_state = match &options.next_batch {
+            Some(token) => SearchState::Next(token.clone()),
+            None => SearchState::Start,
+        };
+        self.options = Some(options);
+        self.count = None;
+        self.highlights.clear();
+        self.results.clear();
+        self.next_page().await
+    }
+
+    /// Asks the server for the next page of the current search.
+    ///
+    /// Does nothing if no search has been started, or if the search has
+    /// reached the last page.
+    // Should never be used concurrently with another `next_page` or a
+    // `search`.
+    pub async fn next_page(&mut self) -> Result<()> {
+        if self.search_state.is_at_end() {
+            return Ok(());
+        }
+
+        let Some(options) = &self.options else {
+            return Ok(());
+        };
+
+        let mut options = options.clone();
+        options.next_batch = self.search_state.next_token().map(ToOwned::to_owned);
+
+        let response = search_messages(&self.client, options).await?;
+
+        if let Some(next_token) = response.next_batch {
+            self.search_state = SearchState::Next(next_token);
+        } else {
+            self.search_state = SearchState::End;
+        }
+
+        if response.count.is_some() {
+            self.count = response.count;
+        }
+
+        for highlight in response.highlights {
+            if !self.highlights.contains(&highlight) {
+                self.highlights.push(highlight);
+            }
+        }
+
+        self.results.append(response.results.into_iter().collect());
+        Ok(())
+    }
+
+    /// Get the initial values of the current results of the search, and a
+    /// stream of updates for them.
+    pub fn results(
+        &self,
+    ) -> (Vector<MessageSearchResult>, impl Stream<Item = Vec<VectorDiff<MessageSearchResult>>>)
+    {
+        self.results.subscribe().into_values_and_batched_stream()
+    }
+
+    /// Get an approximation of the total number of results, if the server
+    /// provided it.
+    pub fn count(&self) -> Option<u64> {
+        self.count
+    }
+
+    /// Get the words that the server used to search so far, that can be
+    /// highlighted in the results.
+    pub fn highlights(&self) -> &[String] {
+        &self.highlights
+    }
+
+    /// Get whether the search is at the last page.
+    pub fn is_at_last_page(&self) -> bool {
+        self.search_state.is_at_end()
+    }
+}
+
+#[cfg(all(test, not(target_arch = "wasm32")))]
+mod tests {
+    use assert_matches2::{assert_let, assert_matches};
+    use eyeball_im::VectorDiff;
+    use matrix_sdk_test::{async_test, event_factory::EventFactory, ALICE};
+    use ruma::{event_id, events::AnyTimelineEvent, room_id, serde::Raw};
+    use stream_assert::{assert_next_matches, assert_pending};
+
+    use super::{MessageSearch, MessageSearchOptions};
+    use crate::{test_utils::mocks::MatrixMockServer, Error};
+
+    #[async_test]
+    async fn test_message_search_pagination() {
+        let server = MatrixMockServer::new().await;
+        let client = server.client_builder().build().await;
+
+        let room_id = room_id!("!a:b.c");
+        let f = EventFactory::new().room(room_id).sender(*ALICE);
+
+        server
+            .mock_search()
+            .ok(
+                vec![f.text_msg("lunch?").event_id(event_id!("$1")).into_raw_timeline()],
+                vec!["lunch"],
+                Some("next"),
+            )
+            .mock_once()
+            .mount()
+            .await;
+
+        let mut search = MessageSearch::new(client);
+        search.search(MessageSearchOptions::new("lunch")).await.unwrap();
+
+        let (results, mut stream) = search.results();
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].room_id.as_deref(), Some(room_id));
+        assert_eq!(results[0].event.event_id().as_deref(), Some(event_id!("$1")));
+        assert_eq!(search.highlights(), ["lunch"]);
+        assert!(!search.is_at_last_page());
+
+        server
+            .mock_search()
+            .match_next_batch("next")
+            .ok(
+                vec![f.text_msg("lunch time").event_id(event_id!("$2")).into_raw_timeline()],
+                vec!["lunch"],
+                None,
+            )
+            .mock_once()
+            .mount()
+            .await;
+
+        search.next_page().await.unwrap();
+
+        assert_next_matches!(stream, diffs => {
+            assert_eq!(diffs.len(), 1);
+            assert_let!(VectorDiff::Append { values } = &diffs[0]);
+            assert_eq!(values.len(), 1);
+            assert_eq!(values[0].event.event_id().as_deref(), Some(event_id!("$2")));
+        });
+        assert!(search.is_at_last_page());
+
+        // Once at the last page, no more requests are sent.
+        search.next_page().await.unwrap();
+        assert_pending!(stream);
+    }
+
+    #[async_test]
+    async fn test_room_search_filter() {
+        let server = MatrixMockServer::new().await;
+        let client = server.client_builder().build().await;
+
+        let room_id = room_id!("!a:b.c");
+        let other_room_id = room_id!("!d:e.f");
+        let room = server.sync_joined_room(&client, room_id).await;
+
+        // Without a filter on the rooms, the search is restricted to the room.
+        server
+            .mock_search()
+            .match_rooms(&[room_id])
+            .ok(Vec::<Raw<AnyTimelineEvent>>::new(), vec![], None)
+            .mock_once()
+            .mount()
+            .await;
+
+        room.search(MessageSearchOptions::new("lunch")).await.unwrap();
+
+        // The rooms of the filter are kept if they include the room.
+        server
+            .mock_search()
+            .match_rooms(&[room_id, other_room_id])
+            .ok(Vec::<Raw<AnyTimelineEvent>>::new(), vec![], None)
+            .mock_once()
+            .mount()
+            .await;
+
+        let mut options = MessageSearchOptions::new("lunch");
+        options.filter.rooms = Some(vec![room_id.to_owned(), other_room_id.to_owned()]);
+        room.search(options).await.unwrap();
+
+        // A filter that excludes the room is rejected.
+        let mut options = MessageSearchOptions::new("lunch");
+        options.filter.rooms = Some(vec![other_room_id.to_owned()]);
+        assert_matches!(room.search(options).await, Err(Error::SearchFilterExcludesRoom));
+    }
+
+    #[cfg(feature = "e2e-encryption")]
+    #[async_test]
+    async fn test_message_search_keeps_undecryptable_results() {
+        use matrix_sdk_common::deserialized_responses::TimelineEventKind;
+        use serde_json::json;
+
+        let server = MatrixMockServer::new().await;
+        let client = server.client_builder().build().await;
+
+        let room_id = room_id!("!a:b.c");
+        server.sync_joined_room(&client, room_id).await;
+
+        let f = EventFactory::new().room(room_id).sender(*ALICE);
+
+        let utd_event: Raw<AnyTimelineEvent> = serde_json::from_value(json!({
+            "event_id": "$utd",
+            "room_id": room_id,
+            "origin_server_ts": 1698579035927u64,
+            "sender": *ALICE,
+            "type": "m.room.encrypted",
+            "content": {
+                "algorithm": "m.megolm.v1.aes-sha2",
+                "ciphertext": "AwgAEpABhetEzzZzyYrxtEVUtlJnZtJcURBlQUQJ9irVeklCTs06LwgTMQj61PMUS4Vy\
+                               YOX+PD67+hhU40/8olOww+Ud0m2afjMjC3wFX+4fFfSkoWPVHEmRVucfcdSF1RSB4EmK\
+                               PIP4eo1X6x8kCIMewBvxl2sI9j4VNvDvAN7M3zkLJfFLOFHbBviI4FN7hSFHFeM739Zg\
+                               iwxEs3hIkUXEiAfrobzaMEM/zY7SDrTdyffZndgJo7CZOVhoV6vuaOhmAy4X2t4UnbuV\
+                               JGJjKfV57NAhp8W+9oT7ugwO",
+                "device_id": "KIUVQQSDTM",
+                "sender_key": "LvryVyoCjdONdBCi2vvoSbI34yTOx7YrCFACUEKoXnc",
+                "session_id": "64H7XKokIx0ASkYDHZKlT5zd/Zccz/cQspPNdvnNULA"
+            }
+        }))
+        .unwrap();
+
+        server
+            .mock_search()
+            .ok(
+                vec![utd_event, f.text_msg("lunch?").event_id(event_id!("$1")).into_raw_timeline()],
+                vec!["lunch"],
+                None,
+            )
+            .mock_once()
+            .mount()
+            .await;
+
+        let mut search = MessageSearch::new(client);
+        search.search(MessageSearchOptions::new("lunch")).await.unwrap();
+
+        // The event that couldn't be decrypted is kept, along with the other results.
+        let (results, _) = search.results();
+        assert_eq!(results.len(), 2);
+        assert_eq!(results[0].event.event_id().as_deref(), Some(event_id!("$utd")));
+        assert_matches!(results[0].event.kind, TimelineEventKind::UnableToDecrypt { .. });
+        assert_eq!(results[1].event.event_id().as_deref(), Some(event_id!("$1")));
+    }
+}
@@ -3,7 +3,7 @@ use std::ops::Deref;
 use ruma::events::room::MediaSource;
 
 use crate::{
-    media::{MediaFormat, MediaRequestParameters},
+    media::{MediaFormat, MediaOrigin, MediaRequestParameters},
     BaseRoomMember, Client, Result,
 };
 
@@ -62,7 +62,12 @@ impl RoomMember {
     pub async fn avatar(&self, format: MediaFormat) -> Result<Option<Vec<u8>>> {
         let Some(url) = self.avatar_url() else { return Ok(None) };
         let request = MediaRequestParameters { source: MediaSource::Plain(url.to_owned()), format };
-        Ok(Some(self.client.media().get_media_content(&request, true).await?))
+        Ok(Some(
+            self.client
+                .media()
+                .get_media_content_with_origin(&request, true, &MediaOrigin::avatar())
+                .await?,
+        ))
     }
 
     /// Adds the room member to the current account data's ignore list
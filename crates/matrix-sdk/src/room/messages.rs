@@ -17,15 +17,26 @@ use std::fmt;
 use matrix_sdk_common::{debug::DebugStructExt as _, deserialized_responses::TimelineEvent};
 use ruma::{
     api::{
-        client::{filter::RoomEventFilter, message::get_message_events},
+        client::{
+            filter::RoomEventFilter,
+            message::get_message_events,
+            relations::{
+                get_relating_events, get_relating_events_with_rel_type,
+                get_relating_events_with_rel_type_and_event_type,
+            },
+            threads::get_threads::{self, v1::IncludeThreads},
+        },
         Direction,
     },
     assign,
-    events::AnyStateEvent,
+    events::{relation::RelationType, AnyStateEvent, TimelineEventType},
     serde::Raw,
-    uint, RoomId, UInt,
+    uint, OwnedEventId, RoomId, UInt,
 };
 
+use super::Room;
+use crate::Result;
+
 /// Options for [`messages`][super::Room::messages].
 ///
 /// See that method and
@@ -174,3 +185,197 @@ pub struct EventWithContextResponse {
     /// membership events.
     pub state: Vec<Raw<AnyStateEvent>>,
 }
+
+/// What kind of relations should be included in a [`super::Room::relations`]
+/// query.
+#[derive(Clone, Debug, Default)]
+pub enum IncludeRelations {
+    /// Include all relations.
+    #[default]
+    AllRelations,
+
+    /// Include all relations of a given relation type.
+    RelationsOfType(RelationType),
+
+    /// Include all relations of a given relation type and event type.
+    RelationsOfTypeAndEventType(RelationType, TimelineEventType),
+}
+
+/// Options for [`relations`][super::Room::relations].
+///
+/// See that method and
+/// <https://spec.matrix.org/v1.12/client-server-api/#get_matrixclientv1roomsroomidrelationseventid>
+/// for details.
+#[derive(Clone, Debug, Default)]
+pub struct RelationsOptions {
+    /// The token to start returning events from.
+    ///
+    /// This token can be obtained from a `prev_batch` or `next_batch` token
+    /// returned by a previous `relations` call.
+    pub from: Option<String>,
+
+    /// The direction to return events in.
+    ///
+    /// Defaults to backward.
+    pub dir: Direction,
+
+    /// The maximum number of events to return.
+    ///
+    /// The server will pick a default value if this is not set.
+    pub limit: Option<UInt>,
+
+    /// Which kind of relations should be included in the response.
+    pub include_relations: IncludeRelations,
+
+    /// Whether to include events which relate indirectly to the given event.
+    pub recurse: bool,
+}
+
+impl RelationsOptions {
+    /// Sends the `/relations` request matching these options, for the given
+    /// target event in the given room, and decrypts the returned events if
+    /// needs be.
+    pub(super) async fn send(self, room: &Room, event_id: OwnedEventId) -> Result<Relations> {
+        let room_id = room.room_id().to_owned();
+
+        let (chunk, prev_batch_token, next_batch_token, recursion_depth) = match self
+            .include_relations
+        {
+            IncludeRelations::AllRelations => {
+                let request = assign!(get_relating_events::v1::Request::new(room_id, event_id), {
+                    from: self.from,
+                    dir: self.dir,
+                    limit: self.limit,
+                    recurse: self.recurse,
+                });
+                let response = room.client.send(request).await?;
+                (response.chunk, response.prev_batch, response.next_batch, response.recursion_depth)
+            }
+
+            IncludeRelations::RelationsOfType(rel_type) => {
+                let request = assign!(
+                    get_relating_events_with_rel_type::v1::Request::new(
+                        room_id, event_id, rel_type
+                    ),
+                    {
+                        from: self.from,
+                        dir: self.dir,
+                        limit: self.limit,
+                        recurse: self.recurse,
+                    }
+                );
+                let response = room.client.send(request).await?;
+                (response.chunk, response.prev_batch, response.next_batch, response.recursion_depth)
+            }
+
+            IncludeRelations::RelationsOfTypeAndEventType(rel_type, event_type) => {
+                let request = assign!(
+                    get_relating_events_with_rel_type_and_event_type::v1::Request::new(
+                        room_id, event_id, rel_type, event_type
+                    ),
+                    {
+                        from: self.from,
+                        dir: self.dir,
+                        limit: self.limit,
+                        recurse: self.recurse,
+                    }
+                );
+                let response = room.client.send(request).await?;
+                (response.chunk, response.prev_batch, response.next_batch, response.recursion_depth)
+            }
+        };
+
+        let mut events = Vec::with_capacity(chunk.len());
+        for event in chunk {
+            events.push(room.try_decrypt_event(event.cast()).await?);
+        }
+
+        Ok(Relations { chunk: events, prev_batch_token, next_batch_token, recursion_depth })
+    }
+}
+
+/// The result of a [`super::Room::relations`] call.
+///
+/// This is a wrapper around the response of the `/relations` endpoints, with
+/// events decrypted if needs be.
+#[derive(Debug, Default)]
+pub struct Relations {
+    /// The events related to the target event, in the order requested by the
+    /// direction of the query.
+    pub chunk: Vec<TimelineEvent>,
+
+    /// An opaque string representing a pagination token, to paginate in the
+    /// opposite direction of the query.
+    pub prev_batch_token: Option<String>,
+
+    /// An opaque string representing a pagination token, to continue
+    /// paginating in the same direction as the query.
+    ///
+    /// If this is `None`, there are no more results to fetch.
+    pub next_batch_token: Option<String>,
+
+    /// If `recurse` was set, the depth to which the server recursed.
+    pub recursion_depth: Option<UInt>,
+}
+
+/// Options for [`threads`][super::Room::threads].
+///
+/// See that method and
+/// <https://spec.matrix.org/v1.12/client-server-api/#get_matrixclientv1roomsroomidthreads>
+/// for details.
+#[derive(Clone, Debug, Default)]
+pub struct ListThreadsOptions {
+    /// Which threads to include in the response.
+    ///
+    /// Defaults to all the threads of the room.
+    pub include_threads: IncludeThreads,
+
+    /// The token to start returning thread roots from.
+    ///
+    /// This token can be obtained from the `prev_batch_token` returned by a
+    /// previous `threads` call.
+    pub from: Option<String>,
+
+    /// The maximum number of thread roots to return.
+    ///
+    /// The server will pick a default value if this is not set.
+    pub limit: Option<UInt>,
+}
+
+impl ListThreadsOptions {
+    /// Sends the `/threads` request matching these options, for the given
+    /// room, and decrypts the returned thread roots if needs be.
+    pub(super) async fn send(self, room: &Room) -> Result<ThreadRoots> {
+        let request = assign!(get_threads::v1::Request::new(room.room_id().to_owned()), {
+            from: self.from,
+            include: self.include_threads,
+            limit: self.limit,
+        });
+
+        let response = room.client.send(request).await?;
+
+        let mut chunk = Vec::with_capacity(response.chunk.len());
+        for event in response.chunk {
+            chunk.push(room.try_decrypt_event(event.cast()).await?);
+        }
+
+        Ok(ThreadRoots { chunk, prev_batch_token: response.next_batch })
+    }
+}
+
+/// The result of a [`super::Room::threads`] call.
+#[derive(Debug, Default)]
+pub struct ThreadRoots {
+    /// The thread roots, in reverse chronological order of their latest
+    /// activity.
+    ///
+    /// The server usually bundles a summary of each thread into its root, in
+    /// the `m.relations` field of its unsigned data.
+    pub chunk: Vec<TimelineEvent>,
+
+    /// An opaque string representing a pagination token, to get the next batch
+    /// of thread roots.
+    ///
+    /// If this is `None`, there are no more thread roots to fetch.
+    pub prev_batch_token: Option<String>,
+}
@@ -60,24 +60,27 @@ use ruma::events::{
     SyncMessageLikeEvent,
 };
 use ruma::{
-    api::client::{
-        config::{set_global_account_data, set_room_account_data},
-        context,
-        error::ErrorKind,
-        filter::LazyLoadOptions,
-        membership::{
-            ban_user, forget_room, get_member_events,
-            invite_user::{self, v3::InvitationRecipient},
-            kick_user, leave_room, unban_user, Invite3pid,
+    api::{
+        client::{
+            config::{set_global_account_data, set_room_account_data},
+            context,
+            error::ErrorKind,
+            filter::LazyLoadOptions,
+            membership::{
+                ban_user, forget_room, get_member_events,
+                invite_user::{self, v3::InvitationRecipient},
+                kick_user, leave_room, unban_user, Invite3pid,
+            },
+            message::send_message_event,
+            read_marker::set_read_marker,
+            receipt::create_receipt,
+            redact::redact_event,
+            room::{get_event_by_timestamp, get_room_event, report_content, upgrade_room},
+            state::{get_state_events_for_key, send_state_event},
+            tag::{create_tag, delete_tag},
+            typing::create_typing_event::{self, v3::Typing},
         },
-        message::send_message_event,
-        read_marker::set_read_marker,
-        receipt::create_receipt,
-        redact::redact_event,
-        room::{get_room_event, report_content},
-        state::{get_state_events_for_key, send_state_event},
-        tag::{create_tag, delete_tag},
-        typing::create_typing_event::{self, v3::Typing},
+        Direction,
     },
     assign,
     events::{
@@ -117,8 +120,9 @@ use ruma::{
     push::{Action, PushConditionRoomCtx},
     serde::Raw,
     time::Instant,
-    EventId, Int, MatrixToUri, MatrixUri, MxcUri, OwnedEventId, OwnedRoomId, OwnedServerName,
-    OwnedTransactionId, OwnedUserId, RoomId, TransactionId, UInt, UserId,
+    EventId, Int, MatrixToUri, MatrixUri, MilliSecondsSinceUnixEpoch, MxcUri, OwnedEventId,
+    OwnedRoomId, OwnedServerName, OwnedTransactionId, OwnedUserId, RoomId, RoomVersionId,
+    TransactionId, UInt, UserId,
 };
 use serde::de::DeserializeOwned;
 use thiserror::Error;
@@ -129,7 +133,10 @@ use tracing::{debug, info, instrument, warn};
 use self::futures::{SendAttachment, SendMessageLikeEvent, SendRawMessageLikeEvent};
 pub use self::{
     member::{RoomMember, RoomMemberRole},
-    messages::{EventWithContextResponse, Messages, MessagesOptions},
+    messages::{
+        EventWithContextResponse, IncludeRelations, ListThreadsOptions, Messages, MessagesOptions,
+        Relations, RelationsOptions, ThreadRoots,
+    },
 };
 #[cfg(doc)]
 use crate::event_cache::EventCache;
@@ -141,13 +148,15 @@ use crate::{
     event_cache::{self, EventCacheDropHandles, RoomEventCache},
     event_handler::{EventHandler, EventHandlerDropGuard, EventHandlerHandle, SyncEvent},
     live_location_share::ObservableLiveLocation,
-    media::{MediaFormat, MediaRequestParameters},
+    media::{Media, MediaFormat, MediaOrigin, MediaRequestParameters},
+    message_search::{MessageSearchOptions, MessageSearchResponse},
     notification_settings::{IsEncrypted, IsOneToOne, RoomNotificationMode},
     room::{
         knock_requests::{KnockRequest, KnockRequestMemberInfo},
         power_levels::{RoomPowerLevelChanges, RoomPowerLevelsExt},
         privacy_settings::RoomPrivacySettings,
     },
+    space_hierarchy::{SpaceHierarchy, SpaceHierarchyOptions},
     sync::RoomUpdate,
     utils::{IntoRawMessageLikeEventContent, IntoRawStateEventContent},
     BaseRoom, Client, Error, HttpResult, Result, RoomState, TransmissionProgress,
@@ -166,6 +175,8 @@ pub mod power_levels;
 
 /// Contains all the functionality for modifying the privacy settings in a room.
 pub mod privacy_settings;
+#[cfg(feature = "e2e-encryption")]
+pub(crate) mod shared_room_history;
 
 /// A struct containing methods that are common for Joined, Invited and Left
 /// Rooms
@@ -231,12 +242,32 @@ impl Room {
                 false
             });
 
+        // Remember who invited us, to import the history of the room they shared with
+        // us once we joined.
+        #[cfg(feature = "e2e-encryption")]
+        let inviter = if prev_room_state == RoomState::Invited
+            && self.client.encryption().settings().share_history_on_invite
+        {
+            self.get_member_no_sync(self.own_user_id())
+                .await
+                .ok()
+                .flatten()
+                .map(|member| member.event().sender().to_owned())
+        } else {
+            None
+        };
+
         self.client.join_room_by_id(self.room_id()).await?;
 
         if mark_as_direct {
             self.set_is_direct(true).await?;
         }
 
+        #[cfg(feature = "e2e-encryption")]
+        if let Some(inviter) = inviter {
+            shared_room_history::import_room_history_on_join(self, &inviter).await;
+        }
+
         Ok(())
     }
 
@@ -247,6 +278,17 @@ impl Room {
         self.client.clone()
     }
 
+    /// Get the media manager for the media of this room.
+    ///
+    /// The media content fetched through it is associated to this room in
+    /// the media cache, so the [`MediaRetentionPolicy`] scoped to this room
+    /// applies to it.
+    ///
+    /// [`MediaRetentionPolicy`]: crate::media::MediaRetentionPolicy
+    pub fn media(&self) -> Media {
+        Media::for_room(self.client.clone(), self.room_id().to_owned())
+    }
+
     /// Get the sync state of this room, i.e. whether it was fully synced with
     /// the server.
     pub fn is_synced(&self) -> bool {
@@ -285,7 +327,12 @@ impl Room {
     pub async fn avatar(&self, format: MediaFormat) -> Result<Option<Vec<u8>>> {
         let Some(url) = self.avatar_url() else { return Ok(None) };
         let request = MediaRequestParameters { source: MediaSource::Plain(url.to_owned()), format };
-        Ok(Some(self.client.media().get_media_content(&request, true).await?))
+        Ok(Some(
+            self.client
+                .media()
+                .get_media_content_with_origin(&request, true, &MediaOrigin::avatar())
+                .await?,
+        ))
     }
 
     /// Sends a request to `/_matrix/client/r0/rooms/{room_id}/messages` and
@@ -452,7 +499,10 @@ impl Room {
     ///
     /// Doesn't return an error `Result` when decryption failed; only logs from
     /// the crypto crate will indicate so.
-    async fn try_decrypt_event(&self, event: Raw<AnyTimelineEvent>) -> Result<TimelineEvent> {
+    pub(crate) async fn try_decrypt_event(
+        &self,
+        event: Raw<AnyTimelineEvent>,
+    ) -> Result<TimelineEvent> {
         #[cfg(feature = "e2e-encryption")]
         if let Ok(AnySyncTimelineEvent::MessageLike(AnySyncMessageLikeEvent::RoomEncrypted(
             SyncMessageLikeEvent::Original(_),
@@ -556,6 +606,154 @@ impl Room {
         })
     }
 
+    /// Find the ID of the event closest to the given timestamp in this room,
+    /// using the `/timestamp_to_event` endpoint.
+    ///
+    /// With [`Direction::Forward`], the first event sent at or after
+    /// `timestamp` is returned. With [`Direction::Backward`], the last event
+    /// sent at or before `timestamp` is returned.
+    ///
+    /// This is useful to jump to a given date in a room, for example by
+    /// focusing a timeline on the returned event.
+    #[instrument(skip(self), fields(room_id = ?self.inner.room_id()))]
+    pub async fn event_id_for_timestamp(
+        &self,
+        timestamp: MilliSecondsSinceUnixEpoch,
+        direction: Direction,
+    ) -> Result<OwnedEventId> {
+        let request = get_event_by_timestamp::v1::Request::new(
+            self.room_id().to_owned(),
+            timestamp,
+            direction,
+        );
+        let response = self.client.send(request).await?;
+
+        Ok(response.event_id)
+    }
+
+    /// Fetch the events related to the event with the given `EventId` in this
+    /// room, using the `/relations` endpoint.
+    ///
+    /// With the encryption feature, events are decrypted if possible. If
+    /// decryption fails for an individual event, that event is returned
+    /// undecrypted.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use matrix_sdk::room::{IncludeRelations, RelationsOptions};
+    /// # use matrix_sdk::{ruma::{event_id, events::relation::RelationType}, Room};
+    /// # async {
+    /// # let room: Room = todo!();
+    /// let options = RelationsOptions {
+    ///     include_relations: IncludeRelations::RelationsOfType(RelationType::Thread),
+    ///     ..Default::default()
+    /// };
+    ///
+    /// let relations = room.relations(event_id!("$thread_root").to_owned(), options).await?;
+    /// # anyhow::Ok(()) };
+    /// ```
+    #[instrument(skip_all, fields(room_id = ?self.inner.room_id(), ?event_id, ?options))]
+    pub async fn relations(
+        &self,
+        event_id: OwnedEventId,
+        options: RelationsOptions,
+    ) -> Result<Relations> {
+        options.send(self, event_id).await
+    }
+
+    /// List the threads of this room, using the `/threads` endpoint.
+    ///
+    /// The thread roots are returned in reverse chronological order of their
+    /// latest activity. Use the `prev_batch_token` of the result as the
+    /// `from` option of a subsequent call, to get the next batch of threads.
+    ///
+    /// With the encryption feature, thread roots are decrypted if possible. If
+    /// decryption fails for an individual event, that event is returned
+    /// undecrypted.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use matrix_sdk::room::ListThreadsOptions;
+    /// # use matrix_sdk::Room;
+    /// # async {
+    /// # let room: Room = todo!();
+    /// let mut options = ListThreadsOptions::default();
+    ///
+    /// loop {
+    ///     let threads = room.threads(options.clone()).await?;
+    ///
+    ///     for thread_root in threads.chunk {
+    ///         println!("Thread root: {:?}", thread_root.event_id());
+    ///     }
+    ///
+    ///     match threads.prev_batch_token {
+    ///         Some(token) => options.from = Some(token),
+    ///         None => break,
+    ///     }
+    /// }
+    /// # anyhow::Ok(()) };
+    /// ```
+    #[instrument(skip_all, fields(room_id = ?self.inner.room_id(), ?options))]
+    pub async fn threads(&self, options: ListThreadsOptions) -> Result<ThreadRoots> {
+        let thread_roots = options.send(self).await?;
+
+        // Feed the event cache with the thread roots, so it knows about their bundled
+        // thread summaries.
+        if let Ok((cache, _handles)) = self.event_cache().await {
+            cache.save_events(thread_roots.chunk.iter().cloned()).await;
+        }
+
+        Ok(thread_roots)
+    }
+
+    /// Search for messages in this room on the server, using the `/search`
+    /// endpoint.
+    ///
+    /// This is the same as [`Client::search_messages`], with the search
+    /// restricted to this room, unless the filter of the options is already
+    /// restricted to rooms including this one.
+    ///
+    /// Returns [`Error::SearchFilterExcludesRoom`] if the filter is restricted
+    /// to rooms that don't include this one.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use matrix_sdk::message_search::MessageSearchOptions;
+    /// # use matrix_sdk::Room;
+    /// # async {
+    /// # let room: Room = todo!();
+    /// let mut options = MessageSearchOptions::new("lunch");
+    ///
+    /// loop {
+    ///     let response = room.search(options.clone()).await?;
+    ///
+    ///     for result in response.results {
+    ///         println!("Found event {:?}", result.event.event_id());
+    ///     }
+    ///
+    ///     match response.next_batch {
+    ///         Some(token) => options.next_batch = Some(token),
+    ///         None => break,
+    ///     }
+    /// }
+    /// # anyhow::Ok(()) };
+    /// ```
+    #[instrument(skip_all, fields(room_id = ?self.inner.room_id()))]
+    pub async fn search(&self, mut options: MessageSearchOptions) -> Result<MessageSearchResponse> {
+        match &options.filter.rooms {
+            Some(rooms) if !rooms.iter().any(|room_id| room_id == self.room_id()) => {
+                return Err(Error::SearchFilterExcludesRoom);
+            }
+            Some(_) => {}
+            None => options.filter.rooms = Some(vec![self.room_id().to_owned()]),
+        }
+
+        self.client.search_messages(options).await
+    }
+
     pub(crate) async fn request_members(&self) -> Result<()> {
         self.client
             .locks()
@@ -981,6 +1179,14 @@ impl Room {
             .collect::<FuturesUnordered<_>>())
     }
 
+    /// Create a [`SpaceHierarchy`] to browse the rooms of this space, and of
+    /// its subspaces.
+    ///
+    /// No request is sent until [`SpaceHierarchy::next_page`] is called.
+    pub fn space_hierarchy(&self, options: SpaceHierarchyOptions) -> SpaceHierarchy {
+        SpaceHierarchy::new(self.client.clone(), self.room_id().to_owned(), options)
+    }
+
     /// Read account data in this room, from storage.
     pub async fn account_data(
         &self,
@@ -1296,10 +1502,14 @@ impl Room {
         {
             RoomEventDecryptionResult::Decrypted(decrypted) => decrypted.into(),
             RoomEventDecryptionResult::UnableToDecrypt(utd_info) => {
-                self.client
-                    .encryption()
-                    .backups()
-                    .maybe_download_room_key(self.room_id().to_owned(), event.clone());
+                // Only the room keys we don't have, or that we only have from a later message
+                // index, can be found in the backup.
+                if utd_info.reason.is_missing_room_key() {
+                    self.client
+                        .encryption()
+                        .backups()
+                        .maybe_download_room_key(self.room_id().to_owned(), event.clone());
+                }
                 TimelineEvent::new_utd_event(event.clone().cast(), utd_info)
             }
         };
@@ -1385,9 +1595,16 @@ impl Room {
 
     /// Invite the specified user by `UserId` to this room.
     ///
+    /// If [`EncryptionSettings::share_history_on_invite`] is set, and the room
+    /// is encrypted with a history that is visible to invited users, the
+    /// history of the room is shared with the user, see
+    /// [`Room::share_history()`].
+    ///
     /// # Arguments
     ///
     /// * `user_id` - The `UserId` of the user to invite to the room.
+    ///
+    /// [`EncryptionSettings::share_history_on_invite`]: crate::encryption::EncryptionSettings::share_history_on_invite
     #[instrument(skip_all)]
     pub async fn invite_user_by_id(&self, user_id: &UserId) -> Result<()> {
         let recipient = InvitationRecipient::UserId { user_id: user_id.to_owned() };
@@ -1399,9 +1616,41 @@ impl Room {
         // but before the /sync request could fetch the membership change event.
         self.mark_members_missing();
 
+        #[cfg(feature = "e2e-encryption")]
+        if self.client.encryption().settings().share_history_on_invite {
+            shared_room_history::share_room_history_on_invite(self, user_id).await;
+        }
+
         Ok(())
     }
 
+    /// Share the history of this encrypted room with the given user, as
+    /// defined in [MSC4268].
+    ///
+    /// A bundle of the room keys that can be shared with users invited to the
+    /// room is encrypted and uploaded to the media repository, and its location
+    /// is sent to the devices of the user selected by the
+    /// [`CollectStrategy`] of the client, like the room keys of the room. The
+    /// bundle is only uploaded if at least one of these devices can receive
+    /// it. The user can then import it when they join the room, which requires
+    /// [`EncryptionSettings::share_history_on_invite`] to be set on their side
+    /// too.
+    ///
+    /// This is done automatically by [`Room::invite_user_by_id()`] if
+    /// [`EncryptionSettings::share_history_on_invite`] is set.
+    ///
+    /// # Arguments
+    ///
+    /// * `user_id` - The user to share the history of the room with.
+    ///
+    /// [MSC4268]: https://github.com/matrix-org/matrix-spec-proposals/pull/4268
+    /// [`EncryptionSettings::share_history_on_invite`]: crate::encryption::EncryptionSettings::share_history_on_invite
+    /// [`CollectStrategy`]: matrix_sdk_base::crypto::CollectStrategy
+    #[cfg(feature = "e2e-encryption")]
+    pub async fn share_history(&self, user_id: &UserId) -> Result<()> {
+        shared_room_history::share_room_history(self, user_id).await
+    }
+
     /// Invite the specified user by third party id to this room.
     ///
     /// # Arguments
@@ -1989,6 +2238,23 @@ impl Room {
     ) -> Result<send_message_event::v3::Response> {
         self.ensure_room_joined()?;
 
+        #[cfg(feature = "image-processing")]
+        let (processed_content_type, data) = match config.image_processing.take() {
+            Some(settings) => {
+                crate::image_processing::process_attachment(
+                    content_type.clone(),
+                    data,
+                    settings,
+                    &mut config.info,
+                    &mut config.thumbnail,
+                )
+                .await
+            }
+            None => (content_type.clone(), data),
+        };
+        #[cfg(feature = "image-processing")]
+        let content_type = &processed_content_type;
+
         let txn_id = config.txn_id.take();
         let mentions = config.mentions.take();
 
@@ -1996,9 +2262,14 @@ impl Room {
 
         // If necessary, store caching data for the thumbnail ahead of time.
         let thumbnail_cache_info = if store_in_cache {
-            thumbnail
-                .as_ref()
-                .map(|thumbnail| (thumbnail.data.clone(), thumbnail.height, thumbnail.width))
+            thumbnail.as_ref().map(|thumbnail| {
+                (
+                    thumbnail.data.clone(),
+                    thumbnail.content_type.clone(),
+                    thumbnail.height,
+                    thumbnail.width,
+                )
+            })
         } else {
             None
         };
@@ -2036,17 +2307,23 @@ impl Room {
             // properly, so only log errors during caching.
 
             debug!("caching the media");
+            let origin = MediaOrigin::room(self.room_id().to_owned());
             let request =
                 MediaRequestParameters { source: media_source.clone(), format: MediaFormat::File };
 
             if let Err(err) = cache_store_lock_guard
-                .add_media_content(&request, data, IgnoreMediaRetentionPolicy::No)
+                .add_media_content_with_origin(
+                    &request,
+                    data,
+                    &origin.clone().with_content_type(content_type.to_string()),
+                    IgnoreMediaRetentionPolicy::No,
+                )
                 .await
             {
                 warn!("unable to cache the media after uploading it: {err}");
             }
 
-            if let Some(((data, height, width), source)) =
+            if let Some(((data, thumbnail_content_type, height, width), source)) =
                 thumbnail_cache_info.zip(thumbnail.as_ref().map(|tuple| &tuple.0))
             {
                 debug!("caching the thumbnail");
@@ -2057,7 +2334,12 @@ impl Room {
                 };
 
                 if let Err(err) = cache_store_lock_guard
-                    .add_media_content(&request, data, IgnoreMediaRetentionPolicy::No)
+                    .add_media_content_with_origin(
+                        &request,
+                        data,
+                        &origin.with_content_type(thumbnail_content_type.to_string()),
+                        IgnoreMediaRetentionPolicy::No,
+                    )
                     .await
                 {
                     warn!("unable to cache the media after uploading it: {err}");
@@ -2923,6 +3205,23 @@ impl Room {
         Ok(())
     }
 
+    /// Upgrade this room to a new room version.
+    ///
+    /// The homeserver creates a new room with the given version, and sends an
+    /// `m.room.tombstone` event in this room, that points to the new room.
+    /// The current user joins the new room automatically.
+    ///
+    /// Returns the ID of the new room. Use [`Client::await_room_remote_echo()`]
+    /// to get the new room once it has been received from sync.
+    pub async fn upgrade(&self, new_version: RoomVersionId) -> Result<OwnedRoomId> {
+        self.ensure_room_joined()?;
+
+        let request = upgrade_room::v3::Request::new(self.room_id().to_owned(), new_version);
+        let response = self.client.send(request).await?;
+
+        Ok(response.replacement_room)
+    }
+
     fn ensure_room_joined(&self) -> Result<()> {
         let state = self.state();
         if state == RoomState::Joined {
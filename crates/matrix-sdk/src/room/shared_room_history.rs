@@ -0,0 +1,268 @@
+// Copyright 2025 The Matrix.org Foundation C.I.C.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Sharing the history of encrypted rooms with the users we invite, and
+//! importing it when we join a room we were invited to, as defined in
+//! [MSC4268].
+//!
+//! [MSC4268]: https://github.com/matrix-org/matrix-spec-proposals/pull/4268
+
+use std::{io::Cursor, iter};
+
+use matrix_sdk_base::{
+    crypto::{
+        types::{
+            events::{room_key_bundle::RoomKeyBundleContent, EventType, ToDeviceEvent},
+            room_history::RoomKeyBundle,
+        },
+        RoomKeyBundleImportError,
+    },
+    media::{MediaFormat, MediaRequestParameters},
+    RoomState,
+};
+use matrix_sdk_common::executor::spawn;
+use ruma::{
+    events::{
+        room::{history_visibility::HistoryVisibility, MediaSource},
+        AnyToDeviceEvent,
+    },
+    serde::Raw,
+    UserId,
+};
+use tracing::{info, instrument, warn};
+
+use crate::{Client, Error, Result, Room};
+
+/// Whether the users invited to the room can see the messages that were sent
+/// before they were invited, and thus whether we should share the history of
+/// the room with them.
+async fn should_share_history(room: &Room) -> Result<bool> {
+    Ok(room.is_encrypted().await?
+        && matches!(
+            room.history_visibility_or_default(),
+            HistoryVisibility::Shared | HistoryVisibility::WorldReadable
+        ))
+}
+
+/// Share the history of the room with the given user, if the room is
+/// encrypted and its history is visible to invited users.
+///
+/// This is called after inviting the user, so errors are only logged.
+pub(super) async fn share_room_history_on_invite(room: &Room, user_id: &UserId) {
+    let should_share = match should_share_history(room).await {
+        Ok(should_share) => should_share,
+        Err(e) => {
+            warn!("Couldn't check whether the history of the room can be shared: {e}");
+            return;
+        }
+    };
+
+    if should_share {
+        if let Err(e) = share_room_history(room, user_id).await {
+            warn!("Couldn't share the history of the room with the invited user: {e}");
+        }
+    }
+}
+
+/// Build a bundle of the room keys of the room, upload it, and send its
+/// location to the devices of the given user.
+///
+/// The devices that receive the bundle are selected with the same
+/// [`CollectStrategy`] as the room keys of the room.
+///
+/// [`CollectStrategy`]: matrix_sdk_base::crypto::CollectStrategy
+#[instrument(skip(room), fields(room_id = ?room.room_id()))]
+pub(super) async fn share_room_history(room: &Room, user_id: &UserId) -> Result<()> {
+    let client = &room.client;
+    let collect_strategy = &client.base_client().room_key_recipient_strategy;
+
+    // Make sure we know the devices of the user, and that we share an Olm session
+    // with all of them, before uploading anything.
+    query_keys(room, user_id).await?;
+    client.claim_one_time_keys(iter::once(user_id)).await?;
+
+    let bundle = {
+        let olm_machine = client.olm_machine().await;
+        let olm_machine = olm_machine.as_ref().ok_or(Error::NoOlmMachine)?;
+
+        if !olm_machine.can_share_room_key_bundle(user_id, collect_strategy).await? {
+            warn!("No device of the user can receive the room key bundle, not sending it");
+            return Ok(());
+        }
+
+        olm_machine.build_room_key_bundle(room.room_id()).await?
+    };
+
+    if bundle.room_keys.is_empty() {
+        info!("No room keys can be shared, not sending a room key bundle");
+        return Ok(());
+    }
+
+    let data = serde_json::to_vec(&bundle)?;
+    let file =
+        client.upload_encrypted_file(&mime::APPLICATION_JSON, &mut Cursor::new(data)).await?;
+    info!(media_url = ?file.url, room_key_count = bundle.room_keys.len(), "Uploaded a room key bundle");
+
+    let content = RoomKeyBundleContent::new(room.room_id().to_owned(), file);
+    let request = {
+        let olm_machine = client.olm_machine().await;
+        let olm_machine = olm_machine.as_ref().ok_or(Error::NoOlmMachine)?;
+        olm_machine.share_room_key_bundle_data(user_id, collect_strategy, &content).await?
+    };
+
+    if let Some(request) = request {
+        client.send_to_device(&request).await?;
+    } else {
+        warn!("No device of the user can receive the room key bundle anymore, not sending it");
+    }
+
+    Ok(())
+}
+
+/// Import the room key bundle that the given user sent us for the room, if
+/// any.
+///
+/// This is called after joining a room we were invited to, so errors are only
+/// logged. If we didn't receive the bundle yet, it is imported when it arrives,
+/// by [`import_room_history_on_bundle`].
+pub(super) async fn import_room_history_on_join(room: &Room, inviter: &UserId) {
+    match import_room_history(room, inviter).await {
+        Ok(true) => {}
+        Ok(false) => {
+            room.client
+                .inner
+                .e2ee
+                .pending_room_history_imports
+                .lock()
+                .insert(room.room_id().to_owned(), inviter.to_owned());
+        }
+        Err(e) => warn!("Couldn't import the history of the room shared by the inviter: {e}"),
+    }
+}
+
+/// Import the room key bundles received in the given to-device events, for the
+/// rooms that we joined before receiving the bundle their inviter shared with
+/// us.
+///
+/// The bundles are imported in the background, so this doesn't block the sync.
+pub(crate) fn import_room_history_on_bundle(
+    client: &Client,
+    to_device_events: &[Raw<AnyToDeviceEvent>],
+) {
+    for event in to_device_events {
+        if event.get_field::<String>("type").ok().flatten().as_deref()
+            != Some(RoomKeyBundleContent::EVENT_TYPE)
+        {
+            continue;
+        }
+
+        let event = match event.deserialize_as::<ToDeviceEvent<RoomKeyBundleContent>>() {
+            Ok(event) => event,
+            Err(e) => {
+                warn!("Couldn't deserialize a room key bundle event: {e}");
+                continue;
+            }
+        };
+        let room_id = &event.content.room_id;
+
+        // Only import the bundle if its sender is the one who invited us to the room.
+        {
+            let mut pending_imports = client.inner.e2ee.pending_room_history_imports.lock();
+            if pending_imports.get(room_id) != Some(&event.sender) {
+                continue;
+            }
+            pending_imports.remove(room_id);
+        }
+
+        let Some(room) = client.get_room(room_id).filter(|room| room.state() == RoomState::Joined)
+        else {
+            continue;
+        };
+
+        let inviter = event.sender;
+        spawn(async move {
+            match import_room_history(&room, &inviter).await {
+                Ok(true) => {}
+                Ok(false) => warn!(
+                    room_id = ?room.room_id(),
+                    "The room key bundle that was just received couldn't be found"
+                ),
+                Err(e) => {
+                    warn!("Couldn't import the history of the room shared by the inviter: {e}")
+                }
+            }
+        });
+    }
+}
+
+/// Import the room key bundle that the inviter sent us for the room.
+///
+/// Returns `false` if we didn't receive a bundle from the inviter for this
+/// room.
+#[instrument(skip(room), fields(room_id = ?room.room_id()))]
+async fn import_room_history(room: &Room, inviter: &UserId) -> Result<bool> {
+    let client = &room.client;
+
+    let bundle_data = {
+        let olm_machine = client.olm_machine().await;
+        let olm_machine = olm_machine.as_ref().ok_or(Error::NoOlmMachine)?;
+        olm_machine.get_received_room_key_bundle_data(room.room_id(), inviter).await?
+    };
+
+    let Some(bundle_data) = bundle_data else {
+        info!("The inviter didn't share the history of the room with us yet");
+        return Ok(false);
+    };
+
+    let request = MediaRequestParameters {
+        source: MediaSource::Encrypted(Box::new(bundle_data.bundle_data.file.clone())),
+        format: MediaFormat::File,
+    };
+    let bundle = client.media().get_media_content(&request, false).await?;
+    let bundle: RoomKeyBundle = serde_json::from_slice(&bundle)?;
+
+    // Make sure we know the current devices and identity of the inviter, to decide
+    // whether we trust the bundle.
+    query_keys(room, inviter).await?;
+
+    let olm_machine = client.olm_machine().await;
+    let olm_machine = olm_machine.as_ref().ok_or(Error::NoOlmMachine)?;
+
+    match olm_machine.receive_room_key_bundle(&bundle_data, bundle, |_, _| {}).await {
+        Ok(result) => {
+            info!(
+                imported_count = result.imported_count,
+                total_count = result.total_count,
+                "Imported the room key bundle"
+            );
+        }
+        Err(RoomKeyBundleImportError::Store(e)) => return Err(e.into()),
+        Err(e) => warn!("Not importing the room key bundle: {e}"),
+    }
+
+    Ok(true)
+}
+
+/// Query the devices and the identity of the given user.
+async fn query_keys(room: &Room, user_id: &UserId) -> Result<()> {
+    let (request_id, request) = {
+        let olm_machine = room.client.olm_machine().await;
+        let olm_machine = olm_machine.as_ref().ok_or(Error::NoOlmMachine)?;
+        olm_machine.query_keys_for_users(iter::once(user_id))
+    };
+
+    room.client.keys_query(&request_id, request.device_keys).await?;
+
+    Ok(())
+}
@@ -65,7 +65,7 @@ impl From<ruma::directory::PublicRoomsChunk> for RoomDescription {
 }
 
 #[derive(Default, Debug)]
-enum SearchState {
+pub(crate) enum SearchState {
     /// The search has more pages and contains the next token to be used in the
     /// next page request.
     Next(String),
@@ -77,7 +77,7 @@ enum SearchState {
 }
 
 impl SearchState {
-    fn next_token(&self) -> Option<&str> {
+    pub(crate) fn next_token(&self) -> Option<&str> {
         if let Self::Next(next_token) = &self {
             Some(next_token)
         } else {
@@ -85,7 +85,7 @@ impl SearchState {
         }
     }
 
-    fn is_at_end(&self) -> bool {
+    pub(crate) fn is_at_end(&self) -> bool {
         matches!(self, Self::End)
     }
 }
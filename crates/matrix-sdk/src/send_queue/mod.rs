@@ -57,6 +57,47 @@
 //! this, the send queue may send such an event, using the dependency system
 //! described below.
 //!
+//! # State events and redactions
+//!
+//! State events can be queued with [`RoomSendQueue::send_state()`] and
+//! [`RoomSendQueue::send_state_raw()`], and redactions of events that have
+//! been sent already with [`RoomSendQueue::redact()`]. They're persisted as
+//! [`QueuedRequestKind::StateEvent`] and [`QueuedRequestKind::Redaction`]
+//! respectively, reflected as local echoes, and sent in order with the other
+//! requests of the room, with the same retry and wedging rules as events.
+//!
+//! Their send handle can be used to abort or unwedge them, but they can't be
+//! edited or reacted to.
+//!
+//! Aborting an event that has been sent already queues a redaction of it too,
+//! but this one is hidden: it's neither reflected as a local echo, nor in the
+//! updates of the queue.
+//!
+//! # Scheduled events
+//!
+//! An event can be scheduled to be sent at a later time, with
+//! [`RoomSendQueue::send_scheduled()`]. It's persisted as a
+//! [`QueuedRequestKind::ScheduledEvent`], and doesn't appear as a local echo
+//! until its time has come.
+//!
+//! If the homeserver supports delayed events ([MSC4140]) and the room isn't
+//! encrypted, the event is handed over to the homeserver as soon as possible,
+//! which will send it even if the client isn't running at that time. The
+//! request stays in the queue as a record, until the event's time has passed.
+//! If the room becomes encrypted in the meantime, the delayed events are
+//! cancelled and the events are kept locally instead.
+//!
+//! Otherwise, the request waits in the queue until its time, at which point
+//! it's turned into a regular [`QueuedRequestKind::Event`] and sent like any
+//! other event. Events of encrypted rooms are always kept locally, since they
+//! must be encrypted for the devices of the room at the time they're sent.
+//!
+//! The scheduled events can be listed with
+//! [`RoomSendQueue::scheduled_events()`], and manipulated with a
+//! [`ScheduledEventHandle`].
+//!
+//! [MSC4140]: https://github.com/matrix-org/matrix-spec-proposals/pull/4140
+//!
 //! # Dependency system
 //!
 //! The send queue includes a simple dependency system, where a
@@ -127,31 +168,77 @@
 //! The rest of the process is then similar to that of uploading a file without
 //! a thumbnail. The only difference is that there's a thumbnail source (MXC ID)
 //! remembered and fixed up into the media event, just before sending it.
+//!
+//! ## Galleries
+//!
+//! A gallery event holds several medias, each with an optional thumbnail. Its
+//! uploads are chained, in order:
+//!
+//! - the first upload (the first item's thumbnail, or its file if it doesn't
+//!   have one) is a [`QueuedRequestKind::MediaUpload`] request,
+//! - every following upload is pushed as a dependent request of the previous
+//!   one, of kind [`DependentQueuedRequestKind::UploadFileOrThumbnail`],
+//! - the gallery event is pushed as a dependent request of the last upload, of
+//!   kind [`DependentQueuedRequestKind::FinishGallery`].
+//!
+//! Each upload request remembers the MXC IDs of the medias uploaded before it,
+//! in its `accumulated` field, so that all of them are known when it's time to
+//! fix them up into the gallery event, just before sending it.
+//!
+//! ### Resumable uploads
+//!
+//! When the homeserver supports it (see
+//! [`Media::supports_resumable_uploads()`]), a media larger than a single
+//! chunk is uploaded in several requests to a preallocated MXC URI, instead of
+//! a single one. The URI and the number of bytes received by the homeserver
+//! are saved in the `resumable` field of the
+//! [`QueuedRequestKind::MediaUpload`] after each chunk, so that an upload
+//! interrupted by a network error is resumed where it stopped, instead of
+//! starting over. Otherwise, the media is uploaded in a single request.
+//!
+//! In encrypted rooms, the key of the media is saved along with its progress,
+//! so the media can be encrypted again to the same bytes when the upload is
+//! resumed, instead of keeping the encrypted media around.
 
 use std::{
+    borrow::Borrow,
     collections::{BTreeMap, HashMap},
     str::FromStr as _,
     sync::{
         atomic::{AtomicBool, Ordering},
         Arc, RwLock,
     },
+    time::Duration,
 };
 
 use as_variant::as_variant;
+use eyeball::{SharedObservable, Subscriber};
 use matrix_sdk_base::{
     event_cache::store::EventCacheStoreError,
     media::MediaRequestParameters,
     store::{
-        ChildTransactionId, DependentQueuedRequest, DependentQueuedRequestKind,
-        FinishUploadThumbnailInfo, QueueWedgeError, QueuedRequest, QueuedRequestKind,
-        SentMediaInfo, SentRequestKey, SerializableEventContent,
+        ChildTransactionId, DependentQueuedRequest, DependentQueuedRequestKind, DynStateStore,
+        FinishGalleryItemInfo, FinishUploadThumbnailInfo, QueueWedgeError, QueuedRequest,
+        QueuedRequestKind, SentMediaInfo, SentRequestKey, SerializableEventContent,
     },
     store_locks::LockStoreError,
-    RoomState, StoreError,
+    RoomInfo, RoomState, StoreError,
+};
+use matrix_sdk_common::{
+    executor::{spawn, JoinHandle},
+    sleep::sleep,
 };
-use matrix_sdk_common::executor::{spawn, JoinHandle};
 use mime::Mime;
 use ruma::{
+    api::client::{
+        delayed_events::{
+            delayed_message_event, update_delayed_event,
+            update_delayed_event::unstable::UpdateAction, DelayParameters,
+        },
+        redact::redact_event,
+        state::send_state_event,
+    },
+    assign,
     events::{
         reaction::ReactionEventContent,
         relation::Annotation,
@@ -159,10 +246,12 @@ use ruma::{
             message::{FormattedBody, RoomMessageEventContent},
             MediaSource,
         },
-        AnyMessageLikeEventContent, EventContent as _, Mentions,
+        AnyMessageLikeEventContent, AnyStateEventContent, EmptyStateKey, EventContent as _,
+        Mentions, StateEventContent,
     },
     serde::Raw,
-    MilliSecondsSinceUnixEpoch, OwnedEventId, OwnedRoomId, OwnedTransactionId, TransactionId,
+    MilliSecondsSinceUnixEpoch, OwnedEventId, OwnedRoomId, OwnedTransactionId, RoomId,
+    TransactionId,
 };
 use tokio::sync::{broadcast, oneshot, Mutex, Notify, OwnedMutexGuard};
 use tracing::{debug, error, info, instrument, trace, warn};
@@ -174,7 +263,7 @@ use crate::{
     config::RequestConfig,
     error::RetryKind,
     room::{edit::EditedContent, WeakRoom},
-    Client, Media, Room,
+    Client, HttpError, Media, Room, TransmissionProgress,
 };
 
 mod upload;
@@ -240,6 +329,7 @@ impl SendQueue {
             self.is_enabled(),
             data.error_reporter.clone(),
             data.is_dropping.clone(),
+            data.report_media_upload_progress.clone(),
             &self.client,
             owned_room_id.clone(),
         );
@@ -273,6 +363,15 @@ impl SendQueue {
         self.respawn_tasks_for_rooms_with_unsent_requests().await;
     }
 
+    /// Enable or disable the reporting of the progress of the media uploads,
+    /// with [`RoomSendQueueUpdate::MediaUploadProgress`] updates, for all
+    /// rooms.
+    ///
+    /// It's disabled by default.
+    pub fn enable_upload_progress(&self, enabled: bool) {
+        self.data().report_media_upload_progress.store(enabled, Ordering::SeqCst);
+    }
+
     /// Returns whether the send queue is enabled, at a client-wide
     /// granularity.
     pub fn is_enabled(&self) -> bool {
@@ -326,6 +425,9 @@ pub(super) struct SendQueueData {
 
     /// Are we currently dropping the Client?
     is_dropping: Arc<AtomicBool>,
+
+    /// Should the progress of the media uploads be reported?
+    report_media_upload_progress: Arc<AtomicBool>,
 }
 
 impl SendQueueData {
@@ -338,6 +440,7 @@ impl SendQueueData {
             globally_enabled: AtomicBool::new(globally_enabled),
             error_reporter: sender,
             is_dropping: Arc::new(false.into()),
+            report_media_upload_progress: Arc::new(false.into()),
         }
     }
 }
@@ -383,6 +486,7 @@ impl RoomSendQueue {
         globally_enabled: bool,
         global_error_reporter: broadcast::Sender<SendQueueRoomError>,
         is_dropping: Arc<AtomicBool>,
+        report_media_upload_progress: Arc<AtomicBool>,
         client: &Client,
         room_id: OwnedRoomId,
     ) -> Self {
@@ -402,6 +506,7 @@ impl RoomSendQueue {
             locally_enabled.clone(),
             global_error_reporter,
             is_dropping,
+            report_media_upload_progress,
         ));
 
         Self {
@@ -453,7 +558,7 @@ impl RoomSendQueue {
         let send_handle = SendHandle {
             room: self.clone(),
             transaction_id: transaction_id.clone(),
-            media_handles: None,
+            media_handles: Vec::new(),
             created_at,
         };
 
@@ -494,6 +599,291 @@ impl RoomSendQueue {
         .await
     }
 
+    /// Queues a raw state event for sending it to this room.
+    ///
+    /// This immediately returns, and will push the state event to be sent
+    /// into a queue, handled in the background, in order with the other
+    /// requests of the queue.
+    ///
+    /// Callers are expected to consume [`RoomSendQueueUpdate`] via calling
+    /// the [`Self::subscribe()`] method to get updates about the sending of
+    /// that state event, which is reflected as a [`LocalEchoContent::State`]
+    /// local echo.
+    ///
+    /// The returned handle can only be used to abort or unwedge the sending of
+    /// the state event; editing it or reacting to it isn't possible.
+    pub async fn send_state_raw(
+        &self,
+        content: Raw<AnyStateEventContent>,
+        event_type: String,
+        state_key: String,
+    ) -> Result<SendHandle, RoomSendQueueError> {
+        let Some(room) = self.inner.room.get() else {
+            return Err(RoomSendQueueError::RoomDisappeared);
+        };
+        if room.state() != RoomState::Joined {
+            return Err(RoomSendQueueError::RoomNotJoined);
+        }
+
+        let created_at = MilliSecondsSinceUnixEpoch::now();
+        let transaction_id = self
+            .inner
+            .queue
+            .push(
+                QueuedRequestKind::StateEvent {
+                    event_type: event_type.clone(),
+                    state_key: state_key.clone(),
+                    content: content.clone(),
+                },
+                created_at,
+            )
+            .await?;
+        trace!(%transaction_id, "manager sends a state event to the background task");
+
+        self.inner.notifier.notify_one();
+
+        let send_handle = SendHandle {
+            room: self.clone(),
+            transaction_id: transaction_id.clone(),
+            media_handles: Vec::new(),
+            created_at,
+        };
+
+        let _ = self.inner.updates.send(RoomSendQueueUpdate::NewLocalEvent(LocalEcho {
+            transaction_id,
+            content: LocalEchoContent::State {
+                event_type,
+                state_key,
+                content,
+                send_handle: send_handle.clone(),
+                send_error: None,
+            },
+        }));
+
+        Ok(send_handle)
+    }
+
+    /// Queues a state event with an empty state key for sending it to this
+    /// room.
+    ///
+    /// See [`Self::send_state_raw()`] for more details.
+    pub async fn send_state(
+        &self,
+        content: impl StateEventContent<StateKey = EmptyStateKey>,
+    ) -> Result<SendHandle, RoomSendQueueError> {
+        self.send_state_for_key(&EmptyStateKey, content).await
+    }
+
+    /// Queues a state event with the given state key for sending it to this
+    /// room.
+    ///
+    /// See [`Self::send_state_raw()`] for more details.
+    pub async fn send_state_for_key<C, K>(
+        &self,
+        state_key: &K,
+        content: C,
+    ) -> Result<SendHandle, RoomSendQueueError>
+    where
+        C: StateEventContent,
+        C::StateKey: Borrow<K>,
+        K: AsRef<str> + ?Sized,
+    {
+        self.send_state_raw(
+            Raw::new(&content).map_err(RoomSendQueueStorageError::JsonSerialization)?.cast(),
+            content.event_type().to_string(),
+            state_key.as_ref().to_owned(),
+        )
+        .await
+    }
+
+    /// Queues the redaction of an event that has been sent already.
+    ///
+    /// To redact a local echo that hasn't been sent yet, use
+    /// [`SendHandle::abort()`] instead.
+    ///
+    /// This immediately returns, and will push the redaction to be sent into a
+    /// queue, handled in the background, in order with the other requests of
+    /// the queue.
+    ///
+    /// Callers are expected to consume [`RoomSendQueueUpdate`] via calling
+    /// the [`Self::subscribe()`] method to get updates about the sending of
+    /// that redaction, which is reflected as a [`LocalEchoContent::Redaction`]
+    /// local echo.
+    pub async fn redact(
+        &self,
+        event_id: OwnedEventId,
+        reason: Option<String>,
+    ) -> Result<SendHandle, RoomSendQueueError> {
+        let Some(room) = self.inner.room.get() else {
+            return Err(RoomSendQueueError::RoomDisappeared);
+        };
+        if room.state() != RoomState::Joined {
+            return Err(RoomSendQueueError::RoomNotJoined);
+        }
+
+        let created_at = MilliSecondsSinceUnixEpoch::now();
+        let transaction_id = self
+            .inner
+            .queue
+            .push(
+                QueuedRequestKind::Redaction {
+                    redacts: event_id.clone(),
+                    reason: reason.clone(),
+                    hidden: false,
+                },
+                created_at,
+            )
+            .await?;
+        trace!(%transaction_id, %event_id, "manager sends a redaction to the background task");
+
+        self.inner.notifier.notify_one();
+
+        let send_handle = SendHandle {
+            room: self.clone(),
+            transaction_id: transaction_id.clone(),
+            media_handles: Vec::new(),
+            created_at,
+        };
+
+        let _ = self.inner.updates.send(RoomSendQueueUpdate::NewLocalEvent(LocalEcho {
+            transaction_id,
+            content: LocalEchoContent::Redaction {
+                redacts: event_id,
+                reason,
+                send_handle: send_handle.clone(),
+                send_error: None,
+            },
+        }));
+
+        Ok(send_handle)
+    }
+
+    /// Queues an event for sending it to this room, not before the given time.
+    ///
+    /// If the homeserver supports delayed events and the room isn't encrypted,
+    /// the event is handed over to the homeserver, which sends it at the given
+    /// time even if the client isn't running anymore. Otherwise, the event is
+    /// kept in the queue until its time, and sent then.
+    ///
+    /// The event isn't reflected as a local echo until its time has come; the
+    /// events scheduled to be sent later can be listed with
+    /// [`Self::scheduled_events()`].
+    pub async fn send_scheduled(
+        &self,
+        content: AnyMessageLikeEventContent,
+        send_at: MilliSecondsSinceUnixEpoch,
+    ) -> Result<ScheduledEventHandle, RoomSendQueueError> {
+        let Some(room) = self.inner.room.get() else {
+            return Err(RoomSendQueueError::RoomDisappeared);
+        };
+        if room.state() != RoomState::Joined {
+            return Err(RoomSendQueueError::RoomNotJoined);
+        }
+
+        let content = SerializableEventContent::new(&content)
+            .map_err(RoomSendQueueStorageError::JsonSerialization)?;
+
+        let use_delayed_event = Self::can_use_delayed_event(&room).await;
+
+        let transaction_id = self
+            .inner
+            .queue
+            .push(
+                QueuedRequestKind::ScheduledEvent {
+                    content,
+                    send_at,
+                    use_delayed_event,
+                    delay_id: None,
+                },
+                MilliSecondsSinceUnixEpoch::now(),
+            )
+            .await?;
+        trace!(%transaction_id, use_delayed_event, "manager schedules an event");
+
+        self.inner.notifier.notify_one();
+
+        Ok(ScheduledEventHandle { room: self.clone(), transaction_id })
+    }
+
+    /// Whether the scheduled events of the given room can be handed over to
+    /// the homeserver, as delayed events.
+    async fn can_use_delayed_event(room: &Room) -> bool {
+        let is_supported = match room.client().unstable_features().await {
+            Ok(features) => features.get("org.matrix.msc4140").copied().unwrap_or(false),
+            Err(err) => {
+                warn!("couldn't get the unstable features of the homeserver: {err}");
+                false
+            }
+        };
+
+        // The events of an encrypted room must be encrypted for the devices of the room
+        // members at the time they're sent, so they can't be handed over in advance.
+        is_supported && matches!(room.is_encrypted().await, Ok(false))
+    }
+
+    /// Take back the scheduled events that are handed over to the homeserver,
+    /// by cancelling their delayed events, so they're kept in the queue until
+    /// their time.
+    ///
+    /// This is used once the room is encrypted, since the events must then be
+    /// encrypted when they're sent.
+    ///
+    /// Returns whether all of them have been taken back.
+    async fn take_back_scheduled_events(room: &Room, queue: &QueueStorage) -> bool {
+        let delayed_events = match queue.scheduled_events_using_delayed_events().await {
+            Ok(delayed_events) => delayed_events,
+            Err(err) => {
+                warn!("couldn't load the scheduled events handed over to the homeserver: {err}");
+                return false;
+            }
+        };
+
+        let mut all_taken_back = true;
+
+        for (transaction_id, delay_id) in delayed_events {
+            if let Some(delay_id) = delay_id {
+                let request =
+                    update_delayed_event::unstable::Request::new(delay_id, UpdateAction::Cancel);
+
+                if let Err(err) = room.client().send(request).await {
+                    warn!(txn_id = %transaction_id, "couldn't cancel the delayed event of a scheduled event: {err}");
+                    all_taken_back = false;
+                    continue;
+                }
+            }
+
+            if let Err(err) = queue.keep_scheduled_event_locally(&transaction_id).await {
+                warn!(txn_id = %transaction_id, "couldn't keep a scheduled event locally: {err}");
+                all_taken_back = false;
+            } else {
+                trace!(txn_id = %transaction_id, "scheduled event taken back from the homeserver");
+            }
+        }
+
+        all_taken_back
+    }
+
+    /// Returns the events scheduled with [`Self::send_scheduled()`] that
+    /// haven't been sent yet, in the order they've been scheduled.
+    pub async fn scheduled_events(&self) -> Result<Vec<ScheduledEvent>, RoomSendQueueError> {
+        Ok(self.inner.queue.scheduled_events(self).await?)
+    }
+
+    /// Update a delayed event held by the homeserver.
+    async fn update_delayed_event(
+        &self,
+        delay_id: String,
+        action: UpdateAction,
+    ) -> Result<(), RoomSendQueueError> {
+        let Some(room) = self.inner.room.get() else {
+            return Err(RoomSendQueueError::RoomDisappeared);
+        };
+
+        room.client().send(update_delayed_event::unstable::Request::new(delay_id, action)).await?;
+
+        Ok(())
+    }
+
     /// Returns the current local requests as well as a receiver to listen to
     /// the send queue updates, as defined in [`RoomSendQueueUpdate`].
     pub async fn subscribe(
@@ -519,9 +909,16 @@ impl RoomSendQueue {
         locally_enabled: Arc<AtomicBool>,
         global_error_reporter: broadcast::Sender<SendQueueRoomError>,
         is_dropping: Arc<AtomicBool>,
+        report_media_upload_progress: Arc<AtomicBool>,
     ) {
         info!("spawned the sending task");
 
+        // The scheduled events handed over to the homeserver must be taken back once
+        // the room is encrypted, which might have happened while the task
+        // wasn't running.
+        let mut room_info = room.get().map(|room| room.subscribe_info());
+        let mut should_take_back_scheduled_events = true;
+
         loop {
             // A request to shut down should be preferred above everything else.
             if is_dropping.load(Ordering::SeqCst) {
@@ -547,13 +944,78 @@ impl RoomSendQueue {
                 continue;
             }
 
+            if should_take_back_scheduled_events {
+                should_take_back_scheduled_events = match room.get() {
+                    Some(room) if room.clone_info().is_encrypted() => {
+                        !Self::take_back_scheduled_events(&room, &queue).await
+                    }
+                    _ => false,
+                };
+            }
+
+            // Scheduled events whose time has come are sent like any other event from now
+            // on.
+            let next_scheduled_at = match queue.promote_due_scheduled_events().await {
+                Ok((promoted, next_scheduled_at)) => {
+                    if !promoted.is_empty() {
+                        if let Some(room) = room.get() {
+                            let send_queue = room.send_queue();
+                            for (transaction_id, content) in promoted {
+                                let send_handle = SendHandle {
+                                    room: send_queue.clone(),
+                                    transaction_id: transaction_id.clone(),
+                                    media_handles: Vec::new(),
+                                    created_at: MilliSecondsSinceUnixEpoch::now(),
+                                };
+
+                                let _ =
+                                    updates.send(RoomSendQueueUpdate::NewLocalEvent(LocalEcho {
+                                        transaction_id,
+                                        content: LocalEchoContent::Event {
+                                            serialized_event: content,
+                                            send_handle,
+                                            send_error: None,
+                                        },
+                                    }));
+                            }
+                        }
+                    }
+
+                    next_scheduled_at
+                }
+
+                Err(err) => {
+                    warn!("error when promoting scheduled events: {err}");
+                    None
+                }
+            };
+
             let (queued_request, cancel_upload_rx) = match queue.peek_next_to_send().await {
                 Ok(Some(request)) => request,
 
                 Ok(None) => {
-                    trace!("queue is empty, sleeping");
-                    // Wait for an explicit wakeup.
-                    notifier.notified().await;
+                    if let Some(send_at) = next_scheduled_at {
+                        let delay = duration_until(send_at);
+                        trace!(?delay, "queue is empty, sleeping until the next scheduled event");
+                        // Wait for an explicit wakeup, for the next scheduled event, or for the
+                        // room to be encrypted.
+                        tokio::select! {
+                            _ = notifier.notified() => {}
+                            _ = sleep(delay) => {}
+                            _ = wait_for_encryption(&mut room_info) => {
+                                should_take_back_scheduled_events = true;
+                            }
+                        }
+                    } else {
+                        trace!("queue is empty, sleeping");
+                        // Wait for an explicit wakeup, or for the room to be encrypted.
+                        tokio::select! {
+                            _ = notifier.notified() => {}
+                            _ = wait_for_encryption(&mut room_info) => {
+                                should_take_back_scheduled_events = true;
+                            }
+                        }
+                    }
                     continue;
                 }
 
@@ -568,6 +1030,11 @@ impl RoomSendQueue {
 
             let related_txn_id = as_variant!(&queued_request.kind, QueuedRequestKind::MediaUpload { related_to, .. } => related_to.clone());
 
+            // Hidden requests aren't known to the observers, so they're not told about
+            // them.
+            let is_hidden =
+                matches!(queued_request.kind, QueuedRequestKind::Redaction { hidden: true, .. });
+
             let Some(room) = room.get() else {
                 if is_dropping.load(Ordering::SeqCst) {
                     break;
@@ -576,10 +1043,25 @@ impl RoomSendQueue {
                 continue;
             };
 
-            match Self::handle_request(&room, queued_request, cancel_upload_rx).await {
+            let media_upload_progress_updates =
+                report_media_upload_progress.load(Ordering::SeqCst).then_some(&updates);
+
+            match Self::handle_request(
+                &room,
+                &queue,
+                queued_request,
+                cancel_upload_rx,
+                media_upload_progress_updates,
+            )
+            .await
+            {
                 Ok(Some(parent_key)) => match queue.mark_as_sent(&txn_id, parent_key.clone()).await
                 {
                     Ok(()) => match parent_key {
+                        SentRequestKey::Event(event_id) if is_hidden => {
+                            trace!(txn_id = %txn_id, %event_id, "hidden request sent");
+                        }
+
                         SentRequestKey::Event(event_id) => {
                             let _ = updates.send(RoomSendQueueUpdate::SentEvent {
                                 transaction_id: txn_id,
@@ -593,6 +1075,12 @@ impl RoomSendQueue {
                                 file: media_info.file,
                             });
                         }
+
+                        SentRequestKey::DelayedEvent(delay_id) => {
+                            // Not an event yet: observers will see it in the timeline once the
+                            // homeserver sends it.
+                            trace!(txn_id = %txn_id, %delay_id, "scheduled event handed over to the homeserver");
+                        }
                     },
 
                     Err(err) => {
@@ -624,6 +1112,18 @@ impl RoomSendQueue {
                         _ => false,
                     };
 
+                    if is_hidden && !is_recoverable {
+                        // A hidden request can't be unwedged, give up on it.
+                        warn!(txn_id = %txn_id, error = ?err, "Unrecoverable error when sending hidden request: {err}, giving up");
+
+                        queue.mark_as_not_being_sent(&txn_id).await;
+                        if let Err(storage_error) = queue.cancel_event(&txn_id).await {
+                            warn!("unable to remove hidden request: {storage_error}");
+                        }
+
+                        continue;
+                    }
+
                     // Disable the queue for this room after any kind of error happened.
                     locally_enabled.store(false, Ordering::SeqCst);
 
@@ -658,11 +1158,13 @@ impl RoomSendQueue {
                         is_recoverable,
                     });
 
-                    let _ = updates.send(RoomSendQueueUpdate::SendError {
-                        transaction_id: related_txn_id.unwrap_or(txn_id),
-                        error,
-                        is_recoverable,
-                    });
+                    if !is_hidden {
+                        let _ = updates.send(RoomSendQueueUpdate::SendError {
+                            transaction_id: related_txn_id.unwrap_or(txn_id),
+                            error,
+                            is_recoverable,
+                        });
+                    }
                 }
             }
         }
@@ -673,10 +1175,15 @@ impl RoomSendQueue {
     /// Handles a single request and returns the [`SentRequestKey`] on success
     /// (unless the request was cancelled, in which case it'll return
     /// `None`).
+    ///
+    /// If `media_upload_progress_updates` is set, the progress of a media
+    /// upload is reported to it.
     async fn handle_request(
         room: &Room,
+        queue: &QueueStorage,
         request: QueuedRequest,
         cancel_upload_rx: Option<oneshot::Receiver<()>>,
+        media_upload_progress_updates: Option<&broadcast::Sender<RoomSendQueueUpdate>>,
     ) -> Result<Option<SentRequestKey>, crate::Error> {
         match request.kind {
             QueuedRequestKind::Event { content } => {
@@ -692,15 +1199,107 @@ impl RoomSendQueue {
                 Ok(Some(SentRequestKey::Event(res.event_id)))
             }
 
+            QueuedRequestKind::ScheduledEvent { content, send_at, .. } => {
+                // Only scheduled events to be handed over to the homeserver are picked, the
+                // others are turned into regular events when their time has come.
+                let (event, event_type) = content.raw();
+
+                // A scheduled event might be handed over again after it's been edited, so the
+                // transaction id of the request can't be reused.
+                let delayed_request = delayed_message_event::unstable::Request::new_raw(
+                    room.room_id().to_owned(),
+                    TransactionId::new(),
+                    event_type.into(),
+                    DelayParameters::Timeout { timeout: duration_until(send_at) },
+                    event.clone(),
+                );
+
+                let res = room
+                    .client()
+                    .send(delayed_request)
+                    .with_request_config(RequestConfig::short_retry())
+                    .await?;
+
+                trace!(txn_id = %request.transaction_id, delay_id = %res.delay_id, "delayed event successfully sent");
+                Ok(Some(SentRequestKey::DelayedEvent(res.delay_id)))
+            }
+
+            QueuedRequestKind::StateEvent { event_type, state_key, content } => {
+                let state_request = send_state_event::v3::Request::new_raw(
+                    room.room_id().to_owned(),
+                    event_type.into(),
+                    state_key,
+                    content,
+                );
+
+                let res = room
+                    .client()
+                    .send(state_request)
+                    .with_request_config(RequestConfig::short_retry())
+                    .await?;
+
+                trace!(txn_id = %request.transaction_id, event_id = %res.event_id, "state event successfully sent");
+                Ok(Some(SentRequestKey::Event(res.event_id)))
+            }
+
+            QueuedRequestKind::Redaction { redacts, reason, .. } => {
+                let redact_request = assign!(
+                    redact_event::v3::Request::new(
+                        room.room_id().to_owned(),
+                        redacts,
+                        request.transaction_id.clone(),
+                    ),
+                    { reason }
+                );
+
+                let res = room
+                    .client()
+                    .send(redact_request)
+                    .with_request_config(RequestConfig::short_retry())
+                    .await?;
+
+                trace!(txn_id = %request.transaction_id, event_id = %res.event_id, "redaction successfully sent");
+                Ok(Some(SentRequestKey::Event(res.event_id)))
+            }
+
             QueuedRequestKind::MediaUpload {
                 content_type,
                 cache_key,
                 thumbnail_source,
                 related_to: relates_to,
+                accumulated,
+                resumable,
             } => {
                 trace!(%relates_to, "uploading media related to event");
 
-                let fut = async move {
+                // The medias are uploaded in order, the thumbnail of each media, if any, before
+                // the media itself.
+                let index = accumulated
+                    .iter()
+                    .map(|media| if media.thumbnail.is_some() { 2 } else { 1 })
+                    .sum::<u64>()
+                    + if thumbnail_source.is_some() { 1 } else { 0 };
+
+                let send_progress = SharedObservable::new(TransmissionProgress::default());
+                let mut progress_subscriber = media_upload_progress_updates
+                    .map(|updates| (updates, send_progress.subscribe()));
+
+                let report_progress = async {
+                    if let Some((updates, subscriber)) = &mut progress_subscriber {
+                        while let Some(progress) = subscriber.next().await {
+                            let _ = updates.send(RoomSendQueueUpdate::MediaUploadProgress {
+                                transaction_id: relates_to.clone(),
+                                index,
+                                progress,
+                            });
+                        }
+                    }
+
+                    // The upload is over, or its progress isn't reported, let it finish.
+                    std::future::pending::<()>().await
+                };
+
+                let fut = async {
                     let mime = Mime::from_str(&content_type).map_err(|_| {
                         crate::Error::SendQueueWedgeError(QueueWedgeError::InvalidMimeType {
                             mime_type: content_type.clone(),
@@ -718,32 +1317,58 @@ impl RoomSendQueue {
                             QueueWedgeError::MissingMediaContent,
                         ))?;
 
-                    #[cfg(feature = "e2e-encryption")]
-                    let media_source = if room.is_encrypted().await? {
-                        trace!("upload will be encrypted (encrypted room)");
-                        let mut cursor = std::io::Cursor::new(data);
-                        let encrypted_file = room
-                            .client()
-                            .upload_encrypted_file(&mime, &mut cursor)
-                            .with_request_config(RequestConfig::short_retry())
-                            .await?;
-                        MediaSource::Encrypted(Box::new(encrypted_file))
+                    let media_source = if resumable.is_some()
+                        || Self::should_upload_resumably(room, data.len()).await
+                    {
+                        Self::upload_media_resumably(
+                            room,
+                            queue,
+                            &request.transaction_id,
+                            &mime,
+                            data,
+                            resumable,
+                            send_progress,
+                        )
+                        .await?
                     } else {
-                        trace!("upload will be in clear text (room without encryption)");
-                        let request_config = RequestConfig::short_retry()
-                            .timeout(Media::reasonable_upload_timeout(&data));
-                        let res =
-                            room.client().media().upload(&mime, data, Some(request_config)).await?;
-                        MediaSource::Plain(res.content_uri)
-                    };
-
-                    #[cfg(not(feature = "e2e-encryption"))]
-                    let media_source = {
-                        let request_config = RequestConfig::short_retry()
-                            .timeout(Media::reasonable_upload_timeout(&data));
-                        let res =
-                            room.client().media().upload(&mime, data, Some(request_config)).await?;
-                        MediaSource::Plain(res.content_uri)
+                        #[cfg(feature = "e2e-encryption")]
+                        let media_source = if room.is_encrypted().await? {
+                            trace!("upload will be encrypted (encrypted room)");
+                            let mut cursor = std::io::Cursor::new(data);
+                            let encrypted_file = room
+                                .client()
+                                .upload_encrypted_file(&mime, &mut cursor)
+                                .with_request_config(RequestConfig::short_retry())
+                                .with_send_progress_observable(send_progress)
+                                .await?;
+                            MediaSource::Encrypted(Box::new(encrypted_file))
+                        } else {
+                            trace!("upload will be in clear text (room without encryption)");
+                            let request_config = RequestConfig::short_retry()
+                                .timeout(Media::reasonable_upload_timeout(&data));
+                            let res = room
+                                .client()
+                                .media()
+                                .upload(&mime, data, Some(request_config))
+                                .with_send_progress_observable(send_progress)
+                                .await?;
+                            MediaSource::Plain(res.content_uri)
+                        };
+
+                        #[cfg(not(feature = "e2e-encryption"))]
+                        let media_source = {
+                            let request_config = RequestConfig::short_retry()
+                                .timeout(Media::reasonable_upload_timeout(&data));
+                            let res = room
+                                .client()
+                                .media()
+                                .upload(&mime, data, Some(request_config))
+                                .with_send_progress_observable(send_progress)
+                                .await?;
+                            MediaSource::Plain(res.content_uri)
+                        };
+
+                        media_source
                     };
 
                     let uri = match &media_source {
@@ -755,6 +1380,7 @@ impl RoomSendQueue {
                     Ok(SentRequestKey::Media(SentMediaInfo {
                         file: media_source,
                         thumbnail: thumbnail_source,
+                        accumulated,
                     }))
                 };
 
@@ -773,6 +1399,10 @@ impl RoomSendQueue {
                         Ok(None)
                     }
 
+                    // Polled before the upload, so the latest progress is always reported before
+                    // the upload's outcome.
+                    _ = report_progress => unreachable!("reporting the progress never ends"),
+
                     res = fut => {
                         res.map(Some)
                     }
@@ -984,7 +1614,17 @@ impl QueueStorage {
         let queued_requests =
             guard.client()?.store().load_send_queue_requests(&self.room_id).await?;
 
-        if let Some(request) = queued_requests.iter().find(|queued| !queued.is_wedged()) {
+        if let Some(request) = queued_requests.iter().find(|queued| {
+            !queued.is_wedged()
+                && match &queued.kind {
+                    // Only the scheduled events to be handed over to the homeserver are picked,
+                    // the others wait in the queue until their time.
+                    QueuedRequestKind::ScheduledEvent { use_delayed_event, delay_id, .. } => {
+                        *use_delayed_event && delay_id.is_none()
+                    }
+                    _ => true,
+                }
+        }) {
             let (cancel_upload_tx, cancel_upload_rx) =
                 if matches!(request.kind, QueuedRequestKind::MediaUpload { .. }) {
                     let (tx, rx) = oneshot::channel();
@@ -1082,6 +1722,35 @@ impl QueueStorage {
         let client = guard.client()?;
         let store = client.store();
 
+        if let SentRequestKey::DelayedEvent(delay_id) = parent_key {
+            // The homeserver holds the event until its time, keep a record of it so it can
+            // still be manipulated.
+            let Some(QueuedRequestKind::ScheduledEvent {
+                content, send_at, use_delayed_event, ..
+            }) = Self::find_scheduled_event(&guard, &self.room_id, transaction_id)
+                .await?
+                .map(|request| request.kind)
+            else {
+                warn!(txn_id = %transaction_id, "scheduled event handed over to the homeserver was missing from storage");
+                return Ok(());
+            };
+
+            store
+                .update_send_queue_request(
+                    &self.room_id,
+                    transaction_id,
+                    QueuedRequestKind::ScheduledEvent {
+                        content,
+                        send_at,
+                        use_delayed_event,
+                        delay_id: Some(delay_id),
+                    },
+                )
+                .await?;
+
+            return Ok(());
+        }
+
         // Update all dependent requests.
         store
             .mark_dependent_queued_requests_as_ready(&self.room_id, transaction_id, parent_key)
@@ -1148,14 +1817,25 @@ impl QueueStorage {
         serializable: SerializableEventContent,
     ) -> Result<bool, RoomSendQueueStorageError> {
         let guard = self.store.lock().await;
+        let client = guard.client()?;
+        let store = client.store();
+
+        // State events and redactions can't be edited.
+        if store.load_send_queue_requests(&self.room_id).await?.into_iter().any(|request| {
+            request.transaction_id == transaction_id
+                && matches!(
+                    request.kind,
+                    QueuedRequestKind::StateEvent { .. } | QueuedRequestKind::Redaction { .. }
+                )
+        }) {
+            return Ok(false);
+        }
 
         if guard.being_sent.as_ref().map(|info| info.transaction_id.as_ref())
             == Some(transaction_id)
         {
             // Save the intent to edit the associated event.
-            guard
-                .client()?
-                .store()
+            store
                 .save_dependent_queued_request(
                     &self.room_id,
                     transaction_id,
@@ -1168,9 +1848,7 @@ impl QueueStorage {
             return Ok(true);
         }
 
-        let edited = guard
-            .client()?
-            .store()
+        let edited = store
             .update_send_queue_request(&self.room_id, transaction_id, serializable.into())
             .await?;
 
@@ -1189,7 +1867,7 @@ impl QueueStorage {
         created_at: MilliSecondsSinceUnixEpoch,
         upload_file_txn: OwnedTransactionId,
         file_media_request: MediaRequestParameters,
-        thumbnail: Option<(FinishUploadThumbnailInfo, MediaRequestParameters, Mime)>,
+        thumbnail: Option<QueueThumbnailInfo>,
     ) -> Result<(), RoomSendQueueStorageError> {
         let guard = self.store.lock().await;
         let client = guard.client()?;
@@ -1211,6 +1889,8 @@ impl QueueStorage {
                             cache_key: thumbnail_media_request,
                             thumbnail_source: None, // the thumbnail has no thumbnails :)
                             related_to: send_event_txn.clone(),
+                            accumulated: Vec::new(),
+                            resumable: None,
                         },
                         Self::LOW_PRIORITY,
                     )
@@ -1244,6 +1924,8 @@ impl QueueStorage {
                             cache_key: file_media_request,
                             thumbnail_source: None,
                             related_to: send_event_txn.clone(),
+                            accumulated: Vec::new(),
+                            resumable: None,
                         },
                         Self::LOW_PRIORITY,
                     )
@@ -1267,51 +1949,557 @@ impl QueueStorage {
             )
             .await?;
 
-        Ok(())
+        Ok(())
+    }
+
+    /// Push requests (and dependents) to upload the medias of a gallery.
+    ///
+    /// See the module-level description for details of the whole process.
+    async fn push_gallery(
+        &self,
+        event: RoomMessageEventContent,
+        send_event_txn: OwnedTransactionId,
+        created_at: MilliSecondsSinceUnixEpoch,
+        items: Vec<GalleryItemQueueInfo>,
+    ) -> Result<(), RoomSendQueueStorageError> {
+        let guard = self.store.lock().await;
+        let client = guard.client()?;
+        let store = client.store();
+
+        let mut item_infos = Vec::with_capacity(items.len());
+        let mut previous_file_upload: Option<OwnedTransactionId> = None;
+
+        for item in items {
+            let GalleryItemQueueInfo {
+                content_type,
+                upload_file_txn,
+                file_media_request,
+                thumbnail,
+            } = item;
+
+            let thumbnail_info =
+                if let Some((thumbnail_info, thumbnail_media_request, thumbnail_content_type)) =
+                    thumbnail
+                {
+                    let upload_thumbnail_txn = thumbnail_info.txn.clone();
+
+                    // Save the thumbnail upload request.
+                    self.push_gallery_upload(
+                        store,
+                        previous_file_upload.as_deref(),
+                        upload_thumbnail_txn.clone(),
+                        created_at,
+                        thumbnail_content_type.to_string(),
+                        thumbnail_media_request,
+                        &send_event_txn,
+                    )
+                    .await?;
+
+                    // Save the file upload request as a dependent request of the thumbnail
+                    // upload.
+                    store
+                        .save_dependent_queued_request(
+                            &self.room_id,
+                            &upload_thumbnail_txn,
+                            upload_file_txn.clone().into(),
+                            created_at,
+                            DependentQueuedRequestKind::UploadFileOrThumbnail {
+                                content_type: content_type.to_string(),
+                                cache_key: file_media_request,
+                                related_to: send_event_txn.clone(),
+                                parent_is_thumbnail_upload: true,
+                            },
+                        )
+                        .await?;
+
+                    Some(thumbnail_info)
+                } else {
+                    // Save the file upload request.
+                    self.push_gallery_upload(
+                        store,
+                        previous_file_upload.as_deref(),
+                        upload_file_txn.clone(),
+                        created_at,
+                        content_type.to_string(),
+                        file_media_request,
+                        &send_event_txn,
+                    )
+                    .await?;
+
+                    None
+                };
+
+            item_infos.push(FinishGalleryItemInfo {
+                file_upload: upload_file_txn.clone(),
+                thumbnail_info,
+            });
+            previous_file_upload = Some(upload_file_txn);
+        }
+
+        let Some(last_file_upload) = previous_file_upload else {
+            // Nothing to upload, there's nothing to send either.
+            return Ok(());
+        };
+
+        // Push the dependent request for the event itself, depending on the last
+        // upload.
+        store
+            .save_dependent_queued_request(
+                &self.room_id,
+                &last_file_upload,
+                send_event_txn.into(),
+                created_at,
+                DependentQueuedRequestKind::FinishGallery { local_echo: event, item_infos },
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    /// Push the upload of a media of a gallery, or its thumbnail: as a request
+    /// if it's the first upload of the gallery, as a dependent request of the
+    /// file upload of the previous item otherwise.
+    #[allow(clippy::too_many_arguments)]
+    async fn push_gallery_upload(
+        &self,
+        store: &DynStateStore,
+        previous_file_upload: Option<&TransactionId>,
+        upload_txn: OwnedTransactionId,
+        created_at: MilliSecondsSinceUnixEpoch,
+        content_type: String,
+        cache_key: MediaRequestParameters,
+        send_event_txn: &TransactionId,
+    ) -> Result<(), RoomSendQueueStorageError> {
+        if let Some(previous_file_upload) = previous_file_upload {
+            store
+                .save_dependent_queued_request(
+                    &self.room_id,
+                    previous_file_upload,
+                    upload_txn.into(),
+                    created_at,
+                    DependentQueuedRequestKind::UploadFileOrThumbnail {
+                        content_type,
+                        cache_key,
+                        related_to: send_event_txn.to_owned(),
+                        parent_is_thumbnail_upload: false,
+                    },
+                )
+                .await?;
+        } else {
+            store
+                .save_send_queue_request(
+                    &self.room_id,
+                    upload_txn,
+                    created_at,
+                    QueuedRequestKind::MediaUpload {
+                        content_type,
+                        cache_key,
+                        thumbnail_source: None,
+                        related_to: send_event_txn.to_owned(),
+                        accumulated: Vec::new(),
+                        resumable: None,
+                    },
+                    Self::LOW_PRIORITY,
+                )
+                .await?;
+        }
+
+        Ok(())
+    }
+
+    /// Reacts to the given local echo of an event.
+    #[instrument(skip(self))]
+    async fn react(
+        &self,
+        transaction_id: &TransactionId,
+        key: String,
+        created_at: MilliSecondsSinceUnixEpoch,
+    ) -> Result<Option<ChildTransactionId>, RoomSendQueueStorageError> {
+        let guard = self.store.lock().await;
+        let client = guard.client()?;
+        let store = client.store();
+
+        let requests = store.load_send_queue_requests(&self.room_id).await?;
+
+        // State events and redactions can't be reacted to.
+        if requests.iter().any(|item| {
+            item.transaction_id == transaction_id
+                && matches!(
+                    item.kind,
+                    QueuedRequestKind::StateEvent { .. } | QueuedRequestKind::Redaction { .. }
+                )
+        }) {
+            return Ok(None);
+        }
+
+        // If the target event has been already sent, abort immediately.
+        if !requests.iter().any(|item| item.transaction_id == transaction_id) {
+            // We didn't find it as a queued request; try to find it as a dependent queued
+            // request.
+            let dependent_requests = store.load_dependent_queued_requests(&self.room_id).await?;
+            if !dependent_requests
+                .into_iter()
+                .filter_map(|item| item.is_own_event().then_some(item.own_transaction_id))
+                .any(|child_txn| *child_txn == *transaction_id)
+            {
+                // We didn't find it as either a request or a dependent request, abort.
+                return Ok(None);
+            }
+        }
+
+        // Record the dependent request.
+        let reaction_txn_id = ChildTransactionId::new();
+        store
+            .save_dependent_queued_request(
+                &self.room_id,
+                transaction_id,
+                reaction_txn_id.clone(),
+                created_at,
+                DependentQueuedRequestKind::ReactEvent { key },
+            )
+            .await?;
+
+        Ok(Some(reaction_txn_id))
+    }
+
+    /// Find the request of the scheduled event with the given transaction id,
+    /// unless it's being handed over to the homeserver.
+    async fn find_scheduled_event(
+        guard: &StoreLockGuard,
+        room_id: &RoomId,
+        transaction_id: &TransactionId,
+    ) -> Result<Option<QueuedRequest>, RoomSendQueueStorageError> {
+        if guard.being_sent.as_ref().is_some_and(|info| info.transaction_id == transaction_id) {
+            return Ok(None);
+        }
+
+        Ok(guard
+            .client()?
+            .store()
+            .load_send_queue_requests(room_id)
+            .await?
+            .into_iter()
+            .find(|request| request.transaction_id == transaction_id)
+            .filter(|request| matches!(request.kind, QueuedRequestKind::ScheduledEvent { .. })))
+    }
+
+    /// Returns the scheduled event with the given transaction id, unless it's
+    /// being handed over to the homeserver or has been sent already.
+    async fn scheduled_event(
+        &self,
+        transaction_id: &TransactionId,
+    ) -> Result<Option<QueuedRequestKind>, RoomSendQueueStorageError> {
+        let guard = self.store.lock().await;
+        Ok(Self::find_scheduled_event(&guard, &self.room_id, transaction_id)
+            .await?
+            .map(|request| request.kind))
+    }
+
+    /// Replace the request of a scheduled event, unless it's being handed over
+    /// to the homeserver or has been sent already.
+    ///
+    /// Returns whether the request has been replaced.
+    async fn replace_scheduled_event(
+        &self,
+        transaction_id: &TransactionId,
+        kind: QueuedRequestKind,
+    ) -> Result<bool, RoomSendQueueStorageError> {
+        let guard = self.store.lock().await;
+
+        if Self::find_scheduled_event(&guard, &self.room_id, transaction_id).await?.is_none() {
+            return Ok(false);
+        }
+
+        Ok(guard
+            .client()?
+            .store()
+            .update_send_queue_request(&self.room_id, transaction_id, kind)
+            .await?)
+    }
+
+    /// Update the content or the time of a scheduled event, unless it's being
+    /// handed over to the homeserver or has been sent already.
+    ///
+    /// The delayed event it's been handed over as, if any, is forgotten in the
+    /// same step, so the new version is handed over again.
+    ///
+    /// Returns the previous request, if it's been updated.
+    async fn update_scheduled_event(
+        &self,
+        transaction_id: &TransactionId,
+        new_content: Option<SerializableEventContent>,
+        new_send_at: Option<MilliSecondsSinceUnixEpoch>,
+    ) -> Result<Option<QueuedRequest>, RoomSendQueueStorageError> {
+        let guard = self.store.lock().await;
+
+        let Some(previous) =
+            Self::find_scheduled_event(&guard, &self.room_id, transaction_id).await?
+        else {
+            return Ok(None);
+        };
+
+        let QueuedRequestKind::ScheduledEvent { content, send_at, use_delayed_event, .. } =
+            previous.kind.clone()
+        else {
+            return Ok(None);
+        };
+
+        let kind = QueuedRequestKind::ScheduledEvent {
+            content: new_content.unwrap_or(content),
+            send_at: new_send_at.unwrap_or(send_at),
+            use_delayed_event,
+            delay_id: None,
+        };
+
+        let updated = guard
+            .client()?
+            .store()
+            .update_send_queue_request(&self.room_id, transaction_id, kind)
+            .await?;
+
+        Ok(updated.then_some(previous))
+    }
+
+    /// Remove the request of a scheduled event, unless it's being handed over
+    /// to the homeserver or has been sent already.
+    ///
+    /// Returns the removed request, if any.
+    async fn remove_scheduled_event(
+        &self,
+        transaction_id: &TransactionId,
+    ) -> Result<Option<QueuedRequest>, RoomSendQueueStorageError> {
+        let guard = self.store.lock().await;
+
+        let Some(request) =
+            Self::find_scheduled_event(&guard, &self.room_id, transaction_id).await?
+        else {
+            return Ok(None);
+        };
+
+        let removed = guard
+            .client()?
+            .store()
+            .remove_send_queue_request(&self.room_id, transaction_id)
+            .await?;
+
+        Ok(removed.then_some(request))
+    }
+
+    /// Restore the request of a scheduled event that has been updated or
+    /// removed with [`Self::update_scheduled_event()`] or
+    /// [`Self::remove_scheduled_event()`], because its delayed event couldn't
+    /// be updated on the homeserver.
+    async fn restore_scheduled_event(
+        &self,
+        request: QueuedRequest,
+    ) -> Result<(), RoomSendQueueStorageError> {
+        let guard = self.store.lock().await;
+        let client = guard.client()?;
+        let store = client.store();
+
+        let current = store
+            .load_send_queue_requests(&self.room_id)
+            .await?
+            .into_iter()
+            .find(|current| current.transaction_id == request.transaction_id);
+
+        let Some(current) = current else {
+            store
+                .save_send_queue_request(
+                    &self.room_id,
+                    request.transaction_id,
+                    request.created_at,
+                    request.kind,
+                    request.priority,
+                )
+                .await?;
+            return Ok(());
+        };
+
+        let is_handed_over_again = guard
+            .being_sent
+            .as_ref()
+            .is_some_and(|info| info.transaction_id == current.transaction_id)
+            || matches!(current.kind, QueuedRequestKind::ScheduledEvent { delay_id: Some(_), .. });
+
+        if is_handed_over_again {
+            // Don't forget about the new delayed event, the previous one is still held by
+            // the homeserver though.
+            warn!(txn_id = %request.transaction_id, "the scheduled event has been handed over again, can't restore it");
+            return Ok(());
+        }
+
+        store
+            .update_send_queue_request(&self.room_id, &request.transaction_id, request.kind)
+            .await?;
+
+        Ok(())
+    }
+
+    /// Returns the transaction id of the scheduled events to be handed over to
+    /// the homeserver, along with the id of the delayed event they've been
+    /// handed over as, if any.
+    async fn scheduled_events_using_delayed_events(
+        &self,
+    ) -> Result<Vec<(OwnedTransactionId, Option<String>)>, RoomSendQueueStorageError> {
+        let guard = self.store.lock().await;
+
+        Ok(guard
+            .client()?
+            .store()
+            .load_send_queue_requests(&self.room_id)
+            .await?
+            .into_iter()
+            .filter_map(|request| match request.kind {
+                QueuedRequestKind::ScheduledEvent { use_delayed_event: true, delay_id, .. } => {
+                    Some((request.transaction_id, delay_id))
+                }
+                _ => None,
+            })
+            .collect())
+    }
+
+    /// Keep the given scheduled event in the queue until its time, instead of
+    /// handing it over to the homeserver.
+    async fn keep_scheduled_event_locally(
+        &self,
+        transaction_id: &TransactionId,
+    ) -> Result<(), RoomSendQueueStorageError> {
+        let guard = self.store.lock().await;
+
+        let Some(QueuedRequestKind::ScheduledEvent { content, send_at, .. }) =
+            Self::find_scheduled_event(&guard, &self.room_id, transaction_id)
+                .await?
+                .map(|request| request.kind)
+        else {
+            return Ok(());
+        };
+
+        guard
+            .client()?
+            .store()
+            .update_send_queue_request(
+                &self.room_id,
+                transaction_id,
+                QueuedRequestKind::ScheduledEvent {
+                    content,
+                    send_at,
+                    use_delayed_event: false,
+                    delay_id: None,
+                },
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    /// Turn the scheduled events kept locally whose time has come into regular
+    /// events, and forget about the ones the homeserver has sent.
+    ///
+    /// Returns the transaction id and content of the events that have been
+    /// turned into regular events, and the time of the next scheduled event
+    /// kept locally, if any.
+    async fn promote_due_scheduled_events(
+        &self,
+    ) -> Result<
+        (Vec<(OwnedTransactionId, SerializableEventContent)>, Option<MilliSecondsSinceUnixEpoch>),
+        RoomSendQueueStorageError,
+    > {
+        let guard = self.store.lock().await;
+        let client = guard.client()?;
+        let store = client.store();
+
+        let now = MilliSecondsSinceUnixEpoch::now();
+        let mut promoted = Vec::new();
+        let mut next_scheduled_at = None;
+
+        for request in store.load_send_queue_requests(&self.room_id).await? {
+            let is_wedged = request.is_wedged();
+
+            let QueuedRequestKind::ScheduledEvent { content, send_at, use_delayed_event, delay_id } =
+                request.kind
+            else {
+                continue;
+            };
+
+            if guard
+                .being_sent
+                .as_ref()
+                .is_some_and(|info| info.transaction_id == request.transaction_id)
+            {
+                continue;
+            }
+
+            if send_at > now {
+                // Events that couldn't be handed over to the homeserver are sent by the queue
+                // at their time.
+                if delay_id.is_none() && (!use_delayed_event || is_wedged) {
+                    next_scheduled_at =
+                        Some(next_scheduled_at.map_or(send_at, |next| send_at.min(next)));
+                }
+                continue;
+            }
+
+            if delay_id.is_some() {
+                // The homeserver has sent the event.
+                store.remove_send_queue_request(&self.room_id, &request.transaction_id).await?;
+                continue;
+            }
+
+            trace!(txn_id = %request.transaction_id, "the time of a scheduled event has come");
+
+            store
+                .update_send_queue_request(
+                    &self.room_id,
+                    &request.transaction_id,
+                    content.clone().into(),
+                )
+                .await?;
+
+            promoted.push((request.transaction_id, content));
+        }
+
+        Ok((promoted, next_scheduled_at))
     }
 
-    /// Reacts to the given local echo of an event.
-    #[instrument(skip(self))]
-    async fn react(
+    /// Returns the scheduled events that haven't been sent yet.
+    async fn scheduled_events(
         &self,
-        transaction_id: &TransactionId,
-        key: String,
-        created_at: MilliSecondsSinceUnixEpoch,
-    ) -> Result<Option<ChildTransactionId>, RoomSendQueueStorageError> {
+        room: &RoomSendQueue,
+    ) -> Result<Vec<ScheduledEvent>, RoomSendQueueStorageError> {
         let guard = self.store.lock().await;
-        let client = guard.client()?;
-        let store = client.store();
-
-        let requests = store.load_send_queue_requests(&self.room_id).await?;
+        let now = MilliSecondsSinceUnixEpoch::now();
 
-        // If the target event has been already sent, abort immediately.
-        if !requests.iter().any(|item| item.transaction_id == transaction_id) {
-            // We didn't find it as a queued request; try to find it as a dependent queued
-            // request.
-            let dependent_requests = store.load_dependent_queued_requests(&self.room_id).await?;
-            if !dependent_requests
-                .into_iter()
-                .filter_map(|item| item.is_own_event().then_some(item.own_transaction_id))
-                .any(|child_txn| *child_txn == *transaction_id)
-            {
-                // We didn't find it as either a request or a dependent request, abort.
-                return Ok(None);
-            }
-        }
+        Ok(guard
+            .client()?
+            .store()
+            .load_send_queue_requests(&self.room_id)
+            .await?
+            .into_iter()
+            .filter_map(|request| {
+                let QueuedRequestKind::ScheduledEvent { content, send_at, delay_id, .. } =
+                    request.kind
+                else {
+                    return None;
+                };
 
-        // Record the dependent request.
-        let reaction_txn_id = ChildTransactionId::new();
-        store
-            .save_dependent_queued_request(
-                &self.room_id,
-                transaction_id,
-                reaction_txn_id.clone(),
-                created_at,
-                DependentQueuedRequestKind::ReactEvent { key },
-            )
-            .await?;
+                // The homeserver has sent the event already, it's only waiting to be forgotten.
+                if delay_id.is_some() && send_at <= now {
+                    return None;
+                }
 
-        Ok(Some(reaction_txn_id))
+                Some(ScheduledEvent {
+                    transaction_id: request.transaction_id.clone(),
+                    content,
+                    send_at,
+                    is_held_by_homeserver: delay_id.is_some(),
+                    handle: ScheduledEventHandle {
+                        room: room.clone(),
+                        transaction_id: request.transaction_id,
+                    },
+                })
+            })
+            .collect())
     }
 
     /// Returns a list of the local echoes, that is, all the requests that we're
@@ -1334,7 +2522,7 @@ impl QueueStorage {
                             send_handle: SendHandle {
                                 room: room.clone(),
                                 transaction_id: queued.transaction_id,
-                                media_handles: None,
+                                media_handles: Vec::new(),
                                 created_at: queued.created_at,
                             },
                             send_error: queued.error,
@@ -1345,6 +2533,45 @@ impl QueueStorage {
                             // event represented as a dependent request should be sufficient.
                             return None;
                         }
+
+                        QueuedRequestKind::ScheduledEvent { .. } => {
+                            // Scheduled events are only reflected once their time has come.
+                            return None;
+                        }
+
+                        QueuedRequestKind::StateEvent { event_type, state_key, content } => {
+                            LocalEchoContent::State {
+                                event_type,
+                                state_key,
+                                content,
+                                send_handle: SendHandle {
+                                    room: room.clone(),
+                                    transaction_id: queued.transaction_id,
+                                    media_handles: Vec::new(),
+                                    created_at: queued.created_at,
+                                },
+                                send_error: queued.error,
+                            }
+                        }
+
+                        QueuedRequestKind::Redaction { hidden: true, .. } => {
+                            // The redaction of an aborted event isn't reflected.
+                            return None;
+                        }
+
+                        QueuedRequestKind::Redaction { redacts, reason, hidden: false } => {
+                            LocalEchoContent::Redaction {
+                                redacts,
+                                reason,
+                                send_handle: SendHandle {
+                                    room: room.clone(),
+                                    transaction_id: queued.transaction_id,
+                                    media_handles: Vec::new(),
+                                    created_at: queued.created_at,
+                                },
+                                send_error: queued.error,
+                            }
+                        }
                     },
                 })
             });
@@ -1372,7 +2599,8 @@ impl QueueStorage {
                     },
                 }),
 
-                DependentQueuedRequestKind::UploadFileWithThumbnail { .. } => {
+                DependentQueuedRequestKind::UploadFileWithThumbnail { .. }
+                | DependentQueuedRequestKind::UploadFileOrThumbnail { .. } => {
                     // Don't reflect these: only the associated event is interesting to observers.
                     None
                 }
@@ -1391,10 +2619,34 @@ impl QueueStorage {
                             send_handle: SendHandle {
                                 room: room.clone(),
                                 transaction_id: dep.own_transaction_id.into(),
-                                media_handles: Some(MediaHandles {
+                                media_handles: vec![MediaHandles {
                                     upload_thumbnail_txn: thumbnail_info.map(|info| info.txn),
                                     upload_file_txn: file_upload,
-                                }),
+                                }],
+                                created_at: dep.created_at,
+                            },
+                            send_error: None,
+                        },
+                    })
+                }
+
+                DependentQueuedRequestKind::FinishGallery { local_echo, item_infos } => {
+                    // Materialize as an event local echo.
+                    Some(LocalEcho {
+                        transaction_id: dep.own_transaction_id.clone().into(),
+                        content: LocalEchoContent::Event {
+                            serialized_event: SerializableEventContent::new(&local_echo.into())
+                                .ok()?,
+                            send_handle: SendHandle {
+                                room: room.clone(),
+                                transaction_id: dep.own_transaction_id.into(),
+                                media_handles: item_infos
+                                    .into_iter()
+                                    .map(|info| MediaHandles {
+                                        upload_thumbnail_txn: info.thumbnail_info.map(|t| t.txn),
+                                        upload_file_txn: info.file_upload,
+                                    })
+                                    .collect(),
                                 created_at: dep.created_at,
                             },
                             send_error: None,
@@ -1516,25 +2768,24 @@ impl QueueStorage {
                         ));
                     };
 
-                    // The parent event has been sent; send a redaction.
-                    let room = client
-                        .get_room(&self.room_id)
-                        .ok_or(RoomSendQueueError::RoomDisappeared)?;
-
-                    // Ideally we'd use the send queue to send the redaction, but the protocol has
-                    // changed the shape of a room.redaction after v11, so keep it simple and try
-                    // once here.
-
+                    // The parent event has been sent; queue a redaction in the send queue 🧠.
                     // Note: no reason is provided because we materialize the intent of "cancel
-                    // sending the parent event".
-
-                    if let Err(err) = room
-                        .redact(&event_id, None, Some(dependent_request.own_transaction_id.into()))
+                    // sending the parent event". The aborted event has already been reported as
+                    // cancelled, so the redaction is hidden from the observers.
+                    store
+                        .save_send_queue_request(
+                            &self.room_id,
+                            dependent_request.own_transaction_id.into(),
+                            dependent_request.created_at,
+                            QueuedRequestKind::Redaction {
+                                redacts: event_id,
+                                reason: None,
+                                hidden: true,
+                            },
+                            Self::HIGH_PRIORITY,
+                        )
                         .await
-                    {
-                        warn!("error when sending a redact for {event_id}: {err}");
-                        return Ok(false);
-                    }
+                        .map_err(RoomSendQueueStorageError::StateStoreError)?;
                 } else {
                     // The parent event is still local (sending must have failed); redact the local
                     // echo.
@@ -1605,6 +2856,28 @@ impl QueueStorage {
                 .await?;
             }
 
+            DependentQueuedRequestKind::UploadFileOrThumbnail {
+                content_type,
+                cache_key,
+                related_to,
+                parent_is_thumbnail_upload,
+            } => {
+                let Some(parent_key) = parent_key else {
+                    // Not finished yet, we should retry later => false.
+                    return Ok(false);
+                };
+                self.handle_dependent_file_or_thumbnail_upload(
+                    client,
+                    dependent_request.own_transaction_id.into(),
+                    parent_key,
+                    content_type,
+                    cache_key,
+                    related_to,
+                    parent_is_thumbnail_upload,
+                )
+                .await?;
+            }
+
             DependentQueuedRequestKind::FinishUpload {
                 local_echo,
                 file_upload,
@@ -1625,6 +2898,22 @@ impl QueueStorage {
                 )
                 .await?;
             }
+
+            DependentQueuedRequestKind::FinishGallery { local_echo, item_infos } => {
+                let Some(parent_key) = parent_key else {
+                    // Not finished yet, we should retry later => false.
+                    return Ok(false);
+                };
+                self.handle_dependent_finish_gallery(
+                    client,
+                    dependent_request.own_transaction_id.into(),
+                    parent_key,
+                    local_echo,
+                    item_infos,
+                    new_updates,
+                )
+                .await?;
+            }
         }
 
         Ok(true)
@@ -1743,6 +3032,34 @@ pub enum LocalEchoContent {
         /// The local echo which has been reacted to.
         applies_to: OwnedTransactionId,
     },
+
+    /// The local echo contains a state event ready to display.
+    State {
+        /// The type of the state event.
+        event_type: String,
+        /// The state key of the state event.
+        state_key: String,
+        /// Content of the state event we are about to send.
+        content: Raw<AnyStateEventContent>,
+        /// A handle to manipulate the sending of the state event.
+        send_handle: SendHandle,
+        /// Whether trying to send this local echo failed in the past with an
+        /// unrecoverable error (see [`SendQueueRoomError::is_recoverable`]).
+        send_error: Option<QueueWedgeError>,
+    },
+
+    /// An event that has been sent already is about to be redacted.
+    Redaction {
+        /// The event that is about to be redacted.
+        redacts: OwnedEventId,
+        /// The reason of the redaction, if any.
+        reason: Option<String>,
+        /// A handle to manipulate the sending of the redaction.
+        send_handle: SendHandle,
+        /// Whether trying to send this local echo failed in the past with an
+        /// unrecoverable error (see [`SendQueueRoomError::is_recoverable`]).
+        send_error: Option<QueueWedgeError>,
+    },
 }
 
 /// A local representation for a request that hasn't been sent yet to the user's
@@ -1813,6 +3130,24 @@ pub enum RoomSendQueueUpdate {
         event_id: OwnedEventId,
     },
 
+    /// A media upload has made progress.
+    ///
+    /// Only sent if [`SendQueue::enable_upload_progress()`] has been called.
+    MediaUploadProgress {
+        /// Transaction id of the media event this upload relates to.
+        transaction_id: OwnedTransactionId,
+
+        /// The index of the upload among the uploads of the media event.
+        ///
+        /// The thumbnail, if any, is uploaded first at index 0, then the file.
+        /// For a gallery, the uploads of each item follow the ones of the
+        /// previous item.
+        index: u64,
+
+        /// The progress of the upload.
+        progress: TransmissionProgress,
+    },
+
     /// A media has been successfully uploaded.
     UploadedMedia {
         /// The media event this uploaded media relates to.
@@ -1836,6 +3171,15 @@ pub enum RoomSendQueueError {
     #[error("the room is now missing from the client")]
     RoomDisappeared,
 
+    /// A gallery can't be sent without any item.
+    #[error("a gallery must contain at least one item")]
+    EmptyGallery,
+
+    /// Error coming from the homeserver, when updating a scheduled event it
+    /// holds.
+    #[error(transparent)]
+    Http(#[from] HttpError),
+
     /// Error coming from storage.
     #[error(transparent)]
     StorageError(#[from] RoomSendQueueStorageError),
@@ -1890,6 +3234,26 @@ struct MediaHandles {
     upload_file_txn: OwnedTransactionId,
 }
 
+/// Information needed to queue the upload of a thumbnail: the record used
+/// when finishing the upload, the request to retrieve the thumbnail from the
+/// cache store, and its content type.
+type QueueThumbnailInfo = (FinishUploadThumbnailInfo, MediaRequestParameters, Mime);
+
+/// Information needed to queue the uploads of an item of a gallery.
+struct GalleryItemQueueInfo {
+    /// Content type of the file.
+    content_type: Mime,
+
+    /// Transaction id used when uploading the file.
+    upload_file_txn: OwnedTransactionId,
+
+    /// Request to retrieve the file from the cache store.
+    file_media_request: MediaRequestParameters,
+
+    /// Information to queue the upload of the thumbnail, if any.
+    thumbnail: Option<QueueThumbnailInfo>,
+}
+
 /// A handle to manipulate an event that was scheduled to be sent to a room.
 #[derive(Clone, Debug)]
 pub struct SendHandle {
@@ -1902,8 +3266,8 @@ pub struct SendHandle {
     /// one used to send the event, and that will be seen by observers.
     transaction_id: OwnedTransactionId,
 
-    /// Additional handles for a media upload.
-    media_handles: Option<MediaHandles>,
+    /// Additional handles for a media upload, one per media of the event.
+    media_handles: Vec<MediaHandles>,
 
     /// The time at which the event to be sent has been created.
     pub created_at: MilliSecondsSinceUnixEpoch,
@@ -1911,7 +3275,7 @@ pub struct SendHandle {
 
 impl SendHandle {
     fn nyi_for_uploads(&self) -> Result<(), RoomSendQueueStorageError> {
-        if self.media_handles.is_some() {
+        if !self.media_handles.is_empty() {
             Err(RoomSendQueueStorageError::OperationNotImplementedYet)
         } else {
             Ok(())
@@ -1928,8 +3292,8 @@ impl SendHandle {
 
         let queue = &self.room.inner.queue;
 
-        if let Some(handles) = &self.media_handles {
-            if queue.abort_upload(&self.transaction_id, handles).await? {
+        if !self.media_handles.is_empty() {
+            if queue.abort_upload(&self.transaction_id, &self.media_handles).await? {
                 // Propagate a cancelled update.
                 let _ = self.room.inner.updates.send(RoomSendQueueUpdate::CancelledLocalEvent {
                     transaction_id: self.transaction_id.clone(),
@@ -2057,11 +3421,11 @@ impl SendHandle {
         // If we have media handles, also try to unwedge them.
         //
         // It's fine to always do it to *all* the transaction IDs at once, because only
-        // one of the three requests will be active at the same time, i.e. only
-        // one entry will be updated in the store. The other two are either
-        // done, or dependent requests.
+        // one of the requests will be active at the same time, i.e. only one entry
+        // will be updated in the store. The other ones are either done, or
+        // dependent requests.
 
-        if let Some(handles) = &self.media_handles {
+        for handles in &self.media_handles {
             room.queue
                 .mark_as_unwedged(&handles.upload_file_txn)
                 .await
@@ -2158,7 +3522,7 @@ impl SendReactionHandle {
         let handle = SendHandle {
             room: self.room.clone(),
             transaction_id: self.transaction_id.clone().into(),
-            media_handles: None,
+            media_handles: Vec::new(),
             created_at: MilliSecondsSinceUnixEpoch::now(),
         };
 
@@ -2171,6 +3535,222 @@ impl SendReactionHandle {
     }
 }
 
+/// An event scheduled to be sent later to a room, with
+/// [`RoomSendQueue::send_scheduled()`].
+#[derive(Clone, Debug)]
+pub struct ScheduledEvent {
+    /// Transaction id used to identify the scheduled event.
+    pub transaction_id: OwnedTransactionId,
+
+    /// Content of the event itself (along with its type).
+    pub content: SerializableEventContent,
+
+    /// The time at which the event will be sent.
+    pub send_at: MilliSecondsSinceUnixEpoch,
+
+    /// Whether the event is held by the homeserver until its time, in which
+    /// case it's sent even if the client isn't running anymore.
+    pub is_held_by_homeserver: bool,
+
+    /// A handle to manipulate the scheduled event.
+    pub handle: ScheduledEventHandle,
+}
+
+/// A handle to manipulate an event scheduled with
+/// [`RoomSendQueue::send_scheduled()`].
+#[derive(Clone, Debug)]
+pub struct ScheduledEventHandle {
+    /// Link to the send queue used to send this event.
+    room: RoomSendQueue,
+
+    /// Transaction id used to identify the scheduled event.
+    transaction_id: OwnedTransactionId,
+}
+
+impl ScheduledEventHandle {
+    /// Edits the content of the scheduled event.
+    ///
+    /// Returns true if the event has been edited, false if it's too late: the
+    /// event has been sent already, or is being handed over to the homeserver
+    /// at the moment.
+    pub async fn edit(
+        &self,
+        new_content: AnyMessageLikeEventContent,
+    ) -> Result<bool, RoomSendQueueError> {
+        let new_content = SerializableEventContent::new(&new_content)
+            .map_err(RoomSendQueueStorageError::JsonSerialization)?;
+
+        self.update(Some(new_content), None).await
+    }
+
+    /// Changes the time at which the scheduled event will be sent.
+    ///
+    /// Returns true if the event has been rescheduled, false if it's too late:
+    /// the event has been sent already, or is being handed over to the
+    /// homeserver at the moment.
+    pub async fn reschedule(
+        &self,
+        send_at: MilliSecondsSinceUnixEpoch,
+    ) -> Result<bool, RoomSendQueueError> {
+        self.update(None, Some(send_at)).await
+    }
+
+    async fn update(
+        &self,
+        new_content: Option<SerializableEventContent>,
+        new_send_at: Option<MilliSecondsSinceUnixEpoch>,
+    ) -> Result<bool, RoomSendQueueError> {
+        trace!(txn_id = %self.transaction_id, "updating scheduled event");
+
+        let Some(previous) = self
+            .room
+            .inner
+            .queue
+            .update_scheduled_event(&self.transaction_id, new_content, new_send_at)
+            .await?
+        else {
+            return Ok(false);
+        };
+
+        // Delayed events can't be edited: cancel it, and hand the new version over
+        // again.
+        self.update_delayed_event_or_restore(previous, UpdateAction::Cancel).await?;
+
+        self.room.inner.notifier.notify_one();
+
+        Ok(true)
+    }
+
+    /// Cancels the scheduled event, so it's never sent.
+    ///
+    /// Returns true if the event has been cancelled, false if it's too late:
+    /// the event has been sent already, or is being handed over to the
+    /// homeserver at the moment.
+    pub async fn cancel(&self) -> Result<bool, RoomSendQueueError> {
+        trace!(txn_id = %self.transaction_id, "cancelling scheduled event");
+
+        let Some(removed) =
+            self.room.inner.queue.remove_scheduled_event(&self.transaction_id).await?
+        else {
+            return Ok(false);
+        };
+
+        self.update_delayed_event_or_restore(removed, UpdateAction::Cancel).await?;
+
+        Ok(true)
+    }
+
+    /// Sends the scheduled event right away, without waiting for its time.
+    ///
+    /// Returns true if the event is being sent, false if it's too late: the
+    /// event has been sent already, or is being handed over to the homeserver
+    /// at the moment.
+    pub async fn send_now(&self) -> Result<bool, RoomSendQueueError> {
+        trace!(txn_id = %self.transaction_id, "sending scheduled event now");
+
+        let queue = &self.room.inner.queue;
+
+        let Some(QueuedRequestKind::ScheduledEvent { content, delay_id, .. }) =
+            queue.scheduled_event(&self.transaction_id).await?
+        else {
+            return Ok(false);
+        };
+
+        if delay_id.is_some() {
+            // The homeserver sends it, forget about it.
+            let Some(removed) = queue.remove_scheduled_event(&self.transaction_id).await? else {
+                return Ok(false);
+            };
+
+            self.update_delayed_event_or_restore(removed, UpdateAction::Send).await?;
+
+            return Ok(true);
+        }
+
+        // Turn it into a regular event, that's sent like any other.
+        if !queue.replace_scheduled_event(&self.transaction_id, content.clone().into()).await? {
+            return Ok(false);
+        }
+
+        self.room.inner.notifier.notify_one();
+
+        let _ = self.room.inner.updates.send(RoomSendQueueUpdate::NewLocalEvent(LocalEcho {
+            transaction_id: self.transaction_id.clone(),
+            content: LocalEchoContent::Event {
+                serialized_event: content,
+                send_handle: SendHandle {
+                    room: self.room.clone(),
+                    transaction_id: self.transaction_id.clone(),
+                    media_handles: Vec::new(),
+                    created_at: MilliSecondsSinceUnixEpoch::now(),
+                },
+                send_error: None,
+            },
+        }));
+
+        Ok(true)
+    }
+
+    /// Apply the given action to the delayed event that the given request of
+    /// the scheduled event had been handed over as, if any.
+    ///
+    /// The request must have been updated or removed locally already. It's
+    /// restored if the action fails, since the delayed event is still held
+    /// by the homeserver then.
+    async fn update_delayed_event_or_restore(
+        &self,
+        previous: QueuedRequest,
+        action: UpdateAction,
+    ) -> Result<(), RoomSendQueueError> {
+        let QueuedRequestKind::ScheduledEvent { delay_id: Some(delay_id), .. } = &previous.kind
+        else {
+            return Ok(());
+        };
+
+        if let Err(err) = self.room.update_delayed_event(delay_id.clone(), action).await {
+            warn!(txn_id = %self.transaction_id, "couldn't update the delayed event, restoring the scheduled event: {err}");
+            self.room.inner.queue.restore_scheduled_event(previous).await?;
+            return Err(err);
+        }
+
+        Ok(())
+    }
+
+    /// Get the underlying transaction id for the scheduled event.
+    pub fn transaction_id(&self) -> &TransactionId {
+        &self.transaction_id
+    }
+}
+
+/// Wait until the room whose info is observed is encrypted, or forever if it
+/// can't be observed anymore.
+///
+/// The info stops being observed once the room is encrypted.
+async fn wait_for_encryption(room_info: &mut Option<Subscriber<RoomInfo>>) {
+    let is_encrypted = match room_info {
+        Some(subscriber) => loop {
+            match subscriber.next().await {
+                Some(info) if info.is_encrypted() => break true,
+                Some(_) => {}
+                None => break false,
+            }
+        },
+        None => false,
+    };
+
+    *room_info = None;
+
+    if !is_encrypted {
+        std::future::pending::<()>().await;
+    }
+}
+
+/// The duration until the given time, or zero if it's in the past.
+fn duration_until(time: MilliSecondsSinceUnixEpoch) -> Duration {
+    let now = MilliSecondsSinceUnixEpoch::now();
+    Duration::from_millis(time.0.saturating_sub(now.0).into())
+}
+
 /// From a given source of [`DependentQueuedRequest`], return only the most
 /// meaningful, i.e. the ones that wouldn't be overridden after applying the
 /// others.
@@ -2202,7 +3782,9 @@ fn canonicalize_dependent_requests(
             }
 
             DependentQueuedRequestKind::UploadFileWithThumbnail { .. }
+            | DependentQueuedRequestKind::UploadFileOrThumbnail { .. }
             | DependentQueuedRequestKind::FinishUpload { .. }
+            | DependentQueuedRequestKind::FinishGallery { .. }
             | DependentQueuedRequestKind::ReactEvent { .. } => {
                 // These requests can't be canonicalized, push them as is.
                 prevs.push(d);
@@ -14,61 +14,83 @@
 
 //! Private implementations of the media upload mechanism.
 
+use std::iter;
+
+use eyeball::SharedObservable;
+use http::StatusCode;
+#[cfg(feature = "e2e-encryption")]
+use matrix_sdk_base::{crypto::AttachmentChunkEncryptor, store::ResumableMediaUploadKey};
 use matrix_sdk_base::{
-    event_cache::store::media::IgnoreMediaRetentionPolicy,
+    event_cache::store::{
+        media::{IgnoreMediaRetentionPolicy, MediaOrigin},
+        EventCacheStoreLockGuard,
+    },
     media::{MediaFormat, MediaRequestParameters},
     store::{
-        ChildTransactionId, DependentQueuedRequestKind, FinishUploadThumbnailInfo,
-        QueuedRequestKind, SentMediaInfo, SentRequestKey, SerializableEventContent,
+        AccumulatedSentMediaInfo, ChildTransactionId, DependentQueuedRequestKind,
+        FinishGalleryItemInfo, FinishUploadThumbnailInfo, QueueWedgeError, QueuedRequestKind,
+        ResumableMediaUpload, SentRequestKey, SerializableEventContent,
     },
     RoomState,
 };
 use mime::Mime;
+#[cfg(feature = "e2e-encryption")]
+use ruma::events::room::EncryptedFileInit;
 use ruma::{
     events::{
-        room::message::{FormattedBody, MessageType, RoomMessageEventContent},
+        room::{
+            message::{FormattedBody, MessageType, RoomMessageEventContent},
+            MediaSource, ThumbnailInfo,
+        },
         AnyMessageLikeEventContent, Mentions,
     },
     MilliSecondsSinceUnixEpoch, OwnedTransactionId, TransactionId,
 };
 use tracing::{debug, error, instrument, trace, warn, Span};
 
-use super::{QueueStorage, RoomSendQueue, RoomSendQueueError};
+use super::{
+    GalleryItemQueueInfo, QueueStorage, QueueThumbnailInfo, RoomSendQueue, RoomSendQueueError,
+};
 use crate::{
-    attachment::AttachmentConfig,
+    attachment::{gallery_items, make_gallery_type, AttachmentConfig, GalleryItem, Thumbnail},
+    config::RequestConfig,
     room::edit::update_media_caption,
     send_queue::{
         LocalEcho, LocalEchoContent, MediaHandles, RoomSendQueueStorageError, RoomSendQueueUpdate,
         SendHandle,
     },
-    Client, Media, Room,
+    Client, Media, Room, TransmissionProgress,
 };
 
 /// Replace the source by the final ones in all the media types handled by
 /// [`Room::make_attachment_type()`].
-fn update_media_event_after_upload(echo: &mut RoomMessageEventContent, sent: SentMediaInfo) {
+fn update_media_type_after_upload(
+    msgtype: &mut MessageType,
+    file: MediaSource,
+    thumbnail: Option<MediaSource>,
+) {
     // Some variants look really similar below, but the `event` and `info` are all
     // different types…
-    match &mut echo.msgtype {
+    match msgtype {
         MessageType::Audio(event) => {
-            event.source = sent.file;
+            event.source = file;
         }
         MessageType::File(event) => {
-            event.source = sent.file;
+            event.source = file;
             if let Some(info) = event.info.as_mut() {
-                info.thumbnail_source = sent.thumbnail;
+                info.thumbnail_source = thumbnail;
             }
         }
         MessageType::Image(event) => {
-            event.source = sent.file;
+            event.source = file;
             if let Some(info) = event.info.as_mut() {
-                info.thumbnail_source = sent.thumbnail;
+                info.thumbnail_source = thumbnail;
             }
         }
         MessageType::Video(event) => {
-            event.source = sent.file;
+            event.source = file;
             if let Some(info) = event.info.as_mut() {
-                info.thumbnail_source = sent.thumbnail;
+                info.thumbnail_source = thumbnail;
             }
         }
 
@@ -76,13 +98,179 @@ fn update_media_event_after_upload(echo: &mut RoomMessageEventContent, sent: Sen
             // All `MessageType` created by `Room::make_attachment_type` should be
             // handled here. The only way to end up here is that a message type has
             // been tampered with in the database.
-            error!("Invalid message type in database: {}", echo.msgtype());
+            error!("Invalid message type in database: {}", msgtype.msgtype());
             // Only crash debug builds.
             debug_assert!(false, "invalid message type in database");
         }
     }
 }
 
+/// Replace the sources by the final ones in all the items of a gallery event
+/// created by [`RoomSendQueue::send_gallery()`].
+fn update_gallery_event_after_upload(
+    echo: &mut RoomMessageEventContent,
+    sent: Vec<AccumulatedSentMediaInfo>,
+) {
+    let Some(mut items) = gallery_items(&echo.msgtype) else {
+        // The only way to end up here is that the gallery has been tampered with in the
+        // database.
+        error!("Invalid gallery in database: {}", echo.msgtype());
+        // Only crash debug builds.
+        debug_assert!(false, "invalid gallery in database");
+        return;
+    };
+
+    if items.len() != sent.len() {
+        warn!(num_items = items.len(), num_sent = sent.len(), "unexpected number of uploads");
+    }
+
+    for (item, sent) in items.iter_mut().zip(sent) {
+        update_media_type_after_upload(item, sent.file, sent.thumbnail);
+    }
+
+    let mut data = echo.msgtype.data().into_owned();
+    data.remove("itemtypes");
+
+    match make_gallery_type(echo.msgtype.body().to_owned(), data, &items) {
+        Ok(msgtype) => echo.msgtype = msgtype,
+        Err(err) => error!("couldn't update the gallery after its upload: {err}"),
+    }
+}
+
+/// Store the thumbnail of a media in the cache store, until it's uploaded.
+///
+/// Returns the transaction id used to upload the thumbnail, the information
+/// needed to fill the thumbnail section of the media event, and the
+/// information needed to queue its upload.
+async fn cache_thumbnail(
+    cache_store: &EventCacheStoreLockGuard<'_>,
+    thumbnail: Option<Thumbnail>,
+    origin: &MediaOrigin,
+) -> Result<
+    (
+        Option<OwnedTransactionId>,
+        Option<(MediaSource, Box<ThumbnailInfo>)>,
+        Option<QueueThumbnailInfo>,
+    ),
+    RoomSendQueueStorageError,
+> {
+    let Some(thumbnail) = thumbnail else {
+        return Ok(Default::default());
+    };
+
+    let txn = TransactionId::new();
+    trace!(upload_thumbnail_txn = %txn, "attachment has a thumbnail");
+
+    // Create the information required for filling the thumbnail section of the
+    // media event.
+    let (data, content_type, thumbnail_info) = thumbnail.into_parts();
+
+    // Cache thumbnail in the cache store.
+    let thumbnail_media_request = Media::make_local_file_media_request(&txn);
+    cache_store
+        .add_media_content_with_origin(
+            &thumbnail_media_request,
+            data,
+            &origin.clone().with_content_type(content_type.to_string()),
+            // Make sure that the thumbnail is stored until it has been uploaded.
+            IgnoreMediaRetentionPolicy::Yes,
+        )
+        .await
+        .map_err(RoomSendQueueStorageError::EventCacheStoreError)?;
+
+    Ok((
+        Some(txn.clone()),
+        Some((thumbnail_media_request.source.clone(), thumbnail_info)),
+        Some((
+            FinishUploadThumbnailInfo { txn, width: None, height: None },
+            thumbnail_media_request,
+            content_type,
+        )),
+    ))
+}
+
+/// Process the image of a gallery item, if it was requested.
+#[cfg(feature = "image-processing")]
+async fn process_gallery_item(mut item: GalleryItem) -> GalleryItem {
+    if let Some(settings) = item.image_processing.take() {
+        (item.content_type, item.data) = crate::image_processing::process_attachment(
+            item.content_type,
+            item.data,
+            settings,
+            &mut item.info,
+            &mut item.thumbnail,
+        )
+        .await;
+    }
+
+    item
+}
+
+/// Update the keys of a media, and its thumbnail, in the cache store after
+/// they've been uploaded, from their local MXC URI to their final one.
+async fn update_media_cache_keys_after_upload(
+    client: &Client,
+    file_upload_txn: &TransactionId,
+    thumbnail_info: Option<&FinishUploadThumbnailInfo>,
+    file: &MediaSource,
+    thumbnail: Option<MediaSource>,
+) -> Result<(), RoomSendQueueError> {
+    // Do it for the file itself.
+    let from_req = Media::make_local_file_media_request(file_upload_txn);
+
+    trace!(from = ?from_req.source, to = ?file, "renaming media file key in cache store");
+    let cache_store =
+        client.event_cache_store().lock().await.map_err(RoomSendQueueStorageError::LockError)?;
+
+    // The media can now be removed during cleanups.
+    cache_store
+        .set_ignore_media_retention_policy(&from_req, IgnoreMediaRetentionPolicy::No)
+        .await
+        .map_err(RoomSendQueueStorageError::EventCacheStoreError)?;
+
+    cache_store
+        .replace_media_key(
+            &from_req,
+            &MediaRequestParameters { source: file.clone(), format: MediaFormat::File },
+        )
+        .await
+        .map_err(RoomSendQueueStorageError::EventCacheStoreError)?;
+
+    // Rename the thumbnail too, if needs be.
+    if let Some((info, new_source)) = thumbnail_info.zip(thumbnail) {
+        // Previously the media request used `MediaFormat::Thumbnail`. Handle this case
+        // for send queue requests that were in the state store before the change.
+        let from_req = if let Some((height, width)) = info.height.zip(info.width) {
+            Media::make_local_thumbnail_media_request(&info.txn, height, width)
+        } else {
+            Media::make_local_file_media_request(&info.txn)
+        };
+
+        trace!(from = ?from_req.source, to = ?new_source, "renaming thumbnail file key in cache store");
+
+        // The media can now be removed during cleanups.
+        cache_store
+            .set_ignore_media_retention_policy(&from_req, IgnoreMediaRetentionPolicy::No)
+            .await
+            .map_err(RoomSendQueueStorageError::EventCacheStoreError)?;
+
+        cache_store
+            .replace_media_key(
+                &from_req,
+                &MediaRequestParameters { source: new_source, format: MediaFormat::File },
+            )
+            .await
+            .map_err(RoomSendQueueStorageError::EventCacheStoreError)?;
+    }
+
+    Ok(())
+}
+
+/// The size of the chunks of a resumable media upload.
+///
+/// Medias that fit in a single chunk are always uploaded in one request.
+const RESUMABLE_UPLOAD_CHUNK_SIZE: usize = 4 * 1024 * 1024;
+
 impl RoomSendQueue {
     /// Queues an attachment to be sent to the room, using the send queue.
     ///
@@ -119,6 +307,21 @@ impl RoomSendQueue {
             return Err(RoomSendQueueError::RoomNotJoined);
         }
 
+        #[cfg(feature = "image-processing")]
+        let (content_type, data) = match config.image_processing.take() {
+            Some(settings) => {
+                crate::image_processing::process_attachment(
+                    content_type,
+                    data,
+                    settings,
+                    &mut config.info,
+                    &mut config.thumbnail,
+                )
+                .await
+            }
+            None => (content_type, data),
+        };
+
         let filename = filename.into();
         let upload_file_txn = TransactionId::new();
         let send_event_txn = config.txn_id.map_or_else(ChildTransactionId::new, Into::into);
@@ -137,10 +340,12 @@ impl RoomSendQueue {
                 .map_err(RoomSendQueueStorageError::LockError)?;
 
             // Cache the file itself in the cache store.
+            let origin = MediaOrigin::room(room.room_id().to_owned());
             cache_store
-                .add_media_content(
+                .add_media_content_with_origin(
                     &file_media_request,
                     data.clone(),
+                    &origin.clone().with_content_type(content_type.to_string()),
                     // Make sure that the file is stored until it has been uploaded.
                     IgnoreMediaRetentionPolicy::Yes,
                 )
@@ -148,38 +353,7 @@ impl RoomSendQueue {
                 .map_err(RoomSendQueueStorageError::EventCacheStoreError)?;
 
             // Process the thumbnail, if it's been provided.
-            if let Some(thumbnail) = config.thumbnail.take() {
-                let txn = TransactionId::new();
-                trace!(upload_thumbnail_txn = %txn, "attachment has a thumbnail");
-
-                // Create the information required for filling the thumbnail section of the
-                // media event.
-                let (data, content_type, thumbnail_info) = thumbnail.into_parts();
-
-                // Cache thumbnail in the cache store.
-                let thumbnail_media_request = Media::make_local_file_media_request(&txn);
-                cache_store
-                    .add_media_content(
-                        &thumbnail_media_request,
-                        data,
-                        // Make sure that the thumbnail is stored until it has been uploaded.
-                        IgnoreMediaRetentionPolicy::Yes,
-                    )
-                    .await
-                    .map_err(RoomSendQueueStorageError::EventCacheStoreError)?;
-
-                (
-                    Some(txn.clone()),
-                    Some((thumbnail_media_request.source.clone(), thumbnail_info)),
-                    Some((
-                        FinishUploadThumbnailInfo { txn, width: None, height: None },
-                        thumbnail_media_request,
-                        content_type,
-                    )),
-                )
-            } else {
-                Default::default()
-            }
+            cache_thumbnail(&cache_store, config.thumbnail.take(), &origin).await?
         };
 
         // Create the content for the media event.
@@ -219,7 +393,7 @@ impl RoomSendQueue {
         let send_handle = SendHandle {
             room: self.clone(),
             transaction_id: send_event_txn.clone().into(),
-            media_handles: Some(MediaHandles { upload_thumbnail_txn, upload_file_txn }),
+            media_handles: vec![MediaHandles { upload_thumbnail_txn, upload_file_txn }],
             created_at,
         };
 
@@ -235,9 +409,398 @@ impl RoomSendQueue {
 
         Ok(send_handle)
     }
+
+    /// Queues a gallery of medias to be sent to the room, using the send
+    /// queue, as a single event with the `msgtype` defined in [MSC4274].
+    ///
+    /// This returns quickly (without sending or uploading anything), and will
+    /// push the event to be sent into a queue, handled in the background.
+    ///
+    /// The medias, and their optional thumbnails, are uploaded in order, then
+    /// the gallery event is sent once all the uploads are done. Its local echo
+    /// contains all the items, using local MXC URIs until the uploads are
+    /// done. The items of the gallery can be retrieved with
+    /// [`gallery_items()`](crate::attachment::gallery_items).
+    ///
+    /// Callers are expected to consume [`RoomSendQueueUpdate`] via calling
+    /// the [`Self::subscribe()`] method to get updates about the sending of
+    /// that event.
+    ///
+    /// As with [`Self::send_attachment()`], the medias and their thumbnails are
+    /// stored in the media cache and can be retrieved at any time.
+    ///
+    /// [MSC4274]: https://github.com/matrix-org/matrix-spec-proposals/pull/4274
+    #[instrument(skip_all, fields(event_txn))]
+    pub async fn send_gallery(
+        &self,
+        items: Vec<GalleryItem>,
+        caption: Option<String>,
+    ) -> Result<SendHandle, RoomSendQueueError> {
+        let Some(room) = self.inner.room.get() else {
+            return Err(RoomSendQueueError::RoomDisappeared);
+        };
+
+        if room.state() != RoomState::Joined {
+            return Err(RoomSendQueueError::RoomNotJoined);
+        }
+
+        if items.is_empty() {
+            return Err(RoomSendQueueError::EmptyGallery);
+        }
+
+        let send_event_txn = ChildTransactionId::new();
+
+        Span::current().record("event_txn", tracing::field::display(&*send_event_txn));
+        debug!(num_items = items.len(), "sending a gallery");
+
+        // Process the items before locking the cache store, since it can take a
+        // while.
+        #[cfg(feature = "image-processing")]
+        let items = {
+            let mut processed_items = Vec::with_capacity(items.len());
+            for item in items {
+                processed_items.push(process_gallery_item(item).await);
+            }
+            processed_items
+        };
+
+        let mut item_types = Vec::with_capacity(items.len());
+        let mut item_queue_infos = Vec::with_capacity(items.len());
+        let mut media_handles = Vec::with_capacity(items.len());
+
+        {
+            let client = room.client();
+            let cache_store = client
+                .event_cache_store()
+                .lock()
+                .await
+                .map_err(RoomSendQueueStorageError::LockError)?;
+            let origin = MediaOrigin::room(room.room_id().to_owned());
+
+            for item in items {
+                let upload_file_txn = TransactionId::new();
+                trace!(filename = item.filename.as_str(), content_type = %item.content_type, %upload_file_txn, "adding an item to the gallery");
+
+                let file_media_request = Media::make_local_file_media_request(&upload_file_txn);
+
+                // Cache the file itself in the cache store.
+                cache_store
+                    .add_media_content_with_origin(
+                        &file_media_request,
+                        item.data,
+                        &origin.clone().with_content_type(item.content_type.to_string()),
+                        // Make sure that the file is stored until it has been uploaded.
+                        IgnoreMediaRetentionPolicy::Yes,
+                    )
+                    .await
+                    .map_err(RoomSendQueueStorageError::EventCacheStoreError)?;
+
+                // Process the thumbnail, if it's been provided.
+                let (upload_thumbnail_txn, event_thumbnail_info, queue_thumbnail_info) =
+                    cache_thumbnail(&cache_store, item.thumbnail, &origin).await?;
+
+                item_types.push(room.make_attachment_type(
+                    &item.content_type,
+                    item.filename,
+                    file_media_request.source.clone(),
+                    item.caption,
+                    item.formatted_caption,
+                    item.info,
+                    event_thumbnail_info,
+                ));
+
+                item_queue_infos.push(GalleryItemQueueInfo {
+                    content_type: item.content_type,
+                    upload_file_txn: upload_file_txn.clone(),
+                    file_media_request,
+                    thumbnail: queue_thumbnail_info,
+                });
+
+                media_handles.push(MediaHandles { upload_thumbnail_txn, upload_file_txn });
+            }
+        }
+
+        // Create the content for the gallery event.
+        let msgtype =
+            make_gallery_type(caption.unwrap_or_default(), Default::default(), &item_types)
+                .map_err(RoomSendQueueStorageError::JsonSerialization)?;
+        let event_content = RoomMessageEventContent::new(msgtype);
+
+        let created_at = MilliSecondsSinceUnixEpoch::now();
+
+        // Save requests in the queue storage.
+        self.inner
+            .queue
+            .push_gallery(
+                event_content.clone(),
+                send_event_txn.clone().into(),
+                created_at,
+                item_queue_infos,
+            )
+            .await?;
+
+        trace!("manager sends a gallery to the background task");
+
+        self.inner.notifier.notify_one();
+
+        let send_handle = SendHandle {
+            room: self.clone(),
+            transaction_id: send_event_txn.clone().into(),
+            media_handles,
+            created_at,
+        };
+
+        let _ = self.inner.updates.send(RoomSendQueueUpdate::NewLocalEvent(LocalEcho {
+            transaction_id: send_event_txn.clone().into(),
+            content: LocalEchoContent::Event {
+                serialized_event: SerializableEventContent::new(&event_content.into())
+                    .map_err(RoomSendQueueStorageError::JsonSerialization)?,
+                send_handle: send_handle.clone(),
+                send_error: None,
+            },
+        }));
+
+        Ok(send_handle)
+    }
+
+    /// Whether a media of the given size should be uploaded in chunks, with
+    /// [`Self::upload_media_resumably()`], rather than in a single request.
+    pub(super) async fn should_upload_resumably(room: &Room, size: usize) -> bool {
+        if size <= RESUMABLE_UPLOAD_CHUNK_SIZE {
+            return false;
+        }
+
+        match room.client().media().supports_resumable_uploads().await {
+            Ok(supported) => supported,
+            Err(err) => {
+                warn!("couldn't check whether resumable uploads are supported: {err}");
+                false
+            }
+        }
+    }
+
+    /// Upload a media in chunks to a preallocated MXC URI.
+    ///
+    /// The progress of the upload is saved in its request after each chunk,
+    /// so that an interrupted upload is resumed where it stopped, from the
+    /// given `resumable` progress, instead of starting over.
+    ///
+    /// In encrypted rooms, only the key of the media is saved: the chunks
+    /// that have already been uploaded are encrypted again to compute the
+    /// hash of the whole encrypted media, but they're not uploaded again.
+    #[instrument(skip_all, fields(txn_id = %transaction_id))]
+    pub(super) async fn upload_media_resumably(
+        room: &Room,
+        queue: &QueueStorage,
+        transaction_id: &TransactionId,
+        content_type: &Mime,
+        data: Vec<u8>,
+        resumable: Option<ResumableMediaUpload>,
+        send_progress: SharedObservable<TransmissionProgress>,
+    ) -> Result<MediaSource, crate::Error> {
+        let media = room.client().media();
+
+        let resumed = match resumable {
+            Some(mut upload) => match media.resumable_upload_offset(&upload.uri).await {
+                Ok(offset) => {
+                    trace!(mxc_uri = %upload.uri, offset, "resuming upload");
+                    upload.offset = offset;
+                    Some(upload)
+                }
+
+                Err(err)
+                    if err
+                        .as_client_api_error()
+                        .is_some_and(|err| err.status_code == StatusCode::NOT_FOUND) =>
+                {
+                    // The preallocated MXC URI has expired, or the homeserver has forgotten it.
+                    warn!(
+                        mxc_uri = %upload.uri,
+                        "previous upload is unknown to the homeserver, starting over"
+                    );
+                    None
+                }
+
+                Err(err) => return Err(err),
+            },
+
+            None => None,
+        };
+
+        let mut upload = match resumed {
+            Some(upload) => upload,
+            None => {
+                let upload = Self::start_resumable_upload(room).await?;
+                queue.save_resumable_upload(transaction_id, upload.clone()).await;
+                upload
+            }
+        };
+
+        #[cfg(feature = "e2e-encryption")]
+        let mut encryptor = upload
+            .encryption_key
+            .clone()
+            .map(|key| AttachmentChunkEncryptor::with_key(key.key, key.iv))
+            .transpose()?;
+
+        let total = data.len() as u64;
+        let mut chunk_start = 0;
+
+        while chunk_start < total {
+            let chunk_end = (chunk_start + RESUMABLE_UPLOAD_CHUNK_SIZE as u64).min(total);
+
+            #[cfg(feature = "e2e-encryption")]
+            let is_encrypted = encryptor.is_some();
+            #[cfg(not(feature = "e2e-encryption"))]
+            let is_encrypted = false;
+
+            // The chunks of an encrypted media are encrypted in order, even the ones that
+            // have already been uploaded, so skipping is only possible in clear
+            // text.
+            if upload.offset >= chunk_end && !is_encrypted {
+                chunk_start = chunk_end;
+                continue;
+            }
+
+            #[allow(unused_mut)]
+            let mut chunk = data[chunk_start as usize..chunk_end as usize].to_vec();
+
+            #[cfg(feature = "e2e-encryption")]
+            if let Some(encryptor) = &mut encryptor {
+                encryptor.encrypt_chunk(&mut chunk);
+            }
+
+            // The homeserver might only have received a part of the chunk.
+            while upload.offset < chunk_end {
+                let skipped = upload.offset.saturating_sub(chunk_start) as usize;
+                let request_config = RequestConfig::short_retry()
+                    .timeout(Media::reasonable_upload_timeout(&chunk[skipped..]));
+
+                let offset = media
+                    .upload_preallocated_chunk(
+                        &upload.uri,
+                        content_type,
+                        upload.offset,
+                        total,
+                        chunk[skipped..].to_vec(),
+                        Some(request_config),
+                    )
+                    .await?;
+
+                if offset <= upload.offset {
+                    return Err(crate::Error::SendQueueWedgeError(
+                        QueueWedgeError::GenericApiError {
+                            msg: format!(
+                                "the homeserver didn't receive the chunk at offset {}",
+                                upload.offset
+                            ),
+                        },
+                    ));
+                }
+
+                upload.offset = offset;
+                send_progress.set(TransmissionProgress {
+                    current: offset.min(total) as usize,
+                    total: total as usize,
+                });
+                queue.save_resumable_upload(transaction_id, upload.clone()).await;
+            }
+
+            chunk_start = chunk_end;
+        }
+
+        trace!(mxc_uri = %upload.uri, "resumable upload complete");
+
+        #[cfg(feature = "e2e-encryption")]
+        if let Some(encryptor) = encryptor {
+            let keys = encryptor.finish();
+            let encrypted_file = EncryptedFileInit {
+                url: upload.uri,
+                key: keys.key,
+                iv: keys.iv,
+                hashes: keys.hashes,
+                v: keys.version,
+            }
+            .into();
+
+            return Ok(MediaSource::Encrypted(Box::new(encrypted_file)));
+        }
+
+        Ok(MediaSource::Plain(upload.uri))
+    }
+
+    /// Preallocate an MXC URI for a resumable upload, and generate the key to
+    /// encrypt the media with if the room is encrypted.
+    async fn start_resumable_upload(room: &Room) -> Result<ResumableMediaUpload, crate::Error> {
+        let uri = room.client().media().create_content_uri().await?.uri;
+
+        trace!(mxc_uri = %uri, "starting resumable upload");
+
+        #[cfg(feature = "e2e-encryption")]
+        if room.is_encrypted().await? {
+            trace!("upload will be encrypted (encrypted room)");
+
+            let encryptor = AttachmentChunkEncryptor::new();
+            let encryption_key = ResumableMediaUploadKey {
+                key: encryptor.web_key().clone(),
+                iv: encryptor.iv().clone(),
+            };
+
+            return Ok(ResumableMediaUpload {
+                uri,
+                offset: 0,
+                encryption_key: Some(encryption_key),
+            });
+        }
+
+        Ok(ResumableMediaUpload { uri, offset: 0, encryption_key: None })
+    }
 }
 
 impl QueueStorage {
+    /// Save the progress of a resumable upload in its request, so the upload
+    /// can be resumed from there if it's interrupted.
+    ///
+    /// Failing to save it isn't fatal: the upload would start over if it's
+    /// interrupted, but it can go on.
+    async fn save_resumable_upload(
+        &self,
+        transaction_id: &TransactionId,
+        upload: ResumableMediaUpload,
+    ) {
+        if let Err(err) = self.try_save_resumable_upload(transaction_id, upload).await {
+            warn!("couldn't save the progress of the upload: {err}");
+        }
+    }
+
+    async fn try_save_resumable_upload(
+        &self,
+        transaction_id: &TransactionId,
+        upload: ResumableMediaUpload,
+    ) -> Result<(), RoomSendQueueStorageError> {
+        let guard = self.store.lock().await;
+        let client = guard.client()?;
+        let store = client.store();
+
+        let Some(mut kind) = store
+            .load_send_queue_requests(&self.room_id)
+            .await?
+            .into_iter()
+            .find(|request| request.transaction_id == transaction_id)
+            .map(|request| request.kind)
+        else {
+            // The upload has been aborted in the meantime.
+            return Ok(());
+        };
+
+        if let QueuedRequestKind::MediaUpload { resumable, .. } = &mut kind {
+            *resumable = Some(upload);
+            store.update_send_queue_request(&self.room_id, transaction_id, kind).await?;
+        }
+
+        Ok(())
+    }
+
     /// Consumes a finished upload and queues sending of the final media event.
     #[allow(clippy::too_many_arguments)]
     pub(super) async fn handle_dependent_finish_upload(
@@ -256,66 +819,76 @@ impl QueueStorage {
             .ok_or(RoomSendQueueError::StorageError(RoomSendQueueStorageError::InvalidParentKey))?;
 
         // Update cache keys in the cache store.
-        {
-            // Do it for the file itself.
-            let from_req = Media::make_local_file_media_request(&file_upload_txn);
-
-            trace!(from = ?from_req.source, to = ?sent_media.file, "renaming media file key in cache store");
-            let cache_store = client
-                .event_cache_store()
-                .lock()
-                .await
-                .map_err(RoomSendQueueStorageError::LockError)?;
-
-            // The media can now be removed during cleanups.
-            cache_store
-                .set_ignore_media_retention_policy(&from_req, IgnoreMediaRetentionPolicy::No)
-                .await
-                .map_err(RoomSendQueueStorageError::EventCacheStoreError)?;
-
-            cache_store
-                .replace_media_key(
-                    &from_req,
-                    &MediaRequestParameters {
-                        source: sent_media.file.clone(),
-                        format: MediaFormat::File,
-                    },
-                )
-                .await
-                .map_err(RoomSendQueueStorageError::EventCacheStoreError)?;
+        update_media_cache_keys_after_upload(
+            client,
+            &file_upload_txn,
+            thumbnail_info.as_ref(),
+            &sent_media.file,
+            sent_media.thumbnail.clone(),
+        )
+        .await?;
+
+        update_media_type_after_upload(
+            &mut local_echo.msgtype,
+            sent_media.file,
+            sent_media.thumbnail,
+        );
 
-            // Rename the thumbnail too, if needs be.
-            if let Some((info, new_source)) =
-                thumbnail_info.as_ref().zip(sent_media.thumbnail.clone())
-            {
-                // Previously the media request used `MediaFormat::Thumbnail`. Handle this case
-                // for send queue requests that were in the state store before the change.
-                let from_req = if let Some((height, width)) = info.height.zip(info.width) {
-                    Media::make_local_thumbnail_media_request(&info.txn, height, width)
-                } else {
-                    Media::make_local_file_media_request(&info.txn)
-                };
+        self.queue_finished_media_event(client, event_txn, local_echo, new_updates).await
+    }
 
-                trace!(from = ?from_req.source, to = ?new_source, "renaming thumbnail file key in cache store");
+    /// Consumes the last finished upload of a gallery and queues sending of
+    /// the final gallery event.
+    pub(super) async fn handle_dependent_finish_gallery(
+        &self,
+        client: &Client,
+        event_txn: OwnedTransactionId,
+        parent_key: SentRequestKey,
+        mut local_echo: RoomMessageEventContent,
+        item_infos: Vec<FinishGalleryItemInfo>,
+        new_updates: &mut Vec<RoomSendQueueUpdate>,
+    ) -> Result<(), RoomSendQueueError> {
+        // All the uploads are ready: enqueue the event with its final data.
+        let sent_media = parent_key
+            .into_media()
+            .ok_or(RoomSendQueueError::StorageError(RoomSendQueueStorageError::InvalidParentKey))?;
 
-                // The media can now be removed during cleanups.
-                cache_store
-                    .set_ignore_media_retention_policy(&from_req, IgnoreMediaRetentionPolicy::No)
-                    .await
-                    .map_err(RoomSendQueueStorageError::EventCacheStoreError)?;
+        // The last upload accumulated all the previous ones.
+        let sent_medias = sent_media
+            .accumulated
+            .into_iter()
+            .chain(iter::once(AccumulatedSentMediaInfo {
+                file: sent_media.file,
+                thumbnail: sent_media.thumbnail,
+            }))
+            .collect::<Vec<_>>();
 
-                cache_store
-                    .replace_media_key(
-                        &from_req,
-                        &MediaRequestParameters { source: new_source, format: MediaFormat::File },
-                    )
-                    .await
-                    .map_err(RoomSendQueueStorageError::EventCacheStoreError)?;
-            }
+        // Update cache keys in the cache store.
+        for (item_info, sent_media) in item_infos.iter().zip(&sent_medias) {
+            update_media_cache_keys_after_upload(
+                client,
+                &item_info.file_upload,
+                item_info.thumbnail_info.as_ref(),
+                &sent_media.file,
+                sent_media.thumbnail.clone(),
+            )
+            .await?;
         }
 
-        update_media_event_after_upload(&mut local_echo, sent_media);
+        update_gallery_event_after_upload(&mut local_echo, sent_medias);
 
+        self.queue_finished_media_event(client, event_txn, local_echo, new_updates).await
+    }
+
+    /// Queues sending of a media event, now that all its medias have been
+    /// uploaded.
+    async fn queue_finished_media_event(
+        &self,
+        client: &Client,
+        event_txn: OwnedTransactionId,
+        local_echo: RoomMessageEventContent,
+        new_updates: &mut Vec<RoomSendQueueUpdate>,
+    ) -> Result<(), RoomSendQueueError> {
         let new_content = SerializableEventContent::new(&local_echo.into())
             .map_err(RoomSendQueueStorageError::JsonSerialization)?;
 
@@ -374,6 +947,68 @@ impl QueueStorage {
             // The thumbnail for the next upload is the file we just uploaded here.
             thumbnail_source: Some(sent_media.file),
             related_to: event_txn,
+            accumulated: sent_media.accumulated,
+            resumable: None,
+        };
+
+        client
+            .store()
+            .save_send_queue_request(
+                &self.room_id,
+                next_upload_txn,
+                MilliSecondsSinceUnixEpoch::now(),
+                request,
+                Self::HIGH_PRIORITY,
+            )
+            .await
+            .map_err(RoomSendQueueStorageError::StateStoreError)?;
+
+        Ok(())
+    }
+
+    /// Consumes a finished upload of a gallery and queues the next one, which
+    /// is either the file of the same item, if the finished upload was its
+    /// thumbnail, or the file or thumbnail of the next item.
+    #[allow(clippy::too_many_arguments)]
+    pub(super) async fn handle_dependent_file_or_thumbnail_upload(
+        &self,
+        client: &Client,
+        next_upload_txn: OwnedTransactionId,
+        parent_key: SentRequestKey,
+        content_type: String,
+        cache_key: MediaRequestParameters,
+        event_txn: OwnedTransactionId,
+        parent_is_thumbnail_upload: bool,
+    ) -> Result<(), RoomSendQueueError> {
+        let sent_media = parent_key
+            .into_media()
+            .ok_or(RoomSendQueueError::StorageError(RoomSendQueueStorageError::InvalidParentKey))?;
+
+        let (thumbnail_source, accumulated) = if parent_is_thumbnail_upload {
+            trace!(related_to = %event_txn, "done uploading thumbnail, now queuing a request to send the media file itself");
+
+            // The thumbnail for the next upload is the file we just uploaded here.
+            (Some(sent_media.file), sent_media.accumulated)
+        } else {
+            trace!(related_to = %event_txn, "done uploading file, now queuing a request to send the next media");
+
+            // The media we just uploaded is done, remember it for the final event.
+            let mut accumulated = sent_media.accumulated;
+            accumulated.push(AccumulatedSentMediaInfo {
+                file: sent_media.file,
+                thumbnail: sent_media.thumbnail,
+            });
+
+            (None, accumulated)
+        };
+
+        let request = QueuedRequestKind::MediaUpload {
+            content_type,
+            cache_key,
+            thumbnail_source,
+            related_to: event_txn,
+            accumulated,
+            resumable: None,
         };
 
         client
@@ -391,9 +1026,9 @@ impl QueueStorage {
         Ok(())
     }
 
-    /// Try to abort an upload that would be ongoing.
+    /// Try to abort the uploads of a media event that would be ongoing.
     ///
-    /// Return true if any media (media itself or its thumbnail) was being
+    /// Return true if any media (a media itself or its thumbnail) was being
     /// uploaded. In this case, the media event has also been removed from
     /// the send queue. If it returns false, then the uploads already
     /// happened, and the event sending *may* have started.
@@ -401,7 +1036,7 @@ impl QueueStorage {
     pub(super) async fn abort_upload(
         &self,
         event_txn: &TransactionId,
-        handles: &MediaHandles,
+        handles: &[MediaHandles],
     ) -> Result<bool, RoomSendQueueStorageError> {
         let mut guard = self.store.lock().await;
         let client = guard.client()?;
@@ -411,93 +1046,59 @@ impl QueueStorage {
 
         let store = client.store();
 
-        let upload_file_as_dependent = ChildTransactionId::from(handles.upload_file_txn.clone());
-        let event_as_dependent = ChildTransactionId::from(event_txn.to_owned());
+        // The uploads happen in order, each one being a dependent request of the
+        // previous one: the ones before the first upload found as a request
+        // have terminated already, and the ones after it are dependent
+        // requests.
+        let upload_txns = handles.iter().flat_map(|handles| {
+            handles.upload_thumbnail_txn.iter().chain(iter::once(&handles.upload_file_txn))
+        });
 
-        let mut removed_dependent_upload = false;
-        let mut removed_dependent_event = false;
+        let mut removed_upload = false;
 
-        if let Some(thumbnail_txn) = &handles.upload_thumbnail_txn {
-            if store.remove_send_queue_request(&self.room_id, thumbnail_txn).await? {
-                // The thumbnail upload existed as a request: either it was pending (something
-                // else was being sent), or it was actively being sent.
-                trace!("could remove thumbnail request, removing 2 dependent requests now");
+        for txn in upload_txns {
+            if removed_upload {
+                // Remove the dependent request.
+                if !store
+                    .remove_dependent_queued_request(
+                        &self.room_id,
+                        &ChildTransactionId::from(txn.clone()),
+                    )
+                    .await?
+                {
+                    warn!(%txn, "unable to find the dependent upload request");
+                }
+            } else if store.remove_send_queue_request(&self.room_id, txn).await? {
+                // The upload existed as a request: either it was pending (something else was
+                // being sent), or it was actively being sent.
+                trace!(%txn, "could remove upload request, removing dependent requests now");
 
-                // 1. Try to abort sending using the being_sent info, in case it was active.
+                // Try to abort sending using the being_sent info, in case it was active.
                 if let Some(info) = guard.being_sent.as_ref() {
-                    if info.transaction_id == *thumbnail_txn {
+                    if info.transaction_id == *txn {
                         // SAFETY: we knew it was Some(), two lines above.
                         let info = guard.being_sent.take().unwrap();
                         if info.cancel_upload() {
-                            trace!("aborted ongoing thumbnail upload");
+                            trace!("aborted ongoing upload");
                         }
                     }
                 }
 
-                // 2. Remove the dependent requests.
-                removed_dependent_upload = store
-                    .remove_dependent_queued_request(&self.room_id, &upload_file_as_dependent)
-                    .await?;
-
-                if !removed_dependent_upload {
-                    warn!("unable to find the dependent file upload request");
-                }
-
-                removed_dependent_event = store
-                    .remove_dependent_queued_request(&self.room_id, &event_as_dependent)
-                    .await?;
-
-                if !removed_dependent_event {
-                    warn!("unable to find the dependent media event upload request");
-                }
+                removed_upload = true;
             }
         }
 
-        // If we're here:
-        // - either there was no thumbnail to upload,
-        // - or the thumbnail request has terminated already.
-        //
-        // So the next target is the upload request itself, in both cases.
-
-        if !removed_dependent_upload {
-            if store.remove_send_queue_request(&self.room_id, &handles.upload_file_txn).await? {
-                // The upload existed as a request: either it was pending (something else was
-                // being sent), or it was actively being sent.
-                trace!("could remove file upload request, removing 1 dependent request");
-
-                // 1. Try to abort sending using the being_sent info, in case it was active.
-                if let Some(info) = guard.being_sent.as_ref() {
-                    if info.transaction_id == handles.upload_file_txn {
-                        // SAFETY: we knew it was Some(), two lines above.
-                        let info = guard.being_sent.take().unwrap();
-                        if info.cancel_upload() {
-                            trace!("aborted ongoing file upload");
-                        }
-                    }
-                }
+        let event_as_dependent = ChildTransactionId::from(event_txn.to_owned());
 
-                // 2. Remove the dependent request.
-                if !store
-                    .remove_dependent_queued_request(&self.room_id, &event_as_dependent)
-                    .await?
-                {
-                    warn!("unable to find the dependent media event upload request");
-                }
+        if !store.remove_dependent_queued_request(&self.room_id, &event_as_dependent).await? {
+            if removed_upload {
+                warn!("unable to find the dependent media event upload request");
             } else {
-                // The upload was not in the send queue, so it's completed.
-                //
-                // It means the event sending is either still queued as a dependent request, or
-                // it's graduated into a request.
-                if !removed_dependent_event
-                    && !store
-                        .remove_dependent_queued_request(&self.room_id, &event_as_dependent)
-                        .await?
-                {
-                    // The media event has been promoted into a request, or the promoted request
-                    // has been sent already: we couldn't abort, let the caller decide what to do.
-                    debug!("uploads already happened => deferring to aborting an event sending");
-                    return Ok(false);
-                }
+                // The uploads were not in the send queue, so they're completed, and the media
+                // event has been promoted into a request, or the promoted request has been sent
+                // already: we couldn't abort, let the caller decide what to do.
+                debug!("uploads already happened => deferring to aborting an event sending");
+                return Ok(false);
             }
         }
 
@@ -505,11 +1106,13 @@ impl QueueStorage {
         // Perform the final step: empty the cache from the local items.
         {
             let event_cache = client.event_cache_store().lock().await?;
-            event_cache
-                .remove_media_content_for_uri(&Media::make_local_uri(&handles.upload_file_txn))
-                .await?;
-            if let Some(txn) = &handles.upload_thumbnail_txn {
-                event_cache.remove_media_content_for_uri(&Media::make_local_uri(txn)).await?;
+            for handles in handles {
+                event_cache
+                    .remove_media_content_for_uri(&Media::make_local_uri(&handles.upload_file_txn))
+                    .await?;
+                if let Some(txn) = &handles.upload_thumbnail_txn {
+                    event_cache.remove_media_content_for_uri(&Media::make_local_uri(txn)).await?;
+                }
             }
         }
 
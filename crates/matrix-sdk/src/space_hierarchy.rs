@@ -0,0 +1,881 @@
+// Copyright 2025 The Matrix.org Foundation C.I.C.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Types for browsing the hierarchy of a space, using the `/hierarchy`
+//! endpoint.
+//!
+//! See <https://spec.matrix.org/v1.13/client-server-api/#spaces> for details
+//! about spaces.
+
+use std::{
+    cmp::Ordering,
+    collections::HashMap,
+    sync::{Arc, Mutex as StdMutex},
+};
+
+use eyeball_im::{ObservableVector, VectorDiff};
+use futures_core::Stream;
+use imbl::Vector;
+use matrix_sdk_base::RoomState;
+use ruma::{
+    api::client::space::{get_hierarchy, SpaceHierarchyRoomsChunk},
+    assign,
+    events::{room::member::SyncRoomMemberEvent, space::child::SyncSpaceChildEvent},
+    room::RoomType,
+    serde::Raw,
+    space::SpaceRoomJoinRule,
+    MilliSecondsSinceUnixEpoch, OwnedMxcUri, OwnedRoomAliasId, OwnedRoomId, OwnedServerName,
+    RoomId, UInt,
+};
+use serde::Deserialize;
+use tracing::{debug, warn};
+
+use crate::{
+    event_handler::{EventHandlerDropGuard, RawEvent},
+    room_directory_search::SearchState,
+    Client, Result, Room,
+};
+
+/// Options for a [`SpaceHierarchy`].
+#[derive(Clone, Debug, Default)]
+#[non_exhaustive]
+pub struct SpaceHierarchyOptions {
+    /// The maximum depth of the hierarchy to explore, the space itself being
+    /// at depth 0; defaults to the maximum allowed by the server.
+    pub max_depth: Option<u32>,
+
+    /// Whether to only include the rooms that are marked as suggested by
+    /// their parent.
+    pub suggested_only: bool,
+
+    /// The maximum number of rooms to include per page; defaults to a limit
+    /// chosen by the server.
+    pub limit: Option<u32>,
+}
+
+/// A room of a space hierarchy, along with its position in the tree.
+///
+/// It's produced by [`SpaceHierarchy::rooms`].
+#[derive(Clone, Debug, PartialEq)]
+pub struct SpaceHierarchyRoom {
+    /// The room's ID.
+    pub room_id: OwnedRoomId,
+    /// The canonical alias of the room, if any.
+    pub canonical_alias: Option<OwnedRoomAliasId>,
+    /// The name of the room, if any.
+    pub name: Option<String>,
+    /// The topic of the room, if any.
+    pub topic: Option<String>,
+    /// The room's avatar URL, if any.
+    pub avatar_url: Option<OwnedMxcUri>,
+    /// The number of members that have joined the room.
+    pub num_joined_members: u64,
+    /// The room type (space, custom) or nothing, if it's a regular room.
+    pub room_type: Option<RoomType>,
+    /// The room's join rule.
+    pub join_rule: SpaceRoomJoinRule,
+    /// Whether the room can be previewed by anyone.
+    pub is_world_readable: bool,
+    /// Whether guests can join the room.
+    pub guest_can_join: bool,
+
+    /// The IDs of the children of this room, if it's a space, in the order
+    /// defined by the spec.
+    ///
+    /// Some of them may not be part of the hierarchy, e.g. if they are
+    /// deeper than [`SpaceHierarchyOptions::max_depth`], or if the server
+    /// can't see them.
+    pub children: Vec<OwnedRoomId>,
+    /// The ID of the parent of this room in the hierarchy, `None` for the
+    /// space at the root of the hierarchy.
+    pub parent: Option<OwnedRoomId>,
+    /// The depth of this room in the hierarchy, the root being at depth 0.
+    pub depth: usize,
+    /// Whether this room is marked as suggested by its parent.
+    pub suggested: bool,
+    /// Has the current user been invited/joined/left this room?
+    ///
+    /// Set to `None` if the room is unknown to the user.
+    pub state: Option<RoomState>,
+}
+
+impl SpaceHierarchyRoom {
+    /// Whether this room is a space.
+    pub fn is_space(&self) -> bool {
+        self.room_type == Some(RoomType::Space)
+    }
+
+    fn new(
+        chunk: SpaceHierarchyRoomsChunk,
+        children: &[SpaceChild],
+        parent: Option<&SpaceHierarchyRoom>,
+        suggested: bool,
+        state: Option<RoomState>,
+    ) -> Self {
+        Self {
+            room_id: chunk.room_id,
+            canonical_alias: chunk.canonical_alias,
+            name: chunk.name,
+            topic: chunk.topic,
+            avatar_url: chunk.avatar_url,
+            num_joined_members: chunk.num_joined_members.into(),
+            room_type: chunk.room_type,
+            join_rule: chunk.join_rule,
+            is_world_readable: chunk.world_readable,
+            guest_can_join: chunk.guest_can_join,
+            children: children.iter().map(|child| child.room_id.clone()).collect(),
+            parent: parent.map(|parent| parent.room_id.clone()),
+            depth: parent.map_or(0, |parent| parent.depth + 1),
+            suggested,
+            state,
+        }
+    }
+}
+
+/// A child of a space, as advertised by an `m.space.child` state event.
+#[derive(Clone, Debug)]
+struct SpaceChild {
+    room_id: OwnedRoomId,
+    order: Option<String>,
+    origin_server_ts: MilliSecondsSinceUnixEpoch,
+    suggested: bool,
+}
+
+impl SpaceChild {
+    /// Parse an `m.space.child` state event.
+    ///
+    /// Returns the ID of the child, and the child itself if the event is
+    /// valid, or `None` if the event removes the child, i.e. if it doesn't
+    /// have a `via` field.
+    fn parse<T>(event: &Raw<T>) -> Option<(OwnedRoomId, Option<Self>)> {
+        #[derive(Deserialize)]
+        struct ContentProbe {
+            #[serde(default)]
+            via: Vec<OwnedServerName>,
+            order: Option<String>,
+            #[serde(default)]
+            suggested: bool,
+        }
+
+        #[derive(Deserialize)]
+        struct EventProbe {
+            state_key: OwnedRoomId,
+            origin_server_ts: MilliSecondsSinceUnixEpoch,
+            content: ContentProbe,
+        }
+
+        let event = match event.deserialize_as::<EventProbe>() {
+            Ok(event) => event,
+            Err(error) => {
+                debug!("Ignoring invalid m.space.child event: {error}");
+                return None;
+            }
+        };
+
+        if event.content.via.is_empty() {
+            return Some((event.state_key, None));
+        }
+
+        // An invalid order must be ignored.
+        let order = event
+            .content
+            .order
+            .filter(|order| order.len() <= 50 && order.chars().all(|c| (' '..='~').contains(&c)));
+
+        let child = Self {
+            room_id: event.state_key.clone(),
+            order,
+            origin_server_ts: event.origin_server_ts,
+            suggested: event.content.suggested,
+        };
+
+        Some((event.state_key, Some(child)))
+    }
+
+    /// Sort children according to the spec.
+    ///
+    /// See <https://spec.matrix.org/v1.13/client-server-api/#ordering-of-children-within-a-space>.
+    fn sort(children: &mut [Self]) {
+        children.sort_by(|a, b| {
+            let by_order = match (&a.order, &b.order) {
+                (Some(a), Some(b)) => a.cmp(b),
+                (Some(_), None) => Ordering::Less,
+                (None, Some(_)) => Ordering::Greater,
+                (None, None) => Ordering::Equal,
+            };
+
+            by_order
+                .then(a.origin_server_ts.cmp(&b.origin_server_ts))
+                .then(a.room_id.cmp(&b.room_id))
+        });
+    }
+}
+
+/// `SpaceHierarchy` allows browsing the rooms of a space, and the rooms of its
+/// subspaces, recursively.
+///
+/// The rooms are exposed as an observable list, which is the depth-first
+/// traversal of the hierarchy: each room comes right after its parent and its
+/// previous siblings, with their own children. Each room appears only once in
+/// the hierarchy, under the first parent it was found in.
+///
+/// The list gets the new rooms every time a new page is loaded, and is kept up
+/// to date when children are added to or removed from the spaces of the
+/// hierarchy with `m.space.child` state events, or when the current user joins
+/// or leaves one of the rooms.
+///
+/// # Example
+///
+/// ```no_run
+/// use matrix_sdk::{
+///     ruma::room_id,
+///     space_hierarchy::{SpaceHierarchy, SpaceHierarchyOptions},
+///     Client,
+/// };
+/// use url::Url;
+///
+/// async {
+///     let homeserver = Url::parse("http://localhost:8080")?;
+///     let client = Client::new(homeserver).await?;
+///     let space_id = room_id!("!space:localhost");
+///     let hierarchy = SpaceHierarchy::new(
+///         client,
+///         space_id.to_owned(),
+///         SpaceHierarchyOptions::default(),
+///     );
+///     let (rooms, mut stream) = hierarchy.rooms();
+///     hierarchy.next_page().await?;
+///     anyhow::Ok(())
+/// };
+/// ```
+#[derive(Debug)]
+pub struct SpaceHierarchy {
+    inner: Arc<SpaceHierarchyInner>,
+    _space_child_handler_guard: EventHandlerDropGuard,
+    _member_handler_guard: EventHandlerDropGuard,
+}
+
+#[derive(Debug)]
+struct SpaceHierarchyInner {
+    client: Client,
+    space_id: OwnedRoomId,
+    options: SpaceHierarchyOptions,
+    state: StdMutex<HierarchyState>,
+}
+
+#[derive(Debug, Default)]
+struct HierarchyState {
+    search_state: SearchState,
+    rooms: ObservableVector<SpaceHierarchyRoom>,
+    /// The children of the rooms of the hierarchy, sorted.
+    children: HashMap<OwnedRoomId, Vec<SpaceChild>>,
+}
+
+impl SpaceHierarchy {
+    /// Create a new `SpaceHierarchy` for the given space.
+    ///
+    /// No request is sent until [`SpaceHierarchy::next_page`] is called.
+    pub fn new(client: Client, space_id: OwnedRoomId, options: SpaceHierarchyOptions) -> Self {
+        let inner = Arc::new(SpaceHierarchyInner {
+            client: client.clone(),
+            space_id,
+            options,
+            state: Default::default(),
+        });
+
+        let weak_inner = Arc::downgrade(&inner);
+        let space_child_handle =
+            client.add_event_handler(move |_: SyncSpaceChildEvent, room: Room, raw: RawEvent| {
+                let weak_inner = weak_inner.clone();
+                async move {
+                    if let Some(inner) = weak_inner.upgrade() {
+                        inner.handle_space_child_event(room.room_id(), &raw).await;
+                    }
+                }
+            });
+
+        let weak_inner = Arc::downgrade(&inner);
+        let member_handle =
+            client.add_event_handler(move |event: SyncRoomMemberEvent, room: Room| {
+                let weak_inner = weak_inner.clone();
+                async move {
+                    if event.state_key() != room.own_user_id() {
+                        return;
+                    }
+
+                    if let Some(inner) = weak_inner.upgrade() {
+                        inner.update_room_state(&room);
+                    }
+                }
+            });
+
+        Self {
+            inner,
+            _space_child_handler_guard: client.event_handler_drop_guard(space_child_handle),
+            _member_handler_guard: client.event_handler_drop_guard(member_handle),
+        }
+    }
+
+    /// Asks the server for the next page of the hierarchy.
+    ///
+    /// Does nothing if the hierarchy has been fully loaded already.
+    // Should never be used concurrently with another `next_page`.
+    pub async fn next_page(&self) -> Result<()> {
+        let from = {
+            let state = self.inner.state.lock().unwrap();
+
+            if state.search_state.is_at_end() {
+                return Ok(());
+            }
+
+            state.search_state.next_token().map(ToOwned::to_owned)
+        };
+
+        let response =
+            self.inner.fetch(&self.inner.space_id, self.inner.options.max_depth, from).await?;
+
+        let mut state = self.inner.state.lock().unwrap();
+
+        state.search_state = match response.next_batch {
+            Some(next_token) => SearchState::Next(next_token),
+            None => SearchState::End,
+        };
+
+        self.inner.insert_rooms(&mut state, response.rooms);
+
+        Ok(())
+    }
+
+    /// Whether the whole hierarchy has been loaded.
+    pub fn is_at_last_page(&self) -> bool {
+        self.inner.state.lock().unwrap().search_state.is_at_end()
+    }
+
+    /// Get the initial values of the rooms of the hierarchy, and a stream of
+    /// updates for them.
+    pub fn rooms(
+        &self,
+    ) -> (Vector<SpaceHierarchyRoom>, impl Stream<Item = Vec<VectorDiff<SpaceHierarchyRoom>>>) {
+        self.inner.state.lock().unwrap().rooms.subscribe().into_values_and_batched_stream()
+    }
+}
+
+impl SpaceHierarchyInner {
+    /// Fetch a page of the hierarchy under the given room.
+    async fn fetch(
+        &self,
+        room_id: &RoomId,
+        max_depth: Option<u32>,
+        from: Option<String>,
+    ) -> Result<get_hierarchy::v1::Response> {
+        let request = assign!(get_hierarchy::v1::Request::new(room_id.to_owned()), {
+            from,
+            limit: self.options.limit.map(UInt::from),
+            max_depth: max_depth.map(UInt::from),
+            suggested_only: self.options.suggested_only,
+        });
+
+        Ok(self.client.send(request).await?)
+    }
+
+    /// Insert rooms returned by the server in the tree.
+    fn insert_rooms(&self, state: &mut HierarchyState, chunks: Vec<SpaceHierarchyRoomsChunk>) {
+        for chunk in chunks {
+            if state.rooms.iter().any(|room| room.room_id == chunk.room_id) {
+                // Each room appears only once in the tree.
+                continue;
+            }
+
+            let mut children: Vec<_> = chunk
+                .children_state
+                .iter()
+                .filter_map(|event| SpaceChild::parse(event)?.1)
+                .collect();
+            SpaceChild::sort(&mut children);
+
+            let state_in_store = self.client.get_room(&chunk.room_id).map(|room| room.state());
+
+            if chunk.room_id == self.space_id {
+                let room = SpaceHierarchyRoom::new(chunk, &children, None, false, state_in_store);
+                state.children.insert(room.room_id.clone(), children);
+                state.rooms.insert(0, room);
+                continue;
+            }
+
+            // Find the first parent of this room in the tree.
+            let Some((parent_index, suggested)) =
+                state.rooms.iter().enumerate().find_map(|(index, room)| {
+                    let siblings = state.children.get(&room.room_id)?;
+                    let child = siblings.iter().find(|child| child.room_id == chunk.room_id)?;
+                    Some((index, child.suggested))
+                })
+            else {
+                warn!(room_id = %chunk.room_id, "Ignoring a room without a parent in the hierarchy");
+                continue;
+            };
+
+            let index = state.insertion_index(parent_index, &chunk.room_id);
+            let room = SpaceHierarchyRoom::new(
+                chunk,
+                &children,
+                Some(&state.rooms[parent_index]),
+                suggested,
+                state_in_store,
+            );
+
+            state.children.insert(room.room_id.clone(), children);
+            state.rooms.insert(index, room);
+        }
+    }
+
+    /// Handle an `m.space.child` state event received in the given room.
+    async fn handle_space_child_event(&self, parent_id: &RoomId, raw: &RawEvent) {
+        let event = Raw::<()>::from_json(raw.0.clone());
+        let Some((child_id, child)) = SpaceChild::parse(&event) else {
+            return;
+        };
+
+        let max_depth = {
+            let mut state = self.state.lock().unwrap();
+
+            let Some(parent_index) = state.index_of(parent_id) else {
+                // Not a room of this hierarchy.
+                return;
+            };
+
+            // Update the children of the parent.
+            let Some(siblings) = state.children.get_mut(parent_id) else {
+                return;
+            };
+            siblings.retain(|sibling| sibling.room_id != child_id);
+            if let Some(child) = &child {
+                siblings.push(child.clone());
+            }
+            SpaceChild::sort(siblings);
+            let children = siblings.iter().map(|child| child.room_id.clone()).collect();
+
+            let mut parent = state.rooms[parent_index].clone();
+            parent.children = children;
+            let parent_depth = parent.depth;
+            state.rooms.set(parent_index, parent);
+
+            // Remove the child and its subtree if it's not a child of the parent anymore,
+            // or it's not suggested anymore and we only want suggested rooms.
+            let child_index = state.rooms.iter().position(|room| {
+                room.room_id == child_id && room.parent.as_deref() == Some(parent_id)
+            });
+
+            match (&child, child_index) {
+                (Some(child), Some(child_index))
+                    if child.suggested || !self.options.suggested_only =>
+                {
+                    // The child is already in the tree, update it.
+                    let mut room = state.rooms[child_index].clone();
+                    room.suggested = child.suggested;
+                    state.rooms.set(child_index, room);
+                    return;
+                }
+
+                (_, Some(child_index)) => {
+                    state.remove_subtree(child_index);
+                    return;
+                }
+
+                (None, None) => return,
+
+                (Some(child), None) => {
+                    if self.options.suggested_only && !child.suggested {
+                        return;
+                    }
+
+                    if state.index_of(&child_id).is_some() {
+                        // Each room appears only once in the tree.
+                        return;
+                    }
+
+                    // Only load the child and its subtree if the child is not too deep.
+                    match self.options.max_depth {
+                        Some(max_depth) => {
+                            let Some(remaining) = max_depth.checked_sub(parent_depth as u32 + 1)
+                            else {
+                                return;
+                            };
+                            Some(remaining)
+                        }
+                        None => None,
+                    }
+                }
+            }
+        };
+
+        // Load the new child, and its own hierarchy page by page.
+        let mut from = None;
+        loop {
+            let is_first_page = from.is_none();
+
+            let response = match self.fetch(&child_id, max_depth, from).await {
+                Ok(response) => response,
+                Err(error) => {
+                    warn!(%child_id, "Failed to load a new child of the space hierarchy: {error}");
+                    return;
+                }
+            };
+
+            let mut state = self.state.lock().unwrap();
+
+            // The tree might have changed while the lock was released, e.g. with a new page
+            // of the hierarchy or another `m.space.child` event, so check that the child
+            // still belongs under the parent.
+            let belongs_under_parent = if is_first_page {
+                state.index_of(&child_id).is_none()
+                    && state.children.get(parent_id).is_some_and(|siblings| {
+                        siblings.iter().any(|sibling| {
+                            sibling.room_id == child_id
+                                && (sibling.suggested || !self.options.suggested_only)
+                        })
+                    })
+            } else {
+                state
+                    .index_of(&child_id)
+                    .is_some_and(|index| state.rooms[index].parent.as_deref() == Some(parent_id))
+            };
+
+            if !belongs_under_parent {
+                debug!(%child_id, "Not loading a child that changed in the space hierarchy");
+                return;
+            }
+
+            self.insert_rooms(&mut state, response.rooms);
+
+            match response.next_batch {
+                Some(next_batch) => from = Some(next_batch),
+                None => return,
+            }
+        }
+    }
+
+    /// Update the state of the given room in the tree, if it's there.
+    fn update_room_state(&self, room: &Room) {
+        let mut state = self.state.lock().unwrap();
+
+        let Some(index) = state.index_of(room.room_id()) else {
+            return;
+        };
+
+        let new_state = Some(room.state());
+        if state.rooms[index].state != new_state {
+            let mut hierarchy_room = state.rooms[index].clone();
+            hierarchy_room.state = new_state;
+            state.rooms.set(index, hierarchy_room);
+        }
+    }
+}
+
+impl HierarchyState {
+    fn index_of(&self, room_id: &RoomId) -> Option<usize> {
+        self.rooms.iter().position(|room| room.room_id == room_id)
+    }
+
+    /// The index after the last room of the subtree starting at the given
+    /// index.
+    fn subtree_end(&self, index: usize) -> usize {
+        let depth = self.rooms[index].depth;
+
+        self.rooms
+            .iter()
+            .enumerate()
+            .skip(index + 1)
+            .find_map(|(index, room)| (room.depth <= depth).then_some(index))
+            .unwrap_or(self.rooms.len())
+    }
+
+    /// The index where to insert a new child of the room at `parent_index`,
+    /// so that it is sorted among its siblings.
+    fn insertion_index(&self, parent_index: usize, child_id: &RoomId) -> usize {
+        let parent = &self.rooms[parent_index];
+        let end = self.subtree_end(parent_index);
+
+        // The siblings that must come after the new child.
+        let next_siblings = parent
+            .children
+            .iter()
+            .skip_while(|sibling| *sibling != child_id)
+            .skip(1)
+            .collect::<Vec<_>>();
+
+        self.rooms
+            .iter()
+            .enumerate()
+            .take(end)
+            .skip(parent_index + 1)
+            .find_map(|(index, room)| {
+                (room.depth == parent.depth + 1 && next_siblings.contains(&&room.room_id))
+                    .then_some(index)
+            })
+            .unwrap_or(end)
+    }
+
+    /// Remove the room at the given index, and all its descendants.
+    fn remove_subtree(&mut self, index: usize) {
+        let end = self.subtree_end(index);
+
+        for _ in index..end {
+            let room = self.rooms.remove(index);
+            self.children.remove(&room.room_id);
+        }
+    }
+}
+
+#[cfg(all(test, not(target_arch = "wasm32")))]
+mod tests {
+    use assert_matches2::assert_let;
+    use eyeball_im::VectorDiff;
+    use matrix_sdk_base::RoomState;
+    use matrix_sdk_test::{async_test, event_factory::EventFactory, JoinedRoomBuilder, ALICE};
+    use ruma::{
+        events::{space::child::SpaceChildEventContent, AnySyncStateEvent},
+        owned_server_name, room_id, RoomId,
+    };
+    use serde_json::{json, Value as JsonValue};
+    use stream_assert::{assert_next_matches, assert_pending};
+
+    use super::{SpaceHierarchy, SpaceHierarchyOptions};
+    use crate::test_utils::mocks::MatrixMockServer;
+
+    fn room_chunk(room_id: &RoomId, children: &[(&RoomId, &str)], is_space: bool) -> JsonValue {
+        let children_state: Vec<_> = children
+            .iter()
+            .map(|(child_id, order)| {
+                json!({
+                    "type": "m.space.child",
+                    "state_key": child_id,
+                    "sender": *ALICE,
+                    "origin_server_ts": 42,
+                    "content": { "via": ["localhost"], "order": order },
+                })
+            })
+            .collect();
+
+        json!({
+            "room_id": room_id,
+            "name": room_id.as_str(),
+            "num_joined_members": 1,
+            "world_readable": false,
+            "guest_can_join": false,
+            "join_rule": "public",
+            "room_type": is_space.then_some("m.space"),
+            "children_state": children_state,
+        })
+    }
+
+    #[async_test]
+    async fn test_space_hierarchy() {
+        let server = MatrixMockServer::new().await;
+        let client = server.client_builder().build().await;
+
+        let space_id = room_id!("!space:localhost");
+        let subspace_id = room_id!("!subspace:localhost");
+        let room_a = room_id!("!a:localhost");
+        let room_b = room_id!("!b:localhost");
+        let room_c = room_id!("!c:localhost");
+
+        // The current user is in the space, and in room C.
+        server.sync_joined_room(&client, space_id).await;
+        server.sync_joined_room(&client, room_c).await;
+
+        server
+            .mock_get_hierarchy()
+            .ok(
+                vec![
+                    room_chunk(space_id, &[(room_a, "b"), (subspace_id, "a")], true),
+                    room_chunk(subspace_id, &[(room_b, "")], true),
+                ],
+                Some("next"),
+            )
+            .mock_once()
+            .mount()
+            .await;
+
+        let hierarchy = SpaceHierarchy::new(
+            client.clone(),
+            space_id.to_owned(),
+            SpaceHierarchyOptions::default(),
+        );
+        hierarchy.next_page().await.unwrap();
+
+        let (rooms, mut stream) = hierarchy.rooms();
+        assert_eq!(rooms.len(), 2);
+        assert_eq!(rooms[0].room_id, space_id);
+        assert_eq!(rooms[0].depth, 0);
+        assert_eq!(rooms[0].children, [subspace_id, room_a]);
+        assert_eq!(rooms[0].state, Some(RoomState::Joined));
+        assert_eq!(rooms[1].room_id, subspace_id);
+        assert_eq!(rooms[1].parent.as_deref(), Some(space_id));
+        assert_eq!(rooms[1].depth, 1);
+        assert!(rooms[1].is_space());
+        assert_eq!(rooms[1].state, None);
+        assert!(!hierarchy.is_at_last_page());
+
+        server
+            .mock_get_hierarchy()
+            .match_from("next")
+            .ok(vec![room_chunk(room_a, &[], false), room_chunk(room_b, &[], false)], None)
+            .mock_once()
+            .mount()
+            .await;
+
+        hierarchy.next_page().await.unwrap();
+        assert!(hierarchy.is_at_last_page());
+
+        // Room B goes under the subspace, room A after it as the last child of the
+        // space.
+        assert_next_matches!(stream, diffs => {
+            assert_eq!(diffs.len(), 2);
+            assert_let!(VectorDiff::Insert { index: 2, value } = &diffs[0]);
+            assert_eq!(value.room_id, room_a);
+            assert_eq!(value.depth, 1);
+            assert_let!(VectorDiff::Insert { index: 2, value } = &diffs[1]);
+            assert_eq!(value.room_id, room_b);
+            assert_eq!(value.parent.as_deref(), Some(subspace_id));
+            assert_eq!(value.depth, 2);
+        });
+
+        // Room C is added as a child of the space.
+        server
+            .mock_get_hierarchy()
+            .ok(vec![room_chunk(room_c, &[], false)], None)
+            .mock_once()
+            .mount()
+            .await;
+
+        let f = EventFactory::new().room(space_id).sender(*ALICE);
+        let add_room_c = f
+            .event(SpaceChildEventContent::new(vec![owned_server_name!("localhost")]))
+            .state_key(room_c.as_str())
+            .into_raw::<AnySyncStateEvent>();
+        server
+            .sync_room(&client, JoinedRoomBuilder::new(space_id).add_state_bulk([add_room_c]))
+            .await;
+
+        assert_next_matches!(stream, diffs => {
+            assert_eq!(diffs.len(), 2);
+            assert_let!(VectorDiff::Set { index: 0, value } = &diffs[0]);
+            assert_eq!(value.children, [subspace_id, room_a, room_c]);
+            assert_let!(VectorDiff::Insert { index: 4, value } = &diffs[1]);
+            assert_eq!(value.room_id, room_c);
+            assert_eq!(value.state, Some(RoomState::Joined));
+        });
+
+        // The subspace is removed from the space, along with room B.
+        let remove_subspace = f
+            .event(SpaceChildEventContent::new(vec![]))
+            .state_key(subspace_id.as_str())
+            .into_raw::<AnySyncStateEvent>();
+        server
+            .sync_room(&client, JoinedRoomBuilder::new(space_id).add_state_bulk([remove_subspace]))
+            .await;
+
+        assert_next_matches!(stream, diffs => {
+            assert_eq!(diffs.len(), 3);
+            assert_let!(VectorDiff::Set { index: 0, value } = &diffs[0]);
+            assert_eq!(value.children, [room_a, room_c]);
+            assert_let!(VectorDiff::Remove { index: 1 } = &diffs[1]);
+            assert_let!(VectorDiff::Remove { index: 1 } = &diffs[2]);
+        });
+
+        let (rooms, _) = hierarchy.rooms();
+        let room_ids: Vec<_> = rooms.iter().map(|room| room.room_id.clone()).collect();
+        assert_eq!(room_ids, [space_id, room_a, room_c]);
+
+        assert_pending!(stream);
+    }
+
+    #[async_test]
+    async fn test_new_child_subtree_is_fully_loaded() {
+        let server = MatrixMockServer::new().await;
+        let client = server.client_builder().build().await;
+
+        let space_id = room_id!("!space:localhost");
+        let subspace_id = room_id!("!subspace:localhost");
+        let room_a = room_id!("!a:localhost");
+        let room_b = room_id!("!b:localhost");
+
+        server.sync_joined_room(&client, space_id).await;
+
+        server
+            .mock_get_hierarchy()
+            .ok(vec![room_chunk(space_id, &[], true)], None)
+            .mock_once()
+            .mount()
+            .await;
+
+        let hierarchy = SpaceHierarchy::new(
+            client.clone(),
+            space_id.to_owned(),
+            SpaceHierarchyOptions::default(),
+        );
+        hierarchy.next_page().await.unwrap();
+        assert!(hierarchy.is_at_last_page());
+
+        let (rooms, mut stream) = hierarchy.rooms();
+        assert_eq!(rooms.len(), 1);
+
+        // The hierarchy of the new subspace is split in two pages.
+        server
+            .mock_get_hierarchy()
+            .match_from("subspace_next")
+            .ok(vec![room_chunk(room_b, &[], false)], None)
+            .mock_once()
+            .mount()
+            .await;
+        server
+            .mock_get_hierarchy()
+            .ok(
+                vec![
+                    room_chunk(subspace_id, &[(room_a, "a"), (room_b, "b")], true),
+                    room_chunk(room_a, &[], false),
+                ],
+                Some("subspace_next"),
+            )
+            .mock_once()
+            .mount()
+            .await;
+
+        let add_subspace = EventFactory::new()
+            .room(space_id)
+            .sender(*ALICE)
+            .event(SpaceChildEventContent::new(vec![owned_server_name!("localhost")]))
+            .state_key(subspace_id.as_str())
+            .into_raw::<AnySyncStateEvent>();
+        server
+            .sync_room(&client, JoinedRoomBuilder::new(space_id).add_state_bulk([add_subspace]))
+            .await;
+
+        // Both pages were loaded.
+        assert_next_matches!(stream, diffs => {
+            assert_eq!(diffs.len(), 4);
+            assert_let!(VectorDiff::Set { index: 0, value } = &diffs[0]);
+            assert_eq!(value.children, [subspace_id]);
+            assert_let!(VectorDiff::Insert { index: 1, value } = &diffs[1]);
+            assert_eq!(value.room_id, subspace_id);
+            assert_let!(VectorDiff::Insert { index: 2, value } = &diffs[2]);
+            assert_eq!(value.room_id, room_a);
+            assert_let!(VectorDiff::Insert { index: 3, value } = &diffs[3]);
+            assert_eq!(value.room_id, room_b);
+            assert_eq!(value.parent.as_deref(), Some(subspace_id));
+        });
+
+        assert_pending!(stream);
+    }
+}
@@ -175,6 +175,11 @@ impl Client {
         self.handle_sync_events(HandlerKind::Presence, None, presence).await?;
         self.handle_sync_events(HandlerKind::ToDevice, None, to_device).await?;
 
+        // The history of the rooms we joined might have been shared with us after we
+        // joined them.
+        #[cfg(feature = "e2e-encryption")]
+        crate::room::shared_room_history::import_room_history_on_bundle(self, to_device);
+
         // Ignore errors when there are no receivers.
         let _ = self.inner.room_updates_sender.send(rooms.clone());
 
@@ -29,15 +29,18 @@ use matrix_sdk_test::{
 };
 use percent_encoding::{AsciiSet, CONTROLS};
 use ruma::{
-    api::client::room::Visibility,
+    api::{
+        client::{receipt::create_receipt::v3::ReceiptType, room::Visibility},
+        Direction,
+    },
     directory::PublicRoomsChunk,
     events::{
-        room::member::RoomMemberEvent, AnyStateEvent, AnyTimelineEvent, MessageLikeEventType,
-        StateEventType,
+        relation::RelationType, room::member::RoomMemberEvent, AnyStateEvent, AnyTimelineEvent,
+        MessageLikeEventType, StateEventType,
     },
     serde::Raw,
     time::Duration,
-    MxcUri, OwnedEventId, OwnedRoomId, RoomId, ServerName,
+    EventId, MilliSecondsSinceUnixEpoch, MxcUri, OwnedEventId, OwnedRoomId, RoomId, ServerName,
 };
 use serde::Deserialize;
 use serde_json::{json, Value};
@@ -502,6 +505,33 @@ impl MatrixMockServer {
         MockEndpoint { mock, server: &self.server, endpoint: RoomMessagesEndpoint }
     }
 
+    /// Create a prebuilt mock for fetching an event along with its context,
+    /// with the `/context` endpoint.
+    pub fn mock_room_event_context(&self) -> MockEndpoint<'_, RoomEventContextEndpoint> {
+        let mock = Mock::given(method("GET")).and(header("authorization", "Bearer 1234"));
+        MockEndpoint {
+            mock,
+            server: &self.server,
+            endpoint: RoomEventContextEndpoint { event_id: None },
+        }
+    }
+
+    /// Create a prebuilt mock for fetching the events related to another event
+    /// with the `/relations` endpoint.
+    pub fn mock_room_relations(&self) -> MockEndpoint<'_, RoomRelationsEndpoint> {
+        let mock = Mock::given(method("GET")).and(header("authorization", "Bearer 1234"));
+        MockEndpoint { mock, server: &self.server, endpoint: RoomRelationsEndpoint::default() }
+    }
+
+    /// Create a prebuilt mock for listing the threads of a room with the
+    /// `/threads` endpoint.
+    pub fn mock_room_threads(&self) -> MockEndpoint<'_, RoomThreadsEndpoint> {
+        let mock = Mock::given(method("GET"))
+            .and(path_regex(r"^/_matrix/client/v1/rooms/.*/threads$"))
+            .and(header("authorization", "Bearer 1234"));
+        MockEndpoint { mock, server: &self.server, endpoint: RoomThreadsEndpoint }
+    }
+
     /// Create a prebuilt mock for uploading media.
     pub fn mock_upload(&self) -> MockEndpoint<'_, UploadEndpoint> {
         let mock = Mock::given(method("POST"))
@@ -510,6 +540,46 @@ impl MatrixMockServer {
         MockEndpoint { mock, server: &self.server, endpoint: UploadEndpoint }
     }
 
+    /// Create a prebuilt mock for preallocating an MXC URI, with
+    /// [`Media::create_content_uri()`](crate::Media::create_content_uri).
+    pub fn mock_create_content_uri(&self) -> MockEndpoint<'_, CreateContentUriEndpoint> {
+        let mock = Mock::given(method("POST"))
+            .and(path("/_matrix/media/v1/create"))
+            .and(header("authorization", "Bearer 1234"));
+        MockEndpoint { mock, server: &self.server, endpoint: CreateContentUriEndpoint }
+    }
+
+    /// Create a prebuilt mock for uploading a chunk of the content of a
+    /// preallocated MXC URI, in a resumable upload.
+    pub fn mock_upload_preallocated_chunk(
+        &self,
+    ) -> MockEndpoint<'_, UploadPreallocatedChunkEndpoint> {
+        let mock = Mock::given(method("PATCH"))
+            .and(path_regex(r"^/_matrix/media/unstable/org.matrix.resumable_upload/upload/.*/.*"))
+            .and(header("authorization", "Bearer 1234"));
+        MockEndpoint { mock, server: &self.server, endpoint: UploadPreallocatedChunkEndpoint }
+    }
+
+    /// Create a prebuilt mock for getting the offset at which a resumable
+    /// upload must be resumed.
+    pub fn mock_resumable_upload_offset(&self) -> MockEndpoint<'_, ResumableUploadOffsetEndpoint> {
+        let mock = Mock::given(method("HEAD"))
+            .and(path_regex(r"^/_matrix/media/unstable/org.matrix.resumable_upload/upload/.*/.*"))
+            .and(header("authorization", "Bearer 1234"));
+        MockEndpoint { mock, server: &self.server, endpoint: ResumableUploadOffsetEndpoint }
+    }
+
+    /// Create a prebuilt mock for downloading the content of a media, with the
+    /// authenticated media endpoint.
+    pub fn mock_authenticated_media_download(
+        &self,
+    ) -> MockEndpoint<'_, AuthenticatedMediaDownloadEndpoint> {
+        let mock = Mock::given(method("GET"))
+            .and(path_regex(r"^/_matrix/client/v1/media/download/.*/.*"))
+            .and(header("authorization", "Bearer 1234"));
+        MockEndpoint { mock, server: &self.server, endpoint: AuthenticatedMediaDownloadEndpoint }
+    }
+
     /// Create a prebuilt mock for resolving room aliases.
     ///
     /// # Examples
@@ -940,6 +1010,61 @@ impl MatrixMockServer {
             .and(header("authorization", "Bearer 1234"));
         MockEndpoint { mock, server: &self.server, endpoint: SetRoomPinnedEventsEndpoint }
     }
+
+    /// Creates a prebuilt mock for the endpoint used to search for messages.
+    pub fn mock_search(&self) -> MockEndpoint<'_, SearchEndpoint> {
+        let mock = Mock::given(method("POST"))
+            .and(path_regex(r"^/_matrix/client/v3/search"))
+            .and(header("authorization", "Bearer 1234"));
+        MockEndpoint { mock, server: &self.server, endpoint: SearchEndpoint }
+    }
+
+    /// Creates a prebuilt mock for the endpoint used to get the hierarchy of
+    /// a space.
+    pub fn mock_get_hierarchy(&self) -> MockEndpoint<'_, GetHierarchyEndpoint> {
+        let mock = Mock::given(method("GET"))
+            .and(path_regex(r"^/_matrix/client/v1/rooms/.*/hierarchy"))
+            .and(header("authorization", "Bearer 1234"));
+        MockEndpoint { mock, server: &self.server, endpoint: GetHierarchyEndpoint }
+    }
+
+    /// Creates a prebuilt mock for the endpoint used to find the event closest
+    /// to a timestamp in a room.
+    pub fn mock_timestamp_to_event(&self) -> MockEndpoint<'_, TimestampToEventEndpoint> {
+        let mock = Mock::given(method("GET"))
+            .and(path_regex(r"^/_matrix/client/v1/rooms/.*/timestamp_to_event"))
+            .and(header("authorization", "Bearer 1234"));
+        MockEndpoint { mock, server: &self.server, endpoint: TimestampToEventEndpoint }
+    }
+
+    /// Creates a prebuilt mock for the endpoint used to upgrade a room.
+    pub fn mock_upgrade_room(&self) -> MockEndpoint<'_, UpgradeRoomEndpoint> {
+        let mock = Mock::given(method("POST"))
+            .and(path_regex(r"^/_matrix/client/v3/rooms/.*/upgrade"))
+            .and(header("authorization", "Bearer 1234"));
+        MockEndpoint { mock, server: &self.server, endpoint: UpgradeRoomEndpoint }
+    }
+
+    /// Creates a prebuilt mock for the endpoint used to update a delayed event
+    /// ([MSC4140](https://github.com/matrix-org/matrix-spec-proposals/pull/4140)).
+    pub fn mock_update_delayed_event(&self) -> MockEndpoint<'_, UpdateDelayedEventEndpoint> {
+        let mock = Mock::given(method("POST"))
+            .and(path_regex(r"^/_matrix/client/unstable/org.matrix.msc4140/delayed_events/.*"))
+            .and(header("authorization", "Bearer 1234"));
+        MockEndpoint { mock, server: &self.server, endpoint: UpdateDelayedEventEndpoint }
+    }
+
+    /// Creates a prebuilt mock for the endpoint used to send a receipt of the
+    /// given type.
+    pub fn mock_send_receipt(
+        &self,
+        receipt_type: ReceiptType,
+    ) -> MockEndpoint<'_, SendReceiptEndpoint> {
+        let mock = Mock::given(method("POST"))
+            .and(path_regex(format!(r"^/_matrix/client/v3/rooms/.*/receipt/{receipt_type}/")))
+            .and(header("authorization", "Bearer 1234"));
+        MockEndpoint { mock, server: &self.server, endpoint: SendReceiptEndpoint }
+    }
 }
 
 /// Parameter to [`MatrixMockServer::sync_room`].
@@ -1946,6 +2071,152 @@ impl<'a> MockEndpoint<'a, RoomMessagesEndpoint> {
     }
 }
 
+/// A prebuilt mock for the `/context` endpoint.
+pub struct RoomEventContextEndpoint {
+    event_id: Option<OwnedEventId>,
+}
+
+impl<'a> MockEndpoint<'a, RoomEventContextEndpoint> {
+    /// Limits the scope of this mock to the context of the given event.
+    pub fn match_event_id(mut self, event_id: &EventId) -> Self {
+        self.endpoint.event_id = Some(event_id.to_owned());
+        self
+    }
+
+    /// Returns a context endpoint that emulates success, i.e. the target event
+    /// could be retrieved without any events around it, with the given
+    /// tokens to paginate from it.
+    pub fn ok(
+        self,
+        event: impl Into<Raw<AnyTimelineEvent>>,
+        start: Option<&str>,
+        end: Option<&str>,
+    ) -> MatrixMock<'a> {
+        // The event id should begin with `$`, which would be taken as the end of the
+        // regex so we need to escape it.
+        let event_id = self
+            .endpoint
+            .event_id
+            .map_or_else(|| "[^/]*".to_owned(), |event_id| event_id.as_str().replace("$", "\\$"));
+
+        let mock = self
+            .mock
+            .and(path_regex(format!(r"^/_matrix/client/v3/rooms/.*/context/{event_id}$")))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "event": event.into(),
+                "events_before": [],
+                "events_after": [],
+                "state": [],
+                "start": start,
+                "end": end,
+            })));
+        MatrixMock { server: self.server, mock }
+    }
+}
+
+/// A prebuilt mock for the `/relations` endpoint.
+#[derive(Default)]
+pub struct RoomRelationsEndpoint {
+    target: Option<OwnedEventId>,
+    rel_type: Option<RelationType>,
+}
+
+impl<'a> MockEndpoint<'a, RoomRelationsEndpoint> {
+    /// Limits the scope of this mock to the relations of the given event.
+    pub fn match_target(mut self, event_id: &EventId) -> Self {
+        self.endpoint.target = Some(event_id.to_owned());
+        self
+    }
+
+    /// Limits the scope of this mock to relations of the given type.
+    pub fn match_rel_type(mut self, rel_type: RelationType) -> Self {
+        self.endpoint.rel_type = Some(rel_type);
+        self
+    }
+
+    /// Expects an optional `from` to be set on the request.
+    pub fn match_from(self, from: &str) -> Self {
+        Self { mock: self.mock.and(query_param("from", from)), ..self }
+    }
+
+    /// Returns a relations endpoint that emulates success, i.e. the related
+    /// events provided in the `response` could be retrieved.
+    pub fn ok(self, response: RoomRelationsResponseTemplate) -> MatrixMock<'a> {
+        // The event id should begin with `$`, which would be taken as the end of the
+        // regex so we need to escape it.
+        let target = self
+            .endpoint
+            .target
+            .map_or_else(|| "[^/]*".to_owned(), |event_id| event_id.as_str().replace("$", "\\$"));
+
+        let rel_type = self
+            .endpoint
+            .rel_type
+            .map_or_else(|| "(/.*)?".to_owned(), |rel_type| format!("/{rel_type}(/.*)?"));
+
+        let mock = self
+            .mock
+            .and(path_regex(format!(r"^/_matrix/client/v1/rooms/.*/relations/{target}{rel_type}$")))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "chunk": response.chunk,
+                "next_batch": response.next_batch,
+                "prev_batch": response.prev_batch,
+            })));
+        MatrixMock { server: self.server, mock }
+    }
+}
+
+/// A response to a [`RoomRelationsEndpoint`] query.
+#[derive(Default)]
+pub struct RoomRelationsResponseTemplate {
+    /// The set of related events returned by this query.
+    pub chunk: Vec<Raw<AnyTimelineEvent>>,
+    /// The token to continue paginating in the same direction.
+    pub next_batch: Option<String>,
+    /// The token to paginate in the opposite direction.
+    pub prev_batch: Option<String>,
+}
+
+impl RoomRelationsResponseTemplate {
+    /// Fill the events returned as part of this response.
+    pub fn events(mut self, chunk: Vec<impl Into<Raw<AnyTimelineEvent>>>) -> Self {
+        self.chunk = chunk.into_iter().map(Into::into).collect();
+        self
+    }
+
+    /// Fill the next batch token.
+    pub fn next_batch(mut self, token: impl Into<String>) -> Self {
+        self.next_batch = Some(token.into());
+        self
+    }
+}
+
+/// A prebuilt mock for the `/threads` endpoint.
+pub struct RoomThreadsEndpoint;
+
+impl<'a> MockEndpoint<'a, RoomThreadsEndpoint> {
+    /// Expects an optional `from` to be set on the request.
+    pub fn match_from(self, from: &str) -> Self {
+        Self { mock: self.mock.and(query_param("from", from)), ..self }
+    }
+
+    /// Returns a threads endpoint that emulates success, i.e. the thread roots
+    /// provided in `chunk` could be retrieved, with the optional `next_batch`
+    /// token to get more thread roots.
+    pub fn ok(
+        self,
+        chunk: Vec<impl Into<Raw<AnyTimelineEvent>>>,
+        next_batch: Option<String>,
+    ) -> MatrixMock<'a> {
+        let chunk: Vec<Raw<AnyTimelineEvent>> = chunk.into_iter().map(Into::into).collect();
+        let mock = self.mock.respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "chunk": chunk,
+            "next_batch": next_batch,
+        })));
+        MatrixMock { server: self.server, mock }
+    }
+}
+
 /// A response to a [`RoomMessagesEndpoint`] query.
 pub struct RoomMessagesResponseTemplate {
     /// The start token for this /messages query.
@@ -2012,6 +2283,83 @@ impl<'a> MockEndpoint<'a, UploadEndpoint> {
     }
 }
 
+/// A prebuilt mock for preallocating an MXC URI.
+pub struct CreateContentUriEndpoint;
+
+impl<'a> MockEndpoint<'a, CreateContentUriEndpoint> {
+    /// Returns an endpoint that emulates success, i.e. the given MXC URI has
+    /// been preallocated.
+    pub fn ok(self, mxc_id: &MxcUri) -> MatrixMock<'a> {
+        let mock = self.mock.respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "content_uri": mxc_id
+        })));
+        MatrixMock { server: self.server, mock }
+    }
+}
+
+/// A prebuilt mock for uploading a chunk of the content of a preallocated MXC
+/// URI.
+pub struct UploadPreallocatedChunkEndpoint;
+
+impl<'a> MockEndpoint<'a, UploadPreallocatedChunkEndpoint> {
+    /// Expect that the chunk is uploaded at the given offset.
+    pub fn match_offset(self, offset: u64) -> Self {
+        Self { mock: self.mock.and(header("upload-offset", offset.to_string())), ..self }
+    }
+
+    /// Returns an endpoint that emulates success, i.e. the homeserver has
+    /// received the content up to `next_offset`.
+    pub fn ok(self, next_offset: u64) -> MatrixMock<'a> {
+        let mock = self.mock.respond_with(
+            ResponseTemplate::new(204).insert_header("upload-offset", next_offset.to_string()),
+        );
+        MatrixMock { server: self.server, mock }
+    }
+}
+
+/// A prebuilt mock for getting the offset of a resumable upload.
+pub struct ResumableUploadOffsetEndpoint;
+
+impl<'a> MockEndpoint<'a, ResumableUploadOffsetEndpoint> {
+    /// Returns an endpoint that emulates success, i.e. the homeserver has
+    /// received the content up to `offset`.
+    pub fn ok(self, offset: u64) -> MatrixMock<'a> {
+        let mock = self.mock.respond_with(
+            ResponseTemplate::new(200).insert_header("upload-offset", offset.to_string()),
+        );
+        MatrixMock { server: self.server, mock }
+    }
+}
+
+/// A prebuilt mock for downloading the content of a media.
+pub struct AuthenticatedMediaDownloadEndpoint;
+
+impl<'a> MockEndpoint<'a, AuthenticatedMediaDownloadEndpoint> {
+    /// Expect that the content is requested from the given offset, with an
+    /// HTTP range request.
+    pub fn match_range_offset(self, offset: u64) -> Self {
+        Self { mock: self.mock.and(header("range", format!("bytes={offset}-"))), ..self }
+    }
+
+    /// Returns an endpoint that emulates success, with the whole content of
+    /// the media.
+    pub fn ok(self, content: Vec<u8>) -> MatrixMock<'a> {
+        let mock = self.mock.respond_with(
+            ResponseTemplate::new(200).set_body_raw(content, "application/octet-stream"),
+        );
+        MatrixMock { server: self.server, mock }
+    }
+
+    /// Returns an endpoint that emulates success, with a part of the content
+    /// of the media.
+    pub fn ok_partial(self, content: Vec<u8>) -> MatrixMock<'a> {
+        let mock = self.mock.respond_with(
+            ResponseTemplate::new(206).set_body_raw(content, "application/octet-stream"),
+        );
+        MatrixMock { server: self.server, mock }
+    }
+}
+
 /// A prebuilt mock for resolving a room alias.
 pub struct ResolveRoomAliasEndpoint;
 
@@ -2278,9 +2626,19 @@ impl<'a> MockEndpoint<'a, VersionsEndpoint> {
     ///
     /// The response will return some commonly supported versions.
     pub fn ok(self) -> MatrixMock<'a> {
+        self.ok_with_unstable_features(&[])
+    }
+
+    /// Returns a successful `/_matrix/client/versions` request, with the given
+    /// unstable features enabled.
+    ///
+    /// The response will return some commonly supported versions.
+    pub fn ok_with_unstable_features(self, unstable_features: &[&str]) -> MatrixMock<'a> {
+        let unstable_features: BTreeMap<_, _> =
+            unstable_features.iter().map(|feature| (*feature, true)).collect();
+
         let mock = self.mock.respond_with(ResponseTemplate::new(200).set_body_json(json!({
-            "unstable_features": {
-            },
+            "unstable_features": unstable_features,
             "versions": [
                 "r0.0.1",
                 "r0.2.0",
@@ -2342,3 +2700,173 @@ impl<'a> MockEndpoint<'a, SetRoomPinnedEventsEndpoint> {
         MatrixMock { server: self.server, mock }
     }
 }
+
+/// A prebuilt mock for sending a receipt.
+pub struct SendReceiptEndpoint;
+
+impl<'a> MockEndpoint<'a, SendReceiptEndpoint> {
+    /// Ensures that the body of the request is a superset of the provided
+    /// `body` parameter.
+    pub fn body_matches_partial_json(self, body: Value) -> Self {
+        Self { mock: self.mock.and(body_partial_json(body)), ..self }
+    }
+
+    /// Returns a successful empty response.
+    pub fn ok(self) -> MatrixMock<'a> {
+        let mock = self.mock.respond_with(ResponseTemplate::new(200).set_body_json(json!({})));
+        MatrixMock { server: self.server, mock }
+    }
+}
+
+/// A prebuilt mock for `GET /rooms/{roomId}/hierarchy` request.
+pub struct GetHierarchyEndpoint;
+
+impl<'a> MockEndpoint<'a, GetHierarchyEndpoint> {
+    /// Expects the given `from` token to be set on the request.
+    pub fn match_from(self, from: &str) -> Self {
+        Self { mock: self.mock.and(query_param("from", from)), ..self }
+    }
+
+    /// Returns a hierarchy endpoint that emulates success, i.e. the given
+    /// `rooms` are part of the hierarchy, with the optional `next_batch` token
+    /// to get more rooms.
+    pub fn ok(self, rooms: Vec<Value>, next_batch: Option<&str>) -> MatrixMock<'a> {
+        let mock = self.mock.respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "rooms": rooms,
+            "next_batch": next_batch,
+        })));
+        MatrixMock { server: self.server, mock }
+    }
+}
+
+/// A prebuilt mock for `GET /rooms/{roomId}/timestamp_to_event` request.
+pub struct TimestampToEventEndpoint;
+
+impl<'a> MockEndpoint<'a, TimestampToEventEndpoint> {
+    /// Expects the given timestamp and direction to be requested.
+    pub fn match_timestamp(self, ts: MilliSecondsSinceUnixEpoch, dir: Direction) -> Self {
+        let dir = match dir {
+            Direction::Forward => "f",
+            Direction::Backward => "b",
+        };
+
+        Self {
+            mock: self
+                .mock
+                .and(query_param("ts", ts.get().to_string()))
+                .and(query_param("dir", dir)),
+            ..self
+        }
+    }
+
+    /// Returns an endpoint that emulates success, i.e. the event `event_id`,
+    /// sent at `origin_server_ts`, is the closest to the requested timestamp.
+    pub fn ok(
+        self,
+        event_id: &EventId,
+        origin_server_ts: MilliSecondsSinceUnixEpoch,
+    ) -> MatrixMock<'a> {
+        let mock = self.mock.respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "event_id": event_id,
+            "origin_server_ts": origin_server_ts,
+        })));
+        MatrixMock { server: self.server, mock }
+    }
+}
+
+/// A prebuilt mock for `POST /rooms/{roomId}/upgrade` request.
+pub struct UpgradeRoomEndpoint;
+
+impl<'a> MockEndpoint<'a, UpgradeRoomEndpoint> {
+    /// Expects the given room version to be requested.
+    pub fn match_new_version(self, new_version: &str) -> Self {
+        Self {
+            mock: self.mock.and(body_partial_json(json!({ "new_version": new_version }))),
+            ..self
+        }
+    }
+
+    /// Returns an upgrade endpoint that emulates success, i.e. the room has
+    /// been replaced by `replacement_room`.
+    pub fn ok(self, replacement_room: &RoomId) -> MatrixMock<'a> {
+        let mock = self.mock.respond_with(
+            ResponseTemplate::new(200)
+                .set_body_json(json!({ "replacement_room": replacement_room })),
+        );
+        MatrixMock { server: self.server, mock }
+    }
+}
+
+/// A prebuilt mock for updating a delayed event.
+pub struct UpdateDelayedEventEndpoint;
+
+impl<'a> MockEndpoint<'a, UpdateDelayedEventEndpoint> {
+    /// Expects the given delayed event to be updated.
+    pub fn match_delay_id(self, delay_id: &str) -> Self {
+        Self {
+            mock: self.mock.and(path_regex(format!(
+                r"^/_matrix/client/unstable/org.matrix.msc4140/delayed_events/{delay_id}$"
+            ))),
+            ..self
+        }
+    }
+
+    /// Expects the given action (`cancel`, `restart` or `send`) to be
+    /// requested.
+    pub fn match_action(self, action: &str) -> Self {
+        Self { mock: self.mock.and(body_partial_json(json!({ "action": action }))), ..self }
+    }
+
+    /// Returns an endpoint that emulates success.
+    pub fn ok(self) -> MatrixMock<'a> {
+        let mock = self.mock.respond_with(ResponseTemplate::new(200).set_body_json(json!({})));
+        MatrixMock { server: self.server, mock }
+    }
+}
+
+/// A prebuilt mock for searching messages.
+pub struct SearchEndpoint;
+
+impl<'a> MockEndpoint<'a, SearchEndpoint> {
+    /// Expects the given `next_batch` token to be set on the request.
+    pub fn match_next_batch(self, next_batch: &str) -> Self {
+        Self { mock: self.mock.and(query_param("next_batch", next_batch)), ..self }
+    }
+
+    /// Expects the search to be restricted to the given rooms.
+    pub fn match_rooms(self, rooms: &[&RoomId]) -> Self {
+        let body =
+            json!({ "search_categories": { "room_events": { "filter": { "rooms": rooms } } } });
+        Self { mock: self.mock.and(body_partial_json(body)), ..self }
+    }
+
+    /// Returns a search endpoint that emulates success, i.e. the events
+    /// provided in `results` matched the search, with the given `highlights`
+    /// and the optional `next_batch` token to get more results.
+    pub fn ok(
+        self,
+        results: Vec<impl Into<Raw<AnyTimelineEvent>>>,
+        highlights: Vec<&str>,
+        next_batch: Option<&str>,
+    ) -> MatrixMock<'a> {
+        let results: Vec<_> = results
+            .into_iter()
+            .map(|event| {
+                let event: Raw<AnyTimelineEvent> = event.into();
+                json!({ "result": event, "context": {} })
+            })
+            .collect();
+
+        let mock = self.mock.respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "search_categories": {
+                "room_events": {
+                    "count": results.len(),
+                    "results": results,
+                    "highlights": highlights,
+                    "next_batch": next_batch,
+                }
+            }
+        })));
+        MatrixMock { server: self.server, mock }
+    }
+}
@@ -1,7 +1,9 @@
 mod backups;
 mod cross_signing;
+mod dehydrated_devices;
 mod recovery;
 mod secret_storage;
+mod shared_room_history;
 mod verification;
 
 /// The backup key, which is also returned (encrypted) as part of the secret
@@ -0,0 +1,319 @@
+// Copyright 2025 The Matrix.org Foundation C.I.C.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::sync::{Arc, Mutex};
+
+use assert_matches2::assert_matches;
+use matrix_sdk::{encryption::dehydrated_devices::DehydratedDeviceError, Client};
+use matrix_sdk_test::async_test;
+use serde_json::{json, Value};
+use wiremock::{
+    matchers::{method, path, path_regex},
+    Mock, MockServer, ResponseTemplate,
+};
+
+use crate::logged_in_client_with_server;
+
+const DEHYDRATED_DEVICE_PATH: &str =
+    "/_matrix/client/unstable/org.matrix.msc3814.v1/dehydrated_device";
+
+/// A logged in client, with its cross-signing keys, which are required to sign
+/// the dehydrated devices.
+async fn cross_signed_client() -> (Client, MockServer) {
+    let (client, server) = logged_in_client_with_server().await;
+
+    Mock::given(method("POST"))
+        .and(path("/_matrix/client/r0/keys/upload"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "one_time_key_counts": {
+                "signed_curve25519": 50
+            }
+        })))
+        .mount(&server)
+        .await;
+
+    Mock::given(method("POST"))
+        .and(path("/_matrix/client/unstable/keys/device_signing/upload"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({})))
+        .mount(&server)
+        .await;
+
+    Mock::given(method("POST"))
+        .and(path("/_matrix/client/unstable/keys/signatures/upload"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({ "failures": {} })))
+        .mount(&server)
+        .await;
+
+    // Secret storage is not enabled.
+    let user_id = client.user_id().unwrap();
+    Mock::given(method("GET"))
+        .and(path(format!(
+            "/_matrix/client/r0/user/{user_id}/account_data/m.secret_storage.default_key"
+        )))
+        .respond_with(ResponseTemplate::new(404).set_body_json(json!({
+            "errcode": "M_NOT_FOUND",
+            "error": "Account data not found",
+        })))
+        .mount(&server)
+        .await;
+
+    client.encryption().wait_for_e2ee_initialization_tasks().await;
+    client
+        .encryption()
+        .bootstrap_cross_signing(None)
+        .await
+        .expect("We should be able to bootstrap our cross-signing");
+
+    (client, server)
+}
+
+#[async_test]
+async fn test_dehydrated_device_lifecycle() {
+    let (client, server) = cross_signed_client().await;
+    let dehydrated_devices = client.encryption().dehydrated_devices();
+
+    assert!(!dehydrated_devices.has_pickle_key().await.unwrap());
+
+    // Remember the uploaded devices, so the server can return them when
+    // rehydrating.
+    let uploaded_devices = Arc::new(Mutex::new(Vec::<Value>::new()));
+
+    Mock::given(method("PUT"))
+        .and(path(DEHYDRATED_DEVICE_PATH))
+        .respond_with({
+            let uploaded_devices = uploaded_devices.clone();
+            move |request: &wiremock::Request| {
+                let body: Value = request.body_json().unwrap();
+                let device_id = body["device_id"].clone();
+                uploaded_devices.lock().unwrap().push(body);
+
+                ResponseTemplate::new(200).set_body_json(json!({ "device_id": device_id }))
+            }
+        })
+        .expect(2)
+        .named("dehydrated device PUT")
+        .mount(&server)
+        .await;
+
+    // Creating a dehydrated device also creates a pickle key.
+    let first_device_id = dehydrated_devices.create().await.unwrap();
+    assert!(dehydrated_devices.has_pickle_key().await.unwrap());
+
+    // Creating another one reuses the pickle key.
+    let device_id = dehydrated_devices.create().await.unwrap();
+    assert_ne!(device_id, first_device_id);
+
+    let (first_uploaded_device, uploaded_device) = {
+        let mut uploaded_devices = uploaded_devices.lock().unwrap();
+        assert_eq!(uploaded_devices.len(), 2);
+        let uploaded_device = uploaded_devices.pop().unwrap();
+        (uploaded_devices.pop().unwrap(), uploaded_device)
+    };
+    assert_eq!(uploaded_device["device_id"], device_id.as_str());
+    assert_eq!(uploaded_device["initial_device_display_name"], "Dehydrated device");
+
+    // The server returns the first device, as if another client replaced our last
+    // device with it.
+    {
+        let _guard = Mock::given(method("GET"))
+            .and(path(DEHYDRATED_DEVICE_PATH))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "device_id": first_device_id,
+                "device_data": first_uploaded_device["device_data"],
+            })))
+            .expect(1)
+            .named("dehydrated device GET")
+            .mount_as_scoped(&server)
+            .await;
+
+        Mock::given(method("POST"))
+            .and(path(format!("{DEHYDRATED_DEVICE_PATH}/{first_device_id}/events")))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "events": [],
+                "next_batch": "next",
+            })))
+            .expect(1)
+            .named("dehydrated device events POST")
+            .mount(&server)
+            .await;
+
+        // The device can be rehydrated with the pickle key, it didn't receive any
+        // room key.
+        assert_eq!(dehydrated_devices.rehydrate().await.unwrap(), 0);
+    }
+
+    Mock::given(method("GET"))
+        .and(path(DEHYDRATED_DEVICE_PATH))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "device_id": device_id,
+            "device_data": uploaded_device["device_data"],
+        })))
+        .expect(1)
+        .named("own dehydrated device GET")
+        .mount(&server)
+        .await;
+
+    Mock::given(method("POST"))
+        .and(path(format!("{DEHYDRATED_DEVICE_PATH}/{device_id}/events")))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .named("own dehydrated device events POST")
+        .mount(&server)
+        .await;
+
+    // The device that we uploaded ourselves is not rehydrated.
+    assert_eq!(dehydrated_devices.rehydrate().await.unwrap(), 0);
+
+    Mock::given(method("DELETE"))
+        .and(path(DEHYDRATED_DEVICE_PATH))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({ "device_id": device_id })))
+        .expect(1)
+        .named("dehydrated device DELETE")
+        .mount(&server)
+        .await;
+
+    // Deleting the device forgets the pickle key.
+    dehydrated_devices.delete().await.unwrap();
+    assert!(!dehydrated_devices.has_pickle_key().await.unwrap());
+
+    server.verify().await;
+}
+
+#[async_test]
+async fn test_rehydrate_without_pickle_key_or_device() {
+    let (client, server) = cross_signed_client().await;
+    let dehydrated_devices = client.encryption().dehydrated_devices();
+
+    // Rehydrating is not possible without the pickle key.
+    assert_matches!(
+        dehydrated_devices.rehydrate().await,
+        Err(DehydratedDeviceError::MissingPickleKey)
+    );
+
+    Mock::given(method("PUT"))
+        .and(path(DEHYDRATED_DEVICE_PATH))
+        .respond_with(
+            ResponseTemplate::new(200).set_body_json(json!({ "device_id": "DEHYDRATED" })),
+        )
+        .mount(&server)
+        .await;
+
+    dehydrated_devices.create().await.unwrap();
+
+    Mock::given(method("GET"))
+        .and(path(DEHYDRATED_DEVICE_PATH))
+        .respond_with(ResponseTemplate::new(404).set_body_json(json!({
+            "errcode": "M_NOT_FOUND",
+            "error": "No dehydrated device found",
+        })))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    // There is nothing to do if the server doesn't have a dehydrated device.
+    assert_eq!(dehydrated_devices.rehydrate().await.unwrap(), 0);
+
+    server.verify().await;
+}
+
+#[async_test]
+async fn test_pickle_key_is_stored_in_secret_storage() {
+    let (client, server) = cross_signed_client().await;
+    let user_id = client.user_id().unwrap();
+
+    Mock::given(method("PUT"))
+        .and(path(DEHYDRATED_DEVICE_PATH))
+        .respond_with(
+            ResponseTemplate::new(200).set_body_json(json!({ "device_id": "DEHYDRATED" })),
+        )
+        .mount(&server)
+        .await;
+
+    client.encryption().dehydrated_devices().create().await.unwrap();
+
+    Mock::given(method("GET"))
+        .and(path_regex(format!(r"_matrix/client/r0/user/{user_id}/account_data/.*")))
+        .respond_with(ResponseTemplate::new(404).set_body_json(json!({
+            "errcode": "M_NOT_FOUND",
+            "error": "Account data not found",
+        })))
+        .mount(&server)
+        .await;
+
+    Mock::given(method("PUT"))
+        .and(path_regex(format!(r"_matrix/client/r0/user/{user_id}/account_data/m\..*")))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({})))
+        .mount(&server)
+        .await;
+
+    Mock::given(method("PUT"))
+        .and(path(format!("_matrix/client/r0/user/{user_id}/account_data/org.matrix.msc3814")))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({})))
+        .expect(1)
+        .named("dehydrated device pickle key secret PUT")
+        .mount(&server)
+        .await;
+
+    // Creating a new secret store exports the pickle key of the dehydrated device.
+    client.encryption().secret_storage().create_secret_store().await.unwrap();
+
+    server.verify().await;
+}
+
+#[async_test]
+async fn test_new_pickle_key_is_stored_in_existing_secret_store() {
+    let (client, server) = cross_signed_client().await;
+    let user_id = client.user_id().unwrap();
+
+    Mock::given(method("GET"))
+        .and(path_regex(format!(r"_matrix/client/r0/user/{user_id}/account_data/.*")))
+        .respond_with(ResponseTemplate::new(404).set_body_json(json!({
+            "errcode": "M_NOT_FOUND",
+            "error": "Account data not found",
+        })))
+        .mount(&server)
+        .await;
+
+    Mock::given(method("PUT"))
+        .and(path_regex(format!(r"_matrix/client/r0/user/{user_id}/account_data/m\..*")))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({})))
+        .mount(&server)
+        .await;
+
+    // There is no pickle key yet, so none is exported.
+    let secret_store = client.encryption().secret_storage().create_secret_store().await.unwrap();
+
+    Mock::given(method("PUT"))
+        .and(path(format!("_matrix/client/r0/user/{user_id}/account_data/org.matrix.msc3814")))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({})))
+        .expect(1)
+        .named("dehydrated device pickle key secret PUT")
+        .mount(&server)
+        .await;
+
+    Mock::given(method("PUT"))
+        .and(path(DEHYDRATED_DEVICE_PATH))
+        .respond_with(
+            ResponseTemplate::new(200).set_body_json(json!({ "device_id": "DEHYDRATED" })),
+        )
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    // The new pickle key is stored in the secret store right away.
+    client.encryption().dehydrated_devices().create_with_secret_store(&secret_store).await.unwrap();
+    assert!(client.encryption().dehydrated_devices().has_pickle_key().await.unwrap());
+
+    server.verify().await;
+}
@@ -53,6 +53,8 @@ async fn test_client(user_id: &UserId) -> (Client, wiremock::MockServer) {
             auto_enable_cross_signing: true,
             backup_download_strategy: BackupDownloadStrategy::Manual,
             auto_enable_backups: true,
+            auto_enable_dehydrated_devices: false,
+            share_history_on_invite: false,
         })
         .build()
         .await
@@ -656,6 +658,15 @@ async fn test_recovery_disabling() {
         .mount(&server)
         .await;
 
+    Mock::given(method("PUT"))
+        .and(path(format!("_matrix/client/r0/user/{user_id}/account_data/org.matrix.msc3814")))
+        .and(header("authorization", "Bearer 1234"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({})))
+        .expect(1)
+        .named("dehydrated device pickle key deletion")
+        .mount(&server)
+        .await;
+
     recovery.disable().await.expect("We should be able to disable recovery again.");
     assert_eq!(client.encryption().backups().state(), BackupState::Unknown);
     assert_eq!(recovery.state(), RecoveryState::Disabled);
@@ -0,0 +1,398 @@
+// Copyright 2025 The Matrix.org Foundation C.I.C.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::{iter, time::Duration};
+
+use matrix_sdk::{
+    authentication::matrix::{MatrixSession, MatrixSessionTokens},
+    config::{RequestConfig, SyncSettings},
+    crypto::{types::requests::AnyOutgoingRequest, CollectStrategy, OlmMachine},
+    encryption::EncryptionSettings,
+    test_utils::test_client_builder_with_server,
+    Client,
+};
+use matrix_sdk_base::SessionMeta;
+use matrix_sdk_test::{
+    async_test, ruma_response_from_json, InvitedRoomBuilder, JoinedRoomBuilder, StateTestEvent,
+    StrippedStateTestEvent, SyncResponseBuilder, DEFAULT_TEST_ROOM_ID,
+};
+use ruma::{
+    api::client::keys::{claim_keys, get_keys},
+    device_id,
+    to_device::DeviceIdOrAllDevices,
+    user_id,
+};
+use serde_json::json;
+use tokio::time::sleep;
+use wiremock::{
+    matchers::{method, path, path_regex},
+    Mock, MockServer, ResponseTemplate,
+};
+
+use crate::{mock_sync, mock_sync_scoped};
+
+/// The device keys and a one-time key of the device of the given machine, as
+/// they are returned by the `/keys/query` and `/keys/claim` endpoints.
+async fn device_keys(machine: &OlmMachine) -> (serde_json::Value, serde_json::Value) {
+    let upload = machine
+        .outgoing_requests()
+        .await
+        .unwrap()
+        .into_iter()
+        .find_map(|request| match request.request() {
+            AnyOutgoingRequest::KeysUpload(upload) => Some(upload.clone()),
+            _ => None,
+        })
+        .expect("The machine should upload its keys");
+
+    let user_id = machine.user_id();
+    let device_id = machine.device_id();
+
+    let device_keys = json!({
+        user_id.as_str(): { device_id.as_str(): upload.device_keys.unwrap() },
+    });
+    let (key_id, one_time_key) = upload.one_time_keys.into_iter().next().unwrap();
+    let one_time_keys = json!({
+        user_id.as_str(): { device_id.as_str(): { key_id.to_string(): one_time_key } },
+    });
+
+    (device_keys, one_time_keys)
+}
+
+/// The device keys and a one-time key of a device of `@bob:localhost`, as they
+/// are returned by the `/keys/query` and `/keys/claim` endpoints.
+async fn bob_device_keys() -> (serde_json::Value, serde_json::Value) {
+    let bob = OlmMachine::new(user_id!("@bob:localhost"), device_id!("BOBDEVICE")).await;
+    device_keys(&bob).await
+}
+
+/// A logged-in client, `@example:localhost`, with a mocked server that accepts
+/// the upload of its keys.
+async fn logged_in_client(
+    share_history_on_invite: bool,
+    room_key_recipient_strategy: CollectStrategy,
+) -> (Client, MockServer) {
+    let (builder, server) = test_client_builder_with_server().await;
+    let client = builder
+        .request_config(RequestConfig::new().disable_retry())
+        .with_encryption_settings(EncryptionSettings {
+            share_history_on_invite,
+            ..Default::default()
+        })
+        .with_room_key_recipient_strategy(room_key_recipient_strategy)
+        .build()
+        .await
+        .unwrap();
+
+    client
+        .restore_session(MatrixSession {
+            meta: SessionMeta {
+                user_id: user_id!("@example:localhost").to_owned(),
+                device_id: device_id!("DEVICEID").to_owned(),
+            },
+            tokens: MatrixSessionTokens { access_token: "1234".to_owned(), refresh_token: None },
+        })
+        .await
+        .unwrap();
+
+    Mock::given(method("POST"))
+        .and(path("/_matrix/client/r0/keys/upload"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "one_time_key_counts": {
+                "signed_curve25519": 50
+            }
+        })))
+        .mount(&server)
+        .await;
+
+    (client, server)
+}
+
+/// A client that is a member of the encrypted [`DEFAULT_TEST_ROOM_ID`] room,
+/// with a room key for that room.
+///
+/// `@bob:localhost` has a single unsigned device, that we can establish an Olm
+/// session with.
+async fn client_with_room_key(
+    share_history_on_invite: bool,
+    room_key_recipient_strategy: CollectStrategy,
+) -> (Client, MockServer) {
+    let (client, server) =
+        logged_in_client(share_history_on_invite, room_key_recipient_strategy).await;
+
+    let (device_keys, one_time_keys) = bob_device_keys().await;
+
+    Mock::given(method("POST"))
+        .and(path("/_matrix/client/r0/keys/query"))
+        .respond_with(
+            ResponseTemplate::new(200).set_body_json(json!({ "device_keys": device_keys })),
+        )
+        .mount(&server)
+        .await;
+
+    Mock::given(method("POST"))
+        .and(path("/_matrix/client/r0/keys/claim"))
+        .respond_with(
+            ResponseTemplate::new(200).set_body_json(json!({ "one_time_keys": one_time_keys })),
+        )
+        .mount(&server)
+        .await;
+
+    Mock::given(method("POST"))
+        .and(path_regex(r"^/_matrix/client/r0/rooms/.*/invite$"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({})))
+        .expect(1)
+        .named("invite")
+        .mount(&server)
+        .await;
+
+    let sync = SyncResponseBuilder::new()
+        .add_joined_room(
+            JoinedRoomBuilder::new(&DEFAULT_TEST_ROOM_ID)
+                .add_state_event(StateTestEvent::Encryption),
+        )
+        .build_json_sync_response();
+    mock_sync(&server, sync, None).await;
+    client.sync_once(SyncSettings::default()).await.unwrap();
+
+    client
+        .olm_machine_for_testing()
+        .await
+        .as_ref()
+        .unwrap()
+        .share_room_key(&DEFAULT_TEST_ROOM_ID, iter::empty(), Default::default())
+        .await
+        .unwrap();
+
+    (client, server)
+}
+
+/// Mock the upload of the room key bundle, and the to-device request sending
+/// its location, expecting them to be called `times` times.
+async fn mock_bundle_sharing(server: &MockServer, times: u64) {
+    Mock::given(method("POST"))
+        .and(path_regex(r"^/_matrix/media/.*/upload"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "content_uri": "mxc://localhost/bundle"
+        })))
+        .expect(times)
+        .named("room key bundle upload")
+        .mount(server)
+        .await;
+
+    Mock::given(method("PUT"))
+        .and(path_regex(r"^/_matrix/client/r0/sendToDevice/m\.room\.encrypted/.*"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({})))
+        .expect(times)
+        .named("room key bundle data to-device message")
+        .mount(server)
+        .await;
+}
+
+#[async_test]
+async fn test_room_key_bundle_is_uploaded_on_invite() {
+    let (client, server) = client_with_room_key(true, CollectStrategy::AllDevices).await;
+    mock_bundle_sharing(&server, 1).await;
+
+    let room = client.get_room(&DEFAULT_TEST_ROOM_ID).unwrap();
+    room.invite_user_by_id(user_id!("@bob:localhost")).await.unwrap();
+
+    server.verify().await;
+}
+
+#[async_test]
+async fn test_room_key_bundle_is_not_uploaded_if_disabled() {
+    let (client, server) = client_with_room_key(false, CollectStrategy::AllDevices).await;
+    mock_bundle_sharing(&server, 0).await;
+
+    let room = client.get_room(&DEFAULT_TEST_ROOM_ID).unwrap();
+    room.invite_user_by_id(user_id!("@bob:localhost")).await.unwrap();
+
+    server.verify().await;
+}
+
+#[async_test]
+async fn test_room_key_bundle_is_not_uploaded_without_recipient_devices() {
+    // Bob's device isn't trusted, so it must not receive the history of the room.
+    let (client, server) = client_with_room_key(true, CollectStrategy::OnlyTrustedDevices).await;
+    mock_bundle_sharing(&server, 0).await;
+
+    let room = client.get_room(&DEFAULT_TEST_ROOM_ID).unwrap();
+    room.invite_user_by_id(user_id!("@bob:localhost")).await.unwrap();
+
+    server.verify().await;
+}
+
+#[async_test]
+async fn test_room_key_bundle_is_imported_when_received_after_joining() {
+    let (client, server) = logged_in_client(true, CollectStrategy::AllDevices).await;
+    let own_user_id = client.user_id().unwrap();
+    let own_device_id = client.device_id().unwrap();
+
+    // Alice invites us to the room, and will share its history with us once we
+    // joined it.
+    let alice = OlmMachine::new(user_id!("@alice:localhost"), device_id!("ALICEDEVICE")).await;
+    let (device_keys, _) = device_keys(&alice).await;
+
+    Mock::given(method("POST"))
+        .and(path("/_matrix/client/r0/keys/query"))
+        .respond_with(
+            ResponseTemplate::new(200).set_body_json(json!({ "device_keys": device_keys })),
+        )
+        .mount(&server)
+        .await;
+
+    Mock::given(method("POST"))
+        .and(path_regex(r"^/_matrix/client/r0/rooms/.*/join$"))
+        .respond_with(
+            ResponseTemplate::new(200).set_body_json(json!({ "room_id": *DEFAULT_TEST_ROOM_ID })),
+        )
+        .mount(&server)
+        .await;
+
+    let mut sync_builder = SyncResponseBuilder::new();
+    sync_builder.add_invited_room(InvitedRoomBuilder::new(&DEFAULT_TEST_ROOM_ID).add_state_event(
+        StrippedStateTestEvent::Custom(json!({
+            "type": "m.room.member",
+            "state_key": own_user_id,
+            "sender": alice.user_id(),
+            "content": { "membership": "invite" },
+        })),
+    ));
+    {
+        let _scope = mock_sync_scoped(&server, sync_builder.build_json_sync_response(), None).await;
+        client.sync_once(SyncSettings::default()).await.unwrap();
+    }
+
+    // We join the room before receiving the room key bundle.
+    let room = client.get_room(&DEFAULT_TEST_ROOM_ID).unwrap();
+    room.join().await.unwrap();
+
+    // We learn about the devices of Alice once we share an encrypted room.
+    sync_builder.add_joined_room(
+        JoinedRoomBuilder::new(&DEFAULT_TEST_ROOM_ID)
+            .add_state_event(StateTestEvent::Encryption)
+            .add_state_event(StateTestEvent::Custom(json!({
+                "type": "m.room.member",
+                "state_key": alice.user_id(),
+                "sender": alice.user_id(),
+                "event_id": "$alice_member",
+                "origin_server_ts": 0,
+                "content": { "membership": "join" },
+            }))),
+    );
+    {
+        let _scope = mock_sync_scoped(&server, sync_builder.build_json_sync_response(), None).await;
+        client.sync_once(SyncSettings::default()).await.unwrap();
+    }
+
+    // Alice establishes an Olm session with our device, with the keys we uploaded.
+    let upload = server
+        .received_requests()
+        .await
+        .unwrap()
+        .into_iter()
+        .find(|request| request.url.path() == "/_matrix/client/r0/keys/upload")
+        .expect("We should have uploaded our keys");
+    let upload: serde_json::Value = upload.body_json().unwrap();
+    let (key_id, one_time_key) =
+        upload["one_time_keys"].as_object().unwrap().iter().next().unwrap();
+
+    alice.update_tracked_users([own_user_id]).await.unwrap();
+    let keys_query_request = alice
+        .outgoing_requests()
+        .await
+        .unwrap()
+        .into_iter()
+        .find(|request| matches!(request.request(), AnyOutgoingRequest::KeysQuery(_)))
+        .unwrap();
+    let keys_query_response = ruma_response_from_json::<get_keys::v3::Response>(&json!({
+        "device_keys": {
+            own_user_id.as_str(): { own_device_id.as_str(): upload["device_keys"] },
+        },
+    }));
+    alice
+        .mark_request_as_sent(keys_query_request.request_id(), &keys_query_response)
+        .await
+        .unwrap();
+
+    let (keys_claim_request_id, _) =
+        alice.get_missing_sessions(iter::once(own_user_id)).await.unwrap().unwrap();
+    let keys_claim_response = ruma_response_from_json::<claim_keys::v3::Response>(&json!({
+        "one_time_keys": {
+            own_user_id.as_str(): { own_device_id.as_str(): { key_id.as_str(): one_time_key } },
+        },
+    }));
+    alice.mark_request_as_sent(&keys_claim_request_id, &keys_claim_response).await.unwrap();
+
+    // Alice sends us the room key bundle.
+    let bundle_content = serde_json::from_value(json!({
+        "room_id": *DEFAULT_TEST_ROOM_ID,
+        "file": {
+            "url": "mxc://localhost/bundle",
+            "key": {
+                "kty": "oct",
+                "key_ops": ["encrypt", "decrypt"],
+                "alg": "A256CTR",
+                "k": "qcHVMSgYg-71CauWBezXI5qkaRb0LuIy-Wx5kIaHMIA",
+                "ext": true,
+            },
+            "iv": "X85+XgHN+HEAAAAAAAAAAA",
+            "hashes": {
+                "sha256": "5qG4fFnbbVdlAB1Q72JDKwCagV6Dbkx9uds4rSak37c",
+            },
+            "v": "v2",
+        },
+    }))
+    .unwrap();
+    let request = alice
+        .share_room_key_bundle_data(own_user_id, &CollectStrategy::AllDevices, &bundle_content)
+        .await
+        .unwrap()
+        .expect("Alice should share an Olm session with our device");
+    let content =
+        &request.messages[own_user_id][&DeviceIdOrAllDevices::DeviceId(own_device_id.to_owned())];
+
+    // The room key bundle is downloaded as soon as we receive it.
+    Mock::given(method("GET"))
+        .and(path_regex(r"^/_matrix/.*/download/localhost/bundle"))
+        .respond_with(ResponseTemplate::new(200).set_body_bytes(b"bundle".to_vec()))
+        .expect(1)
+        .named("room key bundle download")
+        .mount(&server)
+        .await;
+
+    let mut sync = sync_builder.build_json_sync_response();
+    sync["to_device"]["events"] = json!([{
+        "type": "m.room.encrypted",
+        "sender": alice.user_id(),
+        "content": content,
+    }]);
+    {
+        let _scope = mock_sync_scoped(&server, sync, None).await;
+        client.sync_once(SyncSettings::default()).await.unwrap();
+    }
+
+    // The bundle is imported in the background.
+    for _ in 0..50 {
+        let requests = server.received_requests().await.unwrap();
+        if requests.iter().any(|request| request.url.path().ends_with("/download/localhost/bundle"))
+        {
+            break;
+        }
+        sleep(Duration::from_millis(20)).await;
+    }
+
+    server.verify().await;
+}
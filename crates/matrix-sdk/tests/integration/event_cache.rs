@@ -12,6 +12,7 @@ use matrix_sdk::{
         RoomEventCacheUpdate,
     },
     linked_chunk::{ChunkIdentifier, Position, Update},
+    room::ListThreadsOptions,
     test_utils::{
         assert_event_matches_msg,
         mocks::{MatrixMockServer, RoomMessagesResponseTemplate},
@@ -22,7 +23,7 @@ use matrix_sdk_test::{
     async_test, event_factory::EventFactory, GlobalAccountDataTestEvent, JoinedRoomBuilder, ALICE,
 };
 use ruma::{
-    event_id,
+    assign, event_id,
     events::{AnySyncMessageLikeEvent, AnySyncTimelineEvent},
     room_id, user_id, EventId, RoomVersionId,
 };
@@ -1643,7 +1644,7 @@ async fn test_lazy_loading() {
                         previous: Some(ChunkIdentifier::new(0)),
                         new: ChunkIdentifier::new(1),
                         next: None,
-                        gap: Gap { prev_token: "raclette".to_owned() },
+                        gap: Gap { prev_token: "raclette".to_owned(), next_token: None },
                     },
                     // chunk #3
                     Update::NewItemsChunk {
@@ -2143,3 +2144,267 @@ async fn test_deduplication() {
         });
     }
 }
+
+#[async_test]
+async fn test_thread_summaries() {
+    let server = MatrixMockServer::new().await;
+    let client = server.client_builder().build().await;
+
+    let event_cache = client.event_cache();
+    event_cache.subscribe().unwrap();
+
+    let room_id = room_id!("!omelette:fromage.fr");
+    let root = event_id!("$root");
+
+    let f = EventFactory::new().room(room_id);
+
+    let room = server.sync_joined_room(&client, room_id).await;
+
+    let (room_event_cache, _drop_handles) = room.event_cache().await.unwrap();
+    let (events, mut subscriber) = room_event_cache.subscribe().await;
+    assert!(events.is_empty());
+
+    // A thread root and a reply in this thread come from sync.
+    server
+        .sync_room(
+            &client,
+            JoinedRoomBuilder::new(room_id)
+                .add_timeline_event(f.text_msg("root").sender(*ALICE).event_id(root))
+                .add_timeline_event(
+                    f.text_msg("reply")
+                        .sender(user_id!("@b:b.c"))
+                        .in_thread(root, root)
+                        .event_id(event_id!("$1")),
+                ),
+        )
+        .await;
+
+    assert_let_timeout!(Ok(RoomEventCacheUpdate::UpdateTimelineEvents { .. }) = subscriber.recv());
+
+    // The thread summary has been updated.
+    assert_let_timeout!(
+        Ok(RoomEventCacheUpdate::UpdateThreadSummaries { summaries }) = subscriber.recv()
+    );
+    assert_eq!(summaries.len(), 1);
+
+    let summary = &summaries[root];
+    assert_eq!(summary.num_replies, 1);
+    assert_eq!(summary.latest_reply.as_deref(), Some(event_id!("$1")));
+    assert!(summary.participants.contains(user_id!("@b:b.c")));
+
+    assert_eq!(room_event_cache.thread_summary(root).await.as_ref(), Some(summary));
+    assert!(subscriber.is_empty());
+}
+
+#[async_test]
+async fn test_list_threads() {
+    let server = MatrixMockServer::new().await;
+    let client = server.client_builder().build().await;
+
+    let room_id = room_id!("!omelette:fromage.fr");
+    let room = server.sync_joined_room(&client, room_id).await;
+
+    let f = EventFactory::new().room(room_id).sender(*ALICE);
+
+    server
+        .mock_room_threads()
+        .ok(
+            vec![
+                f.text_msg("thread 2").event_id(event_id!("$2")).into_raw_timeline(),
+                f.text_msg("thread 1").event_id(event_id!("$1")).into_raw_timeline(),
+            ],
+            Some("next_batch".to_owned()),
+        )
+        .mock_once()
+        .mount()
+        .await;
+
+    let threads = room.threads(ListThreadsOptions::default()).await.unwrap();
+
+    assert_eq!(threads.chunk.len(), 2);
+    assert_eq!(threads.chunk[0].event_id().as_deref(), Some(event_id!("$2")));
+    assert_eq!(threads.chunk[1].event_id().as_deref(), Some(event_id!("$1")));
+    assert_eq!(threads.prev_batch_token.as_deref(), Some("next_batch"));
+
+    // Paginate to the next batch of threads.
+    server
+        .mock_room_threads()
+        .match_from("next_batch")
+        .ok(vec![f.text_msg("thread 0").event_id(event_id!("$0")).into_raw_timeline()], None)
+        .mock_once()
+        .mount()
+        .await;
+
+    let options =
+        assign!(ListThreadsOptions::default(), { from: threads.prev_batch_token.clone() });
+    let threads = room.threads(options).await.unwrap();
+
+    assert_eq!(threads.chunk.len(), 1);
+    assert_eq!(threads.chunk[0].event_id().as_deref(), Some(event_id!("$0")));
+    assert!(threads.prev_batch_token.is_none());
+}
+
+#[async_test]
+async fn test_fill_interior_gap() {
+    let server = MatrixMockServer::new().await;
+    let client = server.client_builder().build().await;
+
+    let event_cache = client.event_cache();
+
+    event_cache.subscribe().unwrap();
+
+    let room_id = room_id!("!galette:saucisse.bzh");
+    let room = server.sync_joined_room(&client, room_id).await;
+
+    let (room_event_cache, _drop_handles) = room.event_cache().await.unwrap();
+    let (initial_events, mut subscriber) = room_event_cache.subscribe().await;
+    assert!(initial_events.is_empty());
+
+    let f = EventFactory::new().room(room_id).sender(user_id!("@ben:saucisse.bzh"));
+
+    // Without storage, the prev-batch tokens of the syncs are all kept, so each
+    // sync creates a gap.
+    server
+        .sync_room(
+            &client,
+            JoinedRoomBuilder::new(room_id)
+                .add_timeline_event(f.text_msg("one").event_id(event_id!("$1")))
+                .set_timeline_prev_batch("prev-batch-1".to_owned()),
+        )
+        .await;
+    assert_let_timeout!(Ok(RoomEventCacheUpdate::UpdateTimelineEvents { .. }) = subscriber.recv());
+
+    server
+        .sync_room(
+            &client,
+            JoinedRoomBuilder::new(room_id)
+                .add_timeline_event(f.text_msg("four").event_id(event_id!("$4")))
+                .set_timeline_prev_batch("prev-batch-2".to_owned()),
+        )
+        .await;
+    assert_let_timeout!(Ok(RoomEventCacheUpdate::UpdateTimelineEvents { .. }) = subscriber.recv());
+
+    let pagination = room_event_cache.pagination();
+
+    // The first gap is at the start of the timeline, the second one is between the
+    // events of the two syncs.
+    let gaps = pagination.gaps().await;
+    assert_eq!(gaps.len(), 2);
+    assert!(gaps[0].previous_event_id.is_none());
+    assert_eq!(gaps[0].next_event_id.as_deref(), Some(event_id!("$1")));
+    assert!(gaps[0].is_interior().not());
+    assert_eq!(gaps[1].previous_event_id.as_deref(), Some(event_id!("$1")));
+    assert_eq!(gaps[1].next_event_id.as_deref(), Some(event_id!("$4")));
+    assert!(gaps[1].is_interior());
+
+    // Fill the interior gap backwards, from its end.
+    server
+        .mock_room_messages()
+        .match_from("prev-batch-2")
+        .ok(RoomMessagesResponseTemplate::default()
+            .events(vec![f.text_msg("three").event_id(event_id!("$3"))])
+            .end_token("prev-batch-3"))
+        .mock_once()
+        .mount()
+        .await;
+
+    let outcome = pagination.run_backwards_from_gap(&gaps[1], 20).await.unwrap();
+    assert_eq!(outcome.events.len(), 1);
+    assert_event_matches_msg(&outcome.events[0], "three");
+
+    assert_let!(Some(gap) = outcome.remaining_gap);
+    assert_eq!(gap.previous_event_id.as_deref(), Some(event_id!("$1")));
+    assert_eq!(gap.next_event_id.as_deref(), Some(event_id!("$3")));
+
+    assert_let_timeout!(
+        Ok(RoomEventCacheUpdate::UpdateTimelineEvents { diffs, .. }) = subscriber.recv()
+    );
+    assert_eq!(diffs.len(), 1);
+    assert_let!(VectorDiff::Insert { index: 1, value: event } = &diffs[0]);
+    assert_event_matches_msg(event, "three");
+
+    // Fill what remains of the gap forwards, from its start.
+    server
+        .mock_room_event_context()
+        .match_event_id(event_id!("$1"))
+        .ok(f.text_msg("one").event_id(event_id!("$1")), Some("start-1"), Some("next-batch-1"))
+        .mock_once()
+        .mount()
+        .await;
+    server
+        .mock_room_messages()
+        .match_from("next-batch-1")
+        .ok(RoomMessagesResponseTemplate::default()
+            .events(vec![f.text_msg("two").event_id(event_id!("$2"))])
+            .end_token("next-batch-2"))
+        .mock_once()
+        .mount()
+        .await;
+
+    let outcome = pagination.run_forwards_from_gap(&gap, 20).await.unwrap();
+    assert_eq!(outcome.events.len(), 1);
+    assert_event_matches_msg(&outcome.events[0], "two");
+
+    assert_let!(Some(gap) = outcome.remaining_gap);
+    assert_eq!(gap.previous_event_id.as_deref(), Some(event_id!("$2")));
+    assert_eq!(gap.next_event_id.as_deref(), Some(event_id!("$3")));
+
+    assert_let_timeout!(
+        Ok(RoomEventCacheUpdate::UpdateTimelineEvents { diffs, .. }) = subscriber.recv()
+    );
+    assert_eq!(diffs.len(), 1);
+    assert_let!(VectorDiff::Insert { index: 1, value: event } = &diffs[0]);
+    assert_event_matches_msg(event, "two");
+
+    // The forward token of the gap is kept, so the next forward pagination doesn't
+    // need the context of the event before the gap. It reaches the first event
+    // after the gap, so the gap is filled.
+    let gaps = pagination.gaps().await;
+    assert_eq!(gaps.len(), 2);
+    assert_eq!(gaps[1], gap);
+
+    server
+        .mock_room_messages()
+        .match_from("next-batch-2")
+        .ok(RoomMessagesResponseTemplate::default()
+            .events(vec![
+                f.text_msg("two bis").event_id(event_id!("$2b")),
+                f.text_msg("three").event_id(event_id!("$3")),
+                f.text_msg("four").event_id(event_id!("$4")),
+            ])
+            .end_token("next-batch-3"))
+        .mock_once()
+        .mount()
+        .await;
+
+    let outcome = pagination.run_forwards_from_gap(&gaps[1], 20).await.unwrap();
+    assert_eq!(outcome.events.len(), 1);
+    assert_event_matches_msg(&outcome.events[0], "two bis");
+    assert!(outcome.remaining_gap.is_none());
+
+    assert_let_timeout!(
+        Ok(RoomEventCacheUpdate::UpdateTimelineEvents { diffs, .. }) = subscriber.recv()
+    );
+    assert_eq!(diffs.len(), 1);
+    assert_let!(VectorDiff::Insert { index: 2, value: event } = &diffs[0]);
+    assert_event_matches_msg(event, "two bis");
+
+    // The gap doesn't exist anymore.
+    assert_matches!(
+        pagination.run_forwards_from_gap(&gaps[1], 20).await,
+        Err(EventCacheError::GapNotFound)
+    );
+
+    // Only the gap at the start of the timeline remains.
+    let gaps = pagination.gaps().await;
+    assert_eq!(gaps.len(), 1);
+    assert!(gaps[0].previous_event_id.is_none());
+
+    let (events, _) = room_event_cache.subscribe().await;
+    assert_eq!(events.len(), 5);
+    assert_event_matches_msg(&events[0], "one");
+    assert_event_matches_msg(&events[1], "two");
+    assert_event_matches_msg(&events[2], "two bis");
+    assert_event_matches_msg(&events[3], "three");
+    assert_event_matches_msg(&events[4], "four");
+}
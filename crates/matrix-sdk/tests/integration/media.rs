@@ -1,16 +1,23 @@
+use assert_matches2::assert_let;
 use matrix_sdk::{
+    assert_let_timeout,
     authentication::matrix::{MatrixSession, MatrixSessionTokens},
     config::RequestConfig,
-    media::{MediaFormat, MediaRequestParameters, MediaThumbnailSettings},
-    test_utils::logged_in_client_with_server,
+    media::{
+        download_manager::{MediaDownloadPriority, MediaDownloadUpdate},
+        MediaFormat, MediaOrigin, MediaRequestParameters, MediaRetentionPolicy,
+        MediaRetentionScope, MediaThumbnailSettings,
+    },
+    test_utils::{logged_in_client_with_server, mocks::MatrixMockServer},
     Client, SessionMeta,
 };
+use matrix_sdk_base::event_cache::store::media::IgnoreMediaRetentionPolicy;
 use matrix_sdk_test::async_test;
 use ruma::{
     api::client::media::get_content_thumbnail::v3::Method,
     assign, device_id,
     events::room::{message::ImageMessageEventContent, ImageInfo, MediaSource},
-    mxc_uri, owned_mxc_uri, uint, user_id,
+    mxc_uri, owned_mxc_uri, owned_room_id, uint, user_id,
 };
 use serde_json::json;
 use wiremock::{
@@ -465,3 +472,299 @@ async fn test_async_media_upload() {
         .await
         .unwrap();
 }
+
+#[async_test]
+async fn test_media_download_manager() {
+    let server = MatrixMockServer::new().await;
+    let client = server.client_builder().build().await;
+
+    let manager = client.media().download_manager();
+    let mut updates = manager.subscribe();
+
+    let request = MediaRequestParameters {
+        source: MediaSource::Plain(owned_mxc_uri!("mxc://example.org/file")),
+        format: MediaFormat::File,
+    };
+
+    server
+        .mock_authenticated_media_download()
+        .ok(b"Hello, World!".to_vec())
+        .mock_once()
+        .mount()
+        .await;
+
+    let handle = manager.enqueue(request.clone(), MediaDownloadPriority::High).await;
+
+    assert_let_timeout!(Ok(MediaDownloadUpdate::Progress { progress, .. }) = updates.recv());
+    assert_eq!(progress.current, 13);
+    assert_eq!(progress.total, 13);
+
+    assert_let_timeout!(Ok(MediaDownloadUpdate::Finished { request: finished }) = updates.recv());
+    assert_eq!(finished.uri(), request.uri());
+    assert!(manager.downloads().await.is_empty());
+
+    // The media is in the cache now, the homeserver isn't reached again.
+    assert_eq!(client.media().get_media_content(&request, true).await.unwrap(), b"Hello, World!");
+
+    // The download isn't in the queue anymore.
+    assert!(!handle.cancel().await);
+}
+
+#[async_test]
+async fn test_media_download_manager_permanent_failure() {
+    let server = MatrixMockServer::new().await;
+    let client = server.client_builder().build().await;
+
+    let manager = client.media().download_manager();
+    let mut updates = manager.subscribe();
+
+    let request = MediaRequestParameters {
+        source: MediaSource::Plain(owned_mxc_uri!("mxc://example.org/missing")),
+        format: MediaFormat::File,
+    };
+
+    server
+        .mock_authenticated_media_download()
+        .respond_with(ResponseTemplate::new(404).set_body_json(json!({
+            "errcode": "M_NOT_FOUND",
+            "error": "Not found",
+        })))
+        .mock_once()
+        .mount()
+        .await;
+
+    manager.enqueue(request.clone(), Default::default()).await;
+
+    // A missing media isn't retried.
+    assert_let_timeout!(
+        Ok(MediaDownloadUpdate::Failed { request: failed, error }) = updates.recv()
+    );
+    assert_eq!(failed.uri(), request.uri());
+    assert_let!(Some(api_error) = error.as_client_api_error());
+    assert_eq!(api_error.status_code, 404);
+    assert!(manager.downloads().await.is_empty());
+}
+
+#[async_test]
+async fn test_scoped_media_retention_policy_and_pinning() {
+    let server = MatrixMockServer::new().await;
+    let client = server.client_builder().build().await;
+    let media = client.media();
+
+    let room_id = owned_room_id!("!room:localhost");
+    let room_request = MediaRequestParameters {
+        source: MediaSource::Plain(owned_mxc_uri!("mxc://example.org/room")),
+        format: MediaFormat::File,
+    };
+    let pinned_request = MediaRequestParameters {
+        source: MediaSource::Plain(owned_mxc_uri!("mxc://example.org/pinned")),
+        format: MediaFormat::File,
+    };
+
+    server.mock_authenticated_media_download().ok(b"Hello, World!".to_vec()).mount().await;
+
+    // The global policy doesn't accept any media, but the room's policy does.
+    media
+        .set_media_retention_policy(MediaRetentionPolicy::empty().with_max_file_size(Some(1)))
+        .await
+        .unwrap();
+    media
+        .set_scoped_media_retention_policy(
+            MediaRetentionScope::Room(room_id.clone()),
+            Some(MediaRetentionPolicy::empty()),
+        )
+        .await
+        .unwrap();
+    assert_eq!(
+        media
+            .scoped_media_retention_policies()
+            .await
+            .unwrap()
+            .get(&MediaRetentionScope::Room(room_id.clone())),
+        Some(MediaRetentionPolicy::empty())
+    );
+
+    // The media fetched with the media manager of the room is in its scope.
+    let room = server.sync_joined_room(&client, &room_id).await;
+    room.media().get_media_content(&room_request, true).await.unwrap();
+    media.pin_media(&pinned_request).await.unwrap();
+
+    // Adding the pinned media again doesn't unpin it.
+    let cache_store = client.event_cache_store().lock().await.unwrap();
+    cache_store
+        .add_media_content(
+            &pinned_request,
+            b"Hello, World!".to_vec(),
+            IgnoreMediaRetentionPolicy::No,
+        )
+        .await
+        .unwrap();
+    drop(cache_store);
+
+    media.clean_up_media_cache().await.unwrap();
+
+    let cache_store = client.event_cache_store().lock().await.unwrap();
+    assert!(cache_store.get_media_content(&room_request).await.unwrap().is_some());
+    assert!(cache_store.get_media_content(&pinned_request).await.unwrap().is_some());
+    drop(cache_store);
+
+    // Once unpinned, the media is subject to the global policy again.
+    media.unpin_media(&pinned_request).await.unwrap();
+    media.clean_up_media_cache().await.unwrap();
+
+    let cache_store = client.event_cache_store().lock().await.unwrap();
+    assert!(cache_store.get_media_content(&room_request).await.unwrap().is_some());
+    assert!(cache_store.get_media_content(&pinned_request).await.unwrap().is_none());
+}
+
+#[async_test]
+async fn test_media_cache_usage() {
+    let server = MatrixMockServer::new().await;
+    let client = server.client_builder().build().await;
+    let media = client.media();
+
+    let room_id = owned_room_id!("!room:localhost");
+    let room_request = MediaRequestParameters {
+        source: MediaSource::Plain(owned_mxc_uri!("mxc://example.org/room")),
+        format: MediaFormat::File,
+    };
+    let other_request = MediaRequestParameters {
+        source: MediaSource::Plain(owned_mxc_uri!("mxc://example.org/other")),
+        format: MediaFormat::File,
+    };
+
+    server.mock_authenticated_media_download().ok(b"Hello, World!".to_vec()).mount().await;
+
+    media
+        .get_media_content_with_origin(&room_request, true, &MediaOrigin::room(room_id.clone()))
+        .await
+        .unwrap();
+    media.get_media_content(&other_request, true).await.unwrap();
+
+    let usage = media.media_cache_usage().await.unwrap();
+    assert_eq!(usage.total.count, 2);
+    assert_eq!(usage.total.size, 26);
+    assert_eq!(usage.rooms[&room_id].count, 1);
+    assert_eq!(usage.rooms[&room_id].size, 13);
+    // The content type returned by the homeserver is stored with the media.
+    assert_eq!(usage.content_types["application/octet-stream"].count, 2);
+
+    let entries = media.media_cache_entries().await.unwrap();
+    assert_eq!(entries.len(), 2);
+    assert!(entries.iter().any(|entry| entry.uri.as_deref() == Some(other_request.uri())));
+
+    // Clear the media of the room.
+    media.remove_media_content_for_room(&room_id).await.unwrap();
+
+    let usage = media.media_cache_usage().await.unwrap();
+    assert_eq!(usage.total.count, 1);
+    assert!(usage.rooms.is_empty());
+}
+
+#[cfg(feature = "e2e-encryption")]
+#[async_test]
+async fn test_get_media_file_streams_encrypted_content() {
+    use std::io::{Cursor, Read};
+
+    use matrix_sdk::crypto::AttachmentEncryptor;
+    use ruma::events::room::EncryptedFileInit;
+
+    let server = MatrixMockServer::new().await;
+    let client = server.client_builder().build().await;
+
+    let data = b"Hello, encrypted World!".to_vec();
+    let mut cursor = Cursor::new(data.clone());
+    let mut encryptor = AttachmentEncryptor::new(&mut cursor);
+    let mut encrypted = Vec::new();
+    encryptor.read_to_end(&mut encrypted).unwrap();
+    let keys = encryptor.finish();
+
+    let file = EncryptedFileInit {
+        url: owned_mxc_uri!("mxc://example.org/encrypted"),
+        key: keys.key,
+        iv: keys.iv,
+        hashes: keys.hashes,
+        v: keys.version,
+    }
+    .into();
+    let request = MediaRequestParameters {
+        source: MediaSource::Encrypted(Box::new(file)),
+        format: MediaFormat::File,
+    };
+
+    // The content is decrypted on the fly.
+    {
+        let _guard = server
+            .mock_authenticated_media_download()
+            .ok(encrypted.clone())
+            .mock_once()
+            .mount_as_scoped()
+            .await;
+
+        let handle = client
+            .media()
+            .get_media_file(&request, Some("hello.txt".to_owned()), &mime::TEXT_PLAIN, false, None)
+            .await
+            .unwrap();
+
+        assert_eq!(std::fs::read(handle.path()).unwrap(), data);
+    }
+
+    // The content was tampered with, the hash doesn't match.
+    {
+        let mut tampered = encrypted.clone();
+        tampered[0] ^= 1;
+
+        let _guard = server
+            .mock_authenticated_media_download()
+            .ok(tampered)
+            .mock_once()
+            .mount_as_scoped()
+            .await;
+
+        let mut written = Vec::new();
+        client.media().stream_media_content(&request, &mut written).await.unwrap_err();
+    }
+}
+
+#[cfg(feature = "e2e-encryption")]
+#[async_test]
+async fn test_upload_encrypted_file_from_path() {
+    use std::io::{Cursor, Read, Write};
+
+    use matrix_sdk::crypto::AttachmentDecryptor;
+
+    let server = MatrixMockServer::new().await;
+    let client = server.client_builder().build().await;
+
+    // Bigger than a chunk read from the file.
+    let data = (0..200_000u32).map(|i| (i % 251) as u8).collect::<Vec<_>>();
+    let mut file = tempfile::NamedTempFile::new().unwrap();
+    file.write_all(&data).unwrap();
+
+    server
+        .mock_upload()
+        .expect_mime_type("application/octet-stream")
+        .ok(mxc_uri!("mxc://example.org/uploaded"))
+        .mock_once()
+        .mount()
+        .await;
+
+    let encrypted_file = client
+        .upload_encrypted_file_from_path(&mime::APPLICATION_OCTET_STREAM, file.path())
+        .await
+        .unwrap();
+    assert_eq!(encrypted_file.url, owned_mxc_uri!("mxc://example.org/uploaded"));
+
+    // The uploaded content can be decrypted with the returned keys.
+    let requests = server.server().received_requests().await.unwrap();
+    let upload = requests.iter().find(|request| request.method.as_str() == "POST").unwrap();
+    assert_eq!(upload.body.len(), data.len());
+    assert_ne!(upload.body, data);
+
+    let mut cursor = Cursor::new(upload.body.clone());
+    let mut decryptor = AttachmentDecryptor::new(&mut cursor, encrypted_file.into()).unwrap();
+    let mut decrypted = Vec::new();
+    decryptor.read_to_end(&mut decrypted).unwrap();
+    assert_eq!(decrypted, data);
+}
@@ -22,7 +22,10 @@ use matrix_sdk_test::{
     SyncResponseBuilder, DEFAULT_TEST_ROOM_ID,
 };
 use ruma::{
-    api::client::{membership::Invite3pidInit, receipt::create_receipt::v3::ReceiptType},
+    api::{
+        client::{membership::Invite3pidInit, receipt::create_receipt::v3::ReceiptType},
+        Direction,
+    },
     assign, event_id,
     events::{
         direct::DirectUserIdentifier,
@@ -33,7 +36,8 @@ use ruma::{
         },
         TimelineEventType,
     },
-    int, mxc_uri, owned_event_id, room_id, thirdparty, user_id, OwnedUserId, TransactionId,
+    int, mxc_uri, owned_event_id, room_id, thirdparty, uint, user_id, MilliSecondsSinceUnixEpoch,
+    OwnedUserId, RoomVersionId, TransactionId,
 };
 use serde_json::{from_value, json, Value};
 use stream_assert::assert_pending;
@@ -1186,3 +1190,83 @@ async fn test_room_member_updates_sender_on_partial_members_update() {
     assert_let!(RoomMembersUpdate::Partial(user_ids) = next);
     assert_eq!(user_ids, BTreeSet::from_iter(vec![user_id!("@alice:b.c").to_owned()]));
 }
+
+#[async_test]
+async fn test_upgrade_room() {
+    let server = MatrixMockServer::new().await;
+    let client = server.client_builder().build().await;
+
+    let room_id = room_id!("!old:b.c");
+    let new_room_id = room_id!("!new:b.c");
+    let room = server.sync_joined_room(&client, room_id).await;
+
+    server.mock_upgrade_room().match_new_version("11").ok(new_room_id).mock_once().mount().await;
+
+    // The ID of the new room is returned right away.
+    let upgraded_room_id = room.upgrade(RoomVersionId::V11).await.unwrap();
+    assert_eq!(upgraded_room_id, new_room_id);
+
+    // The tombstone and the create event link both rooms.
+    let f = EventFactory::new().sender(user_id!("@example:localhost"));
+    server
+        .sync_room(
+            &client,
+            JoinedRoomBuilder::new(room_id).add_state_bulk(vec![f
+                .room_tombstone("This room has been replaced", new_room_id)
+                .room(room_id)
+                .into_raw_sync()
+                .cast()]),
+        )
+        .await;
+    server
+        .sync_room(
+            &client,
+            JoinedRoomBuilder::new(new_room_id).add_state_event(StateTestEvent::Custom(json!({
+                "type": "m.room.create",
+                "content": {
+                    "room_version": "11",
+                    "predecessor": { "room_id": room_id, "event_id": "$tombstone" },
+                },
+                "event_id": "$create",
+                "origin_server_ts": 1,
+                "sender": "@example:localhost",
+                "state_key": "",
+            }))),
+        )
+        .await;
+
+    let new_room = client.await_room_remote_echo(&upgraded_room_id).await;
+    assert_eq!(new_room.state(), RoomState::Joined);
+
+    assert_eq!(room.successor().unwrap().room_id, new_room_id);
+    assert_eq!(new_room.predecessor().unwrap().room_id, room_id);
+}
+
+#[async_test]
+async fn test_event_id_for_timestamp() {
+    let server = MatrixMockServer::new().await;
+    let client = server.client_builder().build().await;
+
+    let room_id = room_id!("!a:b.c");
+    let room = server.sync_joined_room(&client, room_id).await;
+
+    let ts = MilliSecondsSinceUnixEpoch(uint!(1_700_000_000_000));
+
+    server
+        .mock_timestamp_to_event()
+        .match_timestamp(ts, Direction::Forward)
+        .ok(event_id!("$next"), MilliSecondsSinceUnixEpoch(uint!(1_700_000_001_000)))
+        .mock_once()
+        .mount()
+        .await;
+    server
+        .mock_timestamp_to_event()
+        .match_timestamp(ts, Direction::Backward)
+        .ok(event_id!("$previous"), MilliSecondsSinceUnixEpoch(uint!(1_699_999_999_000)))
+        .mock_once()
+        .mount()
+        .await;
+
+    assert_eq!(room.event_id_for_timestamp(ts, Direction::Forward).await.unwrap(), "$next");
+    assert_eq!(room.event_id_for_timestamp(ts, Direction::Backward).await.unwrap(), "$previous");
+}
@@ -1,9 +1,19 @@
-use std::{ops::Not as _, sync::Arc, time::Duration};
+use std::{
+    ops::Not as _,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+    time::{Duration, SystemTime},
+};
 
 use as_variant::as_variant;
 use assert_matches2::{assert_let, assert_matches};
 use matrix_sdk::{
-    attachment::{AttachmentConfig, AttachmentInfo, BaseImageInfo, Thumbnail},
+    attachment::{
+        gallery_items, AttachmentConfig, AttachmentInfo, BaseImageInfo, GalleryItem, Thumbnail,
+        GALLERY_MSGTYPE,
+    },
     config::StoreConfig,
     media::{MediaFormat, MediaRequestParameters, MediaThumbnailSettings},
     send_queue::{
@@ -14,8 +24,8 @@ use matrix_sdk::{
     Client, MemoryStore,
 };
 use matrix_sdk_test::{
-    async_test, event_factory::EventFactory, InvitedRoomBuilder, KnockedRoomBuilder,
-    LeftRoomBuilder,
+    async_test, event_factory::EventFactory, InvitedRoomBuilder, JoinedRoomBuilder,
+    KnockedRoomBuilder, LeftRoomBuilder, StateTestEvent,
 };
 use ruma::{
     event_id,
@@ -26,13 +36,15 @@ use ruma::{
         },
         room::{
             message::{ImageMessageEventContent, MessageType, RoomMessageEventContent},
+            name::RoomNameEventContent,
+            topic::RoomTopicEventContent,
             MediaSource,
         },
-        AnyMessageLikeEventContent, EventContent as _, Mentions,
+        AnyMessageLikeEventContent, EventContent as _, Mentions, StateEventType,
     },
-    mxc_uri, owned_mxc_uri, owned_user_id, room_id,
+    mxc_uri, owned_event_id, owned_mxc_uri, owned_user_id, room_id,
     serde::Raw,
-    uint, MxcUri, OwnedEventId, OwnedTransactionId, TransactionId,
+    uint, MilliSecondsSinceUnixEpoch, MxcUri, OwnedEventId, OwnedTransactionId, TransactionId,
 };
 use serde_json::json;
 use tokio::{
@@ -99,6 +111,35 @@ async fn queue_attachment_with_thumbnail(q: &RoomSendQueue) -> (SendHandle, &'st
     (handle, filename)
 }
 
+/// Queues a gallery of two images, the first one with a thumbnail, the second
+/// one without.
+async fn queue_gallery(q: &RoomSendQueue) -> SendHandle {
+    let thumbnail = Thumbnail {
+        data: b"thumbnail".to_vec(),
+        content_type: mime::IMAGE_JPEG,
+        height: uint!(13),
+        width: uint!(37),
+        size: uint!(42),
+    };
+
+    let items = vec![
+        GalleryItem::new("first.jpg", mime::IMAGE_JPEG, b"first image".to_vec())
+            .thumbnail(Some(thumbnail)),
+        GalleryItem::new("second.jpg", mime::IMAGE_JPEG, b"second image".to_vec())
+            .caption(Some("second caption".to_owned())),
+    ];
+
+    let handle = q
+        .send_gallery(items, Some("my gallery".to_owned()))
+        .await
+        .expect("queuing the gallery works");
+
+    // Let the background task pick up the request.
+    yield_now().await;
+
+    handle
+}
+
 fn mock_jpeg_upload<'a>(
     mock: &'a MatrixMockServer,
     mxc: &MxcUri,
@@ -2012,6 +2053,319 @@ async fn test_media_uploads() {
     assert!(watch.is_empty());
 }
 
+#[async_test]
+async fn test_gallery_uploads() {
+    let mock = MatrixMockServer::new().await;
+
+    // Mark the room as joined.
+    let room_id = room_id!("!a:b.c");
+    let client = mock.client_builder().build().await;
+    let room = mock.sync_joined_room(&client, room_id).await;
+
+    let q = room.send_queue();
+
+    let (local_echoes, mut watch) = q.subscribe().await.unwrap();
+    assert!(local_echoes.is_empty());
+
+    // Prepare endpoints.
+    mock.mock_room_state_encryption().plain().mount().await;
+    mock.mock_room_send()
+        .body_matches_partial_json(json!({
+            "msgtype": GALLERY_MSGTYPE,
+            "body": "my gallery",
+            "itemtypes": [
+                {
+                    "itemtype": "m.image",
+                    "body": "first.jpg",
+                    "url": "mxc://sdk.rs/first",
+                    "info": { "thumbnail_url": "mxc://sdk.rs/thumbnail" },
+                },
+                {
+                    "itemtype": "m.image",
+                    "body": "second caption",
+                    "filename": "second.jpg",
+                    "url": "mxc://sdk.rs/second",
+                },
+            ],
+        }))
+        .ok(event_id!("$1"))
+        .mock_once()
+        .mount()
+        .await;
+
+    let allow_upload_lock = Arc::new(Mutex::new(()));
+    let block_upload = allow_upload_lock.lock().await;
+
+    for mxc in [
+        mxc_uri!("mxc://sdk.rs/thumbnail"),
+        mxc_uri!("mxc://sdk.rs/first"),
+        mxc_uri!("mxc://sdk.rs/second"),
+    ] {
+        mock_jpeg_upload(&mock, mxc, allow_upload_lock.clone()).mock_once().mount().await;
+    }
+
+    // Send the gallery.
+    assert!(watch.is_empty());
+    let send_handle = queue_gallery(&q).await;
+
+    // Observe the local echo, containing all the items.
+    let (txn, _send_handle, content) = assert_update!(watch => local echo event);
+    assert_eq!(content.msgtype(), GALLERY_MSGTYPE);
+    assert_eq!(content.body(), "my gallery");
+
+    let items = gallery_items(&content.msgtype).expect("the local echo should be a gallery");
+    assert_eq!(items.len(), 2);
+
+    assert_let!(MessageType::Image(first) = &items[0]);
+    assert_eq!(first.body, "first.jpg");
+    assert_let!(MediaSource::Plain(mxc) = &first.source);
+    assert!(mxc.to_string().starts_with("mxc://send-queue.localhost/"), "{mxc}");
+    let local_thumbnail_source = first.info.as_ref().unwrap().thumbnail_source.clone().unwrap();
+
+    assert_let!(MessageType::Image(second) = &items[1]);
+    assert_eq!(second.body, "second caption");
+    assert_eq!(second.filename.as_deref(), Some("second.jpg"));
+    assert!(second.info.as_ref().unwrap().thumbnail_source.is_none());
+
+    // The medias are immediately available from the cache.
+    let second_media = client
+        .media()
+        .get_media_content(
+            &MediaRequestParameters { source: second.source.clone(), format: MediaFormat::File },
+            true,
+        )
+        .await
+        .expect("media should be found");
+    assert_eq!(second_media, b"second image");
+
+    // A gallery can't be edited as a message.
+    assert_matches!(
+        send_handle.edit(RoomMessageEventContent::text_plain("hi").into()).await,
+        Err(RoomSendQueueStorageError::OperationNotImplementedYet)
+    );
+
+    // Let the uploads progress, in order.
+    assert!(watch.is_empty());
+    drop(block_upload);
+
+    assert_update!(watch => uploaded { related_to = txn, mxc = mxc_uri!("mxc://sdk.rs/thumbnail") });
+    assert_update!(watch => uploaded { related_to = txn, mxc = mxc_uri!("mxc://sdk.rs/first") });
+    assert_update!(watch => uploaded { related_to = txn, mxc = mxc_uri!("mxc://sdk.rs/second") });
+
+    // The gallery event is updated with the final MXC URIs.
+    let edit_msg = assert_update!(watch => edit local echo { txn = txn });
+    assert_eq!(edit_msg.body(), "my gallery");
+
+    let items = gallery_items(&edit_msg.msgtype).expect("the edit should be a gallery");
+    assert_eq!(items.len(), 2);
+
+    assert_let!(MessageType::Image(first) = &items[0]);
+    assert_let!(MediaSource::Plain(mxc) = &first.source);
+    assert_eq!(mxc, mxc_uri!("mxc://sdk.rs/first"));
+    let new_thumbnail_source = first.info.as_ref().unwrap().thumbnail_source.clone().unwrap();
+    assert_let!(MediaSource::Plain(mxc) = &new_thumbnail_source);
+    assert_eq!(mxc, mxc_uri!("mxc://sdk.rs/thumbnail"));
+
+    assert_let!(MessageType::Image(second) = &items[1]);
+    assert_let!(MediaSource::Plain(mxc) = &second.source);
+    assert_eq!(mxc, mxc_uri!("mxc://sdk.rs/second"));
+
+    // The medias are in the cache with their final MXC URIs.
+    let thumbnail_media = client
+        .media()
+        .get_media_content(
+            &MediaRequestParameters { source: new_thumbnail_source, format: MediaFormat::File },
+            true,
+        )
+        .await
+        .expect("media should be found with its final MXC uri in the cache");
+    assert_eq!(thumbnail_media, b"thumbnail");
+
+    let second_media = client
+        .media()
+        .get_media_content(
+            &MediaRequestParameters { source: second.source.clone(), format: MediaFormat::File },
+            true,
+        )
+        .await
+        .expect("media should be found with its final MXC uri in the cache");
+    assert_eq!(second_media, b"second image");
+
+    // The local URI does not work anymore.
+    client
+        .media()
+        .get_media_content(
+            &MediaRequestParameters { source: local_thumbnail_source, format: MediaFormat::File },
+            true,
+        )
+        .await
+        .expect_err("media with local URI should not be found");
+
+    // The event is sent, at some point.
+    assert_update!(watch => sent { txn = txn, event_id = event_id!("$1") });
+
+    // That's all, folks!
+    assert!(watch.is_empty());
+}
+
+#[async_test]
+async fn test_empty_gallery() {
+    let mock = MatrixMockServer::new().await;
+
+    // Mark the room as joined.
+    let room_id = room_id!("!a:b.c");
+    let client = mock.client_builder().build().await;
+    let room = mock.sync_joined_room(&client, room_id).await;
+
+    assert_matches!(
+        room.send_queue().send_gallery(Vec::new(), None).await,
+        Err(RoomSendQueueError::EmptyGallery)
+    );
+}
+
+#[async_test]
+async fn test_gallery_uploads_after_restart() {
+    let store = Arc::new(MemoryStore::new());
+    let event_cache_store = Arc::new(matrix_sdk_base::event_cache::store::MemoryStore::new());
+
+    let room_id = room_id!("!a:b.c");
+
+    let mock = MatrixMockServer::new().await;
+
+    let client = mock
+        .client_builder()
+        .store_config(
+            StoreConfig::new("cross-process-store-locks-holder-name".to_owned())
+                .state_store(store.clone())
+                .event_cache_store(event_cache_store.clone()),
+        )
+        .build()
+        .await;
+
+    let room = mock.sync_joined_room(&client, room_id).await;
+
+    // Globally disable the send queue, so nothing is uploaded yet.
+    let q = client.send_queue();
+    q.set_enabled(false).await;
+
+    queue_gallery(&room.send_queue()).await;
+
+    {
+        // Kill the client, let it close background tasks.
+        drop(room);
+        drop(q);
+        drop(client);
+        sleep(Duration::from_secs(1)).await;
+    }
+
+    // Create a new client with the same stores. As the send queues are enabled by
+    // default, it will respawn the task uploading the gallery in the background.
+    mock.mock_room_state_encryption().plain().mount().await;
+    mock.mock_upload().ok(mxc_uri!("mxc://sdk.rs/thumbnail")).mock_once().mount().await;
+    mock.mock_upload().ok(mxc_uri!("mxc://sdk.rs/first")).mock_once().mount().await;
+    mock.mock_upload().ok(mxc_uri!("mxc://sdk.rs/second")).mock_once().mount().await;
+    mock.mock_room_send()
+        .body_matches_partial_json(json!({
+            "msgtype": GALLERY_MSGTYPE,
+            "itemtypes": [
+                { "url": "mxc://sdk.rs/first", "info": { "thumbnail_url": "mxc://sdk.rs/thumbnail" } },
+                { "url": "mxc://sdk.rs/second" },
+            ],
+        }))
+        .ok(event_id!("$1"))
+        .mock_once()
+        .mount()
+        .await;
+
+    let new_client = mock
+        .client_builder()
+        .store_config(
+            StoreConfig::new("cross-process-store-locks-holder-name".to_owned())
+                .state_store(store)
+                .event_cache_store(event_cache_store),
+        )
+        .build()
+        .await;
+
+    // The local echo of the gallery is still there.
+    let room = new_client.get_room(room_id).unwrap();
+    let (local_echoes, mut watch) = room.send_queue().subscribe().await.unwrap();
+    assert_eq!(local_echoes.len(), 1);
+    assert_let!(LocalEchoContent::Event { serialized_event, .. } = &local_echoes[0].content);
+    assert_let!(
+        Ok(AnyMessageLikeEventContent::RoomMessage(content)) = serialized_event.deserialize()
+    );
+    assert_eq!(gallery_items(&content.msgtype).unwrap().len(), 2);
+    let txn = local_echoes[0].transaction_id.clone();
+
+    new_client.send_queue().respawn_tasks_for_rooms_with_unsent_requests().await;
+
+    assert_update!(watch => uploaded { related_to = txn, mxc = mxc_uri!("mxc://sdk.rs/thumbnail") });
+    assert_update!(watch => uploaded { related_to = txn, mxc = mxc_uri!("mxc://sdk.rs/first") });
+    assert_update!(watch => uploaded { related_to = txn, mxc = mxc_uri!("mxc://sdk.rs/second") });
+    assert_update!(watch => edit local echo { txn = txn });
+    assert_update!(watch => sent { txn = txn, event_id = event_id!("$1") });
+}
+
+#[async_test]
+async fn test_cancel_gallery_upload() {
+    let mock = MatrixMockServer::new().await;
+
+    // Mark the room as joined.
+    let room_id = room_id!("!a:b.c");
+    let client = mock.client_builder().build().await;
+    let room = mock.sync_joined_room(&client, room_id).await;
+
+    let q = room.send_queue();
+    let (local_echoes, mut watch) = q.subscribe().await.unwrap();
+    assert!(local_echoes.is_empty());
+
+    // Prepare endpoints: the thumbnail is uploaded, then the upload of the first
+    // file blocks.
+    mock.mock_room_state_encryption().plain().mount().await;
+    mock.mock_upload().ok(mxc_uri!("mxc://sdk.rs/thumbnail")).mock_once().mount().await;
+
+    let allow_upload_lock = Arc::new(Mutex::new(()));
+    let block_upload = allow_upload_lock.lock().await;
+    mock_jpeg_upload(&mock, mxc_uri!("mxc://sdk.rs/first"), allow_upload_lock.clone())
+        .mock_once()
+        .mount()
+        .await;
+
+    // Send the gallery.
+    let send_handle = queue_gallery(&q).await;
+    let (txn, _, content) = assert_update!(watch => local echo event);
+
+    assert_update!(watch => uploaded { related_to = txn, mxc = mxc_uri!("mxc://sdk.rs/thumbnail") });
+
+    // Abort while the first file is being uploaded.
+    assert!(send_handle.abort().await.unwrap());
+    assert_update!(watch => cancelled { txn = txn });
+
+    drop(block_upload);
+
+    // Nothing else happens.
+    sleep(Duration::from_millis(500)).await;
+    assert!(watch.is_empty());
+
+    // The local medias have been removed from the cache.
+    for item in gallery_items(&content.msgtype).unwrap() {
+        assert_let!(MessageType::Image(image) = item);
+        client
+            .media()
+            .get_media_content(
+                &MediaRequestParameters { source: image.source, format: MediaFormat::File },
+                true,
+            )
+            .await
+            .expect_err("media with local URI should not be found");
+    }
+
+    // The gallery is gone from the local echoes.
+    let (local_echoes, _) = q.subscribe().await.unwrap();
+    assert!(local_echoes.is_empty());
+}
+
 #[async_test]
 async fn test_media_upload_retry() {
     let mock = MatrixMockServer::new().await;
@@ -2086,6 +2440,72 @@ async fn test_media_upload_retry() {
     assert!(watch.is_empty());
 }
 
+#[async_test]
+async fn test_media_upload_progress() {
+    let mock = MatrixMockServer::new().await;
+
+    // Mark the room as joined.
+    let room_id = room_id!("!a:b.c");
+    let client = mock.client_builder().build().await;
+    let room = mock.sync_joined_room(&client, room_id).await;
+
+    client.send_queue().enable_upload_progress(true);
+
+    let q = room.send_queue();
+    let (local_echoes, mut watch) = q.subscribe().await.unwrap();
+    assert!(local_echoes.is_empty());
+
+    // Prepare endpoints.
+    mock.mock_room_state_encryption().plain().mount().await;
+    mock.mock_upload()
+        .expect_mime_type("image/jpeg")
+        .ok(mxc_uri!("mxc://sdk.rs/media"))
+        .mock_once()
+        .mount()
+        .await;
+    mock.mock_room_send().ok(event_id!("$1")).mock_once().mount().await;
+
+    // Send the media.
+    queue_attachment_no_thumbnail(&q).await;
+
+    let (event_txn, _send_handle, _content) = assert_update!(watch => local echo event);
+
+    // The progress of the upload is reported, until the whole file has been sent.
+    let mut last_progress = None;
+
+    loop {
+        let update = timeout(Duration::from_secs(1), watch.recv())
+            .await
+            .expect("timeout waiting for the upload")
+            .unwrap();
+
+        match update {
+            RoomSendQueueUpdate::MediaUploadProgress { transaction_id, index, progress } => {
+                assert_eq!(transaction_id, event_txn);
+                assert_eq!(index, 0);
+                last_progress = Some(progress);
+            }
+
+            RoomSendQueueUpdate::UploadedMedia { related_to, .. } => {
+                assert_eq!(related_to, event_txn);
+                break;
+            }
+
+            update => panic!("unexpected update: {update:?}"),
+        }
+    }
+
+    let progress = last_progress.expect("the progress of the upload should have been reported");
+    assert!(progress.total > 0);
+    assert_eq!(progress.current, progress.total);
+
+    assert_update!(watch => edit local echo { txn = event_txn });
+    assert_update!(watch => sent { txn = event_txn, event_id = event_id!("$1") });
+
+    // That's all, folks!
+    assert!(watch.is_empty());
+}
+
 #[async_test]
 async fn test_unwedging_media_upload() {
     let mock = MatrixMockServer::new().await;
@@ -3005,3 +3425,560 @@ async fn test_update_caption_while_sending_media_event() {
     // That's all, folks!
     assert!(watch.is_empty());
 }
+
+#[async_test]
+async fn test_scheduled_events_kept_locally() {
+    let mock = MatrixMockServer::new().await;
+
+    let client = mock.client_builder().build().await;
+    let room = mock.sync_joined_room(&client, room_id!("!a:b.c")).await;
+
+    mock.mock_room_state_encryption().plain().mount().await;
+
+    let q = room.send_queue();
+
+    let (local_echoes, mut watch) = q.subscribe().await.unwrap();
+    assert!(local_echoes.is_empty());
+
+    // The homeserver doesn't support delayed events, so the scheduled events wait
+    // in the queue until their time.
+    let in_an_hour =
+        MilliSecondsSinceUnixEpoch::from_system_time(SystemTime::now() + Duration::from_secs(3600))
+            .unwrap();
+    let later = q
+        .send_scheduled(RoomMessageEventContent::text_plain("later").into(), in_an_hour)
+        .await
+        .unwrap();
+
+    let soon = MilliSecondsSinceUnixEpoch::from_system_time(
+        SystemTime::now() + Duration::from_millis(300),
+    )
+    .unwrap();
+    let soon_handle =
+        q.send_scheduled(RoomMessageEventContent::text_plain("soon").into(), soon).await.unwrap();
+
+    // Scheduled events aren't local echoes.
+    let (local_echoes, _) = q.subscribe().await.unwrap();
+    assert!(local_echoes.is_empty());
+    assert!(watch.is_empty());
+
+    let scheduled = q.scheduled_events().await.unwrap();
+    assert_eq!(scheduled.len(), 2);
+    assert_eq!(scheduled[0].transaction_id, later.transaction_id());
+    assert_eq!(scheduled[0].send_at, in_an_hour);
+    assert!(scheduled[0].is_held_by_homeserver.not());
+    assert_eq!(scheduled[1].transaction_id, soon_handle.transaction_id());
+
+    mock.mock_room_send().ok(event_id!("$1")).mock_once().mount().await;
+
+    // When its time has come, the event is sent like any other event.
+    let (txn, _) = assert_update!(watch => local echo { body = "soon" });
+    assert_eq!(txn, soon_handle.transaction_id());
+    assert_update!(watch => sent { txn = txn, event_id = event_id!("$1") });
+
+    // It's too late to manipulate it.
+    assert!(soon_handle.cancel().await.unwrap().not());
+
+    // The other event can be edited…
+    assert!(later.edit(RoomMessageEventContent::text_plain("edited").into()).await.unwrap());
+
+    let scheduled = q.scheduled_events().await.unwrap();
+    assert_eq!(scheduled.len(), 1);
+    assert_let!(
+        Ok(AnyMessageLikeEventContent::RoomMessage(msg)) = scheduled[0].content.deserialize()
+    );
+    assert_eq!(msg.body(), "edited");
+
+    // … and sent right away.
+    mock.mock_room_send().ok(event_id!("$2")).mock_once().mount().await;
+
+    assert!(later.send_now().await.unwrap());
+
+    let (txn, _) = assert_update!(watch => local echo { body = "edited" });
+    assert_eq!(txn, later.transaction_id());
+    assert_update!(watch => sent { txn = txn, event_id = event_id!("$2") });
+
+    assert!(q.scheduled_events().await.unwrap().is_empty());
+    assert!(watch.is_empty());
+}
+
+/// Wait until all the scheduled events of the queue have been handed over to
+/// the homeserver.
+async fn wait_for_hand_over(q: &RoomSendQueue) {
+    timeout(Duration::from_secs(1), async {
+        while q.scheduled_events().await.unwrap().iter().any(|ev| !ev.is_held_by_homeserver) {
+            sleep(Duration::from_millis(10)).await;
+        }
+    })
+    .await
+    .unwrap();
+}
+
+#[async_test]
+async fn test_scheduled_events_held_by_homeserver() {
+    let mock = MatrixMockServer::new().await;
+
+    let client = mock.client_builder().build().await;
+    let room = mock.sync_joined_room(&client, room_id!("!a:b.c")).await;
+
+    mock.mock_room_state_encryption().plain().mount().await;
+    mock.mock_versions().ok_with_unstable_features(&["org.matrix.msc4140"]).mount().await;
+
+    let num_delayed_events = Arc::new(AtomicUsize::new(0));
+    mock.mock_room_send()
+        .respond_with({
+            let num_delayed_events = num_delayed_events.clone();
+            move |req: &Request| {
+                // Scheduled events are sent as delayed events.
+                assert!(req.url.query_pairs().any(|(key, _)| key == "org.matrix.msc4140.delay"));
+
+                let delay_id = format!("d{}", num_delayed_events.fetch_add(1, Ordering::SeqCst));
+                ResponseTemplate::new(200).set_body_json(json!({ "delay_id": delay_id }))
+            }
+        })
+        .mount()
+        .await;
+
+    let q = room.send_queue();
+    let (_, mut watch) = q.subscribe().await.unwrap();
+
+    let in_an_hour =
+        MilliSecondsSinceUnixEpoch::from_system_time(SystemTime::now() + Duration::from_secs(3600))
+            .unwrap();
+    let handle = q
+        .send_scheduled(RoomMessageEventContent::text_plain("later").into(), in_an_hour)
+        .await
+        .unwrap();
+
+    // The event is handed over to the homeserver right away.
+    wait_for_hand_over(&q).await;
+    assert_eq!(num_delayed_events.load(Ordering::SeqCst), 1);
+
+    let scheduled = q.scheduled_events().await.unwrap();
+    assert_eq!(scheduled.len(), 1);
+    assert_eq!(scheduled[0].transaction_id, handle.transaction_id());
+    assert_eq!(scheduled[0].send_at, in_an_hour);
+
+    // Editing it cancels the delayed event, and hands the new version over.
+    mock.mock_update_delayed_event()
+        .match_delay_id("d0")
+        .match_action("cancel")
+        .ok()
+        .mock_once()
+        .mount()
+        .await;
+
+    assert!(handle.edit(RoomMessageEventContent::text_plain("edited").into()).await.unwrap());
+
+    wait_for_hand_over(&q).await;
+    assert_eq!(num_delayed_events.load(Ordering::SeqCst), 2);
+
+    // Sending it now asks the homeserver to send it, and forgets about it.
+    mock.mock_update_delayed_event()
+        .match_delay_id("d1")
+        .match_action("send")
+        .ok()
+        .mock_once()
+        .mount()
+        .await;
+
+    assert!(handle.send_now().await.unwrap());
+    assert!(q.scheduled_events().await.unwrap().is_empty());
+
+    // Cancelling another one cancels the delayed event.
+    let handle = q
+        .send_scheduled(RoomMessageEventContent::text_plain("never").into(), in_an_hour)
+        .await
+        .unwrap();
+
+    wait_for_hand_over(&q).await;
+    assert_eq!(num_delayed_events.load(Ordering::SeqCst), 3);
+
+    mock.mock_update_delayed_event()
+        .match_delay_id("d2")
+        .match_action("cancel")
+        .ok()
+        .mock_once()
+        .mount()
+        .await;
+
+    assert!(handle.cancel().await.unwrap());
+    assert!(q.scheduled_events().await.unwrap().is_empty());
+
+    // The events held by the homeserver are never local echoes.
+    assert!(watch.is_empty());
+}
+
+#[async_test]
+async fn test_scheduled_event_restored_when_delayed_event_update_fails() {
+    let mock = MatrixMockServer::new().await;
+
+    let client = mock.client_builder().build().await;
+    let room = mock.sync_joined_room(&client, room_id!("!a:b.c")).await;
+
+    mock.mock_room_state_encryption().plain().mount().await;
+    mock.mock_versions().ok_with_unstable_features(&["org.matrix.msc4140"]).mount().await;
+    mock.mock_room_send()
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({ "delay_id": "d0" })))
+        .mock_once()
+        .mount()
+        .await;
+
+    let q = room.send_queue();
+
+    let in_an_hour =
+        MilliSecondsSinceUnixEpoch::from_system_time(SystemTime::now() + Duration::from_secs(3600))
+            .unwrap();
+    let handle = q
+        .send_scheduled(RoomMessageEventContent::text_plain("later").into(), in_an_hour)
+        .await
+        .unwrap();
+
+    wait_for_hand_over(&q).await;
+
+    // The homeserver fails to cancel the delayed event.
+    mock.mock_update_delayed_event()
+        .match_delay_id("d0")
+        .match_action("cancel")
+        .error500()
+        .mount()
+        .await;
+
+    handle.cancel().await.unwrap_err();
+
+    // The event is still held by the homeserver, so it's not forgotten.
+    let scheduled = q.scheduled_events().await.unwrap();
+    assert_eq!(scheduled.len(), 1);
+    assert!(scheduled[0].is_held_by_homeserver);
+
+    // Same when editing it.
+    handle.edit(RoomMessageEventContent::text_plain("edited").into()).await.unwrap_err();
+
+    let scheduled = q.scheduled_events().await.unwrap();
+    assert_eq!(scheduled.len(), 1);
+    assert!(scheduled[0].is_held_by_homeserver);
+    assert_let!(
+        AnyMessageLikeEventContent::RoomMessage(msg) = scheduled[0].content.deserialize().unwrap()
+    );
+    assert_eq!(msg.body(), "later");
+}
+
+#[async_test]
+async fn test_scheduled_events_taken_back_when_room_is_encrypted() {
+    let mock = MatrixMockServer::new().await;
+
+    let client = mock.client_builder().build().await;
+    let room_id = room_id!("!a:b.c");
+    let room = mock.sync_joined_room(&client, room_id).await;
+
+    mock.mock_room_state_encryption().plain().mount().await;
+    mock.mock_versions().ok_with_unstable_features(&["org.matrix.msc4140"]).mount().await;
+    mock.mock_room_send()
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({ "delay_id": "d0" })))
+        .mock_once()
+        .mount()
+        .await;
+
+    let q = room.send_queue();
+    let (_, mut watch) = q.subscribe().await.unwrap();
+
+    let in_an_hour =
+        MilliSecondsSinceUnixEpoch::from_system_time(SystemTime::now() + Duration::from_secs(3600))
+            .unwrap();
+    let handle = q
+        .send_scheduled(RoomMessageEventContent::text_plain("later").into(), in_an_hour)
+        .await
+        .unwrap();
+
+    wait_for_hand_over(&q).await;
+
+    // Once the room is encrypted, the delayed event is cancelled…
+    mock.mock_update_delayed_event()
+        .match_delay_id("d0")
+        .match_action("cancel")
+        .ok()
+        .mock_once()
+        .mount()
+        .await;
+
+    mock.sync_room(
+        &client,
+        JoinedRoomBuilder::new(room_id).add_state_event(StateTestEvent::Encryption),
+    )
+    .await;
+
+    // … and the event is kept in the queue until its time.
+    timeout(Duration::from_secs(1), async {
+        while q.scheduled_events().await.unwrap().iter().any(|ev| ev.is_held_by_homeserver) {
+            sleep(Duration::from_millis(10)).await;
+        }
+    })
+    .await
+    .unwrap();
+
+    let scheduled = q.scheduled_events().await.unwrap();
+    assert_eq!(scheduled.len(), 1);
+    assert_eq!(scheduled[0].transaction_id, handle.transaction_id());
+    assert_eq!(scheduled[0].send_at, in_an_hour);
+
+    // It's not handed over to the homeserver again.
+    sleep(Duration::from_millis(100)).await;
+    assert!(q.scheduled_events().await.unwrap()[0].is_held_by_homeserver.not());
+    assert!(watch.is_empty());
+}
+
+#[async_test]
+async fn test_state_events_and_redactions() {
+    let mock = MatrixMockServer::new().await;
+
+    let client = mock.client_builder().build().await;
+    let room = mock.sync_joined_room(&client, room_id!("!a:b.c")).await;
+
+    mock.mock_room_state_encryption().plain().mount().await;
+
+    let q = room.send_queue();
+    let (_, mut watch) = q.subscribe().await.unwrap();
+
+    // Queue everything while the queue is disabled, like when offline.
+    q.set_enabled(false);
+
+    let topic_handle =
+        q.send_state(RoomTopicEventContent::new("new topic".to_owned())).await.unwrap();
+    q.redact(owned_event_id!("$spam"), Some("spam".to_owned())).await.unwrap();
+    let name_handle = q.send_state(RoomNameEventContent::new("new name".to_owned())).await.unwrap();
+
+    assert_let!(
+        Ok(Ok(RoomSendQueueUpdate::NewLocalEvent(LocalEcho {
+            transaction_id: topic_txn,
+            content: LocalEchoContent::State { event_type, state_key, .. },
+        }))) = timeout(Duration::from_secs(1), watch.recv()).await
+    );
+    assert_eq!(event_type, "m.room.topic");
+    assert!(state_key.is_empty());
+
+    assert_let!(
+        Ok(Ok(RoomSendQueueUpdate::NewLocalEvent(LocalEcho {
+            transaction_id: redaction_txn,
+            content: LocalEchoContent::Redaction { redacts, reason, .. },
+        }))) = timeout(Duration::from_secs(1), watch.recv()).await
+    );
+    assert_eq!(redacts, event_id!("$spam"));
+    assert_eq!(reason.as_deref(), Some("spam"));
+
+    assert_let!(
+        Ok(Ok(RoomSendQueueUpdate::NewLocalEvent(LocalEcho {
+            transaction_id: name_txn,
+            content: LocalEchoContent::State { event_type, .. },
+        }))) = timeout(Duration::from_secs(1), watch.recv()).await
+    );
+    assert_eq!(event_type, "m.room.name");
+
+    // The local echoes are persisted, in order.
+    let (local_echoes, _) = q.subscribe().await.unwrap();
+    assert_eq!(local_echoes.len(), 3);
+    assert_matches!(&local_echoes[0].content, LocalEchoContent::State { .. });
+    assert_matches!(&local_echoes[1].content, LocalEchoContent::Redaction { .. });
+    assert_matches!(&local_echoes[2].content, LocalEchoContent::State { .. });
+
+    // State events can't be edited nor reacted to…
+    assert!(topic_handle
+        .edit(RoomMessageEventContent::text_plain("not a topic").into())
+        .await
+        .unwrap()
+        .not());
+    assert!(topic_handle.react("👍".to_owned()).await.unwrap().is_none());
+
+    // … but they can be aborted.
+    assert!(name_handle.abort().await.unwrap());
+    assert_update!(watch => cancelled { txn = name_txn });
+
+    mock.mock_room_send_state()
+        .for_type(StateEventType::RoomTopic)
+        .ok(event_id!("$topic"))
+        .mock_once()
+        .mount()
+        .await;
+    mock.mock_room_redact().ok(event_id!("$redaction")).mock_once().mount().await;
+
+    q.set_enabled(true);
+
+    assert_update!(watch => sent { txn = topic_txn, event_id = event_id!("$topic") });
+    assert_update!(watch => sent { txn = redaction_txn, event_id = event_id!("$redaction") });
+
+    assert!(watch.is_empty());
+}
+
+#[async_test]
+async fn test_state_event_wedged() {
+    let mock = MatrixMockServer::new().await;
+
+    let client = mock.client_builder().build().await;
+    let room = mock.sync_joined_room(&client, room_id!("!a:b.c")).await;
+
+    mock.mock_room_state_encryption().plain().mount().await;
+
+    let q = room.send_queue();
+    let (_, mut watch) = q.subscribe().await.unwrap();
+
+    mock.mock_room_send_state()
+        .respond_with(ResponseTemplate::new(403).set_body_json(json!({
+            "errcode": "M_FORBIDDEN",
+            "error": "You don't have the permission to change the topic",
+        })))
+        .mock_once()
+        .mount()
+        .await;
+
+    let handle = q.send_state(RoomTopicEventContent::new("new topic".to_owned())).await.unwrap();
+
+    assert_let!(
+        Ok(Ok(RoomSendQueueUpdate::NewLocalEvent(LocalEcho { transaction_id: txn, .. }))) =
+            timeout(Duration::from_secs(1), watch.recv()).await
+    );
+
+    // The state event is wedged.
+    assert_update!(watch => error { recoverable = false, txn = txn });
+    assert!(q.is_enabled().not());
+    q.set_enabled(true);
+
+    let (local_echoes, _) = q.subscribe().await.unwrap();
+    assert_let!(LocalEchoContent::State { send_error, .. } = &local_echoes[0].content);
+    assert!(send_error.is_some());
+
+    // After unwedging it, it's sent again.
+    mock.mock_room_send_state().ok(event_id!("$topic")).mock_once().mount().await;
+
+    handle.unwedge().await.unwrap();
+
+    assert_update!(watch => retry { txn = txn });
+    assert_update!(watch => sent { txn = txn, event_id = event_id!("$topic") });
+
+    assert!(watch.is_empty());
+}
+
+#[async_test]
+async fn test_resumable_media_upload() {
+    let mock = MatrixMockServer::new().await;
+
+    let client = mock.client_builder().build().await;
+    let room = mock.sync_joined_room(&client, room_id!("!a:b.c")).await;
+
+    mock.mock_room_state_encryption().plain().mount().await;
+    mock.mock_versions().ok_with_unstable_features(&["org.matrix.resumable_upload"]).mount().await;
+
+    let q = room.send_queue();
+    let (_, mut watch) = q.subscribe().await.unwrap();
+
+    const MIB: u64 = 1024 * 1024;
+    let mxc = mxc_uri!("mxc://localhost/resumable");
+
+    mock.mock_create_content_uri().ok(mxc).mock_once().mount().await;
+    mock.mock_upload_preallocated_chunk().match_offset(0).ok(4 * MIB).mock_once().mount().await;
+    mock.mock_upload_preallocated_chunk()
+        .match_offset(4 * MIB)
+        .error500()
+        .up_to_n_times(3)
+        .expect(3)
+        .mount()
+        .await;
+
+    // The media is too large to fit in a single chunk.
+    let data = vec![0; 9 * MIB as usize];
+    q.send_attachment("video.mp4", "video/mp4".parse().unwrap(), data, AttachmentConfig::new())
+        .await
+        .unwrap();
+
+    let (txn, _, _) = assert_update!(watch => local echo event);
+
+    // The second chunk fails to be uploaded.
+    assert_update!(watch => error { recoverable=true, txn=txn });
+    assert!(q.is_enabled().not());
+
+    // When the queue is re-enabled, the upload resumes after the first chunk,
+    // without preallocating another MXC URI.
+    mock.mock_resumable_upload_offset().ok(4 * MIB).mock_once().mount().await;
+    mock.mock_upload_preallocated_chunk()
+        .match_offset(4 * MIB)
+        .ok(8 * MIB)
+        .mock_once()
+        .mount()
+        .await;
+    mock.mock_upload_preallocated_chunk()
+        .match_offset(8 * MIB)
+        .ok(9 * MIB)
+        .mock_once()
+        .mount()
+        .await;
+    mock.mock_room_send().ok(event_id!("$1")).mock_once().mount().await;
+
+    q.set_enabled(true);
+
+    assert_update!(watch => uploaded { related_to = txn, mxc = mxc });
+    let edit_msg = assert_update!(watch => edit local echo { txn = txn });
+    assert_let!(MessageType::Video(video) = edit_msg.msgtype);
+    assert_let!(MediaSource::Plain(video_mxc) = video.source);
+    assert_eq!(video_mxc, mxc);
+    assert_update!(watch => sent { txn = txn, event_id = event_id!("$1") });
+
+    assert!(watch.is_empty());
+}
+
+#[async_test]
+async fn test_resumable_media_upload_unknown_to_homeserver() {
+    let mock = MatrixMockServer::new().await;
+
+    let client = mock.client_builder().build().await;
+    let room = mock.sync_joined_room(&client, room_id!("!a:b.c")).await;
+
+    mock.mock_room_state_encryption().plain().mount().await;
+    mock.mock_versions().ok_with_unstable_features(&["org.matrix.resumable_upload"]).mount().await;
+
+    let q = room.send_queue();
+    let (_, mut watch) = q.subscribe().await.unwrap();
+
+    const MIB: u64 = 1024 * 1024;
+    let expired_mxc = mxc_uri!("mxc://localhost/expired");
+    let mxc = mxc_uri!("mxc://localhost/resumable");
+
+    mock.mock_create_content_uri().ok(expired_mxc).mock_once().mount().await;
+    mock.mock_upload_preallocated_chunk()
+        .match_offset(0)
+        .error500()
+        .up_to_n_times(3)
+        .expect(3)
+        .mount()
+        .await;
+
+    let data = vec![0; 5 * MIB as usize];
+    q.send_attachment("video.mp4", "video/mp4".parse().unwrap(), data, AttachmentConfig::new())
+        .await
+        .unwrap();
+
+    let (txn, _, _) = assert_update!(watch => local echo event);
+    assert_update!(watch => error { recoverable=true, txn=txn });
+
+    // The homeserver doesn't know about the previous upload anymore, so it starts
+    // over with another MXC URI.
+    mock.mock_resumable_upload_offset()
+        .respond_with(ResponseTemplate::new(404))
+        .mock_once()
+        .mount()
+        .await;
+    mock.mock_create_content_uri().ok(mxc).mock_once().mount().await;
+    mock.mock_upload_preallocated_chunk().match_offset(0).ok(4 * MIB).mock_once().mount().await;
+    mock.mock_upload_preallocated_chunk()
+        .match_offset(4 * MIB)
+        .ok(5 * MIB)
+        .mock_once()
+        .mount()
+        .await;
+    mock.mock_room_send().ok(event_id!("$1")).mock_once().mount().await;
+
+    q.set_enabled(true);
+
+    assert_update!(watch => uploaded { related_to = txn, mxc = mxc });
+    assert_update!(watch => edit local echo { txn = txn });
+    assert_update!(watch => sent { txn = txn, event_id = event_id!("$1") });
+
+    assert!(watch.is_empty());
+}
@@ -0,0 +1,72 @@
+use std::path::PathBuf;
+
+use anyhow::{bail, Result};
+use clap::Parser;
+use matrix_sdk::{crypto::store::diagnostics::DiagnosticsReport, SqliteCryptoStore};
+
+/// A command line example showcasing how to check the consistency of a SQLite
+/// crypto store, and how to repair it.
+#[derive(Parser, Debug)]
+struct Cli {
+    /// The directory of the crypto store.
+    #[clap(value_parser)]
+    store_path: PathBuf,
+
+    /// The passphrase that was used to encrypt the store.
+    #[clap(long)]
+    passphrase: Option<String>,
+
+    /// Repair the anomalies that can be repaired.
+    #[clap(long, action)]
+    repair: bool,
+
+    /// Print the report as JSON.
+    #[clap(long, action)]
+    json: bool,
+
+    /// Enable verbose logging output.
+    #[clap(short, long, action)]
+    verbose: bool,
+}
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    let cli = Cli::parse();
+
+    if cli.verbose {
+        tracing_subscriber::fmt::init();
+    }
+
+    // Opening the store would create it otherwise.
+    if !cli.store_path.is_dir() {
+        bail!("The crypto store {} doesn't exist", cli.store_path.display());
+    }
+
+    let store = SqliteCryptoStore::open(&cli.store_path, cli.passphrase.as_deref()).await?;
+    let report = DiagnosticsReport::collect(&store).await?;
+
+    if cli.json {
+        println!("{}", serde_json::to_string_pretty(&report)?);
+    } else {
+        println!(
+            "Checked {} tracked users and {} inbound group sessions",
+            report.tracked_user_count, report.inbound_group_session_count
+        );
+
+        for anomaly in &report.anomalies {
+            let repairable = if anomaly.is_repairable() { "repairable" } else { "not repairable" };
+            println!("  - {anomaly} ({repairable})");
+        }
+    }
+
+    if report.is_healthy() {
+        eprintln!("The crypto store is healthy");
+    } else if cli.repair {
+        let repaired = report.repair(&store).await?;
+        eprintln!("Repaired {repaired} of {} anomalies", report.anomalies.len());
+    } else {
+        eprintln!("Found {} anomalies, run with --repair to repair them", report.anomalies.len());
+    }
+
+    Ok(())
+}
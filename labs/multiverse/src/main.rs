@@ -1000,6 +1000,8 @@ async fn configure_client(server_name: &str, config_path: &str) -> anyhow::Resul
             auto_enable_cross_signing: true,
             backup_download_strategy: BackupDownloadStrategy::AfterDecryptionFailure,
             auto_enable_backups: true,
+            auto_enable_dehydrated_devices: false,
+            share_history_on_invite: false,
         });
 
     if let Ok(proxy_url) = env::var("PROXY") {